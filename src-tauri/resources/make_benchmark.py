@@ -0,0 +1,73 @@
+# Writes benchmark.wav: 30 s of synthetic, speech-like audio (formant vowels,
+# consonant noise, phrase pauses) for timing models. Deterministic; needs only Python 3.
+
+import math, os, random, struct, wave
+
+RATE = 16000
+SECONDS = 30.0
+random.seed(1296)
+
+VOWELS = {  # F1, F2, F3 in Hz
+    "i": (270, 2290, 3010), "e": (530, 1840, 2480), "a": (730, 1090, 2440),
+    "o": (570, 840, 2410), "u": (300, 870, 2240), "@": (500, 1500, 2500),
+}
+
+class Resonator:
+    def __init__(self, freq, bw):
+        self.set(freq, bw)
+        self.y1 = self.y2 = 0.0
+    def set(self, freq, bw):
+        r = math.exp(-math.pi * bw / RATE)
+        self.b1 = 2 * r * math.cos(2 * math.pi * freq / RATE)
+        self.b2 = -r * r
+        self.g = 1 - r
+    def __call__(self, x):
+        y = self.g * x + self.b1 * self.y1 + self.b2 * self.y2
+        self.y2, self.y1 = self.y1, y
+        return y
+
+out = []
+total = int(RATE * SECONDS)
+phase = 0.0
+formants = [Resonator(500, 80), Resonator(1500, 100), Resonator(2500, 120)]
+fric = Resonator(4500, 2000)
+while len(out) < total:
+    # A phrase of 4-9 syllables with falling pitch, then a pause
+    syllables = random.randint(4, 9)
+    f0_start = random.uniform(125, 145)
+    for s in range(syllables):
+        vowel = VOWELS[random.choice(list(VOWELS))]
+        f0 = f0_start - 25 * s / syllables
+        if random.random() < 0.6:
+            for n in range(int(RATE * random.uniform(0.03, 0.07))):
+                out.append(0.15 * fric(random.uniform(-1, 1)))
+        length = int(RATE * random.uniform(0.12, 0.26))
+        for i, r in enumerate(formants):
+            r.set(vowel[i], 60 + 40 * i)
+        for n in range(length):
+            env = math.sin(math.pi * n / length) ** 0.6
+            f = f0 * (1 + 0.03 * math.sin(2 * math.pi * 5 * n / RATE))
+            phase += f / RATE
+            pulse = 0.0
+            if phase >= 1.0:
+                phase -= 1.0
+                pulse = 1.0
+            x = pulse + 0.02 * random.uniform(-1, 1)
+            y = 0.0
+            for r in formants:
+                x = r(x)
+            out.append(6.0 * x * env)
+    for n in range(int(RATE * random.uniform(0.25, 0.6))):
+        out.append(0.0)
+
+out = out[:total]
+peak = max(abs(v) for v in out)
+scale = 0.6 / peak
+with wave.open(os.path.join(os.path.dirname(os.path.abspath(__file__)), "benchmark.wav"), "wb") as w:
+    w.setnchannels(1)
+    w.setsampwidth(2)
+    w.setframerate(RATE)
+    hiss = random.Random(7)
+    w.writeframes(b"".join(
+        struct.pack("<h", int(max(-1, min(1, v * scale + 0.0005 * hiss.uniform(-1, 1))) * 32767)) for v in out
+    ))
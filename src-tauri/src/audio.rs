@@ -1,9 +1,20 @@
+use crate::dsp::{
+    estimate_speech, mix_down, mix_weighted, AutoGain, EchoCanceller, LoudnessMeter, ResampleQuality, Resampler,
+    SpectralGate, SpeechEstimate,
+};
+use crate::encoder::{self, AudioFormat, EncoderError};
+use crate::playback::EchoReference;
+use crate::poison::LockExt;
+use crate::telemetry;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Sample, SampleFormat};
-use hound::{WavSpec, WavWriter};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use hound::{WavReader, WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,220 +29,2593 @@ pub enum AudioError {
     IoError(#[from] std::io::Error),
     #[error("Hound error: {0}")]
     HoundError(#[from] hound::Error),
+    #[error("Encoder error: {0}")]
+    EncoderError(#[from] EncoderError),
     #[error("Recording error: {0}")]
     RecordingError(String),
+    #[error("Microphone access is denied. Allow it in the system privacy settings and try again.")]
+    PermissionDenied,
+    #[error("Can't read .{0} audio without ffmpeg installed")]
+    DecoderMissing(String),
+    #[error("Failed to decode audio: {0}")]
+    DecodeError(String),
 }
 
+/// Whether the OS lets the app hear the microphone
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MicrophonePermission {
+    Granted,
+    Denied,
+    NoDevice,
+    /// The device opened but delivered only silence. Only macOS says
+    /// whether access was refused, so elsewhere this is just a warning
+    /// (often a muted input) and recording goes ahead.
+    Silent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InputDevice {
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// Input level over one drain interval, for a VU meter
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct InputLevel {
+    pub rms_dbfs: f32,
+    pub peak_dbfs: f32,
+}
+
+/// Quieter than this is reported as silence
+const LEVEL_FLOOR_DBFS: f32 = -100.0;
+
+/// Samples at or beyond this magnitude count as clipped
+const CLIP_LEVEL: f32 = 0.999;
+/// More than this fraction of clipped samples flags the recording
+const MAX_CLIPPED_RATIO: f64 = 0.001;
+/// A recording quieter than this on average is near-silent
+const QUIET_DBFS: f32 = -50.0;
+/// Audio needed before a live warning is trusted
+const QUALITY_WARMUP_SECONDS: f64 = 5.0;
+
+/// A take with less of its length than this speech is mostly silence
+const MIN_SPEECH_RATIO: f32 = 0.05;
+/// With less than this much speech over the background, in dB, whisper
+/// hears the room as much as the speaker
+const MIN_SNR_DB: f32 = 10.0;
+/// Speech over background a clean take reaches; more earns no extra score
+const GOOD_SNR_DB: f32 = 30.0;
+/// Speech fraction a take needs for full marks on speech presence
+const GOOD_SPEECH_RATIO: f32 = 0.3;
+/// Highest score a take too quiet to transcribe can get
+const TOO_QUIET_SCORE: u8 = 20;
+
+/// Whether a capture looks usable, judged once it is finished
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioQuality {
+    #[default]
+    Ok,
+    Clipped,
+    TooQuiet,
+}
+
+impl AudioQuality {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AudioQuality::Ok => "ok",
+            AudioQuality::Clipped => "clipped",
+            AudioQuality::TooQuiet => "too_quiet",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ok" => Some(AudioQuality::Ok),
+            "clipped" => Some(AudioQuality::Clipped),
+            "too_quiet" => Some(AudioQuality::TooQuiet),
+            _ => None,
+        }
+    }
+}
+
+/// A full-quality copy of the capture kept next to the 16kHz file whisper
+/// reads, for when a recording needs reviewing by ear. Always 24-bit, with
+/// every channel the capture had.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveQuality {
+    /// At the rate the device captured at
+    Original,
+    /// Resampled to 48kHz
+    Studio,
+}
+
+impl ArchiveQuality {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ArchiveQuality::Original => "original",
+            ArchiveQuality::Studio => "studio",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "original" => Some(ArchiveQuality::Original),
+            "studio" => Some(ArchiveQuality::Studio),
+            _ => None,
+        }
+    }
+}
+
+/// Sample rate of `ArchiveQuality::Studio` copies
+const STUDIO_SAMPLE_RATE: u32 = 48000;
+/// Full scale of the 24-bit samples archive copies are written in
+const INT24_MAX: f32 = 8_388_607.0;
+
+/// Clip and level totals for one capture, after gain
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaptureStats {
+    samples: u64,
+    clipped: u64,
+    sum_squares: f64,
+    peak: f32,
+}
+
+impl CaptureStats {
+    fn add(&mut self, samples: &[f32]) {
+        self.samples += samples.len() as u64;
+        self.clipped += samples.iter().filter(|s| s.abs() >= CLIP_LEVEL).count() as u64;
+        self.sum_squares += samples.iter().map(|&s| s as f64 * s as f64).sum::<f64>();
+        self.peak = samples.iter().fold(self.peak, |peak, s| peak.max(s.abs()));
+    }
+
+    /// Samples at or near full scale
+    pub fn clipped_samples(&self) -> u64 {
+        self.clipped
+    }
+
+    pub fn clipped_ratio(&self) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.clipped as f64 / self.samples as f64
+        }
+    }
+
+    pub fn rms_dbfs(&self) -> f32 {
+        if self.samples == 0 {
+            return LEVEL_FLOOR_DBFS;
+        }
+        let rms = (self.sum_squares / self.samples as f64).sqrt() as f32;
+        (20.0 * rms.log10()).max(LEVEL_FLOOR_DBFS)
+    }
+
+    pub fn peak_dbfs(&self) -> f32 {
+        if self.peak <= 0.0 {
+            return LEVEL_FLOOR_DBFS;
+        }
+        (20.0 * self.peak.log10()).max(LEVEL_FLOOR_DBFS)
+    }
+
+    /// Clipping wins over quietness; a capture with no samples at all is
+    /// left alone
+    pub fn quality(&self) -> AudioQuality {
+        if self.samples == 0 {
+            AudioQuality::Ok
+        } else if self.clipped_ratio() > MAX_CLIPPED_RATIO {
+            AudioQuality::Clipped
+        } else if self.rms_dbfs() < QUIET_DBFS {
+            AudioQuality::TooQuiet
+        } else {
+            AudioQuality::Ok
+        }
+    }
+}
+
+/// Quick verdict on a finished take, worked out before anything is
+/// transcribed so a bad one can be made again straight away
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityScore {
+    /// 0 to 100, higher is better
+    pub score: u8,
+    pub snr_db: f32,
+    pub clipped_ratio: f64,
+    pub speech_ratio: f32,
+    /// Set when the take is worth making again, e.g. "re-record: too quiet"
+    pub recommendation: Option<String>,
+}
+
+impl QualityScore {
+    /// Weighs speech over background most, then speech presence, then
+    /// clipping
+    pub fn new(stats: &CaptureStats, speech: SpeechEstimate) -> Self {
+        let clipped_ratio = stats.clipped_ratio();
+        let snr = (speech.snr_db / GOOD_SNR_DB).clamp(0.0, 1.0);
+        let presence = (speech.speech_ratio / GOOD_SPEECH_RATIO).clamp(0.0, 1.0);
+        // Ten times the clipping that flags a capture scores nothing here
+        let clean = 1.0 - (clipped_ratio / (MAX_CLIPPED_RATIO * 10.0)).clamp(0.0, 1.0) as f32;
+        let mut score = (40.0 * snr + 30.0 * presence + 30.0 * clean).round() as u8;
+
+        let quality = stats.quality();
+        if quality == AudioQuality::TooQuiet {
+            score = score.min(TOO_QUIET_SCORE);
+        }
+        let recommendation = if quality == AudioQuality::TooQuiet {
+            Some("re-record: too quiet")
+        } else if speech.speech_ratio < MIN_SPEECH_RATIO {
+            Some("re-record: no speech heard")
+        } else if quality == AudioQuality::Clipped {
+            Some("re-record: too loud, lower the input gain")
+        } else if speech.snr_db < MIN_SNR_DB {
+            Some("re-record: too much background noise")
+        } else {
+            None
+        };
+        Self {
+            score,
+            snr_db: speech.snr_db,
+            clipped_ratio,
+            speech_ratio: speech.speech_ratio,
+            recommendation: recommendation.map(str::to_string),
+        }
+    }
+}
+
+/// Score a converted take from its audio and the capture's totals
+pub fn score_take(path: &Path, stats: &CaptureStats) -> Result<QualityScore, AudioError> {
+    let (samples, sample_rate) = read_audio(path)?;
+    Ok(QualityScore::new(stats, estimate_speech(&samples, sample_rate)))
+}
+
+/// Sent once per recording, per problem, while it's still being captured
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityWarning {
+    pub quality: AudioQuality,
+    pub clipped_ratio: f64,
+    pub rms_dbfs: f32,
+}
+
+/// Something went wrong with the input device mid-recording
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureError {
+    pub message: String,
+    /// Device the recording carries on from, if one could be opened
+    pub switched_to: Option<String>,
+}
+
+type LevelListener = Box<dyn Fn(InputLevel) + Send>;
+type AutoStopListener = Box<dyn Fn() + Send>;
+type CaptureErrorListener = Box<dyn Fn(CaptureError) + Send>;
+type RolloverListener = Box<dyn Fn(PathBuf, CaptureStats, Vec<CaptureMarker>) + Send>;
+type QualityListener = Box<dyn Fn(QualityWarning) + Send>;
+type ChunkListener = Box<dyn Fn(CaptureChunk) + Send>;
+
+/// Gain applied to captured samples before they are written
+#[derive(Debug, Clone, Copy)]
+pub struct InputGain {
+    pub multiplier: f32,
+    /// Run automatic gain control after the fixed multiplier
+    pub automatic: bool,
+}
+
+impl Default for InputGain {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.0,
+            automatic: false,
+        }
+    }
+}
+
+/// Stop automatically after this long below the silence threshold
+#[derive(Debug, Clone, Copy)]
+pub struct SilenceStop {
+    pub seconds: f32,
+    pub threshold_dbfs: f32,
+}
+
+/// Which input carries each side of a 1:1 tutoring session. Both may name
+/// the same device, such as a stereo interface with a mic on each input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DualChannel {
+    pub teacher: ChannelSource,
+    pub student: ChannelSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelSource {
+    /// Input device name; `None` uses the host default
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Channel of that device to take, from 0
+    #[serde(default)]
+    pub channel: u16,
+}
+
+/// Computer sound mixed into the recording, such as the far end of a video
+/// call in an online class
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemAudio {
+    /// On Windows, the output device to record through WASAPI loopback;
+    /// `None` uses the default output. Elsewhere, an input that carries the
+    /// system output, such as a loopback driver, which must be named.
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Level of the system audio relative to the microphone
+    #[serde(default = "unity_gain")]
+    pub gain: f32,
+}
+
+fn unity_gain() -> f32 {
+    1.0
+}
+
+/// A moment flagged while recording, such as a good answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureMarker {
+    /// Seconds into the capture part it was flagged in
+    pub offset_seconds: f64,
+    pub label: String,
+}
+
+/// A stretch of the recording in progress, cut off so it can be
+/// transcribed before the recording ends. The file is a copy; the capture
+/// itself carries on unbroken.
+#[derive(Debug, Clone)]
+pub struct CaptureChunk {
+    pub path: PathBuf,
+    /// Which recording it's from, counting starts of this recorder
+    pub session: u64,
+    /// Counts up from 0 in each capture part
+    pub index: u32,
+    /// Where it starts in its capture part
+    pub offset_seconds: f64,
+    pub duration_seconds: f64,
+}
+
+/// How long after playing a sound its echo is still cancelled. Covers a
+/// laptop's own speakers; a room's reverb tail is left alone.
+const ECHO_TAIL_SECONDS: f32 = 0.05;
+
+/// Most one lane of a dual-channel capture may run ahead of the other
+/// before the other is padded with silence
+const MAX_LANE_LAG_SECONDS: f64 = 0.5;
+
+/// How often captured samples are moved from memory to the capture file
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+/// WAV header is rewritten this often so a crash loses at most a second
+const HEADER_UPDATE_TICKS: u32 = 10;
+
+/// How long the permission probe listens. On macOS, while the user hasn't
+/// answered the access prompt, the stream opens but only delivers exact
+/// zeros, which a live microphone's noise floor never does for this long.
+const PERMISSION_PROBE: std::time::Duration = std::time::Duration::from_millis(300);
+
+/// No samples for this long means the device is gone, even if the backend
+/// never reported an error
+const STALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Drain ticks between attempts to reopen an input after losing every device
+const RECONNECT_TICKS: u32 = 10;
+
+/// Sent to the capture thread, which owns the input streams for as long as
+/// a recording runs
+enum CaptureCommand {
+    SetGain(InputGain),
+    SetSilenceStop(Option<SilenceStop>),
+    SetMaxDuration(Option<f32>),
+    Mark(String),
+    /// Finalize the capture file and exit
+    Stop,
+}
+
+/// What the capture thread reads each tick, changed only by commands
+struct CaptureSettings {
+    gain: InputGain,
+    silence_stop: Option<SilenceStop>,
+    max_duration: Option<f32>,
+    /// Labels marked since the last tick, placed once its audio is written
+    marks: Vec<String>,
+}
+
+impl CaptureSettings {
+    /// Returns whether the thread should stop
+    fn apply(&mut self, command: CaptureCommand) -> bool {
+        match command {
+            CaptureCommand::SetGain(gain) => self.gain = gain,
+            CaptureCommand::SetSilenceStop(silence_stop) => self.silence_stop = silence_stop,
+            CaptureCommand::SetMaxDuration(max_duration) => self.max_duration = max_duration,
+            CaptureCommand::Mark(label) => self.marks.push(label),
+            CaptureCommand::Stop => return true,
+        }
+        false
+    }
+}
+
+/// A recording in progress
+struct CaptureThread {
+    commands: mpsc::Sender<CaptureCommand>,
+    /// Ends with the capture's clip and level totals, and the markers placed
+    /// since the last rollover
+    handle: thread::JoinHandle<Result<(CaptureStats, Vec<CaptureMarker>), AudioError>>,
+}
+
+/// Settings and listeners for recording. cpal streams are not `Send`, so
+/// they never leave the capture thread; the recorder only holds a channel
+/// to it.
 pub struct AudioRecorder {
-    samples: Arc<Mutex<Vec<f32>>>,
-    is_recording: Arc<Mutex<bool>>,
-    sample_rate: Arc<Mutex<u32>>,
-    channels: Arc<Mutex<u16>>,
-    recording_thread: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
+    capture: Option<CaptureThread>,
+    /// Input device picked by the user; `None` uses the host default
+    device_name: Option<String>,
+    /// In-progress capture at the device's native rate and channel count
+    capture_path: PathBuf,
+    /// Called with the input level each time captured samples are drained
+    level_listener: Arc<Mutex<Option<LevelListener>>>,
+    silence_stop: Option<SilenceStop>,
+    gain: InputGain,
+    resample_quality: ResampleQuality,
+    /// Run the converted audio through a spectral gate before saving
+    noise_suppression: bool,
+    /// Called once per recording when the silence limit is reached. It runs on
+    /// the capture thread, so it must not stop the recorder itself.
+    auto_stop_listener: Arc<Mutex<Option<AutoStopListener>>>,
+    error_listener: Arc<Mutex<Option<CaptureErrorListener>>>,
+    /// Longest a single recording may run before it rolls over, in seconds
+    max_duration: Option<f32>,
+    /// Called with each finished part when a recording rolls over. Runs on
+    /// the capture thread, which keeps recording into a fresh file.
+    rollover_listener: Arc<Mutex<Option<RolloverListener>>>,
+    quality_listener: Arc<Mutex<Option<QualityListener>>>,
+    /// Totals for the last capture, filled in when its thread ends
+    last_stats: CaptureStats,
+    last_markers: Vec<CaptureMarker>,
+    /// Outcome of the last probe that let recording go ahead, so later
+    /// starts skip it; cleared when the device changes
+    microphone_checked: Option<MicrophonePermission>,
+    dual_channel: Option<DualChannel>,
+    /// Whether the recording in progress, or the last one, is dual-channel
+    capturing_dual: bool,
+    channel_weights: Option<Vec<f32>>,
+    system_audio: Option<SystemAudio>,
+    /// Cut a copy of the capture every this many seconds
+    chunk_seconds: Option<f32>,
+    /// Called on the capture thread with each chunk as it completes
+    chunk_listener: Arc<Mutex<Option<ChunkListener>>>,
+    /// Recordings started so far
+    sessions: u64,
+    archive: Option<ArchiveQuality>,
+    /// What the app is playing, for echo cancellation
+    echo_reference: Option<EchoReference>,
+    echo_cancellation: bool,
 }
 
 impl AudioRecorder {
-    pub fn new() -> Result<Self, AudioError> {
-        Ok(Self {
-            samples: Arc::new(Mutex::new(Vec::new())),
-            is_recording: Arc::new(Mutex::new(false)),
-            sample_rate: Arc::new(Mutex::new(16000)),
-            channels: Arc::new(Mutex::new(1)),
-            recording_thread: Arc::new(Mutex::new(None)),
-        })
+    /// Devices are only opened when recording starts, so this succeeds even
+    /// with no microphone attached.
+    pub fn new(capture_path: PathBuf) -> Self {
+        Self {
+            capture: None,
+            device_name: None,
+            capture_path,
+            level_listener: Arc::new(Mutex::new(None)),
+            silence_stop: None,
+            gain: InputGain::default(),
+            resample_quality: ResampleQuality::default(),
+            noise_suppression: false,
+            auto_stop_listener: Arc::new(Mutex::new(None)),
+            error_listener: Arc::new(Mutex::new(None)),
+            max_duration: None,
+            rollover_listener: Arc::new(Mutex::new(None)),
+            quality_listener: Arc::new(Mutex::new(None)),
+            last_stats: CaptureStats::default(),
+            last_markers: Vec::new(),
+            microphone_checked: None,
+            dual_channel: None,
+            capturing_dual: false,
+            channel_weights: None,
+            system_audio: None,
+            chunk_seconds: None,
+            chunk_listener: Arc::new(Mutex::new(None)),
+            sessions: 0,
+            archive: None,
+            echo_reference: None,
+            echo_cancellation: false,
+        }
+    }
+
+    /// Resampler used when converting a capture to 16kHz
+    pub fn set_resample_quality(&mut self, quality: ResampleQuality) {
+        self.resample_quality = quality;
+    }
+
+    /// Applies to recordings stopped after the change
+    pub fn set_noise_suppression(&mut self, enabled: bool) {
+        self.noise_suppression = enabled;
+    }
+
+    /// Takes effect immediately, including mid-recording
+    pub fn set_input_gain(&mut self, gain: InputGain) {
+        self.gain = gain;
+        self.send(CaptureCommand::SetGain(gain));
+    }
+
+    /// Record teacher and student into separate channels; applies from the
+    /// next recording
+    pub fn set_dual_channel(&mut self, dual_channel: Option<DualChannel>) {
+        self.dual_channel = dual_channel;
+    }
+
+    /// Weight each channel of a multichannel input gets when it's mixed to
+    /// mono, in the device's channel order; a 0 leaves that channel out.
+    /// `None` averages them. Applies to recordings stopped after the change.
+    pub fn set_channel_weights(&mut self, weights: Option<Vec<f32>>) {
+        self.channel_weights = weights;
+    }
+
+    /// Enable or disable stopping after continuous silence
+    /// Mix computer sound into the recording from the next one on. In dual
+    /// channel mode it goes into the student's channel, since that is who
+    /// comes through the speakers in a remote class.
+    pub fn set_system_audio(&mut self, system_audio: Option<SystemAudio>) {
+        self.system_audio = system_audio;
+    }
+
+    /// Where to hear what the app's own player is sending to the speakers
+    pub fn set_echo_reference(&mut self, echo_reference: Option<EchoReference>) {
+        self.echo_reference = echo_reference;
+    }
+
+    pub fn echo_reference(&self) -> Option<EchoReference> {
+        self.echo_reference.clone()
+    }
+
+    /// Take playback picked up by the microphone back out of the recording;
+    /// applies from the next recording
+    pub fn set_echo_cancellation(&mut self, enabled: bool) {
+        self.echo_cancellation = enabled;
+    }
+
+    pub fn set_silence_stop(&mut self, silence_stop: Option<SilenceStop>) {
+        self.silence_stop = silence_stop;
+        self.send(CaptureCommand::SetSilenceStop(silence_stop));
+    }
+
+    pub fn set_auto_stop_listener(&mut self, listener: impl Fn() + Send + 'static) {
+        *self.auto_stop_listener.lock_or_recover() = Some(Box::new(listener));
+    }
+
+    /// Hand over a chunk of the recording every `seconds` while it runs, so
+    /// it can be transcribed as it goes. What's left after the last full
+    /// chunk is only in the capture. Takes effect from the next recording.
+    pub fn set_chunk_seconds(&mut self, seconds: Option<f32>) {
+        self.chunk_seconds = seconds.filter(|s| *s > 0.0);
+    }
+
+    /// Runs on the capture thread, so it should only pass the chunk on.
+    /// The listener owns the file.
+    pub fn set_chunk_listener(&mut self, listener: impl Fn(CaptureChunk) + Send + 'static) {
+        *self.chunk_listener.lock_or_recover() = Some(Box::new(listener));
+    }
+
+    /// The recording in progress, or the last one, as numbered in its chunks
+    pub fn session(&self) -> u64 {
+        self.sessions
+    }
+
+    /// Chunk files a crash left behind
+    pub fn discard_chunks(&self) {
+        let Some(dir) = self.capture_path.parent() else {
+            return;
+        };
+        for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+            let is_chunk = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|n| n.starts_with(CHUNK_PREFIX) && n.ends_with(".wav"));
+            if is_chunk {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    /// Keep a full-quality copy of each capture as well as the 16kHz file;
+    /// takes effect from the next conversion
+    pub fn set_archive_quality(&mut self, archive: Option<ArchiveQuality>) {
+        self.archive = archive;
+    }
+
+    /// Split recordings into parts of at most `seconds`; takes effect
+    /// mid-recording
+    pub fn set_max_duration(&mut self, seconds: Option<f32>) {
+        self.max_duration = seconds.filter(|s| *s > 0.0);
+        self.send(CaptureCommand::SetMaxDuration(self.max_duration));
+    }
+
+    /// Pass a change on to the recording in progress, if any
+    fn send(&self, command: CaptureCommand) {
+        if let Some(capture) = &self.capture {
+            let _ = capture.commands.send(command);
+        }
+    }
+
+    /// Also receives the finished part's clip and level totals and the
+    /// markers placed in it
+    pub fn set_rollover_listener(
+        &mut self,
+        listener: impl Fn(PathBuf, CaptureStats, Vec<CaptureMarker>) + Send + 'static,
+    ) {
+        *self.rollover_listener.lock_or_recover() = Some(Box::new(listener));
+    }
+
+    /// Warned while recording if the input is clipping or near-silent
+    pub fn set_quality_listener(&mut self, listener: impl Fn(QualityWarning) + Send + 'static) {
+        *self.quality_listener.lock_or_recover() = Some(Box::new(listener));
+    }
+
+    /// Clip and level totals for the recording last stopped
+    pub fn last_capture_stats(&self) -> CaptureStats {
+        self.last_stats
+    }
+
+    /// Flag the current moment of the recording in progress. It lands at
+    /// the end of what has been captured when the capture thread picks it
+    /// up, which is straight away.
+    pub fn mark(&self, label: String) -> Result<(), AudioError> {
+        if !self.is_recording() {
+            return Err(AudioError::RecordingError("Not recording".to_string()));
+        }
+        self.send(CaptureCommand::Mark(label));
+        Ok(())
+    }
+
+    /// Markers placed in the recording last stopped, since its last rollover
+    pub fn take_markers(&mut self) -> Vec<CaptureMarker> {
+        std::mem::take(&mut self.last_markers)
+    }
+
+    /// Called from the capture thread when the input device is lost and
+    /// again if recording resumes on another one
+    pub fn set_error_listener(&mut self, listener: impl Fn(CaptureError) + Send + 'static) {
+        *self.error_listener.lock_or_recover() = Some(Box::new(listener));
+    }
+
+    /// Receive the input level roughly ten times a second while recording
+    pub fn set_level_listener(&mut self, listener: impl Fn(InputLevel) + Send + 'static) {
+        *self.level_listener.lock_or_recover() = Some(Box::new(listener));
+    }
+
+    /// Record from the named device, or the host default when `None`.
+    /// The name is kept even while the device is unplugged.
+    pub fn set_device(&mut self, name: Option<String>) {
+        if name != self.device_name {
+            self.microphone_checked = None;
+        }
+        self.device_name = name;
+    }
+
+    pub fn selected_device(&self) -> Option<String> {
+        self.device_name.clone()
+    }
+
+    /// Briefly open the selected device to see whether the OS passes audio
+    /// through. The first call may bring up the OS permission prompt.
+    pub fn check_microphone_permission(&mut self) -> Result<MicrophonePermission, AudioError> {
+        if let Some(permission) = self.microphone_checked {
+            return Ok(permission);
+        }
+        let permission = probe_microphone(self.selected_device().as_deref())?;
+        if matches!(permission, MicrophonePermission::Granted | MicrophonePermission::Silent) {
+            self.microphone_checked = Some(permission);
+        }
+        Ok(permission)
     }
 
     pub fn start_recording(&mut self) -> Result<(), AudioError> {
-        // Clear previous samples
-        self.samples.lock().unwrap().clear();
-        *self.is_recording.lock().unwrap() = true;
+        match self.check_microphone_permission()? {
+            MicrophonePermission::Granted => {}
+            MicrophonePermission::Silent => eprintln!("Input delivered only silence when probed; recording anyway"),
+            MicrophonePermission::Denied => return Err(AudioError::PermissionDenied),
+            MicrophonePermission::NoDevice => return Err(AudioError::NoInputDevice),
+        }
 
-        let samples = self.samples.clone();
-        let is_recording = self.is_recording.clone();
-        let sample_rate_out = self.sample_rate.clone();
-        let channels_out = self.channels.clone();
+        let capture_path = self.capture_path.clone();
+        let level_listener = self.level_listener.clone();
+        let auto_stop_listener = self.auto_stop_listener.clone();
+        let error_listener = self.error_listener.clone();
+        let rollover_listener = self.rollover_listener.clone();
+        let quality_listener = self.quality_listener.clone();
+        let mut settings = CaptureSettings {
+            gain: self.gain,
+            silence_stop: self.silence_stop,
+            max_duration: self.max_duration,
+            marks: Vec::new(),
+        };
+        self.last_stats = CaptureStats::default();
+        self.last_markers.clear();
+        let dual = self.dual_channel.clone();
+        self.capturing_dual = dual.is_some();
+        let system_audio = self.system_audio.clone();
+        let echo_reference = self.echo_reference.clone().filter(|_| self.echo_cancellation);
+        self.sessions += 1;
+        let session = self.sessions;
+        let chunk_seconds = self.chunk_seconds;
+        let chunk_listener = self.chunk_listener.clone();
+        let device_name = match &dual {
+            Some(dual) => dual.teacher.device.clone(),
+            None => self.selected_device(),
+        };
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (commands_tx, commands) = mpsc::channel();
 
         let handle = thread::spawn(move || {
+            // Filled by the stream callbacks, drained to disk each tick
+            let samples = Arc::new(Mutex::new(Vec::new()));
             let host = cpal::default_host();
-            let device = match host.default_input_device() {
+            let device = match find_input_device(&host, device_name.as_deref()) {
                 Some(d) => d,
                 None => {
-                    eprintln!("No input device available");
-                    return;
+                    let _ = ready_tx.send(Err(AudioError::NoInputDevice));
+                    return Ok(Default::default());
+                }
+            };
+
+            let (errors_tx, errors_rx) = mpsc::channel();
+            let input = match open_input(&device, samples.clone(), errors_tx.clone()) {
+                Ok(i) => i,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return Ok(Default::default());
                 }
             };
 
-            let config = match device.default_input_config() {
-                Ok(c) => c,
+            // The file keeps the first device's layout, or teacher and student
+            // channels in dual mode; a failover device is converted to it
+            let spec = WavSpec {
+                channels: if dual.is_some() { 2 } else { input.channels },
+                sample_rate: input.sample_rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            };
+            let mut layout = match Layout::new(&host, &input, spec, dual.as_ref()) {
+                Ok(l) => l,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return Ok(Default::default());
+                }
+            };
+            // Missing the far end of a call is reported, not a reason to
+            // lose the microphone too
+            let mut system = system_audio.as_ref().and_then(|system_audio| {
+                match SystemMix::open(&host, system_audio, spec, dual.is_some().then_some(1)) {
+                    Ok(mix) => Some(mix),
+                    Err(e) => {
+                        notify_capture_error(&error_listener, CaptureError {
+                            message: format!("System audio unavailable: {}", e),
+                            switched_to: None,
+                        });
+                        None
+                    }
+                }
+            });
+            let mut echo = echo_reference.map(|reference| EchoStage::new(reference, spec));
+            let mut writer = match WavWriter::create(&capture_path, spec) {
+                Ok(w) => w,
                 Err(e) => {
-                    eprintln!("Failed to get config: {}", e);
-                    return;
+                    let _ = ready_tx.send(Err(AudioError::from(e)));
+                    return Ok(Default::default());
                 }
             };
+            let _ = ready_tx.send(Ok(()));
+            let mut chunker =
+                chunk_seconds.map(|seconds| Chunker::new(&capture_path, session, spec, seconds, chunk_listener));
 
-            *sample_rate_out.lock().unwrap() = config.sample_rate().0;
-            *channels_out.lock().unwrap() = config.channels();
+            // Drain captured samples to disk while recording
+            let mut ticks = 0;
+            let mut silent_seconds = 0.0f32;
+            let mut auto_stopped = false;
+            let mut auto_gain = AutoGain::default();
+            let mut current = Some(input);
+            let mut last_heard = Instant::now();
+            let mut frames_written = 0u64;
+            let mut stats = CaptureStats::default();
+            let mut markers = Vec::new();
+            let mut warned: Vec<AudioQuality> = Vec::new();
+            loop {
+                // Wakes early for a command; whatever has been captured by
+                // then is drained as usual
+                let mut stopping = match commands.recv_timeout(FLUSH_INTERVAL) {
+                    Ok(command) => settings.apply(command),
+                    Err(mpsc::RecvTimeoutError::Timeout) => false,
+                    // The recorder was dropped without stopping
+                    Err(mpsc::RecvTimeoutError::Disconnected) => true,
+                };
+                while let Ok(command) = commands.try_recv() {
+                    stopping |= settings.apply(command);
+                }
+                if stopping {
+                    break;
+                }
 
-            let err_fn = |err| eprintln!("Stream error: {}", err);
+                let mut pending = layout.process(std::mem::take(&mut *samples.lock_or_recover()));
+                if !pending.is_empty() {
+                    last_heard = Instant::now();
+                }
+                if let Some(echo) = echo.as_mut() {
+                    echo.process(&mut pending, spec.channels);
+                }
+                if let Some(system) = system.as_mut() {
+                    system.mix(&mut pending, spec.channels);
+                }
+                apply_gain(&mut pending, settings.gain, &mut auto_gain);
+                write_chunk(&mut chunker, &pending);
 
-            let is_rec = is_recording.clone();
-            let samples_clone = samples.clone();
+                let level = measure_level(&pending);
+                if let Some(listener) = level_listener.lock_or_recover().as_ref() {
+                    listener(level);
+                }
 
-            let stream = match config.sample_format() {
-                SampleFormat::F32 => device.build_input_stream(
-                    &config.into(),
-                    move |data: &[f32], _| {
-                        if *is_rec.lock().unwrap() {
-                            samples_clone.lock().unwrap().extend_from_slice(data);
+                if let Some(stop) = settings.silence_stop {
+                    if level.rms_dbfs < stop.threshold_dbfs {
+                        silent_seconds += (pending.len() / spec.channels as usize) as f32 / spec.sample_rate as f32;
+                    } else {
+                        silent_seconds = 0.0;
+                    }
+                    if !auto_stopped && silent_seconds >= stop.seconds {
+                        auto_stopped = true;
+                        if let Some(listener) = auto_stop_listener.lock_or_recover().as_ref() {
+                            listener();
                         }
-                    },
-                    err_fn,
-                    None,
-                ),
-                SampleFormat::I16 => {
-                    let samples_clone = samples.clone();
-                    let is_rec = is_recording.clone();
-                    device.build_input_stream(
-                        &config.into(),
-                        move |data: &[i16], _| {
-                            if *is_rec.lock().unwrap() {
-                                let floats: Vec<f32> = data.iter().map(|&s| s.to_float_sample()).collect();
-                                samples_clone.lock().unwrap().extend(floats);
-                            }
-                        },
-                        err_fn,
-                        None,
-                    )
-                },
-                SampleFormat::U16 => {
-                    let samples_clone = samples.clone();
-                    let is_rec = is_recording.clone();
-                    device.build_input_stream(
-                        &config.into(),
-                        move |data: &[u16], _| {
-                            if *is_rec.lock().unwrap() {
-                                let floats: Vec<f32> = data.iter().map(|&s| s.to_float_sample()).collect();
-                                samples_clone.lock().unwrap().extend(floats);
-                            }
-                        },
-                        err_fn,
-                        None,
-                    )
-                },
-                _ => {
-                    eprintln!("Unsupported sample format");
-                    return;
+                    }
+                }
+                frames_written += (pending.len() / spec.channels as usize) as u64;
+                stats.add(&pending);
+                let quality = stats.quality();
+                let warmed_up = frames_written as f64 >= QUALITY_WARMUP_SECONDS * spec.sample_rate as f64;
+                if quality != AudioQuality::Ok && warmed_up && !warned.contains(&quality) {
+                    warned.push(quality);
+                    if let Some(listener) = quality_listener.lock_or_recover().as_ref() {
+                        listener(QualityWarning {
+                            quality,
+                            clipped_ratio: stats.clipped_ratio(),
+                            rms_dbfs: stats.rms_dbfs(),
+                        });
+                    }
+                }
+                for sample in pending {
+                    writer.write_sample(sample)?;
+                }
+                let position = frames_written as f64 / spec.sample_rate as f64;
+                markers.extend(settings.marks.drain(..).map(|label| CaptureMarker {
+                    offset_seconds: position,
+                    label,
+                }));
+                ticks += 1;
+                if ticks % HEADER_UPDATE_TICKS == 0 {
+                    writer.flush()?;
                 }
-            };
 
-            let stream = match stream {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("Failed to create stream: {}", e);
-                    return;
+                // Hand off the finished part and carry on without a gap
+                if let Some(max) = settings.max_duration {
+                    if frames_written as f64 >= max as f64 * spec.sample_rate as f64 {
+                        let (next, finished) = roll_over(writer, &capture_path, spec)?;
+                        writer = next;
+                        if let Some(chunker) = chunker.as_mut() {
+                            chunker.restart();
+                        }
+                        frames_written = 0;
+                        silent_seconds = 0.0;
+                        auto_stopped = false;
+                        let part_stats = std::mem::take(&mut stats);
+                        let part_markers = std::mem::take(&mut markers);
+                        warned.clear();
+                        match rollover_listener.lock_or_recover().as_ref() {
+                            Some(listener) => listener(finished, part_stats, part_markers),
+                            None => eprintln!("Recording rolled over to {}", finished.display()),
+                        }
+                    }
                 }
-            };
 
-            if let Err(e) = stream.play() {
-                eprintln!("Failed to play stream: {}", e);
-                return;
-            }
+                // A device that goes away either says so or just stops delivering
+                let mut lost = false;
+                while let Ok(err) = errors_rx.try_recv() {
+                    eprintln!("Stream error: {}", err);
+                    match err {
+                        cpal::StreamError::DeviceNotAvailable => lost = true,
+                        _ => telemetry::AUDIO_OVERRUNS.increment(),
+                    }
+                }
+                let mut reopened = false;
+                if let Some(lost_input) = current.take_if(|_| lost || last_heard.elapsed() >= STALL_TIMEOUT) {
+                    drop(lost_input.stream);
+                    let mut rest = layout.process(std::mem::take(&mut *samples.lock_or_recover()));
+                    if let Some(echo) = echo.as_mut() {
+                        echo.process(&mut rest, spec.channels);
+                    }
+                    if let Some(system) = system.as_mut() {
+                        system.mix(&mut rest, spec.channels);
+                    }
+                    apply_gain(&mut rest, settings.gain, &mut auto_gain);
+                    write_chunk(&mut chunker, &rest);
+                    stats.add(&rest);
+                    for sample in rest {
+                        writer.write_sample(sample)?;
+                    }
+                    writer.flush()?;
 
-            // Keep thread alive while recording
-            while *is_recording.lock().unwrap() {
-                thread::sleep(std::time::Duration::from_millis(100));
+                    current = reopen_input(&host, device_name.as_deref(), &samples, &errors_tx);
+                    reopened = current.is_some();
+                    notify_capture_error(&error_listener, CaptureError {
+                        message: format!("Lost input device '{}'", lost_input.name),
+                        switched_to: current.as_ref().map(|i| i.name.clone()),
+                    });
+                } else if current.is_none() && ticks % RECONNECT_TICKS == 0 {
+                    // Keep trying so plugging a mic back in resumes the recording
+                    current = reopen_input(&host, device_name.as_deref(), &samples, &errors_tx);
+                    if let Some(input) = &current {
+                        reopened = true;
+                        notify_capture_error(&error_listener, CaptureError {
+                            message: format!("Recording resumed on '{}'", input.name),
+                            switched_to: Some(input.name.clone()),
+                        });
+                    }
+                }
+                if let Some(input) = current.as_ref().filter(|_| reopened) {
+                    layout.rebind(input, spec);
+                    last_heard = Instant::now();
+                }
             }
 
-            drop(stream);
+            drop(current);
+            let mut pending = layout.process(std::mem::take(&mut *samples.lock_or_recover()));
+            pending.extend(layout.finish());
+            if let Some(echo) = echo.as_mut() {
+                echo.process(&mut pending, spec.channels);
+            }
+            if let Some(system) = system.as_mut() {
+                system.mix(&mut pending, spec.channels);
+            }
+            drop(system);
+            // The tail is left to whoever transcribes the whole capture
+            if let Some(mut chunker) = chunker {
+                chunker.restart();
+            }
+            apply_gain(&mut pending, settings.gain, &mut auto_gain);
+            stats.add(&pending);
+            frames_written += (pending.len() / spec.channels as usize) as u64;
+            for sample in pending {
+                writer.write_sample(sample)?;
+            }
+            writer.finalize()?;
+            let position = frames_written as f64 / spec.sample_rate as f64;
+            markers.extend(settings.marks.drain(..).map(|label| CaptureMarker {
+                offset_seconds: position,
+                label,
+            }));
+            Ok((stats, markers))
         });
 
-        *self.recording_thread.lock().unwrap() = Some(handle);
+        // Wait for the stream to open so a missing mic is reported, not recorded as silence
+        let started = ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err(AudioError::StreamError("Recording thread exited".to_string())));
 
-        // Give the thread time to start
-        thread::sleep(std::time::Duration::from_millis(200));
+        if let Err(e) = started {
+            let _ = handle.join();
+            let _ = std::fs::remove_file(&self.capture_path);
+            return Err(e);
+        }
 
+        self.capture = Some(CaptureThread { commands: commands_tx, handle });
         Ok(())
     }
 
-    pub fn stop_recording(&mut self) -> Vec<f32> {
-        *self.is_recording.lock().unwrap() = false;
+    /// Stop capture and write it to `path` as 16kHz mono, returning the duration
+    pub fn stop_recording(&mut self, path: &Path) -> Result<f64, AudioError> {
+        let capture = self.finish_capture()?;
+        // Short clips like corrections don't need the channels split out
+        let conversion = Conversion { dual: false, ..self.conversion() };
+        Ok(conversion.run(&capture, path, |_| {})?.duration)
+    }
 
-        // Wait for thread to finish
-        if let Some(handle) = self.recording_thread.lock().unwrap().take() {
-            let _ = handle.join();
+    /// Stop capture and move the finished file aside for `conversion` to
+    /// pick up, so converting it doesn't have to hold the recorder. It is
+    /// named like a rollover part, so a crash before it's converted still
+    /// gets it recovered.
+    pub fn finish_capture(&mut self) -> Result<PathBuf, AudioError> {
+        // Wait for the writer to finalize the capture file
+        if let Some(capture) = self.capture.take() {
+            let _ = capture.commands.send(CaptureCommand::Stop);
+            (self.last_stats, self.last_markers) = capture
+                .handle
+                .join()
+                .map_err(|_| AudioError::RecordingError("Recording thread panicked".to_string()))??;
         }
+        set_aside(&self.capture_path)
+    }
 
-        let samples = self.samples.lock().unwrap().clone();
-        let sample_rate = *self.sample_rate.lock().unwrap();
-        let channels = *self.channels.lock().unwrap();
+    /// Convert a finished rollover part to `path` as 16kHz mono and remove
+    /// it, returning the duration
+    pub fn convert_capture(&self, capture: &Path, path: &Path) -> Result<f64, AudioError> {
+        Ok(self.conversion().run(capture, path, |_| {})?.duration)
+    }
 
-        // Resample to 16kHz mono if needed
-        if sample_rate != 16000 || channels != 1 {
-            resample_to_16khz_mono(&samples, sample_rate, channels)
-        } else {
-            samples
+    /// How captures are currently converted to 16kHz mono
+    pub fn conversion(&self) -> Conversion {
+        Conversion {
+            quality: self.resample_quality,
+            denoise: self.noise_suppression,
+            dual: self.capturing_dual,
+            // Dual-channel captures hold the two lanes, not the device's channels
+            weights: self.channel_weights.clone().filter(|_| !self.capturing_dual),
+            archive: self.archive,
         }
     }
 
-    pub fn save_wav(&self, samples: &[f32], path: &PathBuf) -> Result<f64, AudioError> {
-        let spec = WavSpec {
-            channels: 1,
-            sample_rate: 16000,
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
+    /// Rollover parts nobody got to process, oldest first
+    pub fn rollover_captures(&self) -> Vec<PathBuf> {
+        let Some(dir) = self.capture_path.parent() else {
+            return Vec::new();
         };
+        let mut parts: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| {
+                        p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| n.starts_with(ROLLOVER_PREFIX) && n.ends_with(".wav"))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        parts.sort();
+        parts
+    }
 
-        let mut writer = WavWriter::create(path, spec)?;
-
-        for &sample in samples {
-            let amplitude = (sample * i16::MAX as f32) as i16;
-            writer.write_sample(amplitude)?;
+    /// Save a capture left behind by a crash to `path`, returning its duration.
+    /// Returns `None` if there is nothing to recover.
+    pub fn recover_capture(&self, path: &Path) -> Result<Option<f64>, AudioError> {
+        if self.is_recording() || !self.capture_path.exists() {
+            return Ok(None);
+        }
+        let conversion = self.conversion();
+        let duration = conversion.run(&self.capture_path, path, |_| {})?.duration;
+        if duration == 0.0 {
+            std::fs::remove_file(path)?;
+            if let Some(archive) = conversion.archive_path(path) {
+                let _ = std::fs::remove_file(archive);
+            }
+            return Ok(None);
         }
+        Ok(Some(duration))
+    }
 
-        writer.finalize()?;
+    pub fn is_recording(&self) -> bool {
+        self.capture.is_some()
+    }
+}
 
-        // Calculate duration
-        let duration = samples.len() as f64 / 16000.0;
-        Ok(duration)
+fn apply_gain(samples: &mut [f32], gain: InputGain, auto_gain: &mut AutoGain) {
+    if gain.multiplier != 1.0 {
+        for sample in samples.iter_mut() {
+            *sample *= gain.multiplier;
+        }
+    }
+    if gain.automatic {
+        auto_gain.process(samples);
     }
+}
 
-    pub fn is_recording(&self) -> bool {
-        *self.is_recording.lock().unwrap()
+fn measure_level(samples: &[f32]) -> InputLevel {
+    let to_dbfs = |amplitude: f32| (20.0 * amplitude.log10()).max(LEVEL_FLOOR_DBFS);
+    if samples.is_empty() {
+        return InputLevel {
+            rms_dbfs: LEVEL_FLOOR_DBFS,
+            peak_dbfs: LEVEL_FLOOR_DBFS,
+        };
+    }
+    let sum_squares: f32 = samples.iter().map(|s| s * s).sum();
+    let peak = samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+    InputLevel {
+        rms_dbfs: to_dbfs((sum_squares / samples.len() as f32).sqrt()),
+        peak_dbfs: to_dbfs(peak),
     }
 }
 
-fn resample_to_16khz_mono(samples: &[f32], sample_rate: u32, channels: u16) -> Vec<f32> {
-    // First convert to mono by averaging channels
-    let mono: Vec<f32> = if channels > 1 {
-        samples
-            .chunks(channels as usize)
-            .map(|chunk| chunk.iter().sum::<f32>() / channels as f32)
-            .collect()
-    } else {
-        samples.to_vec()
-    };
+/// Start of the file name a finished part is moved to
+const ROLLOVER_PREFIX: &str = "capture.rollover-";
+/// Start of a chunk's file name
+const CHUNK_PREFIX: &str = "capture.chunk-";
+
+/// Move a finalized capture file out of the way of the next one
+fn set_aside(capture_path: &Path) -> Result<PathBuf, AudioError> {
+    let finished = capture_path.with_file_name(format!(
+        "{}{}.wav",
+        ROLLOVER_PREFIX,
+        chrono::Utc::now().format("%Y%m%d%H%M%S%3f")
+    ));
+    std::fs::rename(capture_path, &finished)?;
+    Ok(finished)
+}
+
+/// Finalize the capture file, move it aside and start a new one in its place
+fn roll_over(
+    writer: WavWriter<std::io::BufWriter<std::fs::File>>,
+    capture_path: &Path,
+    spec: WavSpec,
+) -> Result<(WavWriter<std::io::BufWriter<std::fs::File>>, PathBuf), AudioError> {
+    writer.finalize()?;
+    let finished = set_aside(capture_path)?;
+    Ok((WavWriter::create(capture_path, spec)?, finished))
+}
+
+/// Copies what is written to the capture into a chunk file, handing each on
+/// once it is long enough
+struct Chunker {
+    dir: PathBuf,
+    session: u64,
+    spec: WavSpec,
+    frames_per_chunk: u64,
+    listener: Arc<Mutex<Option<ChunkListener>>>,
+    /// Capture parts started, so chunk files never share a name
+    part: u32,
+    index: u32,
+    /// Frames before the current chunk in this capture part
+    offset_frames: u64,
+    frames: u64,
+    current: Option<(PathBuf, WavWriter<std::io::BufWriter<std::fs::File>>)>,
+}
 
-    // Simple linear interpolation resampling to 16kHz
-    let ratio = sample_rate as f64 / 16000.0;
-    let new_len = (mono.len() as f64 / ratio) as usize;
-    let mut resampled = Vec::with_capacity(new_len);
+impl Chunker {
+    fn new(
+        capture_path: &Path,
+        session: u64,
+        spec: WavSpec,
+        seconds: f32,
+        listener: Arc<Mutex<Option<ChunkListener>>>,
+    ) -> Self {
+        Self {
+            dir: capture_path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            session,
+            spec,
+            frames_per_chunk: ((seconds as f64 * spec.sample_rate as f64) as u64).max(1),
+            listener,
+            part: 0,
+            index: 0,
+            offset_frames: 0,
+            frames: 0,
+            current: None,
+        }
+    }
 
-    for i in 0..new_len {
-        let src_idx = i as f64 * ratio;
-        let idx = src_idx as usize;
-        let frac = (src_idx - idx as f64) as f32;
+    fn write(&mut self, samples: &[f32]) -> Result<(), AudioError> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        if self.current.is_none() {
+            let name = format!("{}{}-{}-{}.wav", CHUNK_PREFIX, self.session, self.part, self.index);
+            let path = self.dir.join(name);
+            let writer = WavWriter::create(&path, self.spec)?;
+            self.current = Some((path, writer));
+        }
+        if let Some((_, writer)) = self.current.as_mut() {
+            for &sample in samples {
+                writer.write_sample(sample)?;
+            }
+        }
+        self.frames += (samples.len() / self.spec.channels as usize) as u64;
+        if self.frames < self.frames_per_chunk {
+            return Ok(());
+        }
 
-        if idx + 1 < mono.len() {
-            let interpolated = mono[idx] * (1.0 - frac) + mono[idx + 1] * frac;
-            resampled.push(interpolated);
-        } else if idx < mono.len() {
-            resampled.push(mono[idx]);
+        let Some((path, writer)) = self.current.take() else {
+            return Ok(());
+        };
+        writer.finalize()?;
+        let rate = self.spec.sample_rate as f64;
+        let chunk = CaptureChunk {
+            path,
+            session: self.session,
+            index: self.index,
+            offset_seconds: self.offset_frames as f64 / rate,
+            duration_seconds: self.frames as f64 / rate,
+        };
+        self.index += 1;
+        self.offset_frames += self.frames;
+        self.frames = 0;
+        match self.listener.lock_or_recover().as_ref() {
+            Some(listener) => listener(chunk),
+            None => {
+                let _ = std::fs::remove_file(&chunk.path);
+            }
         }
+        Ok(())
     }
 
-    resampled
+    /// Drop the unfinished chunk and count again from the start of a new part
+    fn restart(&mut self) {
+        if let Some((path, writer)) = self.current.take() {
+            drop(writer);
+            let _ = std::fs::remove_file(path);
+        }
+        self.part += 1;
+        self.index = 0;
+        self.offset_frames = 0;
+        self.frames = 0;
+    }
 }
 
-// Make AudioRecorder Send + Sync safe by not storing the stream
-unsafe impl Send for AudioRecorder {}
-unsafe impl Sync for AudioRecorder {}
+/// Copy `samples` into the current chunk, giving up on chunking, but not
+/// on the recording, if that fails
+fn write_chunk(chunker: &mut Option<Chunker>, samples: &[f32]) {
+    if let Some(c) = chunker.as_mut() {
+        if let Err(e) = c.write(samples) {
+            eprintln!("Stopped cutting chunks: {}", e);
+            if let Some(mut c) = chunker.take() {
+                c.restart();
+            }
+        }
+    }
+}
+
+/// An open capture stream and the layout of what it delivers
+struct Input {
+    stream: cpal::Stream,
+    name: String,
+    channels: u16,
+    sample_rate: u32,
+}
+
+fn open_input(
+    device: &cpal::Device,
+    samples: Arc<Mutex<Vec<f32>>>,
+    errors: mpsc::Sender<cpal::StreamError>,
+) -> Result<Input, AudioError> {
+    let config = device
+        .default_input_config()
+        .map_err(|e| AudioError::ConfigError(e.to_string()))?;
+    open_stream(device, config, samples, errors)
+}
+
+/// Record what an output device plays. WASAPI does this when an input
+/// stream is built on an output device, in the output's own format.
+fn open_loopback(
+    device: &cpal::Device,
+    samples: Arc<Mutex<Vec<f32>>>,
+    errors: mpsc::Sender<cpal::StreamError>,
+) -> Result<Input, AudioError> {
+    let config = device
+        .default_output_config()
+        .map_err(|e| AudioError::ConfigError(e.to_string()))?;
+    open_stream(device, config, samples, errors)
+}
+
+fn open_stream(
+    device: &cpal::Device,
+    config: cpal::SupportedStreamConfig,
+    samples: Arc<Mutex<Vec<f32>>>,
+    errors: mpsc::Sender<cpal::StreamError>,
+) -> Result<Input, AudioError> {
+    let (channels, sample_rate) = (config.channels(), config.sample_rate().0);
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => capture_stream::<f32>(device, &config.into(), samples, errors),
+        SampleFormat::I16 => capture_stream::<i16>(device, &config.into(), samples, errors),
+        SampleFormat::U16 => capture_stream::<u16>(device, &config.into(), samples, errors),
+        format => {
+            return Err(AudioError::ConfigError(format!(
+                "Unsupported sample format: {:?}",
+                format
+            )))
+        }
+    }
+    .map_err(|e| AudioError::StreamError(e.to_string()))?;
+    stream
+        .play()
+        .map_err(|e| AudioError::StreamError(e.to_string()))?;
+
+    Ok(Input {
+        stream,
+        name: device.name().unwrap_or_else(|_| "Unknown device".to_string()),
+        channels,
+        sample_rate,
+    })
+}
+
+fn capture_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: Arc<Mutex<Vec<f32>>>,
+    errors: mpsc::Sender<cpal::StreamError>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            samples.lock_or_recover().extend(data.iter().map(|&s| s.to_sample::<f32>()));
+        },
+        move |err| {
+            let _ = errors.send(err);
+        },
+        None,
+    )
+}
+
+/// The selected device if it's back, otherwise the host default
+fn reopen_input(
+    host: &cpal::Host,
+    device_name: Option<&str>,
+    samples: &Arc<Mutex<Vec<f32>>>,
+    errors: &mpsc::Sender<cpal::StreamError>,
+) -> Option<Input> {
+    let device = find_input_device(host, device_name)?;
+    match open_input(&device, samples.clone(), errors.clone()) {
+        Ok(input) => Some(input),
+        Err(e) => {
+            eprintln!("Failed to reopen input: {}", e);
+            None
+        }
+    }
+}
+
+fn notify_capture_error(listener: &Mutex<Option<CaptureErrorListener>>, error: CaptureError) {
+    eprintln!("{}", error.message);
+    if let Some(listener) = listener.lock_or_recover().as_ref() {
+        listener(error);
+    }
+}
+
+/// How the open inputs' samples become the capture file's frames
+enum Layout {
+    Single(Conform),
+    Dual(Box<Duet>),
+}
+
+impl Layout {
+    fn new(
+        host: &cpal::Host,
+        input: &Input,
+        spec: WavSpec,
+        dual: Option<&DualChannel>,
+    ) -> Result<Self, AudioError> {
+        Ok(match dual {
+            Some(dual) => Self::Dual(Box::new(Duet::new(host, input, spec, dual)?)),
+            None => Self::Single(Conform::new(input, spec)),
+        })
+    }
+
+    fn process(&mut self, samples: Vec<f32>) -> Vec<f32> {
+        match self {
+            Self::Single(conform) => conform.process(samples),
+            Self::Dual(duet) => duet.process(samples),
+        }
+    }
+
+    /// Frames still held back once recording stops
+    fn finish(&mut self) -> Vec<f32> {
+        match self {
+            Self::Single(_) => Vec::new(),
+            Self::Dual(duet) => duet.finish(),
+        }
+    }
+
+    /// Carry on from a failover device in place of the first one
+    fn rebind(&mut self, input: &Input, spec: WavSpec) {
+        match self {
+            Self::Single(conform) => *conform = Conform::new(input, spec),
+            Self::Dual(duet) => duet.rebind(input, spec),
+        }
+    }
+}
+
+/// Brings a failover device's samples to the capture file's channel count
+/// and rate, which are fixed once recording starts
+struct Conform {
+    from_channels: usize,
+    to_channels: usize,
+    /// Take only this channel rather than mixing them all down
+    pick: Option<usize>,
+    resampler: Option<Resampler>,
+}
+
+impl Conform {
+    fn new(input: &Input, spec: WavSpec) -> Self {
+        Self::build(input, spec.channels, spec.sample_rate, None)
+    }
+
+    /// One channel of `input` as mono at `sample_rate`
+    fn lane(input: &Input, channel: u16, sample_rate: u32) -> Self {
+        Self::build(input, 1, sample_rate, Some(channel.min(input.channels.max(1) - 1) as usize))
+    }
+
+    fn build(input: &Input, to_channels: u16, sample_rate: u32, pick: Option<usize>) -> Self {
+        Self {
+            from_channels: input.channels.max(1) as usize,
+            to_channels: to_channels.max(1) as usize,
+            pick,
+            resampler: (input.sample_rate != sample_rate)
+                .then(|| Resampler::new(ResampleQuality::Fast, input.sample_rate, sample_rate)),
+        }
+    }
+
+    fn process(&mut self, samples: Vec<f32>) -> Vec<f32> {
+        if self.from_channels == self.to_channels && self.resampler.is_none() && self.pick.is_none() {
+            return samples;
+        }
+        // Downmix, resample, then copy to every channel of the file
+        let mut mono = Vec::new();
+        match self.pick {
+            Some(channel) => mono.extend(samples.chunks_exact(self.from_channels).map(|f| f[channel])),
+            None => mix_down(&samples, self.from_channels, &mut mono),
+        }
+        let resampled = match self.resampler.as_mut() {
+            Some(resampler) => {
+                let mut resampled = Vec::new();
+                resampler.push_block(&mono, &mut resampled);
+                resampled
+            }
+            None => mono,
+        };
+        resampled
+            .into_iter()
+            .flat_map(|s| std::iter::repeat_n(s, self.to_channels))
+            .collect()
+    }
+}
+
+/// The student's own device in dual-channel mode
+struct StudentInput {
+    input: Input,
+    samples: Arc<Mutex<Vec<f32>>>,
+    errors: mpsc::Receiver<cpal::StreamError>,
+}
+
+/// Teacher and student lanes interleaved into a dual-channel capture's two
+/// channels, teacher first. When the student has a device of their own its
+/// stream isn't failed over; the lane just goes silent if it's unplugged.
+struct Duet {
+    teacher_channel: u16,
+    student_channel: u16,
+    teacher: Conform,
+    student: Conform,
+    /// `None` when both lanes come off the teacher's device
+    student_input: Option<StudentInput>,
+    backlog: [Vec<f32>; 2],
+    max_lag: usize,
+}
+
+impl Duet {
+    fn new(
+        host: &cpal::Host,
+        input: &Input,
+        spec: WavSpec,
+        dual: &DualChannel,
+    ) -> Result<Self, AudioError> {
+        let student_input = if dual.student.device == dual.teacher.device {
+            None
+        } else {
+            let device = find_input_device(host, dual.student.device.as_deref()).ok_or(AudioError::NoInputDevice)?;
+            let samples = Arc::new(Mutex::new(Vec::new()));
+            let (errors_tx, errors) = mpsc::channel();
+            let input = open_input(&device, samples.clone(), errors_tx)?;
+            Some(StudentInput { input, samples, errors })
+        };
+        let student_source = student_input.as_ref().map(|s| &s.input).unwrap_or(input);
+        Ok(Self {
+            teacher_channel: dual.teacher.channel,
+            student_channel: dual.student.channel,
+            teacher: Conform::lane(input, dual.teacher.channel, spec.sample_rate),
+            student: Conform::lane(student_source, dual.student.channel, spec.sample_rate),
+            student_input,
+            backlog: [Vec::new(), Vec::new()],
+            max_lag: (MAX_LANE_LAG_SECONDS * spec.sample_rate as f64) as usize,
+        })
+    }
+
+    fn process(&mut self, samples: Vec<f32>) -> Vec<f32> {
+        let student_samples = match &self.student_input {
+            Some(student) => {
+                while let Ok(err) = student.errors.try_recv() {
+                    eprintln!("Student input error: {}", err);
+                    telemetry::AUDIO_OVERRUNS.increment();
+                }
+                std::mem::take(&mut *student.samples.lock_or_recover())
+            }
+            None => samples.clone(),
+        };
+        self.backlog[0].extend(self.teacher.process(samples));
+        self.backlog[1].extend(self.student.process(student_samples));
+
+        // A lane that stalls, or whose device runs on a slightly slower clock,
+        // is padded with silence rather than holding the other back
+        let [teacher, student] = &mut self.backlog;
+        if teacher.len() > student.len() + self.max_lag {
+            student.resize(teacher.len() - self.max_lag, 0.0);
+        } else if student.len() > teacher.len() + self.max_lag {
+            teacher.resize(student.len() - self.max_lag, 0.0);
+        }
+        let frames = teacher.len().min(student.len());
+        self.interleave(frames)
+    }
+
+    fn finish(&mut self) -> Vec<f32> {
+        let frames = self.backlog[0].len().max(self.backlog[1].len());
+        for lane in &mut self.backlog {
+            lane.resize(frames, 0.0);
+        }
+        self.interleave(frames)
+    }
+
+    fn interleave(&mut self, frames: usize) -> Vec<f32> {
+        let [teacher, student] = &mut self.backlog;
+        teacher
+            .drain(..frames)
+            .zip(student.drain(..frames))
+            .flat_map(|(t, s)| [t, s])
+            .collect()
+    }
+
+    fn rebind(&mut self, input: &Input, spec: WavSpec) {
+        self.teacher = Conform::lane(input, self.teacher_channel, spec.sample_rate);
+        if self.student_input.is_none() {
+            self.student = Conform::lane(input, self.student_channel, spec.sample_rate);
+        }
+    }
+}
+
+/// System audio added onto the microphone's frames. The microphone sets
+/// the pace: a loopback stream delivers nothing while nothing is playing,
+/// so system audio that hasn't arrived counts as silence, and any that runs
+/// too far ahead is dropped. Like the student's own device it isn't failed
+/// over; it just goes silent if the device goes away.
+struct SystemMix {
+    input: Input,
+    samples: Arc<Mutex<Vec<f32>>>,
+    errors: mpsc::Receiver<cpal::StreamError>,
+    conform: Conform,
+    gain: f32,
+    /// Channel of the capture file it goes into; `None` for every channel
+    channel: Option<usize>,
+    backlog: Vec<f32>,
+    max_lag: usize,
+}
+
+impl SystemMix {
+    fn open(
+        host: &cpal::Host,
+        system_audio: &SystemAudio,
+        spec: WavSpec,
+        channel: Option<usize>,
+    ) -> Result<Self, AudioError> {
+        let device = find_system_device(host, system_audio.device.as_deref()).ok_or(AudioError::NoInputDevice)?;
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let (errors_tx, errors) = mpsc::channel();
+        let input = if cfg!(target_os = "windows") {
+            open_loopback(&device, samples.clone(), errors_tx)?
+        } else {
+            open_input(&device, samples.clone(), errors_tx)?
+        };
+        Ok(Self {
+            conform: Conform::build(&input, 1, spec.sample_rate, None),
+            input,
+            samples,
+            errors,
+            gain: system_audio.gain,
+            channel,
+            backlog: Vec::new(),
+            max_lag: (MAX_LANE_LAG_SECONDS * spec.sample_rate as f64) as usize,
+        })
+    }
+
+    fn mix(&mut self, frames: &mut [f32], channels: u16) {
+        while let Ok(err) = self.errors.try_recv() {
+            eprintln!("System audio error on '{}': {}", self.input.name, err);
+            telemetry::AUDIO_OVERRUNS.increment();
+        }
+        let captured = std::mem::take(&mut *self.samples.lock_or_recover());
+        self.backlog.extend(self.conform.process(captured));
+
+        let channels = channels.max(1) as usize;
+        let count = (frames.len() / channels).min(self.backlog.len());
+        for (frame, sample) in frames.chunks_exact_mut(channels).zip(self.backlog.drain(..count)) {
+            let sample = sample * self.gain;
+            match self.channel {
+                Some(channel) => frame[channel.min(channels - 1)] += sample,
+                None => frame.iter_mut().for_each(|s| *s += sample),
+            }
+        }
+        if self.backlog.len() > self.max_lag {
+            let excess = self.backlog.len() - self.max_lag;
+            self.backlog.drain(..excess);
+        }
+    }
+}
+
+/// Playback echo taken out of the microphone's frames. Like `SystemMix`
+/// the microphone sets the pace: each frame is matched with the next
+/// played sample, and frames with nothing played against them pass
+/// through untouched.
+struct EchoStage {
+    reference: EchoReference,
+    /// Rebuilt whenever the output device's rate changes
+    resampler: Option<(u32, Resampler)>,
+    sample_rate: u32,
+    backlog: Vec<f32>,
+    max_lag: usize,
+    /// One per channel of the capture file
+    cancellers: Vec<EchoCanceller>,
+}
+
+impl EchoStage {
+    fn new(reference: EchoReference, spec: WavSpec) -> Self {
+        // Whatever was played before the recording started has no echo in it
+        let _ = reference.take();
+        Self {
+            reference,
+            resampler: None,
+            sample_rate: spec.sample_rate,
+            backlog: Vec::new(),
+            max_lag: (MAX_LANE_LAG_SECONDS * spec.sample_rate as f64) as usize,
+            cancellers: (0..spec.channels.max(1))
+                .map(|_| EchoCanceller::new(spec.sample_rate, ECHO_TAIL_SECONDS))
+                .collect(),
+        }
+    }
+
+    fn process(&mut self, frames: &mut [f32], channels: u16) {
+        let (played, rate) = self.reference.take();
+        if !played.is_empty() {
+            if self.resampler.as_ref().is_none_or(|(from, _)| *from != rate) {
+                self.resampler = Some((rate, Resampler::new(ResampleQuality::Fast, rate, self.sample_rate)));
+            }
+            if let Some((_, resampler)) = self.resampler.as_mut() {
+                resampler.push_block(&played, &mut self.backlog);
+            }
+        }
+
+        let channels = channels.max(1) as usize;
+        let count = (frames.len() / channels).min(self.backlog.len());
+        for (frame, reference) in frames.chunks_exact_mut(channels).zip(self.backlog.drain(..count)) {
+            for (sample, canceller) in frame.iter_mut().zip(self.cancellers.iter_mut()) {
+                *sample = canceller.process(*sample, reference);
+            }
+        }
+        if self.backlog.len() > self.max_lag {
+            let excess = self.backlog.len() - self.max_lag;
+            self.backlog.drain(..excess);
+        }
+    }
+}
+
+/// Where system audio comes from. There's no fallback to a default
+/// outside Windows, where the default input is the microphone itself.
+fn find_system_device(host: &cpal::Host, name: Option<&str>) -> Option<cpal::Device> {
+    if cfg!(target_os = "windows") {
+        let found = name.and_then(|name| {
+            host.output_devices()
+                .ok()
+                .and_then(|mut devices| devices.find(|d| d.name().ok().as_deref() == Some(name)))
+        });
+        return found.or_else(|| host.default_output_device());
+    }
+    let name = name?;
+    host.input_devices()
+        .ok()
+        .and_then(|mut devices| devices.find(|d| d.name().ok().as_deref() == Some(name)))
+}
+
+/// Every input device the host offers. Enumerating can take a while on
+/// some hosts, so this needs no recorder and can run without its lock.
+pub fn list_input_devices() -> Result<Vec<InputDevice>, AudioError> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+    let devices = host
+        .input_devices()
+        .map_err(|e| AudioError::ConfigError(e.to_string()))?;
+
+    Ok(devices
+        .filter_map(|d| d.name().ok())
+        .map(|name| InputDevice {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+        })
+        .collect())
+}
+
+/// Where system audio can be taken from: output devices on Windows,
+/// otherwise inputs, one of which has to be a loopback driver
+pub fn list_system_sources() -> Result<Vec<InputDevice>, AudioError> {
+    let host = cpal::default_host();
+    let (default_name, devices) = if cfg!(target_os = "windows") {
+        (
+            host.default_output_device().and_then(|d| d.name().ok()),
+            host.output_devices().map_err(|e| AudioError::ConfigError(e.to_string()))?,
+        )
+    } else {
+        (None, host.input_devices().map_err(|e| AudioError::ConfigError(e.to_string()))?)
+    };
+
+    Ok(devices
+        .filter_map(|d| d.name().ok())
+        .map(|name| InputDevice {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+        })
+        .collect())
+}
+
+/// Whether recording from `device_name`, or the default, could start now
+pub fn input_device_available(device_name: Option<&str>) -> bool {
+    find_input_device(&cpal::default_host(), device_name).is_some()
+}
+
+/// The named input device, falling back to the default if it has gone away
+fn find_input_device(host: &cpal::Host, name: Option<&str>) -> Option<cpal::Device> {
+    if let Some(name) = name {
+        let found = host
+            .input_devices()
+            .ok()
+            .and_then(|mut devices| devices.find(|d| d.name().ok().as_deref() == Some(name)));
+        if found.is_some() {
+            return found;
+        }
+        eprintln!("Input device '{}' not found, using default", name);
+    }
+    host.default_input_device()
+}
+
+fn probe_microphone(device_name: Option<&str>) -> Result<MicrophonePermission, AudioError> {
+    if microphone_denied_by_policy() {
+        return Ok(MicrophonePermission::Denied);
+    }
+    let Some(device) = find_input_device(&cpal::default_host(), device_name) else {
+        return Ok(MicrophonePermission::NoDevice);
+    };
+    #[cfg(target_os = "macos")]
+    match tcc::microphone_status() {
+        tcc::Status::Authorized => return Ok(MicrophonePermission::Granted),
+        tcc::Status::Denied | tcc::Status::Restricted => return Ok(MicrophonePermission::Denied),
+        // Opening a stream brings up the prompt
+        tcc::Status::NotDetermined => {}
+    }
+    let config = device
+        .default_input_config()
+        .map_err(|e| AudioError::ConfigError(e.to_string()))?;
+
+    let heard = Arc::new(AtomicBool::new(false));
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => probe_stream::<f32>(&device, &config.into(), heard.clone()),
+        SampleFormat::I16 => probe_stream::<i16>(&device, &config.into(), heard.clone()),
+        SampleFormat::U16 => probe_stream::<u16>(&device, &config.into(), heard.clone()),
+        format => {
+            return Err(AudioError::ConfigError(format!(
+                "Unsupported sample format: {:?}",
+                format
+            )))
+        }
+    }
+    .map_err(|e| AudioError::StreamError(e.to_string()))?;
+    stream
+        .play()
+        .map_err(|e| AudioError::StreamError(e.to_string()))?;
+    thread::sleep(PERMISSION_PROBE);
+    drop(stream);
+
+    if heard.load(Ordering::Relaxed) {
+        return Ok(MicrophonePermission::Granted);
+    }
+    // Unanswered counts as denied for now; the next check asks again
+    #[cfg(target_os = "macos")]
+    let permission = match tcc::microphone_status() {
+        tcc::Status::Authorized => MicrophonePermission::Granted,
+        _ => MicrophonePermission::Denied,
+    };
+    #[cfg(not(target_os = "macos"))]
+    let permission = MicrophonePermission::Silent;
+    Ok(permission)
+}
+
+/// Whether the user has let this app use the microphone, from
+/// `+[AVCaptureDevice authorizationStatusForMediaType:]` through the
+/// Objective-C runtime
+#[cfg(target_os = "macos")]
+mod tcc {
+    use std::ffi::{c_char, c_void};
+
+    #[link(name = "AVFoundation", kind = "framework")]
+    extern "C" {
+        static AVMediaTypeAudio: *const c_void;
+    }
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> *const c_void;
+        fn sel_registerName(name: *const c_char) -> *const c_void;
+        fn objc_msgSend();
+    }
+
+    /// `AVAuthorizationStatus`
+    pub enum Status {
+        NotDetermined,
+        Restricted,
+        Denied,
+        Authorized,
+    }
+
+    pub fn microphone_status() -> Status {
+        type Send = unsafe extern "C" fn(*const c_void, *const c_void, *const c_void) -> isize;
+        // SAFETY: objc_msgSend is called with the exact signature of the
+        // class method, on a class AVFoundation (linked above) provides
+        let status = unsafe {
+            let class = objc_getClass(c"AVCaptureDevice".as_ptr());
+            if class.is_null() {
+                return Status::NotDetermined;
+            }
+            let selector = sel_registerName(c"authorizationStatusForMediaType:".as_ptr());
+            let send: Send = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            send(class, selector, AVMediaTypeAudio)
+        };
+        match status {
+            1 => Status::Restricted,
+            2 => Status::Denied,
+            3 => Status::Authorized,
+            _ => Status::NotDetermined,
+        }
+    }
+}
+
+fn probe_stream<T: SizedSample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    heard: Arc<AtomicBool>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            if data.iter().any(|&s| s != T::EQUILIBRIUM) {
+                heard.store(true, Ordering::Relaxed);
+            }
+        },
+        |err| eprintln!("Stream error: {}", err),
+        None,
+    )
+}
+
+/// Windows keeps the privacy toggle in the registry; a denied app gets no
+/// stream at all rather than silence
+#[cfg(target_os = "windows")]
+fn microphone_denied_by_policy() -> bool {
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\microphone",
+            "/v",
+            "Value",
+        ])
+        .output();
+    matches!(output, Ok(o) if String::from_utf8_lossy(&o.stdout).contains("Deny"))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn microphone_denied_by_policy() -> bool {
+    false
+}
+
+/// Deep link to the OS microphone privacy pane, where there is one
+pub fn microphone_settings_url() -> Option<&'static str> {
+    if cfg!(target_os = "macos") {
+        Some("x-apple.systempreferences:com.apple.preference.security?Privacy_Microphone")
+    } else if cfg!(target_os = "windows") {
+        Some("ms-settings:privacy-microphone")
+    } else {
+        None
+    }
+}
+
+/// How to route system audio to an input on platforms that can't record
+/// it directly
+pub fn system_audio_guidance() -> Option<&'static str> {
+    if cfg!(target_os = "macos") {
+        Some("macOS can't record system audio directly. Install a loopback driver such as BlackHole, create a Multi-Output Device in Audio MIDI Setup with your speakers and BlackHole, make it the system output, then pick BlackHole here.")
+    } else if cfg!(target_os = "windows") {
+        None
+    } else {
+        Some("Route the system output to an input with a PulseAudio or PipeWire monitor source, then pick that input here.")
+    }
+}
+
+/// Write mono samples as 16-bit PCM
+pub fn write_wav(samples: &[f32], sample_rate: u32, path: &Path) -> Result<(), AudioError> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut writer = WavWriter::create(path, spec)?;
+
+    for &sample in samples {
+        let amplitude = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(amplitude)?;
+    }
+
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Read a WAV or FLAC file, by extension, as mono f32 samples
+pub fn read_audio(path: &Path) -> Result<(Vec<f32>, u32), AudioError> {
+    let mut reader = AudioReader::open(path)?;
+    let mut samples = Vec::with_capacity(reader.frames() as usize);
+    while reader.next_block(&mut samples)? {}
+    Ok((samples, reader.sample_rate()))
+}
+
+/// Like `read_audio`, but stops after the first `seconds`, so a long
+/// recording isn't decoded whole for a short clip of it
+pub fn read_audio_prefix(path: &Path, seconds: f64) -> Result<(Vec<f32>, u32), AudioError> {
+    let mut reader = AudioReader::open(path)?;
+    let limit = (seconds * reader.sample_rate() as f64) as usize;
+    let mut samples = Vec::with_capacity(limit.min(reader.frames() as usize));
+    while samples.len() < limit && reader.next_block(&mut samples)? {}
+    samples.truncate(limit);
+    Ok((samples, reader.sample_rate()))
+}
+
+/// Re-encode a saved recording in `format` next to the original, returning
+/// the new path. The original is left in place.
+pub fn transcode(source: &Path, format: AudioFormat) -> Result<PathBuf, AudioError> {
+    let dest = source.with_extension(format.extension());
+    if format == AudioFormat::Flac && AudioFormat::from_path(source) == AudioFormat::Wav {
+        // Straight from the 16-bit PCM so the FLAC is bit-exact
+        let mut reader = WavReader::open(source)?;
+        let spec = reader.spec();
+        if spec.channels == 1 && spec.bits_per_sample == 16 && spec.sample_format == hound::SampleFormat::Int {
+            let pcm: Vec<i16> = reader.samples::<i16>().collect::<Result<_, _>>()?;
+            encoder::write_flac(&pcm, spec.sample_rate, &dest)?;
+            return Ok(dest);
+        }
+    }
+    copy_scaled(&mut AudioReader::open(source)?, &dest, format, 1.0)?;
+    Ok(dest)
+}
+
+/// Decode a saved recording of either format to a 16-bit mono WAV at `dest`
+pub fn decode_to_wav(source: &Path, dest: &Path) -> Result<(), AudioError> {
+    copy_scaled(&mut AudioReader::open(source)?, dest, AudioFormat::Wav, 1.0)
+}
+
+/// Formats only read through ffmpeg, such as phones record in
+pub const FFMPEG_EXTENSIONS: [&str; 2] = ["mp3", "m4a"];
+
+/// Convert audio recorded elsewhere to a 16kHz mono WAV at `dest`,
+/// returning its duration. WAV and FLAC are read directly; the formats in
+/// `FFMPEG_EXTENSIONS` need the `ffmpeg` program.
+pub fn import_to_16khz_mono(
+    source: &Path,
+    dest: &Path,
+    quality: ResampleQuality,
+    ffmpeg: Option<&Path>,
+) -> Result<f64, AudioError> {
+    let extension = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if FFMPEG_EXTENSIONS.contains(&extension.as_str()) {
+        let ffmpeg = ffmpeg.ok_or(AudioError::DecoderMissing(extension))?;
+        return decode_with_ffmpeg(ffmpeg, source, dest);
+    }
+
+    let mut reader = AudioReader::open(source)?;
+    let mut writer = WavWriter::create(
+        dest,
+        WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        },
+    )?;
+    let mut resampler = Resampler::new(quality, reader.sample_rate(), 16000);
+    let mut block = Vec::new();
+    let mut output = Vec::new();
+    let mut written = 0usize;
+    let mut finished = false;
+    while !finished {
+        block.clear();
+        if reader.next_block(&mut block)? {
+            resampler.push_block(&block, &mut output);
+        } else {
+            resampler.finish(&mut output);
+            finished = true;
+        }
+        for value in output.drain(..) {
+            writer.write_sample((value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+            written += 1;
+        }
+    }
+    writer.finalize()?;
+    Ok(written as f64 / 16000.0)
+}
+
+/// Have ffmpeg decode, mix down and resample `source` into a 16kHz mono WAV
+fn decode_with_ffmpeg(ffmpeg: &Path, source: &Path, dest: &Path) -> Result<f64, AudioError> {
+    let output = std::process::Command::new(ffmpeg)
+        .args(["-nostdin", "-loglevel", "error", "-y", "-i"])
+        .arg(source)
+        .args(["-vn", "-ac", "1", "-ar", "16000", "-c:a", "pcm_s16le"])
+        .arg(dest)
+        .output()?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(dest);
+        return Err(AudioError::DecodeError(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    let reader = WavReader::open(dest)?;
+    Ok(reader.duration() as f64 / reader.spec().sample_rate as f64)
+}
+
+/// A saved recording as FLAC bytes, for embedding in a single-file export.
+/// FLAC recordings are read as stored.
+pub fn flac_bytes(source: &Path) -> Result<Vec<u8>, AudioError> {
+    if AudioFormat::from_path(source) == AudioFormat::Flac {
+        return Ok(std::fs::read(source)?);
+    }
+    let mut reader = AudioReader::open(source)?;
+    let mut samples = Vec::with_capacity(reader.frames() as usize);
+    let mut block = Vec::new();
+    while reader.next_block(&mut block)? {
+        samples.extend(block.drain(..).map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16));
+    }
+    Ok(encoder::encode_flac(&samples, reader.sample_rate()))
+}
+
+/// Loudness-normalize a saved recording into `dest`, in the format its
+/// extension names. Reads the source twice, once to measure and once to
+/// scale, rather than holding it.
+pub fn normalize_to(source: &Path, dest: &Path, target_lufs: f64) -> Result<(), AudioError> {
+    let mut reader = AudioReader::open(source)?;
+    let mut meter = LoudnessMeter::new(reader.sample_rate());
+    let mut block = Vec::new();
+    while reader.next_block(&mut block)? {
+        meter.push(&block);
+        block.clear();
+    }
+    let gain = meter.gain_to(target_lufs);
+    copy_scaled(&mut AudioReader::open(source)?, dest, AudioFormat::from_path(dest), gain)
+}
+
+/// Write everything left in `reader`, times `gain`, as 16-bit mono audio.
+/// WAV is written as it's read; FLAC is encoded in one go, so its samples
+/// are gathered as 16-bit first.
+fn copy_scaled(reader: &mut AudioReader, dest: &Path, format: AudioFormat, gain: f32) -> Result<(), AudioError> {
+    let pcm = |s: f32| ((s * gain).clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+    let mut block = Vec::new();
+    match format {
+        AudioFormat::Wav => {
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate: reader.sample_rate(),
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut writer = WavWriter::create(dest, spec)?;
+            while reader.next_block(&mut block)? {
+                for sample in block.drain(..) {
+                    writer.write_sample(pcm(sample))?;
+                }
+            }
+            writer.finalize()?;
+        }
+        AudioFormat::Flac => {
+            let mut samples = Vec::with_capacity(reader.frames() as usize);
+            while reader.next_block(&mut block)? {
+                samples.extend(block.drain(..).map(pcm));
+            }
+            encoder::write_flac(&samples, reader.sample_rate(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Interleaved samples read from a WAV per block, per channel
+const READ_BLOCK_FRAMES: usize = 65536;
+
+/// A saved recording read as mono f32 a block at a time, so long recordings
+/// never have to be in memory whole. Shared by transcription prep, waveform
+/// peaks and exports.
+pub struct AudioReader {
+    source: AudioSource,
+    sample_rate: u32,
+    frames: u64,
+}
+
+enum AudioSource {
+    Wav {
+        reader: WavReader<std::io::BufReader<std::fs::File>>,
+        spec: WavSpec,
+        /// Reused for each block before it is mixed down
+        interleaved: Vec<f32>,
+    },
+    Flac(encoder::FlacReader),
+}
+
+impl AudioReader {
+    /// Open a WAV or FLAC file, by extension
+    pub fn open(path: &Path) -> Result<Self, AudioError> {
+        match AudioFormat::from_path(path) {
+            AudioFormat::Wav => {
+                let reader = WavReader::open(path)?;
+                let spec = reader.spec();
+                Ok(Self {
+                    sample_rate: spec.sample_rate,
+                    frames: reader.duration() as u64,
+                    source: AudioSource::Wav { reader, spec, interleaved: Vec::new() },
+                })
+            }
+            AudioFormat::Flac => {
+                let reader = encoder::FlacReader::open(path)?;
+                Ok(Self {
+                    sample_rate: reader.sample_rate(),
+                    frames: reader.total_samples(),
+                    source: AudioSource::Flac(reader),
+                })
+            }
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length in mono samples, or 0 if the file doesn't say
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Append the next block of mono samples to `out`. Returns false once
+    /// there are none left.
+    pub fn next_block(&mut self, out: &mut Vec<f32>) -> Result<bool, AudioError> {
+        match &mut self.source {
+            AudioSource::Wav { reader, spec, interleaved } => {
+                interleaved.clear();
+                let wanted = READ_BLOCK_FRAMES * spec.channels.max(1) as usize;
+                match spec.sample_format {
+                    hound::SampleFormat::Float => {
+                        for sample in reader.samples::<f32>().take(wanted) {
+                            interleaved.push(sample?);
+                        }
+                    }
+                    hound::SampleFormat::Int => {
+                        let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                        for sample in reader.samples::<i32>().take(wanted) {
+                            interleaved.push(sample? as f32 / scale);
+                        }
+                    }
+                }
+                if interleaved.is_empty() {
+                    return Ok(false);
+                }
+                mix_down(interleaved, spec.channels as usize, out);
+                Ok(true)
+            }
+            AudioSource::Flac(reader) => Ok(reader.next_block(out)?),
+        }
+    }
+}
+
+/// Length of each entry in `ChannelActivity::loudest`
+const ACTIVITY_FRAME_SECONDS: f32 = 0.1;
+
+/// A channel quieter than this over a frame doesn't count as hearing anything
+const ACTIVITY_FLOOR_DBFS: f32 = -50.0;
+
+/// How each channel of a multichannel capture was used and which heard the
+/// most over time. With a microphone array, the loudest channel follows
+/// whoever is speaking, a spatial cue for telling speakers apart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelActivity {
+    pub channels: u16,
+    /// Weight each channel had in the saved mix; empty if they were averaged
+    pub weights: Vec<f32>,
+    /// Whole-recording level of each channel
+    pub rms_dbfs: Vec<f32>,
+    pub frame_seconds: f32,
+    /// Loudest channel in each frame, `None` where every channel is quiet
+    pub loudest: Vec<Option<u8>>,
+}
+
+impl ChannelActivity {
+    /// Keep only the frames between `start` and `end` seconds, after the
+    /// audio itself was trimmed to them
+    pub fn trim(&mut self, start: f64, end: f64) {
+        let frame = |seconds: f64| ((seconds / self.frame_seconds as f64).round().max(0.0) as usize).min(self.loudest.len());
+        let (start, end) = (frame(start), frame(end));
+        self.loudest.truncate(end.max(start));
+        self.loudest.drain(..start);
+    }
+}
+
+/// Measures each channel while a capture is converted
+struct ChannelMeter {
+    channels: usize,
+    frame_len: usize,
+    sum_squares: Vec<f64>,
+    frame_sums: Vec<f64>,
+    frame_filled: usize,
+    samples: u64,
+    loudest: Vec<Option<u8>>,
+}
+
+impl ChannelMeter {
+    fn new(channels: usize, sample_rate: u32) -> Self {
+        Self {
+            channels,
+            frame_len: ((sample_rate as f32 * ACTIVITY_FRAME_SECONDS) as usize).max(1),
+            sum_squares: vec![0.0; channels],
+            frame_sums: vec![0.0; channels],
+            frame_filled: 0,
+            samples: 0,
+            loudest: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, interleaved: &[f32]) {
+        for frame in interleaved.chunks_exact(self.channels) {
+            for (c, &sample) in frame.iter().enumerate() {
+                let square = sample as f64 * sample as f64;
+                self.sum_squares[c] += square;
+                self.frame_sums[c] += square;
+            }
+            self.samples += 1;
+            self.frame_filled += 1;
+            if self.frame_filled == self.frame_len {
+                self.close_frame();
+            }
+        }
+    }
+
+    fn close_frame(&mut self) {
+        let floor = 10f64.powf(ACTIVITY_FLOOR_DBFS as f64 / 10.0) * self.frame_filled as f64;
+        let loudest = self
+            .frame_sums
+            .iter()
+            .enumerate()
+            .filter(|(_, &sum)| sum > floor)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(c, _)| c.min(u8::MAX as usize) as u8);
+        self.loudest.push(loudest);
+        self.frame_sums.iter_mut().for_each(|sum| *sum = 0.0);
+        self.frame_filled = 0;
+    }
+
+    fn finish(mut self, weights: Option<&[f32]>) -> ChannelActivity {
+        if self.frame_filled > 0 {
+            self.close_frame();
+        }
+        let samples = self.samples.max(1) as f64;
+        ChannelActivity {
+            channels: self.channels as u16,
+            weights: weights.map(|w| w.to_vec()).unwrap_or_default(),
+            rms_dbfs: self
+                .sum_squares
+                .iter()
+                .map(|&sum| ((10.0 * (sum / samples).log10()) as f32).max(LEVEL_FLOOR_DBFS))
+                .collect(),
+            frame_seconds: ACTIVITY_FRAME_SECONDS,
+            loudest: self.loudest,
+        }
+    }
+}
+
+/// How a finished capture becomes the 16kHz mono file that is kept. Holds
+/// nothing of the recorder's, so it can run on any thread.
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    quality: ResampleQuality,
+    denoise: bool,
+    /// Also write the teacher and student channels out on their own
+    dual: bool,
+    /// How much each of the capture's channels counts in the mix
+    weights: Option<Vec<f32>>,
+    /// Also keep the capture itself, at this quality
+    archive: Option<ArchiveQuality>,
+}
+
+/// A conversion's output besides the audio itself
+#[derive(Debug, Clone)]
+pub struct Converted {
+    pub duration: f64,
+    /// For captures with more than one channel
+    pub channels: Option<ChannelActivity>,
+}
+
+impl Conversion {
+    /// Convert `capture` to `path` and remove it, returning the duration.
+    /// `progress` is called with the fraction done after each block.
+    pub fn run(&self, capture: &Path, path: &Path, mut progress: impl FnMut(f32)) -> Result<Converted, AudioError> {
+        let lanes = self.lane_paths(path);
+        let archive = self.archive.zip(self.archive_path(path));
+        let mut passes = if lanes.is_some() { 3.0 } else { 1.0 };
+        if archive.is_some() {
+            passes += 1.0;
+        }
+        let convert = |dest: &Path,
+                       weights: Option<&[f32]>,
+                       meter: Option<&mut Option<ChannelMeter>>,
+                       progress: &mut dyn FnMut(f32)| {
+            convert_to_16khz_mono(capture, dest, self.quality, self.denoise, weights, meter, progress)
+        };
+        let mut meter = None;
+        let duration = convert(path, self.weights.as_deref(), Some(&mut meter), &mut |p| progress(p / passes))?;
+        if let Some((teacher, student)) = &lanes {
+            convert(teacher, Some(&[1.0, 0.0]), None, &mut |p| progress((1.0 + p) / passes))?;
+            convert(student, Some(&[0.0, 1.0]), None, &mut |p| progress((2.0 + p) / passes))?;
+        }
+        if let Some((archive, dest)) = &archive {
+            archive_capture(capture, dest, *archive, self.quality, &mut |p| {
+                progress((passes - 1.0 + p) / passes)
+            })?;
+        }
+        std::fs::remove_file(capture)?;
+        Ok(Converted {
+            duration,
+            channels: meter.map(|m| m.finish(self.weights.as_deref())),
+        })
+    }
+
+    /// The same conversion writing only the mixed file, without the
+    /// teacher and student files or an archive copy
+    pub fn mixed_only(self) -> Self {
+        Self { dual: false, archive: None, ..self }
+    }
+
+    /// Where `run` writes the full-quality copy of a capture converted to
+    /// `path`, if it keeps one
+    pub fn archive_path(&self, path: &Path) -> Option<PathBuf> {
+        self.archive.map(|_| path.with_extension("archive.wav"))
+    }
+
+    /// Where `run` writes the teacher and student channels of a
+    /// dual-channel capture converted to `path`
+    pub fn lane_paths(&self, path: &Path) -> Option<(PathBuf, PathBuf)> {
+        self.dual
+            .then(|| (path.with_extension("teacher.wav"), path.with_extension("student.wav")))
+    }
+}
+
+/// Frames read, mixed down and resampled per step of a conversion
+const CONVERT_BLOCK_FRAMES: usize = 16384;
+
+/// Stream a capture file into a 24-bit copy with all its channels, resampled
+/// to 48kHz for `ArchiveQuality::Studio`
+fn archive_capture(
+    source: &Path,
+    dest: &Path,
+    archive: ArchiveQuality,
+    quality: ResampleQuality,
+    progress: &mut dyn FnMut(f32),
+) -> Result<(), AudioError> {
+    let mut reader = WavReader::open(source)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let total_frames = reader.duration() as usize;
+    let sample_rate = match archive {
+        ArchiveQuality::Original => spec.sample_rate,
+        ArchiveQuality::Studio => STUDIO_SAMPLE_RATE,
+    };
+    let mut writer = WavWriter::create(
+        dest,
+        WavSpec {
+            channels: channels as u16,
+            sample_rate,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        },
+    )?;
+
+    // One resampler per channel, since they only take mono
+    let mut resamplers: Option<Vec<Resampler>> = (sample_rate != spec.sample_rate)
+        .then(|| (0..channels).map(|_| Resampler::new(quality, spec.sample_rate, sample_rate)).collect());
+    let mut lanes = vec![Vec::new(); channels];
+    let mut resampled = vec![Vec::new(); channels];
+    let mut write = |resampled: &mut Vec<Vec<f32>>| -> Result<(), AudioError> {
+        let frames = resampled.iter().map(Vec::len).min().unwrap_or(0);
+        for i in 0..frames {
+            for lane in resampled.iter() {
+                writer.write_sample((lane[i].clamp(-1.0, 1.0) * INT24_MAX) as i32)?;
+            }
+        }
+        for lane in resampled.iter_mut() {
+            lane.drain(..frames);
+        }
+        Ok(())
+    };
+
+    let mut samples = reader.samples::<f32>();
+    let mut frames_read = 0;
+    loop {
+        for lane in lanes.iter_mut() {
+            lane.clear();
+        }
+        for (i, sample) in samples.by_ref().take(CONVERT_BLOCK_FRAMES * channels).enumerate() {
+            lanes[i % channels].push(sample?);
+        }
+        if lanes[0].is_empty() {
+            break;
+        }
+        frames_read += lanes[0].len();
+        match resamplers.as_mut() {
+            Some(resamplers) => {
+                for ((resampler, lane), out) in resamplers.iter_mut().zip(&lanes).zip(resampled.iter_mut()) {
+                    resampler.push_block(lane, out);
+                }
+            }
+            None => {
+                for (lane, out) in lanes.iter().zip(resampled.iter_mut()) {
+                    out.extend_from_slice(lane);
+                }
+            }
+        }
+        write(&mut resampled)?;
+        if total_frames > 0 {
+            progress((frames_read as f32 / total_frames as f32).min(1.0));
+        }
+    }
+    if let Some(resamplers) = resamplers.as_mut() {
+        for (resampler, out) in resamplers.iter_mut().zip(resampled.iter_mut()) {
+            resampler.finish(out);
+        }
+    }
+    write(&mut resampled)?;
+
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Stream a capture file into a 16kHz mono WAV without loading it into memory,
+/// optionally denoising it on the way. `weights` mixes the channels
+/// unevenly, or picks out one. A multichannel capture's levels are measured
+/// into `meter` if one is passed.
+fn convert_to_16khz_mono(
+    source: &Path,
+    dest: &Path,
+    quality: ResampleQuality,
+    denoise: bool,
+    weights: Option<&[f32]>,
+    meter: Option<&mut Option<ChannelMeter>>,
+    progress: &mut dyn FnMut(f32),
+) -> Result<f64, AudioError> {
+    let mut reader = WavReader::open(source)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let total_frames = reader.duration() as usize;
+    let mut meter = meter.filter(|_| channels > 1).map(|m| m.insert(ChannelMeter::new(channels, spec.sample_rate)));
+
+    let mut writer = WavWriter::create(
+        dest,
+        WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        },
+    )?;
+
+    let mut resampler = Resampler::new(quality, spec.sample_rate, 16000);
+    let mut gate = denoise.then(SpectralGate::default);
+    let mut output = Vec::new();
+    let mut denoised = Vec::new();
+    let mut written = 0usize;
+    let mut write = |output: &mut Vec<f32>, finished: bool| -> Result<(), AudioError> {
+        let output = match gate.as_mut() {
+            Some(gate) => {
+                for value in output.drain(..) {
+                    gate.push(value, &mut denoised);
+                }
+                if finished {
+                    gate.finish(&mut denoised);
+                }
+                &mut denoised
+            }
+            None => output,
+        };
+        for value in output.drain(..) {
+            writer.write_sample((value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+            written += 1;
+        }
+        Ok(())
+    };
+
+    let mut samples = reader.samples::<f32>();
+    let mut block = Vec::with_capacity(CONVERT_BLOCK_FRAMES * channels);
+    let mut mono = Vec::with_capacity(CONVERT_BLOCK_FRAMES);
+    let mut frames_read = 0;
+    loop {
+        block.clear();
+        for sample in samples.by_ref().take(CONVERT_BLOCK_FRAMES * channels) {
+            block.push(sample?);
+        }
+        if block.is_empty() {
+            break;
+        }
+        if let Some(meter) = meter.as_deref_mut() {
+            meter.push(&block);
+        }
+        mono.clear();
+        match weights {
+            Some(weights) => mix_weighted(&block, channels, weights, &mut mono),
+            None => mix_down(&block, channels, &mut mono),
+        }
+
+        resampler.push_block(&mono, &mut output);
+        write(&mut output, false)?;
+
+        frames_read += mono.len();
+        if total_frames > 0 {
+            progress((frames_read as f32 / total_frames as f32).min(1.0));
+        }
+    }
+    resampler.finish(&mut output);
+    write(&mut output, true)?;
+
+    writer.finalize()?;
+    Ok(written as f64 / 16000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stereo_wav_transcodes_to_mono_flac() {
+        let dir = std::env::temp_dir().join(format!("transcode-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("stereo.wav");
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 16_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&source, spec).unwrap();
+        let frames = 5_001;
+        for i in 0..frames {
+            let left = ((i as f32 * 0.01).sin() * 8_000.0) as i16;
+            writer.write_sample(left).unwrap();
+            writer.write_sample(-left / 2).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let expected = read_audio(&source).unwrap().0;
+        let flac = transcode(&source, AudioFormat::Flac).unwrap();
+        let (decoded, rate) = read_audio(&flac).unwrap();
+        assert_eq!(rate, 16_000);
+        assert_eq!(decoded.len(), frames);
+        for (got, want) in decoded.iter().zip(&expected) {
+            assert!((got - want).abs() < 1.0 / 8_000.0, "{} vs {}", got, want);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prefix_stops_at_the_requested_length() {
+        let dir = std::env::temp_dir().join(format!("prefix-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("long.wav");
+        let samples: Vec<f32> = (0..48_000).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        write_wav(&samples, 16_000, &path).unwrap();
+
+        let (prefix, rate) = read_audio_prefix(&path, 1.25).unwrap();
+        assert_eq!(rate, 16_000);
+        assert_eq!(prefix.len(), 20_000);
+        assert_eq!(prefix, read_audio(&path).unwrap().0[..20_000]);
+        assert_eq!(read_audio_prefix(&path, 10.0).unwrap().0.len(), 48_000);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::db::Table;
+use crate::poison::LockExt;
+use std::sync::Mutex;
+
+// Answers the frontend polls for, kept between calls so repeat polls don't
+// wait behind whatever holds the database lock. An answer is good until a
+// row in its table is written; see `Table::version`.
+
+pub struct Cached<T> {
+    table: Table,
+    entry: Mutex<Option<(u64, T)>>,
+}
+
+impl<T: Clone> Cached<T> {
+    pub fn new(table: Table) -> Self {
+        Self {
+            table,
+            entry: Mutex::new(None),
+        }
+    }
+
+    /// The cached answer, unless its table has been written since
+    pub fn get(&self) -> Option<T> {
+        let version = self.table.version();
+        self.entry
+            .lock_or_recover()
+            .as_ref()
+            .filter(|(cached_at, _)| *cached_at == version)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Work the answer out with `load` and keep it. Call with the database
+    /// locked, so no write lands between noting the version and reading.
+    pub fn load<E>(&self, load: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        let version = self.table.version();
+        let value = load()?;
+        *self.entry.lock_or_recover() = Some((version, value.clone()));
+        Ok(value)
+    }
+}
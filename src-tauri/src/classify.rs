@@ -0,0 +1,71 @@
+use crate::whisper::TranscriptSegment;
+use serde::Serialize;
+
+/// Activity tags applied automatically; a recording carries at most one
+pub const ACTIVITY_TAGS: &[&str] = &["read-aloud", "discussion", "lecture-heavy", "silent"];
+
+/// Below this fraction of the recording containing speech it's silent/noise
+const MIN_TALK_RATIO: f64 = 0.1;
+/// A pause this long between segments is counted as a change of speaker
+const TURN_GAP_SECONDS: f64 = 1.5;
+/// Turns per minute above which a recording reads as a discussion
+const DISCUSSION_TURNS_PER_MINUTE: f64 = 4.0;
+/// Words per minute of speech typical of one person reading continuously
+const READ_ALOUD_MIN_WPM: f64 = 90.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivityFeatures {
+    /// Fraction of the recording covered by transcript segments
+    pub talk_ratio: f64,
+    /// Words per minute of speech time
+    pub speech_density: f64,
+    /// Estimated speaker turns per minute, from pauses between segments
+    pub turns_per_minute: f64,
+}
+
+impl ActivityFeatures {
+    pub fn from_segments(segments: &[TranscriptSegment], duration_seconds: f64) -> Self {
+        let speech_seconds: f64 = segments.iter().map(|s| (s.end - s.start).max(0.0)).sum();
+        let words: usize = segments.iter().map(|s| s.text.split_whitespace().count()).sum();
+        let turns = segments
+            .windows(2)
+            .filter(|pair| pair[1].start - pair[0].end >= TURN_GAP_SECONDS)
+            .count();
+
+        let minutes = duration_seconds / 60.0;
+        Self {
+            talk_ratio: if duration_seconds > 0.0 {
+                (speech_seconds / duration_seconds).min(1.0)
+            } else {
+                0.0
+            },
+            speech_density: if speech_seconds > 0.0 {
+                words as f64 / (speech_seconds / 60.0)
+            } else {
+                0.0
+            },
+            turns_per_minute: if minutes > 0.0 { turns as f64 / minutes } else { 0.0 },
+        }
+    }
+}
+
+/// Activity tag for a recording. Until diarization exists, speaker turns
+/// are estimated from pauses, so a passage attached to the recording is the
+/// strongest read-aloud signal.
+pub fn classify(features: &ActivityFeatures, has_passage: bool) -> &'static str {
+    if features.talk_ratio < MIN_TALK_RATIO {
+        "silent"
+    } else if features.turns_per_minute >= DISCUSSION_TURNS_PER_MINUTE {
+        "discussion"
+    } else if has_passage || (features.speech_density >= READ_ALOUD_MIN_WPM && features.turns_per_minute < 1.0) {
+        "read-aloud"
+    } else {
+        "lecture-heavy"
+    }
+}
+
+/// Replace any previous activity tag with `tag`, leaving manual tags alone
+pub fn apply_tag(tags: &mut Vec<String>, tag: &str) {
+    tags.retain(|t| !ACTIVITY_TAGS.contains(&t.as_str()));
+    tags.push(tag.to_string());
+}
@@ -1,6 +1,126 @@
-use rusqlite::{Connection, Result as SqliteResult};
+use crate::audio::{AudioQuality, CaptureMarker, ChannelActivity};
+use crate::encoder::AudioFormat;
+use crate::metrics::FluencyMetrics;
+use crate::rubric::Rubric;
+use crate::speakers::{self, SpeakerStats};
+use crate::voiceprint::Voiceprint;
+use crate::waveform::Waveform;
+use crate::whisper::TranscriptSegment;
+use rusqlite::{Connection, Result as SqliteResult, Row};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Dirty bits for metadata fields edited after the initial sync
+pub const DIRTY_TAGS: u32 = 1 << 0;
+pub const DIRTY_NOTES: u32 = 1 << 1;
+pub const DIRTY_REVIEW_STATUS: u32 = 1 << 2;
+pub const DIRTY_SPEAKER_LABELS: u32 = 1 << 3;
+pub const DIRTY_TRANSCRIPT: u32 = 1 << 4;
+
+/// Server rejections after which a recording is left for an admin to retry
+pub const MAX_SYNC_ATTEMPTS: i64 = 5;
+
+/// Activity history entries kept; older ones are dropped as new ones land
+pub const JOB_HISTORY_LIMIT: i64 = 2000;
+
+/// Attempts at processing a queued recording before it's left failed
+pub const MAX_JOB_ATTEMPTS: i64 = 4;
+
+const RECORDING_COLUMNS: &str = "id, student_id, audio_path, transcript, duration_seconds, recorded_at, synced,
+     tags, notes, review_status, speaker_labels, dirty_fields, confidential, expires_at, audio_purged,
+     reference_passage, audio_uploaded_at, audio_format, audio_quality, teacher_audio_path, student_audio_path, sequence, guest,
+     archive_audio_path, transcript_language, detected_language, session_id, assignment_id, teacher_only,
+     student_speaker, translate, translation, unmasked_transcript, unmasked_translation";
+
+/// How new recording IDs are generated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdScheme {
+    /// Random
+    #[default]
+    V4,
+    /// Led by a millisecond timestamp, so IDs sort in the order they were made
+    V7,
+}
+
+/// Timestamp of the last UUIDv7 handed out, so IDs made within the same
+/// millisecond, or after the clock stepped back, still come out in order
+static LAST_V7_MILLIS: AtomicU64 = AtomicU64::new(0);
+
+/// Tables that cached reads in the app are built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Recordings,
+    /// Device settings and the config layers under them
+    Settings,
+}
+
+/// Bumped on every write to each cached table, by any connection, so a
+/// cache can tell it's stale without taking the database lock
+static RECORDINGS_VERSION: AtomicU64 = AtomicU64::new(0);
+static SETTINGS_VERSION: AtomicU64 = AtomicU64::new(0);
+
+impl Table {
+    fn counter(self) -> &'static AtomicU64 {
+        match self {
+            Table::Recordings => &RECORDINGS_VERSION,
+            Table::Settings => &SETTINGS_VERSION,
+        }
+    }
+
+    fn of(name: &str) -> Option<Self> {
+        match name {
+            "recordings" => Some(Table::Recordings),
+            "settings" | "config_layers" => Some(Table::Settings),
+            _ => None,
+        }
+    }
+
+    pub fn version(self) -> u64 {
+        self.counter().load(Ordering::SeqCst)
+    }
+
+    fn touch(self) {
+        self.counter().fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl IdScheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IdScheme::V4 => "v4",
+            IdScheme::V7 => "v7",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "v4" => Some(IdScheme::V4),
+            "v7" => Some(IdScheme::V7),
+            _ => None,
+        }
+    }
+
+    pub fn new_id(self) -> String {
+        match self {
+            IdScheme::V4 => uuid::Uuid::new_v4().to_string(),
+            IdScheme::V7 => {
+                let now = chrono::Utc::now().timestamp_millis().max(0) as u64;
+                let previous = LAST_V7_MILLIS
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(now.max(last + 1)))
+                    .unwrap_or(now);
+                let millis = now.max(previous + 1);
+                let mut random = [0u8; 10];
+                random.copy_from_slice(&uuid::Uuid::new_v4().as_bytes()[6..]);
+                uuid::Builder::from_unix_timestamp_millis(millis, &random)
+                    .into_uuid()
+                    .to_string()
+            }
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Recording {
@@ -11,6 +131,390 @@ pub struct Recording {
     pub duration_seconds: f64,
     pub recorded_at: String,
     pub synced: bool,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    pub review_status: String,
+    pub speaker_labels: HashMap<String, String>,
+    pub dirty_fields: u32,
+    /// Local only: never synced, exported or shared
+    pub confidential: bool,
+    /// RFC 3339 UTC timestamp after which the audio is purged
+    pub expires_at: Option<String>,
+    pub audio_purged: bool,
+    /// Text the student was asked to read, if any
+    pub reference_passage: Option<String>,
+    /// When the server confirmed it holds an identical copy of the audio
+    pub audio_uploaded_at: Option<String>,
+    pub audio_format: AudioFormat,
+    /// Clipping or near-silence noticed while capturing
+    pub audio_quality: AudioQuality,
+    /// The teacher's and student's channels on their own, for recordings
+    /// made in dual-channel mode. `audio_path` holds both mixed.
+    pub teacher_audio_path: Option<String>,
+    pub student_audio_path: Option<String>,
+    /// Position in the order recordings were made on this device, assigned
+    /// on first save and never reused. 0 until then.
+    pub sequence: i64,
+    /// Made in guest mode, for a demo or a mic test. Also confidential, and
+    /// deleted outright a day after it was recorded.
+    pub guest: bool,
+    /// Full-quality copy of the capture, kept when archival quality is on.
+    /// Always WAV, and left in place when uploaded audio is removed.
+    pub archive_audio_path: Option<String>,
+    /// Language code the transcript was decoded as
+    pub transcript_language: Option<String>,
+    /// Language heard in the audio, when it could be detected. A recording
+    /// whose two languages differ is flagged for re-transcription.
+    pub detected_language: Option<String>,
+    /// Shared by the utterances of one push-to-talk session
+    pub session_id: Option<String>,
+    /// Assignment the recording was made for; its passage can't be changed
+    pub assignment_id: Option<String>,
+    /// Only the teacher was heard, so it isn't scored as the student's reading
+    pub teacher_only: bool,
+    /// Speaker key of the student, whose segments alone are scored; with
+    /// none, every segment is
+    pub student_speaker: Option<String>,
+    /// Also transcribe into English, for a session held in another language
+    pub translate: bool,
+    /// English translation of the transcript, when `translate` is set; the
+    /// transcript itself stays in the language spoken
+    pub translation: Option<String>,
+    /// The transcript as heard, before a mask stage starred anything out;
+    /// kept on the device and never synced
+    pub unmasked_transcript: Option<String>,
+    /// The translation before masking, kept the same way
+    pub unmasked_translation: Option<String>,
+}
+
+impl Recording {
+    pub fn new(id: String, student_id: String, audio_path: String, duration_seconds: f64) -> Self {
+        Self {
+            id,
+            student_id,
+            audio_path,
+            transcript: None,
+            duration_seconds,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            synced: false,
+            tags: Vec::new(),
+            notes: None,
+            review_status: "unreviewed".to_string(),
+            speaker_labels: HashMap::new(),
+            dirty_fields: 0,
+            confidential: false,
+            expires_at: None,
+            audio_purged: false,
+            reference_passage: None,
+            audio_uploaded_at: None,
+            audio_format: AudioFormat::Wav,
+            audio_quality: AudioQuality::Ok,
+            teacher_audio_path: None,
+            student_audio_path: None,
+            sequence: 0,
+            guest: false,
+            archive_audio_path: None,
+            transcript_language: None,
+            detected_language: None,
+            session_id: None,
+            assignment_id: None,
+            teacher_only: false,
+            student_speaker: None,
+            translate: false,
+            translation: None,
+            unmasked_transcript: None,
+            unmasked_translation: None,
+        }
+    }
+
+    /// Whether the audio was heard as a different language from the one it
+    /// was transcribed in
+    pub fn language_mismatch(&self) -> bool {
+        matches!(
+            (&self.transcript_language, &self.detected_language),
+            (Some(transcribed), Some(detected)) if transcribed != detected
+        )
+    }
+
+    /// Every audio file kept for this recording
+    pub fn audio_files(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.audio_path.as_str())
+            .chain(self.teacher_audio_path.as_deref())
+            .chain(self.student_audio_path.as_deref())
+            .chain(self.archive_audio_path.as_deref())
+    }
+
+    fn from_row(row: &Row) -> SqliteResult<Self> {
+        let tags: Option<String> = row.get(7)?;
+        let speaker_labels: Option<String> = row.get(10)?;
+        Ok(Self {
+            id: row.get(0)?,
+            student_id: row.get(1)?,
+            audio_path: row.get(2)?,
+            transcript: row.get(3)?,
+            duration_seconds: row.get(4)?,
+            recorded_at: row.get(5)?,
+            synced: row.get::<_, i32>(6)? != 0,
+            tags: tags
+                .and_then(|t| serde_json::from_str(&t).ok())
+                .unwrap_or_default(),
+            notes: row.get(8)?,
+            review_status: row
+                .get::<_, Option<String>>(9)?
+                .unwrap_or_else(|| "unreviewed".to_string()),
+            speaker_labels: speaker_labels
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            dirty_fields: row.get::<_, Option<u32>>(11)?.unwrap_or(0),
+            confidential: row.get::<_, Option<i32>>(12)?.unwrap_or(0) != 0,
+            expires_at: row.get(13)?,
+            audio_purged: row.get::<_, Option<i32>>(14)?.unwrap_or(0) != 0,
+            reference_passage: row.get(15)?,
+            audio_uploaded_at: row.get(16)?,
+            audio_format: row
+                .get::<_, Option<String>>(17)?
+                .as_deref()
+                .and_then(AudioFormat::parse)
+                .unwrap_or_default(),
+            audio_quality: row
+                .get::<_, Option<String>>(18)?
+                .as_deref()
+                .and_then(AudioQuality::parse)
+                .unwrap_or_default(),
+            teacher_audio_path: row.get(19)?,
+            student_audio_path: row.get(20)?,
+            sequence: row.get::<_, Option<i64>>(21)?.unwrap_or(0),
+            guest: row.get::<_, Option<i32>>(22)?.unwrap_or(0) != 0,
+            archive_audio_path: row.get(23)?,
+            transcript_language: row.get(24)?,
+            detected_language: row.get(25)?,
+            session_id: row.get(26)?,
+            assignment_id: row.get(27)?,
+            teacher_only: row.get::<_, Option<i32>>(28)?.unwrap_or(0) != 0,
+            student_speaker: row.get(29)?,
+            translate: row.get::<_, Option<i32>>(30)?.unwrap_or(0) != 0,
+            translation: row.get(31)?,
+            unmasked_transcript: row.get(32)?,
+            unmasked_translation: row.get(33)?,
+        })
+    }
+}
+
+/// Scored fluency assessment for a recording read from a passage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assessment {
+    pub recording_id: String,
+    /// Every word counted as transcribed
+    pub metrics: FluencyMetrics,
+    /// With low-confidence words and likely hallucinations discounted, when
+    /// confidence weighting is on. The level is scored from these.
+    pub adjusted: Option<FluencyMetrics>,
+    pub rubric_name: Option<String>,
+    pub level: Option<String>,
+    pub scored_at: String,
+}
+
+impl Assessment {
+    /// The metrics the level was scored from
+    pub fn scored_metrics(&self) -> &FluencyMetrics {
+        self.adjusted.as_ref().unwrap_or(&self.metrics)
+    }
+}
+
+/// Earlier text of a segment, kept whenever it is corrected
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentRevision {
+    pub segment_index: usize,
+    pub previous_text: String,
+    pub new_text: String,
+    pub correction_audio_path: Option<String>,
+    pub revised_at: String,
+}
+
+/// A transcript as it was before the recording was transcribed again
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptRevision {
+    pub transcript: String,
+    pub language: Option<String>,
+    pub segments: Vec<TranscriptSegment>,
+    /// How the new transcript was made, such as "model small.en"
+    pub replaced_by: Option<String>,
+    pub replaced_at: String,
+}
+
+/// A moment the teacher flagged while recording
+#[derive(Debug, Clone, Serialize)]
+pub struct Marker {
+    pub id: i64,
+    pub recording_id: String,
+    /// Seconds from the start of the recording's audio
+    pub offset_seconds: f64,
+    pub label: String,
+    pub created_at: String,
+}
+
+/// What a new term starts without: names given to speakers, students'
+/// enrolled voices and vocabularies, and assignments the server has been
+/// told about
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RosterCounts {
+    pub speaker_names: usize,
+    pub voiceprints: usize,
+    pub vocabularies: usize,
+    pub assignments: usize,
+}
+
+/// Recordings still waiting on something, by state
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BacklogCounts {
+    pub awaiting_transcription: usize,
+    pub awaiting_review: usize,
+    pub awaiting_sync: usize,
+    pub failed_permanently: usize,
+    /// `recorded_at` of the oldest recording in any of these states
+    pub oldest_recorded_at: Option<String>,
+}
+
+/// One change to a setting in any layer, kept so it can be audited or undone
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingChange {
+    pub id: i64,
+    pub key: String,
+    /// "device", "classroom" or "org"
+    pub layer: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    /// "user", "remote", "provisioning" or "rollback"
+    pub source: String,
+    pub changed_at: String,
+}
+
+/// Kind of background or user-started work shown in the activity history
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Save, transcribe and sync right after a recording stops
+    Processing,
+    /// Re-transcribing a saved recording
+    Transcription,
+    ModelLoad,
+    /// Fetching a model from the catalog
+    Download,
+    Sync,
+    /// Retention purges and audio offload
+    Maintenance,
+    /// The nightly email to the teacher
+    Digest,
+}
+
+impl JobKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobKind::Processing => "processing",
+            JobKind::Transcription => "transcription",
+            JobKind::ModelLoad => "model_load",
+            JobKind::Download => "download",
+            JobKind::Sync => "sync",
+            JobKind::Maintenance => "maintenance",
+            JobKind::Digest => "digest",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "processing" => Some(JobKind::Processing),
+            "transcription" => Some(JobKind::Transcription),
+            "model_load" => Some(JobKind::ModelLoad),
+            "download" => Some(JobKind::Download),
+            "sync" => Some(JobKind::Sync),
+            "maintenance" => Some(JobKind::Maintenance),
+            "digest" => Some(JobKind::Digest),
+            _ => None,
+        }
+    }
+}
+
+/// One finished job in the activity history
+#[derive(Debug, Clone, Serialize)]
+pub struct JobEntry {
+    pub id: i64,
+    pub kind: JobKind,
+    /// Recording the job worked on, if it was about one
+    pub recording_id: Option<String>,
+    pub started_at: String,
+    pub duration_ms: i64,
+    pub succeeded: bool,
+    /// Summary on success, the error on failure
+    pub detail: Option<String>,
+}
+
+/// Narrows the activity history; empty fields match everything
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct JobFilter {
+    pub kind: Option<JobKind>,
+    pub recording_id: Option<String>,
+    pub failed_only: bool,
+    /// RFC 3339 timestamp; only jobs started at or after it
+    pub since: Option<String>,
+}
+
+/// A recording waiting in the processing queue, or left there failed
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedJob {
+    pub id: i64,
+    pub recording_id: String,
+    /// "queued", "running" or "failed"
+    pub status: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub queued_at: String,
+    /// RFC 3339; a retry waits until then
+    pub run_after: String,
+    /// Set to process it now, ahead of the queue and outside quiet hours
+    pub urgent: bool,
+}
+
+/// Reading a student was given, as handed over when a session is started
+/// from it. While it's active every new recording reads its passage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    pub id: String,
+    pub title: String,
+    pub passage: String,
+    #[serde(default)]
+    pub expected_duration_seconds: Option<f64>,
+    /// Scores the assignment's recordings in place of the device's rubric
+    #[serde(default)]
+    pub rubric: Option<Rubric>,
+    /// "in_progress" or "completed"
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub started_at: String,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    /// When the server was told it was completed
+    #[serde(default)]
+    pub reported_at: Option<String>,
+}
+
+/// One imported version of a grade-level norms table
+#[derive(Debug, Clone, Serialize)]
+pub struct NormsVersion {
+    pub name: String,
+    pub version: u32,
+    pub metric: String,
+    pub imported_at: String,
+}
+
+/// Metadata edits for a recording; `None` leaves the field untouched.
+#[derive(Debug, Default, Deserialize)]
+pub struct MetadataUpdate {
+    pub tags: Option<Vec<String>>,
+    pub notes: Option<String>,
+    pub review_status: Option<String>,
+    pub speaker_labels: Option<HashMap<String, String>>,
 }
 
 pub struct Database {
@@ -21,8 +525,16 @@ impl Database {
     pub fn new(data_dir: &PathBuf) -> SqliteResult<Self> {
         std::fs::create_dir_all(data_dir).ok();
         let db_path = data_dir.join("transcriber.db");
-        let conn = Connection::open(db_path)?;
+        Self::open(Connection::open(db_path)?)
+    }
 
+    /// A database that's gone when the app quits, to run on when the file
+    /// can't be opened
+    pub fn in_memory() -> SqliteResult<Self> {
+        Self::open(Connection::open_in_memory()?)
+    }
+
+    fn open(conn: Connection) -> SqliteResult<Self> {
         conn.execute(
             "CREATE TABLE IF NOT EXISTS recordings (
                 id TEXT PRIMARY KEY,
@@ -44,14 +556,362 @@ impl Database {
             [],
         )?;
 
-        Ok(Self { conn })
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS segments (
+                recording_id TEXT NOT NULL,
+                idx INTEGER NOT NULL,
+                start_seconds REAL NOT NULL,
+                end_seconds REAL NOT NULL,
+                text TEXT NOT NULL,
+                PRIMARY KEY (recording_id, idx)
+            )",
+            [],
+        )?;
+
+        // Segments as heard, before a mask stage; kept on the device only
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS unmasked_segments (
+                recording_id TEXT PRIMARY KEY,
+                segments TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS segment_revisions (
+                recording_id TEXT NOT NULL,
+                idx INTEGER NOT NULL,
+                previous_text TEXT NOT NULL,
+                new_text TEXT NOT NULL,
+                correction_audio_path TEXT,
+                revised_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Whole transcripts replaced by transcribing again
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transcript_revisions (
+                recording_id TEXT NOT NULL,
+                transcript TEXT NOT NULL,
+                language TEXT,
+                segments TEXT NOT NULL,
+                replaced_by TEXT,
+                replaced_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS assessments (
+                recording_id TEXT PRIMARY KEY,
+                metrics TEXT NOT NULL,
+                rubric_name TEXT,
+                level TEXT,
+                scored_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS settings_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                key TEXT NOT NULL,
+                layer TEXT NOT NULL,
+                old_value TEXT,
+                new_value TEXT,
+                source TEXT NOT NULL,
+                changed_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Settings pulled from the server, one row per (layer, key)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS config_layers (
+                layer TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (layer, key)
+            )",
+            [],
+        )?;
+
+        // Peaks computed from the audio, per bucket count the frontend asked for
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS waveforms (
+                recording_id TEXT NOT NULL,
+                buckets INTEGER NOT NULL,
+                peaks TEXT NOT NULL,
+                PRIMARY KEY (recording_id, buckets)
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS channel_activity (
+                recording_id TEXT PRIMARY KEY,
+                activity TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Names given to speakers, to label new recordings of the same
+        // student with the same teacher
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS speaker_names (
+                student_id TEXT NOT NULL,
+                teacher_name TEXT NOT NULL,
+                speaker TEXT NOT NULL,
+                name TEXT NOT NULL,
+                PRIMARY KEY (student_id, teacher_name, speaker)
+            )",
+            [],
+        )?;
+
+        // Students' enrolled voices, to tell them apart from the teacher in
+        // diarized recordings. The teacher's is the `teacher_voiceprint` setting.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS student_voiceprints (
+                student_id TEXT PRIMARY KEY,
+                voiceprint TEXT NOT NULL,
+                enrolled_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Names and terms each student's recordings are likely to hold, on
+        // top of the `vocabulary` setting, as a JSON list
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS student_vocabulary (
+                student_id TEXT PRIMARY KEY,
+                terms TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Kept in step with the segments they're worked out from
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS speaker_stats (
+                recording_id TEXT NOT NULL,
+                speaker TEXT NOT NULL,
+                words INTEGER NOT NULL,
+                seconds REAL NOT NULL,
+                share REAL NOT NULL,
+                PRIMARY KEY (recording_id, speaker)
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS markers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recording_id TEXT NOT NULL,
+                offset_seconds REAL NOT NULL,
+                label TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS job_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                recording_id TEXT,
+                started_at TEXT NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                succeeded INTEGER NOT NULL,
+                detail TEXT
+            )",
+            [],
+        )?;
+
+        // Recordings saved but not yet processed. Rows go once processing
+        // succeeds; the outcome is in job_history.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recording_id TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'queued',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                queued_at TEXT NOT NULL,
+                run_after TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Assignments sessions were started from; a completed one stays until
+        // the server has been told
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS assignments (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                passage TEXT NOT NULL,
+                expected_duration_seconds REAL,
+                rubric_json TEXT,
+                status TEXT NOT NULL DEFAULT 'in_progress',
+                started_at TEXT NOT NULL,
+                completed_at TEXT,
+                reported_at TEXT
+            )",
+            [],
+        )?;
+
+        // Every norms table imported, so a percentile can be traced back to
+        // the table that produced it
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS norms_tables (
+                name TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                metric TEXT NOT NULL,
+                table_json TEXT NOT NULL,
+                imported_at TEXT NOT NULL,
+                PRIMARY KEY (name, version)
+            )",
+            [],
+        )?;
+
+        // One row: this install's identity and the last sequence number used
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS device_identity (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                device_id TEXT NOT NULL,
+                last_sequence INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO device_identity (id, device_id) VALUES (1, ?1)",
+            [uuid::Uuid::new_v4().to_string()],
+        )?;
+
+        // Columns added after the initial schema
+        add_column_if_missing(&conn, "recordings", "tags", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "notes", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "review_status", "TEXT DEFAULT 'unreviewed'")?;
+        add_column_if_missing(&conn, "recordings", "speaker_labels", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "dirty_fields", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&conn, "recordings", "confidential", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&conn, "recordings", "expires_at", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "audio_purged", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&conn, "recordings", "deletion_notice_pending", "INTEGER DEFAULT 0")?;
+        // Replaced by `deletion_notice_pending`; purged audio the server
+        // hadn't been told about yet is still owed a notice
+        if column_exists(&conn, "recordings", "deletion_notified")? {
+            conn.execute(
+                "UPDATE recordings SET deletion_notice_pending = 1 WHERE audio_purged = 1 AND deletion_notified = 0",
+                [],
+            )?;
+            conn.execute("ALTER TABLE recordings DROP COLUMN deletion_notified", [])?;
+        }
+        add_column_if_missing(&conn, "recordings", "reference_passage", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "audio_uploaded_at", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "sync_attempts", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&conn, "recordings", "last_sync_error", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "audio_format", "TEXT DEFAULT 'wav'")?;
+        add_column_if_missing(&conn, "recordings", "audio_quality", "TEXT DEFAULT 'ok'")?;
+        add_column_if_missing(&conn, "recordings", "teacher_audio_path", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "student_audio_path", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "sequence", "INTEGER")?;
+        add_column_if_missing(&conn, "recordings", "guest", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&conn, "recordings", "archive_audio_path", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "transcript_language", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "detected_language", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "session_id", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "assignment_id", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "teacher_only", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&conn, "recordings", "student_speaker", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "translate", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&conn, "recordings", "translation", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "unmasked_transcript", "TEXT")?;
+        add_column_if_missing(&conn, "recordings", "unmasked_translation", "TEXT")?;
+        add_column_if_missing(&conn, "segments", "confidence", "REAL")?;
+        add_column_if_missing(&conn, "segments", "speaker", "TEXT")?;
+        add_column_if_missing(&conn, "segments", "words", "TEXT")?;
+        add_column_if_missing(&conn, "segments", "avg_logprob", "REAL")?;
+        add_column_if_missing(&conn, "segments", "no_speech_prob", "REAL")?;
+        add_column_if_missing(&conn, "assessments", "adjusted_metrics", "TEXT")?;
+        add_column_if_missing(&conn, "jobs", "urgent", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "waveforms", "audio_fingerprint", "TEXT")?;
+
+        let db = Self { conn };
+        db.assign_missing_sequences()?;
+        db.fill_missing_speaker_stats()?;
+        // Listing and paging walk this rather than `recorded_at`, which
+        // follows the wall clock. UUIDv7 IDs are ordered the same way, so the
+        // primary key index serves range scans over them too.
+        db.conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_recordings_sequence ON recordings (sequence)",
+            [],
+        )?;
+        // Every row written counts, so nothing has to remember to invalidate.
+        // No table here is emptied by an unconditional DELETE, which SQLite
+        // would do without calling the hook.
+        db.conn.update_hook(Some(|_, _: &str, table: &str, _| {
+            if let Some(table) = Table::of(table) {
+                table.touch();
+            }
+        }));
+        // A reopened database may not hold what was cached from the last one
+        Table::Recordings.touch();
+        Table::Settings.touch();
+        Ok(db)
+    }
+
+    /// Number recordings made before sequences existed, oldest first
+    fn assign_missing_sequences(&self) -> SqliteResult<()> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM recordings WHERE sequence IS NULL ORDER BY recorded_at, rowid")?;
+        let ids: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<SqliteResult<_>>()?;
+        for id in ids {
+            let sequence = self.next_sequence()?;
+            self.conn.execute(
+                "UPDATE recordings SET sequence = ?1 WHERE id = ?2",
+                rusqlite::params![sequence, id],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn next_sequence(&self) -> SqliteResult<i64> {
+        self.conn.execute(
+            "UPDATE device_identity SET last_sequence = last_sequence + 1 WHERE id = 1",
+            [],
+        )?;
+        self.conn
+            .query_row("SELECT last_sequence FROM device_identity WHERE id = 1", [], |row| row.get(0))
+    }
+
+    /// Stable ID for this install, sent with synced recordings so the server
+    /// can order them by `(device_id, sequence)`
+    pub fn device_id(&self) -> SqliteResult<String> {
+        self.conn
+            .query_row("SELECT device_id FROM device_identity WHERE id = 1", [], |row| row.get(0))
     }
 
     pub fn save_recording(&self, recording: &Recording) -> SqliteResult<()> {
+        // Callers keep saving the same `Recording` as it moves through the
+        // pipeline, so look for one already given to it before taking a new one
+        let sequence = match recording.sequence {
+            0 => match self.sequence_of(&recording.id)? {
+                Some(sequence) => sequence,
+                None => self.next_sequence()?,
+            },
+            sequence => sequence,
+        };
         self.conn.execute(
-            "INSERT OR REPLACE INTO recordings (id, student_id, audio_path, transcript, duration_seconds, recorded_at, synced)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            (
+            "INSERT OR REPLACE INTO recordings (id, student_id, audio_path, transcript, duration_seconds, recorded_at, synced,
+                 tags, notes, review_status, speaker_labels, dirty_fields, confidential, expires_at, audio_purged,
+                 reference_passage, audio_uploaded_at, audio_format, audio_quality, teacher_audio_path,
+                 student_audio_path, sequence, guest, archive_audio_path, transcript_language, detected_language,
+                 session_id, assignment_id, teacher_only, student_speaker, translate, translation,
+                 unmasked_transcript, unmasked_translation)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22,
+                     ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34)",
+            rusqlite::params![
                 &recording.id,
                 &recording.student_id,
                 &recording.audio_path,
@@ -59,82 +919,1563 @@ impl Database {
                 recording.duration_seconds,
                 &recording.recorded_at,
                 recording.synced as i32,
-            ),
+                serde_json::to_string(&recording.tags).unwrap_or_default(),
+                &recording.notes,
+                &recording.review_status,
+                serde_json::to_string(&recording.speaker_labels).unwrap_or_default(),
+                recording.dirty_fields,
+                recording.confidential as i32,
+                &recording.expires_at,
+                recording.audio_purged as i32,
+                &recording.reference_passage,
+                &recording.audio_uploaded_at,
+                recording.audio_format.as_str(),
+                recording.audio_quality.as_str(),
+                &recording.teacher_audio_path,
+                &recording.student_audio_path,
+                sequence,
+                recording.guest as i32,
+                &recording.archive_audio_path,
+                &recording.transcript_language,
+                &recording.detected_language,
+                &recording.session_id,
+                &recording.assignment_id,
+                recording.teacher_only as i32,
+                &recording.student_speaker,
+                recording.translate as i32,
+                &recording.translation,
+                &recording.unmasked_transcript,
+                &recording.unmasked_translation,
+            ],
         )?;
         Ok(())
     }
 
+    fn sequence_of(&self, id: &str) -> SqliteResult<Option<i64>> {
+        let mut stmt = self.conn.prepare("SELECT sequence FROM recordings WHERE id = ?1")?;
+        let mut rows = stmt.query_map([id], |row| row.get::<_, Option<i64>>(0))?;
+        Ok(rows.next().transpose()?.flatten())
+    }
+
+    /// Every recording that may leave the device via exports, share
+    /// features or reports, newest first. Confidential ones are left out, as
+    /// by every list below unless it says otherwise.
     pub fn get_all_recordings(&self) -> SqliteResult<Vec<Recording>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, student_id, audio_path, transcript, duration_seconds, recorded_at, synced
-             FROM recordings ORDER BY recorded_at DESC"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE confidential = 0 ORDER BY sequence DESC",
+            RECORDING_COLUMNS
+        ))?;
 
-        let recordings = stmt.query_map([], |row| {
-            Ok(Recording {
-                id: row.get(0)?,
-                student_id: row.get(1)?,
-                audio_path: row.get(2)?,
-                transcript: row.get(3)?,
-                duration_seconds: row.get(4)?,
-                recorded_at: row.get(5)?,
-                synced: row.get::<_, i32>(6)? != 0,
-            })
-        })?;
+        let recordings = stmt.query_map([], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    /// Every recording, confidential ones too, for what's shown and kept on
+    /// this device
+    pub fn get_all_recordings_including_confidential(&self) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings ORDER BY sequence DESC",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    /// Recordings made at or after `start` and before `end`, both RFC 3339
+    /// UTC timestamps, newest first. Guest recordings are left out.
+    pub fn get_recordings_between(&self, start: &str, end: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings
+             WHERE recorded_at >= ?1 AND recorded_at < ?2 AND guest = 0 AND confidential = 0
+             ORDER BY sequence DESC",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([start, end], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    /// Up to `limit` recordings made before `before_sequence` (or the newest
+    /// if `None`), newest first. Pass the last one's `sequence` to get the
+    /// next page; recordings added meanwhile don't shift pages already read.
+    /// Confidential recordings are included, for the list on this device.
+    pub fn get_recordings_page_including_confidential(
+        &self,
+        before_sequence: Option<i64>,
+        limit: u32,
+    ) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE ?1 IS NULL OR sequence < ?1 ORDER BY sequence DESC LIMIT ?2",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map(rusqlite::params![before_sequence, limit], Recording::from_row)?;
 
         recordings.collect()
     }
 
+    pub fn get_recording(&self, id: &str) -> SqliteResult<Option<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE id = ?1",
+            RECORDING_COLUMNS
+        ))?;
+        let mut rows = stmt.query_map([id], Recording::from_row)?;
+
+        rows.next().transpose()
+    }
+
     pub fn get_unsynced_recordings(&self) -> SqliteResult<Vec<Recording>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, student_id, audio_path, transcript, duration_seconds, recorded_at, synced
-             FROM recordings WHERE synced = 0 AND transcript IS NOT NULL"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings
+             WHERE synced = 0 AND transcript IS NOT NULL AND confidential = 0 AND sync_attempts < ?1",
+            RECORDING_COLUMNS
+        ))?;
 
-        let recordings = stmt.query_map([], |row| {
-            Ok(Recording {
-                id: row.get(0)?,
-                student_id: row.get(1)?,
-                audio_path: row.get(2)?,
-                transcript: row.get(3)?,
-                duration_seconds: row.get(4)?,
-                recorded_at: row.get(5)?,
-                synced: row.get::<_, i32>(6)? != 0,
-            })
-        })?;
+        let recordings = stmt.query_map([MAX_SYNC_ATTEMPTS], Recording::from_row)?;
 
         recordings.collect()
     }
 
-    pub fn mark_synced(&self, id: &str) -> SqliteResult<()> {
+    /// Note why a sync failed. Only the server rejecting the recording uses
+    /// up an attempt; when it didn't get through it's retried on every sync
+    /// for as long as that lasts.
+    pub fn record_sync_failure(&self, id: &str, error: &str, rejected: bool) -> SqliteResult<()> {
         self.conn.execute(
-            "UPDATE recordings SET synced = 1 WHERE id = ?1",
-            [id],
+            "UPDATE recordings SET sync_attempts = sync_attempts + ?3, last_sync_error = ?2 WHERE id = ?1",
+            rusqlite::params![id, error, rejected as i64],
         )?;
         Ok(())
     }
 
-    pub fn delete_recording(&self, id: &str) -> SqliteResult<()> {
-        self.conn.execute("DELETE FROM recordings WHERE id = ?1", [id])?;
-        Ok(())
+    /// Give permanently failed recordings a fresh set of attempts
+    pub fn reset_sync_failures(&self) -> SqliteResult<usize> {
+        self.conn.execute(
+            "UPDATE recordings SET sync_attempts = 0, last_sync_error = NULL
+             WHERE synced = 0 AND sync_attempts >= ?1",
+            [MAX_SYNC_ATTEMPTS],
+        )
     }
 
-    pub fn get_setting(&self, key: &str) -> SqliteResult<Option<String>> {
-        let mut stmt = self.conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
-        let mut rows = stmt.query([key])?;
+    pub fn get_backlog_counts(&self) -> SqliteResult<BacklogCounts> {
+        self.conn.query_row(
+            "SELECT
+                SUM(transcript IS NULL AND audio_purged = 0),
+                SUM(transcript IS NOT NULL AND review_status = 'unreviewed'),
+                SUM(synced = 0 AND transcript IS NOT NULL AND confidential = 0 AND sync_attempts < ?1),
+                SUM(synced = 0 AND transcript IS NOT NULL AND confidential = 0 AND sync_attempts >= ?1),
+                MIN(CASE WHEN (transcript IS NULL AND audio_purged = 0)
+                          OR (transcript IS NOT NULL AND review_status = 'unreviewed')
+                          OR (synced = 0 AND transcript IS NOT NULL AND confidential = 0)
+                     THEN recorded_at END)
+             FROM recordings",
+            [MAX_SYNC_ATTEMPTS],
+            |row| {
+                let count = |i: usize| -> SqliteResult<usize> {
+                    Ok(row.get::<_, Option<i64>>(i)?.unwrap_or(0) as usize)
+                };
+                Ok(BacklogCounts {
+                    awaiting_transcription: count(0)?,
+                    awaiting_review: count(1)?,
+                    awaiting_sync: count(2)?,
+                    failed_permanently: count(3)?,
+                    oldest_recorded_at: row.get(4)?,
+                })
+            },
+        )
+    }
 
-        if let Some(row) = rows.next()? {
-            Ok(Some(row.get(0)?))
-        } else {
-            Ok(None)
-        }
+    /// Synced recordings whose metadata was edited since the last push
+    pub fn get_dirty_recordings(&self) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE synced = 1 AND dirty_fields != 0 AND confidential = 0",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([], Recording::from_row)?;
+
+        recordings.collect()
     }
 
-    pub fn set_setting(&self, key: &str, value: &str) -> SqliteResult<()> {
+    /// A single recording, unless it is confidential
+    pub fn get_exportable_recording(&self, id: &str) -> SqliteResult<Option<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE id = ?1 AND confidential = 0",
+            RECORDING_COLUMNS
+        ))?;
+        let mut rows = stmt.query_map([id], Recording::from_row)?;
+
+        rows.next().transpose()
+    }
+
+    pub fn set_confidential(&self, id: &str, confidential: bool) -> SqliteResult<()> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            "UPDATE recordings SET confidential = ?2 WHERE id = ?1",
+            (id, confidential as i32),
+        )?;
+        Ok(())
+    }
+
+    pub fn set_reference_passage(&self, id: &str, passage: Option<&str>) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET reference_passage = ?2 WHERE id = ?1",
+            (id, passage),
+        )?;
+        Ok(())
+    }
+
+    pub fn set_expiry(&self, id: &str, expires_at: Option<&str>) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET expires_at = ?2 WHERE id = ?1",
+            (id, expires_at),
+        )?;
+        Ok(())
+    }
+
+    /// Recordings whose expiry is at or before `until` and whose audio still exists
+    pub fn get_expiring_recordings(&self, until: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings
+             WHERE expires_at IS NOT NULL AND expires_at <= ?1 AND audio_purged = 0
+             ORDER BY expires_at ASC",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([until], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    /// Recordings heard as a different language from the one they were
+    /// transcribed in, newest first
+    pub fn get_language_mismatches(&self) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings
+             WHERE detected_language IS NOT NULL AND transcript_language IS NOT NULL
+               AND detected_language != transcript_language
+             ORDER BY sequence DESC",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    /// The utterances of a push-to-talk session, in the order they were made
+    pub fn get_session_recordings(&self, session_id: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE session_id = ?1 AND confidential = 0 ORDER BY sequence ASC",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([session_id], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    pub fn get_session_recordings_including_confidential(&self, session_id: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE session_id = ?1 ORDER BY sequence ASC",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([session_id], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    /// Guest recordings made before `cutoff`
+    pub fn get_guest_recordings_before(&self, cutoff: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE guest = 1 AND recorded_at < ?1",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([cutoff], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    /// Record that expired audio was deleted; the server is told on next sync
+    pub fn mark_audio_purged(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET audio_purged = 1, deletion_notice_pending = 1 WHERE id = ?1",
+            [id],
+        )?;
+        Ok(())
+    }
+
+    /// Point a dual-channel recording at its teacher and student files after
+    /// they were re-encoded
+    pub fn set_lane_files(&self, id: &str, teacher_audio_path: &str, student_audio_path: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET teacher_audio_path = ?1, student_audio_path = ?2 WHERE id = ?3",
+            rusqlite::params![teacher_audio_path, student_audio_path, id],
+        )?;
+        Ok(())
+    }
+
+    /// Forget a dual-channel recording's lanes once their files are deleted
+    pub fn clear_lane_files(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET teacher_audio_path = NULL, student_audio_path = NULL WHERE id = ?1",
+            [id],
+        )?;
+        Ok(())
+    }
+
+    /// Forget a recording's archive copy once its file is deleted
+    pub fn clear_archive_audio(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET archive_audio_path = NULL WHERE id = ?1",
+            [id],
+        )?;
+        Ok(())
+    }
+
+    /// Expired recordings whose mixed audio was offloaded but whose lanes or
+    /// archive copy, which the server never held, are still on the device
+    pub fn get_expired_local_only_audio(&self, until: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings
+             WHERE expires_at IS NOT NULL AND expires_at <= ?1 AND audio_purged = 1
+               AND (teacher_audio_path IS NOT NULL OR student_audio_path IS NOT NULL
+                    OR archive_audio_path IS NOT NULL)",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([until], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    /// Point a recording at its audio after it was re-encoded
+    pub fn set_audio_file(&self, id: &str, audio_path: &str, format: AudioFormat) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET audio_path = ?1, audio_format = ?2 WHERE id = ?3",
+            rusqlite::params![audio_path, format.as_str(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Record that local audio was removed because the server keeps a copy
+    pub fn mark_audio_offloaded(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET audio_purged = 1 WHERE id = ?1",
+            [id],
+        )?;
+        Ok(())
+    }
+
+    /// Expired recordings the server holds a copy of but hasn't been told about
+    pub fn get_pending_deletion_notices(&self) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE deletion_notice_pending = 1 AND synced = 1",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    pub fn mark_deletion_notified(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET deletion_notice_pending = 0 WHERE id = ?1",
+            [id],
+        )?;
+        Ok(())
+    }
+
+    /// Synced recordings whose local audio hasn't been uploaded yet
+    pub fn get_audio_awaiting_upload(&self) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings
+             WHERE synced = 1 AND audio_uploaded_at IS NULL AND audio_purged = 0 AND confidential = 0",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    pub fn mark_audio_uploaded(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET audio_uploaded_at = ?2 WHERE id = ?1",
+            (id, chrono::Utc::now().to_rfc3339()),
+        )?;
+        Ok(())
+    }
+
+    /// Uploaded recordings whose local audio is still on disk, confirmed before `before`
+    pub fn get_offloadable_recordings(&self, before: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings
+             WHERE audio_uploaded_at IS NOT NULL AND audio_uploaded_at <= ?1 AND audio_purged = 0",
+            RECORDING_COLUMNS
+        ))?;
+
+        let recordings = stmt.query_map([before], Recording::from_row)?;
+
+        recordings.collect()
+    }
+
+    pub fn mark_synced(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET synced = 1 WHERE id = ?1",
+            [id],
+        )?;
+        Ok(())
+    }
+
+    /// Apply metadata edits and flag the changed fields for the next sync
+    pub fn update_metadata(&self, id: &str, update: &MetadataUpdate) -> SqliteResult<()> {
+        // Each field and its dirty flag land together or not at all, so a
+        // failure part way can't leave an edit that's never synced
+        let tx = self.conn.unchecked_transaction()?;
+        let mut dirty = 0;
+
+        if let Some(ref tags) = update.tags {
+            tx.execute(
+                "UPDATE recordings SET tags = ?2 WHERE id = ?1",
+                (id, serde_json::to_string(tags).unwrap_or_default()),
+            )?;
+            dirty |= DIRTY_TAGS;
+        }
+        if let Some(ref notes) = update.notes {
+            tx.execute(
+                "UPDATE recordings SET notes = ?2 WHERE id = ?1",
+                (id, notes),
+            )?;
+            dirty |= DIRTY_NOTES;
+        }
+        if let Some(ref review_status) = update.review_status {
+            tx.execute(
+                "UPDATE recordings SET review_status = ?2 WHERE id = ?1",
+                (id, review_status),
+            )?;
+            dirty |= DIRTY_REVIEW_STATUS;
+        }
+        if let Some(ref speaker_labels) = update.speaker_labels {
+            tx.execute(
+                "UPDATE recordings SET speaker_labels = ?2 WHERE id = ?1",
+                (id, serde_json::to_string(speaker_labels).unwrap_or_default()),
+            )?;
+            dirty |= DIRTY_SPEAKER_LABELS;
+        }
+
+        tx.execute(
+            "UPDATE recordings SET dirty_fields = COALESCE(dirty_fields, 0) | ?2 WHERE id = ?1",
+            (id, dirty),
+        )?;
+        tx.commit()
+    }
+
+    /// Name `speaker` on a recording, or with `None` go back to showing the
+    /// key. Flagged dirty like any other label edit. Returns whether the
+    /// recording exists.
+    pub fn label_speaker(&self, id: &str, speaker: &str, name: Option<&str>) -> SqliteResult<bool> {
+        let Some(recording) = self.get_recording(id)? else {
+            return Ok(false);
+        };
+        let mut labels = recording.speaker_labels;
+        match name {
+            Some(name) => labels.insert(speaker.to_string(), name.to_string()),
+            None => labels.remove(speaker),
+        };
+        self.update_metadata(id, &MetadataUpdate {
+            speaker_labels: Some(labels),
+            ..Default::default()
+        })?;
+        Ok(true)
+    }
+
+    /// Keep `name` for `speaker` in recordings of `student_id` made with
+    /// `teacher_name`, or with `None` stop naming them
+    pub fn remember_speaker_name(
+        &self,
+        student_id: &str,
+        teacher_name: &str,
+        speaker: &str,
+        name: Option<&str>,
+    ) -> SqliteResult<()> {
+        match name {
+            Some(name) => self.conn.execute(
+                "INSERT OR REPLACE INTO speaker_names (student_id, teacher_name, speaker, name) VALUES (?1, ?2, ?3, ?4)",
+                (student_id, teacher_name, speaker, name),
+            ),
+            None => self.conn.execute(
+                "DELETE FROM speaker_names WHERE student_id = ?1 AND teacher_name = ?2 AND speaker = ?3",
+                (student_id, teacher_name, speaker),
+            ),
+        }?;
+        Ok(())
+    }
+
+    /// Speaker names kept for a student and teacher, by speaker key
+    pub fn get_speaker_names(&self, student_id: &str, teacher_name: &str) -> SqliteResult<HashMap<String, String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT speaker, name FROM speaker_names WHERE student_id = ?1 AND teacher_name = ?2")?;
+        let names = stmt.query_map((student_id, teacher_name), |row| Ok((row.get(0)?, row.get(1)?)))?;
+        names.collect()
+    }
+
+    /// Keep `voiceprint` as the enrolled voice of `student_id`, or with
+    /// `None` forget it
+    pub fn set_student_voiceprint(&self, student_id: &str, voiceprint: Option<&Voiceprint>) -> SqliteResult<()> {
+        match voiceprint {
+            Some(voiceprint) => self.conn.execute(
+                "INSERT OR REPLACE INTO student_voiceprints (student_id, voiceprint, enrolled_at) VALUES (?1, ?2, ?3)",
+                (
+                    student_id,
+                    serde_json::to_string(voiceprint).unwrap_or_default(),
+                    chrono::Utc::now().to_rfc3339(),
+                ),
+            ),
+            None => self
+                .conn
+                .execute("DELETE FROM student_voiceprints WHERE student_id = ?1", [student_id]),
+        }?;
+        Ok(())
+    }
+
+    pub fn get_student_voiceprint(&self, student_id: &str) -> SqliteResult<Option<Voiceprint>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT voiceprint FROM student_voiceprints WHERE student_id = ?1")?;
+        let mut rows = stmt.query_map([student_id], |row| row.get::<_, String>(0))?;
+        Ok(rows.next().transpose()?.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+
+    /// Keep `terms` as the vocabulary of `student_id`, or with none forget it
+    pub fn set_student_vocabulary(&self, student_id: &str, terms: &[String]) -> SqliteResult<()> {
+        if terms.is_empty() {
+            self.conn
+                .execute("DELETE FROM student_vocabulary WHERE student_id = ?1", [student_id])?;
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO student_vocabulary (student_id, terms) VALUES (?1, ?2)",
+                (student_id, serde_json::to_string(terms).unwrap_or_default()),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_student_vocabulary(&self, student_id: &str) -> SqliteResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT terms FROM student_vocabulary WHERE student_id = ?1")?;
+        let mut rows = stmt.query_map([student_id], |row| row.get::<_, String>(0))?;
+        Ok(rows
+            .next()
+            .transpose()?
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn roster_counts(&self) -> SqliteResult<RosterCounts> {
+        let count = |sql: &str| self.conn.query_row(sql, [], |row| row.get::<_, i64>(0)).map(|n| n as usize);
+        Ok(RosterCounts {
+            speaker_names: count("SELECT COUNT(*) FROM speaker_names")?,
+            voiceprints: count("SELECT COUNT(*) FROM student_voiceprints")?,
+            vocabularies: count("SELECT COUNT(*) FROM student_vocabulary")?,
+            assignments: count("SELECT COUNT(*) FROM assignments WHERE reported_at IS NOT NULL")?,
+        })
+    }
+
+    /// Forget everything `roster_counts` counts. Returns what was removed.
+    pub fn reset_roster(&self) -> SqliteResult<RosterCounts> {
+        Ok(RosterCounts {
+            speaker_names: self.conn.execute("DELETE FROM speaker_names", [])?,
+            voiceprints: self.conn.execute("DELETE FROM student_voiceprints", [])?,
+            vocabularies: self.conn.execute("DELETE FROM student_vocabulary", [])?,
+            assignments: self
+                .conn
+                .execute("DELETE FROM assignments WHERE reported_at IS NOT NULL", [])?,
+        })
+    }
+
+    /// Clear only the bits that were pushed, so edits made mid-sync stay dirty
+    pub fn clear_dirty(&self, id: &str, fields: u32) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET dirty_fields = COALESCE(dirty_fields, 0) & ~?2 WHERE id = ?1",
+            (id, fields),
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_recording(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM segments WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM unmasked_segments WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM segment_revisions WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM transcript_revisions WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM assessments WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM waveforms WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM channel_activity WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM markers WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM speaker_stats WHERE recording_id = ?1", [id])?;
+        self.conn.execute("DELETE FROM recordings WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Replace the stored segments for a recording
+    pub fn save_segments(&self, recording_id: &str, segments: &[TranscriptSegment]) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM segments WHERE recording_id = ?1", [recording_id])?;
+        let mut stmt = self.conn.prepare(
+            "INSERT INTO segments (recording_id, idx, start_seconds, end_seconds, text, confidence, speaker, words,
+                                   avg_logprob, no_speech_prob)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )?;
+        for (idx, segment) in segments.iter().enumerate() {
+            stmt.execute((
+                recording_id,
+                idx as i64,
+                segment.start,
+                segment.end,
+                &segment.text,
+                segment.confidence,
+                &segment.speaker,
+                (!segment.words.is_empty()).then(|| serde_json::to_string(&segment.words).unwrap_or_default()),
+                segment.avg_logprob,
+                segment.no_speech_prob,
+            ))?;
+        }
+        self.refresh_speaker_stats(recording_id)
+    }
+
+    /// Replace one segment's text, keeping the old text as a revision
+    pub fn revise_segment(
+        &self,
+        recording_id: &str,
+        index: usize,
+        new_text: &str,
+        correction_audio_path: Option<&str>,
+    ) -> SqliteResult<()> {
+        let previous_text: String = self.conn.query_row(
+            "SELECT text FROM segments WHERE recording_id = ?1 AND idx = ?2",
+            (recording_id, index as i64),
+            |row| row.get(0),
+        )?;
+
+        self.conn.execute(
+            "INSERT INTO segment_revisions (recording_id, idx, previous_text, new_text, correction_audio_path, revised_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                recording_id,
+                index as i64,
+                &previous_text,
+                new_text,
+                correction_audio_path,
+                chrono::Utc::now().to_rfc3339(),
+            ),
+        )?;
+        // The old words no longer match the text
+        self.conn.execute(
+            "UPDATE segments SET text = ?3, words = NULL WHERE recording_id = ?1 AND idx = ?2",
+            (recording_id, index as i64, new_text),
+        )?;
+        self.refresh_speaker_stats(recording_id)
+    }
+
+    /// Returns whether the segment exists
+    pub fn set_segment_speaker(&self, recording_id: &str, index: usize, speaker: Option<&str>) -> SqliteResult<bool> {
+        let changed = self.conn.execute(
+            "UPDATE segments SET speaker = ?3 WHERE recording_id = ?1 AND idx = ?2",
+            (recording_id, index as i64, speaker),
+        )?;
+        self.refresh_speaker_stats(recording_id)?;
+        Ok(changed > 0)
+    }
+
+    /// Recordings given speakers before their statistics were kept
+    fn fill_missing_speaker_stats(&self) -> SqliteResult<()> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT recording_id FROM segments WHERE speaker IS NOT NULL
+             AND recording_id NOT IN (SELECT recording_id FROM speaker_stats)",
+        )?;
+        let ids: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<SqliteResult<_>>()?;
+        for recording_id in ids {
+            self.refresh_speaker_stats(&recording_id)?;
+        }
+        Ok(())
+    }
+
+    /// Work a recording's speaker statistics out again from its segments
+    fn refresh_speaker_stats(&self, recording_id: &str) -> SqliteResult<()> {
+        let stats = speakers::stats(&self.get_segments(recording_id)?);
+        self.conn.execute("DELETE FROM speaker_stats WHERE recording_id = ?1", [recording_id])?;
+        let mut stmt = self.conn.prepare(
+            "INSERT INTO speaker_stats (recording_id, speaker, words, seconds, share) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for entry in &stats {
+            stmt.execute((recording_id, &entry.speaker, entry.words as i64, entry.seconds, entry.share))?;
+        }
+        Ok(())
+    }
+
+    /// Who spoke how much in a recording, most talkative first; labels are
+    /// left for the caller to fill in
+    pub fn get_speaker_stats(&self, recording_id: &str) -> SqliteResult<Vec<SpeakerStats>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT speaker, words, seconds, share FROM speaker_stats WHERE recording_id = ?1 ORDER BY seconds DESC",
+        )?;
+        let stats = stmt.query_map([recording_id], |row| {
+            Ok(SpeakerStats {
+                speaker: row.get(0)?,
+                label: None,
+                words: row.get::<_, i64>(1)? as usize,
+                seconds: row.get(2)?,
+                share: row.get(3)?,
+            })
+        })?;
+        stats.collect()
+    }
+
+    pub fn get_segment_revisions(&self, recording_id: &str) -> SqliteResult<Vec<SegmentRevision>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT idx, previous_text, new_text, correction_audio_path, revised_at
+             FROM segment_revisions WHERE recording_id = ?1 ORDER BY revised_at",
+        )?;
+
+        let revisions = stmt.query_map([recording_id], |row| {
+            Ok(SegmentRevision {
+                segment_index: row.get::<_, i64>(0)? as usize,
+                previous_text: row.get(1)?,
+                new_text: row.get(2)?,
+                correction_audio_path: row.get(3)?,
+                revised_at: row.get(4)?,
+            })
+        })?;
+
+        revisions.collect()
+    }
+
+    /// Keep a recording's current transcript and segments before it's
+    /// transcribed again. Does nothing if it hasn't been transcribed.
+    pub fn save_transcript_revision(&self, recording: &Recording, replaced_by: Option<&str>) -> SqliteResult<()> {
+        let Some(transcript) = &recording.transcript else {
+            return Ok(());
+        };
+        let segments = self.get_segments(&recording.id)?;
+        self.conn.execute(
+            "INSERT INTO transcript_revisions (recording_id, transcript, language, segments, replaced_by, replaced_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                recording.id,
+                transcript,
+                recording.transcript_language,
+                serde_json::to_string(&segments).unwrap_or_default(),
+                replaced_by,
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Earlier transcripts of a recording, oldest first
+    pub fn get_transcript_revisions(&self, recording_id: &str) -> SqliteResult<Vec<TranscriptRevision>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT transcript, language, segments, replaced_by, replaced_at
+             FROM transcript_revisions WHERE recording_id = ?1 ORDER BY rowid",
+        )?;
+
+        let revisions = stmt.query_map([recording_id], |row| {
+            let segments: String = row.get(2)?;
+            Ok(TranscriptRevision {
+                transcript: row.get(0)?,
+                language: row.get(1)?,
+                segments: serde_json::from_str(&segments).unwrap_or_default(),
+                replaced_by: row.get(3)?,
+                replaced_at: row.get(4)?,
+            })
+        })?;
+
+        revisions.collect()
+    }
+
+    /// Overwrite the flat transcript after an edit; synced recordings
+    /// get the change pushed on the next sync
+    pub fn update_transcript(&self, id: &str, transcript: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE recordings SET transcript = ?2,
+                 dirty_fields = CASE WHEN synced = 1 THEN COALESCE(dirty_fields, 0) | ?3 ELSE dirty_fields END
+             WHERE id = ?1",
+            (id, transcript, DIRTY_TRANSCRIPT),
+        )?;
+        Ok(())
+    }
+
+    /// Keep the segments as they were before masking; None drops them once
+    /// nothing is masked any more
+    pub fn set_unmasked_segments(&self, recording_id: &str, segments: Option<&[TranscriptSegment]>) -> SqliteResult<()> {
+        match segments {
+            Some(segments) => self.conn.execute(
+                "INSERT OR REPLACE INTO unmasked_segments (recording_id, segments) VALUES (?1, ?2)",
+                (recording_id, serde_json::to_string(segments).unwrap_or_default()),
+            )?,
+            None => self.conn.execute("DELETE FROM unmasked_segments WHERE recording_id = ?1", [recording_id])?,
+        };
+        Ok(())
+    }
+
+    pub fn get_unmasked_segments(&self, recording_id: &str) -> SqliteResult<Option<Vec<TranscriptSegment>>> {
+        let mut stmt = self.conn.prepare("SELECT segments FROM unmasked_segments WHERE recording_id = ?1")?;
+        let mut rows = stmt.query_map([recording_id], |row| row.get::<_, String>(0))?;
+        Ok(rows.next().transpose()?.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    pub fn get_segments(&self, recording_id: &str) -> SqliteResult<Vec<TranscriptSegment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT start_seconds, end_seconds, text, confidence, speaker, words, avg_logprob, no_speech_prob
+             FROM segments WHERE recording_id = ?1 ORDER BY idx",
+        )?;
+
+        let segments = stmt.query_map([recording_id], |row| {
+            Ok(TranscriptSegment {
+                start: row.get(0)?,
+                end: row.get(1)?,
+                text: row.get(2)?,
+                confidence: row.get(3)?,
+                avg_logprob: row.get(6)?,
+                no_speech_prob: row.get(7)?,
+                speaker: row.get(4)?,
+                words: row
+                    .get::<_, Option<String>>(5)?
+                    .and_then(|w| serde_json::from_str(&w).ok())
+                    .unwrap_or_default(),
+            })
+        })?;
+
+        segments.collect()
+    }
+
+    pub fn delete_assessment(&self, recording_id: &str) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM assessments WHERE recording_id = ?1", [recording_id])?;
+        Ok(())
+    }
+
+    pub fn save_assessment(&self, assessment: &Assessment) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO assessments (recording_id, metrics, rubric_name, level, scored_at, adjusted_metrics)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                &assessment.recording_id,
+                serde_json::to_string(&assessment.metrics).unwrap_or_default(),
+                &assessment.rubric_name,
+                &assessment.level,
+                &assessment.scored_at,
+                assessment
+                    .adjusted
+                    .as_ref()
+                    .map(|m| serde_json::to_string(m).unwrap_or_default()),
+            ),
+        )?;
+        Ok(())
+    }
+
+    pub fn get_assessment(&self, recording_id: &str) -> SqliteResult<Option<Assessment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT recording_id, metrics, rubric_name, level, scored_at, adjusted_metrics
+             FROM assessments WHERE recording_id = ?1",
+        )?;
+        let mut rows = stmt.query_map([recording_id], assessment_from_row)?;
+
+        rows.next().transpose()
+    }
+
+    /// Assessments for recordings that may leave the device
+    pub fn get_exportable_assessments(&self) -> SqliteResult<Vec<Assessment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT a.recording_id, a.metrics, a.rubric_name, a.level, a.scored_at, a.adjusted_metrics
+             FROM assessments a JOIN recordings r ON r.id = a.recording_id
+             WHERE r.confidential = 0 ORDER BY a.scored_at",
+        )?;
+        let assessments = stmt.query_map([], assessment_from_row)?;
+
+        assessments.collect()
+    }
+
+    /// Each student's assessment for their most recent scored recording,
+    /// leaving out guest and teacher-only recordings
+    pub fn get_latest_assessments(&self) -> SqliteResult<Vec<Assessment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT a.recording_id, a.metrics, a.rubric_name, a.level, a.scored_at, a.adjusted_metrics
+             FROM assessments a JOIN recordings r ON r.id = a.recording_id
+             WHERE r.guest = 0 AND r.teacher_only = 0 AND r.recorded_at = (
+                 SELECT MAX(r2.recorded_at) FROM assessments a2 JOIN recordings r2 ON r2.id = a2.recording_id
+                 WHERE r2.student_id = r.student_id AND r2.guest = 0 AND r2.teacher_only = 0
+             )
+             GROUP BY r.student_id",
+        )?;
+        let assessments = stmt.query_map([], assessment_from_row)?;
+
+        assessments.collect()
+    }
+
+    pub fn get_channel_activity(&self, recording_id: &str) -> SqliteResult<Option<ChannelActivity>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT activity FROM channel_activity WHERE recording_id = ?1")?;
+        let mut rows = stmt.query_map([recording_id], |row| row.get::<_, String>(0))?;
+
+        Ok(rows
+            .next()
+            .transpose()?
+            .and_then(|activity| serde_json::from_str(&activity).ok()))
+    }
+
+    pub fn save_channel_activity(&self, recording_id: &str, activity: &ChannelActivity) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO channel_activity (recording_id, activity) VALUES (?1, ?2)",
+            rusqlite::params![recording_id, serde_json::to_string(activity).unwrap_or_default()],
+        )?;
+        Ok(())
+    }
+
+    /// Markers for a recording, in the order they come in the audio
+    pub fn get_markers(&self, recording_id: &str) -> SqliteResult<Vec<Marker>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, recording_id, offset_seconds, label, created_at
+             FROM markers WHERE recording_id = ?1 ORDER BY offset_seconds, id",
+        )?;
+
+        let markers = stmt.query_map([recording_id], |row| {
+            Ok(Marker {
+                id: row.get(0)?,
+                recording_id: row.get(1)?,
+                offset_seconds: row.get(2)?,
+                label: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+
+        markers.collect()
+    }
+
+    pub fn add_markers(&self, recording_id: &str, markers: &[CaptureMarker]) -> SqliteResult<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut stmt = self.conn.prepare(
+            "INSERT INTO markers (recording_id, offset_seconds, label, created_at) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for marker in markers {
+            stmt.execute((recording_id, marker.offset_seconds, &marker.label, &now))?;
+        }
+        Ok(())
+    }
+
+    /// Move markers onto audio that was cut down to `start..end` seconds,
+    /// pinning any outside it to the nearest end
+    pub fn trim_markers(&self, recording_id: &str, start: f64, end: f64) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE markers SET offset_seconds = MAX(MIN(offset_seconds, ?3) - ?2, 0) WHERE recording_id = ?1",
+            rusqlite::params![recording_id, start, end],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_marker(&self, id: i64) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM markers WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Cached peaks, if they were drawn from audio with `fingerprint`. With
+    /// no fingerprint, as once the audio is gone, whatever was cached last.
+    pub fn get_waveform(
+        &self,
+        recording_id: &str,
+        buckets: usize,
+        fingerprint: Option<&str>,
+    ) -> SqliteResult<Option<Waveform>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT peaks, audio_fingerprint FROM waveforms WHERE recording_id = ?1 AND buckets = ?2")?;
+        let mut rows = stmt.query_map(rusqlite::params![recording_id, buckets as i64], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+
+        Ok(rows
+            .next()
+            .transpose()?
+            .filter(|(_, cached)| fingerprint.is_none() || cached.as_deref() == fingerprint)
+            .and_then(|(peaks, _)| serde_json::from_str(&peaks).ok()))
+    }
+
+    pub fn save_waveform(
+        &self,
+        recording_id: &str,
+        buckets: usize,
+        fingerprint: Option<&str>,
+        waveform: &Waveform,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO waveforms (recording_id, buckets, peaks, audio_fingerprint)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![
+                recording_id,
+                buckets as i64,
+                serde_json::to_string(waveform).unwrap_or_default(),
+                fingerprint
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_setting(&self, key: &str) -> SqliteResult<Option<String>> {
+        let mut stmt = self.conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
+        let mut rows = stmt.query([key])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(row.get(0)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Device-level settings, i.e. everything set on this machine
+    pub fn get_all_settings(&self) -> SqliteResult<HashMap<String, String>> {
+        let mut stmt = self.conn.prepare("SELECT key, value FROM settings")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        rows.collect()
+    }
+
+    pub fn get_config_value(&self, layer: &str, key: &str) -> SqliteResult<Option<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT value FROM config_layers WHERE layer = ?1 AND key = ?2")?;
+        let mut rows = stmt.query([layer, key])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(row.get(0)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get_config_layer(&self, layer: &str) -> SqliteResult<HashMap<String, String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, value FROM config_layers WHERE layer = ?1")?;
+        let rows = stmt.query_map([layer], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        rows.collect()
+    }
+
+    /// Replace every value in a pulled layer, logging what the server changed
+    pub fn replace_config_layer(&self, layer: &str, values: &HashMap<String, String>) -> SqliteResult<()> {
+        let previous = self.get_config_layer(layer)?;
+        for (key, old) in &previous {
+            if !values.contains_key(key) {
+                self.log_setting_change(key, layer, Some(old), None, "remote")?;
+            }
+        }
+        for (key, new) in values {
+            let old = previous.get(key);
+            if old != Some(new) {
+                self.log_setting_change(key, layer, old.map(|v| v.as_str()), Some(new), "remote")?;
+            }
+        }
+
+        self.conn.execute("DELETE FROM config_layers WHERE layer = ?1", [layer])?;
+        let mut stmt = self
+            .conn
+            .prepare("INSERT INTO config_layers (layer, key, value) VALUES (?1, ?2, ?3)")?;
+        for (key, value) in values {
+            stmt.execute([layer, key, value])?;
+        }
+        Ok(())
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> SqliteResult<()> {
+        self.set_setting_as(key, value, "user")
+    }
+
+    /// Set a device setting, recording who changed it
+    pub fn set_setting_as(&self, key: &str, value: &str, source: &str) -> SqliteResult<()> {
+        let old = self.get_setting(key)?;
+        if old.as_deref() == Some(value) {
+            return Ok(());
+        }
+        self.conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
             [key, value],
         )?;
+        self.log_setting_change(key, "device", old.as_deref(), Some(value), source)
+    }
+
+    pub fn delete_setting_as(&self, key: &str, source: &str) -> SqliteResult<()> {
+        let Some(old) = self.get_setting(key)? else {
+            return Ok(());
+        };
+        self.conn.execute("DELETE FROM settings WHERE key = ?1", [key])?;
+        self.log_setting_change(key, "device", Some(&old), None, source)
+    }
+
+    fn log_setting_change(
+        &self,
+        key: &str,
+        layer: &str,
+        old_value: Option<&str>,
+        new_value: Option<&str>,
+        source: &str,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO settings_history (key, layer, old_value, new_value, source, changed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![key, layer, old_value, new_value, source, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Setting changes, newest first, optionally for one key
+    pub fn get_settings_history(&self, key: Option<&str>) -> SqliteResult<Vec<SettingChange>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, key, layer, old_value, new_value, source, changed_at
+             FROM settings_history WHERE ?1 IS NULL OR key = ?1 ORDER BY id DESC",
+        )?;
+
+        let changes = stmt.query_map([key], |row| {
+            Ok(SettingChange {
+                id: row.get(0)?,
+                key: row.get(1)?,
+                layer: row.get(2)?,
+                old_value: row.get(3)?,
+                new_value: row.get(4)?,
+                source: row.get(5)?,
+                changed_at: row.get(6)?,
+            })
+        })?;
+
+        changes.collect()
+    }
+
+    pub fn record_job(&self, job: &JobEntry) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO job_history (kind, recording_id, started_at, duration_ms, succeeded, detail)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                job.kind.as_str(),
+                job.recording_id,
+                job.started_at,
+                job.duration_ms,
+                job.succeeded as i32,
+                job.detail,
+            ],
+        )?;
+        self.conn.execute(
+            "DELETE FROM job_history WHERE id <= (SELECT MAX(id) FROM job_history) - ?1",
+            [JOB_HISTORY_LIMIT],
+        )?;
         Ok(())
     }
+
+    /// Queue a saved recording for processing
+    pub fn enqueue_job(&self, recording_id: &str) -> SqliteResult<i64> {
+        let now = chrono::Utc::now().to_rfc3339();
+        self.conn.execute(
+            "INSERT INTO jobs (recording_id, queued_at, run_after) VALUES (?1, ?2, ?2)",
+            rusqlite::params![recording_id, now],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// The oldest queued job that's due at `now`, urgent ones first, marked
+    /// running. With `urgent_only` the rest are left waiting.
+    pub fn start_next_job(&self, now: &str, urgent_only: bool) -> SqliteResult<Option<QueuedJob>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, recording_id, status, attempts, last_error, queued_at, run_after, urgent
+             FROM jobs WHERE status = 'queued' AND run_after <= ?1 AND (urgent = 1 OR NOT ?2)
+             ORDER BY urgent DESC, id LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map(rusqlite::params![now, urgent_only], queued_job_from_row)?;
+        let Some(mut job) = rows.next().transpose()? else {
+            return Ok(None);
+        };
+        self.conn.execute(
+            "UPDATE jobs SET status = 'running', attempts = attempts + 1 WHERE id = ?1",
+            [job.id],
+        )?;
+        job.status = "running".to_string();
+        job.attempts += 1;
+        Ok(Some(job))
+    }
+
+    /// When the next queued job comes due, if any are waiting; with
+    /// `urgent_only`, the next urgent one
+    pub fn next_job_due(&self, urgent_only: bool) -> SqliteResult<Option<String>> {
+        self.conn.query_row(
+            "SELECT MIN(run_after) FROM jobs WHERE status = 'queued' AND (urgent = 1 OR NOT ?1)",
+            [urgent_only],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn finish_job(&self, id: i64) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM jobs WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Put a job that failed back in the queue to run again at `run_after`,
+    /// or with `None` leave it failed
+    pub fn fail_job(&self, id: i64, error: &str, run_after: Option<&str>) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE jobs SET status = CASE WHEN ?3 IS NULL THEN 'failed' ELSE 'queued' END,
+                 last_error = ?2, run_after = COALESCE(?3, run_after)
+             WHERE id = ?1",
+            rusqlite::params![id, error, run_after],
+        )?;
+        Ok(())
+    }
+
+    /// Queue a failed job again with its attempts reset. Returns whether
+    /// there was such a job.
+    pub fn retry_job(&self, id: i64) -> SqliteResult<bool> {
+        let changed = self.conn.execute(
+            "UPDATE jobs SET status = 'queued', attempts = 0, run_after = ?2
+             WHERE id = ?1 AND status = 'failed'",
+            rusqlite::params![id, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Have a queued job run as soon as the worker is free, even outside
+    /// quiet hours and if it was waiting to retry. Returns whether there was
+    /// such a job.
+    pub fn rush_job(&self, id: i64) -> SqliteResult<bool> {
+        let changed = self.conn.execute(
+            "UPDATE jobs SET urgent = 1, run_after = MIN(run_after, ?2) WHERE id = ?1 AND status = 'queued'",
+            rusqlite::params![id, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Queue every recording still waiting on a transcript whose audio can
+    /// be transcribed: failed jobs for them are retried with fresh attempts
+    /// and ones not in the queue are added, oldest first. Returns the ids of
+    /// all of them, including ones that were queued already.
+    pub fn queue_untranscribed(&self) -> SqliteResult<Vec<String>> {
+        const PENDING: &str = "SELECT id FROM recordings
+             WHERE transcript IS NULL AND audio_purged = 0 AND COALESCE(audio_quality, 'ok') != 'too_quiet'";
+        let now = chrono::Utc::now().to_rfc3339();
+        self.conn.execute(
+            &format!(
+                "UPDATE jobs SET status = 'queued', attempts = 0, run_after = ?1
+                 WHERE status = 'failed' AND recording_id IN ({})",
+                PENDING
+            ),
+            [&now],
+        )?;
+        self.conn.execute(
+            &format!(
+                "INSERT INTO jobs (recording_id, queued_at, run_after)
+                 SELECT id, ?1, ?1 FROM recordings
+                 WHERE id IN ({}) AND id NOT IN (SELECT recording_id FROM jobs)
+                 ORDER BY sequence",
+                PENDING
+            ),
+            [&now],
+        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT recording_id FROM jobs WHERE recording_id IN ({}) GROUP BY recording_id ORDER BY MIN(id)",
+            PENDING
+        ))?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+        ids.collect()
+    }
+
+    /// Jobs left running when the app last quit go back in the queue
+    pub fn requeue_interrupted_jobs(&self) -> SqliteResult<usize> {
+        self.conn
+            .execute("UPDATE jobs SET status = 'queued' WHERE status = 'running'", [])
+    }
+
+    /// Everything in the queue, oldest first
+    pub fn get_jobs(&self) -> SqliteResult<Vec<QueuedJob>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, recording_id, status, attempts, last_error, queued_at, run_after, urgent FROM jobs ORDER BY id",
+        )?;
+        let jobs = stmt.query_map([], queued_job_from_row)?;
+        jobs.collect()
+    }
+
+    /// Version the next import of norms table `name` gets
+    /// Start `assignment`, or start it over if it was completed before
+    pub fn start_assignment(&self, assignment: &Assignment) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO assignments (id, title, passage, expected_duration_seconds, rubric_json, status,
+                 started_at, completed_at, reported_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, 'in_progress', ?6, NULL, NULL)",
+            rusqlite::params![
+                &assignment.id,
+                &assignment.title,
+                &assignment.passage,
+                assignment.expected_duration_seconds,
+                assignment.rubric.as_ref().and_then(|r| serde_json::to_string(r).ok()),
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_assignment(&self, id: &str) -> SqliteResult<Option<Assignment>> {
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM assignments WHERE id = ?1", ASSIGNMENT_COLUMNS))?;
+        let mut rows = stmt.query_map([id], assignment_from_row)?;
+        rows.next().transpose()
+    }
+
+    /// The assignment new recordings are being made for
+    pub fn get_active_assignment(&self) -> SqliteResult<Option<Assignment>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM assignments WHERE status = 'in_progress' ORDER BY started_at DESC LIMIT 1",
+            ASSIGNMENT_COLUMNS
+        ))?;
+        let mut rows = stmt.query_map([], assignment_from_row)?;
+        rows.next().transpose()
+    }
+
+    pub fn complete_assignment(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE assignments SET status = 'completed', completed_at = ?2 WHERE id = ?1",
+            (id, chrono::Utc::now().to_rfc3339()),
+        )?;
+        Ok(())
+    }
+
+    /// Drop an assignment nothing was recorded for
+    pub fn delete_assignment(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM assignments WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Completed assignments the server hasn't been told about
+    pub fn get_unreported_assignments(&self) -> SqliteResult<Vec<Assignment>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM assignments WHERE status = 'completed' AND reported_at IS NULL ORDER BY completed_at",
+            ASSIGNMENT_COLUMNS
+        ))?;
+        let assignments = stmt.query_map([], assignment_from_row)?;
+        assignments.collect()
+    }
+
+    pub fn mark_assignment_reported(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE assignments SET reported_at = ?2 WHERE id = ?1",
+            (id, chrono::Utc::now().to_rfc3339()),
+        )?;
+        Ok(())
+    }
+
+    pub fn get_assignment_recordings(&self, assignment_id: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE assignment_id = ?1 AND confidential = 0 ORDER BY sequence ASC",
+            RECORDING_COLUMNS
+        ))?;
+        let recordings = stmt.query_map([assignment_id], Recording::from_row)?;
+        recordings.collect()
+    }
+
+    pub fn get_assignment_recordings_including_confidential(&self, assignment_id: &str) -> SqliteResult<Vec<Recording>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM recordings WHERE assignment_id = ?1 ORDER BY sequence ASC",
+            RECORDING_COLUMNS
+        ))?;
+        let recordings = stmt.query_map([assignment_id], Recording::from_row)?;
+        recordings.collect()
+    }
+
+    pub fn next_norms_version(&self, name: &str) -> SqliteResult<u32> {
+        self.conn.query_row(
+            "SELECT COALESCE(MAX(version), 0) + 1 FROM norms_tables WHERE name = ?1",
+            [name],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn save_norms_table(&self, name: &str, version: u32, metric: &str, json: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO norms_tables (name, version, metric, table_json, imported_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![name, version, metric, json, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Imported norms tables, by name, newest version first
+    pub fn get_norms_versions(&self) -> SqliteResult<Vec<NormsVersion>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, version, metric, imported_at FROM norms_tables ORDER BY name, version DESC",
+        )?;
+        let versions = stmt.query_map([], |row| {
+            Ok(NormsVersion {
+                name: row.get(0)?,
+                version: row.get(1)?,
+                metric: row.get(2)?,
+                imported_at: row.get(3)?,
+            })
+        })?;
+        versions.collect()
+    }
+
+    pub fn get_norms_table_json(&self, name: &str, version: u32) -> SqliteResult<Option<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT table_json FROM norms_tables WHERE name = ?1 AND version = ?2")?;
+        let mut rows = stmt.query_map(rusqlite::params![name, version], |row| row.get(0))?;
+        rows.next().transpose()
+    }
+
+    /// Most recent jobs first
+    pub fn get_job_history(&self, limit: usize, filter: &JobFilter) -> SqliteResult<Vec<JobEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, kind, recording_id, started_at, duration_ms, succeeded, detail
+             FROM job_history
+             WHERE (?1 IS NULL OR kind = ?1)
+               AND (?2 IS NULL OR recording_id = ?2)
+               AND (?3 = 0 OR succeeded = 0)
+               AND (?4 IS NULL OR started_at >= ?4)
+             ORDER BY id DESC LIMIT ?5",
+        )?;
+
+        let jobs = stmt.query_map(
+            rusqlite::params![
+                filter.kind.map(JobKind::as_str),
+                filter.recording_id,
+                filter.failed_only as i32,
+                filter.since,
+                limit as i64,
+            ],
+            |row| {
+                let kind: String = row.get(1)?;
+                Ok(JobEntry {
+                    id: row.get(0)?,
+                    kind: JobKind::parse(&kind).ok_or_else(|| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            1,
+                            rusqlite::types::Type::Text,
+                            format!("unknown job kind {}", kind).into(),
+                        )
+                    })?,
+                    recording_id: row.get(2)?,
+                    started_at: row.get(3)?,
+                    duration_ms: row.get(4)?,
+                    succeeded: row.get::<_, i32>(5)? != 0,
+                    detail: row.get(6)?,
+                })
+            },
+        )?;
+
+        jobs.collect()
+    }
+}
+
+const ASSIGNMENT_COLUMNS: &str = "id, title, passage, expected_duration_seconds, rubric_json, status, started_at,
+     completed_at, reported_at";
+
+fn assignment_from_row(row: &Row) -> SqliteResult<Assignment> {
+    let rubric: Option<String> = row.get(4)?;
+    Ok(Assignment {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        passage: row.get(2)?,
+        expected_duration_seconds: row.get(3)?,
+        rubric: rubric.and_then(|r| serde_json::from_str(&r).ok()),
+        status: row.get(5)?,
+        started_at: row.get(6)?,
+        completed_at: row.get(7)?,
+        reported_at: row.get(8)?,
+    })
+}
+
+fn queued_job_from_row(row: &Row) -> SqliteResult<QueuedJob> {
+    Ok(QueuedJob {
+        id: row.get(0)?,
+        recording_id: row.get(1)?,
+        status: row.get(2)?,
+        attempts: row.get(3)?,
+        last_error: row.get(4)?,
+        queued_at: row.get(5)?,
+        run_after: row.get(6)?,
+        urgent: row.get(7)?,
+    })
+}
+
+fn assessment_from_row(row: &Row) -> SqliteResult<Assessment> {
+    let metrics: String = row.get(1)?;
+    let adjusted: Option<String> = row.get(5)?;
+    Ok(Assessment {
+        recording_id: row.get(0)?,
+        metrics: serde_json::from_str(&metrics).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        adjusted: adjusted.and_then(|a| serde_json::from_str(&a).ok()),
+        rubric_name: row.get(2)?,
+        level: row.get(3)?,
+        scored_at: row.get(4)?,
+    })
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> SqliteResult<()> {
+    if !column_exists(conn, table, column)? {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+    }
+    Ok(())
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> SqliteResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+    Ok(exists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_deletion_notices_survive_the_column_rename() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE recordings (
+                id TEXT PRIMARY KEY,
+                student_id TEXT NOT NULL,
+                audio_path TEXT NOT NULL,
+                transcript TEXT,
+                duration_seconds REAL,
+                recorded_at TEXT NOT NULL,
+                synced INTEGER DEFAULT 0,
+                audio_purged INTEGER DEFAULT 0,
+                deletion_notified INTEGER DEFAULT 0
+            );
+            INSERT INTO recordings
+                (id, student_id, audio_path, duration_seconds, recorded_at, synced, audio_purged, deletion_notified)
+            VALUES ('owed', 's', 'a.wav', 60, '2026-01-01T00:00:00Z', 1, 1, 0),
+                   ('told', 's', 'b.wav', 60, '2026-01-02T00:00:00Z', 1, 1, 1),
+                   ('kept', 's', 'c.wav', 60, '2026-01-03T00:00:00Z', 1, 0, 0);",
+        )
+        .unwrap();
+
+        let db = Database::open(conn).unwrap();
+        assert!(!column_exists(&db.conn, "recordings", "deletion_notified").unwrap());
+        let pending: Vec<String> = db.get_pending_deletion_notices().unwrap().into_iter().map(|r| r.id).collect();
+        assert_eq!(pending, ["owed"]);
+    }
+
+    #[test]
+    fn cached_waveforms_follow_the_audio_they_were_drawn_from() {
+        let db = Database::open(Connection::open_in_memory().unwrap()).unwrap();
+        let waveform = Waveform { duration_seconds: 1.0, min: vec![-0.5], max: vec![0.5] };
+        db.save_waveform("r", 1, Some("100:1"), &waveform).unwrap();
+
+        assert!(db.get_waveform("r", 1, Some("100:1")).unwrap().is_some());
+        // The audio was rewritten since
+        assert!(db.get_waveform("r", 1, Some("120:2")).unwrap().is_none());
+        // The audio is gone, so the cache is all there is
+        assert!(db.get_waveform("r", 1, None).unwrap().is_some());
+    }
 }
@@ -0,0 +1,145 @@
+use crate::db::{Database, JobFilter, JobKind, JOB_HISTORY_LIMIT};
+use crate::locale::Locale;
+use chrono::{Local, NaiveDate, NaiveTime, Utc};
+use rusqlite::Result as SqliteResult;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+/// Students recorded within this many days before the digest's date are
+/// expected to have a recording on it
+const ROSTER_DAYS: u64 = 14;
+
+/// One day's recordings, summed up for the teacher's nightly email
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyDigest {
+    pub date: NaiveDate,
+    pub recordings: usize,
+    /// Recordings that came out of the pipeline with a transcript
+    pub transcribed: usize,
+    pub low_confidence: Vec<LowConfidence>,
+    pub sync_failures: usize,
+    /// Students recorded in the `ROSTER_DAYS` before `date` but not on it
+    pub students_missing: Vec<String>,
+}
+
+/// A transcript whose segments averaged below the confidence threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct LowConfidence {
+    pub recording_id: String,
+    pub student_id: String,
+    pub recorded_at: String,
+    pub confidence: f64,
+}
+
+/// Start of a local day as a UTC timestamp that sorts with `recorded_at`
+fn day_start(date: NaiveDate) -> String {
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| date.and_time(NaiveTime::MIN).and_utc())
+        .to_rfc3339()
+}
+
+pub fn build(db: &Database, date: NaiveDate, confidence_threshold: f64) -> SqliteResult<DailyDigest> {
+    let next_day = date.succ_opt().unwrap_or(date);
+    let (start, end) = (day_start(date), day_start(next_day));
+    let recordings = db.get_recordings_between(&start, &end)?;
+
+    let mut low_confidence = Vec::new();
+    for recording in recordings.iter().filter(|r| r.transcript.is_some()) {
+        let confidences: Vec<f64> = db
+            .get_segments(&recording.id)?
+            .iter()
+            .filter_map(|s| s.confidence)
+            .collect();
+        if confidences.is_empty() {
+            continue;
+        }
+        let confidence = confidences.iter().sum::<f64>() / confidences.len() as f64;
+        if confidence < confidence_threshold {
+            low_confidence.push(LowConfidence {
+                recording_id: recording.id.clone(),
+                student_id: recording.student_id.clone(),
+                recorded_at: recording.recorded_at.clone(),
+                confidence,
+            });
+        }
+    }
+
+    let filter = JobFilter {
+        kind: Some(JobKind::Sync),
+        failed_only: true,
+        since: Some(start.clone()),
+        ..Default::default()
+    };
+    let sync_failures = db
+        .get_job_history(JOB_HISTORY_LIMIT as usize, &filter)?
+        .iter()
+        .filter(|job| job.started_at < end)
+        .count();
+
+    let seen: BTreeSet<&str> = recordings.iter().map(|r| r.student_id.as_str()).collect();
+    let roster_start = day_start(date - chrono::Days::new(ROSTER_DAYS));
+    let students_missing = db
+        .get_recordings_between(&roster_start, &start)?
+        .into_iter()
+        .map(|r| r.student_id)
+        .filter(|id| !seen.contains(id.as_str()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    Ok(DailyDigest {
+        date,
+        recordings: recordings.len(),
+        transcribed: recordings.iter().filter(|r| r.transcript.is_some()).count(),
+        low_confidence,
+        sync_failures,
+        students_missing,
+    })
+}
+
+/// Subject and plain-text body, with numbers and dates in `locale`
+pub fn render(digest: &DailyDigest, locale: &Locale) -> (String, String) {
+    let day = digest
+        .date
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|dt| locale.date(&dt))
+        .unwrap_or_else(|| digest.date.to_string());
+    let count = |n: usize| locale.number(n as f64, 0);
+
+    let subject = format!("Classroom Transcriber: {} recordings on {}", count(digest.recordings), day);
+    let mut body = String::new();
+    let _ = writeln!(body, "Recordings on {}", day);
+    let _ = writeln!(body);
+    let _ = writeln!(body, "Recorded: {}", count(digest.recordings));
+    let _ = writeln!(body, "Transcribed: {}", count(digest.transcribed));
+    let _ = writeln!(body, "Low-confidence transcripts: {}", count(digest.low_confidence.len()));
+    let _ = writeln!(body, "Sync failures: {}", count(digest.sync_failures));
+
+    if !digest.low_confidence.is_empty() {
+        let _ = writeln!(body);
+        let _ = writeln!(body, "Worth checking:");
+        for flagged in &digest.low_confidence {
+            let _ = writeln!(
+                body,
+                "  {}  {}  {}% confidence",
+                locale.timestamp(&flagged.recorded_at),
+                flagged.student_id,
+                locale.number(flagged.confidence * 100.0, 0)
+            );
+        }
+    }
+    if !digest.students_missing.is_empty() {
+        let _ = writeln!(body);
+        let _ = writeln!(body, "No recording on {}:", day);
+        for student_id in &digest.students_missing {
+            let _ = writeln!(body, "  {}", student_id);
+        }
+    }
+    (subject, body)
+}
@@ -0,0 +1,1011 @@
+// Signal processing helpers shared by export and capture
+
+use serde::{Deserialize, Serialize};
+
+/// EBU R128 programme loudness target
+pub const EBU_R128_TARGET_LUFS: f64 = -23.0;
+
+/// Peak ceiling applied after normalization (-1 dBFS)
+const PEAK_CEILING: f32 = 0.891;
+
+/// Direct form I biquad
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Biquad {
+    fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Self {
+        Self { b0, b1, b2, a1, a2, x1: 0.0, x2: 0.0, y1: 0.0, y2: 0.0 }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// ITU-R BS.1770 K-weighting (high shelf + high pass) for any sample rate
+fn k_weighting(sample_rate: u32) -> (Biquad, Biquad) {
+    let fs = sample_rate as f64;
+
+    // Stage 1: high shelf
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        (vh + vb * k / q + k * k) / a0,
+        2.0 * (k * k - vh) / a0,
+        (vh - vb * k / q + k * k) / a0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    );
+
+    // Stage 2: high pass
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        1.0,
+        -2.0,
+        1.0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    );
+
+    (shelf, high_pass)
+}
+
+/// Scale a mono signal to `target_lufs`, limiting the gain so the
+/// sample peak stays under -1 dBFS. Silent input is returned unchanged.
+pub fn normalize_loudness(samples: &[f32], sample_rate: u32, target_lufs: f64) -> Vec<f32> {
+    let mut meter = LoudnessMeter::new(sample_rate);
+    meter.push(samples);
+    let gain = meter.gain_to(target_lufs);
+    samples.iter().map(|&s| s * gain).collect()
+}
+
+/// BS.1770 loudness and sample peak measured a block at a time, so audio
+/// doesn't have to be held whole to be normalized
+pub struct LoudnessMeter {
+    shelf: Biquad,
+    high_pass: Biquad,
+    /// 100 ms of samples; gating blocks are four of these (400 ms, 75% overlap)
+    step: usize,
+    sum: f64,
+    filled: usize,
+    /// Mean square of each complete step
+    steps: Vec<f64>,
+    peak: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        let (shelf, high_pass) = k_weighting(sample_rate);
+        Self {
+            shelf,
+            high_pass,
+            step: sample_rate as usize / 10,
+            sum: 0.0,
+            filled: 0,
+            steps: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        for &s in samples {
+            self.peak = self.peak.max(s.abs());
+            let weighted = self.high_pass.process(self.shelf.process(s as f64));
+            self.sum += weighted * weighted;
+            self.filled += 1;
+            if self.filled == self.step {
+                self.steps.push(self.sum / self.step as f64);
+                self.sum = 0.0;
+                self.filled = 0;
+            }
+        }
+    }
+
+    /// Gated loudness in LUFS of everything pushed so far. `None` when
+    /// there's under 400 ms of audio or all of it is below the absolute gate.
+    pub fn integrated(&self) -> Option<f64> {
+        if self.step == 0 {
+            return None;
+        }
+        let powers: Vec<f64> = self.steps.windows(4).map(|w| w.iter().sum::<f64>() / 4.0).collect();
+
+        let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+
+        // Absolute gate at -70 LUFS
+        let above_absolute: Vec<f64> = powers.into_iter().filter(|&p| loudness(p) > -70.0).collect();
+        if above_absolute.is_empty() {
+            return None;
+        }
+
+        // Relative gate 10 LU below the ungated mean
+        let mean = above_absolute.iter().sum::<f64>() / above_absolute.len() as f64;
+        let relative_gate = loudness(mean) - 10.0;
+        let gated: Vec<f64> = above_absolute
+            .into_iter()
+            .filter(|&p| loudness(p) > relative_gate)
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+
+        Some(loudness(gated.iter().sum::<f64>() / gated.len() as f64))
+    }
+
+    /// Gain that brings what was measured to `target_lufs` while keeping the
+    /// sample peak under -1 dBFS; 1 for silence
+    pub fn gain_to(&self, target_lufs: f64) -> f32 {
+        let Some(current) = self.integrated() else {
+            return 1.0;
+        };
+        let gain = 10f64.powf((target_lufs - current) / 20.0) as f32;
+        if self.peak > 0.0 && self.peak * gain > PEAK_CEILING {
+            PEAK_CEILING / self.peak
+        } else {
+            gain
+        }
+    }
+}
+
+/// Frame length for finding where speech starts and ends (20 ms at 16kHz)
+const TRIM_FRAME: usize = 320;
+
+/// Sample range from the first to the last frame louder than
+/// `threshold_dbfs`, widened by `padding` samples. All-quiet input keeps
+/// everything so nothing is lost to a bad threshold.
+pub fn speech_bounds(samples: &[f32], threshold_dbfs: f32, padding: usize) -> std::ops::Range<usize> {
+    let threshold = 10f32.powf(threshold_dbfs / 20.0);
+    let loud = |frame: &[f32]| {
+        (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt() > threshold
+    };
+    let frames: Vec<bool> = samples.chunks(TRIM_FRAME).map(loud).collect();
+
+    match (frames.iter().position(|&l| l), frames.iter().rposition(|&l| l)) {
+        (Some(first), Some(last)) => {
+            let start = (first * TRIM_FRAME).saturating_sub(padding);
+            let end = ((last + 1) * TRIM_FRAME + padding).min(samples.len());
+            start..end
+        }
+        _ => 0..samples.len(),
+    }
+}
+
+/// Shortest run of loud frames taken as speech rather than a cough or a
+/// dropped pencil
+const MIN_SPEECH_SECONDS: f32 = 0.25;
+
+/// Whether `samples` hold a run of 20 ms frames louder than
+/// `threshold_dbfs` lasting at least `MIN_SPEECH_SECONDS`. Far cheaper than
+/// the model, so it decides which audio is worth sending to it.
+pub fn contains_speech(samples: &[f32], sample_rate: u32, threshold_dbfs: f32) -> bool {
+    let frame = (sample_rate as usize / 50).max(1);
+    let needed = ((MIN_SPEECH_SECONDS * 50.0).ceil() as usize).max(1);
+    let threshold = 10f32.powf(threshold_dbfs / 20.0);
+    let mut run = 0;
+    for f in samples.chunks(frame) {
+        let rms = (f.iter().map(|s| s * s).sum::<f32>() / f.len() as f32).sqrt();
+        run = if rms > threshold { run + 1 } else { 0 };
+        if run >= needed {
+            return true;
+        }
+    }
+    false
+}
+
+/// Frames at or below this fraction of the way up the sorted levels are
+/// the noise floor; at or above `SPEECH_PERCENTILE`, the speaker
+const NOISE_PERCENTILE: f32 = 0.1;
+const SPEECH_PERCENTILE: f32 = 0.9;
+/// How far above the noise floor a frame must be to count as speech
+const SPEECH_MARGIN_DB: f32 = 10.0;
+/// Frames quieter than this never count as speech, however clean the room
+const SPEECH_FLOOR_DBFS: f32 = -50.0;
+/// Level digital silence is counted at
+const SILENCE_DBFS: f32 = -100.0;
+
+/// Rough speech and background levels of a take
+#[derive(Debug, Clone, Copy)]
+pub struct SpeechEstimate {
+    /// Loud frames over the quiet ones, in dB
+    pub snr_db: f32,
+    /// Fraction of 20 ms frames loud enough to be speech
+    pub speech_ratio: f32,
+}
+
+/// Estimate from 20 ms frame levels, taking the quietest as background and
+/// the loudest as speech. Crude, but fast enough to run on every stop.
+pub fn estimate_speech(samples: &[f32], sample_rate: u32) -> SpeechEstimate {
+    let frame = (sample_rate as usize / 50).max(1);
+    let mut levels: Vec<f32> = samples
+        .chunks(frame)
+        .map(|f| {
+            let rms = (f.iter().map(|s| s * s).sum::<f32>() / f.len() as f32).sqrt();
+            (20.0 * rms.log10()).max(SILENCE_DBFS)
+        })
+        .collect();
+    if levels.is_empty() {
+        return SpeechEstimate { snr_db: 0.0, speech_ratio: 0.0 };
+    }
+    levels.sort_by(f32::total_cmp);
+    let at = |fraction: f32| levels[((levels.len() - 1) as f32 * fraction) as usize];
+    let noise = at(NOISE_PERCENTILE);
+    let threshold = (noise + SPEECH_MARGIN_DB).max(SPEECH_FLOOR_DBFS);
+    let speech = levels.iter().filter(|&&level| level > threshold).count();
+    SpeechEstimate {
+        snr_db: (at(SPEECH_PERCENTILE) - noise).max(0.0),
+        speech_ratio: speech as f32 / levels.len() as f32,
+    }
+}
+
+/// Level the capture AGC steers toward (-20 dBFS RMS)
+const AGC_TARGET_RMS: f32 = 0.1;
+const AGC_MIN_GAIN: f32 = 0.25;
+const AGC_MAX_GAIN: f32 = 8.0;
+/// Blocks below -60 dBFS are treated as silence and leave the gain alone,
+/// so pauses don't get pumped up into hiss
+const AGC_GATE_RMS: f32 = 0.001;
+
+/// Block-based automatic gain control for quiet classroom mics
+pub struct AutoGain {
+    gain: f32,
+}
+
+impl Default for AutoGain {
+    fn default() -> Self {
+        Self { gain: 1.0 }
+    }
+}
+
+impl AutoGain {
+    pub fn process(&mut self, block: &mut [f32]) {
+        if block.is_empty() {
+            return;
+        }
+        let rms = (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt();
+        if rms > AGC_GATE_RMS {
+            let desired = (AGC_TARGET_RMS / rms).clamp(AGC_MIN_GAIN, AGC_MAX_GAIN);
+            // Back off quickly when too loud, recover slowly
+            let rate = if desired < self.gain { 0.5 } else { 0.05 };
+            self.gain += (desired - self.gain) * rate;
+        }
+        for sample in block.iter_mut() {
+            *sample = (*sample * self.gain).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResampleQuality {
+    /// Linear interpolation; cheap but aliases when downsampling
+    Fast,
+    /// Windowed-sinc low-pass interpolation
+    #[default]
+    High,
+}
+
+/// Width of the block loops below. Keeping this many independent
+/// accumulators lets the compiler put them in SIMD registers.
+const LANES: usize = 8;
+
+/// Average interleaved frames down to mono, appending to `out`. A trailing
+/// partial frame is dropped.
+pub fn mix_down(interleaved: &[f32], channels: usize, out: &mut Vec<f32>) {
+    match channels.max(1) {
+        1 => out.extend_from_slice(interleaved),
+        // Stereo gets its own call so the frame width is a constant
+        2 => mix_frames(interleaved, 2, out),
+        n => mix_frames(interleaved, n, out),
+    }
+}
+
+/// Mix interleaved frames down to mono with a weight per channel, appending
+/// to `out`. Weights are normalized so the level matches `mix_down`;
+/// channels past the end of `weights` are left out, and if nothing is
+/// weighted the frames are averaged as usual.
+pub fn mix_weighted(interleaved: &[f32], channels: usize, weights: &[f32], out: &mut Vec<f32>) {
+    let channels = channels.max(1);
+    let mut scaled: Vec<f32> = (0..channels).map(|c| weights.get(c).copied().unwrap_or(0.0).max(0.0)).collect();
+    let total: f32 = scaled.iter().sum();
+    if total <= 0.0 {
+        return mix_down(interleaved, channels, out);
+    }
+    for weight in &mut scaled {
+        *weight /= total;
+    }
+    out.extend(
+        interleaved
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().zip(&scaled).map(|(s, w)| s * w).sum::<f32>()),
+    );
+}
+
+#[inline(always)]
+fn mix_frames(interleaved: &[f32], channels: usize, out: &mut Vec<f32>) {
+    let scale = 1.0 / channels as f32;
+    let mut blocks = interleaved.chunks_exact(LANES * channels);
+    out.reserve(interleaved.len() / channels);
+    for block in &mut blocks {
+        let mut mono = [0.0f32; LANES];
+        for c in 0..channels {
+            for (lane, sum) in mono.iter_mut().enumerate() {
+                *sum += block[lane * channels + c];
+            }
+        }
+        out.extend(mono.iter().map(|s| s * scale));
+    }
+    out.extend(
+        blocks
+            .remainder()
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() * scale),
+    );
+}
+
+/// Sum of products of two equal-length slices
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let (a, b) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+    let tail: f32 = a.remainder().iter().zip(b.remainder()).map(|(x, y)| x * y).sum();
+    let mut acc = [0.0f32; LANES];
+    for (x, y) in a.zip(b) {
+        for ((sum, x), y) in acc.iter_mut().zip(x).zip(y) {
+            *sum += x * y;
+        }
+    }
+    acc.iter().sum::<f32>() + tail
+}
+
+/// Streaming sample-rate converter fed mono samples
+pub enum Resampler {
+    Linear(LinearStream),
+    Sinc(SincResampler),
+}
+
+impl Resampler {
+    pub fn new(quality: ResampleQuality, from_rate: u32, to_rate: u32) -> Self {
+        match quality {
+            ResampleQuality::Fast => Self::Linear(LinearStream::new(from_rate, to_rate)),
+            ResampleQuality::High => Self::Sinc(SincResampler::new(from_rate, to_rate)),
+        }
+    }
+
+    /// Feed a run of input samples, appending any output samples they
+    /// complete. Blocks can be any size; the output doesn't depend on how
+    /// the input is split.
+    pub fn push_block(&mut self, block: &[f32], out: &mut Vec<f32>) {
+        match self {
+            Self::Linear(r) => r.push_block(block, out),
+            Self::Sinc(r) => r.push_block(block, out),
+        }
+    }
+
+    /// Emit the output samples still waiting on input that will never come
+    pub fn finish(&mut self, out: &mut Vec<f32>) {
+        match self {
+            Self::Linear(r) => r.finish(out),
+            Self::Sinc(r) => r.finish(out),
+        }
+    }
+}
+
+/// Linear interpolation between consecutive samples
+pub struct LinearStream {
+    ratio: f64,
+    /// Input position of the next output sample
+    next_out: f64,
+    prev: Option<f32>,
+    received: usize,
+}
+
+impl LinearStream {
+    fn new(from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            ratio: from_rate as f64 / to_rate as f64,
+            next_out: 0.0,
+            prev: None,
+            received: 0,
+        }
+    }
+
+    fn push_block(&mut self, block: &[f32], out: &mut Vec<f32>) {
+        out.reserve((block.len() as f64 / self.ratio) as usize + 1);
+        for &sample in block {
+            if let Some(prev) = self.prev {
+                let index = self.received as f64;
+                while self.next_out <= index {
+                    let frac = (self.next_out - (index - 1.0)) as f32;
+                    out.push(prev + (sample - prev) * frac);
+                    self.next_out += self.ratio;
+                }
+            }
+            self.prev = Some(sample);
+            self.received += 1;
+        }
+    }
+
+    fn finish(&mut self, out: &mut Vec<f32>) {
+        // A single sample never forms an interval
+        if self.received == 1 {
+            out.extend(self.prev);
+        }
+    }
+}
+
+/// Zero crossings of the sinc kernel kept on each side
+const SINC_ZERO_CROSSINGS: f64 = 16.0;
+/// Low-pass cutoff as a fraction of the lower Nyquist frequency
+const SINC_ROLLOFF: f64 = 0.95;
+/// Most distinct output phases worth precomputing kernels for. Common
+/// rates need few: 48kHz to 16kHz has one, 44.1kHz to 16kHz has 160.
+const MAX_KERNEL_PHASES: u64 = 1024;
+
+/// Filter taps for one output phase
+struct Kernel {
+    /// Offset of `taps[0]` from the input sample at or before the output
+    first: isize,
+    taps: Vec<f32>,
+}
+
+/// Blackman-windowed sinc interpolation, band-limited to the lower of the
+/// two Nyquist frequencies so downsampling doesn't alias
+pub struct SincResampler {
+    /// Rates divided by their greatest common divisor, so output `n` sits
+    /// at input position `n * from / to` exactly
+    from: u64,
+    to: u64,
+    /// Cutoff in cycles per input sample
+    cutoff: f64,
+    /// Kernel half-width in input samples
+    half_width: f64,
+    /// Kernels by phase (`n * from % to`), when there aren't too many
+    kernels: Vec<Kernel>,
+    history: Vec<f32>,
+    /// Input index of `history[0]`
+    history_start: usize,
+    received: usize,
+    /// Index of the next output sample
+    produced: u64,
+}
+
+impl SincResampler {
+    fn new(from_rate: u32, to_rate: u32) -> Self {
+        let divisor = gcd(from_rate.max(1) as u64, to_rate.max(1) as u64);
+        let (from, to) = (from_rate.max(1) as u64 / divisor, to_rate.max(1) as u64 / divisor);
+        let cutoff = 0.5 * SINC_ROLLOFF * (to as f64 / from as f64).min(1.0);
+        let mut resampler = Self {
+            from,
+            to,
+            cutoff,
+            half_width: SINC_ZERO_CROSSINGS / (2.0 * cutoff),
+            kernels: Vec::new(),
+            history: Vec::new(),
+            history_start: 0,
+            received: 0,
+            produced: 0,
+        };
+        if to <= MAX_KERNEL_PHASES {
+            resampler.kernels = (0..to).map(|phase| resampler.kernel(phase)).collect();
+        }
+        resampler
+    }
+
+    /// Input sample at or before the next output, and how far past it
+    fn next_position(&self) -> (usize, u64) {
+        let position = self.produced * self.from;
+        ((position / self.to) as usize, position % self.to)
+    }
+
+    fn push_block(&mut self, block: &[f32], out: &mut Vec<f32>) {
+        self.history.extend_from_slice(block);
+        self.received += block.len();
+        if self.received == 0 {
+            return;
+        }
+
+        let last = (self.received - 1) as f64;
+        loop {
+            let (base, phase) = self.next_position();
+            if base as f64 + phase as f64 / self.to as f64 + self.half_width > last {
+                break;
+            }
+            out.push(self.output_at(base, phase));
+            self.produced += 1;
+        }
+        self.drop_unreachable();
+    }
+
+    fn finish(&mut self, out: &mut Vec<f32>) {
+        if self.received == 0 {
+            return;
+        }
+        loop {
+            // Up to and including the last input sample
+            let (base, phase) = self.next_position();
+            if base + 1 > self.received || (base + 1 == self.received && phase != 0) {
+                break;
+            }
+            out.push(self.output_at(base, phase));
+            self.produced += 1;
+        }
+    }
+
+    /// Forget input no future output sample can reach. Only shifts the
+    /// buffer once there's at least as much to drop as to keep.
+    fn drop_unreachable(&mut self) {
+        let (base, phase) = self.next_position();
+        let t = base as f64 + phase as f64 / self.to as f64;
+        let keep_from = ((t - self.half_width).floor().max(0.0) as usize).min(self.received);
+        let drop = keep_from.saturating_sub(self.history_start);
+        if drop > 0 && drop >= self.history.len() - drop {
+            self.history.drain(..drop);
+            self.history_start += drop;
+        }
+    }
+
+    fn output_at(&self, base: usize, phase: u64) -> f32 {
+        let computed;
+        let kernel = match self.kernels.get(phase as usize) {
+            Some(kernel) => kernel,
+            None => {
+                computed = self.kernel(phase);
+                &computed
+            }
+        };
+
+        // Clip the kernel to the input that exists
+        let start = base as isize + kernel.first;
+        let from = start.max(self.history_start as isize);
+        let to = (start + kernel.taps.len() as isize).min(self.received as isize);
+        if to <= from {
+            return 0.0;
+        }
+        let taps = &kernel.taps[(from - start) as usize..(to - start) as usize];
+        let history = &self.history
+            [from as usize - self.history_start..to as usize - self.history_start];
+        dot(taps, history)
+    }
+
+    fn kernel(&self, phase: u64) -> Kernel {
+        let frac = phase as f64 / self.to as f64;
+        let first = (frac - self.half_width).ceil() as isize;
+        let last = (frac + self.half_width).floor() as isize;
+        let taps = (first..=last)
+            .map(|j| {
+                let d = frac - j as f64;
+                let x = 2.0 * self.cutoff * d;
+                let sinc = if x.abs() < 1e-9 {
+                    1.0
+                } else {
+                    (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+                };
+                let u = d / self.half_width;
+                let window = 0.42
+                    + 0.5 * (std::f64::consts::PI * u).cos()
+                    + 0.08 * (2.0 * std::f64::consts::PI * u).cos();
+                (2.0 * self.cutoff * sinc * window) as f32
+            })
+            .collect();
+        Kernel { first, taps }
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// STFT frame and hop for noise suppression (32 ms / 16 ms at 16 kHz)
+const GATE_FRAME: usize = 512;
+const GATE_HOP: usize = GATE_FRAME / 2;
+/// Bins this far above the noise floor (magnitude ratio) pass untouched
+const GATE_THRESHOLD: f32 = 2.0;
+/// Most a bin is attenuated (-20 dB), to keep the background natural
+const GATE_FLOOR: f32 = 0.1;
+
+/// Streaming spectral-gating noise suppressor. The per-bin noise floor
+/// tracks quiet frames quickly and rises slowly, so steady HVAC hum and fan
+/// noise are learned while speech passes through.
+pub struct SpectralGate {
+    window: Vec<f32>,
+    input: Vec<f32>,
+    overlap: Vec<f32>,
+    noise: Vec<f32>,
+    gains: Vec<f32>,
+    primed: bool,
+    /// Leading samples of output that only reflect the startup padding
+    skip: usize,
+    received: usize,
+    emitted: usize,
+}
+
+impl Default for SpectralGate {
+    fn default() -> Self {
+        // Square-root periodic Hann, applied on analysis and synthesis
+        let window = (0..GATE_FRAME)
+            .map(|n| {
+                (0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / GATE_FRAME as f32).cos()).sqrt()
+            })
+            .collect();
+        Self {
+            window,
+            input: vec![0.0; GATE_HOP],
+            overlap: vec![0.0; GATE_FRAME],
+            noise: vec![0.0; GATE_FRAME / 2 + 1],
+            gains: vec![1.0; GATE_FRAME / 2 + 1],
+            primed: false,
+            skip: GATE_HOP,
+            received: 0,
+            emitted: 0,
+        }
+    }
+}
+
+impl SpectralGate {
+    pub fn push(&mut self, sample: f32, out: &mut Vec<f32>) {
+        self.received += 1;
+        self.feed(sample, out);
+    }
+
+    /// Flush the samples still inside the analysis window
+    pub fn finish(&mut self, out: &mut Vec<f32>) {
+        while self.emitted < self.received {
+            self.feed(0.0, out);
+        }
+    }
+
+    fn feed(&mut self, sample: f32, out: &mut Vec<f32>) {
+        self.input.push(sample);
+        if self.input.len() == GATE_FRAME {
+            self.process_frame(out);
+            self.input.drain(..GATE_HOP);
+        }
+    }
+
+    fn process_frame(&mut self, out: &mut Vec<f32>) {
+        let mut re: Vec<f32> = self.input.iter().zip(&self.window).map(|(x, w)| x * w).collect();
+        let mut im = vec![0.0; GATE_FRAME];
+        fft(&mut re, &mut im, false);
+
+        for bin in 0..=GATE_FRAME / 2 {
+            let magnitude = (re[bin] * re[bin] + im[bin] * im[bin]).sqrt();
+            let noise = &mut self.noise[bin];
+            if !self.primed {
+                *noise = magnitude;
+            } else if magnitude < *noise {
+                *noise = 0.8 * *noise + 0.2 * magnitude;
+            } else {
+                *noise = (*noise * 1.005).min(magnitude);
+            }
+
+            let target = if magnitude > 0.0 {
+                (1.0 - (GATE_THRESHOLD * *noise / magnitude).powi(2)).max(GATE_FLOOR)
+            } else {
+                GATE_FLOOR
+            };
+            // Smooth over time to avoid musical-noise artifacts
+            let gain = &mut self.gains[bin];
+            *gain = 0.6 * *gain + 0.4 * target;
+
+            re[bin] *= *gain;
+            im[bin] *= *gain;
+            if bin != 0 && bin != GATE_FRAME / 2 {
+                re[GATE_FRAME - bin] *= *gain;
+                im[GATE_FRAME - bin] *= *gain;
+            }
+        }
+        self.primed = true;
+
+        fft(&mut re, &mut im, true);
+        for ((acc, value), w) in self.overlap.iter_mut().zip(&re).zip(&self.window) {
+            *acc += value * w;
+        }
+
+        // The first hop now has both overlapping frames and is final
+        for &value in &self.overlap[..GATE_HOP] {
+            if self.skip > 0 {
+                self.skip -= 1;
+            } else if self.emitted < self.received {
+                out.push(value);
+                self.emitted += 1;
+            }
+        }
+        self.overlap.copy_within(GATE_HOP.., 0);
+        self.overlap[GATE_FRAME - GATE_HOP..].fill(0.0);
+    }
+}
+
+/// NLMS step size; larger adapts faster but settles less deeply
+const ECHO_STEP: f32 = 0.5;
+/// Reference energy below which nothing is playing, so the microphone
+/// passes through untouched and the filter keeps what it learned
+const ECHO_SILENCE: f32 = 1e-6;
+/// Microphone samples louder than this multiple of the loudest echo the
+/// filter could produce are taken as someone talking over the playback.
+/// Adapting to them would teach the filter to cancel the speaker, so it
+/// holds still for `ECHO_HANGOVER_SECONDS`.
+const DOUBLE_TALK_RATIO: f32 = 1.5;
+const ECHO_HANGOVER_SECONDS: f32 = 0.05;
+/// Echo path gain below which the filter hasn't learned enough for
+/// double-talk to be told apart from echo
+const ECHO_SETTLED_GAIN: f32 = 0.01;
+
+/// Normalised LMS acoustic echo canceller. Learns how what the speakers
+/// play reaches the microphone and takes it back out, sample by sample.
+pub struct EchoCanceller {
+    weights: Vec<f32>,
+    /// Ring of reference samples stored twice over, so the latest `taps`
+    /// are always one contiguous slice, oldest first
+    history: Vec<f32>,
+    pos: usize,
+    /// Sum of squares of the reference in the window
+    energy: f32,
+    /// Decaying peak of the reference
+    peak: f32,
+    peak_decay: f32,
+    /// Sum of the filter's magnitudes, refreshed once per window
+    path_gain: f32,
+    hangover: usize,
+    hold: usize,
+}
+
+impl EchoCanceller {
+    /// Cancel echoes arriving up to `tail_seconds` after the sound played
+    pub fn new(sample_rate: u32, tail_seconds: f32) -> Self {
+        let taps = ((sample_rate as f32 * tail_seconds) as usize).max(1);
+        Self {
+            weights: vec![0.0; taps],
+            history: vec![0.0; taps * 2],
+            pos: 0,
+            energy: 0.0,
+            peak: 0.0,
+            peak_decay: 1.0 - 1.0 / taps as f32,
+            path_gain: 0.0,
+            hangover: (sample_rate as f32 * ECHO_HANGOVER_SECONDS) as usize,
+            hold: 0,
+        }
+    }
+
+    /// `mic` with the echo of the playback taken out; `reference` is the
+    /// sample the speakers played at the same moment
+    pub fn process(&mut self, mic: f32, reference: f32) -> f32 {
+        let taps = self.weights.len();
+        let leaving = self.history[self.pos];
+        self.history[self.pos] = reference;
+        self.history[self.pos + taps] = reference;
+        self.pos = (self.pos + 1) % taps;
+        self.energy = (self.energy + reference * reference - leaving * leaving).max(0.0);
+        self.peak = (self.peak * self.peak_decay).max(reference.abs());
+        if self.pos == 0 {
+            self.path_gain = self.weights.iter().map(|w| w.abs()).sum();
+        }
+        if self.energy < ECHO_SILENCE {
+            return mic;
+        }
+
+        let window = &self.history[self.pos..self.pos + taps];
+        let error = mic - dot(&self.weights, window);
+        if self.path_gain > ECHO_SETTLED_GAIN && mic.abs() > DOUBLE_TALK_RATIO * self.path_gain * self.peak {
+            self.hold = self.hangover;
+        }
+        if self.hold > 0 {
+            self.hold -= 1;
+        } else {
+            let step = ECHO_STEP * error / (self.energy + ECHO_SILENCE);
+            for (weight, x) in self.weights.iter_mut().zip(window) {
+                *weight += step * x;
+            }
+        }
+        error
+    }
+}
+
+/// In-place iterative radix-2 FFT; the length must be a power of two.
+/// The inverse transform is scaled by 1/n.
+fn fft(re: &mut [f32], im: &mut [f32], inverse: bool) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * std::f32::consts::PI / len as f32;
+        let (w_im, w_re) = angle.sin_cos();
+        for start in (0..n).step_by(len) {
+            let (mut cur_re, mut cur_im) = (1.0f32, 0.0f32);
+            for k in 0..len / 2 {
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im = cur_re * w_im + cur_im * w_re;
+                cur_re = next_re;
+            }
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        let scale = 1.0 / n as f32;
+        for (r, i) in re.iter_mut().zip(im.iter_mut()) {
+            *r *= scale;
+            *i *= scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequency: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * frequency * n as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    fn resample(quality: ResampleQuality, from: u32, to: u32, input: &[f32], block: usize) -> Vec<f32> {
+        let mut resampler = Resampler::new(quality, from, to);
+        let mut out = Vec::new();
+        for chunk in input.chunks(block.max(1)) {
+            resampler.push_block(chunk, &mut out);
+        }
+        resampler.finish(&mut out);
+        out
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32).sqrt()
+    }
+
+    #[test]
+    fn resampled_length_follows_the_rate_ratio() {
+        for quality in [ResampleQuality::Fast, ResampleQuality::High] {
+            for (from, to) in [(48_000, 16_000), (44_100, 16_000), (8_000, 16_000)] {
+                let out = resample(quality, from, to, &vec![0.1; from as usize], 4096);
+                let expected = to as usize;
+                assert!(out.len().abs_diff(expected) <= 1, "{:?} {}->{}: {}", quality, from, to, out.len());
+            }
+        }
+    }
+
+    #[test]
+    fn output_does_not_depend_on_block_size() {
+        let input = tone(440.0, 44_100, 20_000);
+        for quality in [ResampleQuality::Fast, ResampleQuality::High] {
+            let whole = resample(quality, 44_100, 16_000, &input, input.len());
+            for block in [1, 7, 100, 4097] {
+                assert_eq!(resample(quality, 44_100, 16_000, &input, block), whole, "{:?} block {}", quality, block);
+            }
+        }
+    }
+
+    #[test]
+    fn sinc_keeps_tones_below_nyquist() {
+        let out = resample(ResampleQuality::High, 48_000, 16_000, &tone(1_000.0, 48_000, 48_000), 4096);
+        let expected = tone(1_000.0, 16_000, out.len());
+        // Skip the edges, where the kernel runs off the input
+        let middle = 200..out.len() - 200;
+        let error: Vec<f32> = out[middle.clone()].iter().zip(&expected[middle]).map(|(a, b)| a - b).collect();
+        assert!(rms(&error) < 0.01, "error {}", rms(&error));
+    }
+
+    #[test]
+    fn sinc_removes_tones_above_nyquist() {
+        // 12kHz has no place at 16kHz and would alias to 4kHz
+        let out = resample(ResampleQuality::High, 48_000, 16_000, &tone(12_000.0, 48_000, 48_000), 4096);
+        assert!(rms(&out[200..out.len() - 200]) < 0.01);
+    }
+
+    #[test]
+    fn short_inputs_resample() {
+        for quality in [ResampleQuality::Fast, ResampleQuality::High] {
+            assert!(resample(quality, 48_000, 16_000, &[], 1).is_empty());
+            assert_eq!(resample(quality, 48_000, 16_000, &[0.25], 1).len(), 1);
+        }
+    }
+
+    #[test]
+    fn fft_of_an_impulse_is_flat() {
+        let mut re = vec![0.0; 64];
+        let mut im = vec![0.0; 64];
+        re[0] = 1.0;
+        fft(&mut re, &mut im, false);
+        assert!(re.iter().all(|r| (r - 1.0).abs() < 1e-6));
+        assert!(im.iter().all(|i| i.abs() < 1e-6));
+    }
+
+    #[test]
+    fn fft_puts_a_cosine_in_its_bin() {
+        let n = 256;
+        let angle = |k: usize| 2.0 * std::f32::consts::PI * 10.0 * k as f32 / n as f32;
+        let mut re: Vec<f32> = (0..n).map(|k| angle(k).cos()).collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im, false);
+        for bin in 0..n {
+            let magnitude = (re[bin] * re[bin] + im[bin] * im[bin]).sqrt();
+            let expected = if bin == 10 || bin == n - 10 { n as f32 / 2.0 } else { 0.0 };
+            assert!((magnitude - expected).abs() < 1e-3, "bin {}: {}", bin, magnitude);
+        }
+    }
+
+    #[test]
+    fn inverse_fft_undoes_forward() {
+        let original: Vec<f32> = (0..512).map(|k| ((k * 37 % 101) as f32 - 50.0) / 50.0).collect();
+        let mut re = original.clone();
+        let mut im = vec![0.0; re.len()];
+        fft(&mut re, &mut im, false);
+        fft(&mut re, &mut im, true);
+        for (got, want) in re.iter().zip(&original) {
+            assert!((got - want).abs() < 1e-4);
+        }
+        assert!(im.iter().all(|i| i.abs() < 1e-4));
+    }
+
+    #[test]
+    fn spectral_gate_quiets_steady_noise_and_keeps_tones() {
+        // Deterministic hiss from a linear congruential generator, with a
+        // tone over the second half
+        let mut state = 12345u32;
+        let tone = tone(1_000.0, 16_000, 48_000);
+        let input: Vec<f32> = (0..48_000)
+            .map(|n| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let hiss = ((state >> 16) as f32 / 32_768.0 - 1.0) * 0.05;
+                if n >= 24_000 { hiss + tone[n] } else { hiss }
+            })
+            .collect();
+        let mut gate = SpectralGate::default();
+        let mut out = Vec::new();
+        for &sample in &input {
+            gate.push(sample, &mut out);
+        }
+        gate.finish(&mut out);
+        assert_eq!(out.len(), input.len());
+
+        let (gated, hiss) = (rms(&out[8_000..24_000]), rms(&input[8_000..24_000]));
+        assert!(gated < 0.7 * hiss, "{} vs {}", gated, hiss);
+        let (gated, toned) = (rms(&out[32_000..]), rms(&tone[32_000..]));
+        assert!(gated > 0.9 * toned, "{} vs {}", gated, toned);
+    }
+}
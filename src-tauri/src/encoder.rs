@@ -0,0 +1,713 @@
+// Compressed storage for recordings. Only FLAC is implemented: Opus needs
+// libopus, which isn't bundled, and lossless FLAC keeps the audio identical
+// for re-transcription while roughly halving the size of 16-bit speech.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EncoderError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Invalid FLAC stream: {0}")]
+    InvalidFlac(&'static str),
+    #[error("Unsupported FLAC stream: {0}")]
+    Unsupported(&'static str),
+    #[error("FLAC written to {0} doesn't decode to the audio encoded")]
+    Mismatch(String),
+}
+
+/// File format a recording's audio is stored in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    #[default]
+    Wav,
+    Flac,
+}
+
+impl AudioFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "wav" => Some(AudioFormat::Wav),
+            "flac" => Some(AudioFormat::Flac),
+            _ => None,
+        }
+    }
+
+    /// Format implied by a file's extension, WAV unless it ends in `.flac`
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("flac") => AudioFormat::Flac,
+            _ => AudioFormat::Wav,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        self.as_str()
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "audio/wav",
+            AudioFormat::Flac => "audio/flac",
+        }
+    }
+}
+
+/// Samples per FLAC frame
+const FLAC_BLOCK_SIZE: usize = 4096;
+/// Highest Rice parameter; 15 is the escape code
+const MAX_RICE_PARAM: u32 = 14;
+/// Highest fixed-predictor order FLAC defines
+const MAX_FIXED_ORDER: usize = 4;
+
+/// Write mono 16-bit samples as FLAC, using fixed predictors and a single
+/// Rice partition per frame. The file is read back and checked against
+/// `samples`, and removed if it doesn't match, so the audio it replaces is
+/// never deleted for a bad copy.
+pub fn write_flac(samples: &[i16], sample_rate: u32, path: &Path) -> Result<(), EncoderError> {
+    std::fs::write(path, encode_flac(samples, sample_rate))?;
+    let verified = verify_flac(path, samples);
+    if verified.is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+    verified
+}
+
+/// Decode the FLAC at `path` and check it holds exactly `expected`: the
+/// same number of samples, with the same checksum
+pub fn verify_flac(path: &Path, expected: &[i16]) -> Result<(), EncoderError> {
+    let mismatch = || EncoderError::Mismatch(path.display().to_string());
+    let mut reader = FlacReader::open(path)?;
+    if reader.total_samples() != expected.len() as u64 {
+        return Err(mismatch());
+    }
+    let mut decoded = Sha256::new();
+    let mut count = 0usize;
+    let mut block = Vec::new();
+    while reader.next_block(&mut block)? {
+        for sample in block.drain(..) {
+            decoded.update(((sample * 32768.0).round() as i16).to_le_bytes());
+            count += 1;
+        }
+    }
+    let mut original = Sha256::new();
+    for sample in expected {
+        original.update(sample.to_le_bytes());
+    }
+    if count != expected.len() || decoded.finalize() != original.finalize() {
+        return Err(mismatch());
+    }
+    Ok(())
+}
+
+/// The FLAC stream `write_flac` writes, in memory
+pub fn encode_flac(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(samples.len());
+    out.extend_from_slice(b"fLaC");
+
+    let mut info = BitWriter::default();
+    info.write(1, 1); // last metadata block
+    info.write(0, 7); // STREAMINFO
+    info.write(34, 24);
+    info.write(FLAC_BLOCK_SIZE as u64, 16);
+    info.write(FLAC_BLOCK_SIZE as u64, 16);
+    info.write(0, 24); // frame sizes unknown
+    info.write(0, 24);
+    info.write(sample_rate as u64, 20);
+    info.write(0, 3); // one channel
+    info.write(15, 5); // 16 bits per sample
+    info.write(samples.len() as u64, 36);
+    info.write(0, 64); // MD5 not computed
+    info.write(0, 64);
+    out.extend(info.finish());
+
+    for (number, block) in samples.chunks(FLAC_BLOCK_SIZE).enumerate() {
+        encode_frame(number as u64, block, &mut out);
+    }
+    out
+}
+
+fn encode_frame(number: u64, block: &[i16], out: &mut Vec<u8>) {
+    let mut frame = BitWriter::default();
+    frame.write(0b11_1111_1111_1110, 14); // sync code
+    frame.write(0, 1);
+    frame.write(0, 1); // fixed block size
+    frame.write(0b0111, 4); // block size follows as 16 bits
+    frame.write(0, 4); // sample rate from STREAMINFO
+    frame.write(0, 4); // mono
+    frame.write(0b100, 3); // 16 bits per sample
+    frame.write(0, 1);
+    write_coded_number(&mut frame, number);
+    frame.write(block.len() as u64 - 1, 16);
+    let crc = crc8(&frame.bytes);
+    frame.write(crc as u64, 8);
+
+    let samples: Vec<i64> = block.iter().map(|&s| s as i64).collect();
+    write_subframe(&mut frame, &samples);
+
+    frame.align();
+    let crc = crc16(&frame.bytes);
+    frame.write(crc as u64, 16);
+    out.extend(frame.finish());
+}
+
+fn write_subframe(w: &mut BitWriter, samples: &[i64]) {
+    w.write(0, 1);
+    if samples.iter().all(|&s| s == samples[0]) {
+        w.write(0b000000, 6); // CONSTANT
+        w.write(0, 1);
+        w.write(samples[0] as u64, 16);
+        return;
+    }
+
+    // Pick the fixed order whose residual codes smallest
+    let mut best: Option<(usize, Vec<i64>, u32, u64)> = None;
+    let mut residual = samples.to_vec();
+    for order in 0..=MAX_FIXED_ORDER.min(samples.len()) {
+        if order > 0 {
+            residual = residual.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        }
+        let (param, bits) = rice_cost(&residual);
+        let bits = bits + order as u64 * 16 + 10;
+        if best.as_ref().is_none_or(|b| bits < b.3) {
+            best = Some((order, residual.clone(), param, bits));
+        }
+    }
+
+    match best {
+        Some((order, residual, param, bits)) if bits < samples.len() as u64 * 16 => {
+            w.write(0b001000 | order as u64, 6); // FIXED
+            w.write(0, 1);
+            for &sample in &samples[..order] {
+                w.write(sample as u64, 16);
+            }
+            w.write(0, 2); // Rice, 4-bit parameters
+            w.write(0, 4); // one partition
+            w.write(param as u64, 4);
+            for &value in &residual {
+                let folded = fold(value);
+                w.write_unary(folded >> param);
+                w.write(folded, param);
+            }
+        }
+        _ => {
+            w.write(0b000001, 6); // VERBATIM
+            w.write(0, 1);
+            for &sample in samples {
+                w.write(sample as u64, 16);
+            }
+        }
+    }
+}
+
+/// Zig-zag a signed residual into the unsigned value Rice codes
+fn fold(value: i64) -> u64 {
+    if value >= 0 {
+        (value as u64) << 1
+    } else {
+        ((-value as u64) << 1) - 1
+    }
+}
+
+fn unfold(value: u64) -> i64 {
+    if value & 1 == 0 {
+        (value >> 1) as i64
+    } else {
+        -((value >> 1) as i64) - 1
+    }
+}
+
+/// Best Rice parameter for `residual` and the bits it takes, estimated from
+/// the mean and refined against its neighbour
+fn rice_cost(residual: &[i64]) -> (u32, u64) {
+    if residual.is_empty() {
+        return (0, 0);
+    }
+    let folded: Vec<u64> = residual.iter().map(|&r| fold(r)).collect();
+    let mean = folded.iter().sum::<u64>() / folded.len() as u64;
+    let guess = (64 - mean.leading_zeros()).min(MAX_RICE_PARAM);
+    let cost = |param: u32| -> u64 {
+        folded.iter().map(|&u| (u >> param) + 1 + param as u64).sum()
+    };
+    (guess.saturating_sub(1)..=guess)
+        .map(|param| (param, cost(param)))
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((guess, cost(guess)))
+}
+
+/// Frame numbers use the UTF-8 style variable-length encoding
+fn write_coded_number(w: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        w.write(value, 8);
+        return;
+    }
+    let mut bytes = 2;
+    while value >= 1u64 << (5 * bytes + 1) {
+        bytes += 1;
+    }
+    let lead = (0xFF00u64 >> bytes) & 0xFF;
+    w.write(lead | (value >> (6 * (bytes - 1))), 8);
+    for i in (0..bytes - 1).rev() {
+        w.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+    }
+}
+
+fn read_coded_number(r: &mut BitReader) -> Result<u64, EncoderError> {
+    let first = r.read(8)?;
+    let bytes = (first as u8).leading_ones();
+    match bytes {
+        0 => Ok(first),
+        1 | 8 => Err(EncoderError::InvalidFlac("bad frame number")),
+        _ => {
+            let mut value = first & (0x7F >> bytes);
+            for _ in 1..bytes {
+                value = (value << 6) | (r.read(8)? & 0x3F);
+            }
+            Ok(value)
+        }
+    }
+}
+
+/// A FLAC file decoded one frame at a time, so only the compressed bytes
+/// and a single frame are ever in memory
+pub struct FlacReader {
+    bytes: Vec<u8>,
+    /// Bit position of the next frame
+    pos: usize,
+    sample_rate: u32,
+    bits_per_sample: u32,
+    /// From STREAMINFO; 0 when the encoder didn't know
+    total_samples: u64,
+}
+
+impl FlacReader {
+    pub fn open(path: &Path) -> Result<Self, EncoderError> {
+        let bytes = std::fs::read(path)?;
+        if !bytes.starts_with(b"fLaC") {
+            return Err(EncoderError::InvalidFlac("missing fLaC marker"));
+        }
+        let mut reader = BitReader::new(&bytes);
+        reader.skip(32)?;
+
+        let mut info = None;
+        loop {
+            let last = reader.read(1)? == 1;
+            let kind = reader.read(7)?;
+            let length = reader.read(24)? as usize;
+            if kind == 0 {
+                reader.skip(16 + 16 + 24 + 24)?;
+                let sample_rate = reader.read(20)? as u32;
+                reader.skip(3)?;
+                let bits_per_sample = reader.read(5)? as u32 + 1;
+                let total_samples = reader.read(36)?;
+                reader.skip(128)?;
+                info = Some((sample_rate, bits_per_sample, total_samples));
+            } else {
+                reader.skip(length * 8)?;
+            }
+            if last {
+                break;
+            }
+        }
+        let (sample_rate, bits_per_sample, total_samples) =
+            info.ok_or(EncoderError::InvalidFlac("missing STREAMINFO"))?;
+        let pos = reader.pos;
+
+        Ok(Self { bytes, pos, sample_rate, bits_per_sample, total_samples })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length in samples per channel
+    pub fn total_samples(&self) -> u64 {
+        self.total_samples
+    }
+
+    /// Decode the next frame, appending it to `out` as mono. Returns false
+    /// once the stream is exhausted.
+    pub fn next_block(&mut self, out: &mut Vec<f32>) -> Result<bool, EncoderError> {
+        let mut reader = BitReader { bytes: &self.bytes, pos: self.pos };
+        if reader.is_empty() {
+            return Ok(false);
+        }
+        let channels = decode_frame(&mut reader, self.bits_per_sample)?;
+        self.pos = reader.pos;
+
+        let scale = channels.len() as f32 * (1i64 << (self.bits_per_sample - 1)) as f32;
+        for i in 0..channels[0].len() {
+            let sum: i64 = channels.iter().map(|c| c[i]).sum();
+            out.push(sum as f32 / scale);
+        }
+        Ok(true)
+    }
+}
+
+fn decode_frame(r: &mut BitReader, stream_bits: u32) -> Result<Vec<Vec<i64>>, EncoderError> {
+    if r.read(14)? != 0b11_1111_1111_1110 {
+        return Err(EncoderError::InvalidFlac("lost frame sync"));
+    }
+    r.skip(2)?;
+    let block_code = r.read(4)?;
+    let rate_code = r.read(4)?;
+    let assignment = r.read(4)?;
+    let size_code = r.read(3)?;
+    r.skip(1)?;
+    read_coded_number(r)?;
+
+    let block_size = match block_code {
+        0 => return Err(EncoderError::InvalidFlac("reserved block size")),
+        1 => 192,
+        2..=5 => 576 << (block_code - 2),
+        6 => r.read(8)? as usize + 1,
+        7 => r.read(16)? as usize + 1,
+        _ => 256 << (block_code - 8),
+    };
+    match rate_code {
+        12 => r.skip(8)?,
+        13 | 14 => r.skip(16)?,
+        15 => return Err(EncoderError::InvalidFlac("reserved sample rate")),
+        _ => {}
+    }
+    let bits = match size_code {
+        0 => stream_bits,
+        1 => 8,
+        2 => 12,
+        4 => 16,
+        5 => 20,
+        6 => 24,
+        7 => 32,
+        _ => return Err(EncoderError::InvalidFlac("reserved sample size")),
+    };
+    r.skip(8)?; // header CRC
+
+    if assignment > 7 {
+        return Err(EncoderError::Unsupported("stereo decorrelation"));
+    }
+    let channels = (0..=assignment)
+        .map(|_| decode_subframe(r, block_size, bits))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    r.align();
+    r.skip(16)?; // frame CRC
+    Ok(channels)
+}
+
+fn decode_subframe(r: &mut BitReader, block_size: usize, bits: u32) -> Result<Vec<i64>, EncoderError> {
+    r.skip(1)?;
+    let kind = r.read(6)?;
+    let wasted = if r.read(1)? == 1 { r.read_unary()? as u32 + 1 } else { 0 };
+    let bits = bits
+        .checked_sub(wasted)
+        .ok_or(EncoderError::InvalidFlac("too many wasted bits"))?;
+
+    let mut samples = match kind {
+        0 => vec![r.read_signed(bits)?; block_size],
+        1 => (0..block_size)
+            .map(|_| r.read_signed(bits))
+            .collect::<Result<_, _>>()?,
+        8..=12 => {
+            let coefficients: &[i64] = match kind - 8 {
+                0 => &[],
+                1 => &[1],
+                2 => &[2, -1],
+                3 => &[3, -3, 1],
+                _ => &[4, -6, 4, -1],
+            };
+            let warmup = (0..coefficients.len())
+                .map(|_| r.read_signed(bits))
+                .collect::<Result<_, _>>()?;
+            let residual = read_residual(r, block_size, coefficients.len())?;
+            restore(warmup, coefficients, 0, residual)
+        }
+        32..=63 => {
+            let order = (kind - 31) as usize;
+            let warmup = (0..order)
+                .map(|_| r.read_signed(bits))
+                .collect::<Result<_, _>>()?;
+            let precision = r.read(4)? as u32 + 1;
+            if precision == 16 {
+                return Err(EncoderError::InvalidFlac("reserved LPC precision"));
+            }
+            let shift = u32::try_from(r.read_signed(5)?)
+                .map_err(|_| EncoderError::Unsupported("negative LPC shift"))?;
+            let coefficients: Vec<i64> = (0..order)
+                .map(|_| r.read_signed(precision))
+                .collect::<Result<_, _>>()?;
+            let residual = read_residual(r, block_size, order)?;
+            restore(warmup, &coefficients, shift, residual)
+        }
+        _ => return Err(EncoderError::InvalidFlac("reserved subframe type")),
+    };
+
+    if wasted > 0 {
+        for sample in &mut samples {
+            *sample <<= wasted;
+        }
+    }
+    Ok(samples)
+}
+
+fn read_residual(r: &mut BitReader, block_size: usize, order: usize) -> Result<Vec<i64>, EncoderError> {
+    let param_bits = match r.read(2)? {
+        0 => 4,
+        1 => 5,
+        _ => return Err(EncoderError::InvalidFlac("reserved residual coding")),
+    };
+    let partition_order = r.read(4)? as u32;
+    let partition_size = block_size >> partition_order;
+
+    let mut residual = Vec::with_capacity(block_size);
+    for partition in 0..1usize << partition_order {
+        let count = if partition == 0 {
+            partition_size
+                .checked_sub(order)
+                .ok_or(EncoderError::InvalidFlac("partition shorter than predictor"))?
+        } else {
+            partition_size
+        };
+        let param = r.read(param_bits)? as u32;
+        if param == (1 << param_bits) - 1 {
+            let raw_bits = r.read(5)? as u32;
+            for _ in 0..count {
+                residual.push(r.read_signed(raw_bits)?);
+            }
+        } else {
+            for _ in 0..count {
+                let quotient = r.read_unary()?;
+                residual.push(unfold((quotient << param) | r.read(param)?));
+            }
+        }
+    }
+    Ok(residual)
+}
+
+/// Undo linear prediction: each sample is its residual plus the prediction
+/// from the samples before it
+fn restore(warmup: Vec<i64>, coefficients: &[i64], shift: u32, residual: Vec<i64>) -> Vec<i64> {
+    let mut samples = warmup;
+    samples.reserve(residual.len());
+    for value in residual {
+        let n = samples.len();
+        let prediction: i64 = coefficients
+            .iter()
+            .enumerate()
+            .map(|(j, c)| c * samples[n - 1 - j])
+            .sum();
+        samples.push(value + (prediction >> shift));
+    }
+    samples
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in bytes {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    pending: u32,
+}
+
+impl BitWriter {
+    /// Append the low `bits` bits of `value`, most significant first
+    fn write(&mut self, value: u64, bits: u32) {
+        if bits > 32 {
+            self.write(value >> 32, bits - 32);
+            self.write(value, 32);
+            return;
+        }
+        let value = value & ((1u64 << bits) - 1);
+        self.acc = (self.acc << bits) | value;
+        self.pending += bits;
+        while self.pending >= 8 {
+            self.pending -= 8;
+            self.bytes.push((self.acc >> self.pending) as u8);
+        }
+    }
+
+    fn write_unary(&mut self, mut zeros: u64) {
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros as u32 + 1);
+    }
+
+    fn align(&mut self) {
+        if self.pending > 0 {
+            self.write(0, 8 - self.pending);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// Position in bits
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len() * 8
+    }
+
+    fn read(&mut self, mut bits: u32) -> Result<u64, EncoderError> {
+        if self.pos + bits as usize > self.bytes.len() * 8 {
+            return Err(EncoderError::InvalidFlac("unexpected end of stream"));
+        }
+        let mut value = 0u64;
+        while bits > 0 {
+            let byte = self.bytes[self.pos / 8] as u64;
+            let available = 8 - (self.pos % 8) as u32;
+            let take = available.min(bits);
+            value = (value << take) | ((byte >> (available - take)) & ((1 << take) - 1));
+            self.pos += take as usize;
+            bits -= take;
+        }
+        Ok(value)
+    }
+
+    fn read_signed(&mut self, bits: u32) -> Result<i64, EncoderError> {
+        if bits == 0 {
+            return Ok(0);
+        }
+        let value = self.read(bits)?;
+        Ok(((value << (64 - bits)) as i64) >> (64 - bits))
+    }
+
+    /// Count zero bits up to the terminating one
+    fn read_unary(&mut self) -> Result<u64, EncoderError> {
+        let mut zeros = 0;
+        while self.read(1)? == 0 {
+            zeros += 1;
+        }
+        Ok(zeros)
+    }
+
+    fn skip(&mut self, bits: usize) -> Result<(), EncoderError> {
+        if self.pos + bits > self.bytes.len() * 8 {
+            return Err(EncoderError::InvalidFlac("unexpected end of stream"));
+        }
+        self.pos += bits;
+        Ok(())
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("encoder-test-{}-{}.flac", name, uuid::Uuid::new_v4()))
+    }
+
+    /// Write `samples` as FLAC, which checks itself, then decode it again
+    fn round_trip(samples: &[i16]) -> Vec<i16> {
+        let path = scratch("round-trip");
+        write_flac(samples, 16_000, &path).expect("FLAC written and verified");
+        let mut reader = FlacReader::open(&path).unwrap();
+        assert_eq!(reader.sample_rate(), 16_000);
+        assert_eq!(reader.total_samples(), samples.len() as u64);
+        let mut decoded = Vec::new();
+        while reader.next_block(&mut decoded).unwrap() {}
+        let _ = std::fs::remove_file(&path);
+        decoded.into_iter().map(|s| (s * 32768.0).round() as i16).collect()
+    }
+
+    #[test]
+    fn silence_round_trips() {
+        let samples = vec![0i16; 3 * FLAC_BLOCK_SIZE];
+        assert_eq!(round_trip(&samples), samples);
+    }
+
+    #[test]
+    fn full_scale_round_trips() {
+        let square: Vec<i16> = (0..10_000).map(|i| if i % 2 == 0 { i16::MAX } else { i16::MIN }).collect();
+        assert_eq!(round_trip(&square), square);
+        let ramp: Vec<i16> = (i16::MIN..=i16::MAX).step_by(3).collect();
+        assert_eq!(round_trip(&ramp), ramp);
+    }
+
+    #[test]
+    fn odd_block_sizes_round_trip() {
+        for len in [1, 2, 3, 5, FLAC_BLOCK_SIZE - 1, FLAC_BLOCK_SIZE + 1, 2 * FLAC_BLOCK_SIZE + 17] {
+            let samples: Vec<i16> = (0..len).map(|i| ((i as f64 * 0.05).sin() * 12_000.0) as i16).collect();
+            assert_eq!(round_trip(&samples), samples, "{} samples", len);
+        }
+    }
+
+    #[test]
+    fn empty_input_round_trips() {
+        assert!(round_trip(&[]).is_empty());
+    }
+
+    #[test]
+    fn corrupted_file_fails_verification() {
+        let samples: Vec<i16> = (0..5_000).map(|i| (i * 7 % 2_000) as i16 - 1_000).collect();
+        let path = scratch("corrupt");
+        write_flac(&samples, 16_000, &path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 10;
+        bytes[last] ^= 0x55;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(verify_flac(&path, &samples).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn wrong_expectation_fails_verification() {
+        let samples = vec![100i16; 2_000];
+        let path = scratch("expected");
+        write_flac(&samples, 16_000, &path).unwrap();
+        assert!(verify_flac(&path, &samples[..1_999]).is_err());
+        let mut changed = samples.clone();
+        changed[1_000] = 101;
+        assert!(verify_flac(&path, &changed).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,659 @@
+use crate::db::{Assessment, Recording};
+use crate::locale::Locale;
+use crate::smtp::base64;
+use crate::speakers::SpeakerStats;
+use crate::whisper::{join_segments, TranscriptSegment};
+use chrono::{DateTime, Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+const STYLE: &str = "body{font-family:-apple-system,Segoe UI,sans-serif;margin:2rem;color:#222}
+table{border-collapse:collapse;width:100%}th,td{border-bottom:1px solid #ddd;padding:6px 8px;text-align:left;vertical-align:top}
+th{background:#f5f5f5}.muted{color:#888}.chart rect{fill:#4f7cff}.chart text{font-size:10px;fill:#555}
+a{color:#2750d8}";
+
+/// Write `index.html` plus one page per student into `folder`, with numbers
+/// and dates in `locale`. Returns the path of the index page.
+pub fn export_dashboard(recordings: &[Recording], folder: &Path, locale: &Locale) -> Result<PathBuf, ExportError> {
+    std::fs::create_dir_all(folder)?;
+
+    let mut by_student: BTreeMap<&str, Vec<&Recording>> = BTreeMap::new();
+    for rec in recordings {
+        by_student.entry(rec.student_id.as_str()).or_default().push(rec);
+    }
+
+    // Index: summary per student plus the full recordings table
+    let mut body = String::new();
+    let total_minutes: f64 = recordings.iter().map(|r| r.duration_seconds).sum::<f64>() / 60.0;
+    let _ = write!(
+        body,
+        "<h1>Classroom Transcriber Report</h1><p class=\"muted\">Generated {} &middot; {} recordings &middot; {} minutes</p>",
+        escape_html(&locale.datetime(&chrono::Local::now())),
+        escape_html(&locale.number(recordings.len() as f64, 0)),
+        escape_html(&locale.number(total_minutes, 1))
+    );
+
+    body.push_str("<h2>Students</h2><table><tr><th>Student</th><th>Recordings</th><th>Minutes</th><th>Last recorded</th></tr>");
+    for (student_id, recs) in &by_student {
+        let minutes: f64 = recs.iter().map(|r| r.duration_seconds).sum::<f64>() / 60.0;
+        let last = recs.iter().map(|r| r.recorded_at.as_str()).max().unwrap_or("");
+        let _ = write!(
+            body,
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            student_page_name(student_id),
+            escape_html(student_id),
+            escape_html(&locale.number(recs.len() as f64, 0)),
+            escape_html(&locale.number(minutes, 1)),
+            escape_html(&locale.timestamp(last))
+        );
+    }
+    body.push_str("</table>");
+
+    body.push_str("<h2>Minutes recorded per week</h2>");
+    body.push_str(&weekly_chart(recordings.iter(), locale));
+
+    body.push_str("<h2>All recordings</h2>");
+    body.push_str(&recordings_table(recordings.iter(), true, locale));
+
+    std::fs::write(folder.join("index.html"), page("Classroom Transcriber Report", &body))?;
+
+    // Per-student pages
+    for (student_id, recs) in &by_student {
+        let mut body = String::new();
+        let _ = write!(
+            body,
+            "<p><a href=\"index.html\">&larr; All students</a></p><h1>{}</h1>",
+            escape_html(student_id)
+        );
+        body.push_str("<h2>Progress</h2>");
+        body.push_str(&weekly_chart(recs.iter().copied(), locale));
+        body.push_str("<h2>Recordings</h2>");
+        body.push_str(&recordings_table(recs.iter().copied(), false, locale));
+
+        std::fs::write(
+            folder.join(student_page_name(student_id)),
+            page(student_id, &body),
+        )?;
+    }
+
+    Ok(folder.join("index.html"))
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title><style>{}</style></head><body>{}</body></html>",
+        escape_html(title),
+        STYLE,
+        body
+    )
+}
+
+fn recordings_table<'a>(
+    recordings: impl Iterator<Item = &'a Recording>,
+    show_student: bool,
+    locale: &Locale,
+) -> String {
+    let mut html = String::from("<table><tr><th>Date</th>");
+    if show_student {
+        html.push_str("<th>Student</th>");
+    }
+    html.push_str("<th>Duration</th><th>Status</th><th>Transcript</th></tr>");
+
+    for rec in recordings {
+        let _ = write!(html, "<tr><td>{}</td>", escape_html(&locale.timestamp(&rec.recorded_at)));
+        if show_student {
+            let _ = write!(
+                html,
+                "<td><a href=\"{}\">{}</a></td>",
+                student_page_name(&rec.student_id),
+                escape_html(&rec.student_id)
+            );
+        }
+        let _ = write!(
+            html,
+            "<td>{}</td><td>{}</td><td>{}</td></tr>",
+            locale.duration(rec.duration_seconds),
+            escape_html(&rec.review_status),
+            match (&rec.transcript, &rec.translation) {
+                (Some(t), Some(english)) => format!(
+                    "{}<br><span class=\"muted\">English: {}</span>",
+                    escape_html(t),
+                    escape_html(english)
+                ),
+                (Some(t), None) => escape_html(t),
+                (None, _) => "<span class=\"muted\">Not transcribed</span>".to_string(),
+            }
+        );
+    }
+    html.push_str("</table>");
+    html
+}
+
+/// Inline SVG bar chart of recorded minutes per ISO week
+fn weekly_chart<'a>(recordings: impl Iterator<Item = &'a Recording>, locale: &Locale) -> String {
+    let mut weeks: BTreeMap<(i32, u32), f64> = BTreeMap::new();
+    for rec in recordings {
+        if let Ok(dt) = DateTime::parse_from_rfc3339(&rec.recorded_at) {
+            let week = dt.iso_week();
+            *weeks.entry((week.year(), week.week())).or_default() += rec.duration_seconds / 60.0;
+        }
+    }
+
+    if weeks.is_empty() {
+        return "<p class=\"muted\">No data yet.</p>".to_string();
+    }
+
+    let bar_width = 36.0;
+    let height = 140.0;
+    let max = weeks.values().cloned().fold(0.0_f64, f64::max).max(1.0);
+    let width = weeks.len() as f64 * bar_width + 10.0;
+
+    let mut svg = format!(
+        "<svg class=\"chart\" width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">",
+        width,
+        height + 30.0
+    );
+    for (i, ((year, week), minutes)) in weeks.iter().enumerate() {
+        let bar_height = minutes / max * height;
+        let x = i as f64 * bar_width + 5.0;
+        let _ = write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"><title>{} min</title></rect>\
+             <text x=\"{:.1}\" y=\"{:.1}\">{}-W{:02}</text>",
+            x,
+            height - bar_height,
+            bar_width - 6.0,
+            bar_height,
+            escape_html(&locale.number(*minutes, 1)),
+            x,
+            height + 14.0,
+            year % 100,
+            week
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn student_page_name(student_id: &str) -> String {
+    let slug: String = student_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    format!("student-{}.html", slug)
+}
+
+/// Write a OneRoster 1.1 gradebook bulk CSV set (manifest, categories,
+/// lineItems, results) for scored assessments. Each assessed reading becomes
+/// two line items, one for WCPM and one for accuracy (percent). Scores and
+/// dates stay in the interchange format OneRoster requires; only the line
+/// item titles shown to teachers use `locale`.
+pub fn export_oneroster(
+    assessments: &[(Assessment, Recording)],
+    class_sourced_id: &str,
+    folder: &Path,
+    locale: &Locale,
+) -> Result<usize, ExportError> {
+    std::fs::create_dir_all(folder)?;
+    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+
+    std::fs::write(
+        folder.join("manifest.csv"),
+        "propertyName,value\n\
+         manifest.version,1.0\n\
+         oneroster.version,1.1\n\
+         file.academicSessions,absent\n\
+         file.categories,bulk\n\
+         file.classes,absent\n\
+         file.classResources,absent\n\
+         file.courses,absent\n\
+         file.courseResources,absent\n\
+         file.demographics,absent\n\
+         file.enrollments,absent\n\
+         file.lineItems,bulk\n\
+         file.orgs,absent\n\
+         file.resources,absent\n\
+         file.results,bulk\n\
+         file.users,absent\n\
+         source.systemName,Classroom Transcriber\n",
+    )?;
+
+    std::fs::write(
+        folder.join("categories.csv"),
+        format!(
+            "sourcedId,status,dateLastModified,title\n\
+             oral-reading-fluency,active,{},Oral Reading Fluency\n",
+            now
+        ),
+    )?;
+
+    let mut line_items = String::from(
+        "sourcedId,status,dateLastModified,title,description,assignDate,dueDate,classSourcedId,\
+         categorySourcedId,gradingPeriodSourcedId,resultValueMin,resultValueMax\n",
+    );
+    let mut results = String::from(
+        "sourcedId,status,dateLastModified,lineItemSourcedId,studentSourcedId,scoreStatus,score,scoreDate,comment\n",
+    );
+
+    for (assessment, recording) in assessments {
+        let date = &recording.recorded_at;
+        let day = DateTime::parse_from_rfc3339(date)
+            .map(|dt| locale.date(&dt.with_timezone(&chrono::Local)))
+            .unwrap_or_else(|_| date.get(..10).unwrap_or(date).to_string());
+        let comment = assessment.level.clone().unwrap_or_default();
+        let metrics = assessment.scored_metrics();
+        let scores = [
+            ("wcpm", "Words correct per minute", metrics.wcpm, "500"),
+            ("accuracy", "Accuracy (%)", metrics.accuracy * 100.0, "100"),
+        ];
+
+        for (metric, title, score, max) in scores {
+            let line_item_id = format!("{}-{}", recording.id, metric);
+            let _ = writeln!(
+                line_items,
+                "{},active,{},{},{},{},{},{},oral-reading-fluency,,0,{}",
+                csv_field(&line_item_id),
+                now,
+                csv_field(&format!("ORF {} {}", metric.to_uppercase(), day)),
+                csv_field(title),
+                date,
+                date,
+                csv_field(class_sourced_id),
+                max
+            );
+            let _ = writeln!(
+                results,
+                "{},active,{},{},{},fully graded,{:.2},{},{}",
+                csv_field(&format!("{}-result", line_item_id)),
+                now,
+                csv_field(&line_item_id),
+                csv_field(&recording.student_id),
+                score,
+                assessment.scored_at,
+                csv_field(&comment)
+            );
+        }
+    }
+
+    std::fs::write(folder.join("lineItems.csv"), line_items)?;
+    std::fs::write(folder.join("results.csv"), results)?;
+    Ok(assessments.len())
+}
+
+/// Write `speaker_stats.csv` into `folder`, a row per speaker per
+/// recording, for charting talk ratios elsewhere. Values stay
+/// machine-readable rather than following a locale. Returns the path.
+pub fn export_speaker_stats(
+    recordings: &[(Recording, Vec<SpeakerStats>)],
+    folder: &Path,
+) -> Result<PathBuf, ExportError> {
+    std::fs::create_dir_all(folder)?;
+    let mut csv = String::from("recording_id,student_id,recorded_at,speaker,label,words,seconds,share\n");
+    for (recording, stats) in recordings {
+        for entry in stats {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{:.2},{:.4}",
+                csv_field(&recording.id),
+                csv_field(&recording.student_id),
+                recording.recorded_at,
+                csv_field(&entry.speaker),
+                csv_field(entry.label.as_deref().unwrap_or_default()),
+                entry.words,
+                entry.seconds,
+                entry.share
+            );
+        }
+    }
+    let path = folder.join("speaker_stats.csv");
+    std::fs::write(&path, csv)?;
+    Ok(path)
+}
+
+/// Recordings made on or between two local dates; an open end is unbounded
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, recorded_at: &str) -> bool {
+        let Ok(day) = DateTime::parse_from_rfc3339(recorded_at).map(|dt| dt.with_timezone(&chrono::Local).date_naive())
+        else {
+            return false;
+        };
+        self.from.is_none_or(|from| day >= from) && self.to.is_none_or(|to| day <= to)
+    }
+}
+
+/// One recording in a parent export: the student's own lines and the
+/// scores for them
+pub struct ParentRecord<'a> {
+    pub recording: &'a Recording,
+    pub segments: Vec<TranscriptSegment>,
+    pub assessment: Option<Assessment>,
+}
+
+const WATERMARK_STYLE: &str = ".watermark{position:fixed;top:40%;left:0;right:0;text-align:center;font-size:2.5rem;
+color:rgba(0,0,0,.07);transform:rotate(-30deg);pointer-events:none;z-index:-1}
+.stamp{border:1px solid #ccc;padding:6px 8px;font-size:.9rem}";
+
+/// Write a single HTML file of one student's transcripts and reading
+/// scores, for answering a parent's records request. Only what's passed in
+/// is included, so leaving out other students, teacher notes and other
+/// speakers' lines is up to the caller. Every page is watermarked with
+/// `recipient` and the export date. Returns the path.
+pub fn export_parent_records(
+    student_name: &str,
+    records: &[ParentRecord],
+    recipient: &str,
+    range: &DateRange,
+    destination: &Path,
+    locale: &Locale,
+) -> Result<PathBuf, ExportError> {
+    let stamp = format!(
+        "Prepared for {} on {}",
+        recipient,
+        locale.date(&chrono::Local::now())
+    );
+    let covering = match (range.from, range.to) {
+        (Some(from), Some(to)) => format!("Recordings from {} to {}", from, to),
+        (Some(from), None) => format!("Recordings from {}", from),
+        (None, Some(to)) => format!("Recordings up to {}", to),
+        (None, None) => "All recordings".to_string(),
+    };
+
+    let mut body = format!("<style>{}</style>", WATERMARK_STYLE);
+    let _ = write!(
+        body,
+        "<div class=\"watermark\">{}</div><h1>{}</h1><p class=\"stamp\">{} &middot; {} &middot; {}</p>",
+        escape_html(&stamp),
+        escape_html(student_name),
+        escape_html(&stamp),
+        escape_html(&covering),
+        escape_html(&format!("{} recordings", locale.number(records.len() as f64, 0)))
+    );
+
+    for record in records {
+        let rec = record.recording;
+        let _ = write!(
+            body,
+            "<h2>{}</h2><p class=\"muted\">{}</p>",
+            escape_html(&locale.timestamp(&rec.recorded_at)),
+            locale.duration(rec.duration_seconds)
+        );
+        if let Some(assessment) = &record.assessment {
+            let metrics = assessment.scored_metrics();
+            let _ = write!(
+                body,
+                "<table><tr><th>Words correct per minute</th><th>Accuracy</th><th>Level</th></tr>\
+                 <tr><td>{}</td><td>{}%</td><td>{}</td></tr></table>",
+                escape_html(&locale.number(metrics.wcpm, 0)),
+                escape_html(&locale.number(metrics.accuracy * 100.0, 0)),
+                escape_html(assessment.level.as_deref().unwrap_or("-"))
+            );
+        }
+        let transcript = join_segments(&record.segments);
+        if transcript.trim().is_empty() {
+            body.push_str("<p class=\"muted\">Not transcribed</p>");
+        } else {
+            let _ = write!(body, "<p>{}</p>", escape_html(&transcript));
+        }
+    }
+    let _ = write!(body, "<p class=\"stamp\">{}</p>", escape_html(&stamp));
+
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(destination, page(student_name, &body))?;
+    Ok(destination.to_path_buf())
+}
+
+/// Build an RSS 2.0 podcast feed of a student's readings, one item per
+/// recording with the transcript as show notes and its date in `locale`.
+/// `enclosure_url` gives the address each recording's audio is served from.
+pub fn podcast_feed(
+    title: &str,
+    recordings: &[Recording],
+    locale: &Locale,
+    enclosure_url: impl Fn(&Recording) -> String,
+) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n<channel>\n",
+    );
+    let _ = writeln!(xml, "<title>{}</title>", escape_html(title));
+    let _ = writeln!(
+        xml,
+        "<description>Weekly readings by {}</description>",
+        escape_html(title)
+    );
+    let _ = writeln!(xml, "<lastBuildDate>{}</lastBuildDate>", chrono::Utc::now().to_rfc2822());
+
+    for rec in recordings {
+        let pub_date = DateTime::parse_from_rfc3339(&rec.recorded_at)
+            .map(|d| d.to_rfc2822())
+            .unwrap_or_default();
+        let length = std::fs::metadata(&rec.audio_path).map(|m| m.len()).unwrap_or(0);
+        let notes = rec.transcript.as_deref().unwrap_or("");
+
+        let _ = writeln!(
+            xml,
+            "<item><title>Reading {}</title><guid isPermaLink=\"false\">{}</guid><pubDate>{}</pubDate>\
+             <description>{}</description><enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\
+             <itunes:duration>{}</itunes:duration></item>",
+            escape_html(&locale.timestamp(&rec.recorded_at)),
+            escape_html(&rec.id),
+            pub_date,
+            escape_html(notes),
+            escape_html(&enclosure_url(rec)),
+            length,
+            rec.audio_format.mime_type(),
+            rec.duration_seconds.round() as u64
+        );
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+/// Write `feed.xml` and copies of the audio into `folder`, for opening
+/// directly in a podcast app. Returns the feed path.
+pub fn export_podcast_feed(
+    title: &str,
+    recordings: &[Recording],
+    folder: &Path,
+    locale: &Locale,
+) -> Result<PathBuf, ExportError> {
+    let audio_dir = folder.join("audio");
+    std::fs::create_dir_all(&audio_dir)?;
+    for rec in recordings {
+        std::fs::copy(&rec.audio_path, audio_dir.join(audio_file_name(rec)))?;
+    }
+
+    let feed = podcast_feed(title, recordings, locale, |rec| format!("audio/{}", audio_file_name(rec)));
+    let path = folder.join("feed.xml");
+    std::fs::write(&path, feed)?;
+    Ok(path)
+}
+
+fn audio_file_name(rec: &Recording) -> String {
+    format!("{}.{}", rec.id, rec.audio_format.extension())
+}
+
+/// One recording of a session, as the transcript player shows it
+pub struct PlayerPart<'a> {
+    pub recording: &'a Recording,
+    pub segments: Vec<TranscriptSegment>,
+    /// Encoded audio and its MIME type; None once the audio is purged
+    pub audio: Option<(Vec<u8>, &'static str)>,
+}
+
+#[derive(Serialize)]
+struct PlayerWord<'a> {
+    start: f64,
+    end: f64,
+    text: &'a str,
+}
+
+const PLAYER_STYLE: &str = ".transcript p{line-height:1.8}.transcript span{cursor:pointer;border-radius:3px}
+.transcript span:hover{background:#eef}.transcript span.now{background:#ffe58a}audio{width:100%;margin:0.5rem 0}";
+
+// Builds each transcript from the inlined timings, seeks on a click and
+// highlights the word being spoken
+const PLAYER_SCRIPT: &str = "const parts=JSON.parse(document.getElementById('timings').textContent);
+parts.forEach((words,i)=>{const audio=document.getElementById('audio-'+i);const box=document.getElementById('transcript-'+i);
+const spans=words.map(line=>{const p=box.appendChild(document.createElement('p'));return line.map(w=>{
+const span=p.appendChild(document.createElement('span'));span.textContent=w.text;p.append(' ');
+if(audio){span.onclick=()=>{audio.currentTime=w.start;audio.play();};}return [w,span];});}).flat();
+if(audio){audio.ontimeupdate=()=>{const t=audio.currentTime;
+for(const [w,span] of spans){span.classList.toggle('now',t>=w.start&&t<w.end);}};}});";
+
+/// Write a single HTML file that plays a session back with its transcript,
+/// for reviewing in any browser with nothing installed. Audio is embedded
+/// as base64 and the word timings as JSON: Whisper's own when the segment
+/// was timed word by word, otherwise each word's share of its segment by
+/// length.
+pub fn export_transcript_player(
+    title: &str,
+    parts: &[PlayerPart],
+    path: &Path,
+    locale: &Locale,
+) -> Result<PathBuf, ExportError> {
+    let mut body = String::new();
+    let _ = write!(body, "<h1>{}</h1>", escape_html(title));
+
+    let mut timings = Vec::with_capacity(parts.len());
+    for (i, part) in parts.iter().enumerate() {
+        let rec = part.recording;
+        let _ = write!(
+            body,
+            "<h2>{}</h2><p class=\"muted\">{} &middot; {}</p>",
+            escape_html(&locale.timestamp(&rec.recorded_at)),
+            escape_html(&rec.student_id),
+            locale.duration(rec.duration_seconds)
+        );
+        match &part.audio {
+            Some((bytes, mime)) => {
+                let _ = write!(
+                    body,
+                    "<audio id=\"audio-{}\" controls preload=\"auto\" src=\"data:{};base64,{}\"></audio>",
+                    i,
+                    mime,
+                    base64(bytes)
+                );
+            }
+            None => body.push_str("<p class=\"muted\">Audio for this recording has expired.</p>"),
+        }
+        let _ = write!(body, "<div class=\"transcript\" id=\"transcript-{}\"></div>", i);
+        timings.push(part.segments.iter().map(word_timings).collect::<Vec<_>>());
+    }
+
+    // `<` escaped so a transcript can't close the script element early
+    let json = serde_json::to_string(&timings)?.replace('<', "\\u003c");
+    let _ = write!(
+        body,
+        "<script type=\"application/json\" id=\"timings\">{}</script><script>{}</script>",
+        json, PLAYER_SCRIPT
+    );
+
+    let html = page(title, &format!("<style>{}</style>{}", PLAYER_STYLE, body));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, html)?;
+    Ok(path.to_path_buf())
+}
+
+/// A segment's words with their timings, or each timed in proportion to its
+/// length when the segment has none
+fn word_timings(segment: &TranscriptSegment) -> Vec<PlayerWord<'_>> {
+    if !segment.words.is_empty() {
+        return segment
+            .words
+            .iter()
+            .map(|w| PlayerWord { start: w.start, end: w.end.max(w.start), text: w.text.trim() })
+            .filter(|w| !w.text.is_empty())
+            .collect();
+    }
+    let words: Vec<&str> = segment.text.split_whitespace().collect();
+    let total: usize = words.iter().map(|w| w.chars().count()).sum();
+    let span = (segment.end - segment.start).max(0.0);
+    let mut start = segment.start;
+    words
+        .into_iter()
+        .map(|text| {
+            let share = text.chars().count() as f64 / total.max(1) as f64;
+            let end = start + span * share;
+            let word = PlayerWord { start, end, text };
+            start = end;
+            word
+        })
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::whisper::TranscriptWord;
+
+    fn segment(text: &str, words: Vec<TranscriptWord>) -> TranscriptSegment {
+        TranscriptSegment {
+            start: 1.0,
+            end: 3.0,
+            text: text.to_string(),
+            confidence: None,
+            avg_logprob: None,
+            no_speech_prob: None,
+            speaker: None,
+            words,
+        }
+    }
+
+    fn word(start: f64, end: f64, text: &str) -> TranscriptWord {
+        TranscriptWord { start, end, text: text.to_string(), confidence: None }
+    }
+
+    #[test]
+    fn word_timings_prefer_whisper_timings() {
+        let timed = segment(" the cat", vec![word(1.0, 1.2, " the"), word(1.9, 2.6, " cat")]);
+        let words = word_timings(&timed);
+        assert_eq!(words.iter().map(|w| w.text).collect::<Vec<_>>(), ["the", "cat"]);
+        assert_eq!((words[1].start, words[1].end), (1.9, 2.6));
+    }
+
+    #[test]
+    fn word_timings_share_untimed_segments_by_length() {
+        let untimed = segment("a bbb", Vec::new());
+        let words = word_timings(&untimed);
+        assert_eq!((words[0].start, words[0].end), (1.0, 1.5));
+        assert_eq!((words[1].start, words[1].end), (1.5, 3.0));
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::process::Command;
+use std::sync::OnceLock;
+
+// What this machine can run, looked at once at startup. Picks the model a
+// fresh install defaults to and how many threads transcription uses, so a
+// fast desktop isn't held to the base model and a small laptop isn't
+// handed one it can't keep up with.
+
+/// Memory a model needs free to run, roughly, in MB
+const MODEL_MEMORY_MB: [(&str, u64); 5] = [
+    ("tiny", 400),
+    ("base", 600),
+    ("small", 1_100),
+    ("medium", 2_600),
+    ("large-v3-turbo", 3_500),
+];
+
+/// Threads past this barely speed whisper up
+const MAX_THREADS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpuKind {
+    /// Apple silicon, which whisper.cpp uses through Metal
+    Metal,
+    /// An NVIDIA card with its driver installed
+    Cuda,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Gpu {
+    pub kind: GpuKind,
+    pub name: Option<String>,
+    pub memory_mb: Option<u64>,
+    /// Whether whisper-cli can use it; a card it can't is left out of the
+    /// model choice
+    pub usable: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HardwareProfile {
+    /// Logical cores
+    pub cpu_cores: usize,
+    pub total_memory_mb: Option<u64>,
+    /// Free when the app started
+    pub available_memory_mb: Option<u64>,
+    pub gpu: Option<Gpu>,
+    /// Largest model size it should keep up with, such as "small"; English
+    /// only variants are named without ".en"
+    pub model_size: &'static str,
+    /// Threads to transcribe with, leaving a core for recording
+    pub threads: usize,
+}
+
+static PROFILE: OnceLock<HardwareProfile> = OnceLock::new();
+
+/// This machine's profile, detected on first use
+pub fn profile() -> &'static HardwareProfile {
+    PROFILE.get_or_init(detect)
+}
+
+fn detect() -> HardwareProfile {
+    let cpu_cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let (total_memory_mb, available_memory_mb) = memory_mb();
+    let gpu = detect_gpu();
+    let usable_gpu = gpu.as_ref().filter(|g| g.usable);
+    let model_size = pick_size(cpu_cores, available_memory_mb.or(total_memory_mb), usable_gpu);
+    HardwareProfile {
+        cpu_cores,
+        total_memory_mb,
+        available_memory_mb,
+        gpu,
+        model_size,
+        threads: cpu_cores.saturating_sub(1).clamp(1, MAX_THREADS),
+    }
+}
+
+/// The largest size the cores or GPU can run quickly enough that fits in
+/// `memory_mb`. Without a GPU, anything past small is too slow for
+/// transcripts to keep up with a school day.
+fn pick_size(cpu_cores: usize, memory_mb: Option<u64>, gpu: Option<&Gpu>) -> &'static str {
+    let fast_enough = match (gpu, cpu_cores) {
+        (Some(_), _) => "large-v3-turbo",
+        (None, 8..) => "small",
+        (None, 4..) => "base",
+        (None, _) => "tiny",
+    };
+    let memory_mb = gpu.and_then(|g| g.memory_mb).or(memory_mb);
+    let limit = MODEL_MEMORY_MB
+        .iter()
+        .position(|(size, _)| *size == fast_enough)
+        .unwrap_or(0);
+    MODEL_MEMORY_MB[..=limit]
+        .iter()
+        .rev()
+        .find(|(_, needed)| memory_mb.is_none_or(|m| m >= *needed))
+        .map_or("tiny", |(size, _)| size)
+}
+
+/// Total and free memory, when the OS says
+#[cfg(target_os = "linux")]
+fn memory_mb() -> (Option<u64>, Option<u64>) {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|v| v.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map(|kb| kb / 1024)
+    };
+    (field("MemTotal"), field("MemAvailable"))
+}
+
+#[cfg(target_os = "macos")]
+fn memory_mb() -> (Option<u64>, Option<u64>) {
+    let total = command_output("sysctl", &["-n", "hw.memsize"])
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(|bytes| bytes >> 20);
+    // macOS counts cached files as used, so free memory undersells it
+    (total, None)
+}
+
+#[cfg(windows)]
+fn memory_mb() -> (Option<u64>, Option<u64>) {
+    let output = command_output(
+        "powershell",
+        &[
+            "-NoProfile",
+            "-Command",
+            "$os = Get-CimInstance Win32_OperatingSystem; \"$($os.TotalVisibleMemorySize) $($os.FreePhysicalMemory)\"",
+        ],
+    )
+    .unwrap_or_default();
+    let mut kb = output.split_whitespace().map(|v| v.parse::<u64>().ok().map(|kb| kb / 1024));
+    (kb.next().flatten(), kb.next().flatten())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn memory_mb() -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
+
+fn detect_gpu() -> Option<Gpu> {
+    if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        // Memory is shared with the CPU
+        return Some(Gpu { kind: GpuKind::Metal, name: None, memory_mb: None, usable: true });
+    }
+    let nvidia_smi = crate::whisper::locate_program("nvidia-smi")?;
+    let output = command_output(
+        &nvidia_smi.to_string_lossy(),
+        &["--query-gpu=name,memory.total", "--format=csv,noheader,nounits"],
+    )?;
+    let (name, memory) = output.lines().next()?.rsplit_once(',')?;
+    Some(Gpu {
+        kind: GpuKind::Cuda,
+        name: Some(name.trim().to_string()).filter(|n| !n.is_empty()),
+        memory_mb: memory.trim().parse().ok(),
+        usable: crate::whisper::whisper_cli_uses_cuda(),
+    })
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -0,0 +1,294 @@
+use crate::whisper::TranscriptSegment;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Upper bound on what a script may print, so a runaway one can't exhaust memory
+const MAX_OUTPUT_BYTES: u64 = 16 * 1024 * 1024;
+/// Longest a script stage may be given, so a typo can't hold up the queue
+/// for days
+pub const MAX_TIMEOUT_SECONDS: u64 = 600;
+
+#[derive(Error, Debug)]
+pub enum HookError {
+    #[error("Script name '{0}' must be a plain file name in the hooks folder")]
+    InvalidName(String),
+    #[error("Script not found: {0}")]
+    NotFound(PathBuf),
+    #[error("Failed to run script: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Script timed out after {0}s")]
+    Timeout(u64),
+    #[error("Script exited with {0}: {1}")]
+    Failed(String, String),
+    #[error("Script output is not a valid transcript: {0}")]
+    InvalidOutput(#[from] serde_json::Error),
+}
+
+/// What a script reads on stdin and must write back on stdout, possibly
+/// changed. Only `transcript` and `segments` are taken from the output.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HookPayload {
+    #[serde(default)]
+    pub recording_id: String,
+    pub transcript: String,
+    pub segments: Vec<TranscriptSegment>,
+    #[serde(default)]
+    pub reference_passage: Option<String>,
+}
+
+/// Run `name` from `hooks_dir` with the payload as JSON on stdin.
+///
+/// Scripts only ever come from the hooks folder on this machine, and only a
+/// pipeline set on this device may run one. This is not a sandbox: a script
+/// runs with the user's own permissions. It starts in a scratch directory
+/// with the environment cleared down to `PATH` (and `SystemRoot` on
+/// Windows), and it and anything it started are killed once it exits or
+/// `timeout_seconds` (at most `MAX_TIMEOUT_SECONDS`) is up.
+pub fn run(
+    hooks_dir: &Path,
+    name: &str,
+    args: &[String],
+    timeout_seconds: u64,
+    payload: &HookPayload,
+) -> Result<HookPayload, HookError> {
+    if name.is_empty() || Path::new(name).file_name().and_then(|n| n.to_str()) != Some(name) {
+        return Err(HookError::InvalidName(name.to_string()));
+    }
+    let program = hooks_dir.join(name);
+    if !program.is_file() {
+        return Err(HookError::NotFound(program));
+    }
+
+    let scratch = std::env::temp_dir().join(format!("classroom-hook-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&scratch)?;
+    let timeout_seconds = timeout_seconds.clamp(1, MAX_TIMEOUT_SECONDS);
+    let result = run_in(&program, args, timeout_seconds, payload, &scratch);
+    let _ = std::fs::remove_dir_all(&scratch);
+    result
+}
+
+fn run_in(
+    program: &Path,
+    args: &[String],
+    timeout_seconds: u64,
+    payload: &HookPayload,
+    scratch: &Path,
+) -> Result<HookPayload, HookError> {
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(scratch)
+        .env_clear()
+        .env("TMPDIR", scratch)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Windows programs can't start without SystemRoot
+    let kept: &[&str] = if cfg!(windows) { &["PATH", "SystemRoot"] } else { &["PATH"] };
+    for key in kept {
+        if let Some(value) = std::env::var_os(key) {
+            command.env(key, value);
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command.spawn()?;
+    let tree = ProcessTree::new(&child);
+
+    // Feed and drain on their own threads so a script that writes before it
+    // has read everything can't deadlock against us
+    let input = serde_json::to_vec(payload)?;
+    let mut stdin = child.stdin.take();
+    let writer = std::thread::spawn(move || {
+        if let Some(stdin) = stdin.as_mut() {
+            let _ = stdin.write_all(&input);
+        }
+    });
+    let mut stdout = child.stdout.take();
+    let reader = std::thread::spawn(move || {
+        let mut out = Vec::new();
+        if let Some(stdout) = stdout.as_mut() {
+            let _ = stdout.take(MAX_OUTPUT_BYTES).read_to_end(&mut out);
+        }
+        out
+    });
+    let mut stderr = child.stderr.take();
+    let errors = std::thread::spawn(move || {
+        let mut out = String::new();
+        if let Some(stderr) = stderr.as_mut() {
+            let _ = stderr.take(64 * 1024).read_to_string(&mut out);
+        }
+        out
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(timeout_seconds);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            tree.kill();
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(HookError::Timeout(timeout_seconds));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    // Anything left running in the background would hold the pipes open
+    tree.kill();
+    let _ = writer.join();
+    let output = reader.join().unwrap_or_default();
+    let stderr = errors.join().unwrap_or_default();
+
+    if !status.success() {
+        return Err(HookError::Failed(status.to_string(), stderr.trim().to_string()));
+    }
+    Ok(serde_json::from_slice(&output)?)
+}
+
+/// A script and every process it starts: its own process group on Unix, a
+/// job object on Windows
+struct ProcessTree {
+    #[cfg(unix)]
+    group: i32,
+    #[cfg(windows)]
+    job: windows_sys::Win32::Foundation::HANDLE,
+}
+
+impl ProcessTree {
+    #[cfg(unix)]
+    fn new(child: &Child) -> Self {
+        // The child leads its own group (`process_group(0)`), so the group
+        // id is its pid
+        Self { group: child.id() as i32 }
+    }
+
+    #[cfg(unix)]
+    fn kill(&self) {
+        // SAFETY: kill has no memory-safety preconditions
+        unsafe {
+            libc::kill(-self.group, libc::SIGKILL);
+        }
+    }
+
+    /// Processes the script starts before it's assigned to the job escape
+    /// it, but a script is barely running by then
+    #[cfg(windows)]
+    fn new(child: &Child) -> Self {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW};
+        // SAFETY: both handles are valid for the duration of the calls; a
+        // null job is checked before use
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if !job.is_null() {
+                AssignProcessToJobObject(job, child.as_raw_handle() as _);
+            }
+            Self { job }
+        }
+    }
+
+    #[cfg(windows)]
+    fn kill(&self) {
+        if !self.job.is_null() {
+            // SAFETY: the job handle is open until drop
+            unsafe {
+                windows_sys::Win32::System::JobObjects::TerminateJobObject(self.job, 1);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ProcessTree {
+    fn drop(&mut self) {
+        if !self.job.is_null() {
+            // SAFETY: closed exactly once
+            unsafe {
+                windows_sys::Win32::Foundation::CloseHandle(self.job);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn hooks_dir(script: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hooks-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hook");
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    fn payload() -> HookPayload {
+        HookPayload {
+            recording_id: "rec-1".into(),
+            transcript: "hello there".into(),
+            segments: Vec::new(),
+            reference_passage: None,
+        }
+    }
+
+    #[test]
+    fn payload_round_trips_through_a_script() {
+        let dir = hooks_dir("sed 's/hello/goodbye/'");
+        let out = run(&dir, "hook", &[], 10, &payload()).unwrap();
+        assert_eq!(out.transcript, "goodbye there");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn slow_scripts_time_out() {
+        let dir = hooks_dir("sleep 30");
+        let started = Instant::now();
+        assert!(matches!(run(&dir, "hook", &[], 1, &payload()), Err(HookError::Timeout(1))));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn background_children_are_killed_with_the_script() {
+        // The background sleep inherits stdout; left running it would keep
+        // the output open for its whole 30 seconds
+        let dir = hooks_dir("sleep 30 &\ncat");
+        let started = Instant::now();
+        assert_eq!(run(&dir, "hook", &[], 10, &payload()).unwrap().transcript, "hello there");
+        assert!(started.elapsed() < Duration::from_secs(10));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn environment_is_cleared_apart_from_path() {
+        let dir = hooks_dir(concat!(
+            "cat > /dev/null\n",
+            r#"printf '{"transcript":"%s","segments":[]}' "$(env | cut -d= -f1 | sort | tr '\n' ' ')""#,
+        ));
+        let out = run(&dir, "hook", &[], 10, &payload()).unwrap();
+        let names: Vec<&str> = out.transcript.split_whitespace().collect();
+        assert!(names.contains(&"PATH") && names.contains(&"TMPDIR"), "{:?}", names);
+        assert!(!names.contains(&"HOME"), "{:?}", names);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn names_outside_the_hooks_folder_are_refused() {
+        let dir = hooks_dir("cat");
+        for name in ["", "../hook", "sub/hook", "/bin/sh"] {
+            assert!(matches!(run(&dir, name, &[], 10, &payload()), Err(HookError::InvalidName(_))), "{}", name);
+        }
+        assert!(matches!(run(&dir, "missing", &[], 10, &payload()), Err(HookError::NotFound(_))));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,31 +1,174 @@
 mod audio;
+mod cache;
+mod classify;
 mod db;
+mod digest;
+mod dsp;
+mod encoder;
+mod export;
+mod hardware;
+mod hooks;
+mod locale;
+mod metrics;
+mod models;
+mod norms;
+mod packet;
+mod pdf;
+mod pipeline;
+mod playback;
+mod poison;
+mod qr;
+mod rubric;
+mod schedule;
+mod secrets;
+mod settings;
+mod smtp;
+mod speakers;
+mod startup;
 mod sync;
+mod telemetry;
+mod term;
+mod timing;
+mod voiceprint;
+mod waveform;
 mod whisper;
 
-use audio::AudioRecorder;
-use db::{Database, Recording};
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::sync::Mutex;
-use sync::SyncClient;
-use tauri::{Emitter, State};
-use whisper::Transcriber;
+use audio::{
+    ArchiveQuality, AudioQuality, AudioReader, AudioRecorder, CaptureChunk, CaptureMarker, CaptureStats, ChannelActivity,
+    Conversion, Converted, DualChannel, InputDevice, InputGain, MicrophonePermission, QualityScore, SilenceStop,
+    SystemAudio,
+};
+use cache::Cached;
+use db::{
+    Assessment, Assignment, Database, IdScheme, JobEntry, JobFilter, JobKind, Marker, MetadataUpdate, NormsVersion, QueuedJob,
+    Recording, SegmentRevision, SettingChange, Table, TranscriptRevision, MAX_JOB_ATTEMPTS,
+};
+use digest::DailyDigest;
+use dsp::ResampleQuality;
+use encoder::AudioFormat;
+use export::DateRange;
+use locale::Locale;
+use metrics::{ConfidenceMode, ConfidenceWeighting};
+use models::AvailableModel;
+use norms::{CohortComparison, NormsTable};
+use pipeline::{Pipeline, StageConfig};
+use playback::{PlaybackMonitor, Player};
+use poison::LockExt;
+use qr::QrCode;
+use rubric::Rubric;
+use schedule::BlackoutWindow;
+use serde::Serialize;
+use settings::ResolvedSetting;
+use smtp::SmtpConfig;
+use speakers::{SpeakerStats, StudentStrategy};
+use startup::StartupReport;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::time::Instant;
+use sync::{ServerCapabilities, SyncClient, SyncError, TranscriptDetail};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+use term::{ArchivedRecording, PurgePolicy, StepStatus, TermClose, TermStep};
+use timing::TimingMap;
+use voiceprint::Voiceprint;
+use waveform::Waveform;
+use whisper::{
+    join_segments, BackendDiscovery, BackendKind, DetectedLanguage, Doubt, ProgramPaths, SpeakerCount,
+    TranscribeOptions, TranscriptSegment, TranscriptionBackend, TranscriptionMonitor, TranscriptionProgress,
+    TranscriptionResult, WhisperError,
+};
 
 struct AppState {
     db: Mutex<Database>,
     recorder: Mutex<AudioRecorder>,
-    transcriber: Mutex<Option<Transcriber>>,
+    player: Mutex<Player>,
+    transcriber: Mutex<Option<Box<dyn TranscriptionBackend>>>,
+    /// The segment awaiting a re-spoken correction clip
+    pending_correction: Mutex<Option<PendingCorrection>>,
+    rolling: Mutex<RollingTranscript>,
+    /// Signalled each time a chunk of the recording in progress is done
+    rolling_done: Condvar,
+    /// Locked before `db` and `recorder` when both are needed
+    push_to_talk: Mutex<Option<PushToTalk>>,
+    /// The recording being transcribed in full, so it can be cancelled
+    transcription: Mutex<Option<(String, TranscriptionMonitor)>>,
+    /// Capture sessions of queued recordings, by recording ID; see
+    /// `enqueue_recording`. Locked before `db`.
+    job_queue: Mutex<HashMap<String, u64>>,
+    /// Signalled when a job is queued
+    job_ready: Condvar,
+    /// The batch `transcribe_all_pending` queued, until the worker is done
+    /// with each of its recordings. Not held with any other lock.
+    batch: Mutex<Option<BatchProgress>>,
+    /// Polled by the frontend; see `cache`
+    settings_cache: Cached<AppSettings>,
+    recordings_cache: Cached<Vec<Recording>>,
+    /// Checks made once on launch; see `startup_report` for the rest
+    startup: StartupReport,
+    /// The term close under way, if any. Held while a step runs, before
+    /// any other lock.
+    term_close: Mutex<Option<TermClose>>,
     data_dir: PathBuf,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone)]
+struct PendingCorrection {
+    recording_id: String,
+    segment_index: usize,
+    /// Set once the clip is recorded, so a failed transcription can be
+    /// tried again without speaking it again
+    clip: Option<PathBuf>,
+}
+
+/// A push-to-talk session: recording runs only while a key or button is
+/// held, and each hold is saved as its own short recording
+struct PushToTalk {
+    session_id: String,
+    /// When the key went down, while it's held
+    pressed_at: Option<Instant>,
+    /// Last press or release acted on
+    last_edge: Option<Instant>,
+}
+
+impl PushToTalk {
+    fn bouncing(&self) -> bool {
+        self.last_edge.is_some_and(|edge| edge.elapsed() < PUSH_TO_TALK_DEBOUNCE)
+    }
+}
+
+/// Presses and releases this soon after the last one acted on are switch
+/// bounce, or a key's auto-repeat, and are ignored
+const PUSH_TO_TALK_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(50);
+/// Holds shorter than this are taken as accidental and thrown away
+const PUSH_TO_TALK_MIN_SECONDS: f64 = 0.3;
+
+/// What the recording in progress has been transcribed to so far, one
+/// chunk at a time
+#[derive(Default)]
+struct RollingTranscript {
+    session: u64,
+    /// Chunks handed over and not yet transcribed, from any recording
+    pending: usize,
+    /// End of the unbroken run of transcribed chunks from the start of the
+    /// capture part, and their segments
+    covered_seconds: f64,
+    segments: Vec<TranscriptSegment>,
+    /// A chunk failed or went missing, so the part is transcribed whole
+    broken: bool,
+    /// Live captions so far, kept up even once the part is broken
+    captions: String,
+}
+
+#[derive(Serialize)]
 struct RecordingResult {
     id: String,
     duration: f64,
+    /// Whether the take is good enough to transcribe
+    quality: QualityScore,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 struct AppSettings {
     student_id: String,
     student_name: String,
@@ -33,12 +176,24 @@ struct AppSettings {
     server_url: String,
     model_loaded: bool,
     setup_complete: bool,
+    local_only: bool,
+    guest_mode: bool,
+    audio_device: Option<String>,
+    microphone_available: bool,
+    transcription_language: String,
+    live_captions: bool,
+    word_timestamps: bool,
+    sync_speaker_stats: bool,
+    student_speaker_strategy: Option<StudentStrategy>,
+    transcribe_pending_on_start: bool,
 }
 
 #[derive(Serialize)]
 struct SyncResult {
     synced_count: usize,
     failed_count: usize,
+    metadata_synced_count: usize,
+    audio_uploaded_count: usize,
     errors: Vec<String>,
 }
 
@@ -48,6 +203,134 @@ struct TranscribeResult {
     recording_id: String,
 }
 
+#[derive(Serialize)]
+struct PurgeResult {
+    purged_count: usize,
+    notified_count: usize,
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct PlaybackStatus {
+    playing: bool,
+    position_seconds: f64,
+}
+
+#[derive(Serialize, Clone)]
+struct PlaybackPosition {
+    recording_id: String,
+    position_seconds: f64,
+    segment_index: Option<usize>,
+    word_index: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct BacklogSummary {
+    awaiting_transcription: usize,
+    awaiting_review: usize,
+    awaiting_sync: usize,
+    failed_permanently: usize,
+    oldest_age_seconds: Option<i64>,
+    /// Backlog is past the `backlog_alert_count` or `backlog_alert_hours` threshold
+    alert: bool,
+}
+
+#[derive(Serialize)]
+struct RecordingReceipt {
+    recording_id: String,
+    /// Short enough to read out or type into the server's search
+    short_code: String,
+    url: String,
+    /// QR code for `url`, as a standalone SVG document
+    qr_svg: String,
+}
+
+#[derive(Serialize, Clone)]
+struct AutoStopped {
+    silence_seconds: f32,
+}
+
+#[derive(Serialize, Clone)]
+struct ConversionProgress {
+    recording_id: String,
+    /// Fraction of the capture converted, 0 to 1
+    progress: f32,
+}
+
+#[derive(Serialize, Clone)]
+struct TranscriptionProgressEvent {
+    recording_id: String,
+    #[serde(flatten)]
+    progress: TranscriptionProgress,
+}
+
+#[derive(Serialize, Clone)]
+struct ModelDownloadProgress {
+    model: String,
+    #[serde(flatten)]
+    progress: models::DownloadProgress,
+}
+
+/// Captions for the recording in progress, sent as each live window is
+/// transcribed and once more when the full-quality pass replaces them
+#[derive(Serialize, Clone)]
+struct LiveTranscript {
+    session: u64,
+    text: String,
+    is_final: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct RolledOver {
+    /// The finished part, which goes through the pipeline on its own
+    recording_id: String,
+    max_seconds: f32,
+}
+
+/// Where the audio for a new recording comes from
+enum CaptureSource {
+    /// Stop the recorder and take what it captured
+    Recorder,
+    /// A part the recorder finished on its own at the length limit
+    Rollover(PathBuf, CaptureStats, Vec<CaptureMarker>),
+    /// One hold of a push-to-talk session, already stopped
+    Utterance(String, PathBuf, CaptureStats, Vec<CaptureMarker>),
+}
+
+#[derive(Serialize)]
+struct PushToTalkSession {
+    session_id: String,
+    utterances: Vec<Recording>,
+}
+
+#[derive(Serialize)]
+struct SystemAudioSources {
+    sources: Vec<InputDevice>,
+    /// Setup needed first on platforms that can't record system audio directly
+    guidance: Option<&'static str>,
+}
+
+/// What a sound check heard
+#[derive(Serialize)]
+struct MicTestResult {
+    /// `None` is the system default
+    device: Option<String>,
+    /// The device's own rate, before conversion to 16kHz
+    sample_rate: u32,
+    rms_dbfs: f32,
+    peak_dbfs: f32,
+    clipped_samples: u64,
+    clipped_ratio: f64,
+    quality: AudioQuality,
+    /// What the model made of it, when asked for and a model is loaded
+    transcript: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct MicrophoneStatus {
+    available: bool,
+}
+
 #[derive(Serialize, Clone)]
 struct ProcessingStatus {
     stage: String,  // "saving", "transcribing", "syncing", "done", "error"
@@ -57,434 +340,6809 @@ struct ProcessingStatus {
     synced: bool,
 }
 
-// ========== Settings Commands ==========
-
-#[tauri::command]
-fn get_settings(state: State<AppState>) -> Result<AppSettings, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let student_id = db
-        .get_setting("student_id")
-        .map_err(|e| e.to_string())?
-        .unwrap_or_default();
-    let student_name = db
-        .get_setting("student_name")
-        .map_err(|e| e.to_string())?
-        .unwrap_or_default();
-    let teacher_name = db
-        .get_setting("teacher_name")
+/// Local-only mode disables every network call. It is forced on by the
+/// `local-only` build feature, or can be switched on via the setting.
+fn is_local_only(db: &Database) -> Result<bool, String> {
+    if cfg!(feature = "local-only") {
+        return Ok(true);
+    }
+    Ok(db
+        .get_setting("local_only")
         .map_err(|e| e.to_string())?
-        .unwrap_or_default();
-    let server_url = db
-        .get_setting("server_url")
+        .map(|v| v == "true")
+        .unwrap_or(false))
+}
+
+/// Expiry for new recordings from the `retention_days` policy setting
+fn default_expiry(db: &Database) -> Result<Option<String>, String> {
+    let days = settings::resolve(db, "retention_days")
         .map_err(|e| e.to_string())?
-        .unwrap_or_else(|| "http://localhost:3000".to_string());
-    let setup_complete = db
-        .get_setting("setup_complete")
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|d| *d > 0);
+    Ok(days.map(|d| (chrono::Utc::now() + chrono::Duration::days(d)).to_rfc3339()))
+}
+
+/// Who recordings made in guest mode are filed under
+const GUEST_STUDENT_ID: &str = "guest";
+/// How long a guest recording is kept before it is deleted outright
+const GUEST_RETENTION_HOURS: i64 = 24;
+
+fn is_guest_mode(db: &Database) -> Result<bool, String> {
+    Ok(db
+        .get_setting("guest_mode")
         .map_err(|e| e.to_string())?
         .map(|v| v == "true")
-        .unwrap_or(false);
-    let model_loaded = state.transcriber.lock().unwrap().is_some();
-
-    Ok(AppSettings {
-        student_id,
-        student_name,
-        teacher_name,
-        server_url,
-        model_loaded,
-        setup_complete,
-    })
+        .unwrap_or(false))
 }
 
-#[tauri::command]
-fn save_settings(
-    state: State<AppState>,
-    student_id: String,
-    student_name: String,
-    teacher_name: String,
-    server_url: String,
-) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.set_setting("student_id", &student_id)
-        .map_err(|e| e.to_string())?;
-    db.set_setting("student_name", &student_name)
-        .map_err(|e| e.to_string())?;
-    db.set_setting("teacher_name", &teacher_name)
-        .map_err(|e| e.to_string())?;
-    db.set_setting("server_url", &server_url)
-        .map_err(|e| e.to_string())?;
+/// In guest mode, file a new recording under the guest student and keep it
+/// on this device until it is purged
+fn apply_guest_mode(db: &Database, recording: &mut Recording) -> Result<(), String> {
+    if !is_guest_mode(db)? {
+        return Ok(());
+    }
+    recording.student_id = GUEST_STUDENT_ID.to_string();
+    recording.guest = true;
+    recording.confidential = true;
+    recording.expires_at = Some((chrono::Utc::now() + chrono::Duration::hours(GUEST_RETENTION_HOURS)).to_rfc3339());
     Ok(())
 }
 
-#[tauri::command]
-fn complete_setup(
-    state: State<AppState>,
-    student_name: String,
-    teacher_name: String,
-    server_url: String,
-) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+/// Passage new recordings are read from: the active assignment's if there
+/// is one, otherwise `active_passage`
+fn active_passage(db: &Database) -> Result<Option<String>, String> {
+    if let Some(assignment) = db.get_active_assignment().map_err(|e| e.to_string())? {
+        return Ok(Some(assignment.passage));
+    }
+    Ok(db
+        .get_setting("active_passage")
+        .map_err(|e| e.to_string())?
+        .filter(|p| !p.is_empty()))
+}
 
-    // Generate student ID from name (lowercase, no spaces)
-    let student_id = student_name
-        .to_lowercase()
-        .chars()
-        .filter(|c| c.is_alphanumeric() || *c == ' ')
-        .collect::<String>()
-        .split_whitespace()
-        .collect::<Vec<&str>>()
-        .join("-");
+/// Give a new recording the passage it's read from, tying it to the active
+/// assignment if there is one
+fn apply_passage(db: &Database, recording: &mut Recording) -> Result<(), String> {
+    recording.assignment_id = db
+        .get_active_assignment()
+        .map_err(|e| e.to_string())?
+        .map(|a| a.id);
+    recording.reference_passage = active_passage(db)?;
+    Ok(())
+}
 
-    db.set_setting("student_id", &student_id)
-        .map_err(|e| e.to_string())?;
-    db.set_setting("student_name", &student_name)
-        .map_err(|e| e.to_string())?;
-    db.set_setting("teacher_name", &teacher_name)
+/// Delete every guest recording older than `GUEST_RETENTION_HOURS`, audio
+/// and all. Returns how many went.
+fn purge_guest_recordings(db: &Database) -> Result<usize, String> {
+    let cutoff = (chrono::Utc::now() - chrono::Duration::hours(GUEST_RETENTION_HOURS)).to_rfc3339();
+    let expired = db
+        .get_guest_recordings_before(&cutoff)
         .map_err(|e| e.to_string())?;
-    db.set_setting("server_url", &server_url)
+    for recording in &expired {
+        remove_recording(db, &recording.id)?;
+    }
+    Ok(expired.len())
+}
+
+/// Delete audio for every recording past its expiry. Returns how many were purged.
+fn purge_expired_audio(db: &Database) -> Result<usize, String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let expired = db
+        .get_expiring_recordings(&now)
         .map_err(|e| e.to_string())?;
-    db.set_setting("setup_complete", "true")
+
+    for recording in &expired {
+        for path in recording.audio_files() {
+            let _ = std::fs::remove_file(path);
+        }
+        db.mark_audio_purged(&recording.id)
+            .map_err(|e| e.to_string())?;
+        db.clear_lane_files(&recording.id)
+            .map_err(|e| e.to_string())?;
+        db.clear_archive_audio(&recording.id)
+            .map_err(|e| e.to_string())?;
+    }
+
+    // Offloading keeps the lanes and archive copy, since the server never had them
+    let offloaded = db
+        .get_expired_local_only_audio(&now)
         .map_err(|e| e.to_string())?;
-    Ok(())
+    for recording in &offloaded {
+        for path in recording.audio_files().filter(|p| *p != recording.audio_path) {
+            let _ = std::fs::remove_file(path);
+        }
+        db.clear_lane_files(&recording.id)
+            .map_err(|e| e.to_string())?;
+        db.clear_archive_audio(&recording.id)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(expired.len())
 }
 
-// ========== Recording Commands ==========
-
-#[tauri::command]
-fn start_recording(state: State<AppState>) -> Result<(), String> {
-    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
-    recorder.start_recording().map_err(|e| e.to_string())
+/// Silence auto-stop from `auto_stop_silence_seconds` (0 or unset disables
+/// it) and `auto_stop_threshold_dbfs`
+fn silence_stop_setting(db: &Database) -> Result<Option<SilenceStop>, String> {
+    let read = |key| -> Result<Option<f32>, String> {
+        Ok(settings::resolve(db, key)
+            .map_err(|e| e.to_string())?
+            .and_then(|v| v.parse::<f32>().ok()))
+    };
+    let Some(seconds) = read("auto_stop_silence_seconds")?.filter(|s| *s > 0.0) else {
+        return Ok(None);
+    };
+    Ok(Some(SilenceStop {
+        seconds,
+        threshold_dbfs: read("auto_stop_threshold_dbfs")?.unwrap_or(-50.0),
+    }))
 }
 
-#[tauri::command]
-fn stop_recording(state: State<AppState>) -> Result<RecordingResult, String> {
-    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
-    let samples = recorder.stop_recording();
+/// Capture gain from `input_gain` and `auto_gain`
+fn input_gain_setting(db: &Database) -> Result<InputGain, String> {
+    let multiplier = settings::resolve(db, "input_gain")
+        .map_err(|e| e.to_string())?
+        .and_then(|v| v.parse::<f32>().ok())
+        .unwrap_or(1.0);
+    let automatic = settings::resolve(db, "auto_gain")
+        .map_err(|e| e.to_string())?
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    Ok(InputGain { multiplier, automatic })
+}
 
-    // Generate unique ID
-    let id = uuid::Uuid::new_v4().to_string();
+/// Resampler quality from `resample_quality` ("fast" or "high", the default)
+fn resample_quality_setting(db: &Database) -> Result<ResampleQuality, String> {
+    Ok(match settings::resolve(db, "resample_quality")
+        .map_err(|e| e.to_string())?
+        .as_deref()
+    {
+        Some("fast") => ResampleQuality::Fast,
+        _ => ResampleQuality::High,
+    })
+}
 
-    // Save audio file
-    let audio_dir = state.data_dir.join("audio");
-    std::fs::create_dir_all(&audio_dir).map_err(|e| e.to_string())?;
-    let audio_path = audio_dir.join(format!("{}.wav", id));
+/// Storage format for new recordings from `audio_format` ("wav", the
+/// default, or "flac")
+fn audio_format_setting(db: &Database) -> Result<AudioFormat, String> {
+    Ok(settings::resolve(db, "audio_format")
+        .map_err(|e| e.to_string())?
+        .as_deref()
+        .and_then(AudioFormat::parse)
+        .unwrap_or_default())
+}
 
-    let duration = recorder
-        .save_wav(&samples, &audio_path)
-        .map_err(|e| e.to_string())?;
+/// `archive_quality`, which full-quality copy of each capture to keep, if any
+fn archive_quality_setting(db: &Database) -> Result<Option<ArchiveQuality>, String> {
+    Ok(settings::resolve(db, "archive_quality")
+        .map_err(|e| e.to_string())?
+        .as_deref()
+        .and_then(ArchiveQuality::parse))
+}
 
-    // Get student ID
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let student_id = db
-        .get_setting("student_id")
+/// A fresh recording ID in the configured scheme
+fn new_recording_id(db: &Database) -> Result<String, String> {
+    let scheme = settings::resolve(db, "recording_id_scheme")
         .map_err(|e| e.to_string())?
-        .unwrap_or_else(|| "unknown".to_string());
+        .as_deref()
+        .and_then(IdScheme::parse)
+        .unwrap_or_default();
+    Ok(scheme.new_id())
+}
 
-    // Create recording entry
-    let recording = Recording {
-        id: id.clone(),
-        student_id,
-        audio_path: audio_path.to_string_lossy().to_string(),
-        transcript: None,
-        duration_seconds: duration,
-        recorded_at: chrono::Utc::now().to_rfc3339(),
-        synced: false,
-    };
+/// Re-encode a freshly saved WAV in `format`, removing the WAV, and return
+/// where the audio now lives. The WAV is only removed once the new file has
+/// been read back and matches it; otherwise it's kept as the recording's
+/// audio, so its format should be taken from the path returned.
+fn store_audio(wav_path: &Path, format: AudioFormat) -> Result<PathBuf, String> {
+    if format == AudioFormat::Wav {
+        return Ok(wav_path.to_path_buf());
+    }
+    match audio::transcode(wav_path, format) {
+        Ok(stored) => {
+            std::fs::remove_file(wav_path).map_err(|e| e.to_string())?;
+            Ok(stored)
+        }
+        Err(e) => {
+            eprintln!("Keeping {} as WAV: {}", wav_path.display(), e);
+            Ok(wav_path.to_path_buf())
+        }
+    }
+}
 
-    db.save_recording(&recording).map_err(|e| e.to_string())?;
+/// whisper-cli only reads WAV, so compressed audio is decoded to a temporary
+/// copy. Callers remove it once transcription is done.
+fn transcription_input(recording: &Recording) -> Result<PathBuf, String> {
+    let stored = PathBuf::from(&recording.audio_path);
+    if recording.audio_format == AudioFormat::Wav {
+        return Ok(stored);
+    }
+    let temporary = stored.with_extension("transcribe.wav");
+    audio::decode_to_wav(&stored, &temporary).map_err(|e| e.to_string())?;
+    Ok(temporary)
+}
 
-    Ok(RecordingResult { id, duration })
+/// Whether `noise_suppression` is on; off unless set to "true"
+fn noise_suppression_setting(db: &Database) -> Result<bool, String> {
+    Ok(settings::resolve(db, "noise_suppression")
+        .map_err(|e| e.to_string())?
+        .is_some_and(|v| v == "true"))
 }
 
-#[tauri::command]
-fn is_recording(state: State<AppState>) -> bool {
-    state
-        .recorder
-        .lock()
-        .map(|r| r.is_recording())
-        .unwrap_or(false)
+/// Whether `echo_cancellation` is on; off unless set to "true"
+fn echo_cancellation_setting(db: &Database) -> Result<bool, String> {
+    Ok(settings::resolve(db, "echo_cancellation")
+        .map_err(|e| e.to_string())?
+        .is_some_and(|v| v == "true"))
 }
 
-/// Stop recording, transcribe, and sync - all in one command
-#[tauri::command]
-fn stop_and_process(state: State<AppState>, window: tauri::Window) -> Result<ProcessingStatus, String> {
-    // Stage 1: Stop recording and save audio
-    let _ = window.emit("processing-status", ProcessingStatus {
-        stage: "saving".to_string(),
-        message: "Saving audio...".to_string(),
-        recording_id: None,
-        transcript: None,
-        synced: false,
-    });
+/// Push gain, auto-stop, resampling and denoise settings to the recorder
+fn apply_capture_settings(db: &Database, recorder: &mut AudioRecorder) -> Result<(), String> {
+    recorder.set_silence_stop(silence_stop_setting(db)?);
+    recorder.set_input_gain(input_gain_setting(db)?);
+    recorder.set_resample_quality(resample_quality_setting(db)?);
+    recorder.set_noise_suppression(noise_suppression_setting(db)?);
+    recorder.set_max_duration(max_duration_setting(db)?);
+    recorder.set_dual_channel(dual_channel_setting(db)?);
+    recorder.set_channel_weights(channel_weights_setting(db)?);
+    recorder.set_system_audio(system_audio_setting(db)?);
+    recorder.set_chunk_seconds(chunk_seconds_setting(db)?);
+    recorder.set_archive_quality(archive_quality_setting(db)?);
+    recorder.set_echo_cancellation(echo_cancellation_setting(db)?);
+    Ok(())
+}
 
-    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
-    let samples = recorder.stop_recording();
+/// Level chunks must rise above for a while to be sent to the model
+const DEFAULT_VAD_GATE_DBFS: f32 = -45.0;
 
-    let id = uuid::Uuid::new_v4().to_string();
-    let audio_dir = state.data_dir.join("audio");
-    std::fs::create_dir_all(&audio_dir).map_err(|e| e.to_string())?;
-    let audio_path = audio_dir.join(format!("{}.wav", id));
+/// `vad_gate_dbfs`, the level streamed audio must reach to be transcribed,
+/// so quiet stretches never hit the model; "off" sends everything
+fn vad_gate_setting(db: &Database) -> Result<Option<f32>, String> {
+    match settings::resolve(db, "vad_gate_dbfs").map_err(|e| e.to_string())? {
+        Some(value) if value == "off" => Ok(None),
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid vad_gate_dbfs setting: {}", value)),
+        None => Ok(Some(DEFAULT_VAD_GATE_DBFS)),
+    }
+}
 
-    let duration = recorder
-        .save_wav(&samples, &audio_path)
-        .map_err(|e| e.to_string())?;
-    drop(recorder);
+/// `chunk_minutes`, how often a recording in progress is handed to the
+/// transcriber; unset or 0 transcribes it only once it stops
+fn chunk_minutes_setting(db: &Database) -> Result<Option<f32>, String> {
+    Ok(settings::resolve(db, "chunk_minutes")
+        .map_err(|e| e.to_string())?
+        .and_then(|v| v.parse::<f32>().ok())
+        .filter(|m| *m > 0.0))
+}
 
-    // Get student ID and save recording
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let student_id = db
-        .get_setting("student_id")
+/// Shortest and longest windows transcribed for live captions
+const LIVE_WINDOW_SECONDS: std::ops::RangeInclusive<f32> = 5.0..=10.0;
+
+/// `live_caption_seconds`, the length of the windows transcribed for live
+/// captions while recording. None when captions are off.
+fn live_caption_seconds_setting(db: &Database) -> Result<Option<f32>, String> {
+    Ok(settings::resolve(db, "live_caption_seconds")
         .map_err(|e| e.to_string())?
-        .unwrap_or_else(|| "unknown".to_string());
-    let server_url = db
-        .get_setting("server_url")
+        .and_then(|v| v.parse::<f32>().ok())
+        .filter(|s| *s > 0.0)
+        .map(|s| s.clamp(*LIVE_WINDOW_SECONDS.start(), *LIVE_WINDOW_SECONDS.end())))
+}
+
+/// How often the recorder hands over a chunk: every live caption window
+/// when captions are on, otherwise every `chunk_minutes`
+fn chunk_seconds_setting(db: &Database) -> Result<Option<f32>, String> {
+    Ok(live_caption_seconds_setting(db)?.or(chunk_minutes_setting(db)?.map(|m| m * 60.0)))
+}
+
+/// `system_audio`, computer sound to mix in alongside the microphone
+fn system_audio_setting(db: &Database) -> Result<Option<SystemAudio>, String> {
+    settings::resolve(db, "system_audio")
         .map_err(|e| e.to_string())?
-        .unwrap_or_else(|| "http://localhost:3000".to_string());
+        .filter(|json| !json.is_empty())
+        .map(|json| serde_json::from_str(&json).map_err(|e| format!("Invalid system_audio setting: {}", e)))
+        .transpose()
+}
 
-    let recording = Recording {
-        id: id.clone(),
-        student_id,
-        audio_path: audio_path.to_string_lossy().to_string(),
-        transcript: None,
-        duration_seconds: duration,
-        recorded_at: chrono::Utc::now().to_rfc3339(),
-        synced: false,
+/// `channel_weights`, a JSON array weighting each input channel in the mix
+fn channel_weights_setting(db: &Database) -> Result<Option<Vec<f32>>, String> {
+    settings::resolve(db, "channel_weights")
+        .map_err(|e| e.to_string())?
+        .filter(|json| !json.is_empty())
+        .map(|json| serde_json::from_str(&json).map_err(|e| format!("Invalid channel_weights setting: {}", e)))
+        .transpose()
+}
+
+/// `dual_channel`, which inputs carry the teacher and the student when
+/// they're recorded into separate channels
+fn dual_channel_setting(db: &Database) -> Result<Option<DualChannel>, String> {
+    settings::resolve(db, "dual_channel")
+        .map_err(|e| e.to_string())?
+        .filter(|json| !json.is_empty())
+        .map(|json| serde_json::from_str(&json).map_err(|e| format!("Invalid dual_channel setting: {}", e)))
+        .transpose()
+}
+
+/// `max_recording_minutes`, after which a recording rolls over into a new one
+fn max_duration_setting(db: &Database) -> Result<Option<f32>, String> {
+    Ok(settings::resolve(db, "max_recording_minutes")
+        .map_err(|e| e.to_string())?
+        .and_then(|v| v.parse::<f32>().ok())
+        .filter(|m| *m > 0.0)
+        .map(|m| m * 60.0))
+}
+
+/// Refuse to record during a blackout window configured for this station's
+/// room. Entries that don't parse are logged and skipped.
+fn check_recording_allowed(db: &Database) -> Result<(), String> {
+    let Some(json) = settings::resolve(db, "blackout_windows").map_err(|e| e.to_string())? else {
+        return Ok(());
+    };
+    let (windows, errors) = schedule::parse_windows_lossy(&json);
+    for e in errors {
+        eprintln!("Ignoring a blackout window: {}", e);
+    }
+    let room = settings::resolve(db, "room").map_err(|e| e.to_string())?;
+    let now = chrono::Local::now().naive_local();
+
+    match schedule::active_window(&windows, now, room.as_deref()) {
+        Some(window) => Err(format!(
+            "Recording is not allowed during {} (until {})",
+            window.label, window.end
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Whether only urgent jobs may run now: `quiet_hours` is set and none of
+/// its windows is open. Without any, jobs run as soon as they're queued.
+fn outside_quiet_hours(db: &Database) -> Result<bool, String> {
+    let Some(json) = settings::resolve(db, "quiet_hours").map_err(|e| e.to_string())? else {
+        return Ok(false);
+    };
+    let windows = schedule::parse_windows(&json).map_err(|e| e.to_string())?;
+    if windows.is_empty() {
+        return Ok(false);
+    }
+    let room = settings::resolve(db, "room").map_err(|e| e.to_string())?;
+    let now = chrono::Local::now().naive_local();
+    Ok(schedule::active_window(&windows, now, room.as_deref()).is_none())
+}
+
+/// Whisper model file: the `selected_model` picked from the catalog, or
+/// else the file named by the `model` setting (which a classroom may set),
+/// or else the one that suits the hardware
+fn model_path(db: &Database, data_dir: &Path) -> Result<PathBuf, String> {
+    let models_dir = data_dir.join("models");
+    if let Some(name) = settings::resolve(db, "selected_model")
+        .map_err(|e| e.to_string())?
+        .filter(|n| !n.is_empty())
+    {
+        return Ok(models_dir.join(models::file_name(&name)));
+    }
+    let model = match settings::resolve(db, "model").map_err(|e| e.to_string())? {
+        Some(model) => model,
+        None => models::file_name(auto_model(db, &models_dir)?),
+    };
+    Ok(models_dir.join(model))
+}
+
+/// The model used without a `selected_model` or `model` setting: the
+/// largest the hardware can keep up with, English-only when transcribing
+/// English. When that isn't downloaded but a smaller one is, the largest of
+/// those, so an install keeps the model it already has.
+fn auto_model(db: &Database, models_dir: &Path) -> Result<&'static str, String> {
+    let english = transcription_language(db)? == "en";
+    let largest = models::find(hardware::profile().model_size).map_err(|e| e.to_string())?;
+    // English takes the ".en" build of a size when there is one
+    let suits = |m: &models::ModelSpec| {
+        if english {
+            !m.multilingual() || models::find(&format!("{}.en", m.name)).is_err()
+        } else {
+            m.multilingual()
+        }
+    };
+    let mut candidates: Vec<&models::ModelSpec> = models::MODELS
+        .iter()
+        .filter(|m| m.size_mb <= largest.size_mb && suits(m))
+        .collect();
+    candidates.sort_by_key(|m| std::cmp::Reverse(m.size_mb));
+    let downloaded = candidates.iter().find(|m| models_dir.join(m.file_name()).exists());
+    Ok(downloaded
+        .or(candidates.first())
+        .map_or(models::DEFAULT_MODEL, |m| m.name))
+}
+
+/// `transcription_threads`, or as many as the hardware profile suggests
+fn transcription_threads_setting(db: &Database) -> Result<usize, String> {
+    Ok(settings::resolve(db, "transcription_threads")
+        .map_err(|e| e.to_string())?
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(hardware::profile().threads))
+}
+
+/// `transcription_backend`, the engine models are run with; whisper.cpp
+/// unless set
+fn transcription_backend_setting(db: &Database) -> Result<BackendKind, String> {
+    match settings::resolve(db, "transcription_backend").map_err(|e| e.to_string())? {
+        Some(value) => BackendKind::parse(&value).ok_or_else(|| format!("Unknown transcription backend: {}", value)),
+        None => Ok(BackendKind::default()),
+    }
+}
+
+/// Where to look for the engines before the usual places, from the
+/// `whisper_cli_path` and `whisperx_path` settings
+fn program_paths_setting(db: &Database) -> Result<ProgramPaths, String> {
+    let path = |key: &str| -> Result<Option<PathBuf>, String> {
+        let value = settings::resolve(db, key).map_err(|e| e.to_string())?;
+        Ok(value.filter(|v| !v.trim().is_empty()).map(|v| PathBuf::from(v.trim())))
+    };
+    Ok(ProgramPaths {
+        whisper_cli: path("whisper_cli_path")?,
+        whisperx: path("whisperx_path")?,
+    })
+}
+
+/// The Hugging Face token that unlocks WhisperX's diarization models: the
+/// sealed `hf_token` setting, or else `HF_TOKEN` from the environment. One
+/// that can't be unsealed only turns diarization off.
+fn hf_token(db: &Database, data_dir: &Path) -> Option<String> {
+    match hf_token_with_source(db, data_dir) {
+        Ok(found) => found.map(|(token, _)| token),
+        Err(e) => {
+            eprintln!("Ignoring the Hugging Face token: {}", e);
+            None
+        }
+    }
+}
+
+fn hf_token_with_source(db: &Database, data_dir: &Path) -> Result<Option<(String, &'static str)>, String> {
+    if let Some(sealed) = db.get_setting("hf_token").map_err(|e| e.to_string())? {
+        let token = secrets::unseal(data_dir, "hf_token", &sealed).map_err(|e| e.to_string())?;
+        return Ok(Some((token, "settings")));
+    }
+    Ok(std::env::var("HF_TOKEN")
+        .ok()
+        .filter(|t| !t.trim().is_empty())
+        .map(|t| (t, "environment")))
+}
+
+/// `transcription_language`, the language code recordings are decoded as,
+/// or "auto" to have the model pick
+fn transcription_language(db: &Database) -> Result<String, String> {
+    Ok(settings::resolve(db, "transcription_language")
+        .map_err(|e| e.to_string())?
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "en".to_string()))
+}
+
+/// `word_timestamps`, whether transcripts are timed word by word as well
+/// as by segment
+fn word_timestamps_setting(db: &Database) -> Result<bool, String> {
+    Ok(settings::resolve(db, "word_timestamps")
+        .map_err(|e| e.to_string())?
+        .is_some_and(|v| v == "true"))
+}
+
+/// `min_speakers` and `max_speakers`, the bounds on how many people
+/// diarizing finds in a recording
+fn speaker_count_setting(db: &Database) -> Result<SpeakerCount, String> {
+    let bound = |key: &str| -> Result<Option<u32>, String> {
+        match settings::resolve(db, key).map_err(|e| e.to_string())? {
+            Some(value) if !value.is_empty() => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid {}: {}", key, value)),
+            _ => Ok(None),
+        }
+    };
+    Ok(SpeakerCount {
+        min: bound("min_speakers")?,
+        max: bound("max_speakers")?,
+    })
+}
+
+/// Split a vocabulary as typed, a term per line or comma, dropping blanks
+/// and repeats
+fn parse_terms<'a>(terms: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for term in terms.into_iter().flat_map(|t| t.split([',', '\n'])).map(str::trim) {
+        if !term.is_empty() && !parsed.iter().any(|p| p.eq_ignore_ascii_case(term)) {
+            parsed.push(term.to_string());
+        }
+    }
+    parsed
+}
+
+/// What recordings of `student_id` are prompted with: the teacher's name,
+/// then `vocabulary`, terms for every student such as curriculum words,
+/// then the student's own list
+fn vocabulary_for(db: &Database, student_id: &str) -> Result<Vec<String>, String> {
+    let teacher_name = db.get_setting("teacher_name").map_err(|e| e.to_string())?;
+    let shared = settings::resolve(db, "vocabulary").map_err(|e| e.to_string())?;
+    let own = db.get_student_vocabulary(student_id).map_err(|e| e.to_string())?;
+    Ok(parse_terms(
+        teacher_name
+            .iter()
+            .chain(&shared)
+            .map(String::as_str)
+            .chain(own.iter().map(String::as_str)),
+    ))
+}
+
+/// `transcribe_pending_on_start`, whether recordings still waiting on a
+/// transcript are queued each time the app starts
+fn transcribe_pending_on_start_setting(db: &Database) -> Result<bool, String> {
+    Ok(settings::resolve(db, "transcribe_pending_on_start")
+        .map_err(|e| e.to_string())?
+        .is_some_and(|v| v == "true"))
+}
+
+/// `sync_speaker_stats`, whether each speaker's words and speaking time are
+/// sent with transcripts
+fn sync_speaker_stats_setting(db: &Database) -> Result<bool, String> {
+    Ok(settings::resolve(db, "sync_speaker_stats")
+        .map_err(|e| e.to_string())?
+        .is_some_and(|v| v == "true"))
+}
+
+/// Detections less sure than this are ignored
+const LANGUAGE_DETECTION_MIN_PROBABILITY: f64 = 0.5;
+
+/// A multilingual transcriber to use instead of the loaded model when that
+/// is English-only and `language` isn't English
+fn transcriber_for_language(state: &AppState, language: &str) -> Result<Option<Box<dyn TranscriptionBackend>>, String> {
+    let english_only = state
+        .transcriber
+        .lock_or_recover()
+        .as_ref()
+        .is_some_and(|t| !t.is_multilingual());
+    if language == "en" || !english_only {
+        return Ok(None);
+    }
+    multilingual_transcriber(state)?.map(Some).ok_or_else(|| {
+        format!(
+            "Transcribing in \"{}\" needs a multilingual model. Download the {} model first.",
+            language,
+            models::DEFAULT_MULTILINGUAL_MODEL
+        )
+    })
+}
+
+/// A transcriber that can tell languages apart: the configured model if it
+/// is multilingual, otherwise the `language_model` file (the base
+/// multilingual model unless set) once it has been downloaded
+fn multilingual_transcriber(state: &AppState) -> Result<Option<Box<dyn TranscriptionBackend>>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let configured = model_path(&db, &state.data_dir)?;
+    let fallback = settings::resolve(&db, "language_model")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| models::file_name(models::DEFAULT_MULTILINGUAL_MODEL));
+    let backend = transcription_backend_setting(&db)?;
+    let hf_token = hf_token(&db, &state.data_dir);
+    drop(db);
+
+    for path in [configured, state.data_dir.join("models").join(fallback)] {
+        match whisper::load_backend(backend, &path, hf_token.clone()) {
+            Ok(transcriber) if transcriber.is_multilingual() => return Ok(Some(transcriber)),
+            Ok(_) | Err(WhisperError::ModelNotFound(_)) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(None)
+}
+
+/// The language a transcript came out in: the one asked for, or the one the
+/// model picked when asked for "auto"
+fn transcript_language(requested: &str, result: &TranscriptionResult) -> Option<String> {
+    if requested == whisper::AUTO_LANGUAGE {
+        result.language.clone()
+    } else {
+        Some(requested.to_string())
+    }
+}
+
+/// Whether new recordings are translated to English as well, from
+/// `translate_to_english`; off unless set to "true"
+fn translate_setting(db: &Database) -> Result<bool, String> {
+    Ok(settings::resolve(db, "translate_to_english")
+        .map_err(|e| e.to_string())?
+        .is_some_and(|v| v == "true"))
+}
+
+/// An English translation of `recording`, transcribed from `audio_path`
+/// again in translate mode with a multilingual model. A transcript already
+/// in English is its own translation.
+fn translate_recording(
+    state: &AppState,
+    recording: &Recording,
+    audio_path: &Path,
+    vocabulary: &[String],
+) -> Result<String, String> {
+    let spoken = recording
+        .transcript_language
+        .as_deref()
+        .filter(|l| *l != "en")
+        .or(recording.detected_language.as_deref())
+        .unwrap_or(whisper::AUTO_LANGUAGE);
+    if spoken == "en" {
+        return Ok(recording.transcript.clone().unwrap_or_default());
+    }
+    let transcriber = multilingual_transcriber(state)?.ok_or_else(|| {
+        format!(
+            "Translating needs a multilingual model. Download the {} model first.",
+            models::DEFAULT_MULTILINGUAL_MODEL
+        )
+    })?;
+    let options = TranscribeOptions {
+        language: Some(spoken.to_string()),
+        vocabulary: vocabulary.to_vec(),
+        translate: true,
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let result = transcriber.transcribe_with(audio_path, &options);
+    telemetry::TRANSCRIPTION_SECONDS.observe(started.elapsed().as_secs_f64());
+    result.map(|r| r.text).map_err(|e| e.to_string())
+}
+
+/// The language heard in `audio_path`, unless `detect_language` is "false",
+/// no multilingual model is around, or whisper isn't sure
+fn detect_language(state: &AppState, audio_path: &Path) -> Option<DetectedLanguage> {
+    let enabled = state
+        .db
+        .lock()
+        .ok()
+        .and_then(|db| settings::resolve(&db, "detect_language").ok())
+        .flatten()
+        .is_none_or(|v| v != "false");
+    if !enabled {
+        return None;
+    }
+    let transcriber = match multilingual_transcriber(state) {
+        Ok(transcriber) => transcriber?,
+        Err(e) => {
+            eprintln!("Language detection unavailable: {}", e);
+            return None;
+        }
+    };
+    match transcriber.detect_language(audio_path) {
+        Ok(detected) if detected.probability >= LANGUAGE_DETECTION_MIN_PROBABILITY => Some(detected),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("Language detection failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Pull org and classroom settings from the server, reloading the model if
+/// the resolved choice changed.
+fn pull_config(state: &AppState, client: &SyncClient) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let classroom_id = db.get_setting("classroom_id").map_err(|e| e.to_string())?;
+    let before = (
+        model_path(&db, &state.data_dir)?,
+        transcription_backend_setting(&db)?,
+        program_paths_setting(&db)?,
+    );
+    drop(db);
+
+    let layers = client
+        .fetch_config(classroom_id.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.replace_config_layer(settings::ORG_LAYER, &layers.org)
+        .map_err(|e| e.to_string())?;
+    db.replace_config_layer(settings::CLASSROOM_LAYER, &layers.classroom)
+        .map_err(|e| e.to_string())?;
+    let after = (
+        model_path(&db, &state.data_dir)?,
+        transcription_backend_setting(&db)?,
+        program_paths_setting(&db)?,
+    );
+    apply_capture_settings(&db, &mut *state.recorder.lock().map_err(|e| e.to_string())?)?;
+    whisper::set_threads(transcription_threads_setting(&db)?);
+    let hf_token = hf_token(&db, &state.data_dir);
+    drop(db);
+
+    if after != before {
+        let (model_path, backend, program_paths) = after;
+        whisper::set_program_paths(program_paths);
+        match whisper::load_backend(backend, &model_path, hf_token) {
+            Ok(transcriber) => *state.transcriber.lock().map_err(|e| e.to_string())? = Some(transcriber),
+            Err(WhisperError::ModelNotFound(_)) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Days to keep local audio once the server has confirmed its copy, from the
+/// `audio_after_upload` policy ("keep", "delete", or "keep_days" and
+/// "compress_keep_days" with `audio_keep_days`). `None` means audio is
+/// never uploaded or removed.
+fn audio_keep_days(db: &Database) -> Result<Option<i64>, String> {
+    let policy = audio_after_upload_setting(db)?;
+    match policy.as_str() {
+        "delete" => Ok(Some(0)),
+        "keep_days" | "compress_keep_days" => Ok(Some(
+            settings::resolve(db, "audio_keep_days")
+                .map_err(|e| e.to_string())?
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(0)
+                .max(0),
+        )),
+        _ => Ok(None),
+    }
+}
+
+fn audio_after_upload_setting(db: &Database) -> Result<String, String> {
+    Ok(settings::resolve(db, "audio_after_upload")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "keep".to_string()))
+}
+
+/// Under "compress_keep_days", re-encode uploaded audio still kept as WAV to
+/// FLAC for the days it stays. The archive copy isn't touched, since the
+/// FLAC encoder writes 16-bit samples and would lose its bit depth. Encoded with the database unlocked, since a
+/// long recording takes a while.
+fn compress_uploaded_audio(state: &AppState) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    if audio_after_upload_setting(&db)? != "compress_keep_days" {
+        return Ok(0);
+    }
+    let uploaded = db
+        .get_offloadable_recordings(&chrono::Utc::now().to_rfc3339())
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    let mut compressed = 0;
+    for recording in uploaded.iter().filter(|r| r.audio_format == AudioFormat::Wav) {
+        let audio_path = Path::new(&recording.audio_path);
+        if !audio_path.is_file() {
+            continue;
+        }
+        let stored = store_audio(audio_path, AudioFormat::Flac)?;
+        let lanes = match (&recording.teacher_audio_path, &recording.student_audio_path) {
+            (Some(teacher), Some(student)) => Some((
+                store_audio(Path::new(teacher), AudioFormat::Flac)?,
+                store_audio(Path::new(student), AudioFormat::Flac)?,
+            )),
+            _ => None,
+        };
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.set_audio_file(&recording.id, &stored.to_string_lossy(), AudioFormat::from_path(&stored))
+            .map_err(|e| e.to_string())?;
+        if let Some((teacher, student)) = lanes {
+            db.set_lane_files(&recording.id, &teacher.to_string_lossy(), &student.to_string_lossy())
+                .map_err(|e| e.to_string())?;
+        }
+        compressed += (stored != audio_path) as usize;
+    }
+    Ok(compressed)
+}
+
+/// Delete local audio the server has held for at least `keep_days`.
+/// Only recordings with a checksum-confirmed upload are touched, and only
+/// the files the server has a copy of. That's the mixed audio alone: a
+/// dual-channel recording's teacher and student lanes and the archive copy
+/// are never uploaded, so they're kept until the recording expires. The
+/// archive is original-quality audio a school may need for review, which
+/// is also why `compress_uploaded_audio` leaves it as it is.
+fn offload_uploaded_audio(db: &Database, keep_days: i64) -> Result<usize, String> {
+    let before = (chrono::Utc::now() - chrono::Duration::days(keep_days)).to_rfc3339();
+    let uploaded = db
+        .get_offloadable_recordings(&before)
+        .map_err(|e| e.to_string())?;
+
+    let mut removed = 0;
+    'recordings: for recording in &uploaded {
+        for path in recording.audio_files().filter(|p| *p == recording.audio_path) {
+            match std::fs::remove_file(path) {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(_) => continue 'recordings,
+            }
+        }
+        db.mark_audio_offloaded(&recording.id)
+            .map_err(|e| e.to_string())?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Upload audio for synced recordings; returns (uploaded, errors)
+fn upload_pending_audio(state: &AppState, client: &SyncClient) -> Result<(usize, Vec<String>), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let pending = db
+        .get_audio_awaiting_upload()
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    let mut uploaded = 0;
+    let mut errors = Vec::new();
+    for recording in &pending {
+        match client.upload_audio(recording) {
+            Ok(_) => {
+                let db = state.db.lock().map_err(|e| e.to_string())?;
+                db.mark_audio_uploaded(&recording.id)
+                    .map_err(|e| e.to_string())?;
+                uploaded += 1;
+            }
+            Err(e) => errors.push(format!("Recording {} audio upload: {}", recording.id, e)),
+        }
+    }
+    Ok((uploaded, errors))
+}
+
+/// Send outstanding deletion notices; returns (sent, errors)
+fn send_deletion_notices(state: &AppState, client: &SyncClient) -> Result<(usize, Vec<String>), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let pending = db
+        .get_pending_deletion_notices()
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    let mut sent = 0;
+    let mut errors = Vec::new();
+    for recording in &pending {
+        match client.notify_deletion(recording, "expired") {
+            Ok(_) => {
+                let db = state.db.lock().map_err(|e| e.to_string())?;
+                db.mark_deletion_notified(&recording.id)
+                    .map_err(|e| e.to_string())?;
+                sent += 1;
+            }
+            Err(e) => errors.push(format!("Recording {} deletion notice: {}", recording.id, e)),
+        }
+    }
+    Ok((sent, errors))
+}
+
+/// Tell the server about completed assignments whose recordings have all
+/// gone up. The rest wait for a later sync.
+fn report_assignments(state: &AppState, client: &SyncClient, device_id: &str) -> Result<Vec<String>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let pending = db
+        .get_unreported_assignments()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|a| Ok((db.get_assignment_recordings(&a.id).map_err(|e| e.to_string())?, a)))
+        .collect::<Result<Vec<_>, String>>()?;
+    drop(db);
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !client.capabilities().assignments {
+        return Ok(vec!["The server doesn't take assignment reports, so completed assignments are kept".to_string()]);
+    }
+
+    let mut errors = Vec::new();
+    for (recordings, assignment) in pending.iter().filter(|(r, _)| r.iter().all(|r| r.synced)) {
+        match client.report_assignment(assignment, recordings, device_id) {
+            Ok(_) => {
+                let db = state.db.lock().map_err(|e| e.to_string())?;
+                db.mark_assignment_reported(&assignment.id)
+                    .map_err(|e| e.to_string())?;
+            }
+            Err(e) => errors.push(format!("Assignment {}: {}", assignment.id, e)),
+        }
+    }
+    Ok(errors)
+}
+
+/// Save a capture interrupted by a crash as a new recording
+fn recover_interrupted_capture(db: &Database, recorder: &AudioRecorder, data_dir: &Path) -> Result<(), String> {
+    // Chunks only ever feed the transcript of the recording they came from
+    recorder.discard_chunks();
+
+    // Parts finished at the length limit that never made it through the pipeline
+    for part in recorder.rollover_captures() {
+        let id = new_recording_id(db)?;
+        let audio_path = data_dir.join("audio").join(format!("{}.wav", id));
+        let duration = recorder
+            .convert_capture(&part, &audio_path)
+            .map_err(|e| e.to_string())?;
+        let archive = recorder.conversion().archive_path(&audio_path);
+        save_recovered(db, id, &audio_path, archive, duration)?;
+    }
+
+    let id = new_recording_id(db)?;
+    let audio_path = data_dir.join("audio").join(format!("{}.wav", id));
+    let Some(duration) = recorder
+        .recover_capture(&audio_path)
+        .map_err(|e| e.to_string())?
+    else {
+        return Ok(());
+    };
+    let archive = recorder.conversion().archive_path(&audio_path);
+    save_recovered(db, id, &audio_path, archive, duration)
+}
+
+fn save_recovered(
+    db: &Database,
+    id: String,
+    audio_path: &Path,
+    archive: Option<PathBuf>,
+    duration: f64,
+) -> Result<(), String> {
+    let format = audio_format_setting(db)?;
+    let audio_path = store_audio(audio_path, format)?;
+
+    let student_id = db
+        .get_setting("student_id")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "unknown".to_string());
+    let mut recording = Recording::new(id, student_id, audio_path.to_string_lossy().to_string(), duration);
+    recording.audio_format = AudioFormat::from_path(&audio_path);
+    recording.archive_audio_path = archive.map(|p| p.to_string_lossy().to_string());
+    recording.expires_at = default_expiry(db)?;
+    apply_guest_mode(db, &mut recording)?;
+    db.save_recording(&recording).map_err(|e| e.to_string())?;
+    println!("Recovered {:.0}s of audio from an interrupted recording", duration);
+    Ok(())
+}
+
+/// Watch for input devices being plugged in or removed. cpal has no
+/// device-change callback, so the host's device list is polled.
+///
+/// Emits `audio-devices-changed` with the new list whenever it differs, and
+/// `microphone-status` when recording becomes possible or impossible.
+fn spawn_device_watcher(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut last_available = None;
+        let mut last_devices: Option<Vec<InputDevice>> = None;
+        loop {
+            let state = app.state::<AppState>();
+            let selected = match state.recorder.lock() {
+                Ok(recorder) => recorder.selected_device(),
+                Err(_) => return,
+            };
+            // Enumerating can stall for a while, and with the recorder
+            // locked that would hold up starting or stopping a recording
+            let available = audio::input_device_available(selected.as_deref());
+            let devices = audio::list_input_devices().ok();
+
+            if let Some(devices) = devices {
+                if last_devices.as_ref() != Some(&devices) {
+                    // The first poll only records the baseline
+                    if last_devices.is_some() {
+                        let _ = app.emit("audio-devices-changed", &devices);
+                    }
+                    last_devices = Some(devices);
+                }
+            }
+            if last_available != Some(available) {
+                let _ = app.emit("microphone-status", MicrophoneStatus { available });
+                last_available = Some(available);
+            }
+            std::thread::sleep(std::time::Duration::from_secs(2));
+        }
+    });
+}
+
+/// Email the teacher a digest of the day once `digest_time` (local HH:MM)
+/// has passed. A station that was off then sends it when it next runs that
+/// day. Checked once a minute.
+fn spawn_digest_scheduler(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(std::time::Duration::from_secs(60));
+        let state = app.state::<AppState>();
+        let now = chrono::Local::now();
+        let due = match state.db.lock() {
+            Ok(db) => digest_due(&db, now),
+            Err(_) => return,
+        };
+        match due {
+            Ok(true) => {
+                if let Err(e) = send_digest_for(&state, now.date_naive()) {
+                    eprintln!("Nightly digest failed: {}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => eprintln!("{}", e),
+        }
+    });
+}
+
+/// Delete guest recordings as they come due while the app stays open; the
+/// startup pass catches the rest. Checked once an hour.
+fn spawn_guest_purger(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(std::time::Duration::from_secs(3600));
+        let state = app.state::<AppState>();
+        let purged = match state.db.lock() {
+            Ok(db) => purge_guest_recordings(&db),
+            Err(_) => return,
+        };
+        if let Err(e) = purged {
+            eprintln!("Failed to delete guest recordings: {}", e);
+        }
+    });
+}
+
+/// Whether `digest_time` has passed today with no digest job since. A failed
+/// send counts, so a broken mail server is tried once a night rather than
+/// every minute.
+fn digest_due(db: &Database, now: chrono::DateTime<chrono::Local>) -> Result<bool, String> {
+    let Some(time) = digest_time_setting(db)? else {
+        return Ok(false);
+    };
+    if now.time() < time {
+        return Ok(false);
+    }
+    let since = now
+        .date_naive()
+        .and_time(time)
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .unwrap_or(now)
+        .with_timezone(&chrono::Utc)
+        .to_rfc3339();
+    let filter = JobFilter {
+        kind: Some(JobKind::Digest),
+        since: Some(since),
+        ..Default::default()
+    };
+    Ok(db.get_job_history(1, &filter).map_err(|e| e.to_string())?.is_empty())
+}
+
+/// `digest_time`, when the nightly digest goes out; unset means never
+fn digest_time_setting(db: &Database) -> Result<Option<chrono::NaiveTime>, String> {
+    settings::resolve(db, "digest_time")
+        .map_err(|e| e.to_string())?
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            chrono::NaiveTime::parse_from_str(&t, "%H:%M").map_err(|_| format!("Invalid digest_time setting: {}", t))
+        })
+        .transpose()
+}
+
+/// `smtp`, the mail server the digest is sent through. Its password is
+/// kept apart, sealed in `smtp_password`, unless a pushed config carries one.
+fn smtp_setting(db: &Database, data_dir: &Path) -> Result<Option<SmtpConfig>, String> {
+    let Some(mut smtp) = settings::resolve(db, "smtp")
+        .map_err(|e| e.to_string())?
+        .filter(|json| !json.is_empty())
+        .map(|json| serde_json::from_str::<SmtpConfig>(&json).map_err(|e| format!("Invalid smtp setting: {}", e)))
+        .transpose()?
+    else {
+        return Ok(None);
+    };
+    if smtp.password.is_none() {
+        if let Some(sealed) = db.get_setting("smtp_password").map_err(|e| e.to_string())? {
+            smtp.password = Some(secrets::unseal(data_dir, "smtp_password", &sealed).map_err(|e| e.to_string())?);
+        }
+    }
+    Ok(Some(smtp))
+}
+
+/// Build the digest for `date` and email it to `teacher_email`, logged as
+/// a digest job
+fn send_digest_for(state: &AppState, date: chrono::NaiveDate) -> Result<DailyDigest, String> {
+    run_job(
+        &state.db,
+        JobKind::Digest,
+        None,
+        || {
+            let db = state.db.lock().map_err(|e| e.to_string())?;
+            if is_local_only(&db)? {
+                return Err("Local-only mode is on, so the digest is not emailed".to_string());
+            }
+            let to = settings::resolve(&db, "teacher_email")
+                .map_err(|e| e.to_string())?
+                .filter(|email| !email.is_empty())
+                .ok_or_else(|| "No teacher email is set".to_string())?;
+            let smtp = smtp_setting(&db, &state.data_dir)?.ok_or_else(|| "No mail server is set".to_string())?;
+            let digest = digest::build(&db, date, confidence_threshold_setting(&db)?).map_err(|e| e.to_string())?;
+            let locale = report_locale(&db)?;
+            drop(db);
+
+            let (subject, body) = digest::render(&digest, &locale);
+            smtp::send(&smtp, &to, &subject, &body).map_err(|e| e.to_string())?;
+            Ok(digest)
+        },
+        |digest| Ok(Some(format!("Sent the digest for {}", digest.date))),
+    )
+}
+
+/// `None` is today; otherwise a `YYYY-MM-DD` date
+fn digest_date(date: Option<String>) -> Result<chrono::NaiveDate, String> {
+    match date {
+        Some(date) => {
+            chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", date))
+        }
+        None => Ok(chrono::Local::now().date_naive()),
+    }
+}
+
+/// Fill in who said each segment, where the way the recording was captured
+/// tells; see `speakers`. Lanes that can't be read leave segments as they are.
+fn attribute_speakers(recording: &Recording, activity: Option<&ChannelActivity>, segments: &mut [TranscriptSegment]) {
+    match (&recording.teacher_audio_path, &recording.student_audio_path) {
+        (Some(teacher), Some(student)) => {
+            match (audio::read_audio(Path::new(teacher)), audio::read_audio(Path::new(student))) {
+                (Ok((teacher, sample_rate)), Ok((student, _))) => {
+                    speakers::from_lanes(segments, &teacher, &student, sample_rate)
+                }
+                (Err(e), _) | (_, Err(e)) => eprintln!("Failed to read lanes of {}: {}", recording.id, e),
+            }
+        }
+        _ => {
+            if let Some(activity) = activity {
+                speakers::from_channel_activity(segments, activity);
+            }
+        }
+    }
+}
+
+/// Name the speakers of a freshly transcribed recording that were named in
+/// earlier recordings of the same student with this teacher and remembered.
+/// Names already on the recording stay.
+fn apply_speaker_names(db: &Database, recording: &mut Recording, segments: &[TranscriptSegment]) -> Result<(), String> {
+    let teacher_name = db
+        .get_setting("teacher_name")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    let names = db
+        .get_speaker_names(&recording.student_id, &teacher_name)
+        .map_err(|e| e.to_string())?;
+    for speaker in segments.iter().filter_map(|s| s.speaker.as_ref()) {
+        if let Some(name) = names.get(speaker) {
+            recording.speaker_labels.entry(speaker.clone()).or_insert_with(|| name.clone());
+        }
+    }
+    Ok(())
+}
+
+/// The teacher's enrolled voice, from `teacher_voiceprint`
+fn teacher_voiceprint(db: &Database) -> Result<Option<Voiceprint>, String> {
+    Ok(settings::resolve(db, "teacher_voiceprint")
+        .map_err(|e| e.to_string())?
+        .and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+/// `student_speaker_strategy`, how the student's segments are picked out
+/// for scoring; unset scores every segment
+fn student_strategy_setting(db: &Database) -> Result<Option<StudentStrategy>, String> {
+    match settings::resolve(db, "student_speaker_strategy").map_err(|e| e.to_string())? {
+        Some(value) if !value.is_empty() => StudentStrategy::parse(&value)
+            .map(Some)
+            .ok_or_else(|| format!("Unknown student speaker strategy: {}", value)),
+        _ => Ok(None),
+    }
+}
+
+/// Flag a freshly transcribed recording in which only the teacher is heard,
+/// and otherwise pick out the student by `student_speaker_strategy`,
+/// reading `audio_path` to match voices against the enrolled ones. Speakers
+/// sounding like the teacher's or the student's enrolled voice are labelled
+/// as them first. With `archive_teacher_only` on, an unreviewed
+/// teacher-only one is archived too.
+fn classify_speakers(
+    db: &Database,
+    recording: &mut Recording,
+    audio_path: &Path,
+    segments: &[TranscriptSegment],
+) -> Result<(), String> {
+    let voiceprint = teacher_voiceprint(db)?;
+    let student_voiceprint = db
+        .get_student_voiceprint(&recording.student_id)
+        .map_err(|e| e.to_string())?;
+    let strategy = student_strategy_setting(db)?;
+    let teacher_name = db
+        .get_setting("teacher_name")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    let (samples, sample_rate) = if voiceprint.is_some() || student_voiceprint.is_some() {
+        audio::read_audio(audio_path).map_err(|e| e.to_string())?
+    } else {
+        (Vec::new(), 16000)
+    };
+    speakers::label_enrolled(
+        segments,
+        &mut recording.speaker_labels,
+        &samples,
+        sample_rate,
+        voiceprint.as_ref(),
+        student_voiceprint.as_ref(),
+    );
+    recording.teacher_only = speakers::teacher_only(
+        segments,
+        &recording.speaker_labels,
+        &teacher_name,
+        &samples,
+        sample_rate,
+        voiceprint.as_ref(),
+    );
+    let archive = settings::resolve(db, "archive_teacher_only")
+        .map_err(|e| e.to_string())?
+        .is_some_and(|v| v == "true");
+    if recording.teacher_only && archive && recording.review_status == "unreviewed" {
+        recording.review_status = "archived".to_string();
+    }
+    recording.student_speaker = match strategy {
+        _ if recording.teacher_only => None,
+        // A pick made by hand stands while that speaker is still heard
+        Some(StudentStrategy::Manual) => recording
+            .student_speaker
+            .take()
+            .filter(|student| segments.iter().any(|s| s.speaker.as_ref() == Some(student))),
+        Some(strategy) => speakers::pick_student(
+            segments,
+            &recording.speaker_labels,
+            &teacher_name,
+            strategy,
+            &samples,
+            sample_rate,
+            voiceprint.as_ref(),
+        ),
+        _ => None,
+    };
+    Ok(())
+}
+
+/// Tag a freshly transcribed recording with its detected activity type.
+/// Applied to the tags as stored, so edits made while it was transcribing
+/// stay, and flagged for the next sync like a tag edit.
+fn tag_activity(db: &Database, recording: &mut Recording, segments: &[TranscriptSegment]) -> Result<(), String> {
+    let features = classify::ActivityFeatures::from_segments(segments, recording.duration_seconds);
+    let tag = classify::classify(&features, recording.reference_passage.is_some());
+    let mut tags = db
+        .get_recording(&recording.id)
+        .map_err(|e| e.to_string())?
+        .map_or_else(|| recording.tags.clone(), |stored| stored.tags);
+    let before = tags.clone();
+    classify::apply_tag(&mut tags, tag);
+    if tags != before {
+        let update = MetadataUpdate { tags: Some(tags.clone()), ..Default::default() };
+        db.update_metadata(&recording.id, &update).map_err(|e| e.to_string())?;
+        // So a later save of the whole recording keeps the flag
+        recording.dirty_fields |= db::DIRTY_TAGS;
+    }
+    recording.tags = tags;
+    Ok(())
+}
+
+/// `confidence_weighting` ("exclude" or "weight"; off by default) and
+/// `confidence_threshold`, which defaults to 0.5
+fn confidence_weighting_setting(db: &Database) -> Result<Option<ConfidenceWeighting>, String> {
+    let mode = match settings::resolve(db, "confidence_weighting")
+        .map_err(|e| e.to_string())?
+        .as_deref()
+    {
+        Some("exclude") => ConfidenceMode::Exclude,
+        Some("weight") => ConfidenceMode::Weight,
+        _ => return Ok(None),
+    };
+    let threshold = confidence_threshold_setting(db)?;
+    Ok(Some(ConfidenceWeighting { mode, threshold }))
+}
+
+fn confidence_threshold_setting(db: &Database) -> Result<f64, String> {
+    Ok(settings::resolve(db, "confidence_threshold")
+        .map_err(|e| e.to_string())?
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|t| (0.0..=1.0).contains(t))
+        .unwrap_or(metrics::DEFAULT_CONFIDENCE_THRESHOLD))
+}
+
+/// Score a passage reading with fluency metrics and the active rubric.
+/// With confidence weighting on, adjusted metrics are kept alongside the raw
+/// ones and decide the level.
+/// Returns `None` for recordings without a passage or transcript, and for
+/// teacher-only ones, which lose any assessment they had.
+fn score_assessment(db: &Database, data_dir: &Path, recording_id: &str) -> Result<Option<Assessment>, String> {
+    let Some(recording) = db.get_recording(recording_id).map_err(|e| e.to_string())? else {
+        return Err("Recording not found".to_string());
+    };
+    if recording.teacher_only {
+        db.delete_assessment(recording_id).map_err(|e| e.to_string())?;
+        return Ok(None);
+    }
+    let (Some(passage), Some(transcript)) = (&recording.reference_passage, &recording.transcript) else {
+        return Ok(None);
+    };
+
+    let mut segments = db.get_segments(recording_id).map_err(|e| e.to_string())?;
+    // Only the student's reading counts once they're picked out
+    let student_transcript = recording.student_speaker.as_ref().map(|student| {
+        segments.retain(|s| s.speaker.as_ref() == Some(student));
+        join_segments(&segments)
+    });
+    let transcript = student_transcript.as_ref().unwrap_or(transcript);
+    let reading_seconds = metrics::reading_seconds(&segments, recording.duration_seconds);
+    let fluency = metrics::fluency(passage, transcript, reading_seconds);
+    let adjusted = confidence_weighting_setting(db)?
+        .map(|weighting| metrics::adjusted_fluency(passage, &segments, reading_seconds, weighting));
+
+    // An assignment that brought its own rubric is scored by it
+    let assignment_rubric = match &recording.assignment_id {
+        Some(id) => db.get_assignment(id).map_err(|e| e.to_string())?.and_then(|a| a.rubric),
+        None => None,
+    };
+    let rubric_path = data_dir.join("rubric.json");
+    let rubric = if assignment_rubric.is_some() {
+        assignment_rubric
+    } else if rubric_path.exists() {
+        Some(Rubric::load(&rubric_path).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+
+    let assessment = Assessment {
+        recording_id: recording_id.to_string(),
+        level: rubric
+            .as_ref()
+            .and_then(|r| r.evaluate(adjusted.as_ref().unwrap_or(&fluency))),
+        rubric_name: rubric.map(|r| r.name),
+        metrics: fluency,
+        adjusted,
+        scored_at: chrono::Utc::now().to_rfc3339(),
+    };
+    db.save_assessment(&assessment).map_err(|e| e.to_string())?;
+    Ok(Some(assessment))
+}
+
+// ========== Settings Commands ==========
+
+#[tauri::command]
+fn get_settings(state: State<AppState>) -> Result<AppSettings, String> {
+    let stored = match state.settings_cache.get() {
+        Some(stored) => stored,
+        None => {
+            let db = state.db.lock().map_err(|e| e.to_string())?;
+            state.settings_cache.load(|| stored_settings(&db))?
+        }
+    };
+    let model_loaded = state.transcriber.lock_or_recover().is_some();
+    let audio_device = state.recorder.lock().map_err(|e| e.to_string())?.selected_device();
+    Ok(AppSettings {
+        model_loaded,
+        // Checked without the recorder locked, since enumerating devices can stall
+        microphone_available: audio::input_device_available(audio_device.as_deref()),
+        audio_device,
+        ..stored
+    })
+}
+
+/// The part of `AppSettings` read from the database; the rest is filled in
+/// by `get_settings`
+fn stored_settings(db: &Database) -> Result<AppSettings, String> {
+    let student_id = db
+        .get_setting("student_id")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    let student_name = db
+        .get_setting("student_name")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    let teacher_name = db
+        .get_setting("teacher_name")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let setup_complete = db
+        .get_setting("setup_complete")
+        .map_err(|e| e.to_string())?
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    let local_only = is_local_only(db)?;
+    let guest_mode = is_guest_mode(db)?;
+    let transcription_language = transcription_language(db)?;
+    let live_captions = live_caption_seconds_setting(db)?.is_some();
+    let word_timestamps = word_timestamps_setting(db)?;
+    let sync_speaker_stats = sync_speaker_stats_setting(db)?;
+    let student_speaker_strategy = student_strategy_setting(db)?;
+    let transcribe_pending_on_start = transcribe_pending_on_start_setting(db)?;
+
+    Ok(AppSettings {
+        student_id,
+        student_name,
+        teacher_name,
+        server_url,
+        model_loaded: false,
+        setup_complete,
+        local_only,
+        guest_mode,
+        audio_device: None,
+        microphone_available: false,
+        transcription_language,
+        live_captions,
+        word_timestamps,
+        sync_speaker_stats,
+        student_speaker_strategy,
+        transcribe_pending_on_start,
+    })
+}
+
+/// Set what happens to local audio after the server confirms receipt:
+/// "keep", "delete", "keep_days" (removed `keep_days` days after upload) or
+/// "compress_keep_days" (kept as FLAC until then)
+#[tauri::command]
+fn set_audio_upload_policy(
+    state: State<AppState>,
+    policy: String,
+    keep_days: Option<u32>,
+) -> Result<(), String> {
+    if !["keep", "delete", "keep_days", "compress_keep_days"].contains(&policy.as_str()) {
+        return Err(format!("Unknown audio policy: {}", policy));
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("audio_after_upload", &policy)
+        .map_err(|e| e.to_string())?;
+    if let Some(days) = keep_days {
+        db.set_setting("audio_keep_days", &days.to_string())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Assign this station to a classroom; its settings apply on the next config pull
+#[tauri::command]
+fn set_classroom(state: State<AppState>, classroom_id: String) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("classroom_id", &classroom_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Locale for reports and exports, as a BCP 47 tag such as `en-GB` or `de-DE`
+#[tauri::command]
+fn set_locale(state: State<AppState>, locale: String) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("locale", locale.trim())
+        .map_err(|e| e.to_string())
+}
+
+/// Every setting with the layer (org, classroom or device) its value comes from
+#[tauri::command]
+fn get_effective_settings(state: State<AppState>) -> Result<Vec<ResolvedSetting>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    Ok(settings::effective(&db)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|setting| ResolvedSetting { value: settings::redact(&setting.key, setting.value), ..setting })
+        .collect())
+}
+
+#[tauri::command]
+fn get_settings_history(state: State<AppState>, key: Option<String>) -> Result<Vec<SettingChange>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    Ok(db
+        .get_settings_history(key.as_deref())
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|change| SettingChange {
+            old_value: change.old_value.map(|v| settings::redact(&change.key, v)),
+            new_value: change.new_value.map(|v| settings::redact(&change.key, v)),
+            ..change
+        })
+        .collect())
+}
+
+/// Undo the most recent change to `key`. A device change is reverted in
+/// place; a remote change is overridden on this device with the previous
+/// value, since the next config pull would otherwise reapply it.
+#[tauri::command]
+fn rollback_setting(state: State<AppState>, key: String) -> Result<Option<String>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let Some(change) = db
+        .get_settings_history(Some(&key))
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+    else {
+        return Err(format!("No changes recorded for {}", key));
+    };
+
+    let reverted = match (change.layer.as_str(), &change.old_value) {
+        (settings::DEVICE_LAYER, Some(old)) => db.set_setting_as(&key, old, "rollback"),
+        (settings::DEVICE_LAYER, None) => db.delete_setting_as(&key, "rollback"),
+        // Copying a remote pipeline onto the device mustn't let its scripts in
+        (layer, Some(old)) if key == "pipeline" => {
+            let pipeline = local_scripts_only(Pipeline::from_json(old).map_err(|e| e.to_string())?, layer);
+            let json = serde_json::to_string(&pipeline).map_err(|e| e.to_string())?;
+            db.set_setting_as(&key, &json, "rollback")
+        }
+        (_, Some(old)) => db.set_setting_as(&key, old, "rollback"),
+        (_, None) => {
+            return Err(format!(
+                "{} was introduced by remote config; change it on the server",
+                key
+            ))
+        }
+    };
+    reverted.map_err(|e| e.to_string())?;
+
+    settings::resolve(&db, &key).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_local_only(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    if cfg!(feature = "local-only") && !enabled {
+        return Err("This build is local-only; sync cannot be enabled.".to_string());
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("local_only", if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())
+}
+
+/// Record as the guest student, for demos and mic tests. Guest recordings
+/// are never synced or exported, stay out of the digest, and are deleted a
+/// day after they were made.
+#[tauri::command]
+fn set_guest_mode(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("guest_mode", if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn save_settings(
+    state: State<AppState>,
+    student_id: String,
+    student_name: String,
+    teacher_name: String,
+    server_url: String,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("student_id", &student_id)
+        .map_err(|e| e.to_string())?;
+    db.set_setting("student_name", &student_name)
+        .map_err(|e| e.to_string())?;
+    db.set_setting("teacher_name", &teacher_name)
+        .map_err(|e| e.to_string())?;
+    db.set_setting("server_url", &server_url)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn complete_setup(
+    state: State<AppState>,
+    student_name: String,
+    teacher_name: String,
+    server_url: String,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+
+    // Generate student ID from name (lowercase, no spaces)
+    let student_id = student_name
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ')
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("-");
+
+    db.set_setting_as("student_id", &student_id, "provisioning")
+        .map_err(|e| e.to_string())?;
+    db.set_setting_as("student_name", &student_name, "provisioning")
+        .map_err(|e| e.to_string())?;
+    db.set_setting_as("teacher_name", &teacher_name, "provisioning")
+        .map_err(|e| e.to_string())?;
+    db.set_setting_as("server_url", &server_url, "provisioning")
+        .map_err(|e| e.to_string())?;
+    db.set_setting_as("setup_complete", "true", "provisioning")
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// ========== Recording Commands ==========
+
+/// The stages run after each recording, defaults filled in
+#[tauri::command]
+fn get_pipeline(state: State<AppState>) -> Result<Pipeline, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    pipeline_setting(&db)
+}
+
+/// Replace this device's pipeline; `None` returns to the classroom or org
+/// pipeline, or the built-in one
+#[tauri::command]
+fn set_pipeline(state: State<AppState>, pipeline: Option<Pipeline>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match pipeline {
+        Some(pipeline) => {
+            let json = serde_json::to_string(&pipeline).map_err(|e| e.to_string())?;
+            Pipeline::from_json(&json).map_err(|e| e.to_string())?;
+            db.set_setting("pipeline", &json).map_err(|e| e.to_string())
+        }
+        None => db.delete_setting_as("pipeline", "user").map_err(|e| e.to_string()),
+    }
+}
+
+#[tauri::command]
+fn start_recording(state: State<AppState>) -> Result<(), String> {
+    state.startup.require(startup::RECORDING)?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    check_recording_allowed(&db)?;
+    drop(db);
+    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+    recorder.start_recording().map_err(|e| e.to_string())
+}
+
+/// Bookmark this moment of the recording in progress, e.g. "good answer
+/// here". It is saved with the recording once that is processed.
+#[tauri::command]
+fn add_marker(state: State<AppState>, label: String) -> Result<(), String> {
+    let label = label.trim();
+    if label.is_empty() {
+        return Err("Marker label is required".to_string());
+    }
+    let recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+    recorder.mark(label.to_string()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_blackout_windows(state: State<AppState>) -> Result<Vec<BlackoutWindow>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match settings::resolve(&db, "blackout_windows").map_err(|e| e.to_string())? {
+        Some(json) => schedule::parse_windows(&json).map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Replace this device's blackout windows; `room` places the station for room-specific windows
+#[tauri::command]
+fn set_blackout_windows(
+    state: State<AppState>,
+    windows: Vec<BlackoutWindow>,
+    room: Option<String>,
+) -> Result<(), String> {
+    let json = serde_json::to_string(&windows).map_err(|e| e.to_string())?;
+    schedule::parse_windows(&json).map_err(|e| e.to_string())?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("blackout_windows", &json)
+        .map_err(|e| e.to_string())?;
+    if let Some(room) = room {
+        db.set_setting("room", &room).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Longest sound check allowed, in seconds
+const MAX_MIC_TEST_SECONDS: f32 = 30.0;
+
+/// Record a few seconds with the current input settings and report what
+/// came through, for checking a setup before the first real session.
+/// Nothing is saved.
+#[tauri::command(async)]
+fn run_mic_test(state: State<AppState>, seconds: f32, transcribe: Option<bool>) -> Result<MicTestResult, String> {
+    check_recording_allowed(&*state.db.lock().map_err(|e| e.to_string())?)?;
+    if state.recorder.lock().map_err(|e| e.to_string())?.is_recording() {
+        return Err("A recording is already in progress".to_string());
+    }
+    // Its own recorder, so none of the recording events fire and a crash
+    // mid-test leaves nothing for recovery to pick up
+    let scratch = std::env::temp_dir().join(format!("classroom-mic-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&scratch).map_err(|e| e.to_string())?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut recorder = configured_recorder(&db, scratch.join("capture.partial.wav"));
+    drop(db);
+    let result = mic_test(&state, &mut recorder, &scratch, seconds, transcribe.unwrap_or(false));
+    let _ = std::fs::remove_dir_all(&scratch);
+    result
+}
+
+fn mic_test(
+    state: &AppState,
+    recorder: &mut AudioRecorder,
+    scratch: &Path,
+    seconds: f32,
+    transcribe: bool,
+) -> Result<MicTestResult, String> {
+    recorder.start_recording().map_err(|e| e.to_string())?;
+    std::thread::sleep(std::time::Duration::from_secs_f32(seconds.clamp(1.0, MAX_MIC_TEST_SECONDS)));
+    let capture = recorder.finish_capture().map_err(|e| e.to_string())?;
+    let stats = recorder.last_capture_stats();
+    let sample_rate = AudioReader::open(&capture).map_err(|e| e.to_string())?.sample_rate();
+
+    let mut transcript = None;
+    if transcribe {
+        let clip = scratch.join("clip.wav");
+        recorder
+            .convert_capture(&capture, &clip)
+            .map_err(|e| e.to_string())?;
+        if let Some(transcriber) = state.transcriber.lock_or_recover().as_ref() {
+            transcript = Some(transcriber.transcribe(&clip).map_err(|e| e.to_string())?.text);
+        }
+    }
+
+    Ok(MicTestResult {
+        device: recorder.selected_device(),
+        sample_rate,
+        rms_dbfs: stats.rms_dbfs(),
+        peak_dbfs: stats.peak_dbfs(),
+        clipped_samples: stats.clipped_samples(),
+        clipped_ratio: stats.clipped_ratio(),
+        quality: stats.quality(),
+        transcript,
+    })
+}
+
+/// Probe the selected microphone; "denied" means the OS is withholding audio
+#[tauri::command]
+fn check_microphone_permission(state: State<AppState>) -> Result<MicrophonePermission, String> {
+    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+    recorder
+        .check_microphone_permission()
+        .map_err(|e| e.to_string())
+}
+
+/// Open the OS privacy pane where microphone access is granted
+#[tauri::command]
+fn open_microphone_settings(app: AppHandle) -> Result<(), String> {
+    let url = audio::microphone_settings_url()
+        .ok_or_else(|| "Microphone access is managed by the system on this platform".to_string())?;
+    app.opener()
+        .open_url(url, None::<&str>)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_audio_devices() -> Result<Vec<InputDevice>, String> {
+    audio::list_input_devices().map_err(|e| e.to_string())
+}
+
+/// Devices system audio can be recorded from, and how to set one up where
+/// the OS doesn't offer loopback
+#[tauri::command]
+fn list_system_audio_sources() -> Result<SystemAudioSources, String> {
+    Ok(SystemAudioSources {
+        sources: audio::list_system_sources().map_err(|e| e.to_string())?,
+        guidance: audio::system_audio_guidance(),
+    })
+}
+
+/// Choose the microphone to record from; `None` returns to the system default
+#[tauri::command]
+fn set_audio_device(state: State<AppState>, name: Option<String>) -> Result<(), String> {
+    if let Some(name) = &name {
+        let devices = audio::list_input_devices().map_err(|e| e.to_string())?;
+        if !devices.iter().any(|d| &d.name == name) {
+            return Err(format!("Input device not found: {}", name));
+        }
+    }
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_device(name.clone());
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("audio_device", name.as_deref().unwrap_or(""))
+        .map_err(|e| e.to_string())
+}
+
+/// Fixed input gain multiplier (0.1–10), with optional automatic gain control
+#[tauri::command]
+fn set_input_gain(state: State<AppState>, gain: f32, automatic: Option<bool>) -> Result<(), String> {
+    if !(0.1..=10.0).contains(&gain) {
+        return Err("Input gain must be between 0.1 and 10".to_string());
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("input_gain", &gain.to_string())
+        .map_err(|e| e.to_string())?;
+    if let Some(automatic) = automatic {
+        db.set_setting("auto_gain", if automatic { "true" } else { "false" })
+            .map_err(|e| e.to_string())?;
+    }
+    let input_gain = input_gain_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_input_gain(input_gain);
+    Ok(())
+}
+
+#[tauri::command]
+fn set_resample_quality(state: State<AppState>, quality: ResampleQuality) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let value = match quality {
+        ResampleQuality::Fast => "fast",
+        ResampleQuality::High => "high",
+    };
+    db.set_setting("resample_quality", value)
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_resample_quality(quality);
+    Ok(())
+}
+
+/// Denoise recordings with a spectral gate before they're saved
+#[tauri::command]
+fn set_noise_suppression(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("noise_suppression", if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_noise_suppression(enabled);
+    Ok(())
+}
+
+/// Cancel what the app plays back out of recordings made meanwhile, for
+/// laptops whose microphone picks up their own speakers
+#[tauri::command]
+fn set_echo_cancellation(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("echo_cancellation", if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_echo_cancellation(enabled);
+    Ok(())
+}
+
+/// Format new recordings are stored in; existing recordings keep theirs
+#[tauri::command]
+fn set_audio_format(state: State<AppState>, format: AudioFormat) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("audio_format", format.as_str())
+        .map_err(|e| e.to_string())
+}
+
+/// Keep a full-quality copy of each new recording next to the 16kHz file
+/// used for transcription, at the microphone's own rate or at 48kHz, for
+/// reviewing by ear. `None` keeps only the 16kHz file.
+#[tauri::command]
+fn set_archive_quality(state: State<AppState>, quality: Option<ArchiveQuality>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match quality {
+        Some(quality) => db.set_setting("archive_quality", quality.as_str()),
+        None => db.delete_setting_as("archive_quality", "user"),
+    }
+    .map_err(|e| e.to_string())?;
+    let quality = archive_quality_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_archive_quality(quality);
+    Ok(())
+}
+
+/// ID scheme for new recordings. UUIDv7 IDs sort by creation time; existing
+/// recordings keep their IDs either way.
+#[tauri::command]
+fn set_recording_id_scheme(state: State<AppState>, scheme: IdScheme) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("recording_id_scheme", scheme.as_str())
+        .map_err(|e| e.to_string())
+}
+
+/// Stop automatically after `seconds` of input below `threshold_dbfs`;
+/// `None` or 0 seconds turns auto-stop off
+#[tauri::command]
+fn set_auto_stop(
+    state: State<AppState>,
+    seconds: Option<f32>,
+    threshold_dbfs: Option<f32>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("auto_stop_silence_seconds", &seconds.unwrap_or(0.0).to_string())
+        .map_err(|e| e.to_string())?;
+    if let Some(threshold) = threshold_dbfs {
+        db.set_setting("auto_stop_threshold_dbfs", &threshold.to_string())
+            .map_err(|e| e.to_string())?;
+    }
+    let silence_stop = silence_stop_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_silence_stop(silence_stop);
+    Ok(())
+}
+
+/// Roll recordings over into a new one every `minutes`; `None` or 0 lets
+/// them run as long as they like
+#[tauri::command]
+fn set_max_duration(state: State<AppState>, minutes: Option<f32>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("max_recording_minutes", &minutes.unwrap_or(0.0).to_string())
+        .map_err(|e| e.to_string())?;
+    let max_duration = max_duration_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_max_duration(max_duration);
+    Ok(())
+}
+
+/// Transcribe long recordings `minutes` at a time while they're still going,
+/// so the transcript is ready soon after they stop; `None` or 0 waits for
+/// the end. Takes effect from the next recording.
+#[tauri::command]
+fn set_chunk_minutes(state: State<AppState>, minutes: Option<f32>) -> Result<(), String> {
+    if minutes.is_some_and(|m| !m.is_finite() || m < 0.0) {
+        return Err("Chunk length must be a positive number of minutes".to_string());
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("chunk_minutes", &minutes.unwrap_or(0.0).to_string())
+        .map_err(|e| e.to_string())?;
+    let chunk_seconds = chunk_seconds_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_chunk_seconds(chunk_seconds);
+    Ok(())
+}
+
+/// Caption recordings as they're made, transcribing `window_seconds` (5 to
+/// 10) at a time and sending the text so far as `live-transcript` events.
+/// The windows replace `chunk_minutes` while on, and the whole recording is
+/// still transcribed at full quality once it stops. `None` turns captions
+/// off. Takes effect from the next recording.
+#[tauri::command]
+fn set_live_captions(state: State<AppState>, window_seconds: Option<f32>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match window_seconds {
+        Some(seconds) if !LIVE_WINDOW_SECONDS.contains(&seconds) => {
+            return Err("Live captions need a window of 5 to 10 seconds".to_string());
+        }
+        Some(seconds) => db.set_setting("live_caption_seconds", &seconds.to_string()),
+        None => db.delete_setting_as("live_caption_seconds", "user"),
+    }
+    .map_err(|e| e.to_string())?;
+    let chunk_seconds = chunk_seconds_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_chunk_seconds(chunk_seconds);
+    Ok(())
+}
+
+/// Point a dual-channel recording at its teacher and student files
+fn set_lane_paths(recording: &mut Recording, lanes: Option<(PathBuf, PathBuf)>) {
+    if let Some((teacher, student)) = lanes {
+        recording.teacher_audio_path = Some(teacher.to_string_lossy().to_string());
+        recording.student_audio_path = Some(student.to_string_lossy().to_string());
+    }
+}
+
+/// Record teacher and student into separate channels from the next
+/// recording on; `None` goes back to a single mixed input
+#[tauri::command]
+fn set_dual_channel(state: State<AppState>, dual_channel: Option<DualChannel>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match &dual_channel {
+        Some(dual) => {
+            let json = serde_json::to_string(dual).map_err(|e| e.to_string())?;
+            db.set_setting("dual_channel", &json).map_err(|e| e.to_string())?;
+        }
+        None => db.delete_setting_as("dual_channel", "user").map_err(|e| e.to_string())?,
+    }
+    let dual_channel = dual_channel_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_dual_channel(dual_channel);
+    Ok(())
+}
+
+/// Weight each channel of a multichannel microphone in the saved mix, in
+/// the device's channel order, e.g. `[1, 1, 0, 0]` to keep only the first
+/// two; `None` averages every channel
+#[tauri::command]
+fn set_channel_weights(state: State<AppState>, weights: Option<Vec<f32>>) -> Result<(), String> {
+    if let Some(weights) = &weights {
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) || !weights.iter().any(|w| *w > 0.0) {
+            return Err("Channel weights must be non-negative with at least one above 0".to_string());
+        }
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match &weights {
+        Some(weights) => {
+            let json = serde_json::to_string(weights).map_err(|e| e.to_string())?;
+            db.set_setting("channel_weights", &json).map_err(|e| e.to_string())?;
+        }
+        None => db.delete_setting_as("channel_weights", "user").map_err(|e| e.to_string())?,
+    }
+    let weights = channel_weights_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_channel_weights(weights);
+    Ok(())
+}
+
+/// Mix computer sound, such as a video call, into recordings from the next
+/// one on; `None` records the microphone alone
+#[tauri::command]
+fn set_system_audio(state: State<AppState>, system_audio: Option<SystemAudio>) -> Result<(), String> {
+    if let Some(system_audio) = &system_audio {
+        if !(0.0..=10.0).contains(&system_audio.gain) {
+            return Err("System audio gain must be between 0 and 10".to_string());
+        }
+        let sources = audio::list_system_sources().map_err(|e| e.to_string())?;
+        match &system_audio.device {
+            Some(name) if !sources.iter().any(|d| &d.name == name) => {
+                return Err(format!("System audio source not found: {}", name));
+            }
+            None if audio::system_audio_guidance().is_some() => {
+                return Err("Pick the input that carries system audio on this platform".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match &system_audio {
+        Some(system_audio) => {
+            let json = serde_json::to_string(system_audio).map_err(|e| e.to_string())?;
+            db.set_setting("system_audio", &json).map_err(|e| e.to_string())?;
+        }
+        None => db.delete_setting_as("system_audio", "user").map_err(|e| e.to_string())?,
+    }
+    let system_audio = system_audio_setting(&db)?;
+    drop(db);
+
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_system_audio(system_audio);
+    Ok(())
+}
+
+/// Runs off the main thread: converting an hour-long capture takes a while
+#[tauri::command(async)]
+fn stop_recording(state: State<AppState>, app: AppHandle) -> Result<RecordingResult, String> {
+    // Generate unique ID
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let id = new_recording_id(&db)?;
+    let format = audio_format_setting(&db)?;
+    drop(db);
+
+    // Save audio file
+    let audio_dir = state.data_dir.join("audio");
+    std::fs::create_dir_all(&audio_dir).map_err(|e| e.to_string())?;
+    let audio_path = audio_dir.join(format!("{}.wav", id));
+
+    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+    let capture = recorder.finish_capture().map_err(|e| e.to_string())?;
+    let stats = recorder.last_capture_stats();
+    let conversion = recorder.conversion();
+    drop(recorder);
+    let converted = convert_capture(&app, &conversion, &capture, &audio_path, &id)?;
+    let duration = converted.duration;
+    // Read back before it's compressed
+    let score = audio::score_take(&audio_path, &stats).map_err(|e| e.to_string())?;
+    let lanes = match conversion.lane_paths(&audio_path) {
+        Some((teacher, student)) => Some((store_audio(&teacher, format)?, store_audio(&student, format)?)),
+        None => None,
+    };
+    let archive = conversion.archive_path(&audio_path);
+    let audio_path = store_audio(&audio_path, format)?;
+
+    // Get student ID
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let student_id = db
+        .get_setting("student_id")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // Create recording entry
+    let mut recording = Recording::new(
+        id.clone(),
+        student_id,
+        audio_path.to_string_lossy().to_string(),
+        duration,
+    );
+    recording.audio_format = AudioFormat::from_path(&audio_path);
+    recording.audio_quality = stats.quality();
+    set_lane_paths(&mut recording, lanes);
+    recording.archive_audio_path = archive.map(|p| p.to_string_lossy().to_string());
+    recording.expires_at = default_expiry(&db)?;
+    apply_guest_mode(&db, &mut recording)?;
+    apply_passage(&db, &mut recording)?;
+    recording.translate = translate_setting(&db)?;
+
+    db.save_recording(&recording).map_err(|e| e.to_string())?;
+    telemetry::RECORDINGS_MADE.increment();
+    if let Some(activity) = &converted.channels {
+        db.save_channel_activity(&id, activity).map_err(|e| e.to_string())?;
+    }
+
+    Ok(RecordingResult {
+        id,
+        duration,
+        quality: score,
+    })
+}
+
+#[tauri::command]
+fn is_recording(state: State<AppState>) -> bool {
+    state
+        .recorder
+        .lock()
+        .map(|r| r.is_recording())
+        .unwrap_or(false)
+}
+
+/// How one file given to `import_audio` went
+#[derive(Debug, Clone, Serialize)]
+struct ImportedAudio {
+    source: String,
+    /// The recording made from it, queued for transcription
+    recording_id: Option<String>,
+    error: Option<String>,
+}
+
+/// Bring in audio recorded elsewhere, such as sessions recorded on a phone,
+/// as recordings that go through the same pipeline as the app's own. Each
+/// file is copied into the data folder, converted to 16kHz mono and queued
+/// for transcription; one that can't be read doesn't stop the rest. The
+/// file's modified time is taken as when it was recorded.
+#[tauri::command(async)]
+fn import_audio(state: State<AppState>, paths: Vec<String>) -> Result<Vec<ImportedAudio>, String> {
+    state.startup.require(startup::RECORDING)?;
+    let audio_dir = state.data_dir.join("audio");
+    std::fs::create_dir_all(&audio_dir).map_err(|e| e.to_string())?;
+    let ffmpeg = whisper::locate_program("ffmpeg");
+
+    let mut imported = Vec::new();
+    for source in paths {
+        let outcome = import_file(&state, &audio_dir, Path::new(&source), ffmpeg.as_deref());
+        if let Ok(id) = &outcome {
+            enqueue_recording(&state, id, None)?;
+        }
+        let (recording_id, error) = match outcome {
+            Ok(id) => (Some(id), None),
+            Err(e) => (None, Some(e)),
+        };
+        imported.push(ImportedAudio { source, recording_id, error });
+    }
+    Ok(imported)
+}
+
+fn import_file(state: &AppState, audio_dir: &Path, source: &Path, ffmpeg: Option<&Path>) -> Result<String, String> {
+    let extension = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .filter(|e| e == "wav" || e == "flac" || audio::FFMPEG_EXTENSIONS.contains(&e.as_str()))
+        .ok_or_else(|| "Only WAV, FLAC, MP3 and M4A files can be imported".to_string())?;
+    let recorded_at = std::fs::metadata(source)
+        .and_then(|m| m.modified())
+        .map_err(|e| e.to_string())?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let id = new_recording_id(&db)?;
+    let quality = resample_quality_setting(&db)?;
+    drop(db);
+
+    // Converted from a copy, so a phone unplugged part way through doesn't
+    // leave half a recording
+    let copy = audio_dir.join(format!("{}.import.{}", id, extension));
+    let audio_path = audio_dir.join(format!("{}.wav", id));
+    std::fs::copy(source, &copy).map_err(|e| e.to_string())?;
+    let converted = audio::import_to_16khz_mono(&copy, &audio_path, quality, ffmpeg);
+    let _ = std::fs::remove_file(&copy);
+    let duration = converted.map_err(|e| e.to_string())?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let student_id = db
+        .get_setting("student_id")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "unknown".to_string());
+    let mut recording = Recording::new(id.clone(), student_id, audio_path.to_string_lossy().to_string(), duration);
+    recording.recorded_at = chrono::DateTime::<chrono::Utc>::from(recorded_at).to_rfc3339();
+    recording.expires_at = default_expiry(&db)?;
+    apply_guest_mode(&db, &mut recording)?;
+    recording.translate = translate_setting(&db)?;
+    db.save_recording(&recording).map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Add a finished job to the activity history. Failing to log is only
+/// printed, so it never fails the job itself.
+fn log_job(
+    db: &Database,
+    kind: JobKind,
+    recording_id: Option<&str>,
+    started_at: String,
+    started: Instant,
+    outcome: Result<Option<String>, String>,
+) {
+    let job = JobEntry {
+        id: 0,
+        kind,
+        recording_id: recording_id.map(str::to_string),
+        started_at,
+        duration_ms: started.elapsed().as_millis() as i64,
+        succeeded: outcome.is_ok(),
+        detail: outcome.unwrap_or_else(Some),
+    };
+    if let Err(e) = db.record_job(&job) {
+        eprintln!("Failed to log {} job: {}", kind.as_str(), e);
+    }
+}
+
+/// Run `job` and log it. `describe` turns a successful result into its
+/// outcome, since some jobs finish without error but still fail at what
+/// they were for. The db must not be locked by the caller.
+fn run_job<T>(
+    db: &Mutex<Database>,
+    kind: JobKind,
+    recording_id: Option<&str>,
+    job: impl FnOnce() -> Result<T, String>,
+    describe: impl FnOnce(&T) -> Result<Option<String>, String>,
+) -> Result<T, String> {
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let started = Instant::now();
+    let result = job();
+    let outcome = match &result {
+        Ok(value) => describe(value),
+        Err(e) => Err(e.clone()),
+    };
+    match db.lock() {
+        Ok(db) => log_job(&db, kind, recording_id, started_at, started, outcome),
+        Err(e) => eprintln!("Failed to log {} job: {}", kind.as_str(), e),
+    }
+    result
+}
+
+/// Stages run after a recording stops, from the `pipeline` setting
+fn pipeline_setting(db: &Database) -> Result<Pipeline, String> {
+    match settings::resolve_with_source(db, "pipeline").map_err(|e| e.to_string())? {
+        Some(setting) => {
+            let pipeline = Pipeline::from_json(&setting.value).map_err(|e| e.to_string())?;
+            Ok(local_scripts_only(pipeline, setting.source))
+        }
+        None => Ok(Pipeline::default()),
+    }
+}
+
+/// Script stages only run from a pipeline set on this device; one pushed
+/// from the classroom or org layer has them dropped
+fn local_scripts_only(pipeline: Pipeline, layer: &str) -> Pipeline {
+    if layer == settings::DEVICE_LAYER {
+        return pipeline;
+    }
+    let (pipeline, dropped) = pipeline.without_scripts();
+    for name in dropped {
+        eprintln!("Ignoring script stage '{}' from the {} pipeline; scripts must be set on this device", name, layer);
+    }
+    pipeline
+}
+
+/// Mask a transcript, its segments and its translation per a redact stage's
+/// options
+fn apply_redaction(
+    db: &Database,
+    stage: &StageConfig,
+    result: &mut TranscriptionResult,
+    translation: Option<&mut String>,
+) -> Result<(), String> {
+    let Some((terms, numbers)) = redaction_terms(db, stage)? else {
+        return Ok(());
+    };
+    result.text = pipeline::redact(&result.text, &terms, numbers);
+    for segment in &mut result.segments {
+        segment.text = pipeline::redact(&segment.text, &terms, numbers);
+        pipeline::redact_words(&mut segment.words, &terms, numbers);
+    }
+    if let Some(translation) = translation {
+        *translation = pipeline::redact(translation, &terms, numbers);
+    }
+    Ok(())
+}
+
+/// Star out profanity and terms in a transcript, its segments and its
+/// translation per a mask stage's options. The transcript and translation
+/// as they were are kept on `recording` when anything was masked, and the
+/// segments as they were returned for `Database::set_unmasked_segments`.
+/// A second mask stage keeps what the first was given.
+fn apply_masking(
+    stage: &StageConfig,
+    recording: &mut Recording,
+    result: &mut TranscriptionResult,
+) -> Option<Vec<TranscriptSegment>> {
+    let terms = masked_terms(stage)?;
+    let masked = pipeline::mask(&result.text, &terms);
+    if !masked.split_whitespace().eq(result.text.split_whitespace()) {
+        let unmasked = std::mem::replace(&mut result.text, masked);
+        recording.unmasked_transcript.get_or_insert(unmasked);
+    }
+    let unmasked_segments = result.segments.clone();
+    for segment in &mut result.segments {
+        segment.text = pipeline::mask(&segment.text, &terms);
+        pipeline::mask_words(&mut segment.words, &terms);
+    }
+    if let Some(translation) = recording.translation.as_mut() {
+        let masked = pipeline::mask(translation, &terms);
+        if !masked.split_whitespace().eq(translation.split_whitespace()) {
+            let unmasked = std::mem::replace(translation, masked);
+            recording.unmasked_translation.get_or_insert(unmasked);
+        }
+    }
+    recording.transcript = Some(result.text.clone());
+    let changed = unmasked_segments
+        .iter()
+        .zip(&result.segments)
+        .any(|(before, after)| !before.text.split_whitespace().eq(after.text.split_whitespace()));
+    changed.then_some(unmasked_segments)
+}
+
+/// The terms a mask stage stars out, with the profanity list added if it
+/// asks; None for any other stage
+fn masked_terms(stage: &StageConfig) -> Option<Vec<String>> {
+    let StageConfig::Mask { profanity, terms } = stage else {
+        return None;
+    };
+    let mut terms = terms.clone();
+    if *profanity {
+        terms.extend(pipeline::PROFANITY.iter().map(|w| w.to_string()));
+    }
+    Some(terms)
+}
+
+/// The terms a redact stage masks, with the student's name added if it
+/// asks, and whether it masks numbers; None for any other stage
+fn redaction_terms(db: &Database, stage: &StageConfig) -> Result<Option<(Vec<String>, bool)>, String> {
+    let StageConfig::Redact { terms, student_name, numbers } = stage else {
+        return Ok(None);
+    };
+    let mut terms = terms.clone();
+    if *student_name {
+        if let Some(name) = db.get_setting("student_name").map_err(|e| e.to_string())? {
+            terms.extend(name.split_whitespace().map(str::to_string));
+        }
+    }
+    Ok(Some((terms, *numbers)))
+}
+
+fn quality_message(quality: AudioQuality) -> &'static str {
+    match quality {
+        AudioQuality::Ok => "Audio level is fine.",
+        AudioQuality::Clipped => "Audio is clipping; lower the input gain or move the mic away.",
+        AudioQuality::TooQuiet => "Audio is nearly silent; check the microphone.",
+    }
+}
+
+fn emit_stage(app: &AppHandle, stage: &str, message: &str, recording_id: &str) {
+    let _ = app.emit("processing-status", ProcessingStatus {
+        stage: stage.to_string(),
+        message: message.to_string(),
+        recording_id: Some(recording_id.to_string()),
+        transcript: None,
+        synced: false,
+    });
+}
+
+/// Save a recording's audio and queue it for the job worker, which runs the
+/// configured pipeline. Returns once the recording is saved, with a
+/// `queued` status that is also emitted as a `processing-status` event.
+fn process_recording(state: &AppState, app: &AppHandle, source: CaptureSource) -> Result<ProcessingStatus, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let id = new_recording_id(&db)?;
+    drop(db);
+    if let CaptureSource::Rollover(..) = source {
+        let max_seconds = state
+            .db
+            .lock()
+            .ok()
+            .and_then(|db| max_duration_setting(&db).ok().flatten())
+            .unwrap_or(0.0);
+        let _ = app.emit("recording-rolled-over", RolledOver {
+            recording_id: id.clone(),
+            max_seconds,
+        });
+    }
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let started = Instant::now();
+    let session = match save_capture(state, app, &id, source) {
+        Ok(session) => session,
+        // Successful runs are logged by the worker once processed
+        Err(e) => {
+            if let Ok(db) = state.db.lock() {
+                log_job(&db, JobKind::Processing, Some(&id), started_at, started, Err(e.clone()));
+            }
+            return Err(e);
+        }
+    };
+    enqueue_recording(state, &id, session)?;
+    let deferred = state.db.lock().ok().and_then(|db| outside_quiet_hours(&db).ok()).unwrap_or(false);
+
+    let status = ProcessingStatus {
+        stage: "queued".to_string(),
+        message: if deferred {
+            "Saved. It will be transcribed during quiet hours.".to_string()
+        } else {
+            "Saved. Transcribing in the background...".to_string()
+        },
+        recording_id: Some(id),
+        transcript: None,
+        synced: false,
+    };
+    let _ = app.emit("processing-status", status.clone());
+    Ok(status)
+}
+
+/// Add a saved recording to the job queue and wake the worker. `session` is
+/// the capture session whose chunks were transcribed while it recorded.
+fn enqueue_recording(state: &AppState, recording_id: &str, session: Option<u64>) -> Result<(), String> {
+    let mut queue = state.job_queue.lock_or_recover();
+    state
+        .db
+        .lock()
+        .map_err(|e| e.to_string())?
+        .enqueue_job(recording_id)
+        .map_err(|e| e.to_string())?;
+    if let Some(session) = session {
+        queue.insert(recording_id.to_string(), session);
+    }
+    drop(queue);
+    state.job_ready.notify_all();
+    Ok(())
+}
+
+/// Longest the job worker sleeps without checking the queue
+const JOB_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Wait before the first retry of a failed job; each retry after waits four
+/// times as long
+const JOB_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Sent as `job-finished` when the worker is done with a queued recording,
+/// for now or for good
+#[derive(Clone, Serialize)]
+struct JobFinished {
+    job_id: i64,
+    recording_id: String,
+    /// "done", "retrying" or "failed"
+    status: &'static str,
+    attempts: i64,
+    error: Option<String>,
+    transcript: Option<String>,
+}
+
+/// How far `transcribe_all_pending` has got, sent as `batch-progress` when
+/// it starts and each time one of its recordings is done with
+#[derive(Clone, Serialize)]
+struct BatchProgress {
+    total: usize,
+    transcribed: usize,
+    /// Given up on, or done with but left untranscribed
+    failed: usize,
+    /// Ones the worker hasn't finished with yet
+    #[serde(skip)]
+    pending: Vec<String>,
+}
+
+/// Queue every recording still waiting on a transcript and start counting
+/// them off as a batch. A batch already under way is counted afresh.
+fn transcribe_pending(state: &AppState, app: &AppHandle) -> Result<BatchProgress, String> {
+    let queue = state.job_queue.lock_or_recover();
+    let pending = state
+        .db
+        .lock()
+        .map_err(|e| e.to_string())?
+        .queue_untranscribed()
+        .map_err(|e| e.to_string())?;
+    drop(queue);
+    let progress = BatchProgress {
+        total: pending.len(),
+        transcribed: 0,
+        failed: 0,
+        pending,
+    };
+    *state.batch.lock_or_recover() = (progress.total > 0).then(|| progress.clone());
+    let _ = app.emit("batch-progress", progress.clone());
+    state.job_ready.notify_all();
+    Ok(progress)
+}
+
+/// Count a recording of the batch off once the worker is done with it for
+/// good, ending the batch with its last
+fn advance_batch(state: &AppState, app: &AppHandle, recording_id: &str, status: &str, transcribed: bool) {
+    let mut batch = state.batch.lock_or_recover();
+    let Some(progress) = batch.as_mut().filter(|_| status != "retrying") else {
+        return;
+    };
+    let Some(at) = progress.pending.iter().position(|id| id == recording_id) else {
+        return;
+    };
+    progress.pending.remove(at);
+    if transcribed {
+        progress.transcribed += 1;
+    } else {
+        progress.failed += 1;
+    }
+    let _ = app.emit("batch-progress", progress.clone());
+    if progress.pending.is_empty() {
+        *batch = None;
+    }
+}
+
+/// Process queued recordings one at a time, oldest first. A job that fails
+/// is tried again after a delay, up to `MAX_JOB_ATTEMPTS` attempts. Outside
+/// quiet hours only urgent jobs run; the rest wait for the next window,
+/// which the poll interval notices opening.
+fn spawn_job_worker(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let state = app.state::<AppState>();
+        // Held until the worker waits, so a job queued meanwhile isn't missed
+        let mut queue = state.job_queue.lock_or_recover();
+        // A job that panicked holding the database mustn't stop every later
+        // one; the connection rolls back any transaction it left open
+        let next = {
+            let db = state.db.lock_or_recover();
+            // Checked when set, so only a pushed setting can be invalid
+            let urgent_only = outside_quiet_hours(&db).unwrap_or(false);
+            db.start_next_job(&chrono::Utc::now().to_rfc3339(), urgent_only)
+                .map(|job| job.ok_or_else(|| time_until_next_job(&db, urgent_only)))
+        };
+        match next {
+            Ok(Ok(job)) => {
+                let session = queue.remove(&job.recording_id);
+                drop(queue);
+                run_queued_job(&state, &app, job, session);
+            }
+            Ok(Err(wait)) => {
+                let _ = state.job_ready.wait_timeout(queue, wait);
+            }
+            Err(e) => {
+                eprintln!("Failed to read the job queue: {}", e);
+                let _ = state.job_ready.wait_timeout(queue, JOB_POLL_INTERVAL);
+            }
+        }
+    });
+}
+
+/// How long until the next queued job is due, within the poll interval
+fn time_until_next_job(db: &Database, urgent_only: bool) -> std::time::Duration {
+    db.next_job_due(urgent_only)
+        .ok()
+        .flatten()
+        .and_then(|due| chrono::DateTime::parse_from_rfc3339(&due).ok())
+        .map(|due| {
+            (due.with_timezone(&chrono::Utc) - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default()
+        })
+        .map_or(JOB_POLL_INTERVAL, |wait| wait.min(JOB_POLL_INTERVAL))
+}
+
+fn run_queued_job(state: &AppState, app: &AppHandle, job: QueuedJob, session: Option<u64>) {
+    let id = job.recording_id.clone();
+    let outcome = run_job(
+        &state.db,
+        JobKind::Processing,
+        Some(&id),
+        // A panic fails this job like any other error instead of ending the worker
+        || {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                run_pipeline(state, app, &id, session, job.attempts)
+            }))
+            .unwrap_or_else(|_| Err("Processing stopped unexpectedly".to_string()))
+        },
+        |status| {
+            if status.transcript.is_some() {
+                Ok(Some(status.message.clone()))
+            } else {
+                Err(status.message.clone())
+            }
+        },
+    );
+
+    let retry_at = (job.attempts < MAX_JOB_ATTEMPTS).then(|| {
+        let delay = JOB_RETRY_DELAY * 4u32.pow((job.attempts - 1).clamp(0, 8) as u32);
+        (chrono::Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default()).to_rfc3339()
+    });
+    let (status, error, transcript) = match outcome {
+        Ok(status) => ("done", None, status.transcript),
+        Err(e) => (if retry_at.is_some() { "retrying" } else { "failed" }, Some(e), None),
+    };
+    let db = state.db.lock_or_recover();
+    let updated = match &error {
+        None => db.finish_job(job.id),
+        Some(e) => db.fail_job(job.id, e, retry_at.as_deref()),
+    };
+    if let Err(e) = updated {
+        eprintln!("Failed to update job {}: {}", job.id, e);
+    }
+    drop(db);
+    if status == "failed" {
+        let _ = app.emit("processing-status", ProcessingStatus {
+            stage: "done".to_string(),
+            message: "Recording saved. Transcription failed.".to_string(),
+            recording_id: Some(id.clone()),
+            transcript: None,
+            synced: false,
+        });
+    }
+    advance_batch(state, app, &id, status, transcript.is_some());
+    let _ = app.emit("job-finished", JobFinished {
+        job_id: job.id,
+        recording_id: id,
+        status,
+        attempts: job.attempts,
+        error,
+        transcript,
+    });
+}
+
+/// Longest the pipeline waits on chunks still being transcribed before it
+/// gives up on them and transcribes the recording whole
+const ROLLING_WAIT: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// Transcribe each chunk of a recording as the recorder hands it over, in
+/// order, on one thread
+fn spawn_chunk_transcriber(app: AppHandle, chunks: mpsc::Receiver<CaptureChunk>) {
+    std::thread::spawn(move || {
+        for chunk in chunks {
+            let state = app.state::<AppState>();
+            let transcribed = transcribe_chunk(&state, &chunk);
+            let _ = std::fs::remove_file(&chunk.path);
+            let live = state
+                .db
+                .lock()
+                .ok()
+                .and_then(|db| live_caption_seconds_setting(&db).ok().flatten())
+                .is_some();
+
+            let mut rolling = state.rolling.lock_or_recover();
+            if chunk.index == 0 {
+                *rolling = RollingTranscript {
+                    session: chunk.session,
+                    pending: rolling.pending,
+                    ..Default::default()
+                };
+            }
+            let in_order = chunk.session == rolling.session
+                && (chunk.offset_seconds - rolling.covered_seconds).abs() < 0.001;
+            if let Some(segments) = transcribed.as_ref().ok().filter(|_| live && chunk.session == rolling.session) {
+                for text in segments.iter().map(|s| s.text.trim()).filter(|t| !t.is_empty()) {
+                    if !rolling.captions.is_empty() {
+                        rolling.captions.push(' ');
+                    }
+                    rolling.captions.push_str(text);
+                }
+                let _ = app.emit("live-transcript", LiveTranscript {
+                    session: chunk.session,
+                    text: rolling.captions.clone(),
+                    is_final: false,
+                });
+            }
+            match transcribed {
+                Ok(segments) if in_order && !rolling.broken => {
+                    rolling.segments.extend(segments.into_iter().map(|mut s| {
+                        s.shift(chunk.offset_seconds);
+                        s
+                    }));
+                    rolling.covered_seconds = chunk.offset_seconds + chunk.duration_seconds;
+                }
+                Ok(_) => rolling.broken = true,
+                Err(e) => {
+                    eprintln!("Chunk {} of recording {} failed: {}", chunk.index, chunk.session, e);
+                    rolling.broken = true;
+                }
+            }
+            rolling.pending = rolling.pending.saturating_sub(1);
+            drop(rolling);
+            state.rolling_done.notify_all();
+        }
+    });
+}
+
+fn transcribe_chunk(state: &AppState, chunk: &CaptureChunk) -> Result<Vec<TranscriptSegment>, String> {
+    // Mixed down even in dual-channel mode; the lanes are split out later
+    let conversion = state.recorder.lock().map_err(|e| e.to_string())?.conversion().mixed_only();
+    let audio_path = chunk.path.with_extension("16k.wav");
+    conversion.run(&chunk.path, &audio_path, |_| {}).map_err(|e| e.to_string())?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let student_id = db
+        .get_setting("student_id")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "unknown".to_string());
+    let language = transcription_language(&db)?;
+    let options = TranscribeOptions {
+        passage: active_passage(&db)?,
+        language: Some(language.clone()),
+        word_timestamps: word_timestamps_setting(&db)?,
+        speakers: speaker_count_setting(&db)?,
+        vocabulary: vocabulary_for(&db, &student_id)?,
+        ..Default::default()
+    };
+    let gate = vad_gate_setting(&db)?;
+    drop(db);
+    // A silent chunk covers its stretch with no segments
+    if let Some(threshold_dbfs) = gate {
+        let (samples, sample_rate) = audio::read_audio(&audio_path).map_err(|e| e.to_string())?;
+        if !dsp::contains_speech(&samples, sample_rate, threshold_dbfs) {
+            let _ = std::fs::remove_file(&audio_path);
+            return Ok(Vec::new());
+        }
+    }
+    // The same model the whole recording would get, so chunks in another
+    // language aren't forced through an English-only one
+    let outcome = transcriber_for_language(state, &language).and_then(|multilingual| {
+        match multilingual.as_ref().or(state.transcriber.lock_or_recover().as_ref()) {
+            Some(transcriber) => transcriber
+                .transcribe_with(&audio_path, &options)
+                .map(|r| r.segments)
+                .map_err(|e| e.to_string()),
+            None => Err("Model not loaded".to_string()),
+        }
+    });
+    let _ = std::fs::remove_file(&audio_path);
+    outcome
+}
+
+/// Once no chunks are left in flight, what the chunks of recording
+/// `session` covered, if they all went through. Gives up as soon as chunks
+/// of a later recording arrive, since those replace it.
+fn take_rolling_transcript(state: &AppState, session: u64) -> Option<RollingTranscript> {
+    let rolling = state.rolling.lock_or_recover();
+    let (mut rolling, _) = state
+        .rolling_done
+        .wait_timeout_while(rolling, ROLLING_WAIT, |r| r.pending > 0 && r.session <= session)
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if rolling.pending > 0 || rolling.broken || rolling.session != session || rolling.covered_seconds == 0.0
+    {
+        return None;
+    }
+    let pending = rolling.pending;
+    Some(std::mem::replace(&mut *rolling, RollingTranscript { pending, ..Default::default() }))
+}
+
+/// Finish a rolling transcript with the audio after its last chunk. The
+/// audio may have been trimmed by `trimmed_from` seconds since.
+fn transcribe_tail(
+    transcriber: &dyn TranscriptionBackend,
+    audio_path: &Path,
+    options: &TranscribeOptions,
+    gate: Option<f32>,
+    rolling: RollingTranscript,
+    trimmed_from: f64,
+) -> Result<TranscriptionResult, String> {
+    let mut segments: Vec<TranscriptSegment> = rolling
+        .segments
+        .into_iter()
+        .filter(|s| s.end > trimmed_from)
+        .map(|mut s| {
+            s.shift(-trimmed_from);
+            s
+        })
+        .collect();
+
+    let (samples, sample_rate) = audio::read_audio(audio_path).map_err(|e| e.to_string())?;
+    let tail_start = (rolling.covered_seconds - trimmed_from).max(0.0);
+    let from = ((tail_start * sample_rate as f64) as usize).min(samples.len());
+    // Anything shorter is too little for the model to make out
+    let heard = gate.is_none_or(|threshold_dbfs| dsp::contains_speech(&samples[from..], sample_rate, threshold_dbfs));
+    if samples.len() - from >= sample_rate as usize / 2 && heard {
+        let tail_path = audio_path.with_extension("tail.wav");
+        audio::write_wav(&samples[from..], sample_rate, &tail_path).map_err(|e| e.to_string())?;
+        let tail = transcriber.transcribe_with(&tail_path, options);
+        let _ = std::fs::remove_file(&tail_path);
+        segments.extend(tail.map_err(|e| e.to_string())?.segments.into_iter().map(|mut s| {
+            s.shift(tail_start);
+            s
+        }));
+    }
+    let duration = samples.len() as f64 / sample_rate as f64;
+    segments.retain(|s| s.start < duration);
+    Ok(TranscriptionResult {
+        text: join_segments(&segments),
+        segments,
+        language: options.language.clone().filter(|l| l != whisper::AUTO_LANGUAGE),
+    })
+}
+
+/// Convert a finished capture for `recording_id` to `audio_path`, emitting
+/// `conversion-progress` at each whole percent. Must not be called with the
+/// recorder locked.
+fn convert_capture(
+    app: &AppHandle,
+    conversion: &Conversion,
+    capture: &Path,
+    audio_path: &Path,
+    recording_id: &str,
+) -> Result<Converted, String> {
+    let mut reported = 0;
+    conversion
+        .run(capture, audio_path, |progress| {
+            let percent = (progress * 100.0) as u32;
+            if percent > reported {
+                reported = percent;
+                let _ = app.emit("conversion-progress", ConversionProgress {
+                    recording_id: recording_id.to_string(),
+                    progress,
+                });
+            }
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// Finish and convert a capture and save it as recording `id`. Returns the
+/// capture session whose chunks were transcribed as it recorded, if any.
+fn save_capture(
+    state: &AppState,
+    app: &AppHandle,
+    id: &str,
+    source: CaptureSource,
+) -> Result<Option<u64>, String> {
+    // Stage 1: Save audio
+    let _ = app.emit("processing-status", ProcessingStatus {
+        stage: "saving".to_string(),
+        message: "Saving audio...".to_string(),
+        recording_id: None,
+        transcript: None,
+        synced: false,
+    });
+
+    let audio_dir = state.data_dir.join("audio");
+    std::fs::create_dir_all(&audio_dir).map_err(|e| e.to_string())?;
+    let audio_path = audio_dir.join(format!("{}.wav", id));
+
+    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+    // Only the part a recording stops in is chunked; see `RollingTranscript`
+    let session = matches!(source, CaptureSource::Recorder).then(|| recorder.session());
+    let mut session_id = None;
+    let (capture, stats, markers) = match source {
+        CaptureSource::Recorder => {
+            let capture = recorder.finish_capture().map_err(|e| e.to_string())?;
+            (capture, recorder.last_capture_stats(), recorder.take_markers())
+        }
+        CaptureSource::Rollover(capture, stats, markers) => (capture, stats, markers),
+        CaptureSource::Utterance(id, capture, stats, markers) => {
+            session_id = Some(id);
+            (capture, stats, markers)
+        }
+    };
+    let conversion = recorder.conversion();
+    drop(recorder);
+    let converted = convert_capture(app, &conversion, &capture, &audio_path, id)?;
+
+    // Get student ID and save recording
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let student_id = db
+        .get_setting("student_id")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let mut recording = Recording::new(
+        id.to_string(),
+        student_id,
+        audio_path.to_string_lossy().to_string(),
+        converted.duration,
+    );
+    recording.expires_at = default_expiry(&db)?;
+    apply_guest_mode(&db, &mut recording)?;
+    apply_passage(&db, &mut recording)?;
+    recording.translate = translate_setting(&db)?;
+    recording.session_id = session_id;
+    recording.audio_quality = stats.quality();
+    set_lane_paths(&mut recording, conversion.lane_paths(&audio_path));
+    recording.archive_audio_path = conversion
+        .archive_path(&audio_path)
+        .map(|p| p.to_string_lossy().to_string());
+    db.save_recording(&recording).map_err(|e| e.to_string())?;
+    telemetry::RECORDINGS_MADE.increment();
+    if let Some(activity) = &converted.channels {
+        db.save_channel_activity(id, activity).map_err(|e| e.to_string())?;
+    }
+    db.add_markers(id, &markers).map_err(|e| e.to_string())?;
+    drop(db);
+    if recording.audio_quality != AudioQuality::Ok {
+        emit_stage(app, "warning", quality_message(recording.audio_quality), id);
+    }
+    Ok(session)
+}
+
+/// Run the configured pipeline on saved recording `id`, reporting each stage
+/// as a `processing-status` event. `attempt` counts from 1; on later attempts
+/// the audio stages, which already ran, are skipped. A failed transcription
+/// is an error, so the worker retries it, and the audio is left as WAV until
+/// one succeeds.
+fn run_pipeline(
+    state: &AppState,
+    app: &AppHandle,
+    id: &str,
+    session: Option<u64>,
+    attempt: i64,
+) -> Result<ProcessingStatus, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let Some(mut recording) = db.get_recording(id).map_err(|e| e.to_string())? else {
+        // Deleted while it waited
+        return Ok(ProcessingStatus {
+            stage: "done".to_string(),
+            message: "Recording was deleted.".to_string(),
+            recording_id: Some(id.to_string()),
+            transcript: None,
+            synced: false,
+        });
+    };
+    let mut channel_activity = db.get_channel_activity(id).map_err(|e| e.to_string())?;
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let local_only = is_local_only(&db)?;
+    let format = audio_format_setting(&db)?;
+    let pipeline = pipeline_setting(&db)?;
+    let language = transcription_language(&db)?;
+    let gate = vad_gate_setting(&db)?;
+    let word_timestamps = word_timestamps_setting(&db)?;
+    let speakers = speaker_count_setting(&db)?;
+    let vocabulary = vocabulary_for(&db, &recording.student_id)?;
+    drop(db);
+    // Already compressed but untranscribed means it was queued again to be
+    // transcribed, such as by `transcribe_all_pending`
+    if recording.audio_format != AudioFormat::Wav
+        && recording.transcript.is_none()
+        && recording.audio_quality != AudioQuality::TooQuiet
+        && !recording.audio_purged
+    {
+        let result = transcribe_saved(state, app, id.to_string(), None, None)?;
+        return Ok(ProcessingStatus {
+            stage: "done".to_string(),
+            message: "Recording transcribed.".to_string(),
+            recording_id: Some(id.to_string()),
+            transcript: Some(result.transcript),
+            synced: recording.synced,
+        });
+    }
+    // Otherwise it means a run finished before the app quit
+    if recording.audio_format != AudioFormat::Wav {
+        return Ok(ProcessingStatus {
+            stage: "done".to_string(),
+            message: "Recording saved.".to_string(),
+            recording_id: Some(id.to_string()),
+            transcript: recording.transcript,
+            synced: recording.synced,
+        });
+    }
+    let audio_path = PathBuf::from(&recording.audio_path);
+    let id = id.to_string();
+
+    // Then the configured stages, in order
+    let mut result: Option<TranscriptionResult> = None;
+    let mut transcribed = false;
+    let mut synced = false;
+    // Seconds cut from the start of the audio by vad-trim
+    let mut trimmed_from = 0.0;
+    // The transcript isn't stored until the redact and mask stages have
+    // run, so an unredacted one is never there for a sync to pick up
+    let privacy_stage = pipeline
+        .enabled()
+        .enumerate()
+        .filter(|(_, c)| matches!(c, StageConfig::Redact { .. } | StageConfig::Mask { .. }))
+        .map(|(index, _)| index)
+        .last();
+    // A metrics stage before then scores once the transcript is stored
+    let mut score_when_stored = false;
+    let mut unmasked_segments = None;
+    for (index, stage) in pipeline.enabled().enumerate() {
+        let store = privacy_stage.is_none_or(|last| index >= last);
+        match stage {
+            StageConfig::VadTrim { .. } | StageConfig::Normalize { .. } if attempt > 1 => continue,
+            StageConfig::VadTrim { threshold_dbfs, padding_ms } => {
+                emit_stage(app, "trimming", "Trimming silence...", &id);
+                let (samples, sample_rate) = audio::read_audio(&audio_path).map_err(|e| e.to_string())?;
+                let padding = (*padding_ms as u64 * sample_rate as u64 / 1000) as usize;
+                let speech = dsp::speech_bounds(&samples, *threshold_dbfs, padding);
+                if speech.len() < samples.len() {
+                    audio::write_wav(&samples[speech.clone()], sample_rate, &audio_path)
+                        .map_err(|e| e.to_string())?;
+                    // Keep the separate channels lined up with the mix
+                    for lane in [&recording.teacher_audio_path, &recording.student_audio_path].into_iter().flatten() {
+                        let (lane_samples, _) = audio::read_audio(Path::new(lane)).map_err(|e| e.to_string())?;
+                        let end = speech.end.min(lane_samples.len());
+                        let start = speech.start.min(end);
+                        audio::write_wav(&lane_samples[start..end], sample_rate, Path::new(lane))
+                            .map_err(|e| e.to_string())?;
+                    }
+                    recording.duration_seconds = speech.len() as f64 / sample_rate as f64;
+                    trimmed_from = speech.start as f64 / sample_rate as f64;
+                    let db = state.db.lock().map_err(|e| e.to_string())?;
+                    db.save_recording(&recording).map_err(|e| e.to_string())?;
+                    if let Some(activity) = channel_activity.as_mut() {
+                        activity.trim(
+                            speech.start as f64 / sample_rate as f64,
+                            speech.end as f64 / sample_rate as f64,
+                        );
+                        db.save_channel_activity(&id, activity).map_err(|e| e.to_string())?;
+                    }
+                    db.trim_markers(
+                        &id,
+                        speech.start as f64 / sample_rate as f64,
+                        speech.end as f64 / sample_rate as f64,
+                    )
+                    .map_err(|e| e.to_string())?;
+                }
+            }
+            StageConfig::Normalize { target_lufs } => {
+                emit_stage(app, "normalizing", "Normalizing volume...", &id);
+                let (samples, sample_rate) = audio::read_audio(&audio_path).map_err(|e| e.to_string())?;
+                let normalized = dsp::normalize_loudness(&samples, sample_rate, *target_lufs);
+                audio::write_wav(&normalized, sample_rate, &audio_path).map_err(|e| e.to_string())?;
+            }
+            // Near-silent audio would only come back empty or hallucinated
+            StageConfig::Transcribe if recording.audio_quality == AudioQuality::TooQuiet => continue,
+            StageConfig::Transcribe => {
+                transcribed = true;
+                emit_stage(app, "transcribing", "Transcribing audio...", &id);
+                // Live caption windows are too short to transcribe well, so
+                // with captions on the whole recording is transcribed again
+                let db = state.db.lock().map_err(|e| e.to_string())?;
+                let live = live_caption_seconds_setting(&db)?.is_some();
+                drop(db);
+                // Taken before the transcriber is locked, since the chunks
+                // still being worked on need it
+                let rolling = session
+                    .filter(|_| !live)
+                    .and_then(|session| take_rolling_transcript(state, session));
+                let multilingual = match transcriber_for_language(state, &language) {
+                    Ok(multilingual) => multilingual,
+                    Err(e) => {
+                        emit_stage(app, "error", &format!("Transcription failed: {}", e), &id);
+                        return Err(e);
+                    }
+                };
+                let monitor = begin_transcription(state, app, &id);
+                let transcriber_guard = state.transcriber.lock_or_recover();
+                let options = TranscribeOptions {
+                    passage: recording.reference_passage.clone(),
+                    language: Some(language.clone()),
+                    monitor: Some(monitor.clone()),
+                    word_timestamps,
+                    speakers,
+                    vocabulary: vocabulary.clone(),
+                    ..Default::default()
+                };
+                let outcome = if let Some(transcriber) = multilingual.as_ref().or(transcriber_guard.as_ref()) {
+                    let started = Instant::now();
+                    let outcome = match rolling {
+                        Some(rolling) => {
+                            transcribe_tail(transcriber.as_ref(), &audio_path, &options, gate, rolling, trimmed_from)
+                        }
+                        None => transcriber
+                            .transcribe_with(&audio_path, &options)
+                            .map_err(|e| e.to_string()),
+                    };
+                    telemetry::TRANSCRIPTION_SECONDS.observe(started.elapsed().as_secs_f64());
+                    match outcome {
+                        Err(_) if monitor.is_cancelled() => Ok(None),
+                        outcome => outcome.map(Some),
+                    }
+                } else {
+                    Err("Model not loaded. Please load the model in Settings.".to_string())
+                };
+                drop(transcriber_guard);
+                end_transcription(state, &id);
+                result = match outcome {
+                    Ok(Some(r)) => Some(r),
+                    // Kept untranscribed, to transcribe later
+                    Ok(None) => {
+                        emit_stage(app, "cancelled", "Transcription cancelled", &id);
+                        None
+                    }
+                    Err(e) => {
+                        emit_stage(app, "error", &format!("Transcription failed: {}", e), &id);
+                        return Err(e);
+                    }
+                };
+
+                if let Some(r) = result.as_mut() {
+                    attribute_speakers(&recording, channel_activity.as_ref(), &mut r.segments);
+                    recording.transcript = Some(r.text.clone());
+                    recording.transcript_language = transcript_language(&language, r);
+                    // The model already picked the language when left to
+                    recording.detected_language = match r.language.clone() {
+                        Some(picked) if language == whisper::AUTO_LANGUAGE => Some(picked),
+                        _ => detect_language(state, &audio_path).map(|d| d.code),
+                    };
+                    // A failed translation leaves the transcript standing
+                    if recording.translate {
+                        emit_stage(app, "translating", "Translating to English...", &id);
+                        match translate_recording(state, &recording, &audio_path, &vocabulary) {
+                            Ok(translation) => recording.translation = Some(translation),
+                            Err(e) => emit_stage(app, "error", &format!("Translation failed: {}", e), &id),
+                        }
+                    }
+                    let db = state.db.lock().map_err(|e| e.to_string())?;
+                    apply_speaker_names(&db, &mut recording, &r.segments)?;
+                    classify_speakers(&db, &mut recording, &audio_path, &r.segments)?;
+                    if store {
+                        db.save_recording(&recording).map_err(|e| e.to_string())?;
+                        db.save_segments(&id, &r.segments).map_err(|e| e.to_string())?;
+                    }
+                    drop(db);
+                    if let Some(session) = session.filter(|_| live) {
+                        let _ = app.emit("live-transcript", LiveTranscript {
+                            session,
+                            text: r.text.clone(),
+                            is_final: true,
+                        });
+                    }
+                    if recording.teacher_only {
+                        emit_stage(app, "warning", "Only the teacher is heard, so this won't be scored.", &id);
+                    }
+                    if recording.language_mismatch() {
+                        let message = format!(
+                            "This sounds like \"{}\" but was transcribed as \"{}\". \
+                             It can be transcribed again in the right language.",
+                            recording.detected_language.as_deref().unwrap_or_default(),
+                            recording.transcript_language.as_deref().unwrap_or_default()
+                        );
+                        emit_stage(app, "warning", &message, &id);
+                    }
+                }
+            }
+            StageConfig::Redact { .. } => {
+                let Some(r) = result.as_mut() else { continue };
+                emit_stage(app, "redacting", "Redacting transcript...", &id);
+                let db = state.db.lock().map_err(|e| e.to_string())?;
+                apply_redaction(&db, stage, r, recording.translation.as_mut())?;
+                recording.transcript = Some(r.text.clone());
+                if store {
+                    db.save_recording(&recording).map_err(|e| e.to_string())?;
+                    db.save_segments(&id, &r.segments).map_err(|e| e.to_string())?;
+                    if std::mem::take(&mut score_when_stored) {
+                        score_stored(&db, state, &id);
+                    }
+                }
+            }
+            StageConfig::Mask { .. } => {
+                let Some(r) = result.as_mut() else { continue };
+                emit_stage(app, "masking", "Masking transcript...", &id);
+                let masked = apply_masking(stage, &mut recording, r);
+                unmasked_segments = unmasked_segments.or(masked);
+                if store {
+                    let db = state.db.lock().map_err(|e| e.to_string())?;
+                    db.save_recording(&recording).map_err(|e| e.to_string())?;
+                    db.save_segments(&id, &r.segments).map_err(|e| e.to_string())?;
+                    db.set_unmasked_segments(&id, unmasked_segments.as_deref())
+                        .map_err(|e| e.to_string())?;
+                    if std::mem::take(&mut score_when_stored) {
+                        score_stored(&db, state, &id);
+                    }
+                }
+            }
+            StageConfig::Script { name, args, timeout_seconds } => {
+                let Some(r) = result.as_mut() else { continue };
+                emit_stage(app, "scripting", &format!("Running {}...", name), &id);
+                let payload = hooks::HookPayload {
+                    recording_id: id.clone(),
+                    transcript: r.text.clone(),
+                    segments: r.segments.clone(),
+                    reference_passage: recording.reference_passage.clone(),
+                };
+                // A failing script leaves the transcript as it was
+                match hooks::run(&state.data_dir.join("hooks"), name, args, *timeout_seconds, &payload) {
+                    Ok(output) => {
+                        r.text = output.transcript;
+                        r.segments = output.segments;
+                        recording.transcript = Some(r.text.clone());
+                        if store {
+                            let db = state.db.lock().map_err(|e| e.to_string())?;
+                            db.save_recording(&recording).map_err(|e| e.to_string())?;
+                            db.save_segments(&id, &r.segments).map_err(|e| e.to_string())?;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Script {} failed for {}: {}", name, id, e);
+                        emit_stage(app, "error", &format!("Script {} failed: {}", name, e), &id);
+                    }
+                }
+            }
+            StageConfig::Metrics => {
+                let Some(ref r) = result else { continue };
+                emit_stage(app, "scoring", "Scoring...", &id);
+                let db = state.db.lock().map_err(|e| e.to_string())?;
+                tag_activity(&db, &mut recording, &r.segments)?;
+                if !store {
+                    score_when_stored = true;
+                    continue;
+                }
+                score_stored(&db, state, &id);
+            }
+            // Skipped entirely in local-only mode
+            StageConfig::Sync => {
+                if result.is_none() || local_only {
+                    continue;
+                }
+                let _ = app.emit("processing-status", ProcessingStatus {
+                    stage: "syncing".to_string(),
+                    message: "Syncing to server...".to_string(),
+                    recording_id: Some(id.clone()),
+                    transcript: recording.transcript.clone(),
+                    synced: false,
+                });
+
+                let client = sync_client(&server_url);
+                let db = state.db.lock().map_err(|e| e.to_string())?;
+                let recordings = db.get_unsynced_recordings().map_err(|e| e.to_string())?;
+                let device_id = db.device_id().map_err(|e| e.to_string())?;
+                if let Some(rec) = recordings.iter().find(|r| r.id == id) {
+                    let detail = transcript_detail(&db, rec)?;
+                    match client.submit_transcript(rec, &device_id, &detail) {
+                        Ok(_) => {
+                            db.mark_synced(&id).map_err(|e| e.to_string())?;
+                            synced = true;
+                        }
+                        Err(e) => {
+                            telemetry::SYNC_FAILURES.increment();
+                            db.record_sync_failure(&id, &e.to_string(), e.is_rejection())
+                                .map_err(|e| e.to_string())?
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // The audio stages and whisper are done with the WAV, so it can be
+    // compressed now
+    if format != AudioFormat::Wav {
+        let stored = store_audio(&audio_path, format)?;
+        let lanes = match (&recording.teacher_audio_path, &recording.student_audio_path) {
+            (Some(teacher), Some(student)) => Some((
+                store_audio(Path::new(teacher), format)?,
+                store_audio(Path::new(student), format)?,
+            )),
+            _ => None,
+        };
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.set_audio_file(&id, &stored.to_string_lossy(), AudioFormat::from_path(&stored))
+            .map_err(|e| e.to_string())?;
+        if let Some((teacher, student)) = lanes {
+            db.set_lane_files(&id, &teacher.to_string_lossy(), &student.to_string_lossy())
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let transcript = result.map(|r| r.text);
+    let final_status = ProcessingStatus {
+        stage: "done".to_string(),
+        message: if synced { "Done! Transcript synced to server.".to_string() }
+                 else if transcript.is_some() && local_only { "Done! Transcript saved locally.".to_string() }
+                 else if transcript.is_some() { "Done! Transcript saved locally (sync pending).".to_string() }
+                 else if !transcribed { "Recording saved.".to_string() }
+                 else { "Recording saved. Transcription cancelled.".to_string() },
+        recording_id: Some(id),
+        transcript,
+        synced,
+    };
+
+    let _ = app.emit("processing-status", final_status.clone());
+
+    Ok(final_status)
+}
+
+/// Score a recording from its stored transcript; a failure is only logged
+fn score_stored(db: &Database, state: &AppState, id: &str) {
+    if let Err(e) = score_assessment(db, &state.data_dir, id) {
+        eprintln!("Failed to score recording {}: {}", id, e);
+    }
+}
+
+/// Stop recording and queue it to be transcribed and synced in the
+/// background. Runs off the main thread, which would otherwise stall on the
+/// conversion.
+#[tauri::command(async)]
+fn stop_and_process(state: State<AppState>, app: AppHandle) -> Result<ProcessingStatus, String> {
+    process_recording(&state, &app, CaptureSource::Recorder)
+}
+
+// ========== Push-to-Talk Commands ==========
+
+/// Begin a push-to-talk session. Nothing is recorded until the key goes
+/// down, so this works with the microphone idle.
+#[tauri::command]
+fn start_push_to_talk(state: State<AppState>) -> Result<String, String> {
+    let mut push_to_talk = state.push_to_talk.lock().map_err(|e| e.to_string())?;
+    if let Some(session) = push_to_talk.as_ref() {
+        return Ok(session.session_id.clone());
+    }
+    if state.recorder.lock().map_err(|e| e.to_string())?.is_recording() {
+        return Err("Stop the recording in progress first".to_string());
+    }
+    let session_id = uuid::Uuid::new_v4().to_string();
+    *push_to_talk = Some(PushToTalk {
+        session_id: session_id.clone(),
+        pressed_at: None,
+        last_edge: None,
+    });
+    Ok(session_id)
+}
+
+/// The key went down: start recording an utterance. Returns whether one
+/// started; repeats and bounces of a press already acted on don't.
+#[tauri::command]
+fn push_to_talk_press(state: State<AppState>) -> Result<bool, String> {
+    let mut push_to_talk = state.push_to_talk.lock().map_err(|e| e.to_string())?;
+    let session = push_to_talk
+        .as_mut()
+        .ok_or_else(|| "No push-to-talk session in progress".to_string())?;
+    if session.pressed_at.is_some() || session.bouncing() {
+        return Ok(false);
+    }
+    state.startup.require(startup::RECORDING)?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    check_recording_allowed(&db)?;
+    drop(db);
+    state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .start_recording()
+        .map_err(|e| e.to_string())?;
+    session.pressed_at = Some(Instant::now());
+    session.last_edge = session.pressed_at;
+    Ok(true)
+}
+
+/// The key came up: save and process the utterance, unless it was too
+/// short to be one. Returns `None` when nothing was kept. Runs off the main
+/// thread, and the next press can start while this one is transcribed.
+#[tauri::command(async)]
+fn push_to_talk_release(state: State<AppState>, app: AppHandle) -> Result<Option<ProcessingStatus>, String> {
+    let mut push_to_talk = state.push_to_talk.lock().map_err(|e| e.to_string())?;
+    let Some(session) = push_to_talk.as_mut() else {
+        return Ok(None);
+    };
+    let Some(pressed_at) = session.pressed_at.filter(|_| !session.bouncing()) else {
+        return Ok(None);
+    };
+    session.pressed_at = None;
+    session.last_edge = Some(Instant::now());
+
+    // Stopped before the lock is let go, so a quick next press finds the
+    // recorder free
+    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+    let capture = recorder.finish_capture().map_err(|e| e.to_string())?;
+    let (stats, markers) = (recorder.last_capture_stats(), recorder.take_markers());
+    drop(recorder);
+    let session_id = session.session_id.clone();
+    drop(push_to_talk);
+
+    if pressed_at.elapsed().as_secs_f64() < PUSH_TO_TALK_MIN_SECONDS {
+        let _ = std::fs::remove_file(&capture);
+        return Ok(None);
+    }
+    process_recording(&state, &app, CaptureSource::Utterance(session_id, capture, stats, markers)).map(Some)
+}
+
+/// End the session, dropping an utterance still being held, and list the
+/// ones it saved
+#[tauri::command]
+fn end_push_to_talk(state: State<AppState>) -> Result<PushToTalkSession, String> {
+    let mut push_to_talk = state.push_to_talk.lock().map_err(|e| e.to_string())?;
+    let session = push_to_talk
+        .take()
+        .ok_or_else(|| "No push-to-talk session in progress".to_string())?;
+    if session.pressed_at.is_some() {
+        let capture = state
+            .recorder
+            .lock()
+            .map_err(|e| e.to_string())?
+            .finish_capture()
+            .map_err(|e| e.to_string())?;
+        let _ = std::fs::remove_file(&capture);
+    }
+    drop(push_to_talk);
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let utterances = db
+        .get_session_recordings_including_confidential(&session.session_id)
+        .map_err(|e| e.to_string())?;
+    Ok(PushToTalkSession {
+        session_id: session.session_id,
+        utterances,
+    })
+}
+
+// ========== Assignment Commands ==========
+
+/// Assignments the server has for the configured student
+#[tauri::command]
+fn get_assignments(state: State<AppState>) -> Result<Vec<Assignment>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    if is_local_only(&db)? {
+        return Err("Syncing is disabled in local-only mode".to_string());
+    }
+    let student_id = db
+        .get_setting("student_id")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "unknown".to_string());
+    drop(db);
+
+    let client = sync_client(&server_url);
+    if !client.capabilities().assignments {
+        return Ok(Vec::new());
+    }
+    client.fetch_assignments(&student_id).map_err(|e| e.to_string())
+}
+
+/// Start a session from `assignment`. Until it's finished, every recording
+/// reads its passage, is scored by its rubric if it has one, and can't have
+/// the passage changed. Starting the one already in progress carries on
+/// with it.
+#[tauri::command]
+fn start_assignment(state: State<AppState>, assignment: Assignment) -> Result<Assignment, String> {
+    if assignment.id.trim().is_empty() {
+        return Err("Assignment has no ID".to_string());
+    }
+    if assignment.passage.trim().is_empty() {
+        return Err("Assignment has no passage".to_string());
+    }
+    if assignment.expected_duration_seconds.is_some_and(|d| !d.is_finite() || d <= 0.0) {
+        return Err("Expected duration must be positive".to_string());
+    }
+    let mut assignment = assignment;
+    assignment.rubric = assignment
+        .rubric
+        .map(Rubric::validate)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match db.get_active_assignment().map_err(|e| e.to_string())? {
+        Some(active) if active.id == assignment.id => return Ok(active),
+        Some(active) => return Err(format!("Finish assignment \"{}\" first", active.title)),
+        None => {}
+    }
+    db.start_assignment(&assignment).map_err(|e| e.to_string())?;
+    db.get_assignment(&assignment.id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Assignment not found".to_string())
+}
+
+#[tauri::command]
+fn get_active_assignment(state: State<AppState>) -> Result<Option<Assignment>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_active_assignment().map_err(|e| e.to_string())
+}
+
+/// End the assignment in progress. It's marked completed and reported at
+/// the next sync, or dropped if nothing was recorded for it.
+#[tauri::command]
+fn finish_assignment(state: State<AppState>) -> Result<Option<Assignment>, String> {
+    if state.recorder.lock().map_err(|e| e.to_string())?.is_recording() {
+        return Err("Stop the recording in progress first".to_string());
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let Some(assignment) = db.get_active_assignment().map_err(|e| e.to_string())? else {
+        return Err("No assignment is in progress".to_string());
+    };
+    if db
+        .get_assignment_recordings_including_confidential(&assignment.id)
+        .map_err(|e| e.to_string())?
+        .is_empty()
+    {
+        db.delete_assignment(&assignment.id).map_err(|e| e.to_string())?;
+        return Ok(None);
+    }
+    db.complete_assignment(&assignment.id).map_err(|e| e.to_string())?;
+    db.get_assignment(&assignment.id).map_err(|e| e.to_string())
+}
+
+// ========== Transcription Commands ==========
+
+#[tauri::command]
+fn load_model(state: State<AppState>) -> Result<(), String> {
+    run_job(&state.db, JobKind::ModelLoad, None, || load_transcriber(&state), |_| Ok(None))
+}
+
+fn load_transcriber(state: &AppState) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let model_path = model_path(&db, &state.data_dir)?;
+    let backend = transcription_backend_setting(&db)?;
+    let hf_token = hf_token(&db, &state.data_dir);
+    whisper::set_program_paths(program_paths_setting(&db)?);
+    drop(db);
+
+    let transcriber = match whisper::load_backend(backend, &model_path, hf_token) {
+        Ok(transcriber) => transcriber,
+        Err(WhisperError::ModelNotFound(_)) => {
+            return Err(format!(
+                "Model not found. Please download it to: {}",
+                model_path.display()
+            ))
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    *state.transcriber.lock_or_recover() = Some(transcriber);
+
+    Ok(())
+}
+
+#[tauri::command]
+fn transcribe_recording(state: State<AppState>, app: AppHandle, recording_id: String) -> Result<TranscribeResult, String> {
+    run_job(
+        &state.db,
+        JobKind::Transcription,
+        Some(&recording_id),
+        || transcribe_saved(&state, &app, recording_id.clone(), None, None),
+        |_| Ok(None),
+    )
+}
+
+/// Watch a full transcription of `recording_id`, emitting
+/// `transcription-progress` as segments are decoded, until
+/// `end_transcription`
+fn begin_transcription(state: &AppState, app: &AppHandle, recording_id: &str) -> TranscriptionMonitor {
+    let (app, id) = (app.clone(), recording_id.to_string());
+    let monitor = TranscriptionMonitor::new(move |progress| {
+        let _ = app.emit("transcription-progress", TranscriptionProgressEvent {
+            recording_id: id.clone(),
+            progress: progress.clone(),
+        });
+    });
+    *state.transcription.lock_or_recover() = Some((recording_id.to_string(), monitor.clone()));
+    monitor
+}
+
+fn end_transcription(state: &AppState, recording_id: &str) {
+    let mut current = state.transcription.lock_or_recover();
+    if current.as_ref().is_some_and(|(id, _)| id == recording_id) {
+        *current = None;
+    }
+}
+
+/// Stop the transcription in progress, if it's of `recording_id` or, with
+/// `None`, whatever it is. The engine's process is killed and the recording
+/// kept untranscribed. Returns whether anything was running.
+#[tauri::command]
+fn cancel_transcription(state: State<AppState>, recording_id: Option<String>) -> Result<bool, String> {
+    let current = state.transcription.lock_or_recover();
+    match current.as_ref() {
+        Some((id, monitor)) if recording_id.as_ref().is_none_or(|wanted| wanted == id) => {
+            monitor.cancel();
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// A transcriber to run in place of the loaded one, for trying a bad
+/// transcript again with other settings
+struct Rerun {
+    transcriber: Box<dyn TranscriptionBackend>,
+    /// Kept with the transcript it replaces, such as "model small.en"
+    description: String,
+}
+
+/// Transcribe a saved recording again, in the configured language with the
+/// loaded model, or in `language` with a multilingual one, or with `rerun`'s
+/// transcriber. A transcript it already had is kept as a revision.
+fn transcribe_saved(
+    state: &AppState,
+    app: &AppHandle,
+    recording_id: String,
+    language: Option<String>,
+    rerun: Option<Rerun>,
+) -> Result<TranscribeResult, String> {
+    // Get the recording
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    let configured_language = transcription_language(&db)?;
+    let word_timestamps = word_timestamps_setting(&db)?;
+    let speakers = speaker_count_setting(&db)?;
+    let vocabulary = vocabulary_for(&db, &recording.student_id)?;
+    drop(db); // Release lock before transcription
+
+    let replaced_by = match (&rerun, &language) {
+        (Some(rerun), _) => Some(rerun.description.clone()),
+        (None, Some(language)) => Some(format!("language {}", language)),
+        (None, None) => None,
+    };
+    let multilingual = match (rerun, &language) {
+        (Some(rerun), _) => Some(rerun.transcriber),
+        (None, Some(_)) => Some(multilingual_transcriber(state)?.ok_or_else(|| {
+            format!(
+                "No multilingual model found. Download the {} model first.",
+                models::DEFAULT_MULTILINGUAL_MODEL
+            )
+        })?),
+        (None, None) => transcriber_for_language(state, &configured_language)?,
+    };
+    let language = language.unwrap_or(configured_language);
+
+    // Get audio file path
+    let audio_path = transcription_input(&recording)?;
+
+    // Transcribe using CLI
+    let transcriber_guard = state.transcriber.lock_or_recover();
+    let transcriber = match multilingual.as_ref() {
+        Some(transcriber) => transcriber,
+        None => transcriber_guard
+            .as_ref()
+            .ok_or_else(|| "Model not loaded. Please load the model first.".to_string())?,
+    };
+
+    let options = TranscribeOptions {
+        passage: recording.reference_passage.clone(),
+        language: Some(language.clone()),
+        monitor: Some(begin_transcription(state, app, &recording.id)),
+        word_timestamps,
+        speakers,
+        vocabulary: vocabulary.clone(),
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let result = transcriber.transcribe_with(&audio_path, &options);
+    telemetry::TRANSCRIPTION_SECONDS.observe(started.elapsed().as_secs_f64());
+    drop(transcriber_guard); // Release lock
+    end_transcription(state, &recording.id);
+    // A decoded copy is kept until voices have been matched in it
+    let discard_input = || {
+        if recording.audio_format != AudioFormat::Wav {
+            let _ = std::fs::remove_file(&audio_path);
+        }
+    };
+    let mut result = result.map_err(|e| {
+        discard_input();
+        e.to_string()
+    })?;
+
+    // Update recording with transcript
+    let mut updated_recording = recording.clone();
+    updated_recording.transcript = Some(result.text.clone());
+    updated_recording.transcript_language = transcript_language(&language, &result);
+    if recording.translate {
+        let translated = translate_recording(state, &updated_recording, &audio_path, &vocabulary);
+        updated_recording.translation = match translated {
+            Ok(translation) => Some(translation),
+            Err(e) => {
+                eprintln!("Failed to translate recording {}: {}", recording_id, e);
+                None
+            }
+        };
+    }
+
+    // A redact stage applies to re-transcriptions too, as does a mask stage
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let activity = db.get_channel_activity(&recording_id).map_err(|e| e.to_string())?;
+    attribute_speakers(&recording, activity.as_ref(), &mut result.segments);
+    let pipeline = pipeline_setting(&db)?;
+    if let Some(stage) = pipeline.redaction() {
+        apply_redaction(&db, stage, &mut result, updated_recording.translation.as_mut())?;
+    }
+    updated_recording.transcript = Some(result.text.clone());
+    updated_recording.unmasked_transcript = None;
+    updated_recording.unmasked_translation = None;
+    let unmasked_segments = pipeline
+        .masking()
+        .and_then(|stage| apply_masking(stage, &mut updated_recording, &mut result));
+    tag_activity(&db, &mut updated_recording, &result.segments)?;
+    let flagged = apply_speaker_names(&db, &mut updated_recording, &result.segments)
+        .and_then(|_| classify_speakers(&db, &mut updated_recording, &audio_path, &result.segments));
+    discard_input();
+    flagged?;
+    db.save_transcript_revision(&recording, replaced_by.as_deref())
+        .map_err(|e| e.to_string())?;
+    db.save_recording(&updated_recording)
+        .map_err(|e| e.to_string())?;
+    db.save_segments(&recording_id, &result.segments)
+        .map_err(|e| e.to_string())?;
+    db.set_unmasked_segments(&recording_id, unmasked_segments.as_deref())
+        .map_err(|e| e.to_string())?;
+    if let Err(e) = score_assessment(&db, &state.data_dir, &recording_id) {
+        eprintln!("Failed to score recording {}: {}", recording_id, e);
+    }
+
+    Ok(TranscribeResult {
+        transcript: result.text,
+        recording_id,
+    })
+}
+
+/// Transcribe a recording again to fix a bad transcript, with `model` (a
+/// downloaded model's name, such as "small.en"), in `language`, or with
+/// diarization turned on or off; each left unset is as configured. The
+/// transcript it replaces is kept as a revision.
+#[tauri::command]
+fn retranscribe_recording(
+    state: State<AppState>,
+    app: AppHandle,
+    recording_id: String,
+    model: Option<String>,
+    language: Option<String>,
+    diarize: Option<bool>,
+) -> Result<TranscribeResult, String> {
+    let language = language.map(|l| l.trim().to_lowercase()).filter(|l| !l.is_empty());
+    let model = model.map(|m| m.trim().to_string()).filter(|m| !m.is_empty());
+    run_job(
+        &state.db,
+        JobKind::Transcription,
+        Some(&recording_id),
+        || {
+            let rerun = match (&model, diarize) {
+                (None, None) => None,
+                _ => Some(rerun_transcriber(&state, model.as_deref(), diarize)?),
+            };
+            transcribe_saved(&state, &app, recording_id.clone(), language.clone(), rerun)
+        },
+        |_| Ok(None),
+    )
+}
+
+/// Turn translating a recording to English on or off. Turned on for one
+/// that's already transcribed, it's translated straight away, keeping the
+/// transcript in the language spoken; turned off, the translation is
+/// dropped.
+#[tauri::command(async)]
+fn set_recording_translation(state: State<AppState>, recording_id: String, translate: bool) -> Result<Recording, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    let vocabulary = vocabulary_for(&db, &recording.student_id)?;
+    drop(db);
+
+    recording.translate = translate;
+    recording.translation = None;
+    if translate && recording.transcript.is_some() {
+        if recording.audio_purged {
+            return Err("This recording's audio has been deleted, so it can't be translated".to_string());
+        }
+        let audio_path = transcription_input(&recording)?;
+        let translated = translate_recording(&state, &recording, &audio_path, &vocabulary);
+        if recording.audio_format != AudioFormat::Wav {
+            let _ = std::fs::remove_file(&audio_path);
+        }
+        let mut translation = translated?;
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        let pipeline = pipeline_setting(&db)?;
+        if let Some(stage) = pipeline.redaction() {
+            if let Some((terms, numbers)) = redaction_terms(&db, stage)? {
+                translation = pipeline::redact(&translation, &terms, numbers);
+            }
+        }
+        if let Some(terms) = pipeline.masking().and_then(masked_terms) {
+            translation = pipeline::mask(&translation, &terms);
+        }
+        recording.translation = Some(translation);
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.save_recording(&recording).map_err(|e| e.to_string())?;
+    Ok(recording)
+}
+
+/// What `retranscribe_recording` runs: `model` in place of the configured
+/// one, and diarizing or not as `diarize` says. Diarizing needs WhisperX
+/// and a Hugging Face token, whatever the configured engine.
+fn rerun_transcriber(state: &AppState, model: Option<&str>, diarize: Option<bool>) -> Result<Rerun, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let model_path = match model {
+        Some(name) => {
+            let spec = models::find(name).map_err(|e| e.to_string())?;
+            state.data_dir.join("models").join(spec.file_name())
+        }
+        None => model_path(&db, &state.data_dir)?,
+    };
+    let mut backend = transcription_backend_setting(&db)?;
+    let mut hf_token = hf_token(&db, &state.data_dir);
+    whisper::set_program_paths(program_paths_setting(&db)?);
+    drop(db);
+
+    let mut description: Vec<String> = model.map(|name| format!("model {}", name)).into_iter().collect();
+    match diarize {
+        Some(true) => {
+            if hf_token.is_none() {
+                return Err("Diarizing needs a Hugging Face token. Set one first.".to_string());
+            }
+            backend = BackendKind::WhisperX;
+            description.push("diarized".to_string());
+        }
+        Some(false) => {
+            hf_token = None;
+            description.push("not diarized".to_string());
+        }
+        None => {}
+    }
+
+    let transcriber = match whisper::load_backend(backend, &model_path, hf_token) {
+        Ok(transcriber) => transcriber,
+        Err(WhisperError::ModelNotFound(_)) => {
+            return Err(format!(
+                "Model not found. Please download it to: {}",
+                model_path.display()
+            ))
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok(Rerun {
+        transcriber,
+        description: description.join(", "),
+    })
+}
+
+/// Transcripts a recording had before it was transcribed again, oldest first
+#[tauri::command]
+fn get_transcript_revisions(state: State<AppState>, recording_id: String) -> Result<Vec<TranscriptRevision>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_transcript_revisions(&recording_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Recordings that sound like a different language from the one they were
+/// transcribed in, newest first
+#[tauri::command]
+fn get_language_mismatches(state: State<AppState>) -> Result<Vec<Recording>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_language_mismatches().map_err(|e| e.to_string())
+}
+
+/// Transcribe a recording again in the language detected in it, using a
+/// multilingual model
+#[tauri::command]
+fn retranscribe_detected_language(
+    state: State<AppState>,
+    app: AppHandle,
+    recording_id: String,
+) -> Result<TranscribeResult, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let detected = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?
+        .detected_language
+        .ok_or_else(|| "No language was detected for this recording".to_string())?;
+    drop(db);
+
+    run_job(
+        &state.db,
+        JobKind::Transcription,
+        Some(&recording_id),
+        || transcribe_saved(&state, &app, recording_id.clone(), Some(detected.clone()), None),
+        |_| Ok(None),
+    )
+}
+
+/// Engine to run the model with. The model is reloaded straight away if
+/// one was loaded; otherwise the next load uses it.
+#[tauri::command]
+fn set_transcription_backend(state: State<AppState>, backend: BackendKind) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("transcription_backend", backend.as_str())
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    if state.transcriber.lock_or_recover().is_some() {
+        load_transcriber(&state)?;
+    }
+    Ok(())
+}
+
+/// Where to find whisper.cpp's CLI and WhisperX when they aren't installed
+/// anywhere the app looks; None reverts to the pushed setting or looking in
+/// the usual places. WhisperX's may be its program or the Python
+/// environment it's installed in. The model is reloaded straight away if
+/// one was loaded. Returns what's found with the new paths.
+#[tauri::command]
+fn set_backend_paths(
+    state: State<AppState>,
+    whisper_cli: Option<String>,
+    whisperx: Option<String>,
+) -> Result<Vec<BackendDiscovery>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    for (key, value) in [("whisper_cli_path", whisper_cli), ("whisperx_path", whisperx)] {
+        match value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+            Some(path) => db.set_setting(key, &path),
+            None => db.delete_setting_as(key, "user"),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    whisper::set_program_paths(program_paths_setting(&db)?);
+    drop(db);
+
+    if state.transcriber.lock_or_recover().is_some() {
+        load_transcriber(&state)?;
+    }
+    Ok(whisper::detect_backends())
+}
+
+/// Where each transcription engine was found, if it was, and everywhere
+/// that was looked
+#[tauri::command]
+fn detect_transcription_backends() -> Vec<BackendDiscovery> {
+    whisper::detect_backends()
+}
+
+/// Language new recordings are transcribed in, as a whisper language code
+/// such as "en" or "es", or "auto" to have the model pick for each
+/// recording; None reverts to the pushed setting or English. Anything but
+/// English needs a multilingual model, which is used in place of an
+/// English-only one once downloaded.
+#[tauri::command]
+fn set_transcription_language(state: State<AppState>, language: Option<String>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let Some(language) = language else {
+        return db
+            .delete_setting_as("transcription_language", "user")
+            .map_err(|e| e.to_string());
+    };
+    let language = language.trim().to_lowercase();
+    if language.is_empty() || language.len() > 8 || !language.chars().all(|c| c.is_ascii_lowercase()) {
+        return Err(format!("Not a language code: {}", language));
+    }
+    db.set_setting("transcription_language", &language)
+        .map_err(|e| e.to_string())
+}
+
+/// Whether new transcripts are timed word by word, for playing single words
+/// back and timing them in reading scores; None reverts to the pushed
+/// setting or off. Transcripts made before keep their segment times only.
+#[tauri::command]
+fn set_word_timestamps(state: State<AppState>, enabled: Option<bool>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match enabled {
+        Some(enabled) => db
+            .set_setting("word_timestamps", if enabled { "true" } else { "false" })
+            .map_err(|e| e.to_string()),
+        None => db
+            .delete_setting_as("word_timestamps", "user")
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[derive(Serialize)]
+struct Vocabulary {
+    /// `vocabulary`, prompted for every student
+    shared: Vec<String>,
+    /// Prompted for this student only
+    student: Vec<String>,
+    /// Everything their recordings are prompted with, teacher's name included
+    prompted: Vec<String>,
+}
+
+/// Terms recordings of `student_id` are prompted with, this device's
+/// student when None
+#[tauri::command]
+fn get_vocabulary(state: State<AppState>, student_id: Option<String>) -> Result<Vocabulary, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let student_id = match student_id {
+        Some(student_id) => student_id,
+        None => db
+            .get_setting("student_id")
+            .map_err(|e| e.to_string())?
+            .unwrap_or_else(|| "unknown".to_string()),
+    };
+    let shared = settings::resolve(&db, "vocabulary").map_err(|e| e.to_string())?;
+    Ok(Vocabulary {
+        shared: parse_terms(shared.as_deref()),
+        student: db.get_student_vocabulary(&student_id).map_err(|e| e.to_string())?,
+        prompted: vocabulary_for(&db, &student_id)?,
+    })
+}
+
+/// Names and terms every recording is prompted with so they stop being
+/// misheard, a term per line or comma; None reverts to the pushed setting
+/// or none. Applies to transcripts made from now on.
+#[tauri::command]
+fn set_vocabulary(state: State<AppState>, terms: Option<String>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match terms {
+        Some(terms) => db.set_setting("vocabulary", &parse_terms([terms.as_str()]).join("\n")),
+        None => db.delete_setting_as("vocabulary", "user"),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Names and terms only `student_id`'s recordings are prompted with, such
+/// as their own name or a book they're reading; empty forgets them
+#[tauri::command]
+fn set_student_vocabulary(state: State<AppState>, student_id: String, terms: Vec<String>) -> Result<(), String> {
+    let terms = parse_terms(terms.iter().map(String::as_str));
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_student_vocabulary(&student_id, &terms)
+        .map_err(|e| e.to_string())
+}
+
+// ========== Diarization Commands ==========
+
+#[derive(Serialize)]
+struct DiarizationStatus {
+    /// Whether the transcription backend can diarize; only WhisperX can
+    supported: bool,
+    /// "settings" or "environment", when there's a token
+    token_source: Option<&'static str>,
+    /// Set when the stored token can't be unsealed, so it must be entered again
+    token_error: Option<String>,
+    /// Whether the loaded transcriber is diarizing
+    active: bool,
+    speakers: SpeakerCount,
+}
+
+#[tauri::command]
+fn get_diarization_status(state: State<AppState>) -> Result<DiarizationStatus, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let supported = transcription_backend_setting(&db)? == BackendKind::WhisperX;
+    let (token_source, token_error) = match hf_token_with_source(&db, &state.data_dir) {
+        Ok(found) => (found.map(|(_, source)| source), None),
+        Err(e) => (None, Some(e)),
+    };
+    let speakers = speaker_count_setting(&db)?;
+    drop(db);
+    let active = state.transcriber.lock_or_recover().as_ref().is_some_and(|t| t.diarizes());
+    Ok(DiarizationStatus {
+        supported,
+        token_source,
+        token_error,
+        active,
+        speakers,
+    })
+}
+
+/// Store the Hugging Face token WhisperX's diarization models need, sealed
+/// with a key kept on this device, and start diarizing with it straight
+/// away. None removes it, falling back to `HF_TOKEN` if that's set.
+#[tauri::command]
+fn set_hf_token(state: State<AppState>, token: Option<String>) -> Result<DiarizationStatus, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match token.as_deref().map(str::trim) {
+        Some(token) if token.is_empty() || token.contains(char::is_whitespace) => {
+            return Err("Not a Hugging Face token".to_string());
+        }
+        Some(token) => {
+            let sealed = secrets::seal(&state.data_dir, "hf_token", token).map_err(|e| e.to_string())?;
+            db.set_setting("hf_token", &sealed)
+        }
+        None => db.delete_setting_as("hf_token", "user"),
+    }
+    .map_err(|e| e.to_string())?;
+    let hf_token = hf_token(&db, &state.data_dir);
+    drop(db);
+
+    if let Some(transcriber) = state.transcriber.lock_or_recover().as_mut() {
+        transcriber.set_hf_token(hf_token);
+    }
+    get_diarization_status(state)
+}
+
+/// Bound how many speakers diarizing finds: a 1:1 session is exactly two,
+/// so `min` and `max` both 2. A None bound reverts to the pushed setting or
+/// the model's own guess.
+#[tauri::command]
+fn set_speaker_count(
+    state: State<AppState>,
+    min: Option<u32>,
+    max: Option<u32>,
+) -> Result<DiarizationStatus, String> {
+    if min == Some(0) || max == Some(0) {
+        return Err("A recording has at least one speaker".to_string());
+    }
+    if let (Some(min), Some(max)) = (min, max) {
+        if min > max {
+            return Err(format!("At least {} speakers can't be at most {}", min, max));
+        }
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    for (key, bound) in [("min_speakers", min), ("max_speakers", max)] {
+        match bound {
+            Some(bound) => db.set_setting(key, &bound.to_string()),
+            None => db.delete_setting_as(key, "user"),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    drop(db);
+    get_diarization_status(state)
+}
+
+// ========== Model Commands ==========
+
+/// Every model that can be downloaded, and which are already here
+#[tauri::command]
+fn list_available_models(state: State<AppState>) -> Result<Vec<AvailableModel>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let selected = model_path(&db, &state.data_dir)?;
+    drop(db);
+    Ok(models::list(&state.data_dir.join("models"), &selected))
+}
+
+#[derive(Serialize)]
+struct HardwareDecision {
+    profile: hardware::HardwareProfile,
+    /// The model used when none is picked or set
+    auto_model: &'static str,
+    /// Whether a picked or pushed model is used instead
+    model_overridden: bool,
+    /// Threads transcription runs with
+    threads: usize,
+    /// Whether that's `transcription_threads` rather than the profile's
+    threads_overridden: bool,
+}
+
+/// What was detected about this machine at startup and what it decided:
+/// the default model and how many threads transcribe with
+#[tauri::command]
+fn get_hardware_profile(state: State<AppState>) -> Result<HardwareDecision, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let auto_model = auto_model(&db, &state.data_dir.join("models"))?;
+    let mut model_overridden = false;
+    for key in ["selected_model", "model"] {
+        model_overridden |= settings::resolve(&db, key)
+            .map_err(|e| e.to_string())?
+            .is_some_and(|v| !v.is_empty());
+    }
+    let threads_overridden = settings::resolve(&db, "transcription_threads")
+        .map_err(|e| e.to_string())?
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|n| n > 0);
+    Ok(HardwareDecision {
+        profile: hardware::profile().clone(),
+        auto_model,
+        model_overridden,
+        threads: transcription_threads_setting(&db)?,
+        threads_overridden,
+    })
+}
+
+/// Transcribe with this many threads rather than the hardware profile's
+/// choice; None goes back to that
+#[tauri::command]
+fn set_transcription_threads(state: State<AppState>, threads: Option<usize>) -> Result<(), String> {
+    if threads == Some(0) {
+        return Err("Transcription needs at least one thread".to_string());
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match threads {
+        Some(threads) => db.set_setting("transcription_threads", &threads.to_string()),
+        None => db.delete_setting_as("transcription_threads", "user"),
+    }
+    .map_err(|e| e.to_string())?;
+    whisper::set_threads(transcription_threads_setting(&db)?);
+    Ok(())
+}
+
+/// Length of audio each model is timed on
+const BENCHMARK_SECONDS: f64 = 30.0;
+
+/// Shortest sample worth timing; below it model load time dominates
+const MIN_BENCHMARK_SECONDS: f64 = 10.0;
+
+/// Least share of the sample that must be speech. Silence decodes to next
+/// to nothing, so a quiet sample makes every model look fast.
+const MIN_BENCHMARK_SPEECH_RATIO: f32 = 0.2;
+
+/// Slowest a model may run, in seconds taken per second of audio, to be
+/// recommended; leaves headroom for the rest of the pipeline and whatever
+/// else the machine is doing
+const RECOMMEND_MAX_REALTIME_FACTOR: f64 = 0.5;
+
+#[derive(Serialize)]
+struct ModelBenchmark {
+    model: &'static str,
+    /// Seconds taken to transcribe the sample
+    seconds: Option<f64>,
+    /// Seconds taken per second of audio; under 1 is faster than realtime
+    realtime_factor: Option<f64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BenchmarkResult {
+    sample_seconds: f64,
+    /// Where the sample came from: "bundled" or a recording id
+    sample: String,
+    models: Vec<ModelBenchmark>,
+    /// Largest model fast enough on this machine that suits the
+    /// transcription language
+    recommended: Option<&'static str>,
+}
+
+/// Time every downloaded model on the same short sample, to pick the
+/// largest one this machine can keep up with. The sample is the one shipped
+/// with the app, or the first half minute of the latest recording long
+/// enough when the build has none. Always run with whisper.cpp, whose models these are.
+/// Runs off the main thread; large models take minutes.
+#[tauri::command(async)]
+fn benchmark_transcription(state: State<AppState>, app: AppHandle) -> Result<BenchmarkResult, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let selected = model_path(&db, &state.data_dir)?;
+    let language = transcription_language(&db)?;
+    let latest = db
+        .get_all_recordings()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|r| {
+            !r.audio_purged && r.duration_seconds >= MIN_BENCHMARK_SECONDS && Path::new(&r.audio_path).is_file()
+        });
+    whisper::set_program_paths(program_paths_setting(&db)?);
+    drop(db);
+
+    let bundled = app
+        .path()
+        .resolve("resources/benchmark.wav", tauri::path::BaseDirectory::Resource)
+        .ok()
+        .filter(|p| p.is_file());
+    let (source, sample) = match (bundled, latest) {
+        (Some(path), _) => (path, "bundled".to_string()),
+        (None, Some(recording)) => (PathBuf::from(&recording.audio_path), recording.id),
+        (None, None) => return Err("No benchmark sample is bundled; make a recording to benchmark with".to_string()),
+    };
+
+    let scratch = std::env::temp_dir().join(format!("classroom-benchmark-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&scratch).map_err(|e| e.to_string())?;
+    let result = run_benchmark(&state.data_dir, &selected, &language, &source, &scratch).map(
+        |(sample_seconds, models)| BenchmarkResult {
+            recommended: recommend_model(&models, &language),
+            sample_seconds,
+            sample,
+            models,
+        },
+    );
+    let _ = std::fs::remove_dir_all(&scratch);
+    result
+}
+
+fn run_benchmark(
+    data_dir: &Path,
+    selected: &Path,
+    language: &str,
+    source: &Path,
+    scratch: &Path,
+) -> Result<(f64, Vec<ModelBenchmark>), String> {
+    let (samples, sample_rate) = audio::read_audio_prefix(source, BENCHMARK_SECONDS).map_err(|e| e.to_string())?;
+    let sample_seconds = samples.len() as f64 / sample_rate as f64;
+    if sample_seconds < MIN_BENCHMARK_SECONDS {
+        return Err(format!(
+            "The benchmark sample is {:.0}s long; at least {:.0}s is needed",
+            sample_seconds, MIN_BENCHMARK_SECONDS
+        ));
+    }
+    if dsp::estimate_speech(&samples, sample_rate).speech_ratio < MIN_BENCHMARK_SPEECH_RATIO {
+        return Err("The benchmark sample is mostly silence".to_string());
+    }
+    let excerpt = scratch.join("excerpt.wav");
+    audio::write_wav(&samples, sample_rate, &excerpt).map_err(|e| e.to_string())?;
+    let clip = scratch.join("sample.wav");
+    audio::import_to_16khz_mono(&excerpt, &clip, ResampleQuality::Fast, None).map_err(|e| e.to_string())?;
+
+    let benchmarks = models::list(&data_dir.join("models"), selected)
+        .into_iter()
+        .filter(|m| m.downloaded)
+        .map(|model| {
+            let options = TranscribeOptions {
+                language: Some(if model.multilingual { language } else { "en" }.to_string()),
+                ..Default::default()
+            };
+            let timed = whisper::load_backend(
+                BackendKind::WhisperCpp,
+                &data_dir.join("models").join(&model.file_name),
+                None,
+            )
+            .and_then(|backend| {
+                let started = Instant::now();
+                backend.transcribe_with(&clip, &options)?;
+                Ok(started.elapsed().as_secs_f64())
+            });
+            match timed {
+                Ok(seconds) => ModelBenchmark {
+                    model: model.name,
+                    seconds: Some(seconds),
+                    realtime_factor: Some(seconds / sample_seconds),
+                    error: None,
+                },
+                Err(e) => ModelBenchmark {
+                    model: model.name,
+                    seconds: None,
+                    realtime_factor: None,
+                    error: Some(e.to_string()),
+                },
+            }
+        })
+        .collect();
+    Ok((sample_seconds, benchmarks))
+}
+
+/// The largest benchmarked model within `RECOMMEND_MAX_REALTIME_FACTOR`.
+/// English-only models are only picked for English, and preferred there
+/// over a multilingual model of the same size.
+fn recommend_model(benchmarks: &[ModelBenchmark], language: &str) -> Option<&'static str> {
+    let english = language == "en";
+    benchmarks
+        .iter()
+        .filter(|b| b.realtime_factor.is_some_and(|f| f <= RECOMMEND_MAX_REALTIME_FACTOR))
+        .filter_map(|b| models::find(b.model).ok())
+        .filter(|m| english || m.multilingual())
+        .max_by_key(|m| (m.size_mb, english && !m.multilingual()))
+        .map(|m| m.name)
+}
+
+/// Fetch a model, emitting `model-download-progress` as it arrives, and
+/// load it if it's the one selected. Runs off the main thread; the larger
+/// models take a while. A failed download resumes when retried.
+#[tauri::command(async)]
+fn download_model(state: State<AppState>, app: AppHandle, model_name: String) -> Result<(), String> {
+    let model = models::find(&model_name).map_err(|e| e.to_string())?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    if is_local_only(&db)? {
+        return Err("Local-only mode is on, so models can't be downloaded".to_string());
+    }
+    drop(db);
+    let path = run_job(
+        &state.db,
+        JobKind::Download,
+        None,
+        || {
+            // One event per whole percent, or per megabyte when the size is unknown
+            let mut reported = None;
+            models::download(&state.data_dir.join("models"), model, |progress| {
+                let step = match progress.percent {
+                    Some(percent) => percent as u64,
+                    None => progress.bytes_downloaded >> 20,
+                };
+                if reported != Some(step) {
+                    reported = Some(step);
+                    let _ = app.emit("model-download-progress", ModelDownloadProgress {
+                        model: model.name.to_string(),
+                        progress,
+                    });
+                }
+            })
+            .map_err(|e| e.to_string())
+        },
+        |_| Ok(Some(format!("Downloaded the {} model", model.name))),
+    )?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let selected = model_path(&db, &state.data_dir)?;
+    drop(db);
+    if path == selected {
+        load_transcriber(&state)?;
+    }
+    Ok(())
+}
+
+/// Remove a downloaded model to free disk space. The one in use can't be
+/// removed.
+#[tauri::command]
+fn delete_model(state: State<AppState>, model_name: String) -> Result<(), String> {
+    let model = models::find(&model_name).map_err(|e| e.to_string())?;
+    let models_dir = state.data_dir.join("models");
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    if model_path(&db, &state.data_dir)? == models_dir.join(model.file_name()) {
+        return Err("Select another model before deleting the one in use".to_string());
+    }
+    drop(db);
+    models::delete(&models_dir, model).map_err(|e| e.to_string())
+}
+
+/// Transcribe with `model_name` from now on. It's loaded straight away if
+/// it has been downloaded; until then no model is loaded.
+#[tauri::command]
+fn select_model(state: State<AppState>, model_name: String) -> Result<(), String> {
+    let model = models::find(&model_name).map_err(|e| e.to_string())?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting("selected_model", model.name)
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    if state.data_dir.join("models").join(model.file_name()).exists() {
+        load_transcriber(&state)
+    } else {
+        *state.transcriber.lock_or_recover() = None;
+        Ok(())
+    }
+}
+
+// ========== Correction Commands ==========
+
+/// Begin recording a spoken correction for one segment
+#[tauri::command]
+fn start_correction(state: State<AppState>, recording_id: String, segment_index: usize) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let segments = db.get_segments(&recording_id).map_err(|e| e.to_string())?;
+    drop(db);
+    if segment_index >= segments.len() {
+        return Err(format!("Segment {} not found", segment_index));
+    }
+
+    state.startup.require(startup::RECORDING)?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    check_recording_allowed(&db)?;
+    drop(db);
+    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+    if recorder.is_recording() {
+        return Err("A recording is already in progress".to_string());
+    }
+    recorder.start_recording().map_err(|e| e.to_string())?;
+    *state.pending_correction.lock().map_err(|e| e.to_string())? =
+        Some(PendingCorrection { recording_id, segment_index, clip: None });
+    Ok(())
+}
+
+/// Stop the correction clip, transcribe it and replace the segment text.
+/// The clip and previous text are kept in the revision history. If the
+/// clip can't be transcribed the correction stays pending, and calling
+/// this again retries with the same clip.
+#[tauri::command]
+fn finish_correction(state: State<AppState>) -> Result<TranscriptSegment, String> {
+    let PendingCorrection { recording_id, segment_index, clip } = state
+        .pending_correction
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .ok_or_else(|| "No correction in progress".to_string())?;
+
+    let clip_path = match clip {
+        Some(clip) => clip,
+        None => {
+            let corrections_dir = state.data_dir.join("audio").join("corrections");
+            std::fs::create_dir_all(&corrections_dir).map_err(|e| e.to_string())?;
+            let clip_path = corrections_dir.join(format!("{}.wav", uuid::Uuid::new_v4()));
+            let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+            recorder
+                .stop_recording(&clip_path)
+                .map_err(|e| e.to_string())?;
+            drop(recorder);
+            if let Some(pending) = state.pending_correction.lock().map_err(|e| e.to_string())?.as_mut() {
+                pending.clip = Some(clip_path.clone());
+            }
+            clip_path
+        }
+    };
+
+    let transcriber_guard = state.transcriber.lock_or_recover();
+    let transcriber = transcriber_guard
+        .as_ref()
+        .ok_or_else(|| "Model not loaded. Please load the model first.".to_string())?;
+    let result = transcriber.transcribe(&clip_path).map_err(|e| e.to_string())?;
+    drop(transcriber_guard);
+
+    // Another try would hear the same silence, so this one is given up
+    if result.text.is_empty() {
+        *state.pending_correction.lock().map_err(|e| e.to_string())? = None;
+        let _ = std::fs::remove_file(&clip_path);
+        return Err("No speech detected in the correction clip".to_string());
+    }
+
+    let clip_path = clip_path.to_string_lossy().to_string();
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.revise_segment(&recording_id, segment_index, &result.text, Some(&clip_path))
+        .map_err(|e| e.to_string())?;
+
+    let segments = db.get_segments(&recording_id).map_err(|e| e.to_string())?;
+    db.update_transcript(&recording_id, &join_segments(&segments))
+        .map_err(|e| e.to_string())?;
+    drop(db);
+    *state.pending_correction.lock().map_err(|e| e.to_string())? = None;
+
+    segments
+        .get(segment_index)
+        .cloned()
+        .ok_or_else(|| format!("Segment {} not found", segment_index))
+}
+
+#[tauri::command]
+fn get_segment_revisions(state: State<AppState>, recording_id: String) -> Result<Vec<SegmentRevision>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_segment_revisions(&recording_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_model_path(state: State<AppState>) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    Ok(model_path(&db, &state.data_dir)?.to_string_lossy().to_string())
+}
+
+// ========== Recording List Commands ==========
+
+#[tauri::command]
+fn get_recordings(state: State<AppState>, activity: Option<String>) -> Result<Vec<Recording>, String> {
+    let recordings = match state.recordings_cache.get() {
+        Some(recordings) => recordings,
+        None => {
+            let db = state.db.lock().map_err(|e| e.to_string())?;
+            state
+                .recordings_cache
+                .load(|| db.get_all_recordings_including_confidential())
+                .map_err(|e| e.to_string())?
+        }
+    };
+    Ok(match activity {
+        Some(tag) => recordings.into_iter().filter(|r| r.tags.contains(&tag)).collect(),
+        None => recordings,
+    })
+}
+
+/// Per-channel levels of a recording made on a multichannel microphone, for
+/// telling speakers apart by direction
+#[tauri::command]
+fn get_channel_activity(state: State<AppState>, recording_id: String) -> Result<Option<ChannelActivity>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_channel_activity(&recording_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Bookmarks flagged while a recording was made, in playback order
+#[tauri::command]
+fn get_markers(state: State<AppState>, recording_id: String) -> Result<Vec<Marker>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_markers(&recording_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_marker(state: State<AppState>, id: i64) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.delete_marker(id).map_err(|e| e.to_string())
+}
+
+/// A page of recordings, newest first; pass the last `sequence` seen as
+/// `before_sequence` for the next one
+#[tauri::command]
+fn get_recordings_page(
+    state: State<AppState>,
+    before_sequence: Option<i64>,
+    limit: u32,
+) -> Result<Vec<Recording>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_recordings_page_including_confidential(before_sequence, limit)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_recording(state: State<AppState>, recording_id: String) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    remove_recording(&db, &recording_id)
+}
+
+/// Delete a recording along with its audio files
+fn remove_recording(db: &Database, recording_id: &str) -> Result<(), String> {
+    // Get the recording to delete the audio file
+    if let Some(recording) = db.get_recording(recording_id).map_err(|e| e.to_string())? {
+        for path in recording.audio_files() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    // Correction clips go with it
+    let revisions = db.get_segment_revisions(recording_id).map_err(|e| e.to_string())?;
+    for path in revisions.iter().filter_map(|r| r.correction_audio_path.as_ref()) {
+        let _ = std::fs::remove_file(path);
+    }
+
+    db.delete_recording(recording_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Set or clear the date after which a recording's audio is deleted
+#[tauri::command]
+fn set_recording_expiry(
+    state: State<AppState>,
+    recording_id: String,
+    expires_at: Option<String>,
+) -> Result<(), String> {
+    let expires_at = match expires_at {
+        Some(ts) => Some(
+            chrono::DateTime::parse_from_rfc3339(&ts)
+                .map_err(|e| format!("Invalid expiry date: {}", e))?
+                .with_timezone(&chrono::Utc)
+                .to_rfc3339(),
+        ),
+        None => None,
+    };
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_expiry(&recording_id, expires_at.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Recordings whose audio will be purged within the next `days` days
+#[tauri::command]
+fn get_upcoming_expirations(state: State<AppState>, days: u32) -> Result<Vec<Recording>, String> {
+    let until = (chrono::Utc::now() + chrono::Duration::days(days as i64)).to_rfc3339();
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_expiring_recordings(&until)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn purge_expired_recordings(state: State<AppState>) -> Result<PurgeResult, String> {
+    run_job(
+        &state.db,
+        JobKind::Maintenance,
+        None,
+        || purge_and_notify(&state),
+        |result| {
+            let summary = format!(
+                "Purged {} recording(s), notified server of {}",
+                result.purged_count, result.notified_count
+            );
+            if result.errors.is_empty() {
+                Ok(Some(summary))
+            } else {
+                Err(format!("{}; {}", summary, result.errors.join("; ")))
+            }
+        },
+    )
+}
+
+fn purge_and_notify(state: &AppState) -> Result<PurgeResult, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let purged_count = purge_expired_audio(&db)? + purge_guest_recordings(&db)?;
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let local_only = is_local_only(&db)?;
+    drop(db);
+
+    let (notified_count, errors) = if local_only {
+        (0, Vec::new())
+    } else {
+        send_deletion_notices(state, &SyncClient::new(&server_url))?
+    };
+
+    Ok(PurgeResult {
+        purged_count,
+        notified_count,
+        errors,
+    })
+}
+
+/// A recording's transcript as timed segments, in order, each with its
+/// speaker when known
+#[tauri::command]
+fn get_segments(state: State<AppState>, recording_id: String) -> Result<Vec<TranscriptSegment>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_segments(&recording_id).map_err(|e| e.to_string())
+}
+
+/// The segments as heard, before a mask stage starred anything out; None
+/// when nothing was masked. Like the unmasked transcript, only on this device.
+#[tauri::command]
+fn get_unmasked_segments(state: State<AppState>, recording_id: String) -> Result<Option<Vec<TranscriptSegment>>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_unmasked_segments(&recording_id).map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+struct DoubtfulSegment {
+    segment_index: usize,
+    segment: TranscriptSegment,
+    doubts: Vec<Doubt>,
+}
+
+/// The segments of a recording worth a second listen: less confident than
+/// `threshold` (the `confidence_threshold` setting when None), scored low
+/// overall by the model, or likely not speech. Segments already corrected
+/// by hand are left out.
+#[tauri::command]
+fn get_low_confidence_segments(
+    state: State<AppState>,
+    recording_id: String,
+    threshold: Option<f64>,
+) -> Result<Vec<DoubtfulSegment>, String> {
+    if threshold.is_some_and(|t| !(0.0..=1.0).contains(&t)) {
+        return Err("Confidence threshold must be between 0 and 1".to_string());
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let threshold = match threshold {
+        Some(threshold) => threshold,
+        None => confidence_threshold_setting(&db)?,
+    };
+    let corrected: Vec<usize> = db
+        .get_segment_revisions(&recording_id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|r| r.segment_index)
+        .collect();
+    let segments = db.get_segments(&recording_id).map_err(|e| e.to_string())?;
+    Ok(segments
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !corrected.contains(index))
+        .filter_map(|(segment_index, segment)| {
+            let doubts = segment.doubts(threshold);
+            (!doubts.is_empty()).then_some(DoubtfulSegment { segment_index, segment, doubts })
+        })
+        .collect())
+}
+
+/// Correct who said a segment, or clear it with `None`
+#[tauri::command]
+fn set_segment_speaker(
+    state: State<AppState>,
+    recording_id: String,
+    segment_index: usize,
+    speaker: Option<String>,
+) -> Result<(), String> {
+    let speaker = speaker.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    if !db
+        .set_segment_speaker(&recording_id, segment_index, speaker.as_deref())
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("Recording {} has no segment {}", recording_id, segment_index));
+    }
+    Ok(())
+}
+
+/// Passage attached to every new recording until cleared with `None`.
+/// Refused while an assignment is in progress, since it sets the passage.
+#[tauri::command]
+fn set_active_passage(state: State<AppState>, passage: Option<String>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    if let Some(assignment) = db.get_active_assignment().map_err(|e| e.to_string())? {
+        return Err(format!("The passage is set by assignment \"{}\" until it's finished", assignment.title));
+    }
+    db.set_setting("active_passage", passage.as_deref().unwrap_or(""))
+        .map_err(|e| e.to_string())
+}
+
+/// Attach the reference passage a saved recording was read from;
+/// it biases decoding the next time the recording is transcribed.
+/// Recordings made for an assignment keep the assignment's passage.
+#[tauri::command]
+fn attach_passage(state: State<AppState>, recording_id: String, passage: Option<String>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    if let Some(assignment_id) = &recording.assignment_id {
+        let title = db
+            .get_assignment(assignment_id)
+            .map_err(|e| e.to_string())?
+            .map_or_else(|| assignment_id.clone(), |a| a.title);
+        return Err(format!("This recording was made for assignment \"{}\", so its passage can't be changed", title));
+    }
+    db.set_reference_passage(&recording_id, passage.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Flag a recording as confidential so it stays on this device only
+#[tauri::command]
+fn set_recording_confidential(
+    state: State<AppState>,
+    recording_id: String,
+    confidential: bool,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let guest = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .is_some_and(|r| r.guest);
+    if guest && !confidential {
+        return Err("Guest recordings stay on this device".to_string());
+    }
+    db.set_confidential(&recording_id, confidential)
+        .map_err(|e| e.to_string())
+}
+
+/// Edit tags, notes, review status or speaker labels. Changed fields are
+/// flagged dirty and pushed to the server on the next sync.
+#[tauri::command]
+fn update_recording_metadata(
+    state: State<AppState>,
+    recording_id: String,
+    update: MetadataUpdate,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.update_metadata(&recording_id, &update)
+        .map_err(|e| e.to_string())
+}
+
+/// Name a speaker on a recording, such as "SPEAKER_00" as "Ms. Rivera", or
+/// with `None` unname them. With `remember`, new recordings of the same
+/// student with this teacher get the name too. Diarized keys follow the
+/// order people first speak in, so that suits sessions that always start
+/// the same way.
+#[tauri::command]
+fn rename_speaker(
+    state: State<AppState>,
+    recording_id: String,
+    speaker: String,
+    name: Option<String>,
+    remember: bool,
+) -> Result<(), String> {
+    let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    db.label_speaker(&recording_id, &speaker, name.as_deref())
+        .map_err(|e| e.to_string())?;
+    if remember {
+        let teacher_name = db
+            .get_setting("teacher_name")
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default();
+        db.remember_speaker_name(&recording.student_id, &teacher_name, &speaker, name.as_deref())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// ========== Playback Commands ==========
+
+/// Emit `playback-position` events every 100 ms until the session ends
+fn spawn_position_events(app: AppHandle, recording_id: String, monitor: PlaybackMonitor, timing: TimingMap) {
+    std::thread::spawn(move || {
+        while let Some((position_seconds, paused)) = monitor.poll() {
+            if !paused {
+                let (segment_index, word_index) = timing.locate(position_seconds);
+                let _ = app.emit("playback-position", PlaybackPosition {
+                    recording_id: recording_id.clone(),
+                    position_seconds,
+                    segment_index,
+                    word_index,
+                });
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    });
+}
+
+/// Segment and word timings used to highlight the transcript during playback
+#[tauri::command]
+fn get_timing_map(state: State<AppState>, recording_id: String) -> Result<TimingMap, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let segments = db.get_segments(&recording_id).map_err(|e| e.to_string())?;
+    Ok(TimingMap::from_segments(&segments))
+}
+
+/// Min/max peaks in `buckets` slices for drawing a recording's waveform.
+/// Cached until the audio file changes, and still served once expired
+/// audio has been purged.
+#[tauri::command]
+fn get_waveform(state: State<AppState>, recording_id: String, buckets: usize) -> Result<Waveform, String> {
+    let buckets = buckets.clamp(1, waveform::MAX_BUCKETS);
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    let fingerprint = if recording.audio_purged {
+        None
+    } else {
+        waveform::audio_fingerprint(Path::new(&recording.audio_path))
+    };
+    if let Some(cached) = db
+        .get_waveform(&recording_id, buckets, fingerprint.as_deref())
+        .map_err(|e| e.to_string())?
+    {
+        return Ok(cached);
+    }
+    drop(db);
+
+    if recording.audio_purged {
+        return Err("Audio for this recording has expired and was deleted".to_string());
+    }
+
+    let mut reader = AudioReader::open(&PathBuf::from(&recording.audio_path)).map_err(|e| e.to_string())?;
+    let waveform = Waveform::from_reader(&mut reader, buckets).map_err(|e| e.to_string())?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.save_waveform(&recording_id, buckets, fingerprint.as_deref(), &waveform)
+        .map_err(|e| e.to_string())?;
+    Ok(waveform)
+}
+
+/// Play a saved recording. `speed` (0.5–2.0) time-stretches without
+/// changing pitch.
+#[tauri::command]
+fn play_recording(
+    state: State<AppState>,
+    app: AppHandle,
+    recording_id: String,
+    speed: Option<f32>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    let segments = db.get_segments(&recording_id).map_err(|e| e.to_string())?;
+    drop(db);
+
+    if recording.audio_purged {
+        return Err("Audio for this recording has expired and was deleted".to_string());
+    }
+
+    let (samples, sample_rate) =
+        audio::read_audio(&PathBuf::from(&recording.audio_path)).map_err(|e| e.to_string())?;
+
+    let mut player = state.player.lock().map_err(|e| e.to_string())?;
+    player
+        .play(samples, sample_rate, speed.unwrap_or(1.0))
+        .map_err(|e| e.to_string())?;
+
+    spawn_position_events(app, recording_id, player.monitor(), TimingMap::from_segments(&segments));
+    Ok(())
+}
+
+/// Loop one transcript segment `times` times, or until stopped when omitted
+#[tauri::command]
+fn loop_segment(
+    state: State<AppState>,
+    app: AppHandle,
+    recording_id: String,
+    segment_index: usize,
+    times: Option<u32>,
+    speed: Option<f32>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    let segments = db.get_segments(&recording_id).map_err(|e| e.to_string())?;
+    drop(db);
+
+    let segment = segments
+        .get(segment_index)
+        .ok_or_else(|| format!("Segment {} not found", segment_index))?;
+
+    if recording.audio_purged {
+        return Err("Audio for this recording has expired and was deleted".to_string());
+    }
+
+    let (samples, sample_rate) =
+        audio::read_audio(&PathBuf::from(&recording.audio_path)).map_err(|e| e.to_string())?;
+
+    let mut player = state.player.lock().map_err(|e| e.to_string())?;
+    player
+        .play_loop(
+            samples,
+            sample_rate,
+            speed.unwrap_or(1.0),
+            segment.start,
+            segment.end,
+            times,
+        )
+        .map_err(|e| e.to_string())?;
+
+    spawn_position_events(app, recording_id, player.monitor(), TimingMap::from_segments(&segments));
+    Ok(())
+}
+
+/// Play one timed word of a segment once
+#[tauri::command]
+fn play_word(
+    state: State<AppState>,
+    app: AppHandle,
+    recording_id: String,
+    segment_index: usize,
+    word_index: usize,
+    speed: Option<f32>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    let segments = db.get_segments(&recording_id).map_err(|e| e.to_string())?;
+    drop(db);
+
+    let word = segments
+        .get(segment_index)
+        .and_then(|s| s.words.get(word_index))
+        .ok_or_else(|| format!("Word {} of segment {} not found", word_index, segment_index))?;
+
+    if recording.audio_purged {
+        return Err("Audio for this recording has expired and was deleted".to_string());
+    }
+
+    let (samples, sample_rate) =
+        audio::read_audio(&PathBuf::from(&recording.audio_path)).map_err(|e| e.to_string())?;
+
+    let mut player = state.player.lock().map_err(|e| e.to_string())?;
+    player
+        .play_loop(samples, sample_rate, speed.unwrap_or(1.0), word.start, word.end, Some(1))
+        .map_err(|e| e.to_string())?;
+
+    spawn_position_events(app, recording_id, player.monitor(), TimingMap::from_segments(&segments));
+    Ok(())
+}
+
+#[tauri::command]
+fn pause_playback(state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().map_err(|e| e.to_string())?;
+    player.pause().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn resume_playback(state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().map_err(|e| e.to_string())?;
+    player.resume().map_err(|e| e.to_string())
+}
+
+/// Jump to `seconds` into the recording that's playing
+#[tauri::command]
+fn seek_playback(state: State<AppState>, seconds: f64) -> Result<(), String> {
+    let player = state.player.lock().map_err(|e| e.to_string())?;
+    player.seek(seconds).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn stop_playback(state: State<AppState>) -> Result<(), String> {
+    let mut player = state.player.lock().map_err(|e| e.to_string())?;
+    player.stop();
+    Ok(())
+}
+
+#[tauri::command]
+fn set_playback_speed(state: State<AppState>, speed: f32) -> Result<(), String> {
+    let player = state.player.lock().map_err(|e| e.to_string())?;
+    player.set_speed(speed).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_playback_status(state: State<AppState>) -> Result<PlaybackStatus, String> {
+    let player = state.player.lock().map_err(|e| e.to_string())?;
+    Ok(PlaybackStatus {
+        playing: player.is_playing(),
+        position_seconds: player.position_seconds(),
+    })
+}
+
+// ========== Sync Commands ==========
+
+/// Fetch org and classroom settings now rather than waiting for the next sync
+#[tauri::command]
+fn pull_classroom_config(state: State<AppState>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let local_only = is_local_only(&db)?;
+    drop(db);
+
+    if local_only {
+        return Err("Config pull is disabled in local-only mode".to_string());
+    }
+    pull_config(&state, &SyncClient::new(&server_url))
+}
+
+#[tauri::command]
+fn check_server_connection(state: State<AppState>) -> Result<bool, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let local_only = is_local_only(&db)?;
+    drop(db);
+
+    if local_only {
+        return Ok(false);
+    }
+
+    let client = SyncClient::new(&server_url);
+    Ok(client.check_connection())
+}
+
+/// A client for `server_url` adapted to what the server supports. If the
+/// handshake fails it falls back to the legacy protocol, which every
+/// server takes.
+fn sync_client(server_url: &str) -> SyncClient {
+    let mut client = SyncClient::new(server_url);
+    if let Err(e) = client.negotiate() {
+        eprintln!("Capability negotiation with {} failed: {}", server_url, e);
+    }
+    client
+}
+
+/// Segments, scored metrics and, with `sync_speaker_stats` on, speaker
+/// statistics to send with a recording's transcript
+fn transcript_detail(db: &Database, recording: &Recording) -> Result<TranscriptDetail, String> {
+    let speaker_stats = if sync_speaker_stats_setting(db)? {
+        let stats = db.get_speaker_stats(&recording.id).map_err(|e| e.to_string())?;
+        speakers::labelled(stats, &recording.speaker_labels)
+    } else {
+        Vec::new()
+    };
+    Ok(TranscriptDetail {
+        segments: db.get_segments(&recording.id).map_err(|e| e.to_string())?,
+        metrics: db
+            .get_assessment(&recording.id)
+            .map_err(|e| e.to_string())?
+            .map(|a| a.scored_metrics().clone()),
+        speaker_stats,
+    })
+}
+
+/// What the configured server supports, asked afresh
+#[tauri::command]
+fn get_server_capabilities(state: State<AppState>) -> Result<ServerCapabilities, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    if is_local_only(&db)? {
+        return Err("Syncing is disabled in local-only mode".to_string());
+    }
+    drop(db);
+
+    let mut client = SyncClient::new(&server_url);
+    client.negotiate().cloned().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn sync_transcripts(state: State<AppState>) -> Result<SyncResult, String> {
+    run_job(
+        &state.db,
+        JobKind::Sync,
+        None,
+        || sync_all(&state),
+        |result| {
+            let summary = format!(
+                "{} synced, {} metadata update(s), {} audio upload(s), {} failed",
+                result.synced_count, result.metadata_synced_count, result.audio_uploaded_count, result.failed_count
+            );
+            if result.failed_count == 0 {
+                Ok(Some(summary))
+            } else {
+                Err(format!("{}: {}", summary, result.errors.join("; ")))
+            }
+        },
+    )
+}
+
+fn sync_all(state: &AppState) -> Result<SyncResult, String> {
+    state.startup.require(startup::SYNC)?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+
+    if is_local_only(&db)? {
+        return Ok(SyncResult {
+            synced_count: 0,
+            failed_count: 0,
+            metadata_synced_count: 0,
+            audio_uploaded_count: 0,
+            errors: Vec::new(),
+        });
+    }
+
+    let keep_days = audio_keep_days(&db)?;
+    let unsynced = db
+        .get_unsynced_recordings()
+        .map_err(|e| e.to_string())?;
+    telemetry::UNSYNCED_RECORDINGS.set(unsynced.len() as f64);
+    let device_id = db.device_id().map_err(|e| e.to_string())?;
+    drop(db);
+
+    let client = sync_client(&server_url);
+
+    let mut synced_count = 0;
+    let mut failed_count = 0;
+    let mut errors = Vec::new();
+
+    if let Err(e) = pull_config(state, &client) {
+        errors.push(format!("Config pull: {}", e));
+    }
+
+    // Batched where the server takes it, so a backlog goes up in a few requests
+    let batch_size = if client.capabilities().batch { client.capabilities().batch_size() } else { 1 };
+    for chunk in unsynced.chunks(batch_size) {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        let batch = chunk
+            .iter()
+            .map(|recording| Ok((recording, transcript_detail(&db, recording)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        drop(db);
+        let outcomes = match batch.as_slice() {
+            [(recording, detail)] if batch_size == 1 => vec![client.submit_transcript(recording, &device_id, detail)],
+            _ => match client.submit_batch(&batch, &device_id) {
+                Ok(outcomes) => outcomes,
+                Err(e) => batch
+                    .iter()
+                    .map(|_| {
+                        Err(match &e {
+                            SyncError::Rejected(reason) => SyncError::Rejected(reason.clone()),
+                            e => SyncError::ServerError(e.to_string()),
+                        })
+                    })
+                    .collect(),
+            },
+        };
+
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        for ((recording, _), outcome) in batch.iter().zip(outcomes) {
+            match outcome {
+                Ok(_) => {
+                    db.mark_synced(&recording.id)
+                        .map_err(|e| e.to_string())?;
+                    synced_count += 1;
+                }
+                Err(e) => {
+                    telemetry::SYNC_FAILURES.increment();
+                    db.record_sync_failure(&recording.id, &e.to_string(), e.is_rejection())
+                        .map_err(|e| e.to_string())?;
+                    failed_count += 1;
+                    errors.push(format!("Recording {}: {}", recording.id, e));
+                }
+            }
+        }
+    }
+
+    let (_, notice_errors) = send_deletion_notices(state, &client)?;
+    failed_count += notice_errors.len();
+    errors.extend(notice_errors);
+
+    let report_errors = report_assignments(state, &client, &device_id)?;
+    failed_count += report_errors.len();
+    errors.extend(report_errors);
+
+    // Push metadata edits for recordings the server already has
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let dirty = db.get_dirty_recordings().map_err(|e| e.to_string())?;
+    drop(db);
+
+    let mut metadata_synced_count = 0;
+    for recording in &dirty {
+        match client.patch_metadata(recording) {
+            Ok(fields) => {
+                let db = state.db.lock().map_err(|e| e.to_string())?;
+                db.clear_dirty(&recording.id, fields)
+                    .map_err(|e| e.to_string())?;
+                metadata_synced_count += 1;
+            }
+            Err(e) => {
+                failed_count += 1;
+                errors.push(format!("Recording {} metadata: {}", recording.id, e));
+            }
+        }
+    }
+
+    // Under a deletion policy, audio goes up too and is only removed locally
+    // once the server has confirmed an identical copy
+    let mut audio_uploaded_count = 0;
+    if let Some(days) = keep_days {
+        if client.capabilities().audio_upload {
+            let (uploaded, upload_errors) = upload_pending_audio(state, &client)?;
+            audio_uploaded_count = uploaded;
+            failed_count += upload_errors.len();
+            errors.extend(upload_errors);
+        } else {
+            errors.push("The server doesn't take audio uploads, so local audio is kept".to_string());
+        }
+
+        compress_uploaded_audio(state)?;
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        offload_uploaded_audio(&db, days)?;
+    }
+
+    Ok(SyncResult {
+        synced_count,
+        failed_count,
+        metadata_synced_count,
+        audio_uploaded_count,
+        errors,
+    })
+}
+
+#[tauri::command]
+fn get_unsynced_count(state: State<AppState>) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    if is_local_only(&db)? {
+        return Ok(0);
+    }
+    let unsynced = db
+        .get_unsynced_recordings()
+        .map_err(|e| e.to_string())?;
+    Ok(unsynced.len())
+}
+
+/// Counts of recordings by the step they are waiting on, for the badge and admin alerts
+#[tauri::command]
+fn get_backlog_summary(state: State<AppState>) -> Result<BacklogSummary, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut counts = db.get_backlog_counts().map_err(|e| e.to_string())?;
+    if is_local_only(&db)? {
+        counts.awaiting_sync = 0;
+        counts.failed_permanently = 0;
+    }
+
+    let oldest_age_seconds = counts
+        .oldest_recorded_at
+        .as_deref()
+        .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+        .map(|ts| (chrono::Utc::now() - ts.with_timezone(&chrono::Utc)).num_seconds());
+
+    let threshold = |key| -> Result<Option<i64>, String> {
+        Ok(settings::resolve(&db, key)
+            .map_err(|e| e.to_string())?
+            .and_then(|v| v.parse::<i64>().ok()))
+    };
+    // Unsent student audio and transcripts are what admins need to hear about
+    let unsent = counts.awaiting_transcription + counts.awaiting_sync + counts.failed_permanently;
+    let over_count = threshold("backlog_alert_count")?.is_some_and(|max| unsent as i64 > max);
+    let over_age = threshold("backlog_alert_hours")?
+        .zip(oldest_age_seconds)
+        .is_some_and(|(hours, age)| age > hours * 3600);
+
+    Ok(BacklogSummary {
+        awaiting_transcription: counts.awaiting_transcription,
+        awaiting_review: counts.awaiting_review,
+        awaiting_sync: counts.awaiting_sync,
+        failed_permanently: counts.failed_permanently,
+        oldest_age_seconds,
+        alert: over_count || over_age || counts.failed_permanently > 0,
+    })
+}
+
+/// Retry recordings that ran out of sync attempts on the next sync
+#[tauri::command]
+fn retry_failed_syncs(state: State<AppState>) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.reset_sync_failures().map_err(|e| e.to_string())
+}
+
+/// Whether each speaker's words and speaking time go up with transcripts,
+/// for talk-ratio dashboards; None reverts to the pushed setting or off.
+/// Servers that don't take them are sent none either way.
+#[tauri::command]
+fn set_sync_speaker_stats(state: State<AppState>, enabled: Option<bool>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match enabled {
+        Some(enabled) => db
+            .set_setting("sync_speaker_stats", if enabled { "true" } else { "false" })
+            .map_err(|e| e.to_string()),
+        None => db
+            .delete_setting_as("sync_speaker_stats", "user")
+            .map_err(|e| e.to_string()),
+    }
+}
+
+// ========== Activity Commands ==========
+
+/// Recent pipeline jobs across processing, transcription, model loads, syncs
+/// and maintenance, newest first
+#[tauri::command]
+fn get_job_history(
+    state: State<AppState>,
+    limit: Option<usize>,
+    filter: Option<JobFilter>,
+) -> Result<Vec<JobEntry>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_job_history(limit.unwrap_or(100), &filter.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Recordings waiting to be processed, being processed, or left failed
+#[tauri::command]
+fn get_job_queue(state: State<AppState>) -> Result<Vec<QueuedJob>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_jobs().map_err(|e| e.to_string())
+}
+
+/// Queue a failed job again, with a fresh set of attempts
+#[tauri::command]
+fn retry_job(state: State<AppState>, job_id: i64) -> Result<(), String> {
+    let queue = state.job_queue.lock_or_recover();
+    let retried = state
+        .db
+        .lock()
+        .map_err(|e| e.to_string())?
+        .retry_job(job_id)
+        .map_err(|e| e.to_string())?;
+    drop(queue);
+    if !retried {
+        return Err(format!("No failed job {}", job_id));
+    }
+    state.job_ready.notify_all();
+    Ok(())
+}
+
+/// Process a queued job as soon as the worker is free, ahead of the rest of
+/// the queue and whether or not it's quiet hours
+#[tauri::command]
+fn process_job_now(state: State<AppState>, job_id: i64) -> Result<(), String> {
+    let queue = state.job_queue.lock_or_recover();
+    let rushed = state
+        .db
+        .lock()
+        .map_err(|e| e.to_string())?
+        .rush_job(job_id)
+        .map_err(|e| e.to_string())?;
+    drop(queue);
+    if !rushed {
+        return Err(format!("No queued job {}", job_id));
+    }
+    state.job_ready.notify_all();
+    Ok(())
+}
+
+#[tauri::command]
+fn get_quiet_hours(state: State<AppState>) -> Result<Vec<BlackoutWindow>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match settings::resolve(&db, "quiet_hours").map_err(|e| e.to_string())? {
+        Some(json) => schedule::parse_windows(&json).map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Hold recordings' processing for these windows, such as lunch and after
+/// school, so stations stay responsive while teaching. Recordings are still
+/// saved and queued straight away. None or no windows processes them as
+/// they're queued again, unless quiet hours are pushed.
+#[tauri::command]
+fn set_quiet_hours(state: State<AppState>, windows: Option<Vec<BlackoutWindow>>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match windows {
+        Some(windows) => {
+            let json = serde_json::to_string(&windows).map_err(|e| e.to_string())?;
+            schedule::parse_windows(&json).map_err(|e| e.to_string())?;
+            db.set_setting("quiet_hours", &json)
+        }
+        None => db.delete_setting_as("quiet_hours", "user"),
+    }
+    .map_err(|e| e.to_string())?;
+    drop(db);
+    state.job_ready.notify_all();
+    Ok(())
+}
+
+/// Queue every recording that hasn't been transcribed yet, such as ones
+/// made before a model was downloaded, retrying any that failed. They're
+/// transcribed one by one in the background, with `batch-progress` sent as
+/// each is done. Returns the batch as queued.
+#[tauri::command]
+fn transcribe_all_pending(state: State<AppState>, app: AppHandle) -> Result<BatchProgress, String> {
+    transcribe_pending(&state, &app)
+}
+
+/// The batch `transcribe_all_pending` queued, while it's still going
+#[tauri::command]
+fn get_batch_progress(state: State<AppState>) -> Option<BatchProgress> {
+    state.batch.lock_or_recover().clone()
+}
+
+/// Whether to queue recordings still waiting on a transcript each time the
+/// app starts; None reverts to the pushed setting or off
+#[tauri::command]
+fn set_transcribe_pending_on_start(state: State<AppState>, enabled: Option<bool>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match enabled {
+        Some(enabled) => db
+            .set_setting("transcribe_pending_on_start", if enabled { "true" } else { "false" })
+            .map_err(|e| e.to_string()),
+        None => db
+            .delete_setting_as("transcribe_pending_on_start", "user")
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Process-wide counters and timings since the app started
+#[tauri::command]
+fn get_metrics() -> Vec<telemetry::Metric> {
+    telemetry::snapshot()
+}
+
+// ========== Assessment Commands ==========
+
+/// Validate a rubric JSON or TOML file and make it the active rubric. It's
+/// kept as JSON whichever it was written in.
+#[tauri::command]
+fn load_rubric(state: State<AppState>, path: String) -> Result<Rubric, String> {
+    let rubric = Rubric::load(Path::new(&path)).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&rubric).map_err(|e| e.to_string())?;
+    std::fs::write(state.data_dir.join("rubric.json"), json).map_err(|e| e.to_string())?;
+    Ok(rubric)
+}
+
+#[tauri::command]
+fn get_rubric(state: State<AppState>) -> Result<Option<Rubric>, String> {
+    let path = state.data_dir.join("rubric.json");
+    if !path.exists() {
+        return Ok(None);
+    }
+    Rubric::load(&path).map(Some).map_err(|e| e.to_string())
+}
+
+/// Recompute metrics and level, e.g. after the rubric or passage changed
+#[tauri::command]
+fn score_recording(state: State<AppState>, recording_id: String) -> Result<Option<Assessment>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    score_assessment(&db, &state.data_dir, &recording_id)
+}
+
+#[tauri::command]
+fn get_assessment(state: State<AppState>, recording_id: String) -> Result<Option<Assessment>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_assessment(&recording_id).map_err(|e| e.to_string())
+}
+
+/// Discount words below `threshold` confidence, and likely hallucinations,
+/// in adjusted metrics; `None` scores raw metrics only. Takes effect the
+/// next time a recording is scored.
+#[tauri::command]
+fn set_confidence_weighting(
+    state: State<AppState>,
+    mode: Option<ConfidenceMode>,
+    threshold: Option<f64>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mode = match mode {
+        Some(ConfidenceMode::Exclude) => "exclude",
+        Some(ConfidenceMode::Weight) => "weight",
+        None => "off",
+    };
+    db.set_setting("confidence_weighting", mode)
+        .map_err(|e| e.to_string())?;
+    if let Some(threshold) = threshold {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err("Confidence threshold must be between 0 and 1".to_string());
+        }
+        db.set_setting("confidence_threshold", &threshold.to_string())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Grade-level norms assessments are compared against: the `grade_norms`
+/// setting when one is set or pushed with the config, otherwise the table
+/// that ships with the app
+fn norms_table(db: &Database) -> Result<NormsTable, String> {
+    let json = settings::resolve(db, "grade_norms").map_err(|e| e.to_string())?;
+    NormsTable::from_json(json.as_deref().unwrap_or(norms::DEFAULT_NORMS)).map_err(|e| e.to_string())
+}
+
+/// How a recording's assessment compares with the classroom and with
+/// grade-level norms, worked out on this device. `grade` defaults to the
+/// `grade_level` setting. `None` if the recording hasn't been scored.
+#[tauri::command]
+fn get_cohort_comparison(
+    state: State<AppState>,
+    recording_id: String,
+    grade: Option<String>,
+) -> Result<Option<CohortComparison>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let Some(assessment) = db.get_assessment(&recording_id).map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    let cohort: Vec<_> = db
+        .get_latest_assessments()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|a| a.scored_metrics().clone())
+        .collect();
+    let grade = match grade {
+        Some(grade) => Some(grade),
+        None => settings::resolve(&db, "grade_level").map_err(|e| e.to_string())?,
+    };
+    let table = norms_table(&db)?;
+    drop(db);
+
+    let metrics = assessment.scored_metrics();
+    Ok(Some(CohortComparison {
+        recording_id,
+        classroom: norms::compare_to_classroom(metrics, &cohort),
+        norms: grade
+            .filter(|g| !g.trim().is_empty())
+            .and_then(|g| norms::compare_to_norms(&table, metrics, g.trim(), &recording.recorded_at)),
+    }))
+}
+
+#[tauri::command]
+fn get_norms_table(state: State<AppState>) -> Result<NormsTable, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    norms_table(&db)
+}
+
+/// Record `table` as the next version of its name and make it the one
+/// assessments are compared against
+fn install_norms_table(db: &Database, mut table: NormsTable) -> Result<NormsTable, String> {
+    let version = db.next_norms_version(&table.name).map_err(|e| e.to_string())?;
+    table.version = Some(version);
+    let json = serde_json::to_string(&table).map_err(|e| e.to_string())?;
+    db.save_norms_table(&table.name, version, &table.metric, &json)
+        .map_err(|e| e.to_string())?;
+    db.set_setting("grade_norms", &json).map_err(|e| e.to_string())?;
+    Ok(table)
+}
+
+/// Replace the grade-level norms with a JSON table, or `None` to go back to
+/// the pushed or built-in one. A table is saved as a new version of its name.
+#[tauri::command]
+fn set_norms_table(state: State<AppState>, json: Option<String>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match json {
+        Some(json) => {
+            let table = NormsTable::from_json(&json).map_err(|e| e.to_string())?;
+            install_norms_table(&db, table).map(|_| ())
+        }
+        None => db.delete_setting_as("grade_norms", "user").map_err(|e| e.to_string()),
+    }
+}
+
+/// Import grade-level norms for `metric` from a CSV file and use them; see
+/// `NormsTable::from_csv` for the layout. Importing under a name already
+/// used adds a version rather than replacing the earlier one.
+#[tauri::command]
+fn import_norms_csv(state: State<AppState>, path: String, name: String, metric: String) -> Result<NormsTable, String> {
+    if name.trim().is_empty() {
+        return Err("A norms table needs a name".to_string());
+    }
+    let csv = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let table = NormsTable::from_csv(name.trim(), &metric, &csv).map_err(|e| e.to_string())?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    install_norms_table(&db, table)
+}
+
+/// Every norms table imported on this device
+#[tauri::command]
+fn get_norms_versions(state: State<AppState>) -> Result<Vec<NormsVersion>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_norms_versions().map_err(|e| e.to_string())
+}
+
+/// Go back to an earlier import of a norms table
+#[tauri::command]
+fn use_norms_version(state: State<AppState>, name: String, version: u32) -> Result<NormsTable, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let json = db
+        .get_norms_table_json(&name, version)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No version {} of {}", version, name))?;
+    let table = NormsTable::from_json(&json).map_err(|e| e.to_string())?;
+    db.set_setting("grade_norms", &json).map_err(|e| e.to_string())?;
+    Ok(table)
+}
+
+/// Grade the classroom's students are compared against norms for, such as "3"
+#[tauri::command]
+fn set_grade_level(state: State<AppState>, grade: Option<String>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match grade.map(|g| g.trim().to_string()).filter(|g| !g.is_empty()) {
+        Some(grade) => db.set_setting("grade_level", &grade).map_err(|e| e.to_string()),
+        None => db.delete_setting_as("grade_level", "user").map_err(|e| e.to_string()),
+    }
+}
+
+/// Enroll the teacher's voice from a recording of them alone, or forget it
+/// with `None`. Recordings in which only that voice is heard are flagged
+/// teacher-only and left unscored.
+#[tauri::command]
+fn set_teacher_voiceprint(state: State<AppState>, recording_id: Option<String>) -> Result<Option<Voiceprint>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let Some(recording_id) = recording_id else {
+        db.delete_setting_as("teacher_voiceprint", "user")
+            .map_err(|e| e.to_string())?;
+        return Ok(None);
+    };
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    drop(db);
+
+    let audio_path = transcription_input(&recording)?;
+    let audio = audio::read_audio(&audio_path);
+    if recording.audio_format != AudioFormat::Wav {
+        let _ = std::fs::remove_file(&audio_path);
+    }
+    let (samples, sample_rate) = audio.map_err(|e| e.to_string())?;
+    let voiceprint = Voiceprint::from_samples(&samples, sample_rate)
+        .ok_or_else(|| "Too little speech in this recording to learn a voice from".to_string())?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_setting(
+        "teacher_voiceprint",
+        &serde_json::to_string(&voiceprint).map_err(|e| e.to_string())?,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(Some(voiceprint))
+}
+
+/// Longest voice sample taken for enrolling, in seconds
+const MAX_ENROLLMENT_SECONDS: f32 = 30.0;
+
+/// Record a short sample of one voice alone and enroll it: "teacher" for
+/// the teacher, or "student" for `student_id`, this device's student by
+/// default. From then on, diarized speakers sounding like an enrolled voice
+/// are labelled as them in each new transcript. Like the sound check, it
+/// uses its own recorder and keeps no audio.
+#[tauri::command(async)]
+fn enroll_voice(
+    state: State<AppState>,
+    role: String,
+    student_id: Option<String>,
+    seconds: Option<f32>,
+) -> Result<Voiceprint, String> {
+    let student_id = match role.as_str() {
+        speakers::TEACHER => None,
+        speakers::STUDENT => match student_id.filter(|id| !id.is_empty()) {
+            Some(id) => Some(id),
+            None => Some(
+                state
+                    .db
+                    .lock()
+                    .map_err(|e| e.to_string())?
+                    .get_setting("student_id")
+                    .map_err(|e| e.to_string())?
+                    .filter(|id| !id.is_empty())
+                    .ok_or_else(|| "Choose the student whose voice to enroll".to_string())?,
+            ),
+        },
+        other => return Err(format!("Unknown role '{}', expected teacher or student", other)),
     };
+    state.startup.require(startup::RECORDING)?;
+    check_recording_allowed(&*state.db.lock().map_err(|e| e.to_string())?)?;
+    if state.recorder.lock().map_err(|e| e.to_string())?.is_recording() {
+        return Err("A recording is already in progress".to_string());
+    }
+
+    let scratch = std::env::temp_dir().join(format!("classroom-enroll-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&scratch).map_err(|e| e.to_string())?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut recorder = configured_recorder(&db, scratch.join("capture.partial.wav"));
+    drop(db);
+    let sample = recorder.start_recording().map_err(|e| e.to_string()).and_then(|_| {
+        let seconds = seconds.unwrap_or(10.0).clamp(3.0, MAX_ENROLLMENT_SECONDS);
+        std::thread::sleep(std::time::Duration::from_secs_f32(seconds));
+        let capture = recorder.finish_capture().map_err(|e| e.to_string())?;
+        audio::read_audio(&capture).map_err(|e| e.to_string())
+    });
+    let _ = std::fs::remove_dir_all(&scratch);
+    let (samples, sample_rate) = sample?;
+    let voiceprint = Voiceprint::from_samples(&samples, sample_rate)
+        .ok_or_else(|| "Too little speech in the sample to learn a voice from".to_string())?;
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match student_id {
+        Some(student_id) => db.set_student_voiceprint(&student_id, Some(&voiceprint)),
+        None => db.set_setting(
+            "teacher_voiceprint",
+            &serde_json::to_string(&voiceprint).map_err(|e| e.to_string())?,
+        ),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(voiceprint)
+}
+
+/// Forget a student's enrolled voice. The teacher's is forgotten with
+/// `set_teacher_voiceprint`.
+#[tauri::command]
+fn forget_student_voice(state: State<AppState>, student_id: String) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_student_voiceprint(&student_id, None)
+        .map_err(|e| e.to_string())
+}
+
+/// Whether teacher-only recordings are archived as they're flagged, taking
+/// them out of the review queue; None reverts to the pushed setting or off
+#[tauri::command]
+fn set_archive_teacher_only(state: State<AppState>, enabled: Option<bool>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match enabled {
+        Some(enabled) => db
+            .set_setting("archive_teacher_only", if enabled { "true" } else { "false" })
+            .map_err(|e| e.to_string()),
+        None => db
+            .delete_setting_as("archive_teacher_only", "user")
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// How new recordings' students are picked out from the teacher for
+/// scoring; None reverts to the pushed setting or scoring every segment.
+/// Recordings already transcribed keep their pick until re-transcribed.
+#[tauri::command]
+fn set_student_speaker_strategy(state: State<AppState>, strategy: Option<StudentStrategy>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match strategy {
+        Some(strategy) => db
+            .set_setting("student_speaker_strategy", strategy.as_str())
+            .map_err(|e| e.to_string()),
+        None => db
+            .delete_setting_as("student_speaker_strategy", "user")
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Say which speaker on a recording is the student and score their reading
+/// alone, or with `None` score every segment again. Picking one clears a
+/// teacher-only flag.
+#[tauri::command]
+fn set_student_speaker(
+    state: State<AppState>,
+    recording_id: String,
+    speaker: Option<String>,
+) -> Result<Option<Assessment>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    if let Some(speaker) = &speaker {
+        let segments = db.get_segments(&recording_id).map_err(|e| e.to_string())?;
+        if !segments.iter().any(|s| s.speaker.as_ref() == Some(speaker)) {
+            return Err(format!("No one is heard as {} in this recording", speaker));
+        }
+        recording.teacher_only = false;
+    }
+    recording.student_speaker = speaker;
     db.save_recording(&recording).map_err(|e| e.to_string())?;
+    score_assessment(&db, &state.data_dir, &recording_id)
+}
+
+/// Words and speaking time of each speaker in a recording, most talkative
+/// first, named with the recording's speaker labels
+#[tauri::command]
+fn get_speaker_stats(state: State<AppState>, recording_id: String) -> Result<Vec<SpeakerStats>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found".to_string())?;
+    let stats = db.get_speaker_stats(&recording_id).map_err(|e| e.to_string())?;
+    Ok(speakers::labelled(stats, &recording.speaker_labels))
+}
+
+// ========== Export Commands ==========
+
+/// Conventions for numbers and dates in reports, from the `locale` setting
+fn report_locale(db: &Database) -> Result<Locale, String> {
+    Ok(settings::resolve(db, "locale")
+        .map_err(|e| e.to_string())?
+        .map(|tag| Locale::from_tag(&tag))
+        .unwrap_or_default())
+}
+
+/// Render a self-contained HTML report of local recordings into `folder`
+#[tauri::command]
+fn export_dashboard(state: State<AppState>, folder: String) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recordings = db.get_all_recordings().map_err(|e| e.to_string())?;
+    let locale = report_locale(&db)?;
+    drop(db);
+
+    let index = export::export_dashboard(&recordings, &PathBuf::from(folder), &locale)
+        .map_err(|e| e.to_string())?;
+    Ok(index.to_string_lossy().to_string())
+}
+
+/// Copy a recording's audio to `destination`, optionally loudness-normalized
+/// to the EBU R128 target so quiet recordings play back at a usable level.
+#[tauri::command]
+fn export_recording_audio(
+    state: State<AppState>,
+    recording_id: String,
+    destination: String,
+    normalize: bool,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_exportable_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found or confidential".to_string())?;
+    drop(db);
+
+    if recording.audio_purged {
+        return Err("Audio for this recording has expired and was deleted".to_string());
+    }
+
+    let source = PathBuf::from(&recording.audio_path);
+    let destination = PathBuf::from(destination);
+
+    if normalize {
+        audio::normalize_to(&source, &destination, dsp::EBU_R128_TARGET_LUFS).map_err(|e| e.to_string())?;
+    } else {
+        std::fs::copy(&source, &destination).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Export scored assessments as OneRoster 1.1 CSV for district data systems.
+/// `class_sourced_id` defaults to the `class_sourced_id` setting.
+#[tauri::command]
+fn export_oneroster(
+    state: State<AppState>,
+    folder: String,
+    class_sourced_id: Option<String>,
+) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let class_sourced_id = match class_sourced_id {
+        Some(id) => id,
+        None => db
+            .get_setting("class_sourced_id")
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default(),
+    };
+
+    let mut rows = Vec::new();
+    for assessment in db.get_exportable_assessments().map_err(|e| e.to_string())? {
+        if let Some(recording) = db
+            .get_exportable_recording(&assessment.recording_id)
+            .map_err(|e| e.to_string())?
+        {
+            rows.push((assessment, recording));
+        }
+    }
+    let locale = report_locale(&db)?;
+    drop(db);
+
+    export::export_oneroster(&rows, &class_sourced_id, &PathBuf::from(folder), &locale)
+        .map_err(|e| e.to_string())
+}
+
+/// Export each speaker's words and speaking time in every non-confidential
+/// recording as a CSV file in `folder`. Returns the file's path.
+#[tauri::command]
+fn export_speaker_stats(state: State<AppState>, folder: String) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut rows = Vec::new();
+    for recording in db.get_all_recordings().map_err(|e| e.to_string())? {
+        let stats = db.get_speaker_stats(&recording.id).map_err(|e| e.to_string())?;
+        if !stats.is_empty() {
+            let stats = speakers::labelled(stats, &recording.speaker_labels);
+            rows.push((recording, stats));
+        }
+    }
+    drop(db);
+
+    let path = export::export_speaker_stats(&rows, &PathBuf::from(folder)).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// A student's recordings, oldest first, and a feed title. Confidential,
+/// guest and teacher-only recordings are left out.
+fn podcast_recordings(db: &Database, student_id: &str) -> Result<(String, Vec<Recording>), String> {
+    let mut recordings: Vec<Recording> = db
+        .get_all_recordings()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|r| r.student_id == student_id && !r.guest && !r.teacher_only)
+        .collect();
+    recordings.reverse();
+    Ok((student_display_name(db, student_id)?, recordings))
+}
+
+/// This device's student name for their own id, otherwise the id itself
+fn student_display_name(db: &Database, student_id: &str) -> Result<String, String> {
+    Ok(match db.get_setting("student_id").map_err(|e| e.to_string())? {
+        Some(id) if id == student_id => db
+            .get_setting("student_name")
+            .map_err(|e| e.to_string())?
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| student_id.to_string()),
+        _ => student_id.to_string(),
+    })
+}
+
+/// Write a podcast feed of a student's readings, with audio, to `folder`
+#[tauri::command]
+fn export_podcast_feed(state: State<AppState>, student_id: String, folder: String) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let (title, mut recordings) = podcast_recordings(&db, &student_id)?;
+    let locale = report_locale(&db)?;
+    drop(db);
+    recordings.retain(|r| !r.audio_purged);
+
+    let feed = export::export_podcast_feed(&title, &recordings, &PathBuf::from(folder), &locale)
+        .map_err(|e| e.to_string())?;
+    Ok(feed.to_string_lossy().to_string())
+}
+
+/// Write a single-file HTML player for a recording's session, or just the
+/// recording if it wasn't part of one, to `destination`
+#[tauri::command]
+fn export_transcript_player(
+    state: State<AppState>,
+    recording_id: String,
+    destination: String,
+) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_exportable_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found or confidential".to_string())?;
+    let recordings = match recording.session_id {
+        Some(ref session_id) => db
+            .get_session_recordings(session_id)
+            .map_err(|e| e.to_string())?,
+        None => vec![recording],
+    };
+    let mut segments = Vec::with_capacity(recordings.len());
+    for rec in &recordings {
+        segments.push(db.get_segments(&rec.id).map_err(|e| e.to_string())?);
+    }
+    let locale = report_locale(&db)?;
+    drop(db);
+
+    let mut parts = Vec::with_capacity(recordings.len());
+    for (rec, segments) in recordings.iter().zip(segments) {
+        let audio = if rec.audio_purged {
+            None
+        } else {
+            let bytes = audio::flac_bytes(Path::new(&rec.audio_path)).map_err(|e| e.to_string())?;
+            Some((bytes, AudioFormat::Flac.mime_type()))
+        };
+        parts.push(export::PlayerPart { recording: rec, segments, audio });
+    }
+
+    let title = format!(
+        "{}, {}",
+        recordings[0].student_id,
+        locale.timestamp(&recordings[0].recorded_at)
+    );
+    let path = export::export_transcript_player(&title, &parts, &PathBuf::from(destination), &locale)
+        .map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Write one student's transcripts and reading scores from `date_range`
+/// to `destination` as a single HTML file, for a parent's records request.
+/// Confidential, guest and teacher-only recordings are left out, as are
+/// tags, notes and markers, and only the student's own lines are kept where
+/// they've been told apart from others'. Each page is watermarked with
+/// `recipient` and today's date.
+#[tauri::command]
+fn generate_parent_export(
+    state: State<AppState>,
+    student_id: String,
+    date_range: DateRange,
+    recipient: String,
+    destination: String,
+) -> Result<String, String> {
+    let recipient = recipient.trim();
+    if recipient.is_empty() {
+        return Err("Say who the export is for, to watermark it with".to_string());
+    }
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut recordings: Vec<Recording> = db
+        .get_all_recordings()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|r| r.student_id == student_id && !r.guest && !r.teacher_only && date_range.contains(&r.recorded_at))
+        .collect();
+    recordings.reverse();
+    let mut content = Vec::with_capacity(recordings.len());
+    for rec in &recordings {
+        let mut segments = db.get_segments(&rec.id).map_err(|e| e.to_string())?;
+        if let Some(student) = &rec.student_speaker {
+            segments.retain(|s| s.speaker.as_ref() == Some(student));
+        }
+        let assessment = db.get_assessment(&rec.id).map_err(|e| e.to_string())?;
+        content.push((segments, assessment));
+    }
+    let student_name = student_display_name(&db, &student_id)?;
+    let locale = report_locale(&db)?;
+    drop(db);
+
+    if recordings.is_empty() {
+        return Err("No recordings of this student in that range can be exported".to_string());
+    }
+    let records: Vec<export::ParentRecord> = recordings
+        .iter()
+        .zip(content)
+        .map(|(recording, (segments, assessment))| export::ParentRecord { recording, segments, assessment })
+        .collect();
+    let path = export::export_parent_records(
+        &student_name,
+        &records,
+        recipient,
+        &date_range,
+        &PathBuf::from(destination),
+        &locale,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Publish a student's feed through the server and return the share link
+/// for parents. Audio of the feed's recordings not yet on the server is
+/// uploaded first; nothing outside the feed is.
+#[tauri::command]
+fn share_podcast_feed(state: State<AppState>, student_id: String) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    if is_local_only(&db)? {
+        return Err("Sharing is disabled in local-only mode".to_string());
+    }
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let (title, recordings) = podcast_recordings(&db, &student_id)?;
+    let locale = report_locale(&db)?;
+    drop(db);
+
+    // Only what goes in the feed is uploaded, and audio that's gone from
+    // the device can't be unless the server already has it
+    let shared: Vec<Recording> = recordings
+        .into_iter()
+        .filter(|r| r.synced && (r.audio_uploaded_at.is_some() || !r.audio_purged))
+        .collect();
+    let client = SyncClient::new(&server_url);
+    for recording in shared.iter().filter(|r| r.audio_uploaded_at.is_none()) {
+        // It may have been marked confidential since the feed was put together
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        if db.get_exportable_recording(&recording.id).map_err(|e| e.to_string())?.is_none() {
+            return Err("A recording in the feed was marked confidential; share it again".to_string());
+        }
+        drop(db);
+        client.upload_audio(recording).map_err(|e| e.to_string())?;
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.mark_audio_uploaded(&recording.id)
+            .map_err(|e| e.to_string())?;
+    }
+
+    let feed = export::podcast_feed(&title, &shared, &locale, |rec| client.audio_url(&rec.id));
+    client
+        .publish_feed(&student_id, feed)
+        .map_err(|e| e.to_string())
+}
+
+/// First eight hex digits of a recording id, grouped like `1A2B-3C4D`
+fn receipt_code(recording_id: &str) -> String {
+    let digits: String = recording_id
+        .chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .take(8)
+        .collect::<String>()
+        .to_ascii_uppercase();
+    match digits.split_at_checked(4) {
+        Some((first, rest)) if !rest.is_empty() => format!("{}-{}", first, rest),
+        _ => digits,
+    }
+}
+
+/// A QR code and short code linking to the server's transcript view, to
+/// print or show parents at conferences
+#[tauri::command]
+fn get_recording_receipt(state: State<AppState>, recording_id: String) -> Result<RecordingReceipt, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    if is_local_only(&db)? {
+        return Err("Receipts link to the server, which is disabled in local-only mode".to_string());
+    }
+    let recording = db
+        .get_exportable_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found or confidential".to_string())?;
+    let server_url = db
+        .get_setting("server_url")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
     drop(db);
 
-    // Stage 2: Transcribe
-    let _ = window.emit("processing-status", ProcessingStatus {
-        stage: "transcribing".to_string(),
-        message: "Transcribing audio...".to_string(),
-        recording_id: Some(id.clone()),
-        transcript: None,
-        synced: false,
-    });
+    if !recording.synced {
+        return Err("Recording hasn't been synced to the server yet".to_string());
+    }
 
-    let transcriber_guard = state.transcriber.lock().unwrap();
-    let transcript = if let Some(transcriber) = transcriber_guard.as_ref() {
-        match transcriber.transcribe(&audio_path) {
-            Ok(t) => Some(t),
-            Err(e) => {
-                let _ = window.emit("processing-status", ProcessingStatus {
-                    stage: "error".to_string(),
-                    message: format!("Transcription failed: {}", e),
-                    recording_id: Some(id.clone()),
-                    transcript: None,
-                    synced: false,
-                });
-                None
-            }
+    let url = SyncClient::new(&server_url).transcript_view_url(&recording.id);
+    let qr_svg = QrCode::encode(&url).map_err(|e| e.to_string())?.to_svg(4);
+    Ok(RecordingReceipt {
+        short_code: receipt_code(&recording.id),
+        recording_id,
+        url,
+        qr_svg,
+    })
+}
+
+/// Write a printable review packet for the student who made a recording:
+/// their transcript in large print with miscues highlighted, a star rating
+/// for their reading speed and, once synced, a QR code to listen again.
+/// With no `destination` it's saved with the app's data and opened in the
+/// system PDF viewer, ready to print.
+#[tauri::command]
+fn export_review_packet(
+    state: State<AppState>,
+    app: AppHandle,
+    recording_id: String,
+    destination: Option<String>,
+) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let recording = db
+        .get_exportable_recording(&recording_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recording not found or confidential".to_string())?;
+    let transcript = recording
+        .transcript
+        .as_deref()
+        .ok_or_else(|| "Recording hasn't been transcribed yet".to_string())?;
+
+    let wpm = match db.get_assessment(&recording.id).map_err(|e| e.to_string())? {
+        Some(assessment) => assessment.scored_metrics().wpm,
+        None => {
+            let segments = db.get_segments(&recording.id).map_err(|e| e.to_string())?;
+            let seconds = metrics::reading_seconds(&segments, recording.duration_seconds);
+            let words = metrics::normalize_words(transcript).len();
+            if seconds > 0.0 { words as f64 / seconds * 60.0 } else { 0.0 }
         }
+    };
+    let listen_url = if recording.synced && !is_local_only(&db)? {
+        let server_url = db
+            .get_setting("server_url")
+            .map_err(|e| e.to_string())?
+            .unwrap_or_else(|| "http://localhost:3000".to_string());
+        Some(SyncClient::new(&server_url).transcript_view_url(&recording.id))
     } else {
-        let _ = window.emit("processing-status", ProcessingStatus {
-            stage: "error".to_string(),
-            message: "Model not loaded. Please load the model in Settings.".to_string(),
-            recording_id: Some(id.clone()),
-            transcript: None,
-            synced: false,
-        });
         None
     };
-    drop(transcriber_guard);
+    let student_name = student_display_name(&db, &recording.student_id)?;
+    let locale = report_locale(&db)?;
+    drop(db);
 
-    // Update recording with transcript
-    if let Some(ref t) = transcript {
-        let db = state.db.lock().map_err(|e| e.to_string())?;
-        let mut updated_recording = recording.clone();
-        updated_recording.transcript = Some(t.clone());
-        db.save_recording(&updated_recording).map_err(|e| e.to_string())?;
-        drop(db);
+    let pdf = packet::review_packet(
+        &packet::PacketContent { student_name: &student_name, recording: &recording, wpm, listen_url },
+        &locale,
+    );
+    let open = destination.is_none();
+    let path = match destination {
+        Some(destination) => PathBuf::from(destination),
+        None => state.data_dir.join("packets").join(format!("{}.pdf", recording.id)),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, pdf).map_err(|e| e.to_string())?;
+    let path = path.to_string_lossy().to_string();
+    if open {
+        app.opener().open_path(&path, None::<&str>).map_err(|e| e.to_string())?;
     }
+    Ok(path)
+}
 
-    // Stage 3: Sync to server
-    let _ = window.emit("processing-status", ProcessingStatus {
-        stage: "syncing".to_string(),
-        message: "Syncing to server...".to_string(),
-        recording_id: Some(id.clone()),
-        transcript: transcript.clone(),
-        synced: false,
-    });
+// ========== Term Close Commands ==========
 
-    let mut synced = false;
-    if transcript.is_some() {
-        let client = SyncClient::new(&server_url);
-        let db = state.db.lock().map_err(|e| e.to_string())?;
-        let recordings = db.get_all_recordings().map_err(|e| e.to_string())?;
-        if let Some(rec) = recordings.iter().find(|r| r.id == id) {
-            if client.submit_transcript(rec).is_ok() {
-                db.mark_synced(&id).map_err(|e| e.to_string())?;
-                synced = true;
-            }
-        }
+/// `term_close_purge`, what closing a term does with archived recordings;
+/// their audio is deleted by default
+fn term_purge_setting(db: &Database) -> Result<PurgePolicy, String> {
+    match settings::resolve(db, "term_close_purge").map_err(|e| e.to_string())? {
+        Some(value) => PurgePolicy::parse(&value).ok_or_else(|| format!("Unknown term close purge policy: {}", value)),
+        None => Ok(PurgePolicy::default()),
     }
+}
 
-    // Stage 4: Done
-    let final_status = ProcessingStatus {
-        stage: "done".to_string(),
-        message: if synced { "Done! Transcript synced to server.".to_string() }
-                 else if transcript.is_some() { "Done! Transcript saved locally (sync pending).".to_string() }
-                 else { "Recording saved. Transcription failed.".to_string() },
-        recording_id: Some(id),
-        transcript,
-        synced,
-    };
-
-    let _ = window.emit("processing-status", final_status.clone());
+/// Fill in what each step of `close` will do, as things stand
+fn describe_term_close(db: &Database, close: &mut TermClose) -> Result<(), String> {
+    if is_local_only(db)? {
+        close.skip(TermStep::Sync, "Nothing is synced in local-only mode");
+    } else {
+        let unsynced = db.get_unsynced_recordings().map_err(|e| e.to_string())?.len();
+        close.describe(TermStep::Sync, format!("Sync {} recording(s) not yet on the server", unsynced));
+    }
+    let recordings = db.get_all_recordings().map_err(|e| e.to_string())?;
+    let archived = recordings.iter().filter(|r| !r.guest).count();
+    close.describe(
+        TermStep::Archive,
+        format!("Copy {} recording(s) to {} and check the copy", archived, close.archive_folder.display()),
+    );
+    match term_purge_setting(db)? {
+        PurgePolicy::Keep => close.skip(TermStep::Purge, "Recordings are kept, per policy"),
+        PurgePolicy::Audio => close.describe(TermStep::Purge, "Delete the audio of archived recordings"),
+        PurgePolicy::Recordings => close.describe(TermStep::Purge, "Delete archived recordings from this device"),
+    }
+    let roster = db.roster_counts().map_err(|e| e.to_string())?;
+    close.describe(
+        TermStep::ResetRoster,
+        format!(
+            "Forget {} speaker name(s), {} student voice(s), {} vocabulary list(s), {} reported assignment(s) \
+             and this device's student",
+            roster.speaker_names, roster.voiceprints, roster.vocabularies, roster.assignments
+        ),
+    );
+    Ok(())
+}
 
-    Ok(final_status)
+/// Start closing the term, archiving to `archive_folder`. Nothing happens
+/// until each step is confirmed; this returns what each will do.
+#[tauri::command]
+fn begin_term_close(state: State<AppState>, archive_folder: String) -> Result<TermClose, String> {
+    let mut term_close = state.term_close.lock().map_err(|e| e.to_string())?;
+    if term_close.as_ref().is_some_and(|c| c.steps.iter().any(|s| s.status == StepStatus::Done)) {
+        return Err("A term close is already under way; finish or cancel it first".to_string());
+    }
+    let mut close = TermClose::new(PathBuf::from(archive_folder));
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    describe_term_close(&db, &mut close)?;
+    drop(db);
+    *term_close = Some(close.clone());
+    Ok(close)
 }
 
-// ========== Transcription Commands ==========
+/// The term close under way, if any
+#[tauri::command]
+fn get_term_close(state: State<AppState>) -> Result<Option<TermClose>, String> {
+    Ok(state.term_close.lock().map_err(|e| e.to_string())?.clone())
+}
 
+/// Give up on the term close under way. Steps already done stay done.
 #[tauri::command]
-fn load_model(state: State<AppState>) -> Result<(), String> {
-    let model_path = state.data_dir.join("models").join("ggml-base.en.bin");
+fn cancel_term_close(state: State<AppState>) -> Result<(), String> {
+    *state.term_close.lock().map_err(|e| e.to_string())? = None;
+    Ok(())
+}
+
+/// Run `step` of the term close, which must be the next one. A failed step
+/// can be confirmed again. Once the last is done the close is over.
+#[tauri::command(async)]
+fn confirm_step(state: State<AppState>, step: TermStep) -> Result<TermClose, String> {
+    let mut term_close = state.term_close.lock().map_err(|e| e.to_string())?;
+    let close = term_close
+        .as_mut()
+        .ok_or_else(|| "No term close under way".to_string())?;
+    if close.next_step() != Some(step) {
+        return Err(match close.next_step() {
+            Some(_) => "Confirm the steps before it first".to_string(),
+            None => "Every step is done".to_string(),
+        });
+    }
+    let outcome = match step {
+        TermStep::Sync => term_sync(&state),
+        TermStep::Archive => term_archive(&state, close),
+        TermStep::Purge => term_purge(&state, &close.archived),
+        TermStep::ResetRoster => term_reset_roster(&state),
+    };
+    close.finish(step, outcome);
+    let close = close.clone();
+    if close.complete() {
+        *term_close = None;
+    }
+    Ok(close)
+}
 
-    if !model_path.exists() {
+/// Sync everything; the step fails while anything is left unsent
+fn term_sync(state: &AppState) -> Result<String, String> {
+    let result = sync_all(state)?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let left = db.get_unsynced_recordings().map_err(|e| e.to_string())?.len();
+    if result.failed_count > 0 || left > 0 {
         return Err(format!(
-            "Model not found. Please download ggml-base.en.bin to: {}",
-            model_path.display()
+            "{} recording(s) still to sync: {}",
+            left.max(result.failed_count),
+            result.errors.join("; ")
         ));
     }
-
-    let transcriber = Transcriber::new(&model_path).map_err(|e| e.to_string())?;
-    *state.transcriber.lock().unwrap() = Some(transcriber);
-
-    Ok(())
+    Ok(format!("{} recording(s) synced", result.synced_count))
 }
 
-#[tauri::command]
-fn transcribe_recording(state: State<AppState>, recording_id: String) -> Result<TranscribeResult, String> {
-    // Get the recording
+/// Archive every recording that may leave the device and check the copy.
+/// Confidential and guest recordings stay on the device and out of it.
+fn term_archive(state: &AppState, close: &mut TermClose) -> Result<String, String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    let recordings = db.get_all_recordings().map_err(|e| e.to_string())?;
-    let recording = recordings
+    let mut recordings = Vec::new();
+    for recording in db.get_all_recordings().map_err(|e| e.to_string())? {
+        if recording.guest {
+            continue;
+        }
+        recordings.push(ArchivedRecording {
+            segments: db.get_segments(&recording.id).map_err(|e| e.to_string())?,
+            assessment: db.get_assessment(&recording.id).map_err(|e| e.to_string())?,
+            recording,
+        });
+    }
+    let confidential = db
+        .get_all_recordings_including_confidential()
+        .map_err(|e| e.to_string())?
         .iter()
-        .find(|r| r.id == recording_id)
-        .ok_or_else(|| "Recording not found".to_string())?
-        .clone();
-    drop(db); // Release lock before transcription
-
-    // Get audio file path
-    let audio_path = PathBuf::from(&recording.audio_path);
-
-    // Transcribe using CLI
-    let transcriber_guard = state.transcriber.lock().unwrap();
-    let transcriber = transcriber_guard
-        .as_ref()
-        .ok_or_else(|| "Model not loaded. Please load the model first.".to_string())?;
+        .filter(|r| r.confidential && !r.guest)
+        .count();
+    drop(db);
 
-    let transcript = transcriber.transcribe(&audio_path).map_err(|e| e.to_string())?;
-    drop(transcriber_guard); // Release lock
+    let dir = term::write_archive(&close.archive_folder, &recordings).map_err(|e| e.to_string())?;
+    let files = term::verify_archive(&dir).map_err(|e| e.to_string())?;
+    close.archived = recordings.into_iter().map(|a| a.recording.id).collect();
+    close.archive_path = Some(dir.clone());
+    let mut summary = format!(
+        "{} recording(s) archived to {}, {} file(s) verified",
+        close.archived.len(),
+        dir.display(),
+        files
+    );
+    if confidential > 0 {
+        summary.push_str(&format!("; {} confidential recording(s) left on this device", confidential));
+    }
+    Ok(summary)
+}
 
-    // Update recording with transcript
-    let mut updated_recording = recording.clone();
-    updated_recording.transcript = Some(transcript.clone());
+/// Purge the archived recordings per `term_close_purge`. Ones the server
+/// doesn't have yet are kept, unless nothing is synced here.
+fn term_purge(state: &AppState, archived: &[String]) -> Result<String, String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.save_recording(&updated_recording)
-        .map_err(|e| e.to_string())?;
+    let policy = term_purge_setting(&db)?;
+    let local_only = is_local_only(&db)?;
+    let (mut purged, mut kept) = (0, 0);
+    for id in archived {
+        let Some(recording) = db.get_recording(id).map_err(|e| e.to_string())? else {
+            continue;
+        };
+        if !recording.synced && !local_only {
+            kept += 1;
+            continue;
+        }
+        match policy {
+            PurgePolicy::Keep => {}
+            PurgePolicy::Audio if recording.audio_purged => {}
+            PurgePolicy::Audio => {
+                for path in recording.audio_files() {
+                    let _ = std::fs::remove_file(path);
+                }
+                db.mark_audio_purged(&recording.id).map_err(|e| e.to_string())?;
+                purged += 1;
+            }
+            PurgePolicy::Recordings => {
+                remove_recording(&db, &recording.id)?;
+                purged += 1;
+            }
+        }
+    }
+    let mut summary = format!("{} recording(s) purged ({})", purged, policy.as_str());
+    if kept > 0 {
+        summary.push_str(&format!("; {} not yet on the server kept", kept));
+    }
+    Ok(summary)
+}
 
-    Ok(TranscribeResult {
-        transcript,
-        recording_id,
-    })
+/// Start the roster afresh for the next class
+fn term_reset_roster(state: &AppState) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let removed = db.reset_roster().map_err(|e| e.to_string())?;
+    for key in ["student_id", "student_name", "grade_level", "class_sourced_id"] {
+        db.delete_setting_as(key, "user").map_err(|e| e.to_string())?;
+    }
+    Ok(format!(
+        "Forgot {} speaker name(s), {} enrolled voice(s), {} vocabulary list(s) and {} assignment(s)",
+        removed.speaker_names, removed.voiceprints, removed.vocabularies, removed.assignments
+    ))
 }
 
+/// What closing a term does with archived recordings: "keep", "audio" or
+/// "recordings"; None reverts to the pushed setting or deleting audio
 #[tauri::command]
-fn get_model_path(state: State<AppState>) -> String {
-    state
-        .data_dir
-        .join("models")
-        .join("ggml-base.en.bin")
-        .to_string_lossy()
-        .to_string()
+fn set_term_close_purge(state: State<AppState>, policy: Option<PurgePolicy>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match policy {
+        Some(policy) => db.set_setting("term_close_purge", policy.as_str()),
+        None => db.delete_setting_as("term_close_purge", "user"),
+    }
+    .map_err(|e| e.to_string())
 }
 
-// ========== Recording List Commands ==========
+// ========== Digest Commands ==========
 
+/// Email a digest of each day's recordings to `email` at `time` (local
+/// HH:MM); `None` for either turns the digest off
 #[tauri::command]
-fn get_recordings(state: State<AppState>) -> Result<Vec<Recording>, String> {
+fn set_digest(state: State<AppState>, email: Option<String>, time: Option<String>) -> Result<(), String> {
+    let email = email.map(|e| e.trim().to_string()).filter(|e| !e.is_empty());
+    let time = time.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    if let Some(time) = &time {
+        chrono::NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| format!("Invalid time '{}', expected HH:MM", time))?;
+    }
+    if email.as_ref().is_some_and(|e| !e.contains('@')) {
+        return Err("Invalid email address".to_string());
+    }
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_all_recordings().map_err(|e| e.to_string())
+    match (&email, &time) {
+        (Some(email), Some(time)) => {
+            db.set_setting("teacher_email", email).map_err(|e| e.to_string())?;
+            db.set_setting("digest_time", time).map_err(|e| e.to_string())
+        }
+        _ => db.delete_setting_as("digest_time", "user").map_err(|e| e.to_string()),
+    }
 }
 
+/// Mail server for the digest; `None` removes it. The password is sealed
+/// like the Hugging Face token, so settings and their history never hold it.
 #[tauri::command]
-fn delete_recording(state: State<AppState>, recording_id: String) -> Result<(), String> {
+fn set_smtp(state: State<AppState>, smtp: Option<SmtpConfig>) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    // Get the recording to delete the audio file
-    let recordings = db.get_all_recordings().map_err(|e| e.to_string())?;
-    if let Some(recording) = recordings.iter().find(|r| r.id == recording_id) {
-        let _ = std::fs::remove_file(&recording.audio_path);
+    match smtp {
+        Some(mut smtp) => {
+            match smtp.password.take().filter(|p| !p.is_empty()) {
+                Some(password) => {
+                    let sealed =
+                        secrets::seal(&state.data_dir, "smtp_password", &password).map_err(|e| e.to_string())?;
+                    db.set_setting("smtp_password", &sealed)
+                }
+                None => db.delete_setting_as("smtp_password", "user"),
+            }
+            .map_err(|e| e.to_string())?;
+            let json = serde_json::to_string(&smtp).map_err(|e| e.to_string())?;
+            db.set_setting("smtp", &json).map_err(|e| e.to_string())
+        }
+        None => {
+            db.delete_setting_as("smtp_password", "user").map_err(|e| e.to_string())?;
+            db.delete_setting_as("smtp", "user").map_err(|e| e.to_string())
+        }
     }
-
-    db.delete_recording(&recording_id)
-        .map_err(|e| e.to_string())
 }
 
-// ========== Sync Commands ==========
-
+/// What the digest for `date` (`YYYY-MM-DD`, today if `None`) would say,
+/// without sending it
 #[tauri::command]
-fn check_server_connection(state: State<AppState>) -> Result<bool, String> {
+fn get_digest(state: State<AppState>, date: Option<String>) -> Result<DailyDigest, String> {
+    let date = digest_date(date)?;
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    let server_url = db
-        .get_setting("server_url")
-        .map_err(|e| e.to_string())?
-        .unwrap_or_else(|| "http://localhost:3000".to_string());
-    drop(db);
+    digest::build(&db, date, confidence_threshold_setting(&db)?).map_err(|e| e.to_string())
+}
 
-    let client = SyncClient::new(&server_url);
-    Ok(client.check_connection())
+/// Email the digest for `date` now, e.g. to check the mail settings. Sent
+/// after today's `digest_time`, it stands in for tonight's.
+#[tauri::command(async)]
+fn send_digest(state: State<AppState>, date: Option<String>) -> Result<DailyDigest, String> {
+    send_digest_for(&state, digest_date(date)?)
 }
 
-#[tauri::command]
-fn sync_transcripts(state: State<AppState>) -> Result<SyncResult, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let server_url = db
-        .get_setting("server_url")
-        .map_err(|e| e.to_string())?
-        .unwrap_or_else(|| "http://localhost:3000".to_string());
+// ========== Recovery Commands ==========
 
-    let unsynced = db
-        .get_unsynced_recordings()
-        .map_err(|e| e.to_string())?;
+/// The launch checks, with the microphone, model, WhisperX and server
+/// checked again as they are now, since those can be fixed while running
+fn startup_report(state: &AppState) -> StartupReport {
+    let mut live = StartupReport::default();
+
+    let selected = state.recorder.lock_or_recover().selected_device();
+    if audio::input_device_available(selected.as_deref()) {
+        live.pass("microphone");
+    } else {
+        live.fail("microphone", "No microphone found", &[startup::RECORDING]);
+    }
+
+    let db = state.db.lock_or_recover();
+    let backend = transcription_backend_setting(&db).unwrap_or_default();
+    let model = model_path(&db, &state.data_dir);
+    let local_only = is_local_only(&db).unwrap_or(false);
+    let server = db.get_setting("server_url").ok().flatten().filter(|url| !url.trim().is_empty());
     drop(db);
 
-    let client = SyncClient::new(&server_url);
+    if state.transcriber.lock_or_recover().is_some() {
+        live.pass("model");
+    } else {
+        let detail = match model.map(|path| whisper::load_backend(backend, &path, None)) {
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => e,
+            Ok(Ok(_)) => "Model isn't loaded yet".to_string(),
+        };
+        live.fail("model", detail, &[startup::TRANSCRIPTION]);
+    }
 
-    let mut synced_count = 0;
-    let mut failed_count = 0;
-    let mut errors = Vec::new();
+    match backend {
+        BackendKind::WhisperX if whisper::whisperx_installed() => live.pass("python"),
+        BackendKind::WhisperX => live.fail("python", WhisperError::WhisperXNotFound.to_string(), &[startup::TRANSCRIPTION]),
+        BackendKind::WhisperCpp => live.skip("python", "Only WhisperX needs Python"),
+    }
 
-    for recording in &unsynced {
-        match client.submit_transcript(recording) {
-            Ok(_) => {
-                let db = state.db.lock().map_err(|e| e.to_string())?;
-                db.mark_synced(&recording.id)
-                    .map_err(|e| e.to_string())?;
-                synced_count += 1;
-            }
-            Err(e) => {
-                failed_count += 1;
-                errors.push(format!("Recording {}: {}", recording.id, e));
+    match server {
+        _ if local_only => live.skip("server", "Local-only mode never syncs"),
+        Some(_) => live.pass("server"),
+        None => live.fail("server", "No sync server is set up", &[startup::SYNC]),
+    }
+
+    state.startup.merged(live)
+}
+
+/// What works and what doesn't: each startup check, and the features the
+/// failing ones turn off
+#[tauri::command]
+fn get_startup_report(state: State<AppState>) -> StartupReport {
+    startup_report(&state)
+}
+
+/// Subsystems a panic left poisoned. Their commands keep failing until
+/// `reset_subsystem` rebuilds them.
+#[tauri::command]
+fn get_poisoned_subsystems(state: State<AppState>) -> Vec<String> {
+    [
+        ("recorder", state.recorder.is_poisoned()),
+        ("player", state.player.is_poisoned()),
+        ("transcriber", state.transcriber.is_poisoned()),
+        ("database", state.db.is_poisoned()),
+    ]
+    .into_iter()
+    .filter(|(_, poisoned)| *poisoned)
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+/// Rebuild "recorder", "player", "transcriber" or "database" in place,
+/// without restarting the app. A recording in progress is stopped and kept
+/// as an untranscribed recording.
+#[tauri::command]
+fn reset_subsystem(state: State<AppState>, app: AppHandle, name: String) -> Result<(), String> {
+    match name.as_str() {
+        "recorder" => {
+            let db = state.db.lock().map_err(|e| e.to_string())?;
+            let mut recorder = state.recorder.lock_or_recover();
+            if recorder.is_recording() {
+                // Moved aside, so the recovery below saves it
+                if let Err(e) = recorder.finish_capture() {
+                    eprintln!("Failed to stop recording before reset: {}", e);
+                }
             }
+            let echo_reference = recorder.echo_reference();
+            *recorder = build_recorder(&db, &state.data_dir);
+            recorder.set_echo_reference(echo_reference);
+            attach_recorder_listeners(&app, &mut recorder);
+            recover_interrupted_capture(&db, &recorder, &state.data_dir)?;
+        }
+        "player" => {
+            // The recorder keeps listening to the same playback
+            let mut player = state.player.lock_or_recover();
+            *player = Player::with_echo_reference(player.echo_reference());
+        }
+        "transcriber" => {
+            let db = state.db.lock().map_err(|e| e.to_string())?;
+            let transcriber = auto_load_transcriber(&db, &state.data_dir)?;
+            drop(db);
+            *state.transcriber.lock_or_recover() = transcriber;
+        }
+        "database" => {
+            let db = Database::new(&state.data_dir).map_err(|e| e.to_string())?;
+            *state.db.lock_or_recover() = db;
         }
+        other => return Err(format!("Unknown subsystem '{}'", other)),
     }
+    Ok(())
+}
 
-    Ok(SyncResult {
-        synced_count,
-        failed_count,
-        errors,
-    })
+/// A recorder with the saved device and capture settings. It opens its
+/// device lazily, so the app starts without a microphone; recording falls
+/// back to the default while the chosen one is unplugged.
+fn build_recorder(db: &Database, data_dir: &Path) -> AudioRecorder {
+    configured_recorder(db, data_dir.join("audio").join("capture.partial.wav"))
 }
 
-#[tauri::command]
-fn get_unsynced_count(state: State<AppState>) -> Result<usize, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let unsynced = db
-        .get_unsynced_recordings()
-        .map_err(|e| e.to_string())?;
-    Ok(unsynced.len())
+/// A recorder capturing to `capture_path` with the saved device and
+/// capture settings, but no listeners
+fn configured_recorder(db: &Database, capture_path: PathBuf) -> AudioRecorder {
+    let mut recorder = AudioRecorder::new(capture_path);
+    if let Ok(Some(name)) = db.get_setting("audio_device") {
+        if !name.is_empty() {
+            recorder.set_device(Some(name));
+        }
+    }
+    if let Err(e) = apply_capture_settings(db, &mut recorder) {
+        eprintln!("Failed to apply capture settings: {}", e);
+    }
+    recorder
+}
+
+/// The configured model, if it has been downloaded and loads
+fn auto_load_transcriber(db: &Database, data_dir: &Path) -> Result<Option<Box<dyn TranscriptionBackend>>, String> {
+    let model_path = model_path(db, data_dir)?;
+    let backend = transcription_backend_setting(db)?;
+    whisper::set_program_paths(program_paths_setting(db)?);
+    whisper::set_threads(transcription_threads_setting(db)?);
+    match whisper::load_backend(backend, &model_path, hf_token(db, data_dir)) {
+        Ok(t) => {
+            println!("Model auto-loaded from: {} ({})", model_path.display(), backend.as_str());
+            Ok(Some(t))
+        }
+        Err(WhisperError::ModelNotFound(_)) => {
+            println!("Model not found at: {}", model_path.display());
+            Ok(None)
+        }
+        Err(e) => {
+            eprintln!("Failed to auto-load model: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// Forward the recorder's callbacks to the frontend and the pipeline. Done
+/// at startup and again whenever the recorder is rebuilt.
+fn attach_recorder_listeners(app: &AppHandle, recorder: &mut AudioRecorder) {
+    // Chunks of long recordings are transcribed while recording goes on
+    let (chunks_tx, chunks) = mpsc::channel();
+    spawn_chunk_transcriber(app.clone(), chunks);
+    let handle = app.clone();
+    recorder.set_chunk_listener(move |chunk| {
+        handle.state::<AppState>().rolling.lock_or_recover().pending += 1;
+        if let Err(mpsc::SendError(chunk)) = chunks_tx.send(chunk) {
+            let _ = std::fs::remove_file(&chunk.path);
+            handle.state::<AppState>().rolling.lock_or_recover().pending -= 1;
+        }
+    });
+
+    // Live input level for the VU meter
+    let handle = app.clone();
+    recorder.set_level_listener(move |level| {
+        let _ = handle.emit("audio-level", level);
+    });
+
+    // Lost microphones: the capture thread fails over on its own, this tells the user
+    let handle = app.clone();
+    recorder.set_error_listener(move |error| {
+        let _ = handle.emit("recording-error", error);
+    });
+
+    // Clipping or a dead-quiet input, flagged while there's still time to fix it
+    let handle = app.clone();
+    recorder.set_quality_listener(move |warning| {
+        let _ = handle.emit("audio-quality-warning", warning);
+    });
+
+    // Long sessions are cut into parts that are processed while recording continues
+    let handle = app.clone();
+    recorder.set_rollover_listener(move |capture, stats, markers| {
+        let app = handle.clone();
+        std::thread::spawn(move || {
+            let state = app.state::<AppState>();
+            if let Err(e) = process_recording(&state, &app, CaptureSource::Rollover(capture, stats, markers)) {
+                eprintln!("Rolled-over recording failed to process: {}", e);
+            }
+        });
+    });
+
+    // Forgotten recordings stop on silence and go through the usual pipeline.
+    // Runs on its own thread because stopping joins the capture thread.
+    let handle = app.clone();
+    recorder.set_auto_stop_listener(move || {
+        let app = handle.clone();
+        std::thread::spawn(move || {
+            let state = app.state::<AppState>();
+            // Correction clips are finished by the user, not the pipeline
+            let correcting = state.pending_correction.lock().map(|p| p.as_ref().is_some_and(|p| p.clip.is_none()));
+            if correcting.unwrap_or(true) {
+                return;
+            }
+            let silence_seconds = state
+                .db
+                .lock()
+                .ok()
+                .and_then(|db| silence_stop_setting(&db).ok().flatten())
+                .map(|s| s.seconds)
+                .unwrap_or(0.0);
+            let _ = app.emit("recording-auto-stopped", AutoStopped { silence_seconds });
+            if let Err(e) = process_recording(&state, &app, CaptureSource::Recorder) {
+                eprintln!("Auto-stopped recording failed to process: {}", e);
+            }
+        });
+    });
 }
 
 // ========== App Entry Point ==========
@@ -496,66 +7154,354 @@ pub fn run() {
         .unwrap_or_else(|| PathBuf::from("."))
         .join("classroom-transcriber");
 
-    std::fs::create_dir_all(&data_dir).expect("Failed to create data directory");
-    std::fs::create_dir_all(data_dir.join("models")).expect("Failed to create models directory");
-    std::fs::create_dir_all(data_dir.join("audio")).expect("Failed to create audio directory");
+    // Problems from here on are reported rather than stopping the app
+    let mut checks = StartupReport::default();
+    // Admin-installed post-processing scripts go in hooks; see hooks::run
+    let folders = ["models", "audio", "hooks"]
+        .iter()
+        .try_for_each(|folder| std::fs::create_dir_all(data_dir.join(folder)));
+    match folders {
+        Ok(()) => checks.pass("storage"),
+        Err(e) => checks.fail(
+            "storage",
+            format!("Can't create folders in {}: {}", data_dir.display(), e),
+            &[startup::RECORDING],
+        ),
+    }
 
     // Initialize database
-    let db = Database::new(&data_dir).expect("Failed to initialize database");
+    let db = match Database::new(&data_dir) {
+        Ok(db) => {
+            checks.pass("database");
+            db
+        }
+        Err(e) => {
+            checks.fail(
+                "database",
+                format!("Can't open the database, so nothing is being saved: {}", e),
+                &[startup::RECORDING, startup::SYNC],
+            );
+            Database::in_memory().expect("Failed to create an in-memory database")
+        }
+    };
+
+    // Purge audio past its retention date; server notices go out with the next sync
+    let maintenance_started_at = chrono::Utc::now().to_rfc3339();
+    let maintenance_started = Instant::now();
+    let purged = purge_expired_audio(&db);
+    match &purged {
+        Ok(0) => {}
+        Ok(n) => println!("Purged audio for {} expired recording(s)", n),
+        Err(e) => eprintln!("Failed to purge expired recordings: {}", e),
+    }
 
-    // Initialize audio recorder
-    let recorder = AudioRecorder::new().expect("Failed to initialize audio recorder");
+    // Remove uploaded audio whose keep-after-upload window has passed
+    let offloaded = audio_keep_days(&db).and_then(|days| match days {
+        Some(d) => offload_uploaded_audio(&db, d),
+        None => Ok(0),
+    });
+    match &offloaded {
+        Ok(0) => {}
+        Ok(n) => println!("Removed local audio for {} uploaded recording(s)", n),
+        Err(e) => eprintln!("Failed to remove uploaded audio: {}", e),
+    }
 
-    // Auto-load model if it exists
-    let model_path = data_dir.join("models").join("ggml-base.en.bin");
-    let transcriber = if model_path.exists() {
-        match Transcriber::new(&model_path) {
-            Ok(t) => {
-                println!("Model auto-loaded from: {}", model_path.display());
-                Some(t)
-            }
-            Err(e) => {
-                eprintln!("Failed to auto-load model: {}", e);
-                None
+    let guests = purge_guest_recordings(&db);
+    match &guests {
+        Ok(0) => {}
+        Ok(n) => println!("Deleted {} guest recording(s)", n),
+        Err(e) => eprintln!("Failed to delete guest recordings: {}", e),
+    }
+    let maintenance = purged.and_then(|purged| {
+        offloaded.and_then(|offloaded| {
+            guests.map(|guests| {
+                Some(format!(
+                    "Startup: purged {} expired, removed {} uploaded, deleted {} guest",
+                    purged, offloaded, guests
+                ))
+            })
+        })
+    });
+    log_job(&db, JobKind::Maintenance, None, maintenance_started_at, maintenance_started, maintenance);
+
+    // Prometheus scrape endpoint for fleet monitoring, off unless a port is set
+    match settings::resolve(&db, "metrics_port").map(|p| p.and_then(|p| p.parse::<u16>().ok())) {
+        Ok(Some(port)) => {
+            if let Err(e) = telemetry::serve(port) {
+                eprintln!("Failed to serve metrics on port {}: {}", port, e);
             }
         }
-    } else {
-        println!("Model not found at: {}", model_path.display());
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to read metrics port: {}", e),
+    }
+
+    let player = Player::new();
+    let mut recorder = build_recorder(&db, &data_dir);
+    recorder.set_echo_reference(Some(player.echo_reference()));
+
+    // Keep whatever was captured before a crash as an untranscribed recording
+    if let Err(e) = recover_interrupted_capture(&db, &recorder, &data_dir) {
+        eprintln!("Failed to recover interrupted recording: {}", e);
+    }
+
+    // Recordings that were being processed when the app quit start over
+    if let Err(e) = db.requeue_interrupted_jobs() {
+        eprintln!("Failed to requeue interrupted jobs: {}", e);
+    }
+
+    // The profile checks whether the configured whisper-cli can use the GPU
+    match program_paths_setting(&db) {
+        Ok(paths) => whisper::set_program_paths(paths),
+        Err(e) => eprintln!("Failed to read program paths: {}", e),
+    }
+    let hardware = hardware::profile();
+    println!(
+        "{} cores, {} MB memory free, GPU: {}; suits the {} model",
+        hardware.cpu_cores,
+        hardware.available_memory_mb.or(hardware.total_memory_mb).unwrap_or(0),
+        hardware.gpu.as_ref().map_or("none", |g| match (g.kind, g.usable) {
+            (hardware::GpuKind::Metal, _) => "Metal",
+            (hardware::GpuKind::Cuda, true) => "CUDA",
+            (hardware::GpuKind::Cuda, false) => "CUDA, unused by this whisper-cli build",
+        }),
+        hardware.model_size
+    );
+    let transcriber = auto_load_transcriber(&db, &data_dir).unwrap_or_else(|e| {
+        eprintln!("Failed to read model setting: {}", e);
         None
-    };
+    });
 
     let app_state = AppState {
         db: Mutex::new(db),
         recorder: Mutex::new(recorder),
+        player: Mutex::new(player),
         transcriber: Mutex::new(transcriber),
+        pending_correction: Mutex::new(None),
+        term_close: Mutex::new(None),
+        rolling: Mutex::new(RollingTranscript::default()),
+        rolling_done: Condvar::new(),
+        push_to_talk: Mutex::new(None),
+        transcription: Mutex::new(None),
+        job_queue: Mutex::new(HashMap::new()),
+        job_ready: Condvar::new(),
+        batch: Mutex::new(None),
+        settings_cache: Cached::new(Table::Settings),
+        recordings_cache: Cached::new(Table::Recordings),
+        startup: checks,
         data_dir,
     };
+    let report = startup_report(&app_state);
+    for check in report.checks.iter().filter(|c| c.status == startup::CheckStatus::Failed) {
+        eprintln!("Startup check {} failed: {}", check.name, check.detail.as_deref().unwrap_or_default());
+    }
+    if report.degraded() {
+        eprintln!("Starting with {} turned off", report.disabled.join(", "));
+    }
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(app_state)
+        .setup(|app| {
+            spawn_device_watcher(app.handle().clone());
+            spawn_digest_scheduler(app.handle().clone());
+            spawn_guest_purger(app.handle().clone());
+            spawn_job_worker(app.handle().clone());
+
+            let state = app.state::<AppState>();
+            attach_recorder_listeners(app.handle(), &mut state.recorder.lock_or_recover());
+            let pending_on_start = transcribe_pending_on_start_setting(&state.db.lock_or_recover());
+            if pending_on_start.unwrap_or(false) {
+                if let Err(e) = transcribe_pending(&state, app.handle()) {
+                    eprintln!("Failed to queue untranscribed recordings: {}", e);
+                }
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // Settings
             get_settings,
             save_settings,
             complete_setup,
+            set_classroom,
+            set_locale,
+            get_settings_history,
+            rollback_setting,
+            get_effective_settings,
+            set_audio_upload_policy,
+            set_local_only,
+            set_guest_mode,
             // Recording
+            get_pipeline,
+            set_pipeline,
             start_recording,
+            add_marker,
+            set_auto_stop,
+            set_max_duration,
+            set_chunk_minutes,
+            set_live_captions,
+            set_dual_channel,
+            set_channel_weights,
+            set_system_audio,
+            get_blackout_windows,
+            set_blackout_windows,
+            set_input_gain,
+            set_resample_quality,
+            set_noise_suppression,
+            set_echo_cancellation,
+            set_audio_format,
+            set_archive_quality,
+            set_recording_id_scheme,
+            check_microphone_permission,
+            run_mic_test,
+            open_microphone_settings,
+            list_audio_devices,
+            list_system_audio_sources,
+            set_audio_device,
             stop_recording,
             stop_and_process,
             is_recording,
+            import_audio,
+            // Push-to-talk
+            start_push_to_talk,
+            push_to_talk_press,
+            push_to_talk_release,
+            end_push_to_talk,
+            // Assignments
+            get_assignments,
+            start_assignment,
+            get_active_assignment,
+            finish_assignment,
             // Transcription
             load_model,
             transcribe_recording,
+            cancel_transcription,
             get_model_path,
+            set_transcription_backend,
+            set_backend_paths,
+            detect_transcription_backends,
+            set_transcription_language,
+            set_word_timestamps,
+            get_vocabulary,
+            set_vocabulary,
+            set_student_vocabulary,
+            get_language_mismatches,
+            retranscribe_detected_language,
+            retranscribe_recording,
+            set_recording_translation,
+            get_transcript_revisions,
+            // Diarization
+            get_diarization_status,
+            set_hf_token,
+            set_speaker_count,
+            // Models
+            list_available_models,
+            benchmark_transcription,
+            get_hardware_profile,
+            set_transcription_threads,
+            download_model,
+            delete_model,
+            select_model,
+            // Corrections
+            start_correction,
+            finish_correction,
+            get_segment_revisions,
             // Recordings list
             get_recordings,
+            get_recordings_page,
+            get_channel_activity,
+            get_markers,
+            delete_marker,
             delete_recording,
+            get_segments,
+            get_unmasked_segments,
+            get_low_confidence_segments,
+            set_segment_speaker,
+            update_recording_metadata,
+            rename_speaker,
+            set_recording_confidential,
+            set_active_passage,
+            attach_passage,
+            set_recording_expiry,
+            get_upcoming_expirations,
+            purge_expired_recordings,
+            // Playback
+            play_recording,
+            loop_segment,
+            play_word,
+            pause_playback,
+            resume_playback,
+            seek_playback,
+            stop_playback,
+            set_playback_speed,
+            get_playback_status,
+            get_timing_map,
+            get_waveform,
             // Sync
             check_server_connection,
+            get_server_capabilities,
+            pull_classroom_config,
             sync_transcripts,
             get_unsynced_count,
+            get_backlog_summary,
+            retry_failed_syncs,
+            set_sync_speaker_stats,
+            // Activity
+            get_job_history,
+            get_job_queue,
+            retry_job,
+            process_job_now,
+            transcribe_all_pending,
+            get_batch_progress,
+            set_transcribe_pending_on_start,
+            get_quiet_hours,
+            set_quiet_hours,
+            get_metrics,
+            // Assessment
+            load_rubric,
+            get_rubric,
+            score_recording,
+            get_assessment,
+            set_confidence_weighting,
+            get_cohort_comparison,
+            get_norms_table,
+            set_norms_table,
+            import_norms_csv,
+            get_norms_versions,
+            use_norms_version,
+            set_grade_level,
+            set_teacher_voiceprint,
+            enroll_voice,
+            forget_student_voice,
+            set_archive_teacher_only,
+            set_student_speaker_strategy,
+            set_student_speaker,
+            get_speaker_stats,
+            // Export
+            export_dashboard,
+            export_recording_audio,
+            export_oneroster,
+            export_speaker_stats,
+            export_podcast_feed,
+            export_transcript_player,
+            generate_parent_export,
+            begin_term_close,
+            get_term_close,
+            cancel_term_close,
+            confirm_step,
+            set_term_close_purge,
+            share_podcast_feed,
+            get_recording_receipt,
+            export_review_packet,
+            // Digest
+            set_digest,
+            set_smtp,
+            get_digest,
+            send_digest,
+            // Recovery
+            get_poisoned_subsystems,
+            get_startup_report,
+            reset_subsystem,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,207 @@
+use chrono::{DateTime, Local};
+
+/// Number, date and time conventions for human-readable reports. Machine
+/// formats (CSV scores, RSS dates, RFC 3339 timestamps) never go through
+/// this.
+#[derive(Debug, Clone)]
+pub struct Locale {
+    decimal: char,
+    group: &'static str,
+    hour24: bool,
+    date_format: &'static str,
+}
+
+const EN_US: Locale = Locale {
+    decimal: '.',
+    group: ",",
+    hour24: false,
+    date_format: "%m/%d/%Y",
+};
+
+/// Conventions by BCP 47 tag, matched on the full tag, then the language
+const LOCALES: &[(&str, Locale)] = &[
+    ("en-us", EN_US),
+    (
+        "en-gb",
+        Locale { decimal: '.', group: ",", hour24: true, date_format: "%d/%m/%Y" },
+    ),
+    (
+        "en-au",
+        Locale { decimal: '.', group: ",", hour24: false, date_format: "%d/%m/%Y" },
+    ),
+    ("en", EN_US),
+    (
+        "de",
+        Locale { decimal: ',', group: ".", hour24: true, date_format: "%d.%m.%Y" },
+    ),
+    (
+        "fr",
+        Locale { decimal: ',', group: "\u{202F}", hour24: true, date_format: "%d/%m/%Y" },
+    ),
+    (
+        "es",
+        Locale { decimal: ',', group: ".", hour24: true, date_format: "%d/%m/%Y" },
+    ),
+    (
+        "it",
+        Locale { decimal: ',', group: ".", hour24: true, date_format: "%d/%m/%Y" },
+    ),
+    (
+        "pt",
+        Locale { decimal: ',', group: ".", hour24: true, date_format: "%d/%m/%Y" },
+    ),
+    (
+        "nl",
+        Locale { decimal: ',', group: ".", hour24: true, date_format: "%d-%m-%Y" },
+    ),
+    (
+        "ko",
+        Locale { decimal: '.', group: ",", hour24: false, date_format: "%Y. %m. %d." },
+    ),
+    (
+        "ja",
+        Locale { decimal: '.', group: ",", hour24: true, date_format: "%Y/%m/%d" },
+    ),
+    (
+        "zh",
+        Locale { decimal: '.', group: ",", hour24: true, date_format: "%Y/%m/%d" },
+    ),
+];
+
+impl Default for Locale {
+    fn default() -> Self {
+        EN_US
+    }
+}
+
+impl Locale {
+    /// Conventions for a tag like `de-DE` or `en_GB`; unknown tags fall back
+    /// to US English
+    pub fn from_tag(tag: &str) -> Self {
+        let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+        let language = tag.split('-').next().unwrap_or("");
+        LOCALES
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .or_else(|| LOCALES.iter().find(|(t, _)| *t == language))
+            .map(|(_, locale)| locale.clone())
+            .unwrap_or_default()
+    }
+
+    /// `value` with `decimals` places and grouped thousands
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let fixed = format!("{:.*}", decimals, value.abs());
+        let (integer, fraction) = fixed.split_once('.').unwrap_or((fixed.as_str(), ""));
+
+        let mut out = String::new();
+        if value < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                out.push_str(self.group);
+            }
+            out.push(digit);
+        }
+        if !fraction.is_empty() {
+            out.push(self.decimal);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    pub fn date(&self, dt: &DateTime<Local>) -> String {
+        dt.format(self.date_format).to_string()
+    }
+
+    pub fn time(&self, dt: &DateTime<Local>) -> String {
+        dt.format(if self.hour24 { "%H:%M" } else { "%-I:%M %p" }).to_string()
+    }
+
+    pub fn datetime(&self, dt: &DateTime<Local>) -> String {
+        format!("{} {}", self.date(dt), self.time(dt))
+    }
+
+    /// An RFC 3339 timestamp in local time, or the input unchanged if it
+    /// doesn't parse
+    pub fn timestamp(&self, iso: &str) -> String {
+        DateTime::parse_from_rfc3339(iso)
+            .map(|dt| self.datetime(&dt.with_timezone(&Local)))
+            .unwrap_or_else(|_| iso.to_string())
+    }
+
+    /// `m:ss`, or `h:mm:ss` from an hour up
+    pub fn duration(&self, seconds: f64) -> String {
+        let secs = seconds.max(0.0) as u64;
+        if secs >= 3600 {
+            format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+        } else {
+            format!("{}:{:02}", secs / 60, secs % 60)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn afternoon() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 3, 5, 14, 7, 0).unwrap()
+    }
+
+    #[test]
+    fn tags_match_full_then_language_then_default() {
+        assert_eq!(Locale::from_tag("en_GB").date_format, "%d/%m/%Y");
+        assert_eq!(Locale::from_tag(" DE-at ").decimal, ',');
+        assert_eq!(Locale::from_tag("en-NZ").date_format, "%m/%d/%Y");
+        assert_eq!(Locale::from_tag("xx").date_format, EN_US.date_format);
+        assert_eq!(Locale::from_tag("").date_format, EN_US.date_format);
+    }
+
+    #[test]
+    fn numbers_group_thousands_and_use_the_local_decimal() {
+        let us = Locale::from_tag("en-US");
+        assert_eq!(us.number(1234567.891, 2), "1,234,567.89");
+        assert_eq!(us.number(999.0, 0), "999");
+        assert_eq!(us.number(1000.0, 0), "1,000");
+        assert_eq!(Locale::from_tag("de").number(1234.5, 1), "1.234,5");
+        assert_eq!(Locale::from_tag("fr").number(12345.0, 0), "12\u{202F}345");
+    }
+
+    #[test]
+    fn negative_numbers_keep_their_sign_unless_they_round_to_zero() {
+        let us = Locale::default();
+        assert_eq!(us.number(-1234.5, 1), "-1,234.5");
+        assert_eq!(us.number(-0.004, 2), "0.00");
+        assert_eq!(us.number(-0.0, 0), "0");
+    }
+
+    #[test]
+    fn dates_and_times_follow_the_locale() {
+        let dt = afternoon();
+        assert_eq!(Locale::from_tag("en-US").datetime(&dt), "03/05/2026 2:07 PM");
+        assert_eq!(Locale::from_tag("en-GB").datetime(&dt), "05/03/2026 14:07");
+        assert_eq!(Locale::from_tag("de").date(&dt), "05.03.2026");
+        assert_eq!(Locale::from_tag("ko").date(&dt), "2026. 03. 05.");
+    }
+
+    #[test]
+    fn timestamps_become_local_or_pass_through() {
+        let dt = afternoon();
+        let de = Locale::from_tag("de");
+        assert_eq!(de.timestamp(&dt.to_rfc3339()), "05.03.2026 14:07");
+        assert_eq!(de.timestamp("not a date"), "not a date");
+    }
+
+    #[test]
+    fn durations_switch_to_hours_at_an_hour() {
+        let locale = Locale::default();
+        assert_eq!(locale.duration(0.0), "0:00");
+        assert_eq!(locale.duration(-5.0), "0:00");
+        assert_eq!(locale.duration(65.9), "1:05");
+        assert_eq!(locale.duration(3599.0), "59:59");
+        assert_eq!(locale.duration(3600.0), "1:00:00");
+        assert_eq!(locale.duration(37_230.0), "10:20:30");
+    }
+}
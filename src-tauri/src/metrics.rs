@@ -0,0 +1,257 @@
+use crate::whisper::TranscriptSegment;
+use serde::{Deserialize, Serialize};
+
+/// Oral reading fluency measures for one recording against its passage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FluencyMetrics {
+    pub passage_words: usize,
+    pub words_read: usize,
+    pub words_correct: usize,
+    pub substitutions: usize,
+    pub omissions: usize,
+    pub insertions: usize,
+    pub reading_seconds: f64,
+    /// Words per minute, all words spoken
+    pub wpm: f64,
+    /// Words correct per minute
+    pub wcpm: f64,
+    /// Fraction of passage words read correctly
+    pub accuracy: f64,
+}
+
+/// Lowercased words with surrounding punctuation stripped
+pub fn normalize_words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|w| {
+            w.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+                .to_lowercase()
+        })
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlignOp {
+    Match,
+    Substitution,
+    Omission,
+    Insertion,
+}
+
+/// Word-level edit alignment of what was read against the passage
+pub fn align(passage: &[String], spoken: &[String]) -> Vec<AlignOp> {
+    let (n, m) = (passage.len(), spoken.len());
+    let mut cost = vec![vec![0usize; m + 1]; n + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in cost[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let diagonal = cost[i - 1][j - 1] + usize::from(passage[i - 1] != spoken[j - 1]);
+            cost[i][j] = diagonal.min(cost[i - 1][j] + 1).min(cost[i][j - 1] + 1);
+        }
+    }
+
+    // Walk back from the end to recover the operations
+    let mut ops = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            let same = passage[i - 1] == spoken[j - 1];
+            if cost[i][j] == cost[i - 1][j - 1] + usize::from(!same) {
+                ops.push(if same { AlignOp::Match } else { AlignOp::Substitution });
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+        if i > 0 && cost[i][j] == cost[i - 1][j] + 1 {
+            ops.push(AlignOp::Omission);
+            i -= 1;
+        } else {
+            ops.push(AlignOp::Insertion);
+            j -= 1;
+        }
+    }
+    ops.reverse();
+    ops
+}
+
+/// Reading time from first to last segment, falling back to the full duration.
+/// Timed words narrow it to the first and last word spoken, leaving out the
+/// silence segments tend to start or end with.
+pub fn reading_seconds(segments: &[TranscriptSegment], duration_seconds: f64) -> f64 {
+    match (segments.first(), segments.last()) {
+        (Some(first), Some(last)) => {
+            let start = first.words.first().map_or(first.start, |w| w.start);
+            let end = last.words.last().map_or(last.end, |w| w.end);
+            if end > start {
+                end - start
+            } else {
+                duration_seconds
+            }
+        }
+        _ => duration_seconds,
+    }
+}
+
+pub fn fluency(passage: &str, transcript: &str, reading_seconds: f64) -> FluencyMetrics {
+    let passage_words = normalize_words(passage);
+    let spoken = normalize_words(transcript);
+    let ops = align(&passage_words, &spoken);
+
+    let count = |op: AlignOp| ops.iter().filter(|&&o| o == op).count();
+    let words_correct = count(AlignOp::Match);
+    let minutes = reading_seconds / 60.0;
+    let per_minute = |words: usize| if minutes > 0.0 { words as f64 / minutes } else { 0.0 };
+
+    FluencyMetrics {
+        passage_words: passage_words.len(),
+        words_read: spoken.len(),
+        words_correct,
+        substitutions: count(AlignOp::Substitution),
+        omissions: count(AlignOp::Omission),
+        insertions: count(AlignOp::Insertion),
+        reading_seconds,
+        wpm: per_minute(spoken.len()),
+        wcpm: per_minute(words_correct),
+        accuracy: if passage_words.is_empty() {
+            0.0
+        } else {
+            words_correct as f64 / passage_words.len() as f64
+        },
+    }
+}
+
+pub const DEFAULT_CONFIDENCE_THRESHOLD: f64 = 0.5;
+
+/// Phrases Whisper commonly invents over silence or noise
+const STOCK_PHRASES: &[&str] = &[
+    "thank you for watching",
+    "thanks for watching",
+    "please subscribe",
+    "subtitles by",
+    "transcribed by",
+];
+
+/// How words below the confidence threshold count towards adjusted metrics
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfidenceMode {
+    /// Dropped entirely
+    Exclude,
+    /// Scaled by confidence / threshold
+    Weight,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConfidenceWeighting {
+    pub mode: ConfidenceMode,
+    pub threshold: f64,
+}
+
+/// Segments that look like decoder hallucinations rather than speech: a
+/// repeat of the previous segment, more words than anyone can say in the
+/// time, or a stock caption phrase
+pub fn flag_hallucinations(segments: &[TranscriptSegment]) -> Vec<bool> {
+    let mut previous: Vec<String> = Vec::new();
+    segments
+        .iter()
+        .map(|segment| {
+            let words = normalize_words(&segment.text);
+            let duration = segment.end - segment.start;
+            let joined = words.join(" ");
+
+            let repeated = words.len() >= 3 && words == previous;
+            let too_fast = words.len() >= 4 && (duration <= 0.0 || words.len() as f64 / duration > 6.0);
+            let stock = STOCK_PHRASES.iter().any(|p| joined.contains(p));
+
+            previous = words;
+            repeated || too_fast || stock
+        })
+        .collect()
+}
+
+/// Fluency against the passage with each segment's words counted by their
+/// weight: zero for hallucinations and, in exclude mode, low-confidence
+/// segments. Discounted time is taken off the reading time so the rates
+/// stay comparable. Segments without a confidence count in full.
+pub fn adjusted_fluency(
+    passage: &str,
+    segments: &[TranscriptSegment],
+    reading_seconds: f64,
+    weighting: ConfidenceWeighting,
+) -> FluencyMetrics {
+    let hallucinated = flag_hallucinations(segments);
+    let mut spoken = Vec::new();
+    let mut weights = Vec::new();
+    let mut discounted_seconds = 0.0;
+
+    for (segment, &flagged) in segments.iter().zip(&hallucinated) {
+        let weight = match segment.confidence {
+            _ if flagged => 0.0,
+            Some(c) if c < weighting.threshold => match weighting.mode {
+                ConfidenceMode::Exclude => 0.0,
+                ConfidenceMode::Weight => (c / weighting.threshold).max(0.0),
+            },
+            _ => 1.0,
+        };
+        let words = normalize_words(&segment.text);
+        weights.extend(std::iter::repeat_n(weight, words.len()));
+        spoken.extend(words);
+        discounted_seconds += (1.0 - weight) * (segment.end - segment.start).max(0.0);
+    }
+
+    let passage_words = normalize_words(passage);
+    let ops = align(&passage_words, &spoken);
+
+    // Omitted passage words take the weight of the spoken word before them,
+    // so a skipped stretch inside a discounted region isn't held against
+    // the reader
+    let (mut correct, mut read, mut passage_total) = (0.0f64, 0.0f64, 0.0f64);
+    let (mut substitutions, mut omissions, mut insertions) = (0.0f64, 0.0f64, 0.0f64);
+    let mut j = 0;
+    let mut last_weight = 1.0;
+    for op in ops {
+        let weight = match op {
+            AlignOp::Omission => last_weight,
+            _ => {
+                last_weight = weights[j];
+                j += 1;
+                last_weight
+            }
+        };
+        match op {
+            AlignOp::Match => correct += weight,
+            AlignOp::Substitution => substitutions += weight,
+            AlignOp::Omission => omissions += weight,
+            AlignOp::Insertion => insertions += weight,
+        }
+        if op != AlignOp::Omission {
+            read += weight;
+        }
+        if op != AlignOp::Insertion {
+            passage_total += weight;
+        }
+    }
+
+    let reading_seconds = (reading_seconds - discounted_seconds).max(0.0);
+    let minutes = reading_seconds / 60.0;
+    let per_minute = |words: f64| if minutes > 0.0 { words / minutes } else { 0.0 };
+
+    FluencyMetrics {
+        passage_words: passage_words.len(),
+        words_read: read.round() as usize,
+        words_correct: correct.round() as usize,
+        substitutions: substitutions.round() as usize,
+        omissions: omissions.round() as usize,
+        insertions: insertions.round() as usize,
+        reading_seconds,
+        wpm: per_minute(read),
+        wcpm: per_minute(correct),
+        accuracy: if passage_total > 0.0 { correct / passage_total } else { 0.0 },
+    }
+}
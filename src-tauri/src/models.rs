@@ -0,0 +1,285 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Where ggml builds of the whisper models are published
+const DOWNLOAD_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+/// Used until a model is picked if none in the catalog suits the hardware
+pub const DEFAULT_MODEL: &str = "base.en";
+/// Used for language detection when the selected model is English-only
+pub const DEFAULT_MULTILINGUAL_MODEL: &str = "base";
+
+#[derive(Error, Debug)]
+pub enum ModelError {
+    #[error("Unknown model: {0}")]
+    UnknownModel(String),
+    #[error("Download failed: {0}")]
+    NetworkError(#[from] reqwest::Error),
+    #[error("Download failed: server returned {0}")]
+    ServerError(reqwest::StatusCode),
+    #[error("Download incomplete: got {got} of {expected} bytes")]
+    Incomplete { got: u64, expected: u64 },
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// A whisper model this app knows how to fetch
+#[derive(Debug, Clone, Copy)]
+pub struct ModelSpec {
+    /// Such as "small" or "small.en"
+    pub name: &'static str,
+    /// Approximate download size
+    pub size_mb: u32,
+    /// SHA256 of the ggml file as published on Hugging Face. Pinned here so
+    /// a download is checked against what the release had, not against a
+    /// checksum handed out by the same server the file came from.
+    pub sha256: &'static str,
+}
+
+impl ModelSpec {
+    pub fn file_name(&self) -> String {
+        file_name(self.name)
+    }
+
+    /// English-only models end in ".en"
+    pub fn multilingual(&self) -> bool {
+        !self.name.ends_with(".en")
+    }
+}
+
+pub const MODELS: &[ModelSpec] = &[
+    ModelSpec {
+        name: "tiny",
+        size_mb: 75,
+        sha256: "be07e048e1e599ad46341c8d2a135645097a538221678b7acdd1b1919c6e1b21",
+    },
+    ModelSpec {
+        name: "tiny.en",
+        size_mb: 75,
+        sha256: "921e4cf8686fdd993dcd081a5da5b6c365bfde1162e72b08d75ac75289920b1f",
+    },
+    ModelSpec {
+        name: "base",
+        size_mb: 142,
+        sha256: "60ed5bc3dd14eea856493d334349b405782ddcaf0028d4b5df4088345fba2efe",
+    },
+    ModelSpec {
+        name: "base.en",
+        size_mb: 142,
+        sha256: "a03779c86df3323075f5e796cb2ce5029f00ec8869eee3fdfb897afe36c6d002",
+    },
+    ModelSpec {
+        name: "small",
+        size_mb: 466,
+        sha256: "1be3a9b2063867b937e64e2ec7483364a79917e157fa98c5d94b5c1fffea987b",
+    },
+    ModelSpec {
+        name: "small.en",
+        size_mb: 466,
+        sha256: "c6138d6d58ecc8322097e0f987c32f1be8bb0a18532a3f88f734d1bbf9c41e5d",
+    },
+    ModelSpec {
+        name: "medium",
+        size_mb: 1500,
+        sha256: "6c14d5adee5f86394037b4e4e8b59f1673b6cee10e3cf0b11bbdbee79c156208",
+    },
+    ModelSpec {
+        name: "medium.en",
+        size_mb: 1500,
+        sha256: "cc37e93478338ec7700281a7ac30a10128929eb8f427dda2e865faa8f6da4356",
+    },
+    // Multilingual only
+    ModelSpec {
+        name: "large-v3-turbo",
+        size_mb: 1600,
+        sha256: "1fc70f774d38eb169993ac391eea357ef47c88757ef72ee5943879b7e8e2bc69",
+    },
+    ModelSpec {
+        name: "large-v3",
+        size_mb: 3100,
+        sha256: "64d182b440b98d5203c4f9bd541544d84c605196c4f7b845dfa11fb23594d1e2",
+    },
+];
+
+/// One entry of the model list shown in settings
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableModel {
+    pub name: &'static str,
+    pub file_name: String,
+    pub size_mb: u32,
+    pub multilingual: bool,
+    pub downloaded: bool,
+    pub selected: bool,
+}
+
+pub fn find(name: &str) -> Result<&'static ModelSpec, ModelError> {
+    MODELS
+        .iter()
+        .find(|m| m.name == name)
+        .ok_or_else(|| ModelError::UnknownModel(name.to_string()))
+}
+
+/// The ggml file a model is stored as, like `ggml-base.en.bin`
+pub fn file_name(name: &str) -> String {
+    format!("ggml-{}.bin", name)
+}
+
+/// Every known model, with whether it's in `models_dir` and whether its
+/// file is `selected`
+pub fn list(models_dir: &Path, selected: &Path) -> Vec<AvailableModel> {
+    MODELS
+        .iter()
+        .map(|m| {
+            let path = models_dir.join(m.file_name());
+            AvailableModel {
+                name: m.name,
+                file_name: m.file_name(),
+                size_mb: m.size_mb,
+                multilingual: m.multilingual(),
+                downloaded: path.exists(),
+                selected: path == selected,
+            }
+        })
+        .collect()
+}
+
+/// How far a download has got, counting bytes kept from an earlier attempt
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DownloadProgress {
+    pub bytes_downloaded: u64,
+    /// None when the server doesn't say how big the file is
+    pub total_bytes: Option<u64>,
+    pub percent: Option<f32>,
+}
+
+impl DownloadProgress {
+    fn new(bytes_downloaded: u64, total_bytes: Option<u64>) -> Self {
+        Self {
+            bytes_downloaded,
+            total_bytes,
+            percent: total_bytes
+                .filter(|&t| t > 0)
+                .map(|t| (bytes_downloaded as f64 / t as f64 * 100.0) as f32),
+        }
+    }
+}
+
+/// Fetch `model` into `models_dir`, streaming it to disk under a temporary
+/// name. An interrupted download leaves that file behind and the next
+/// attempt resumes from where it stopped. The file is only moved into place
+/// once its SHA256 matches the one pinned in `MODELS`, so a truncated,
+/// corrupt or swapped model never gets loaded.
+pub fn download(
+    models_dir: &Path,
+    model: &ModelSpec,
+    mut progress: impl FnMut(DownloadProgress),
+) -> Result<PathBuf, ModelError> {
+    std::fs::create_dir_all(models_dir)?;
+    let path = models_dir.join(model.file_name());
+    let partial = path.with_extension("bin.part");
+    let url = format!("{}/{}", DOWNLOAD_BASE_URL, model.file_name());
+
+    // Large models take minutes, far past the default request timeout
+    let client = reqwest::blocking::Client::builder()
+        .timeout(None::<std::time::Duration>)
+        .build()?;
+    let existing = std::fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+    let mut request = client.get(&url);
+    if existing > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+    }
+    let mut response = request.send()?;
+
+    match response.status() {
+        // What's on disk already covers the whole file
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE => {}
+        status if status.is_success() => {
+            let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+            let kept = if resumed { existing } else { 0 };
+            let total = response.content_length().map(|len| len + kept);
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(resumed)
+                .write(true)
+                .truncate(!resumed)
+                .open(&partial)?;
+            save_body(&mut response, file, kept, total, &mut progress)?;
+        }
+        status => return Err(ModelError::ServerError(status)),
+    }
+
+    let actual = sha256_file(&partial)?;
+    if !actual.eq_ignore_ascii_case(model.sha256) {
+        // Resuming would only append to the same bad bytes
+        let _ = std::fs::remove_file(&partial);
+        return Err(ModelError::ChecksumMismatch { expected: model.sha256.to_string(), actual });
+    }
+    std::fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+fn save_body(
+    body: &mut impl Read,
+    file: std::fs::File,
+    mut got: u64,
+    expected: Option<u64>,
+    progress: &mut impl FnMut(DownloadProgress),
+) -> Result<(), ModelError> {
+    let mut file = std::io::BufWriter::new(file);
+    let mut buffer = vec![0u8; 1 << 16];
+    loop {
+        let read = body.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])?;
+        got += read as u64;
+        progress(DownloadProgress::new(got, expected));
+    }
+    file.flush()?;
+    match expected {
+        Some(expected) if got != expected => Err(ModelError::Incomplete { got, expected }),
+        _ => Ok(()),
+    }
+}
+
+fn sha256_file(path: &Path) -> Result<String, ModelError> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Remove a downloaded model; one that isn't there is already gone
+pub fn delete(models_dir: &Path, model: &ModelSpec) -> Result<(), ModelError> {
+    match std::fs::remove_file(models_dir.join(model.file_name())) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_model_has_a_pinned_checksum() {
+        for model in MODELS {
+            assert_eq!(model.sha256.len(), 64, "{}", model.name);
+            assert!(model.sha256.chars().all(|c| c.is_ascii_hexdigit()), "{}", model.name);
+        }
+    }
+
+    #[test]
+    fn hashes_a_file() {
+        let path = std::env::temp_dir().join(format!("model-{}.bin", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"abc").unwrap();
+        let hash = sha256_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(hash, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+}
@@ -0,0 +1,322 @@
+use crate::metrics::FluencyMetrics;
+use chrono::{DateTime, Datelike};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+// Context for a reading score: where it sits in this classroom and against
+// published grade-level norms. Everything is computed on the device from
+// local assessments; nothing is sent anywhere.
+
+/// Used unless a `grade_norms` setting, usually pushed with the org or
+/// classroom config, replaces it
+pub const DEFAULT_NORMS: &str = include_str!("../data/orf_norms.json");
+
+/// Metrics a norms table may be keyed on
+const METRICS: &[&str] = &["wpm", "wcpm", "accuracy"];
+
+/// Highest percentile reported, however far past the table a score is
+const MAX_PERCENTILE: f64 = 99.0;
+
+#[derive(Error, Debug)]
+pub enum NormsError {
+    #[error("Invalid norms table: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("Unknown metric in norms table: {0}")]
+    UnknownMetric(String),
+    #[error("Norms for grade {0} need at least one percentile")]
+    Empty(String),
+    #[error("Invalid norms CSV at line {line}: {message}")]
+    CsvError { line: usize, message: String },
+}
+
+/// Part of the school year a norm was measured in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Season {
+    Fall,
+    Winter,
+    Spring,
+}
+
+impl Season {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "fall" | "autumn" => Some(Season::Fall),
+            "winter" => Some(Season::Winter),
+            "spring" => Some(Season::Spring),
+            _ => None,
+        }
+    }
+
+    /// Season of a northern-hemisphere school year: fall from August,
+    /// winter from December, spring from April
+    pub fn of(recorded_at: &str) -> Option<Self> {
+        let month = DateTime::parse_from_rfc3339(recorded_at).ok()?.month();
+        Some(match month {
+            8..=11 => Season::Fall,
+            12 | 1..=3 => Season::Winter,
+            _ => Season::Spring,
+        })
+    }
+}
+
+/// Grade-level norms for one metric, e.g.
+///
+/// ```json
+/// { "name": "District ORF", "metric": "wcpm", "norms": [
+///     { "grade": "2", "season": "fall", "percentiles": { "10": 23, "50": 50, "90": 111 } } ] }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormsTable {
+    pub name: String,
+    pub metric: String,
+    /// Counts up each time a table of this name is imported on the device;
+    /// None for the built-in table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    pub norms: Vec<GradeNorms>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradeNorms {
+    pub grade: String,
+    pub season: Season,
+    /// Score at each percentile
+    pub percentiles: BTreeMap<u8, f64>,
+}
+
+impl NormsTable {
+    pub fn from_json(json: &str) -> Result<Self, NormsError> {
+        serde_json::from_str::<NormsTable>(json)?.validate()
+    }
+
+    /// Read a table of `metric` from CSV: a header row of `grade`, `season`
+    /// and the percentiles, then the scores for one grade and season a row.
+    ///
+    /// ```text
+    /// grade,season,p10,p25,p50,p75,p90
+    /// 2,fall,23,36,50,84,111
+    /// ```
+    ///
+    /// Percentile columns may be written `10`, `p10`, `10th` or `10%`. A
+    /// blank cell leaves that percentile out for the row.
+    pub fn from_csv(name: &str, metric: &str, csv: &str) -> Result<Self, NormsError> {
+        // Spreadsheets often save with a byte order mark
+        let mut rows = csv
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let csv_error = |line: usize, message: String| NormsError::CsvError { line, message };
+
+        let (header_line, header) = rows.next().ok_or_else(|| csv_error(1, "no header row".to_string()))?;
+        let columns: Vec<String> = header.split(',').map(|c| c.trim().to_ascii_lowercase()).collect();
+        if columns.len() < 3 || columns[0] != "grade" || columns[1] != "season" {
+            return Err(csv_error(
+                header_line,
+                "header must start with grade,season then the percentiles".to_string(),
+            ));
+        }
+        let percentiles = columns[2..]
+            .iter()
+            .map(|column| {
+                let number = column.trim_start_matches('p').trim_end_matches('%').trim_end_matches("th");
+                number
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|p| (1..100).contains(p))
+                    .ok_or_else(|| csv_error(header_line, format!("\"{}\" isn't a percentile", column)))
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        let mut norms: Vec<GradeNorms> = Vec::new();
+        for (line, row) in rows {
+            let cells: Vec<&str> = row.split(',').map(str::trim).collect();
+            if cells.len() > columns.len() {
+                return Err(csv_error(line, format!("expected {} columns, found {}", columns.len(), cells.len())));
+            }
+            let grade = cells[0].to_string();
+            if grade.is_empty() {
+                return Err(csv_error(line, "missing grade".to_string()));
+            }
+            let season = cells
+                .get(1)
+                .and_then(|s| Season::parse(s))
+                .ok_or_else(|| csv_error(line, "season must be fall, winter or spring".to_string()))?;
+            if norms.iter().any(|n| n.grade == grade && n.season == season) {
+                return Err(csv_error(line, format!("grade {} appears twice for the same season", grade)));
+            }
+            let mut scores = BTreeMap::new();
+            for (&percentile, cell) in percentiles.iter().zip(cells.iter().skip(2)) {
+                if cell.is_empty() {
+                    continue;
+                }
+                let score = cell
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite() && *v >= 0.0)
+                    .ok_or_else(|| csv_error(line, format!("\"{}\" isn't a score", cell)))?;
+                scores.insert(percentile, score);
+            }
+            norms.push(GradeNorms { grade, season, percentiles: scores });
+        }
+
+        NormsTable {
+            name: name.to_string(),
+            metric: metric.to_string(),
+            version: None,
+            norms,
+        }
+        .validate()
+    }
+
+    fn validate(self) -> Result<Self, NormsError> {
+        if !METRICS.contains(&self.metric.as_str()) {
+            return Err(NormsError::UnknownMetric(self.metric));
+        }
+        if let Some(empty) = self.norms.iter().find(|n| n.percentiles.is_empty()) {
+            return Err(NormsError::Empty(empty.grade.clone()));
+        }
+        Ok(self)
+    }
+
+    pub fn find(&self, grade: &str, season: Season) -> Option<&GradeNorms> {
+        self.norms
+            .iter()
+            .find(|n| n.grade.eq_ignore_ascii_case(grade) && n.season == season)
+    }
+}
+
+impl GradeNorms {
+    /// Estimated percentile of `value`, interpolating between the table's
+    /// points. Below the lowest point it falls linearly to 0 at a score of
+    /// 0; above the highest it continues the last step, up to 99.
+    pub fn percentile_of(&self, value: f64) -> f64 {
+        let points: Vec<(f64, f64)> = self.percentiles.iter().map(|(&p, &v)| (v, p as f64)).collect();
+        let mut below = (0.0, 0.0);
+        for &(score, percentile) in &points {
+            if value <= score {
+                return interpolate(below, (score, percentile), value);
+            }
+            below = (score, percentile);
+        }
+        let extended = match points.as_slice() {
+            [.., a, b] if b.0 > a.0 => interpolate(*a, *b, value),
+            _ => MAX_PERCENTILE,
+        };
+        extended.max(below.1).min(MAX_PERCENTILE)
+    }
+
+    pub fn median(&self) -> Option<f64> {
+        self.percentiles.get(&50).copied()
+    }
+}
+
+fn interpolate((x0, y0): (f64, f64), (x1, y1): (f64, f64), x: f64) -> f64 {
+    if x1 <= x0 {
+        return y1;
+    }
+    y0 + (x - x0) / (x1 - x0) * (y1 - y0)
+}
+
+pub fn metric_value(metrics: &FluencyMetrics, metric: &str) -> Option<f64> {
+    match metric {
+        "wpm" => Some(metrics.wpm),
+        "wcpm" => Some(metrics.wcpm),
+        "accuracy" => Some(metrics.accuracy),
+        _ => None,
+    }
+}
+
+/// One metric of an assessment next to the rest of the classroom
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassroomComparison {
+    pub metric: &'static str,
+    pub value: f64,
+    /// Median of each student's latest assessment
+    pub classroom_median: f64,
+    /// Share of those students scoring below this one, counting ties as
+    /// half, as a percentage
+    pub classroom_percentile: f64,
+    pub students: usize,
+}
+
+/// The assessment against the norms table's grade and season
+#[derive(Debug, Clone, Serialize)]
+pub struct NormsComparison {
+    pub table: String,
+    /// Which import of `table` produced the percentile
+    pub table_version: Option<u32>,
+    pub metric: String,
+    pub grade: String,
+    pub season: Season,
+    pub value: f64,
+    pub percentile: f64,
+    pub grade_median: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CohortComparison {
+    pub recording_id: String,
+    pub classroom: Vec<ClassroomComparison>,
+    /// None without a grade, or when the table has nothing for it
+    pub norms: Option<NormsComparison>,
+}
+
+/// `metrics` against `cohort`, the latest scored metrics of each student in
+/// the classroom
+pub fn compare_to_classroom(metrics: &FluencyMetrics, cohort: &[FluencyMetrics]) -> Vec<ClassroomComparison> {
+    METRICS
+        .iter()
+        .filter_map(|&metric| {
+            let value = metric_value(metrics, metric)?;
+            let mut values: Vec<f64> = cohort.iter().filter_map(|m| metric_value(m, metric)).collect();
+            if values.is_empty() {
+                return None;
+            }
+            values.sort_by(f64::total_cmp);
+            let below = values.iter().filter(|&&v| v < value).count() as f64;
+            let ties = values.iter().filter(|&&v| v == value).count() as f64;
+            Some(ClassroomComparison {
+                metric,
+                value,
+                classroom_median: median(&values),
+                classroom_percentile: (below + ties / 2.0) / values.len() as f64 * 100.0,
+                students: values.len(),
+            })
+        })
+        .collect()
+}
+
+/// `metrics` against `grade`'s norms for the season it was recorded in
+pub fn compare_to_norms(
+    table: &NormsTable,
+    metrics: &FluencyMetrics,
+    grade: &str,
+    recorded_at: &str,
+) -> Option<NormsComparison> {
+    let season = Season::of(recorded_at)?;
+    let norms = table.find(grade, season)?;
+    let value = metric_value(metrics, &table.metric)?;
+    Some(NormsComparison {
+        table: table.name.clone(),
+        table_version: table.version,
+        metric: table.metric.clone(),
+        grade: norms.grade.clone(),
+        season,
+        value,
+        percentile: norms.percentile_of(value),
+        grade_median: norms.median(),
+    })
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
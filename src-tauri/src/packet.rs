@@ -0,0 +1,203 @@
+use crate::db::Recording;
+use crate::locale::Locale;
+use crate::metrics::{self, AlignOp};
+use crate::pdf::{self, Color, Font, Page, PdfDocument, BLACK, PAGE_HEIGHT, PAGE_WIDTH};
+use crate::qr::QrCode;
+use chrono::{DateTime, Local};
+
+// A printable page for a student to look back over their own reading:
+// what they read in large print, words that didn't match the passage
+// highlighted, a star rating for their speed and a QR code to listen again.
+
+/// Words per minute needed for each star after the first
+const STAR_WPM: [f64; 4] = [40.0, 70.0, 100.0, 130.0];
+pub const MAX_STARS: usize = STAR_WPM.len() + 1;
+
+const MARGIN: f64 = 54.0;
+const TRANSCRIPT_SIZE: f64 = 22.0;
+const LINE_HEIGHT: f64 = 36.0;
+const STAR_SIZE: f64 = 36.0;
+const QR_SIZE: f64 = 120.0;
+
+const MISCUE_HIGHLIGHT: Color = (1.0, 0.88, 0.4);
+const SKIPPED_TEXT: Color = (0.55, 0.55, 0.55);
+const STAR_EARNED: Color = (0.98, 0.75, 0.1);
+const STAR_UNEARNED: Color = (0.88, 0.88, 0.88);
+
+/// What the packet needs beyond the recording itself
+pub struct PacketContent<'a> {
+    pub student_name: &'a str,
+    pub recording: &'a Recording,
+    pub wpm: f64,
+    /// Where the recording can be listened to, when it's on the server
+    pub listen_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WordKind {
+    Read,
+    /// Read differently from the passage, or not in it at all
+    Miscue,
+    /// In the passage but not read
+    Skipped,
+}
+
+/// Star rating out of `MAX_STARS`; everyone who reads gets at least one
+pub fn stars(wpm: f64) -> usize {
+    1 + STAR_WPM.iter().filter(|&&needed| wpm >= needed).count()
+}
+
+/// Whitespace-separated words that still have letters or digits once
+/// punctuation is stripped, with their normalized form for alignment
+fn tokens(text: &str) -> Vec<(&str, String)> {
+    text.split_whitespace()
+        .filter_map(|word| metrics::normalize_words(word).pop().map(|normalized| (word, normalized)))
+        .collect()
+}
+
+/// The transcript's words, marked against the passage when there is one.
+/// Skipped passage words are placed where they were missed.
+fn marked_words<'a>(transcript: &'a str, passage: Option<&'a str>) -> Vec<(&'a str, WordKind)> {
+    let spoken = tokens(transcript);
+    let Some(passage) = passage else {
+        return spoken.into_iter().map(|(word, _)| (word, WordKind::Read)).collect();
+    };
+    let passage = tokens(passage);
+    let ops = metrics::align(
+        &passage.iter().map(|(_, n)| n.clone()).collect::<Vec<_>>(),
+        &spoken.iter().map(|(_, n)| n.clone()).collect::<Vec<_>>(),
+    );
+
+    let (mut i, mut j) = (0, 0);
+    let mut words = Vec::with_capacity(ops.len());
+    for op in ops {
+        match op {
+            AlignOp::Match => {
+                words.push((spoken[j].0, WordKind::Read));
+                i += 1;
+                j += 1;
+            }
+            AlignOp::Substitution => {
+                words.push((spoken[j].0, WordKind::Miscue));
+                i += 1;
+                j += 1;
+            }
+            AlignOp::Insertion => {
+                words.push((spoken[j].0, WordKind::Miscue));
+                j += 1;
+            }
+            AlignOp::Omission => {
+                words.push((passage[i].0, WordKind::Skipped));
+                i += 1;
+            }
+        }
+    }
+    words
+}
+
+/// Five-pointed star centred on `(x, y)`
+fn star(page: &mut Page, x: f64, y: f64, radius: f64, color: Color) {
+    let points: Vec<(f64, f64)> = (0..10)
+        .map(|i| {
+            let angle = std::f64::consts::FRAC_PI_2 + i as f64 * std::f64::consts::PI / 5.0;
+            let r = if i % 2 == 0 { radius } else { radius * 0.4 };
+            (x + r * angle.cos(), y + r * angle.sin())
+        })
+        .collect();
+    page.fill_polygon(&points, color);
+}
+
+/// QR code with its top left corner at `(x, top)`
+fn qr_code(page: &mut Page, qr: &QrCode, x: f64, top: f64, size: f64) {
+    let module = size / qr.size() as f64;
+    for row in 0..qr.size() {
+        for col in 0..qr.size() {
+            if qr.is_dark(col, row) {
+                // A hair oversized so neighbouring modules don't show seams
+                page.fill_rect(
+                    x + col as f64 * module,
+                    top - (row + 1) as f64 * module,
+                    module + 0.1,
+                    module + 0.1,
+                    BLACK,
+                );
+            }
+        }
+    }
+}
+
+/// Render the packet as a PDF, continuing the transcript onto more pages
+/// if it doesn't fit on one
+pub fn review_packet(content: &PacketContent, locale: &Locale) -> Vec<u8> {
+    let mut doc = PdfDocument::default();
+    let page = doc.add_page();
+    let recording = content.recording;
+
+    let mut y = PAGE_HEIGHT - MARGIN - 32.0;
+    page.text(MARGIN, y, 32.0, Font::Bold, BLACK, "My Reading");
+    y -= 28.0;
+    let date = DateTime::parse_from_rfc3339(&recording.recorded_at)
+        .map(|dt| locale.date(&dt.with_timezone(&Local)))
+        .unwrap_or_else(|_| recording.recorded_at.clone());
+    page.text(MARGIN, y, 16.0, Font::Regular, BLACK, &format!("{}  \u{b7}  {}", content.student_name, date));
+
+    y -= 24.0 + STAR_SIZE / 2.0;
+    let earned = stars(content.wpm);
+    for i in 0..MAX_STARS {
+        let color = if i < earned { STAR_EARNED } else { STAR_UNEARNED };
+        star(page, MARGIN + STAR_SIZE / 2.0 + i as f64 * (STAR_SIZE + 6.0), y, STAR_SIZE / 2.0, color);
+    }
+    y -= STAR_SIZE / 2.0 + 24.0;
+    let speed = format!("You read {} words a minute!", locale.number(content.wpm, 0));
+    page.text(MARGIN, y, 16.0, Font::Regular, BLACK, &speed);
+
+    if let Some(qr) = content.listen_url.as_deref().and_then(|url| QrCode::encode(url).ok()) {
+        let x = PAGE_WIDTH - MARGIN - QR_SIZE;
+        let top = PAGE_HEIGHT - MARGIN;
+        qr_code(page, &qr, x, top, QR_SIZE);
+        let caption = "Scan to listen";
+        let width = pdf::text_width(caption, 12.0, Font::Regular);
+        page.text(x + (QR_SIZE - width) / 2.0, top - QR_SIZE - 16.0, 12.0, Font::Regular, BLACK, caption);
+    }
+
+    let words = marked_words(
+        recording.transcript.as_deref().unwrap_or(""),
+        recording.reference_passage.as_deref(),
+    );
+    let has_marks = words.iter().any(|(_, kind)| *kind != WordKind::Read);
+
+    let space = pdf::text_width(" ", TRANSCRIPT_SIZE, Font::Regular);
+    let right = PAGE_WIDTH - MARGIN;
+    let bottom = MARGIN + if has_marks { 48.0 } else { 0.0 };
+    y -= LINE_HEIGHT + 12.0;
+    let mut x = MARGIN;
+    let mut page = page;
+    for (word, kind) in words {
+        let width = pdf::text_width(word, TRANSCRIPT_SIZE, Font::Regular);
+        if x > MARGIN && x + width > right {
+            x = MARGIN;
+            y -= LINE_HEIGHT;
+        }
+        if y < bottom {
+            page = doc.add_page();
+            y = PAGE_HEIGHT - MARGIN - TRANSCRIPT_SIZE;
+        }
+        let color = match kind {
+            WordKind::Read => BLACK,
+            WordKind::Miscue => {
+                page.fill_rect(x - 2.0, y - 6.0, width + 4.0, TRANSCRIPT_SIZE + 4.0, MISCUE_HIGHLIGHT);
+                BLACK
+            }
+            WordKind::Skipped => SKIPPED_TEXT,
+        };
+        page.text(x, y, TRANSCRIPT_SIZE, Font::Regular, color, word);
+        x += width + space;
+    }
+
+    if has_marks {
+        page.fill_rect(MARGIN, MARGIN + 14.0, 14.0, 14.0, MISCUE_HIGHLIGHT);
+        page.text(MARGIN + 20.0, MARGIN + 16.0, 12.0, Font::Regular, BLACK, "Words to practice");
+        page.text(MARGIN + 180.0, MARGIN + 16.0, 12.0, Font::Regular, SKIPPED_TEXT, "Words you skipped");
+    }
+    doc.to_bytes()
+}
@@ -0,0 +1,250 @@
+// Minimal PDF writing for printouts. Only what they need is implemented:
+// US Letter pages of text in the built-in Helvetica fonts, which every
+// viewer has, plus filled shapes. Text is WinAnsi-encoded, so characters
+// outside Latin-1 print as '?'.
+
+use std::io::Write;
+
+pub const PAGE_WIDTH: f64 = 612.0;
+pub const PAGE_HEIGHT: f64 = 792.0;
+
+pub type Color = (f64, f64, f64);
+pub const BLACK: Color = (0.0, 0.0, 0.0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// Helvetica advance widths for ' ' to '~', in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // ' ' to '/'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // '0' to '?'
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // '@' to 'O'
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // 'P' to '_'
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // '`' to 'o'
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // 'p' to '~'
+];
+/// Helvetica-Bold runs about this much wider than the regular weight
+const BOLD_WIDTH_FACTOR: f64 = 1.06;
+
+/// Width of `text` set in `font` at `size` points
+pub fn text_width(text: &str, size: f64, font: Font) -> f64 {
+    let units: u32 = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => HELVETICA_WIDTHS[c as usize - 32] as u32,
+            _ => 556,
+        })
+        .sum();
+    let width = units as f64 / 1000.0 * size;
+    match font {
+        Font::Regular => width,
+        Font::Bold => width * BOLD_WIDTH_FACTOR,
+    }
+}
+
+/// One page's content stream. Coordinates are points from the bottom left.
+#[derive(Default)]
+pub struct Page {
+    content: Vec<u8>,
+}
+
+impl Page {
+    pub fn text(&mut self, x: f64, y: f64, size: f64, font: Font, color: Color, text: &str) {
+        let _ = write!(
+            self.content,
+            "BT {:.3} {:.3} {:.3} rg /{} {:.1} Tf {:.2} {:.2} Td (",
+            color.0,
+            color.1,
+            color.2,
+            font.resource(),
+            size,
+            x,
+            y
+        );
+        for c in text.chars() {
+            let byte = match c as u32 {
+                0x20..=0x7e | 0xa0..=0xff => c as u8,
+                _ => b'?',
+            };
+            if matches!(byte, b'(' | b')' | b'\\') {
+                self.content.push(b'\\');
+            }
+            self.content.push(byte);
+        }
+        self.content.extend_from_slice(b") Tj ET\n");
+    }
+
+    pub fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
+        let _ = writeln!(
+            self.content,
+            "{:.3} {:.3} {:.3} rg {:.2} {:.2} {:.2} {:.2} re f",
+            color.0, color.1, color.2, x, y, width, height
+        );
+    }
+
+    pub fn fill_polygon(&mut self, points: &[(f64, f64)], color: Color) {
+        let Some(((x, y), rest)) = points.split_first() else {
+            return;
+        };
+        let _ = write!(self.content, "{:.3} {:.3} {:.3} rg {:.2} {:.2} m", color.0, color.1, color.2, x, y);
+        for (x, y) in rest {
+            let _ = write!(self.content, " {:.2} {:.2} l", x, y);
+        }
+        self.content.extend_from_slice(b" h f\n");
+    }
+}
+
+#[derive(Default)]
+pub struct PdfDocument {
+    pages: Vec<Page>,
+}
+
+impl PdfDocument {
+    pub fn add_page(&mut self) -> &mut Page {
+        self.pages.push(Page::default());
+        self.pages.last_mut().expect("just pushed")
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // Objects: 1 catalog, 2 page tree, 3-4 fonts, then a page and its
+        // content stream for each page
+        let mut out = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        let mut object = |out: &mut Vec<u8>, body: &[u8]| {
+            offsets.push(out.len());
+            let _ = writeln!(out, "{} 0 obj", offsets.len());
+            out.extend_from_slice(body);
+            out.extend_from_slice(b"\nendobj\n");
+        };
+
+        object(&mut out, b"<< /Type /Catalog /Pages 2 0 R >>");
+        let kids: Vec<String> = (0..self.pages.len()).map(|i| format!("{} 0 R", 5 + i * 2)).collect();
+        object(
+            &mut out,
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), self.pages.len()).as_bytes(),
+        );
+        for name in ["Helvetica", "Helvetica-Bold"] {
+            object(
+                &mut out,
+                format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>", name).as_bytes(),
+            );
+        }
+        for (i, page) in self.pages.iter().enumerate() {
+            object(
+                &mut out,
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                     /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    PAGE_WIDTH,
+                    PAGE_HEIGHT,
+                    6 + i * 2
+                )
+                .as_bytes(),
+            );
+            let mut stream = format!("<< /Length {} >>\nstream\n", page.content.len()).into_bytes();
+            stream.extend_from_slice(&page.content);
+            stream.extend_from_slice(b"\nendstream");
+            object(&mut out, &stream);
+        }
+
+        let xref = out.len();
+        let _ = writeln!(out, "xref\n0 {}\n0000000000 65535 f ", offsets.len() + 1);
+        for offset in &offsets {
+            let _ = writeln!(out, "{:010} 00000 n ", offset);
+        }
+        let _ = writeln!(
+            out,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF",
+            offsets.len() + 1,
+            xref
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
+
+    fn document(pages: usize) -> Vec<u8> {
+        let mut doc = PdfDocument::default();
+        for i in 0..pages {
+            let page = doc.add_page();
+            page.text(72.0, 720.0, 12.0, Font::Bold, BLACK, &format!("Page {}", i + 1));
+            page.fill_rect(72.0, 700.0, 100.0, 2.0, (0.5, 0.5, 0.5));
+            page.fill_polygon(&[(72.0, 600.0), (82.0, 610.0), (92.0, 600.0)], BLACK);
+        }
+        doc.to_bytes()
+    }
+
+    #[test]
+    fn xref_offsets_point_at_their_objects() {
+        let bytes = document(3);
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        let startxref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(bytes[startxref..].starts_with(b"xref\n0 11\n"));
+
+        let entries: Vec<usize> = text[startxref..]
+            .lines()
+            .skip(3)
+            .take_while(|l| l.ends_with(" n "))
+            .map(|l| l[..10].parse().unwrap())
+            .collect();
+        assert_eq!(entries.len(), 10);
+        for (i, offset) in entries.iter().enumerate() {
+            assert!(bytes[*offset..].starts_with(format!("{} 0 obj\n", i + 1).as_bytes()), "object {}", i + 1);
+        }
+        assert!(text.contains("/Count 3 >>"));
+        assert!(text.contains("/Kids [5 0 R 7 0 R 9 0 R]"));
+        assert!(text.ends_with("%%EOF\n"));
+    }
+
+    #[test]
+    fn stream_lengths_match_their_content() {
+        let bytes = document(2);
+        let mut rest = &bytes[..];
+        let mut streams = 0;
+        while let Some(at) = find(rest, b"<< /Length ") {
+            rest = &rest[at + 11..];
+            let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+            let length: usize = std::str::from_utf8(&rest[..digits]).unwrap().parse().unwrap();
+            let start = find(rest, b"stream\n").unwrap() + 7;
+            assert!(rest[start + length..].starts_with(b"\nendstream"));
+            streams += 1;
+        }
+        assert_eq!(streams, 2);
+    }
+
+    #[test]
+    fn text_is_escaped_and_winansi_encoded() {
+        let mut page = Page::default();
+        page.text(0.0, 0.0, 10.0, Font::Regular, BLACK, "a(b)c\\ café – 日本");
+        let content = page.content;
+        assert!(find(&content, b"(a\\(b\\)c\\\\ caf\xe9 ? ??) Tj ET\n").is_some());
+        assert!(find(&content, b"/F1 10.0 Tf").is_some());
+    }
+
+    #[test]
+    fn text_width_uses_helvetica_metrics() {
+        // H e l l o = 722 + 556 + 222 + 222 + 556
+        assert!((text_width("Hello", 10.0, Font::Regular) - 22.78).abs() < 1e-9);
+        assert!((text_width("Hello", 10.0, Font::Bold) - 22.78 * BOLD_WIDTH_FACTOR).abs() < 1e-9);
+        assert_eq!(text_width("", 12.0, Font::Regular), 0.0);
+    }
+}
@@ -0,0 +1,395 @@
+use crate::whisper::TranscriptWord;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PipelineError {
+    #[error("Invalid pipeline: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("Stage '{0}' appears more than once")]
+    Duplicate(&'static str),
+    #[error("Stage '{0}' must come after '{1}'")]
+    OutOfOrder(&'static str, &'static str),
+    #[error("Script '{0}' needs a timeout of 1 to {max} seconds", max = crate::hooks::MAX_TIMEOUT_SECONDS)]
+    ScriptTimeout(String),
+}
+
+/// What happens to a recording after it stops, in order, e.g.
+///
+/// ```json
+/// [
+///   { "stage": "vad-trim", "threshold_dbfs": -45 },
+///   { "stage": "normalize", "enabled": false },
+///   { "stage": "transcribe" },
+///   { "stage": "redact", "terms": ["Room 12"], "numbers": true },
+///   { "stage": "mask", "terms": ["detention"] },
+///   { "stage": "script", "name": "district-nlp", "timeout_seconds": 60 },
+///   { "stage": "metrics" },
+///   { "stage": "sync" }
+/// ]
+/// ```
+///
+/// Saving the audio always happens first. Stages left out don't run. Only
+/// `script` may appear more than once, and only in a pipeline set on this
+/// device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Pipeline {
+    pub stages: Vec<Stage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stage {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub config: StageConfig,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "kebab-case")]
+pub enum StageConfig {
+    /// Cut leading and trailing silence from the saved audio
+    VadTrim {
+        #[serde(default = "default_trim_threshold")]
+        threshold_dbfs: f32,
+        /// Silence kept either side of the speech
+        #[serde(default = "default_trim_padding")]
+        padding_ms: u32,
+    },
+    /// Loudness-normalize the saved audio before transcription
+    Normalize {
+        #[serde(default = "default_target_lufs")]
+        target_lufs: f64,
+    },
+    Transcribe,
+    /// Mask terms in the transcript before it is stored or synced
+    Redact {
+        #[serde(default)]
+        terms: Vec<String>,
+        /// Also mask the configured student name
+        #[serde(default = "enabled")]
+        student_name: bool,
+        /// Also mask runs of 7 or more digits, such as phone numbers
+        #[serde(default)]
+        numbers: bool,
+    },
+    /// Mask profanity and sensitive terms in the transcript that's stored
+    /// and synced, for sharing with parents and administrators. Unlike
+    /// redacting, the unmasked transcript is kept on the device.
+    Mask {
+        /// Mask the built-in list of profanity
+        #[serde(default = "enabled")]
+        profanity: bool,
+        #[serde(default)]
+        terms: Vec<String>,
+    },
+    /// Pass the transcript through an executable from the hooks folder; see
+    /// `hooks::run`
+    Script {
+        /// File name inside the hooks folder
+        name: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default = "default_script_timeout")]
+        timeout_seconds: u64,
+    },
+    /// Activity tag and fluency scores
+    Metrics,
+    Sync,
+}
+
+fn default_trim_threshold() -> f32 {
+    -45.0
+}
+
+fn default_trim_padding() -> u32 {
+    250
+}
+
+fn default_script_timeout() -> u64 {
+    30
+}
+
+fn default_target_lufs() -> f64 {
+    crate::dsp::EBU_R128_TARGET_LUFS
+}
+
+impl StageConfig {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StageConfig::VadTrim { .. } => "vad-trim",
+            StageConfig::Normalize { .. } => "normalize",
+            StageConfig::Transcribe => "transcribe",
+            StageConfig::Redact { .. } => "redact",
+            StageConfig::Mask { .. } => "mask",
+            StageConfig::Script { .. } => "script",
+            StageConfig::Metrics => "metrics",
+            StageConfig::Sync => "sync",
+        }
+    }
+}
+
+impl Default for Pipeline {
+    /// Behavior before pipelines were configurable: transcribe, score, sync
+    fn default() -> Self {
+        let stage = |enabled, config| Stage { enabled, config };
+        Self {
+            stages: vec![
+                stage(false, StageConfig::VadTrim {
+                    threshold_dbfs: default_trim_threshold(),
+                    padding_ms: default_trim_padding(),
+                }),
+                stage(false, StageConfig::Normalize {
+                    target_lufs: default_target_lufs(),
+                }),
+                stage(true, StageConfig::Transcribe),
+                stage(false, StageConfig::Redact {
+                    terms: Vec::new(),
+                    student_name: true,
+                    numbers: false,
+                }),
+                stage(false, StageConfig::Mask {
+                    profanity: true,
+                    terms: Vec::new(),
+                }),
+                stage(true, StageConfig::Metrics),
+                stage(true, StageConfig::Sync),
+            ],
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn from_json(json: &str) -> Result<Self, PipelineError> {
+        let pipeline: Pipeline = serde_json::from_str(json)?;
+        pipeline.validate()?;
+        Ok(pipeline)
+    }
+
+    /// Audio work has to happen before transcription, transcript work after
+    /// it, and nothing unredacted or unmasked may be synced
+    fn validate(&self) -> Result<(), PipelineError> {
+        let positions = |name: &str| {
+            self.stages
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.config.name() == name)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+        for stage in &self.stages {
+            let name = stage.config.name();
+            if name != "script" && positions(name).len() > 1 {
+                return Err(PipelineError::Duplicate(name));
+            }
+            if let StageConfig::Script { name, timeout_seconds, .. } = &stage.config {
+                if !(1..=crate::hooks::MAX_TIMEOUT_SECONDS).contains(timeout_seconds) {
+                    return Err(PipelineError::ScriptTimeout(name.clone()));
+                }
+            }
+        }
+
+        let rules = [
+            ("transcribe", "vad-trim"),
+            ("transcribe", "normalize"),
+            ("redact", "transcribe"),
+            ("mask", "transcribe"),
+            // Otherwise the unmasked copy would keep what was redacted
+            ("mask", "redact"),
+            ("script", "transcribe"),
+            ("metrics", "transcribe"),
+            ("sync", "transcribe"),
+            ("sync", "redact"),
+            ("sync", "mask"),
+            ("sync", "script"),
+        ];
+        for (later, earlier) in rules {
+            let (later_at, earlier_at) = (positions(later), positions(earlier));
+            if let (Some(l), Some(e)) = (later_at.first(), earlier_at.last()) {
+                if l < e {
+                    return Err(PipelineError::OutOfOrder(later, earlier));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> impl Iterator<Item = &StageConfig> {
+        self.stages.iter().filter(|s| s.enabled).map(|s| &s.config)
+    }
+
+    /// Drop the script stages, returning their names. Pipelines from the
+    /// classroom or org layer go through this, since a script runs with
+    /// this user's permissions and only they should choose to run one.
+    pub fn without_scripts(mut self) -> (Self, Vec<String>) {
+        let mut dropped = Vec::new();
+        self.stages.retain(|stage| match &stage.config {
+            StageConfig::Script { name, .. } => {
+                dropped.push(name.clone());
+                false
+            }
+            _ => true,
+        });
+        (self, dropped)
+    }
+
+    /// The redact stage's options, if it is on
+    pub fn redaction(&self) -> Option<&StageConfig> {
+        self.enabled().find(|c| matches!(c, StageConfig::Redact { .. }))
+    }
+
+    /// The mask stage's options, if it is on
+    pub fn masking(&self) -> Option<&StageConfig> {
+        self.enabled().find(|c| matches!(c, StageConfig::Mask { .. }))
+    }
+}
+
+pub const REDACTED: &str = "[redacted]";
+
+/// Mask whole-word, case-insensitive matches of `terms` (which may span
+/// several words) and, optionally, long digit runs
+pub fn redact(text: &str, terms: &[String], numbers: bool) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut out: Vec<String> = Vec::with_capacity(words.len());
+    let mut i = 0;
+    for (start, end, term) in redacted_runs(&words, terms, numbers) {
+        out.extend(words[i..start].iter().map(|w| w.to_string()));
+        // Keep trailing punctuation after terms so sentences still read
+        let trailing = if term { trailing_punctuation(words[end - 1]) } else { "" };
+        out.push(format!("{}{}", REDACTED, trailing));
+        i = end;
+    }
+    out.extend(words[i..].iter().map(|w| w.to_string()));
+    out.join(" ")
+}
+
+/// Mask timed words the way `redact` masks text, each word of a match on
+/// its own so the timings still line up
+pub fn redact_words(words: &mut [TranscriptWord], terms: &[String], numbers: bool) {
+    let texts: Vec<String> = words.iter().map(|w| w.text.clone()).collect();
+    let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+    for (start, end, term) in redacted_runs(&texts, terms, numbers) {
+        for word in &mut words[start..end] {
+            let trailing = if term { trailing_punctuation(&word.text) } else { "" };
+            word.text = format!("{}{}", REDACTED, trailing);
+        }
+    }
+}
+
+/// Words masked with the `profanity` option, with their common endings
+/// spelled out since matching is by whole word
+pub const PROFANITY: [&str; 40] = [
+    "arse", "arsehole", "ass", "asshole", "assholes", "bastard", "bastards", "bitch", "bitches", "bitching",
+    "bollocks", "bullshit", "crap", "crappy", "cunt", "cunts", "damn", "damned", "dick", "dickhead", "fuck",
+    "fucked", "fucker", "fuckers", "fucking", "fucks", "goddamn", "motherfucker", "motherfucking", "piss",
+    "pissed", "prick", "shit", "shits", "shitty", "shitting", "slut", "twat", "wanker", "whore",
+];
+
+/// Mask whole-word, case-insensitive matches of `terms` (which may span
+/// several words) by starring all but the first letter of each word, so
+/// "damn it" reads "d*** it". Punctuation is kept.
+pub fn mask(text: &str, terms: &[String]) -> String {
+    let originals: Vec<&str> = text.split_whitespace().collect();
+    let mut words: Vec<String> = originals.iter().map(|w| w.to_string()).collect();
+    for (start, end, _) in redacted_runs(&originals, terms, false) {
+        for word in &mut words[start..end] {
+            *word = mask_word(word);
+        }
+    }
+    words.join(" ")
+}
+
+/// Mask timed words the way `mask` masks text
+pub fn mask_words(words: &mut [TranscriptWord], terms: &[String]) {
+    let texts: Vec<String> = words.iter().map(|w| w.text.clone()).collect();
+    let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+    for (start, end, _) in redacted_runs(&texts, terms, false) {
+        for word in &mut words[start..end] {
+            word.text = mask_word(&word.text);
+        }
+    }
+}
+
+fn mask_word(word: &str) -> String {
+    let mut letters = 0;
+    word.chars()
+        .map(|c| {
+            if !c.is_alphanumeric() {
+                return c;
+            }
+            letters += 1;
+            if letters == 1 { c } else { '*' }
+        })
+        .collect()
+}
+
+fn trailing_punctuation(word: &str) -> &str {
+    &word[word.trim_end_matches(|c: char| !c.is_alphanumeric()).len()..]
+}
+
+/// Start and end of each run of `words` to mask, in order, and whether it
+/// matched a term rather than being a number
+fn redacted_runs(words: &[&str], terms: &[String], numbers: bool) -> Vec<(usize, usize, bool)> {
+    let bare = |w: &str| {
+        w.trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase()
+    };
+    let terms: Vec<Vec<String>> = terms
+        .iter()
+        .map(|t| t.split_whitespace().map(bare).collect::<Vec<_>>())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let matched = terms.iter().find(|term| {
+            i + term.len() <= words.len()
+                && term.iter().zip(&words[i..]).all(|(t, w)| *t == bare(w))
+        });
+        if let Some(term) = matched {
+            runs.push((i, i + term.len(), true));
+            i += term.len();
+        } else if numbers && words[i].chars().filter(|c| c.is_ascii_digit()).count() >= 7 {
+            runs.push((i, i + 1, false));
+            i += 1;
+        } else {
+            i += 1;
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_timeouts_are_capped() {
+        let with_timeout = |seconds: u64| {
+            format!(r#"[{{"stage":"transcribe"}},{{"stage":"script","name":"nlp","timeout_seconds":{}}}]"#, seconds)
+        };
+        assert!(Pipeline::from_json(&with_timeout(60)).is_ok());
+        assert!(Pipeline::from_json(&with_timeout(crate::hooks::MAX_TIMEOUT_SECONDS)).is_ok());
+        for seconds in [0, crate::hooks::MAX_TIMEOUT_SECONDS + 1] {
+            assert!(matches!(Pipeline::from_json(&with_timeout(seconds)), Err(PipelineError::ScriptTimeout(_))));
+        }
+    }
+
+    #[test]
+    fn without_scripts_keeps_the_other_stages() {
+        let pipeline = Pipeline::from_json(
+            r#"[{"stage":"transcribe"},{"stage":"script","name":"a"},{"stage":"script","name":"b"},{"stage":"sync"}]"#,
+        )
+        .unwrap();
+        let (pipeline, dropped) = pipeline.without_scripts();
+        assert_eq!(dropped, ["a", "b"]);
+        let names: Vec<&str> = pipeline.stages.iter().map(|s| s.config.name()).collect();
+        assert_eq!(names, ["transcribe", "sync"]);
+    }
+}
@@ -0,0 +1,657 @@
+use crate::poison::LockExt;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PlaybackError {
+    #[error("No output device available")]
+    NoOutputDevice,
+    #[error("Failed to get device config: {0}")]
+    ConfigError(String),
+    #[error("Stream error: {0}")]
+    StreamError(String),
+    #[error("Playback speed must be between 0.5x and 2x")]
+    InvalidSpeed,
+    #[error("Nothing is playing")]
+    NotPlaying,
+}
+
+pub const MIN_SPEED: f32 = 0.5;
+pub const MAX_SPEED: f32 = 2.0;
+
+// Keep roughly 100 ms queued for the output callback
+const BUFFER_SECONDS: f32 = 0.1;
+
+/// Most played audio kept for an echo canceller that isn't draining it
+const ECHO_BACKLOG_SECONDS: f32 = 1.0;
+
+/// Section of the source replayed repeatedly, in source samples
+struct LoopRegion {
+    start: f64,
+    end: f64,
+    /// Repeats left after the current pass; `None` loops until stopped
+    remaining: Option<u32>,
+}
+
+struct Shared {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    /// Read position in source samples
+    position: f64,
+    speed: f32,
+    paused: bool,
+    stopped: bool,
+    loop_region: Option<LoopRegion>,
+    /// Set when the output thread must discard its stretcher state
+    discontinuity: bool,
+    /// Bumped on every start so stale monitors can tell they're done
+    generation: u64,
+}
+
+/// Read-only view of one playback session, usable from another thread
+pub struct PlaybackMonitor {
+    shared: Arc<Mutex<Shared>>,
+    generation: u64,
+}
+
+impl PlaybackMonitor {
+    /// `(position_seconds, paused)` while this session is still active
+    pub fn poll(&self) -> Option<(f64, bool)> {
+        let shared = self.shared.lock_or_recover();
+        if shared.stopped || shared.generation != self.generation {
+            return None;
+        }
+        Some((shared.position / shared.sample_rate as f64, shared.paused))
+    }
+}
+
+/// What the player has handed the speakers lately, as mono at the output
+/// rate, so the recorder can cancel it out of the microphone. Samples are
+/// added as the output callback takes them, silence included, so they line
+/// up in time with what the microphone hears.
+#[derive(Clone)]
+pub struct EchoReference {
+    shared: Arc<Mutex<EchoBuffer>>,
+}
+
+struct EchoBuffer {
+    samples: VecDeque<f32>,
+    sample_rate: u32,
+}
+
+impl EchoReference {
+    fn new() -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(EchoBuffer {
+                samples: VecDeque::new(),
+                sample_rate: 48000,
+            })),
+        }
+    }
+
+    /// Everything played since the last call, and the rate it played at
+    pub fn take(&self) -> (Vec<f32>, u32) {
+        let mut buffer = self.shared.lock_or_recover();
+        (buffer.samples.drain(..).collect(), buffer.sample_rate)
+    }
+}
+
+/// Plays a mono buffer on the default output device. The cpal stream lives
+/// on a dedicated thread, mirroring how `AudioRecorder` owns its input stream.
+pub struct Player {
+    shared: Arc<Mutex<Shared>>,
+    playback_thread: Option<thread::JoinHandle<()>>,
+    echo: EchoReference,
+}
+
+impl Player {
+    pub fn new() -> Self {
+        Self::with_echo_reference(EchoReference::new())
+    }
+
+    /// A player feeding an existing echo reference, so a recorder already
+    /// holding it keeps hearing what is played
+    pub fn with_echo_reference(echo: EchoReference) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                samples: Vec::new(),
+                sample_rate: 16000,
+                position: 0.0,
+                speed: 1.0,
+                paused: false,
+                stopped: true,
+                loop_region: None,
+                discontinuity: false,
+                generation: 0,
+            })),
+            playback_thread: None,
+            echo,
+        }
+    }
+
+    pub fn echo_reference(&self) -> EchoReference {
+        self.echo.clone()
+    }
+
+    pub fn play(&mut self, samples: Vec<f32>, sample_rate: u32, speed: f32) -> Result<(), PlaybackError> {
+        self.start(samples, sample_rate, speed, 0.0, None)
+    }
+
+    /// Play `start..end` (seconds) `times` times, or until stopped if `None`
+    pub fn play_loop(
+        &mut self,
+        samples: Vec<f32>,
+        sample_rate: u32,
+        speed: f32,
+        start: f64,
+        end: f64,
+        times: Option<u32>,
+    ) -> Result<(), PlaybackError> {
+        let rate = sample_rate as f64;
+        let region = LoopRegion {
+            start: start * rate,
+            end: (end * rate).min(samples.len() as f64),
+            remaining: times.map(|t| t.saturating_sub(1)),
+        };
+        let position = region.start;
+        self.start(samples, sample_rate, speed, position, Some(region))
+    }
+
+    fn start(
+        &mut self,
+        samples: Vec<f32>,
+        sample_rate: u32,
+        speed: f32,
+        position: f64,
+        loop_region: Option<LoopRegion>,
+    ) -> Result<(), PlaybackError> {
+        validate_speed(speed)?;
+        self.stop();
+
+        {
+            let mut shared = self.shared.lock_or_recover();
+            shared.samples = samples;
+            shared.sample_rate = sample_rate;
+            shared.position = position;
+            shared.speed = speed;
+            shared.paused = false;
+            shared.stopped = false;
+            shared.loop_region = loop_region;
+            shared.discontinuity = false;
+            shared.generation += 1;
+        }
+
+        // The thread reports whether the output stream started
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let shared = self.shared.clone();
+        let echo = self.echo.clone();
+        let handle = thread::spawn(move || {
+            if let Err(e) = run_output(shared.clone(), echo, ready_tx) {
+                eprintln!("Playback failed: {}", e);
+            }
+            shared.lock_or_recover().stopped = true;
+        });
+        self.playback_thread = Some(handle);
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => {
+                self.stop();
+                Err(e)
+            }
+            Err(_) => {
+                self.stop();
+                Err(PlaybackError::StreamError("Playback thread exited".to_string()))
+            }
+        }
+    }
+
+    pub fn pause(&self) -> Result<(), PlaybackError> {
+        let mut shared = self.shared.lock_or_recover();
+        if shared.stopped {
+            return Err(PlaybackError::NotPlaying);
+        }
+        shared.paused = true;
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), PlaybackError> {
+        let mut shared = self.shared.lock_or_recover();
+        if shared.stopped {
+            return Err(PlaybackError::NotPlaying);
+        }
+        shared.paused = false;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.shared.lock_or_recover().stopped = true;
+        if let Some(handle) = self.playback_thread.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Jump to `seconds` into the source. Seeking outside a looped segment
+    /// ends the loop and plays on from there.
+    pub fn seek(&self, seconds: f64) -> Result<(), PlaybackError> {
+        let mut shared = self.shared.lock_or_recover();
+        if shared.stopped {
+            return Err(PlaybackError::NotPlaying);
+        }
+        let position = (seconds.max(0.0) * shared.sample_rate as f64).min(shared.samples.len() as f64);
+        if shared
+            .loop_region
+            .as_ref()
+            .is_some_and(|r| position < r.start || position >= r.end)
+        {
+            shared.loop_region = None;
+        }
+        shared.position = position;
+        shared.discontinuity = true;
+        Ok(())
+    }
+
+    pub fn set_speed(&self, speed: f32) -> Result<(), PlaybackError> {
+        validate_speed(speed)?;
+        self.shared.lock_or_recover().speed = speed;
+        Ok(())
+    }
+
+    pub fn is_playing(&self) -> bool {
+        let shared = self.shared.lock_or_recover();
+        !shared.stopped && !shared.paused
+    }
+
+    /// Monitor for the current (or most recently started) session
+    pub fn monitor(&self) -> PlaybackMonitor {
+        PlaybackMonitor {
+            shared: self.shared.clone(),
+            generation: self.shared.lock_or_recover().generation,
+        }
+    }
+
+    /// Current position in seconds of source audio
+    pub fn position_seconds(&self) -> f64 {
+        let shared = self.shared.lock_or_recover();
+        shared.position / shared.sample_rate as f64
+    }
+}
+
+fn validate_speed(speed: f32) -> Result<(), PlaybackError> {
+    if (MIN_SPEED..=MAX_SPEED).contains(&speed) {
+        Ok(())
+    } else {
+        Err(PlaybackError::InvalidSpeed)
+    }
+}
+
+/// Open the output stream and keep it fed until playback stops or ends
+fn run_output(
+    shared: Arc<Mutex<Shared>>,
+    echo: EchoReference,
+    ready: mpsc::Sender<Result<(), PlaybackError>>,
+) -> Result<(), PlaybackError> {
+    let queue: Arc<Mutex<VecDeque<f32>>> = Arc::new(Mutex::new(VecDeque::new()));
+
+    let (stream, out_rate, channels) = match open_output(queue.clone(), echo) {
+        Ok(opened) => {
+            let _ = ready.send(Ok(()));
+            opened
+        }
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return Ok(());
+        }
+    };
+
+    let source_rate = shared.lock_or_recover().sample_rate;
+    let mut stretcher = TimeStretcher::new(source_rate);
+    let mut resampler = LinearResampler::new(source_rate, out_rate);
+    let target_queue = (out_rate as f32 * BUFFER_SECONDS) as usize * channels;
+
+    loop {
+        let queued = queue.lock_or_recover().len();
+
+        let block = {
+            let mut state = shared.lock_or_recover();
+            if state.stopped {
+                break;
+            }
+            let end = state
+                .loop_region
+                .as_ref()
+                .map(|r| r.end)
+                .unwrap_or(state.samples.len() as f64);
+
+            // Wrap to the loop start while repeats remain
+            if state.position >= end {
+                if let Some(region) = state.loop_region.as_mut() {
+                    let repeat = match region.remaining {
+                        None => true,
+                        Some(0) => false,
+                        Some(ref mut n) => {
+                            *n -= 1;
+                            true
+                        }
+                    };
+                    if repeat {
+                        let loop_start = region.start;
+                        state.position = loop_start;
+                        state.discontinuity = true;
+                    }
+                }
+            }
+            if state.discontinuity {
+                stretcher.reset();
+                state.discontinuity = false;
+            }
+
+            if state.paused || queued >= target_queue {
+                None
+            } else if state.position >= end {
+                // Let the queue drain, then finish
+                if queued == 0 {
+                    break;
+                }
+                None
+            } else {
+                let speed = state.speed;
+                let position = state.position;
+                let block = stretcher.next_block(&state.samples, position, speed);
+                state.position += stretcher.hop() as f64 * speed as f64;
+                Some(block)
+            }
+        };
+
+        match block {
+            Some(block) => {
+                let mut resampled = Vec::with_capacity(block.len() * 3);
+                resampler.process(&block, &mut resampled);
+                let mut queue = queue.lock_or_recover();
+                for sample in resampled {
+                    for _ in 0..channels {
+                        queue.push_back(sample);
+                    }
+                }
+            }
+            None => thread::sleep(std::time::Duration::from_millis(10)),
+        }
+    }
+
+    drop(stream);
+    Ok(())
+}
+
+fn open_output(
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    echo: EchoReference,
+) -> Result<(cpal::Stream, u32, usize), PlaybackError> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or(PlaybackError::NoOutputDevice)?;
+    let config = device
+        .default_output_config()
+        .map_err(|e| PlaybackError::ConfigError(e.to_string()))?;
+
+    let out_rate = config.sample_rate().0;
+    let channels = config.channels() as usize;
+    {
+        let mut buffer = echo.shared.lock_or_recover();
+        buffer.samples.clear();
+        buffer.sample_rate = out_rate;
+    }
+
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => build_output::<f32>(&device, &config.into(), queue, echo),
+        SampleFormat::I16 => build_output::<i16>(&device, &config.into(), queue, echo),
+        SampleFormat::U16 => build_output::<u16>(&device, &config.into(), queue, echo),
+        _ => return Err(PlaybackError::StreamError("Unsupported sample format".to_string())),
+    }?;
+    stream
+        .play()
+        .map_err(|e| PlaybackError::StreamError(e.to_string()))?;
+
+    Ok((stream, out_rate, channels))
+}
+
+fn build_output<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    echo: EchoReference,
+) -> Result<cpal::Stream, PlaybackError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels.max(1) as usize;
+    let max_backlog = (config.sample_rate.0 as f32 * ECHO_BACKLOG_SECONDS) as usize;
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                let mut queue = queue.lock_or_recover();
+                let mut echo = echo.shared.lock_or_recover();
+                // Every channel carries the same mono sample
+                for (i, out) in data.iter_mut().enumerate() {
+                    let sample = queue.pop_front().unwrap_or(0.0);
+                    if i % channels == 0 {
+                        echo.samples.push_back(sample);
+                    }
+                    *out = T::from_sample(sample);
+                }
+                let excess = echo.samples.len().saturating_sub(max_backlog);
+                echo.samples.drain(..excess);
+            },
+            |err| eprintln!("Playback stream error: {}", err),
+            None,
+        )
+        .map_err(|e| PlaybackError::StreamError(e.to_string()))
+}
+
+/// WSOLA (waveform-similarity overlap-add) time stretcher. Changes tempo
+/// without shifting pitch by picking, for each output frame, the input
+/// frame near the nominal position that best continues the previous one.
+pub struct TimeStretcher {
+    frame: usize,
+    hop: usize,
+    tolerance: usize,
+    window: Vec<f32>,
+    overlap: Vec<f32>,
+    /// Where the previously chosen frame started in the source
+    prev_offset: Option<usize>,
+}
+
+impl TimeStretcher {
+    pub fn new(sample_rate: u32) -> Self {
+        // ~32 ms frames, 50% overlap, ~8 ms search tolerance
+        let frame = ((sample_rate as usize * 32) / 1000).next_power_of_two();
+        let hop = frame / 2;
+        let window = (0..frame)
+            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / frame as f32).cos())
+            .collect();
+        Self {
+            frame,
+            hop,
+            tolerance: frame / 4,
+            window,
+            overlap: vec![0.0; frame],
+            prev_offset: None,
+        }
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Forget the previous frame after a jump in the source position
+    pub fn reset(&mut self) {
+        self.prev_offset = None;
+        self.overlap.iter_mut().for_each(|s| *s = 0.0);
+    }
+
+    /// Produce the next `hop` output samples with the analysis frame
+    /// centred on `position` (in source samples).
+    pub fn next_block(&mut self, source: &[f32], position: f64, speed: f32) -> Vec<f32> {
+        let nominal = position as usize;
+
+        // Unity speed needs no search; keeps 1x playback bit-exact
+        if (speed - 1.0).abs() < f32::EPSILON {
+            self.reset();
+            return (nominal..nominal + self.hop)
+                .map(|i| source.get(i).copied().unwrap_or(0.0))
+                .collect();
+        }
+
+        let offset = match self.prev_offset {
+            Some(prev) => self.best_offset(source, prev + self.hop, nominal),
+            None => {
+                // Starting cold, nothing overlaps the first half of the frame
+                // and it would fade in from silence, dipping the audio when
+                // speed moves off 1x. Lay in the tail of the frame a hop
+                // earlier, which sums with this one's rise to unity gain.
+                for n in 0..self.hop {
+                    let sample = source.get(nominal + n).copied().unwrap_or(0.0);
+                    self.overlap[n] = sample * self.window[n + self.hop];
+                }
+                nominal
+            }
+        };
+        self.prev_offset = Some(offset);
+
+        for n in 0..self.frame {
+            let sample = source.get(offset + n).copied().unwrap_or(0.0);
+            self.overlap[n] += sample * self.window[n];
+        }
+
+        let out: Vec<f32> = self.overlap[..self.hop].to_vec();
+        self.overlap.copy_within(self.hop.., 0);
+        let frame = self.frame;
+        self.overlap[frame - self.hop..].iter_mut().for_each(|s| *s = 0.0);
+        out
+    }
+
+    /// Search around `nominal` for the frame most similar to the natural
+    /// continuation of the previous frame (which starts at `continuation`).
+    fn best_offset(&self, source: &[f32], continuation: usize, nominal: usize) -> usize {
+        let start = nominal.saturating_sub(self.tolerance);
+        let end = nominal + self.tolerance;
+        let len = self.hop;
+
+        if continuation + len > source.len() {
+            return nominal;
+        }
+        let reference = &source[continuation..continuation + len];
+
+        let mut best = nominal;
+        let mut best_score = f32::MIN;
+        for candidate in start..=end {
+            if candidate + len > source.len() {
+                break;
+            }
+            let score: f32 = source[candidate..candidate + len]
+                .iter()
+                .zip(reference)
+                .map(|(a, b)| a * b)
+                .sum();
+            if score > best_score {
+                best_score = score;
+                best = candidate;
+            }
+        }
+        best
+    }
+}
+
+/// Streaming linear-interpolation resampler for playback to the device rate
+struct LinearResampler {
+    step: f64,
+    pos: f64,
+    prev: f32,
+}
+
+impl LinearResampler {
+    fn new(from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            step: from_rate as f64 / to_rate as f64,
+            pos: 0.0,
+            prev: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        // Index 0 is the last sample of the previous block
+        let len = input.len() as f64;
+        while self.pos < len {
+            let i = self.pos as usize;
+            let frac = (self.pos - i as f64) as f32;
+            let a = if i == 0 { self.prev } else { input[i - 1] };
+            let b = input[i];
+            out.push(a + (b - a) * frac);
+            self.pos += self.step;
+        }
+        self.pos -= len;
+        if let Some(&last) = input.last() {
+            self.prev = last;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    fn tone(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * 220.0 * n as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    /// Play `source` through at `speed` the way `run_output` does
+    fn stretch(source: &[f32], speed: f32) -> Vec<f32> {
+        let mut stretcher = TimeStretcher::new(RATE);
+        let mut position = 0.0;
+        let mut out = Vec::new();
+        while position < source.len() as f64 {
+            out.extend(stretcher.next_block(source, position, speed));
+            position += stretcher.hop() as f64 * speed as f64;
+        }
+        out
+    }
+
+    #[test]
+    fn unity_speed_passes_samples_through() {
+        let source = tone(RATE as usize);
+        let out = stretch(&source, 1.0);
+        assert_eq!(&out[..source.len()], &source[..]);
+    }
+
+    #[test]
+    fn output_length_scales_with_one_over_speed() {
+        let source = tone(RATE as usize * 2);
+        let hop = TimeStretcher::new(RATE).hop() as f64;
+        for speed in [0.5f32, 0.75, 1.5, 2.0] {
+            let expected = source.len() as f64 / speed as f64;
+            let got = stretch(&source, speed).len() as f64;
+            assert!((got - expected).abs() <= hop, "{}x: {} samples, expected {}", speed, got, expected);
+        }
+    }
+
+    #[test]
+    fn no_fade_in_when_leaving_unity_speed() {
+        let source = tone(RATE as usize);
+        let mut stretcher = TimeStretcher::new(RATE);
+        let hop = stretcher.hop();
+        stretcher.next_block(&source, 0.0, 1.0);
+        let block = stretcher.next_block(&source, hop as f64, 1.5);
+        for (n, (got, want)) in block.iter().zip(&source[hop..]).enumerate() {
+            assert!((got - want).abs() < 1e-4, "sample {}: {} instead of {}", n, got, want);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+// Mutex locking that outlives a panic on another thread
+
+use std::sync::{Mutex, MutexGuard};
+
+pub trait LockExt<T> {
+    /// Lock, taking over a mutex that a panicking thread left poisoned.
+    /// Meant for state that is consistent between statements (flags, sample
+    /// buffers, listeners) where refusing to go on would only wedge the app.
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
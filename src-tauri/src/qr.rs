@@ -0,0 +1,584 @@
+// QR code generation for printable receipts. Only what receipts need is
+// implemented: byte mode at error-correction level M, versions 1-10, which
+// fits links of up to 213 bytes.
+
+use std::fmt::Write;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum QrError {
+    #[error("Text too long for a QR code ({0} bytes)")]
+    TooLong(usize),
+}
+
+const MAX_VERSION: usize = 10;
+/// Error-correction codewords per block at level M, by version
+const ECC_PER_BLOCK: [usize; MAX_VERSION + 1] = [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26];
+/// Error-correction blocks at level M, by version
+const ECC_BLOCKS: [usize; MAX_VERSION + 1] = [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5];
+/// Format-information bits identifying level M
+const LEVEL_M_BITS: u32 = 0b00;
+
+/// Light/dark runs that resemble a finder pattern, penalised when masking
+const FINDER_LIKE: [[bool; 11]; 2] = [
+    [true, false, true, true, true, false, true, false, false, false, false],
+    [false, false, false, false, true, false, true, true, true, false, true],
+];
+
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+    /// Finder, timing, alignment and format modules, which masking skips
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode `text` in the smallest version that holds it
+    pub fn encode(text: &str) -> Result<Self, QrError> {
+        let bytes = text.as_bytes();
+        let version = (1..=MAX_VERSION)
+            .find(|&v| 4 + count_bits(v) + bytes.len() * 8 <= data_codewords(v) * 8)
+            .ok_or(QrError::TooLong(bytes.len()))?;
+
+        let mut bits = Vec::new();
+        push_bits(&mut bits, 0b0100, 4); // byte mode
+        push_bits(&mut bits, bytes.len() as u32, count_bits(version));
+        for &byte in bytes {
+            push_bits(&mut bits, byte as u32, 8);
+        }
+        let capacity = data_codewords(version) * 8;
+        let terminator = (capacity - bits.len()).min(4);
+        push_bits(&mut bits, 0, terminator);
+        let padding = (8 - bits.len() % 8) % 8;
+        push_bits(&mut bits, 0, padding);
+        for pad in [0xEC, 0x11].into_iter().cycle() {
+            if bits.len() >= capacity {
+                break;
+            }
+            push_bits(&mut bits, pad, 8);
+        }
+        let data: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, &bit| (acc << 1) | bit as u8))
+            .collect();
+
+        let size = version * 4 + 17;
+        let mut qr = Self {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        };
+        qr.draw_function_patterns(version);
+        qr.draw_codewords(&add_error_correction(&data, version));
+
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                qr.apply_mask(mask);
+                qr.draw_format_bits(mask);
+                let penalty = qr.penalty();
+                qr.apply_mask(mask);
+                penalty
+            })
+            .unwrap_or(0);
+        qr.apply_mask(mask);
+        qr.draw_format_bits(mask);
+        Ok(qr)
+    }
+
+    /// Modules per side
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the module at column `x`, row `y` from the top left is dark
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.get(x, y)
+    }
+
+    /// Render as a scalable SVG with a `border`-module quiet zone
+    pub fn to_svg(&self, border: usize) -> String {
+        let dimension = self.size + border * 2;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.get(x, y) {
+                    let _ = write!(path, "M{},{}h1v1h-1z", x + border, y + border);
+                }
+            }
+        }
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {0} {0}\" shape-rendering=\"crispEdges\">\
+             <rect width=\"100%\" height=\"100%\" fill=\"#fff\"/><path d=\"{1}\" fill=\"#000\"/></svg>",
+            dimension, path
+        )
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4i32..=4 {
+                for dx in -4i32..=4 {
+                    let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                    if (0..size as i32).contains(&x) && (0..size as i32).contains(&y) {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(x as usize, y as usize, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+
+        let positions = alignment_positions(version, size);
+        let last = positions.len().saturating_sub(1);
+        for (i, &cy) in positions.iter().enumerate() {
+            for (j, &cx) in positions.iter().enumerate() {
+                // Corners already hold finder patterns
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2i32..=2 {
+                    for dx in -2i32..=2 {
+                        let dark = dx.abs().max(dy.abs()) != 1;
+                        self.set_function((cx as i32 + dx) as usize, (cy as i32 + dy) as usize, dark);
+                    }
+                }
+            }
+        }
+
+        // Reserve the format area until a mask is chosen
+        self.draw_format_bits(0);
+
+        if version >= 7 {
+            let mut remainder = version as u32;
+            for _ in 0..12 {
+                remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+            }
+            let bits = ((version as u32) << 12) | remainder;
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        let data = (LEVEL_M_BITS << 3) | mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = ((data << 10) | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+
+        let size = self.size;
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Fill the data area in the two-column zigzag from the bottom right
+    fn draw_codewords(&mut self, data: &[u8]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward { size - 1 - vertical } else { vertical };
+                    if !self.function[y * size + x] && i < data.len() * 8 {
+                        self.modules[y * size + x] = (data[i >> 3] >> (7 - (i & 7))) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// XOR a mask pattern over the data area; applying it twice undoes it
+    fn apply_mask(&mut self, mask: u32) {
+        let size = self.size;
+        for y in 0..size {
+            for x in 0..size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if invert && !self.function[y * size + x] {
+                    self.modules[y * size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    /// Penalty for long runs, 2x2 blocks, finder-like patterns and uneven
+    /// balance, used to pick the mask that scans most reliably
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut score = 0;
+
+        let rows = (0..size).map(|y| (0..size).map(|x| self.get(x, y)).collect::<Vec<_>>());
+        let columns = (0..size).map(|x| (0..size).map(|y| self.get(x, y)).collect::<Vec<_>>());
+        for line in rows.chain(columns) {
+            let mut run = 1;
+            for i in 1..size {
+                if line[i] == line[i - 1] {
+                    run += 1;
+                } else {
+                    if run >= 5 {
+                        score += run - 2;
+                    }
+                    run = 1;
+                }
+            }
+            if run >= 5 {
+                score += run - 2;
+            }
+            score += line
+                .windows(11)
+                .filter(|w| FINDER_LIKE.iter().any(|p| p[..] == **w))
+                .count()
+                * 40;
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.get(x, y);
+                if dark == self.get(x + 1, y) && dark == self.get(x, y + 1) && dark == self.get(x + 1, y + 1) {
+                    score += 3;
+                }
+            }
+        }
+
+        let total = size * size;
+        let dark = self.modules.iter().filter(|&&m| m).count();
+        let deviation = (dark * 20).abs_diff(total * 10);
+        score + deviation.div_ceil(total).saturating_sub(1) * 10
+    }
+}
+
+fn count_bits(version: usize) -> usize {
+    if version < 10 { 8 } else { 16 }
+}
+
+/// Modules available for data and error correction, in codewords
+fn total_codewords(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules / 8
+}
+
+fn data_codewords(version: usize) -> usize {
+    total_codewords(version) - ECC_PER_BLOCK[version] * ECC_BLOCKS[version]
+}
+
+fn alignment_positions(version: usize, size: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = (version * 8 + count * 3 + 5) / (count * 4 - 4) * 2;
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+    for i in (0..count).rev() {
+        bits.push((value >> i) & 1 != 0);
+    }
+}
+
+/// Split the data into blocks, append Reed-Solomon codewords to each and
+/// interleave them
+fn add_error_correction(data: &[u8], version: usize) -> Vec<u8> {
+    let blocks = ECC_BLOCKS[version];
+    let ecc_len = ECC_PER_BLOCK[version];
+    let total = total_codewords(version);
+    let short_blocks = blocks - total % blocks;
+    let short_len = total / blocks;
+    let divisor = reed_solomon_divisor(ecc_len);
+
+    let mut split = Vec::with_capacity(blocks);
+    let mut offset = 0;
+    for i in 0..blocks {
+        let data_len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let mut block = data[offset..offset + data_len].to_vec();
+        offset += data_len;
+        let ecc = reed_solomon_remainder(&block, &divisor);
+        if i < short_blocks {
+            block.push(0);
+        }
+        block.extend(ecc);
+        split.push(block);
+    }
+
+    let mut result = Vec::with_capacity(total);
+    for i in 0..short_len + 1 {
+        for (j, block) in split.iter().enumerate() {
+            // Short blocks carry a placeholder where long blocks have data
+            if i != short_len - ecc_len || j >= short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    result
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (r, &d) in result.iter_mut().zip(divisor) {
+            *r ^= gf_multiply(d, factor);
+        }
+    }
+    result
+}
+
+/// Multiply in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read a code back the way a scanner would: format bits, unmask, read
+    /// the zigzag, de-interleave and check every block's Reed-Solomon
+    /// syndromes, then parse the byte-mode segment
+    fn decode(mut qr: QrCode) -> Vec<u8> {
+        let size = qr.size();
+        let version = (size - 17) / 4;
+
+        let mut first = 0u32;
+        let mut second = 0u32;
+        for i in 0..15 {
+            let (x, y) = match i {
+                0..=5 => (8, i),
+                6 => (8, 7),
+                7 => (8, 8),
+                8 => (7, 8),
+                _ => (14 - i, 8),
+            };
+            first |= (qr.is_dark(x, y) as u32) << i;
+            let (x, y) = if i < 8 { (size - 1 - i, 8) } else { (8, size - 15 + i) };
+            second |= (qr.is_dark(x, y) as u32) << i;
+        }
+        assert_eq!(first, second, "format copies differ");
+        let format = first ^ 0x5412;
+        let mut check = format >> 10;
+        for _ in 0..10 {
+            check = (check << 1) ^ ((check >> 9) * 0x537);
+        }
+        assert_eq!(format >> 10 << 10 | check, format, "format bits fail their BCH check");
+        assert_eq!(format >> 13, LEVEL_M_BITS);
+        assert!(qr.is_dark(8, size - 8), "dark module missing");
+        qr.apply_mask((format >> 10) & 7);
+
+        let mut bits = Vec::new();
+        let mut right = size - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let y = if (right + 1) & 2 == 0 { size - 1 - vertical } else { vertical };
+                    if !qr.function[y * size + x] {
+                        bits.push(qr.is_dark(x, y));
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+        let total = total_codewords(version);
+        let codewords: Vec<u8> = bits[..total * 8]
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, &bit| (acc << 1) | bit as u8))
+            .collect();
+
+        let blocks = ECC_BLOCKS[version];
+        let ecc_len = ECC_PER_BLOCK[version];
+        let short_blocks = blocks - total % blocks;
+        let short_data = total / blocks - ecc_len;
+        let mut split = vec![Vec::new(); blocks];
+        let mut next = codewords.iter();
+        for i in 0..=short_data {
+            for (j, block) in split.iter_mut().enumerate() {
+                if i < short_data || j >= short_blocks {
+                    block.push(*next.next().unwrap());
+                }
+            }
+        }
+        let data_lens: Vec<usize> = split.iter().map(Vec::len).collect();
+        for _ in 0..ecc_len {
+            for block in &mut split {
+                block.push(*next.next().unwrap());
+            }
+        }
+
+        for block in &split {
+            let mut root = 1u8;
+            for _ in 0..ecc_len {
+                let syndrome = block.iter().fold(0u8, |acc, &byte| gf_multiply(acc, root) ^ byte);
+                assert_eq!(syndrome, 0, "block fails its Reed-Solomon check");
+                root = gf_multiply(root, 0x02);
+            }
+        }
+
+        let data: Vec<bool> = split
+            .iter()
+            .zip(&data_lens)
+            .flat_map(|(block, &len)| block[..len].to_vec())
+            .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 != 0))
+            .collect();
+        let read = |from: usize, count: usize| {
+            data[from..from + count].iter().fold(0usize, |acc, &bit| (acc << 1) | bit as usize)
+        };
+        assert_eq!(read(0, 4), 0b0100, "not byte mode");
+        let len = read(4, count_bits(version));
+        let start = 4 + count_bits(version);
+        (0..len).map(|i| read(start + i * 8, 8) as u8).collect()
+    }
+
+    #[test]
+    fn codes_decode_back_to_their_text() {
+        let long = "https://example.com/transcripts/".to_owned() + &"x".repeat(181);
+        for text in ["", "a", "https://school.example/r/3f2a9c", long.as_str()] {
+            let qr = QrCode::encode(text).unwrap();
+            assert_eq!(decode(qr), text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn smallest_version_that_fits_is_used() {
+        // Byte-mode capacity at level M for versions 1-10
+        let capacities = [14, 26, 42, 62, 84, 106, 122, 152, 180, 213];
+        for (i, &capacity) in capacities.iter().enumerate() {
+            let version = i + 1;
+            let qr = QrCode::encode(&"a".repeat(capacity)).unwrap();
+            assert_eq!(qr.size(), version * 4 + 17, "{} bytes", capacity);
+            assert_eq!(decode(qr).len(), capacity);
+            if version < MAX_VERSION {
+                assert_eq!(QrCode::encode(&"a".repeat(capacity + 1)).unwrap().size(), version * 4 + 21);
+            }
+        }
+        assert!(matches!(QrCode::encode(&"a".repeat(214)), Err(QrError::TooLong(214))));
+    }
+
+    #[test]
+    fn finder_patterns_sit_in_three_corners() {
+        let qr = QrCode::encode("finder").unwrap();
+        let size = qr.size();
+        for (left, top) in [(0, 0), (size - 7, 0), (0, size - 7)] {
+            for dy in 0..7 {
+                for dx in 0..7 {
+                    let ring = dx.min(dy).min(6 - dx).min(6 - dy);
+                    assert_eq!(qr.is_dark(left + dx, top + dy), ring != 1, "({}, {})", left + dx, top + dy);
+                }
+            }
+        }
+        for i in 8..size - 8 {
+            assert_eq!(qr.is_dark(i, 6), i % 2 == 0);
+            assert_eq!(qr.is_dark(6, i), i % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn reed_solomon_matches_the_reference_example() {
+        // "HELLO WORLD" at 1-M, from the ISO/IEC 18004 worked example
+        let data = [32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17];
+        let ecc = reed_solomon_remainder(&data, &reed_solomon_divisor(10));
+        assert_eq!(ecc, [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]);
+    }
+
+    #[test]
+    fn svg_draws_one_square_per_dark_module() {
+        let qr = QrCode::encode("svg").unwrap();
+        let dark = (0..qr.size())
+            .flat_map(|y| (0..qr.size()).map(move |x| (x, y)))
+            .filter(|&(x, y)| qr.is_dark(x, y))
+            .count();
+        let svg = qr.to_svg(4);
+        assert!(svg.contains("viewBox=\"0 0 29 29\""));
+        assert_eq!(svg.matches("h1v1h-1z").count(), dark);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::metrics::FluencyMetrics;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RubricError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Invalid rubric: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("Invalid rubric: {0}")]
+    TomlError(#[from] toml::de::Error),
+    #[error("Rubric has no levels")]
+    Empty,
+    #[error("Unknown metric in rubric: {0}")]
+    UnknownMetric(String),
+    #[error("Rubric rule on {0}, which isn't measured yet")]
+    UnmeasuredMetric(String),
+}
+
+/// Metrics a rubric rule may reference
+const METRICS: &[&str] = &["wpm", "wcpm", "accuracy"];
+
+/// Known but not measured, so a rule on one could never pass
+const UNMEASURED: &[&str] = &["prosody"];
+
+/// Admin-defined scoring rubric, e.g.
+///
+/// ```json
+/// { "name": "District ORF", "levels": [
+///     { "level": "Meets", "rules": [{ "metric": "wcpm", "min": 90 }] },
+///     { "level": "Approaching", "rules": [{ "metric": "wcpm", "min": 60 }] },
+///     { "level": "Below", "rules": [] } ] }
+/// ```
+///
+/// or the same as TOML:
+///
+/// ```toml
+/// name = "District ORF"
+///
+/// [[levels]]
+/// level = "Meets"
+/// rules = [{ metric = "wcpm", min = 90 }]
+///
+/// [[levels]]
+/// level = "Below"
+/// ```
+///
+/// Levels are checked in order; the first whose rules all pass wins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rubric {
+    pub name: String,
+    pub levels: Vec<RubricLevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RubricLevel {
+    pub level: String,
+    #[serde(default)]
+    pub rules: Vec<RubricRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RubricRule {
+    pub metric: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Rubric {
+    pub fn from_json(json: &str) -> Result<Self, RubricError> {
+        serde_json::from_str::<Rubric>(json)?.validate()
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, RubricError> {
+        toml::from_str::<Rubric>(toml)?.validate()
+    }
+
+    /// Check a rubric that arrived other than through `from_json`/`from_toml`,
+    /// such as one inside an `Assignment`
+    pub fn validate(self) -> Result<Self, RubricError> {
+        if self.levels.is_empty() {
+            return Err(RubricError::Empty);
+        }
+        for rule in self.levels.iter().flat_map(|l| &l.rules) {
+            if UNMEASURED.contains(&rule.metric.as_str()) {
+                return Err(RubricError::UnmeasuredMetric(rule.metric.clone()));
+            }
+            if !METRICS.contains(&rule.metric.as_str()) {
+                return Err(RubricError::UnknownMetric(rule.metric.clone()));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Read a rubric file, as TOML if it's named `.toml` and JSON otherwise
+    pub fn load(path: &Path) -> Result<Self, RubricError> {
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("toml")) {
+            Self::from_toml(&text)
+        } else {
+            Self::from_json(&text)
+        }
+    }
+
+    /// Level for the given metrics, or `None` if no level matches
+    pub fn evaluate(&self, metrics: &FluencyMetrics) -> Option<String> {
+        self.levels
+            .iter()
+            .find(|level| level.rules.iter().all(|rule| rule.passes(metrics)))
+            .map(|level| level.level.clone())
+    }
+}
+
+impl RubricRule {
+    fn passes(&self, metrics: &FluencyMetrics) -> bool {
+        let value = match self.metric.as_str() {
+            "wpm" => Some(metrics.wpm),
+            "wcpm" => Some(metrics.wcpm),
+            "accuracy" => Some(metrics.accuracy),
+            // Refused when the rubric is loaded
+            _ => None,
+        };
+        match value {
+            Some(v) => self.min.is_none_or(|min| v >= min) && self.max.is_none_or(|max| v <= max),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(wcpm: f64, accuracy: f64) -> FluencyMetrics {
+        FluencyMetrics {
+            passage_words: 100,
+            words_read: 100,
+            words_correct: (accuracy * 100.0) as usize,
+            substitutions: 0,
+            omissions: 0,
+            insertions: 0,
+            reading_seconds: 60.0,
+            wpm: wcpm,
+            wcpm,
+            accuracy,
+        }
+    }
+
+    #[test]
+    fn toml_and_json_rubrics_score_alike() {
+        let toml = r#"
+            name = "District ORF"
+
+            [[levels]]
+            level = "Meets"
+            rules = [{ metric = "wcpm", min = 90 }, { metric = "accuracy", min = 0.95 }]
+
+            [[levels]]
+            level = "Approaching"
+            rules = [{ metric = "wcpm", min = 60 }]
+
+            [[levels]]
+            level = "Below"
+        "#;
+        let json = r#"{ "name": "District ORF", "levels": [
+            { "level": "Meets", "rules": [{ "metric": "wcpm", "min": 90 }, { "metric": "accuracy", "min": 0.95 }] },
+            { "level": "Approaching", "rules": [{ "metric": "wcpm", "min": 60 }] },
+            { "level": "Below" } ] }"#;
+        let from_toml = Rubric::from_toml(toml).unwrap();
+        let from_json = Rubric::from_json(json).unwrap();
+        for (wcpm, accuracy, level) in [(95.0, 0.97, "Meets"), (95.0, 0.9, "Approaching"), (40.0, 0.99, "Below")] {
+            assert_eq!(from_toml.evaluate(&metrics(wcpm, accuracy)).as_deref(), Some(level));
+            assert_eq!(from_json.evaluate(&metrics(wcpm, accuracy)).as_deref(), Some(level));
+        }
+    }
+
+    #[test]
+    fn rules_on_prosody_are_refused() {
+        let json = r#"{ "name": "Expressive", "levels": [{ "level": "Meets", "rules": [{ "metric": "prosody", "min": 3 }] }] }"#;
+        assert!(matches!(Rubric::from_json(json), Err(RubricError::UnmeasuredMetric(m)) if m == "prosody"));
+        let json = r#"{ "name": "Typo", "levels": [{ "level": "Meets", "rules": [{ "metric": "wcmp", "min": 3 }] }] }"#;
+        assert!(matches!(Rubric::from_json(json), Err(RubricError::UnknownMetric(_))));
+    }
+}
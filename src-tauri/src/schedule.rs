@@ -0,0 +1,140 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ScheduleError {
+    #[error("Invalid blackout windows: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("Invalid time '{0}', expected HH:MM")]
+    InvalidTime(String),
+    #[error("Invalid day '{0}'")]
+    InvalidDay(String),
+}
+
+/// A period during which recording is refused, e.g.
+///
+/// ```json
+/// { "label": "Counseling", "days": ["tue", "thu"], "start": "13:00", "end": "14:00", "rooms": ["B12"] }
+/// ```
+///
+/// Empty `days` means every day and empty `rooms` means every room. A window
+/// whose end is before its start runs past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlackoutWindow {
+    pub label: String,
+    #[serde(default)]
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub rooms: Vec<String>,
+}
+
+impl BlackoutWindow {
+    fn validate(&self) -> Result<(), ScheduleError> {
+        parse_time(&self.start)?;
+        parse_time(&self.end)?;
+        for day in &self.days {
+            day.parse::<Weekday>()
+                .map_err(|_| ScheduleError::InvalidDay(day.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn applies_to_room(&self, room: Option<&str>) -> bool {
+        self.rooms.is_empty() || room.is_some_and(|r| self.rooms.iter().any(|w| w == r))
+    }
+
+    fn covers(&self, now: NaiveDateTime) -> bool {
+        let (Ok(start), Ok(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return false;
+        };
+        let time = now.time();
+        // Past midnight the window belongs to the day it started on
+        let (in_window, day) = if start <= end {
+            (time >= start && time < end, now.weekday())
+        } else if time >= start {
+            (true, now.weekday())
+        } else {
+            (time < end, now.weekday().pred())
+        };
+        in_window && self.on_day(day)
+    }
+
+    fn on_day(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.iter().any(|d| d.parse::<Weekday>().ok() == Some(day))
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, ScheduleError> {
+    NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| ScheduleError::InvalidTime(value.to_string()))
+}
+
+pub fn parse_windows(json: &str) -> Result<Vec<BlackoutWindow>, ScheduleError> {
+    let windows: Vec<BlackoutWindow> = serde_json::from_str(json)?;
+    for window in &windows {
+        window.validate()?;
+    }
+    Ok(windows)
+}
+
+/// Like `parse_windows`, but keeps the entries that parse and returns an
+/// error for each one that doesn't, so one bad entry can't stop recording
+/// in every room
+pub fn parse_windows_lossy(json: &str) -> (Vec<BlackoutWindow>, Vec<ScheduleError>) {
+    let entries: Vec<serde_json::Value> = match serde_json::from_str(json) {
+        Ok(entries) => entries,
+        Err(e) => return (Vec::new(), vec![e.into()]),
+    };
+    let mut windows = Vec::with_capacity(entries.len());
+    let mut errors = Vec::new();
+    for entry in entries {
+        match serde_json::from_value::<BlackoutWindow>(entry) {
+            Ok(window) => match window.validate() {
+                Ok(()) => windows.push(window),
+                Err(e) => errors.push(e),
+            },
+            Err(e) => errors.push(e.into()),
+        }
+    }
+    (windows, errors)
+}
+
+/// The window blocking recording in `room` at local time `now`, if any
+pub fn active_window<'a>(
+    windows: &'a [BlackoutWindow],
+    now: NaiveDateTime,
+    room: Option<&str>,
+) -> Option<&'a BlackoutWindow> {
+    windows
+        .iter()
+        .find(|w| w.applies_to_room(room) && w.covers(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossy_parse_skips_only_bad_entries() {
+        let json = r#"[
+            { "label": "Counseling", "start": "13:00", "end": "14:00" },
+            { "label": "Typo", "start": "25:00", "end": "26:00" },
+            { "label": "No end", "start": "09:00" },
+            { "label": "Assembly", "days": ["fri"], "start": "08:00", "end": "09:00" }
+        ]"#;
+        let (windows, errors) = parse_windows_lossy(json);
+        let labels: Vec<&str> = windows.iter().map(|w| w.label.as_str()).collect();
+        assert_eq!(labels, ["Counseling", "Assembly"]);
+        assert_eq!(errors.len(), 2);
+        assert!(parse_windows(json).is_err());
+    }
+
+    #[test]
+    fn lossy_parse_of_garbage_blocks_nothing() {
+        let (windows, errors) = parse_windows_lossy("not json");
+        assert!(windows.is_empty());
+        assert_eq!(errors.len(), 1);
+    }
+}
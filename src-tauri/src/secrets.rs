@@ -0,0 +1,89 @@
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::path::Path;
+use thiserror::Error;
+
+// Secrets kept in settings, such as access tokens, sealed with a key that
+// never leaves the device. The settings table, its history and backups of
+// it only ever hold the sealed form.
+
+/// Made on first use, next to the database
+const KEY_FILE: &str = "secret.key";
+
+/// Leads every sealed value, so the format can change later
+const SEALED_PREFIX: &str = "sealed1:";
+
+#[derive(Error, Debug)]
+pub enum SecretError {
+    #[error("Failed to read or write the device key: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Device key is invalid")]
+    InvalidKey,
+    #[error("Secret could not be unsealed; it may have come from another device")]
+    Unreadable,
+    #[error("Failed to seal secret")]
+    SealFailed,
+}
+
+fn device_key(data_dir: &Path) -> Result<LessSafeKey, SecretError> {
+    let path = data_dir.join(KEY_FILE);
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut bytes = vec![0u8; CHACHA20_POLY1305.key_len()];
+            SystemRandom::new().fill(&mut bytes).map_err(|_| SecretError::InvalidKey)?;
+            std::fs::create_dir_all(data_dir)?;
+            std::fs::write(&path, &bytes)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+            }
+            bytes
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &bytes).map_err(|_| SecretError::InvalidKey)?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Seal `secret` for storing under `name`; it only unseals under that name
+pub fn seal(data_dir: &Path, name: &str, secret: &str) -> Result<String, SecretError> {
+    let key = device_key(data_dir)?;
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| SecretError::SealFailed)?;
+    let mut sealed = secret.as_bytes().to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(name.as_bytes()), &mut sealed)
+        .map_err(|_| SecretError::SealFailed)?;
+    Ok(format!("{}{}{}", SEALED_PREFIX, to_hex(&nonce), to_hex(&sealed)))
+}
+
+pub fn unseal(data_dir: &Path, name: &str, sealed: &str) -> Result<String, SecretError> {
+    let bytes = sealed
+        .strip_prefix(SEALED_PREFIX)
+        .and_then(from_hex)
+        .filter(|b| b.len() >= NONCE_LEN)
+        .ok_or(SecretError::Unreadable)?;
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| SecretError::Unreadable)?;
+    let mut ciphertext = ciphertext.to_vec();
+    let key = device_key(data_dir)?;
+    let secret = key
+        .open_in_place(nonce, Aad::from(name.as_bytes()), &mut ciphertext)
+        .map_err(|_| SecretError::Unreadable)?;
+    String::from_utf8(secret.to_vec()).map_err(|_| SecretError::Unreadable)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
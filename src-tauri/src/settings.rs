@@ -0,0 +1,95 @@
+use crate::db::Database;
+use rusqlite::Result as SqliteResult;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Organization-wide defaults pulled from the server
+pub const ORG_LAYER: &str = "org";
+/// Overrides for the classroom this station belongs to
+pub const CLASSROOM_LAYER: &str = "classroom";
+/// Values set on this machine, stored in the `settings` table
+pub const DEVICE_LAYER: &str = "device";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedSetting {
+    pub key: String,
+    pub value: String,
+    /// Layer the value came from
+    pub source: &'static str,
+}
+
+/// Resolve a setting through device overrides, then the classroom, then the
+/// organization defaults.
+pub fn resolve(db: &Database, key: &str) -> SqliteResult<Option<String>> {
+    Ok(resolve_with_source(db, key)?.map(|s| s.value))
+}
+
+pub fn resolve_with_source(db: &Database, key: &str) -> SqliteResult<Option<ResolvedSetting>> {
+    let found = |value: String, source| ResolvedSetting {
+        key: key.to_string(),
+        value,
+        source,
+    };
+    if let Some(value) = db.get_setting(key)? {
+        return Ok(Some(found(value, DEVICE_LAYER)));
+    }
+    for layer in [CLASSROOM_LAYER, ORG_LAYER] {
+        if let Some(value) = db.get_config_value(layer, key)? {
+            return Ok(Some(found(value, layer)));
+        }
+    }
+    Ok(None)
+}
+
+/// `value` as it may be shown: a password inside an `smtp` config, as a
+/// pushed one can carry, is blanked out. Sealed values are left as they are.
+pub fn redact(key: &str, value: String) -> String {
+    if key != "smtp" {
+        return value;
+    }
+    match serde_json::from_str::<serde_json::Value>(&value) {
+        Ok(mut config) => match config.get_mut("password") {
+            Some(password) if !password.is_null() => {
+                *password = serde_json::Value::String("********".to_string());
+                config.to_string()
+            }
+            _ => value,
+        },
+        Err(_) => value,
+    }
+}
+
+/// Every known setting with the value that wins, sorted by key
+pub fn effective(db: &Database) -> SqliteResult<Vec<ResolvedSetting>> {
+    let mut merged = BTreeMap::new();
+    for (layer, values) in [
+        (ORG_LAYER, db.get_config_layer(ORG_LAYER)?),
+        (CLASSROOM_LAYER, db.get_config_layer(CLASSROOM_LAYER)?),
+        (DEVICE_LAYER, db.get_all_settings()?),
+    ] {
+        for (key, value) in values {
+            merged.insert(key, (value, layer));
+        }
+    }
+    Ok(merged
+        .into_iter()
+        .map(|(key, (value, source))| ResolvedSetting { key, value, source })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_blanks_only_smtp_passwords() {
+        let smtp = r#"{"host":"smtp.district.org","password":"hunter2","from":"t@district.org"}"#;
+        let shown = redact("smtp", smtp.to_string());
+        assert!(!shown.contains("hunter2"));
+        assert!(shown.contains("smtp.district.org"));
+
+        let unsigned = r#"{"host":"smtp.district.org","from":"t@district.org"}"#;
+        assert_eq!(redact("smtp", unsigned.to_string()), unsigned);
+        assert_eq!(redact("room", "hunter2".to_string()), "hunter2");
+    }
+}
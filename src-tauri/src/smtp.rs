@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Longest any one exchange with the server may take
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Error, Debug)]
+pub enum SmtpError {
+    #[error("Connection failed: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("TLS error: {0}")]
+    TlsError(String),
+    #[error("Mail server rejected {0}: {1}")]
+    Rejected(&'static str, String),
+    #[error("Invalid email address: {0}")]
+    InvalidAddress(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// TLS from the start, usually port 465
+    Tls,
+    /// Plain connection upgraded with STARTTLS, usually port 587
+    StartTls,
+    /// Unencrypted, for a relay on the school network only
+    None,
+}
+
+/// Outgoing mail server, from the `smtp` setting, e.g.
+///
+/// ```json
+/// { "host": "smtp.district.org", "port": 587, "security": "start_tls",
+///   "username": "transcriber", "from": "transcriber@district.org" }
+/// ```
+///
+/// The password set on this device is stored sealed, apart from the rest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_security")]
+    pub security: SmtpSecurity,
+    /// Signs in with AUTH PLAIN when both are set
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+}
+
+fn default_port() -> u16 {
+    587
+}
+
+fn default_security() -> SmtpSecurity {
+    SmtpSecurity::StartTls
+}
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+struct Session {
+    reader: BufReader<Box<dyn Stream>>,
+}
+
+impl Session {
+    fn new(stream: Box<dyn Stream>) -> Self {
+        Self { reader: BufReader::new(stream) }
+    }
+
+    /// One reply, joining the lines of a multi-line one
+    fn reply(&mut self) -> Result<(u16, String), SmtpError> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            let code = line
+                .get(..3)
+                .and_then(|c| c.parse::<u16>().ok())
+                .ok_or_else(|| SmtpError::Rejected("the connection", line.trim().to_string()))?;
+            text.push_str(line.get(4..).unwrap_or("").trim_end());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+            text.push('\n');
+        }
+    }
+
+    /// Fails unless the reply is in the same class (2xx, 3xx) as `expect`
+    fn expect(&mut self, what: &'static str, expect: u16) -> Result<String, SmtpError> {
+        let (code, text) = self.reply()?;
+        if code / 100 != expect / 100 {
+            return Err(SmtpError::Rejected(what, format!("{} {}", code, text)));
+        }
+        Ok(text)
+    }
+
+    fn command(&mut self, what: &'static str, line: &str, expect: u16) -> Result<String, SmtpError> {
+        let stream = self.reader.get_mut();
+        stream.write_all(line.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+        self.expect(what, expect)
+    }
+}
+
+fn connect(config: &SmtpConfig) -> Result<Session, SmtpError> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    tcp.set_write_timeout(Some(TIMEOUT))?;
+    let tls = || native_tls::TlsConnector::new().map_err(|e| SmtpError::TlsError(e.to_string()));
+
+    let mut session = match config.security {
+        SmtpSecurity::None => {
+            let mut session = Session::new(Box::new(tcp));
+            session.expect("the connection", 220)?;
+            session
+        }
+        SmtpSecurity::Tls => {
+            let stream = tls()?
+                .connect(&config.host, tcp)
+                .map_err(|e| SmtpError::TlsError(e.to_string()))?;
+            let mut session = Session::new(Box::new(stream));
+            session.expect("the connection", 220)?;
+            session
+        }
+        SmtpSecurity::StartTls => {
+            // The server says nothing more until the handshake, so nothing
+            // is left behind in the plain session's buffer
+            let mut plain = Session::new(Box::new(tcp.try_clone()?));
+            plain.expect("the connection", 220)?;
+            plain.command("EHLO", "EHLO localhost", 250)?;
+            plain.command("STARTTLS", "STARTTLS", 220)?;
+            drop(plain);
+            let stream = tls()?
+                .connect(&config.host, tcp)
+                .map_err(|e| SmtpError::TlsError(e.to_string()))?;
+            Session::new(Box::new(stream))
+        }
+    };
+    session.command("EHLO", "EHLO localhost", 250)?;
+    Ok(session)
+}
+
+/// Send a plain-text message to one recipient
+pub fn send(config: &SmtpConfig, to: &str, subject: &str, body: &str) -> Result<(), SmtpError> {
+    for address in [config.from.as_str(), to] {
+        let valid = address.contains('@')
+            && !address.chars().any(|c| c.is_whitespace() || c.is_control() || c == '<' || c == '>');
+        if !valid {
+            return Err(SmtpError::InvalidAddress(address.to_string()));
+        }
+    }
+
+    let mut session = connect(config)?;
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        let token = base64(format!("\0{}\0{}", username, password).as_bytes());
+        session.command("the credentials", &format!("AUTH PLAIN {}", token), 235)?;
+    }
+    session.command("the sender", &format!("MAIL FROM:<{}>", config.from), 250)?;
+    session.command("the recipient", &format!("RCPT TO:<{}>", to), 250)?;
+    session.command("DATA", "DATA", 354)?;
+
+    // Base64 keeps the body 7-bit clean whatever the server supports, and
+    // can't contain a line with a lone dot
+    let encoded = base64(body.replace("\r\n", "\n").replace('\n', "\r\n").as_bytes());
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@classroom-transcriber>\r\n\
+         MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n",
+        config.from,
+        to,
+        encode_header(subject),
+        chrono::Local::now().to_rfc2822(),
+        uuid::Uuid::new_v4()
+    );
+    for line in encoded.as_bytes().chunks(76) {
+        message.push_str(std::str::from_utf8(line).unwrap_or_default());
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    session.command("the message", &message, 250)?;
+    let _ = session.command("QUIT", "QUIT", 221);
+    Ok(())
+}
+
+/// RFC 2047 encoded-word for anything outside printable ASCII
+fn encode_header(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64(value.as_bytes()))
+    }
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn unbase64(text: &str) -> Vec<u8> {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let values: Vec<u32> = text
+            .bytes()
+            .filter(|&b| b != b'=' && !b.is_ascii_whitespace())
+            .map(|b| ALPHABET.iter().position(|&a| a == b).unwrap() as u32)
+            .collect();
+        let mut out = Vec::new();
+        for chunk in values.chunks(4) {
+            let n = chunk.iter().enumerate().fold(0, |acc, (i, v)| acc | v << (18 - 6 * i));
+            out.extend((0..chunk.len() - 1).map(|i| (n >> (16 - 8 * i)) as u8));
+        }
+        out
+    }
+
+    /// Accept one connection and answer each command from `replies`,
+    /// returning everything the client sent
+    fn fake_server(replies: &'static [(&'static str, &'static str)]) -> (u16, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            writer.write_all(b"220 test ESMTP\r\n").unwrap();
+            let mut received = Vec::new();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    break;
+                }
+                received.push(line.clone());
+                if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        writer.write_all(b"250 queued\r\n").unwrap();
+                    }
+                    continue;
+                }
+                let reply = replies
+                    .iter()
+                    .find(|(prefix, _)| line.starts_with(prefix))
+                    .map(|(_, reply)| *reply)
+                    .unwrap_or("500 unexpected\r\n");
+                in_data = line.starts_with("DATA") && reply.starts_with("354");
+                writer.write_all(reply.as_bytes()).unwrap();
+                if line.starts_with("QUIT") {
+                    break;
+                }
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: "127.0.0.1".into(),
+            port,
+            security: SmtpSecurity::None,
+            username: Some("teacher".into()),
+            password: Some("hunter2".into()),
+            from: "transcriber@school.example".into(),
+        }
+    }
+
+    const ACCEPT_ALL: &[(&str, &str)] = &[
+        ("EHLO", "250-test\r\n250 AUTH PLAIN\r\n"),
+        ("AUTH", "235 ok\r\n"),
+        ("MAIL", "250 ok\r\n"),
+        ("RCPT", "250 ok\r\n"),
+        ("DATA", "354 go ahead\r\n"),
+        ("QUIT", "221 bye\r\n"),
+    ];
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(plain.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn headers_outside_ascii_are_encoded() {
+        assert_eq!(encode_header("Nightly digest"), "Nightly digest");
+        let encoded = encode_header("Résumé du jour");
+        assert!(encoded.starts_with("=?UTF-8?B?") && encoded.ends_with("?="));
+        assert_eq!(unbase64(&encoded[10..encoded.len() - 2]), "Résumé du jour".as_bytes());
+    }
+
+    #[test]
+    fn sends_a_message_through_the_dialogue() {
+        let (port, server) = fake_server(ACCEPT_ALL);
+        let body = "Line one\n.\nLine three";
+        send(&config(port), "teacher@school.example", "Digest", body).unwrap();
+        let received = server.join().unwrap();
+
+        let auth = received.iter().find(|l| l.starts_with("AUTH PLAIN ")).unwrap();
+        assert_eq!(unbase64(auth["AUTH PLAIN ".len()..].trim()), b"\0teacher\0hunter2");
+        assert!(received.contains(&"MAIL FROM:<transcriber@school.example>\r\n".to_string()));
+        assert!(received.contains(&"RCPT TO:<teacher@school.example>\r\n".to_string()));
+        assert!(received.contains(&"Subject: Digest\r\n".to_string()));
+
+        // The body is everything between the blank line and the lone dot
+        let start = received.iter().position(|l| l == "\r\n").unwrap() + 1;
+        let end = received.iter().position(|l| l == ".\r\n").unwrap();
+        let encoded: String = received[start..end].concat();
+        assert!(received[start..end].iter().all(|l| l.len() <= 78));
+        assert_eq!(unbase64(&encoded), b"Line one\r\n.\r\nLine three");
+        assert_eq!(received.last().unwrap(), "QUIT\r\n");
+    }
+
+    #[test]
+    fn rejected_recipient_is_reported() {
+        let (port, server) = fake_server(&[
+            ("EHLO", "250 test\r\n"),
+            ("AUTH", "235 ok\r\n"),
+            ("MAIL", "250 ok\r\n"),
+            ("RCPT", "550 no such user\r\n"),
+        ]);
+        let err = send(&config(port), "nobody@school.example", "Digest", "body").unwrap_err();
+        assert!(matches!(err, SmtpError::Rejected("the recipient", ref reply) if reply == "550 no such user"));
+        drop(server);
+    }
+
+    #[test]
+    fn invalid_addresses_are_refused_before_connecting() {
+        for to in ["no-at-sign", "a b@school.example", "<x>@school.example"] {
+            assert!(matches!(send(&config(1), to, "s", "b"), Err(SmtpError::InvalidAddress(_))));
+        }
+    }
+}
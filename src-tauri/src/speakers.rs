@@ -0,0 +1,285 @@
+use crate::audio::ChannelActivity;
+use crate::voiceprint::Voiceprint;
+use crate::whisper::TranscriptSegment;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// Who said each segment, from how the audio was captured. A dual-channel
+// recording has a lane for each side of the session; with a microphone
+// array the loudest channel follows whoever is talking. A single
+// microphone gives no such cue, so those segments are left for a backend
+// that diarizes, such as WhisperX.
+
+/// Speaker keys for the two lanes of a dual-channel recording
+pub const TEACHER: &str = "teacher";
+pub const STUDENT: &str = "student";
+
+/// How the student is told apart from the teacher among speakers that
+/// aren't already placed by lane or label
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StudentStrategy {
+    /// Suits one-to-one reading, where the student reads most of the time
+    MostTalkative,
+    /// Suits lecture-style sessions, where the teacher does most talking
+    LeastTalkative,
+    /// Whoever doesn't sound like the teacher's enrolled voiceprint
+    Voiceprint,
+    /// Only ever picked by hand
+    Manual,
+}
+
+impl StudentStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MostTalkative => "most_talkative",
+            Self::LeastTalkative => "least_talkative",
+            Self::Voiceprint => "voiceprint",
+            Self::Manual => "manual",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "most_talkative" => Some(Self::MostTalkative),
+            "least_talkative" => Some(Self::LeastTalkative),
+            "voiceprint" => Some(Self::Voiceprint),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// Lanes quieter than this over a segment don't count as speaking in it
+const SPEAKING_DBFS: f64 = -50.0;
+
+/// Louder lane must beat the other by this much to be credited; crosstalk
+/// into the other microphone is usually well below it
+const LANE_MARGIN_DB: f64 = 3.0;
+
+fn rms_dbfs(samples: &[f32]) -> f64 {
+    if samples.is_empty() {
+        return f64::NEG_INFINITY;
+    }
+    let mean_square = samples.iter().map(|&s| s as f64 * s as f64).sum::<f64>() / samples.len() as f64;
+    10.0 * mean_square.max(1e-12).log10()
+}
+
+fn span<'a>(samples: &'a [f32], sample_rate: u32, segment: &TranscriptSegment) -> &'a [f32] {
+    let at = |seconds: f64| ((seconds.max(0.0) * sample_rate as f64) as usize).min(samples.len());
+    let end = at(segment.end);
+    &samples[at(segment.start).min(end)..end]
+}
+
+/// How much one speaker said in a recording, for talk-ratio trends
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeakerStats {
+    /// Speaker key as on the segments, such as "teacher" or "SPEAKER_00"
+    pub speaker: String,
+    /// Name the key is labelled with on the recording, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub words: usize,
+    pub seconds: f64,
+    /// Fraction of the recording's credited speaking time that is theirs
+    pub share: f64,
+}
+
+/// Words and speaking time of each speaker in `segments`, most talkative
+/// first. Segments no one is credited with are left out of the shares.
+pub fn stats(segments: &[TranscriptSegment]) -> Vec<SpeakerStats> {
+    let mut totals: BTreeMap<&str, (usize, f64)> = BTreeMap::new();
+    for segment in segments {
+        if let Some(speaker) = segment.speaker.as_deref() {
+            let total = totals.entry(speaker).or_default();
+            total.0 += segment.text.split_whitespace().count();
+            total.1 += (segment.end - segment.start).max(0.0);
+        }
+    }
+    let spoken: f64 = totals.values().map(|(_, seconds)| seconds).sum();
+    let mut stats: Vec<SpeakerStats> = totals
+        .into_iter()
+        .map(|(speaker, (words, seconds))| SpeakerStats {
+            speaker: speaker.to_string(),
+            label: None,
+            words,
+            seconds,
+            share: if spoken > 0.0 { seconds / spoken } else { 0.0 },
+        })
+        .collect();
+    stats.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+    stats
+}
+
+/// `stats` with each speaker's name from `labels` filled in
+pub fn labelled(mut stats: Vec<SpeakerStats>, labels: &HashMap<String, String>) -> Vec<SpeakerStats> {
+    for entry in &mut stats {
+        entry.label = labels.get(&entry.speaker).cloned();
+    }
+    stats
+}
+
+/// Credit each segment to the lane that was clearly louder while it was
+/// spoken. Segments that already have a speaker keep it.
+pub fn from_lanes(segments: &mut [TranscriptSegment], teacher: &[f32], student: &[f32], sample_rate: u32) {
+    for segment in segments.iter_mut().filter(|s| s.speaker.is_none()) {
+        let teacher_level = rms_dbfs(span(teacher, sample_rate, segment));
+        let student_level = rms_dbfs(span(student, sample_rate, segment));
+        segment.speaker = if teacher_level.max(student_level) < SPEAKING_DBFS {
+            None
+        } else if teacher_level >= student_level + LANE_MARGIN_DB {
+            Some(TEACHER.to_string())
+        } else if student_level >= teacher_level + LANE_MARGIN_DB {
+            Some(STUDENT.to_string())
+        } else {
+            None
+        };
+    }
+}
+
+/// Credit each segment to the channel that was loudest for most of it, as
+/// "channel 1", "channel 2" and so on. Segments that already have a speaker
+/// keep it.
+pub fn from_channel_activity(segments: &mut [TranscriptSegment], activity: &ChannelActivity) {
+    if activity.channels < 2 || activity.frame_seconds <= 0.0 {
+        return;
+    }
+    let frame = |seconds: f64| ((seconds.max(0.0) / activity.frame_seconds as f64) as usize).min(activity.loudest.len());
+    for segment in segments.iter_mut().filter(|s| s.speaker.is_none()) {
+        let end = frame(segment.end);
+        let mut votes = vec![0usize; activity.channels as usize];
+        for channel in activity.loudest[frame(segment.start).min(end)..end].iter().flatten() {
+            if let Some(count) = votes.get_mut(*channel as usize) {
+                *count += 1;
+            }
+        }
+        segment.speaker = votes
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .max_by_key(|(_, &count)| count)
+            .map(|(channel, _)| format!("channel {}", channel + 1));
+    }
+}
+
+/// Label the diarized speakers that sound like an enrolled voice "teacher"
+/// or "student", so they're told apart without being named by hand. Each
+/// voice labels at most the one speaker it's closest to, and a speaker is
+/// only labelled for the voice it sounds nearer to. Lanes and speakers
+/// already labelled are left as they are.
+pub fn label_enrolled(
+    segments: &[TranscriptSegment],
+    labels: &mut HashMap<String, String>,
+    samples: &[f32],
+    sample_rate: u32,
+    teacher: Option<&Voiceprint>,
+    student: Option<&Voiceprint>,
+) {
+    let mut unlabelled: BTreeMap<&str, Vec<&[f32]>> = BTreeMap::new();
+    for segment in segments {
+        if let Some(speaker) = segment.speaker.as_deref() {
+            if speaker != TEACHER && speaker != STUDENT && !labels.contains_key(speaker) {
+                unlabelled.entry(speaker).or_default().push(span(samples, sample_rate, segment));
+            }
+        }
+    }
+    let heard: Vec<(&str, Voiceprint)> = unlabelled
+        .into_iter()
+        .filter_map(|(speaker, spans)| Some((speaker, Voiceprint::from_spans(spans, sample_rate)?)))
+        .collect();
+    let distance = |enrolled: Option<&Voiceprint>, voice: &Voiceprint| {
+        enrolled.filter(|e| e.matches(voice)).map(|e| e.octaves_from(voice))
+    };
+    for (role, enrolled, other) in [(TEACHER, teacher, student), (STUDENT, student, teacher)] {
+        let closest = heard
+            .iter()
+            .filter(|(speaker, _)| !labels.contains_key(*speaker))
+            .filter_map(|(speaker, voice)| {
+                let near = distance(enrolled, voice)?;
+                let nearer_other = distance(other, voice).is_some_and(|far| far < near);
+                (!nearer_other).then_some((*speaker, near))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((speaker, _)) = closest {
+            labels.insert(speaker.to_string(), role.to_string());
+        }
+    }
+}
+
+/// The speaker key of the student in `segments`, for scoring their reading
+/// alone. The student lane, or a speaker labelled "student", is taken as is;
+/// otherwise speakers that are plainly the teacher are set aside and
+/// `strategy` picks among the rest. None when fewer than two speakers are
+/// heard or the strategy can't tell, in which case everything is scored.
+pub fn pick_student(
+    segments: &[TranscriptSegment],
+    labels: &HashMap<String, String>,
+    teacher_name: &str,
+    strategy: StudentStrategy,
+    samples: &[f32],
+    sample_rate: u32,
+    voiceprint: Option<&Voiceprint>,
+) -> Option<String> {
+    let name = |key: &str| labels.get(key).map_or(key, String::as_str).to_string();
+    let is_teacher = |key: &str| {
+        let n = name(key);
+        n.eq_ignore_ascii_case(TEACHER) || (!teacher_name.is_empty() && n.eq_ignore_ascii_case(teacher_name))
+    };
+    let heard = stats(segments);
+    if let Some(student) = heard.iter().find(|s| name(&s.speaker).eq_ignore_ascii_case(STUDENT)) {
+        return Some(student.speaker.clone());
+    }
+    if heard.len() < 2 {
+        return None;
+    }
+    // Most talkative first, as `stats` orders them
+    let candidates: Vec<&SpeakerStats> = heard.iter().filter(|s| !is_teacher(&s.speaker)).collect();
+    let picked = match strategy {
+        StudentStrategy::MostTalkative => candidates.first().copied(),
+        StudentStrategy::LeastTalkative => candidates.last().copied(),
+        StudentStrategy::Voiceprint => {
+            let teacher = voiceprint?;
+            candidates.into_iter().find(|candidate| {
+                let spans = segments
+                    .iter()
+                    .filter(|s| s.speaker.as_deref() == Some(candidate.speaker.as_str()))
+                    .map(|s| span(samples, sample_rate, s));
+                Voiceprint::from_spans(spans, sample_rate).is_some_and(|spoken| !teacher.matches(&spoken))
+            })
+        }
+        StudentStrategy::Manual => None,
+    };
+    picked.map(|s| s.speaker.clone())
+}
+
+/// Whether only the teacher is heard in `segments`, so there's no student
+/// reading to score. A segment's speaker counts as the teacher if it's the
+/// teacher's lane or is labelled "teacher" or with `teacher_name`;
+/// speakers placed neither way are matched as a whole against the
+/// teacher's voiceprint. Without one, such speakers might be the student.
+pub fn teacher_only(
+    segments: &[TranscriptSegment],
+    labels: &HashMap<String, String>,
+    teacher_name: &str,
+    samples: &[f32],
+    sample_rate: u32,
+    voiceprint: Option<&Voiceprint>,
+) -> bool {
+    if segments.is_empty() {
+        return false;
+    }
+    let is_role = |name: &str, role: &str| name.eq_ignore_ascii_case(role);
+    let mut unplaced: BTreeMap<Option<&str>, Vec<&TranscriptSegment>> = BTreeMap::new();
+    for segment in segments {
+        let name = segment.speaker.as_deref().map(|s| labels.get(s).map_or(s, String::as_str));
+        match name {
+            Some(n) if is_role(n, TEACHER) || (!teacher_name.is_empty() && is_role(n, teacher_name)) => {}
+            Some(n) if is_role(n, STUDENT) => return false,
+            _ => unplaced.entry(segment.speaker.as_deref()).or_default().push(segment),
+        }
+    }
+    unplaced.values().all(|group| {
+        let spoken = Voiceprint::from_spans(group.iter().map(|s| span(samples, sample_rate, s)), sample_rate);
+        matches!((voiceprint, spoken), (Some(teacher), Some(spoken)) if teacher.matches(&spoken))
+    })
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+// What was found wrong on launch, and what it turns off. The app starts
+// whatever fails rather than stopping at the first problem; the report says
+// which features to grey out and why.
+
+/// Features a failed check can turn off
+pub const RECORDING: &str = "recording";
+pub const TRANSCRIPTION: &str = "transcription";
+pub const SYNC: &str = "sync";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    /// Not needed with the current settings
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupCheck {
+    /// Such as "database" or "microphone"
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: Option<String>,
+    /// Features that stay off while it fails
+    pub disables: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StartupReport {
+    pub checks: Vec<StartupCheck>,
+    /// Every feature a failed check turns off
+    pub disabled: Vec<&'static str>,
+}
+
+impl StartupReport {
+    pub fn pass(&mut self, name: &'static str) {
+        self.push(name, CheckStatus::Ok, None, &[]);
+    }
+
+    pub fn fail(&mut self, name: &'static str, detail: impl Into<String>, disables: &[&'static str]) {
+        self.push(name, CheckStatus::Failed, Some(detail.into()), disables);
+    }
+
+    pub fn skip(&mut self, name: &'static str, detail: impl Into<String>) {
+        self.push(name, CheckStatus::Skipped, Some(detail.into()), &[]);
+    }
+
+    fn push(&mut self, name: &'static str, status: CheckStatus, detail: Option<String>, disables: &[&'static str]) {
+        for feature in disables {
+            if !self.disabled.contains(feature) {
+                self.disabled.push(feature);
+            }
+        }
+        self.checks.push(StartupCheck {
+            name,
+            status,
+            detail,
+            disables: disables.to_vec(),
+        });
+    }
+
+    /// This report followed by `other`'s checks
+    pub fn merged(&self, other: StartupReport) -> StartupReport {
+        let mut report = self.clone();
+        for check in other.checks {
+            report.push(check.name, check.status, check.detail, &check.disables);
+        }
+        report
+    }
+
+    pub fn degraded(&self) -> bool {
+        !self.disabled.is_empty()
+    }
+
+    /// Refuse `feature` if a failed check turned it off, saying why
+    pub fn require(&self, feature: &str) -> Result<(), String> {
+        match self.checks.iter().find(|c| c.disables.contains(&feature)) {
+            Some(check) => Err(format!(
+                "Unavailable until this is fixed and the app restarted: {}",
+                check.detail.as_deref().unwrap_or(check.name)
+            )),
+            None => Ok(()),
+        }
+    }
+}
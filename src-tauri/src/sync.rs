@@ -1,6 +1,14 @@
-use crate::db::Recording;
+use crate::db::{
+    Assignment, Recording, DIRTY_NOTES, DIRTY_REVIEW_STATUS, DIRTY_SPEAKER_LABELS, DIRTY_TAGS,
+    DIRTY_TRANSCRIPT,
+};
+use crate::metrics::FluencyMetrics;
+use crate::speakers::SpeakerStats;
+use crate::whisper::TranscriptSegment;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,6 +17,107 @@ pub enum SyncError {
     NetworkError(#[from] reqwest::Error),
     #[error("Server returned error: {0}")]
     ServerError(String),
+    #[error("Server rejected the transcript: {0}")]
+    Rejected(String),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Server copy of the audio does not match (expected {expected}, got {actual})")]
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+impl SyncError {
+    /// Whether the server looked at the submission and refused it, as
+    /// opposed to it not getting through
+    pub fn is_rejection(&self) -> bool {
+        matches!(self, SyncError::Rejected(_))
+    }
+}
+
+/// Read a reply to a submission. A 4xx (other than a timeout or rate limit)
+/// is the server rejecting it; any other failure is worth retrying as is.
+fn submit_reply<T: serde::de::DeserializeOwned>(response: reqwest::blocking::Response) -> Result<T, SyncError> {
+    let status = response.status();
+    let transient = matches!(status.as_u16(), 408 | 429);
+    if status.is_client_error() && !transient {
+        let body = response.text().unwrap_or_default();
+        let reason = serde_json::from_str::<SubmitResponse>(&body)
+            .ok()
+            .and_then(|r| r.error)
+            .unwrap_or_else(|| status.to_string());
+        return Err(SyncError::Rejected(reason));
+    }
+    if !status.is_success() {
+        return Err(SyncError::ServerError(status.to_string()));
+    }
+    Ok(response.json()?)
+}
+
+/// Version of the sync protocol this client speaks. 1 is the original
+/// one-transcript-per-request API; 2 added `/api/capabilities`.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Most transcripts sent in one batch when the server doesn't set a limit
+const DEFAULT_BATCH_SIZE: usize = 50;
+
+/// What the server accepts, from `GET /api/capabilities`. Payloads are cut
+/// down to match, so servers that predate a feature keep working.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerCapabilities {
+    pub protocol: u32,
+    /// `POST /api/transcripts/batch`
+    #[serde(default)]
+    pub batch: bool,
+    #[serde(default)]
+    pub max_batch_size: Option<usize>,
+    /// `PUT /api/transcripts/{id}/audio`
+    #[serde(default)]
+    pub audio_upload: bool,
+    /// Timed segments alongside the flat transcript
+    #[serde(default)]
+    pub segments: bool,
+    /// Fluency metrics of scored recordings
+    #[serde(default)]
+    pub metrics: bool,
+    /// Assignments to fetch, recordings tied to them, and completion reports
+    #[serde(default)]
+    pub assignments: bool,
+    /// Words and speaking time per speaker
+    #[serde(default)]
+    pub speaker_stats: bool,
+    /// English translations of transcripts in other languages
+    #[serde(default)]
+    pub translations: bool,
+}
+
+impl ServerCapabilities {
+    /// A server from before capabilities were advertised, which only takes
+    /// transcripts one at a time and audio uploads
+    pub fn legacy() -> Self {
+        Self {
+            protocol: 1,
+            batch: false,
+            max_batch_size: None,
+            audio_upload: true,
+            segments: false,
+            metrics: false,
+            assignments: false,
+            speaker_stats: false,
+            translations: false,
+        }
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.max_batch_size.filter(|&n| n > 0).unwrap_or(DEFAULT_BATCH_SIZE)
+    }
+}
+
+/// What's sent with a transcript when the server takes it
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptDetail {
+    pub segments: Vec<TranscriptSegment>,
+    pub metrics: Option<FluencyMetrics>,
+    /// Only filled in when `sync_speaker_stats` is on
+    pub speaker_stats: Vec<SpeakerStats>,
 }
 
 #[derive(Serialize)]
@@ -19,28 +128,151 @@ struct SubmitTranscript {
     transcript: String,
     recorded_at: String,
     client_id: String,
+    /// With `sequence`, orders a device's recordings without trusting its clock
+    device_id: String,
+    sequence: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    segments: Option<Vec<TranscriptSegment>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<FluencyMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assignment_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speaker_stats: Option<Vec<SpeakerStats>>,
+    /// Language the transcript is in, sent with its English translation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transcript_language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    translation: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SubmitBatch {
+    transcripts: Vec<SubmitTranscript>,
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    success: bool,
+    #[serde(default)]
+    results: Vec<BatchResult>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BatchResult {
+    client_id: String,
+    success: bool,
+    error: Option<String>,
+}
+
+/// Only the fields flagged dirty are serialized
+#[derive(Serialize, Default)]
+struct PatchMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    review_status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speaker_labels: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transcript: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AssignmentCompletion {
+    device_id: String,
+    status: String,
+    started_at: String,
+    completed_at: Option<String>,
+    /// Client IDs of the recordings made for it, all already synced
+    recording_ids: Vec<String>,
+    recorded_seconds: f64,
+    expected_duration_seconds: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct DeletionNotice {
+    client_id: String,
+    reason: String,
+    deleted_at: String,
+}
+
+#[derive(Deserialize)]
+struct AudioUploadResponse {
+    success: bool,
+    /// Hex SHA-256 of the bytes the server stored
+    sha256: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FeedResponse {
+    success: bool,
+    /// Share link parents subscribe to
+    url: Option<String>,
+    error: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct SubmitResponse {
     success: bool,
-    id: Option<i64>,
     error: Option<String>,
 }
 
+/// Settings layers served for a classroom
+#[derive(Deserialize, Default)]
+pub struct ConfigLayers {
+    #[serde(default)]
+    pub org: HashMap<String, String>,
+    #[serde(default)]
+    pub classroom: HashMap<String, String>,
+}
+
 pub struct SyncClient {
     client: Client,
     server_url: String,
+    capabilities: ServerCapabilities,
 }
 
 impl SyncClient {
+    /// A client that assumes a legacy server until `negotiate` is called
     pub fn new(server_url: &str) -> Self {
         Self {
             client: Client::new(),
             server_url: server_url.trim_end_matches('/').to_string(),
+            capabilities: ServerCapabilities::legacy(),
         }
     }
 
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        &self.capabilities
+    }
+
+    /// Ask the server what it supports and adapt to it. A server without
+    /// the endpoint is treated as legacy. On any other failure the client
+    /// stays as it was.
+    pub fn negotiate(&mut self) -> Result<&ServerCapabilities, SyncError> {
+        let response = self
+            .client
+            .get(format!("{}/api/capabilities", self.server_url))
+            .query(&[("client_protocol", PROTOCOL_VERSION)])
+            .timeout(std::time::Duration::from_secs(10))
+            .send()?;
+        self.capabilities = match response.status() {
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::METHOD_NOT_ALLOWED => {
+                ServerCapabilities::legacy()
+            }
+            status if status.is_success() => response.json()?,
+            status => {
+                return Err(SyncError::ServerError(format!("Capabilities request failed: {}", status)));
+            }
+        };
+        Ok(&self.capabilities)
+    }
+
     pub fn check_connection(&self) -> bool {
         self.client
             .get(format!("{}/api/health", self.server_url))
@@ -50,19 +282,180 @@ impl SyncClient {
             .unwrap_or(false)
     }
 
-    pub fn submit_transcript(&self, recording: &Recording) -> Result<(), SyncError> {
-        let payload = SubmitTranscript {
+    /// Fetch the organization defaults and the classroom's overrides
+    pub fn fetch_config(&self, classroom_id: Option<&str>) -> Result<ConfigLayers, SyncError> {
+        let mut request = self.client.get(format!("{}/api/config", self.server_url));
+        if let Some(id) = classroom_id {
+            request = request.query(&[("classroom_id", id)]);
+        }
+        let response = request.send()?;
+        if !response.status().is_success() {
+            return Err(SyncError::ServerError(format!("Config request failed: {}", response.status())));
+        }
+        Ok(response.json()?)
+    }
+
+    /// The submission for `recording`, with as much of `detail` as the
+    /// server takes
+    fn transcript_payload(&self, recording: &Recording, device_id: &str, detail: &TranscriptDetail) -> SubmitTranscript {
+        SubmitTranscript {
             student_id: recording.student_id.clone(),
             device_type: "desktop".to_string(),
             audio_duration_seconds: recording.duration_seconds,
             transcript: recording.transcript.clone().unwrap_or_default(),
             recorded_at: recording.recorded_at.clone(),
             client_id: recording.id.clone(),
+            device_id: device_id.to_string(),
+            sequence: recording.sequence,
+            segments: (self.capabilities.segments && !detail.segments.is_empty()).then(|| detail.segments.clone()),
+            metrics: detail.metrics.clone().filter(|_| self.capabilities.metrics),
+            assignment_id: recording.assignment_id.clone().filter(|_| self.capabilities.assignments),
+            speaker_stats: (self.capabilities.speaker_stats && !detail.speaker_stats.is_empty())
+                .then(|| detail.speaker_stats.clone()),
+            transcript_language: recording
+                .transcript_language
+                .clone()
+                .filter(|_| self.capabilities.translations && recording.translation.is_some()),
+            translation: recording.translation.clone().filter(|_| self.capabilities.translations),
+        }
+    }
+
+    pub fn submit_transcript(
+        &self,
+        recording: &Recording,
+        device_id: &str,
+        detail: &TranscriptDetail,
+    ) -> Result<(), SyncError> {
+        let payload = self.transcript_payload(recording, device_id, detail);
+
+        let response: SubmitResponse = submit_reply(
+            self.client
+                .post(format!("{}/api/transcripts", self.server_url))
+                .json(&payload)
+                .send()?,
+        )?;
+
+        if response.success {
+            Ok(())
+        } else {
+            Err(SyncError::Rejected(
+                response.error.unwrap_or_else(|| "Unknown error".to_string()),
+            ))
+        }
+    }
+
+    /// Submit several transcripts in one request, on servers that take
+    /// batches. Returns each recording's outcome, in the order given; one
+    /// the server didn't report on counts as failed.
+    pub fn submit_batch(
+        &self,
+        recordings: &[(&Recording, TranscriptDetail)],
+        device_id: &str,
+    ) -> Result<Vec<Result<(), SyncError>>, SyncError> {
+        let payload = SubmitBatch {
+            transcripts: recordings
+                .iter()
+                .map(|(recording, detail)| self.transcript_payload(recording, device_id, detail))
+                .collect(),
+        };
+
+        let response: BatchResponse = submit_reply(
+            self.client
+                .post(format!("{}/api/transcripts/batch", self.server_url))
+                .json(&payload)
+                .send()?,
+        )?;
+
+        if !response.success {
+            return Err(SyncError::Rejected(
+                response.error.unwrap_or_else(|| "Unknown error".to_string()),
+            ));
+        }
+        let mut results: HashMap<String, BatchResult> =
+            response.results.into_iter().map(|r| (r.client_id.clone(), r)).collect();
+        Ok(recordings
+            .iter()
+            .map(|(recording, _)| match results.remove(&recording.id) {
+                Some(BatchResult { success: true, .. }) => Ok(()),
+                Some(BatchResult { error, .. }) => Err(SyncError::Rejected(
+                    error.unwrap_or_else(|| "Unknown error".to_string()),
+                )),
+                None => Err(SyncError::ServerError("Missing from the batch response".to_string())),
+            })
+            .collect())
+    }
+
+    /// Push only the edited metadata fields of an already-synced recording.
+    /// Returns the dirty bits that were sent so the caller can clear them.
+    pub fn patch_metadata(&self, recording: &Recording) -> Result<u32, SyncError> {
+        let fields = recording.dirty_fields;
+        let mut payload = PatchMetadata::default();
+
+        if fields & DIRTY_TAGS != 0 {
+            payload.tags = Some(recording.tags.clone());
+        }
+        if fields & DIRTY_NOTES != 0 {
+            payload.notes = Some(recording.notes.clone());
+        }
+        if fields & DIRTY_REVIEW_STATUS != 0 {
+            payload.review_status = Some(recording.review_status.clone());
+        }
+        if fields & DIRTY_SPEAKER_LABELS != 0 {
+            payload.speaker_labels = Some(recording.speaker_labels.clone());
+        }
+        if fields & DIRTY_TRANSCRIPT != 0 {
+            payload.transcript = recording.transcript.clone();
+        }
+
+        let response: SubmitResponse = self
+            .client
+            .patch(format!("{}/api/transcripts/{}", self.server_url, recording.id))
+            .json(&payload)
+            .send()?
+            .json()?;
+
+        if response.success {
+            Ok(fields)
+        } else {
+            Err(SyncError::ServerError(
+                response.error.unwrap_or_else(|| "Unknown error".to_string()),
+            ))
+        }
+    }
+
+    /// Assignments the server has for `student_id`
+    pub fn fetch_assignments(&self, student_id: &str) -> Result<Vec<Assignment>, SyncError> {
+        let response = self
+            .client
+            .get(format!("{}/api/students/{}/assignments", self.server_url, student_id))
+            .send()?;
+        if !response.status().is_success() {
+            return Err(SyncError::ServerError(format!("Assignments request failed: {}", response.status())));
+        }
+        Ok(response.json()?)
+    }
+
+    /// Tell the server where `assignment` got to, so it can mark it done.
+    /// `recordings` are the ones made for it.
+    pub fn report_assignment(
+        &self,
+        assignment: &Assignment,
+        recordings: &[Recording],
+        device_id: &str,
+    ) -> Result<(), SyncError> {
+        let payload = AssignmentCompletion {
+            device_id: device_id.to_string(),
+            status: assignment.status.clone(),
+            started_at: assignment.started_at.clone(),
+            completed_at: assignment.completed_at.clone(),
+            recording_ids: recordings.iter().map(|r| r.id.clone()).collect(),
+            recorded_seconds: recordings.iter().map(|r| r.duration_seconds).sum(),
+            expected_duration_seconds: assignment.expected_duration_seconds,
         };
 
         let response: SubmitResponse = self
             .client
-            .post(format!("{}/api/transcripts", self.server_url))
+            .post(format!("{}/api/assignments/{}/completion", self.server_url, assignment.id))
             .json(&payload)
             .send()?
             .json()?;
@@ -75,4 +468,85 @@ impl SyncClient {
             ))
         }
     }
+
+    /// Tell the server the local audio for a recording was purged
+    pub fn notify_deletion(&self, recording: &Recording, reason: &str) -> Result<(), SyncError> {
+        let payload = DeletionNotice {
+            client_id: recording.id.clone(),
+            reason: reason.to_string(),
+            deleted_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        let response: SubmitResponse = self
+            .client
+            .post(format!("{}/api/transcripts/{}/deletion", self.server_url, recording.id))
+            .json(&payload)
+            .send()?
+            .json()?;
+
+        if response.success {
+            Ok(())
+        } else {
+            Err(SyncError::ServerError(
+                response.error.unwrap_or_else(|| "Unknown error".to_string()),
+            ))
+        }
+    }
+
+    /// Server address the recording's uploaded audio is served from
+    pub fn audio_url(&self, recording_id: &str) -> String {
+        format!("{}/api/transcripts/{}/audio", self.server_url, recording_id)
+    }
+
+    /// Publish a student's podcast feed and return its share link
+    pub fn publish_feed(&self, student_id: &str, feed: String) -> Result<String, SyncError> {
+        let response: FeedResponse = self
+            .client
+            .put(format!("{}/api/students/{}/feed", self.server_url, student_id))
+            .header("Content-Type", "application/rss+xml")
+            .body(feed)
+            .send()?
+            .json()?;
+
+        match (response.success, response.url) {
+            (true, Some(url)) => Ok(url),
+            _ => Err(SyncError::ServerError(
+                response.error.unwrap_or_else(|| "Unknown error".to_string()),
+            )),
+        }
+    }
+
+    /// Page on the server where parents and staff can read the transcript
+    pub fn transcript_view_url(&self, recording_id: &str) -> String {
+        format!("{}/transcripts/{}", self.server_url, recording_id)
+    }
+
+    /// Upload the recording's audio and confirm the server stored the same bytes
+    pub fn upload_audio(&self, recording: &Recording) -> Result<(), SyncError> {
+        let mut file = std::fs::File::open(&recording.audio_path)?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        let expected = format!("{:x}", hasher.finalize());
+
+        let response: AudioUploadResponse = self
+            .client
+            .put(self.audio_url(&recording.id))
+            .header("Content-Type", recording.audio_format.mime_type())
+            .header("X-Content-SHA256", &expected)
+            .body(std::fs::File::open(&recording.audio_path)?)
+            .send()?
+            .json()?;
+
+        if !response.success {
+            return Err(SyncError::ServerError(
+                response.error.unwrap_or_else(|| "Unknown error".to_string()),
+            ));
+        }
+
+        let actual = response.sha256.unwrap_or_default();
+        if !actual.eq_ignore_ascii_case(&expected) {
+            return Err(SyncError::ChecksumMismatch { expected, actual });
+        }
+        Ok(())
+    }
 }
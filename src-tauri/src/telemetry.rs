@@ -0,0 +1,214 @@
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Counters and timings kept for the life of the process, for watching a
+// station over a long soak test or across a fleet. Nothing here is stored
+// or synced.
+
+pub static RECORDINGS_MADE: Counter = Counter::new("classroom_recordings_total", "Recordings saved");
+pub static SYNC_FAILURES: Counter = Counter::new("classroom_sync_failures_total", "Failed transcript submissions");
+pub static AUDIO_OVERRUNS: Counter = Counter::new(
+    "classroom_audio_overruns_total",
+    "Stream errors other than a lost device, such as buffer overruns",
+);
+pub static UNSYNCED_RECORDINGS: Gauge = Gauge::new(
+    "classroom_unsynced_recordings",
+    "Recordings waiting to be synced when the last sync started",
+);
+pub static TRANSCRIPTION_SECONDS: Histogram<8> = Histogram::new(
+    "classroom_transcription_seconds",
+    "Time taken to transcribe a recording",
+    [1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0],
+);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MetricValue {
+    Counter { value: u64 },
+    Gauge { value: f64 },
+    /// `buckets` holds `(upper bound, observations at or below it)`
+    Histogram { buckets: Vec<(f64, u64)>, sum: f64, count: u64 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Metric {
+    pub name: &'static str,
+    pub help: &'static str,
+    #[serde(flatten)]
+    pub value: MetricValue,
+}
+
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        Self { name, help, value: AtomicU64::new(0) }
+    }
+
+    pub fn increment(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Metric {
+        Metric {
+            name: self.name,
+            help: self.help,
+            value: MetricValue::Counter { value: self.value.load(Ordering::Relaxed) },
+        }
+    }
+}
+
+/// An `f64` kept as its bits so it can be updated without a lock
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    bits: AtomicU64,
+}
+
+impl Gauge {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        Self { name, help, bits: AtomicU64::new(0) }
+    }
+
+    pub fn set(&self, value: f64) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Metric {
+        Metric {
+            name: self.name,
+            help: self.help,
+            value: MetricValue::Gauge { value: f64::from_bits(self.bits.load(Ordering::Relaxed)) },
+        }
+    }
+}
+
+pub struct Histogram<const N: usize> {
+    name: &'static str,
+    help: &'static str,
+    bounds: [f64; N],
+    /// Observations at or below each bound, not cumulative
+    counts: [AtomicU64; N],
+    count: AtomicU64,
+    sum_bits: AtomicU64,
+}
+
+impl<const N: usize> Histogram<N> {
+    const fn new(name: &'static str, help: &'static str, bounds: [f64; N]) -> Self {
+        Self {
+            name,
+            help,
+            bounds,
+            counts: [const { AtomicU64::new(0) }; N],
+            count: AtomicU64::new(0),
+            sum_bits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        if let Some(i) = self.bounds.iter().position(|&bound| value <= bound) {
+            self.counts[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum_bits
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| Some((f64::from_bits(bits) + value).to_bits()));
+    }
+
+    fn snapshot(&self) -> Metric {
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds
+            .iter()
+            .zip(&self.counts)
+            .map(|(&bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (bound, cumulative)
+            })
+            .collect();
+        Metric {
+            name: self.name,
+            help: self.help,
+            value: MetricValue::Histogram {
+                buckets,
+                sum: f64::from_bits(self.sum_bits.load(Ordering::Relaxed)),
+                count: self.count.load(Ordering::Relaxed),
+            },
+        }
+    }
+}
+
+pub fn snapshot() -> Vec<Metric> {
+    vec![
+        RECORDINGS_MADE.snapshot(),
+        SYNC_FAILURES.snapshot(),
+        AUDIO_OVERRUNS.snapshot(),
+        UNSYNCED_RECORDINGS.snapshot(),
+        TRANSCRIPTION_SECONDS.snapshot(),
+    ]
+}
+
+/// Every metric in the Prometheus text exposition format
+pub fn prometheus() -> String {
+    let mut out = String::new();
+    for metric in snapshot() {
+        let kind = match metric.value {
+            MetricValue::Counter { .. } => "counter",
+            MetricValue::Gauge { .. } => "gauge",
+            MetricValue::Histogram { .. } => "histogram",
+        };
+        out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", metric.name, metric.help, metric.name, kind));
+        match metric.value {
+            MetricValue::Counter { value } => out.push_str(&format!("{} {}\n", metric.name, value)),
+            MetricValue::Gauge { value } => out.push_str(&format!("{} {}\n", metric.name, value)),
+            MetricValue::Histogram { buckets, sum, count } => {
+                for (bound, cumulative) in buckets {
+                    out.push_str(&format!("{}_bucket{{le=\"{}\"}} {}\n", metric.name, bound, cumulative));
+                }
+                out.push_str(&format!("{}_bucket{{le=\"+Inf\"}} {}\n", metric.name, count));
+                out.push_str(&format!("{}_sum {}\n{}_count {}\n", metric.name, sum, metric.name, count));
+            }
+        }
+    }
+    out
+}
+
+/// Serve `GET /metrics` on `port`, loopback only, from a background thread.
+/// A scraper on the same machine (or a tunnel to it) reads it; nothing is
+/// reachable from the network.
+pub fn serve(port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = respond(stream) {
+                eprintln!("Metrics request failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = if request_line.starts_with("GET ") && path == "/metrics" {
+        ("200 OK", prometheus())
+    } else {
+        ("404 Not Found", String::new())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,231 @@
+use crate::db::{Assessment, Recording};
+use crate::whisper::TranscriptSegment;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+// Closing a term: everything outstanding synced, the term's recordings
+// copied to an archive that's read back and checked, then purged per
+// `term_close_purge` and the roster cleared for the next class. Each step
+// waits for the teacher to confirm it, and only runs once every step before
+// it has succeeded.
+
+const MANIFEST_FILE: &str = "manifest.json";
+const RECORDINGS_FILE: &str = "recordings.json";
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Archive copy of {0} doesn't match what was written")]
+    Mismatch(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TermStep {
+    Sync,
+    Archive,
+    Purge,
+    ResetRoster,
+}
+
+pub const STEPS: [TermStep; 4] = [TermStep::Sync, TermStep::Archive, TermStep::Purge, TermStep::ResetRoster];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    Done,
+    Failed,
+    /// Nothing to do with the current settings
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepState {
+    pub step: TermStep,
+    pub status: StepStatus,
+    /// What it will do while pending, what it did, or why it failed
+    pub detail: Option<String>,
+}
+
+/// A term close under way
+#[derive(Debug, Clone, Serialize)]
+pub struct TermClose {
+    pub started_at: String,
+    /// Where the archive is written
+    pub archive_folder: PathBuf,
+    pub steps: Vec<StepState>,
+    /// The archive written, once verified
+    pub archive_path: Option<PathBuf>,
+    /// Recordings in the verified archive; only these are purged
+    #[serde(skip)]
+    pub archived: Vec<String>,
+}
+
+impl TermClose {
+    pub fn new(archive_folder: PathBuf) -> Self {
+        Self {
+            started_at: chrono::Utc::now().to_rfc3339(),
+            archive_folder,
+            steps: STEPS
+                .iter()
+                .map(|&step| StepState { step, status: StepStatus::Pending, detail: None })
+                .collect(),
+            archive_path: None,
+            archived: Vec::new(),
+        }
+    }
+
+    /// The step the next confirmation runs; a failed one is tried again
+    pub fn next_step(&self) -> Option<TermStep> {
+        self.steps
+            .iter()
+            .find(|s| matches!(s.status, StepStatus::Pending | StepStatus::Failed))
+            .map(|s| s.step)
+    }
+
+    pub fn complete(&self) -> bool {
+        self.next_step().is_none()
+    }
+
+    fn state(&mut self, step: TermStep) -> &mut StepState {
+        self.steps
+            .iter_mut()
+            .find(|s| s.step == step)
+            .expect("every step has a state")
+    }
+
+    /// Say what a pending step will do
+    pub fn describe(&mut self, step: TermStep, detail: impl Into<String>) {
+        self.state(step).detail = Some(detail.into());
+    }
+
+    pub fn skip(&mut self, step: TermStep, detail: impl Into<String>) {
+        let state = self.state(step);
+        state.status = StepStatus::Skipped;
+        state.detail = Some(detail.into());
+    }
+
+    pub fn finish(&mut self, step: TermStep, outcome: Result<String, String>) {
+        let state = self.state(step);
+        let (status, detail) = match outcome {
+            Ok(summary) => (StepStatus::Done, summary),
+            Err(e) => (StepStatus::Failed, e),
+        };
+        state.status = status;
+        state.detail = Some(detail);
+    }
+}
+
+/// What the purge step does with archived recordings, from
+/// `term_close_purge`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PurgePolicy {
+    /// Leave them on the device
+    Keep,
+    /// Delete their audio, keeping transcripts and scores
+    #[default]
+    Audio,
+    /// Delete them outright
+    Recordings,
+}
+
+impl PurgePolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Keep => "keep",
+            Self::Audio => "audio",
+            Self::Recordings => "recordings",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "keep" => Some(Self::Keep),
+            "audio" => Some(Self::Audio),
+            "recordings" => Some(Self::Recordings),
+            _ => None,
+        }
+    }
+}
+
+/// One recording as kept in the archive
+#[derive(Debug, Serialize)]
+pub struct ArchivedRecording {
+    pub recording: Recording,
+    pub segments: Vec<TranscriptSegment>,
+    pub assessment: Option<Assessment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    /// Relative to the archive folder
+    file: String,
+    bytes: u64,
+    sha256: String,
+}
+
+/// Write `recordings` and their audio into a new dated folder in `folder`,
+/// with a manifest of every file's checksum. Audio already purged is left
+/// out. Returns the archive folder.
+pub fn write_archive(folder: &Path, recordings: &[ArchivedRecording]) -> Result<PathBuf, ArchiveError> {
+    let dir = folder.join(format!("term-archive-{}", chrono::Local::now().format("%Y-%m-%d-%H%M%S")));
+    std::fs::create_dir_all(&dir)?;
+
+    let mut files = vec![RECORDINGS_FILE.to_string()];
+    std::fs::write(dir.join(RECORDINGS_FILE), serde_json::to_vec_pretty(recordings)?)?;
+    for archived in recordings.iter().filter(|a| !a.recording.audio_purged) {
+        let audio_dir = Path::new("audio").join(&archived.recording.id);
+        for source in archived.recording.audio_files().map(Path::new).filter(|p| p.is_file()) {
+            let Some(name) = source.file_name() else {
+                continue;
+            };
+            std::fs::create_dir_all(dir.join(&audio_dir))?;
+            let file = audio_dir.join(name);
+            std::fs::copy(source, dir.join(&file))?;
+            files.push(file.to_string_lossy().replace('\\', "/"));
+        }
+    }
+
+    let manifest = files
+        .into_iter()
+        .map(|file| {
+            let path = dir.join(&file);
+            Ok(ManifestEntry {
+                bytes: std::fs::metadata(&path)?.len(),
+                sha256: sha256_file(&path)?,
+                file,
+            })
+        })
+        .collect::<Result<Vec<_>, ArchiveError>>()?;
+    std::fs::write(dir.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(dir)
+}
+
+/// Read an archive back and check every file against its manifest.
+/// Returns how many files were checked.
+pub fn verify_archive(dir: &Path) -> Result<usize, ArchiveError> {
+    let manifest: Vec<ManifestEntry> = serde_json::from_slice(&std::fs::read(dir.join(MANIFEST_FILE))?)?;
+    for entry in &manifest {
+        let path = dir.join(&entry.file);
+        let intact = path.is_file()
+            && std::fs::metadata(&path)?.len() == entry.bytes
+            && sha256_file(&path)?.eq_ignore_ascii_case(&entry.sha256);
+        if !intact {
+            return Err(ArchiveError::Mismatch(entry.file.clone()));
+        }
+    }
+    Ok(manifest.len())
+}
+
+fn sha256_file(path: &Path) -> Result<String, ArchiveError> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,83 @@
+use crate::whisper::TranscriptSegment;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WordTiming {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentTiming {
+    pub start: f64,
+    pub end: f64,
+    pub words: Vec<WordTiming>,
+}
+
+/// Canonical mapping from playback time to transcript segment and word,
+/// shared by the position events and the frontend highlighter.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingMap {
+    pub segments: Vec<SegmentTiming>,
+}
+
+impl TimingMap {
+    /// Word times are spread across each segment in proportion to word
+    /// length, since the CLI only reports segment boundaries.
+    pub fn from_segments(segments: &[TranscriptSegment]) -> Self {
+        let segments = segments
+            .iter()
+            .map(|segment| {
+                let words: Vec<&str> = segment.text.split_whitespace().collect();
+                let total_chars: usize = words.iter().map(|w| w.chars().count()).sum();
+                let duration = (segment.end - segment.start).max(0.0);
+
+                let mut cursor = segment.start;
+                let words = words
+                    .iter()
+                    .map(|word| {
+                        let share = if total_chars > 0 {
+                            word.chars().count() as f64 / total_chars as f64
+                        } else {
+                            0.0
+                        };
+                        let start = cursor;
+                        cursor += duration * share;
+                        WordTiming {
+                            start,
+                            end: cursor,
+                            text: word.to_string(),
+                        }
+                    })
+                    .collect();
+
+                SegmentTiming {
+                    start: segment.start,
+                    end: segment.end,
+                    words,
+                }
+            })
+            .collect();
+
+        Self { segments }
+    }
+
+    /// Segment and word index playing at `seconds`, if any
+    pub fn locate(&self, seconds: f64) -> (Option<usize>, Option<usize>) {
+        let Some(segment_index) = self
+            .segments
+            .iter()
+            .position(|s| seconds >= s.start && seconds < s.end)
+        else {
+            return (None, None);
+        };
+
+        let word_index = self.segments[segment_index]
+            .words
+            .iter()
+            .position(|w| seconds >= w.start && seconds < w.end);
+
+        (Some(segment_index), word_index)
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+// A coarse voiceprint: the typical pitch of a voice. It can't tell two
+// adults apart, but a teacher's voice sits well below the children they
+// teach, which is enough to say whether anyone but the teacher spoke.
+
+/// Frames are pitched this long, enough for two periods of a low voice
+const FRAME_SECONDS: f64 = 0.04;
+const MIN_PITCH_HZ: f64 = 70.0;
+const MAX_PITCH_HZ: f64 = 500.0;
+
+/// Quieter frames are skipped as silence
+const VOICED_DBFS: f64 = -45.0;
+
+/// Correlation a frame needs at its pitch period to count as voiced
+const VOICING_THRESHOLD: f64 = 0.5;
+
+/// Fewest voiced frames to go on, about half a second of voicing
+const MIN_VOICED_FRAMES: usize = 12;
+
+/// Voiced frames looked at before the rest is skipped, about 30 seconds;
+/// the median has long settled by then
+const MAX_VOICED_FRAMES: usize = 750;
+
+/// Voices pitched within this many octaves of each other are taken as the
+/// same speaker
+const MATCH_OCTAVES: f64 = 0.25;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Voiceprint {
+    /// Median pitch over the voiced frames
+    pub pitch_hz: f64,
+    pub voiced_seconds: f64,
+}
+
+impl Voiceprint {
+    pub fn from_samples(samples: &[f32], sample_rate: u32) -> Option<Self> {
+        Self::from_spans(std::iter::once(samples), sample_rate)
+    }
+
+    /// A voiceprint of everything in `spans`, taken as one speaker. None
+    /// when too little of it is voiced.
+    pub fn from_spans<'a>(spans: impl IntoIterator<Item = &'a [f32]>, sample_rate: u32) -> Option<Self> {
+        let frame = (FRAME_SECONDS * sample_rate as f64) as usize;
+        if frame == 0 {
+            return None;
+        }
+        let mut pitches: Vec<f64> = spans
+            .into_iter()
+            .flat_map(|span| span.chunks_exact(frame))
+            .filter_map(|f| frame_pitch(f, sample_rate))
+            .take(MAX_VOICED_FRAMES)
+            .collect();
+        if pitches.len() < MIN_VOICED_FRAMES {
+            return None;
+        }
+        pitches.sort_by(f64::total_cmp);
+        Some(Self {
+            pitch_hz: pitches[pitches.len() / 2],
+            voiced_seconds: pitches.len() as f64 * FRAME_SECONDS,
+        })
+    }
+
+    pub fn matches(&self, other: &Voiceprint) -> bool {
+        self.octaves_from(other) <= MATCH_OCTAVES
+    }
+
+    /// How far apart the two voices are pitched, for picking the closer of
+    /// two that both match
+    pub fn octaves_from(&self, other: &Voiceprint) -> f64 {
+        (self.pitch_hz / other.pitch_hz).log2().abs()
+    }
+}
+
+/// Pitch of one frame by autocorrelation, or None if it's quiet or unvoiced
+fn frame_pitch(frame: &[f32], sample_rate: u32) -> Option<f64> {
+    // Running sums of squares, so each lag's overlap power is a subtraction
+    let mut power = Vec::with_capacity(frame.len() + 1);
+    power.push(0.0);
+    for &s in frame {
+        power.push(power[power.len() - 1] + s as f64 * s as f64);
+    }
+    let n = frame.len();
+    let energy = power[n];
+    if energy <= 0.0 || 10.0 * (energy / n as f64).log10() < VOICED_DBFS {
+        return None;
+    }
+    let min_lag = (sample_rate as f64 / MAX_PITCH_HZ) as usize;
+    let max_lag = ((sample_rate as f64 / MIN_PITCH_HZ) as usize).min(n / 2);
+    let correlations: Vec<(usize, f64)> = (min_lag.max(1)..=max_lag)
+        .map(|lag| {
+            let product: f64 = frame[..n - lag]
+                .iter()
+                .zip(&frame[lag..])
+                .map(|(&a, &b)| a as f64 * b as f64)
+                .sum();
+            let overlap = (power[n - lag] * (energy - power[lag])).sqrt();
+            (lag, product / overlap.max(f64::MIN_POSITIVE))
+        })
+        .collect();
+    let best = correlations.iter().map(|&(_, c)| c).fold(f64::NEG_INFINITY, f64::max);
+    if best < VOICING_THRESHOLD {
+        return None;
+    }
+    // Multiples of the period correlate about as well; the shortest is the
+    // pitch, taken at the top of its peak
+    let mut i = correlations.iter().position(|&(_, c)| c >= best * 0.9)?;
+    while correlations.get(i + 1).is_some_and(|&(_, next)| next > correlations[i].1) {
+        i += 1;
+    }
+    Some(sample_rate as f64 / correlations[i].0 as f64)
+}
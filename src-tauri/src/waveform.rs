@@ -0,0 +1,101 @@
+use crate::audio::{AudioError, AudioReader};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Upper bound on buckets so a bad request can't build a huge payload
+pub const MAX_BUCKETS: usize = 4000;
+
+/// Min/max sample peaks over equal slices of a recording, enough for the
+/// frontend to draw a waveform and map clicks back to a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Waveform {
+    pub duration_seconds: f64,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+impl Waveform {
+    /// `buckets` peaks across `samples`; a bucket past the end of very short
+    /// audio is silent
+    pub fn from_samples(samples: &[f32], sample_rate: u32, buckets: usize) -> Self {
+        let buckets = buckets.clamp(1, MAX_BUCKETS);
+        let mut min = vec![0.0f32; buckets];
+        let mut max = vec![0.0f32; buckets];
+
+        for bucket in 0..buckets {
+            let start = bucket * samples.len() / buckets;
+            let end = ((bucket + 1) * samples.len() / buckets).max(start);
+            if let Some(slice) = samples.get(start..end).filter(|s| !s.is_empty()) {
+                min[bucket] = slice.iter().copied().fold(f32::INFINITY, f32::min);
+                max[bucket] = slice.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            }
+        }
+
+        Self {
+            duration_seconds: seconds(samples.len(), sample_rate),
+            min,
+            max,
+        }
+    }
+
+    /// Same peaks as `from_samples`, read a block at a time so only the
+    /// buckets are ever in memory
+    pub fn from_reader(reader: &mut AudioReader, buckets: usize) -> Result<Self, AudioError> {
+        let total = reader.frames() as usize;
+        let mut block = Vec::new();
+        if total == 0 {
+            // Length unknown up front, so bucket boundaries can't be placed
+            while reader.next_block(&mut block)? {}
+            return Ok(Self::from_samples(&block, reader.sample_rate(), buckets));
+        }
+
+        let buckets = buckets.clamp(1, MAX_BUCKETS);
+        let mut min = vec![f32::INFINITY; buckets];
+        let mut max = vec![f32::NEG_INFINITY; buckets];
+        let mut bucket = 0;
+        let mut bucket_end = total / buckets;
+        let mut index = 0;
+        while reader.next_block(&mut block)? {
+            for &sample in &block {
+                while index >= bucket_end && bucket + 1 < buckets {
+                    bucket += 1;
+                    bucket_end = (bucket + 1) * total / buckets;
+                }
+                min[bucket] = min[bucket].min(sample);
+                max[bucket] = max[bucket].max(sample);
+                index += 1;
+            }
+            block.clear();
+        }
+
+        // Buckets nothing landed in are silent
+        for value in min.iter_mut().chain(max.iter_mut()) {
+            if value.is_infinite() {
+                *value = 0.0;
+            }
+        }
+        Ok(Self {
+            duration_seconds: seconds(index, reader.sample_rate()),
+            min,
+            max,
+        })
+    }
+}
+
+/// Size and modification time of the audio a waveform is drawn from, so
+/// peaks cached before the file was rewritten aren't served for the new
+/// audio. None when the file can't be read, as once it's been purged.
+pub fn audio_fingerprint(path: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{}:{}", metadata.len(), modified.as_nanos()))
+}
+
+fn seconds(samples: usize, sample_rate: u32) -> f64 {
+    if sample_rate > 0 {
+        samples as f64 / sample_rate as f64
+    } else {
+        0.0
+    }
+}
@@ -1,24 +1,540 @@
-use std::path::PathBuf;
-use std::process::Command;
+use crate::poison::LockExt;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum WhisperError {
     #[error("Model not found at {0}")]
     ModelNotFound(String),
-    #[error("Whisper CLI not found. Please install: brew install whisper-cpp")]
+    #[error("Whisper CLI not found. Install whisper.cpp, or set where it is in settings")]
     CliNotFound,
+    #[error("WhisperX not found. Please install: pip install whisperx")]
+    WhisperXNotFound,
     #[error("Transcription failed: {0}")]
     TranscriptionError(String),
+    #[error("Transcription was cancelled")]
+    Cancelled,
 }
 
+/// One timed span of the transcript, in seconds from the start of the audio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    /// Mean token probability (0-1), when the backend reports one
+    #[serde(default)]
+    pub confidence: Option<f64>,
+    /// Mean log probability of the tokens, as Whisper reports it
+    #[serde(default)]
+    pub avg_logprob: Option<f64>,
+    /// Chance the span holds no speech at all, when the backend reports it
+    #[serde(default)]
+    pub no_speech_prob: Option<f64>,
+    /// Who said it, such as "teacher" or WhisperX's "SPEAKER_00", when
+    /// that's known; the recording's speaker labels name them
+    #[serde(default)]
+    pub speaker: Option<String>,
+    /// Timed words, when word timestamps were asked for and the backend
+    /// could place them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<TranscriptWord>,
+}
+
+/// One word of a segment, in seconds from the start of the audio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptWord {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    #[serde(default)]
+    pub confidence: Option<f64>,
+}
+
+/// Whisper's own cut-offs for a decode it would retry: below this mean log
+/// probability, or above this no-speech probability
+pub const LOW_LOGPROB: f64 = -1.0;
+pub const LIKELY_NO_SPEECH: f64 = 0.6;
+
+/// Why a segment looks doubtful
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Doubt {
+    /// Mean token probability under the threshold asked for
+    LowConfidence,
+    /// Mean log probability under `LOW_LOGPROB`
+    LowLogprob,
+    /// Probably not speech, so the text may be made up
+    NoSpeech,
+}
+
+impl TranscriptSegment {
+    /// What makes the segment doubtful with `threshold` as the lowest
+    /// acceptable confidence; empty when nothing does
+    pub fn doubts(&self, threshold: f64) -> Vec<Doubt> {
+        let mut doubts = Vec::new();
+        if self.confidence.is_some_and(|c| c < threshold) {
+            doubts.push(Doubt::LowConfidence);
+        }
+        if self.avg_logprob.is_some_and(|l| l < LOW_LOGPROB) {
+            doubts.push(Doubt::LowLogprob);
+        }
+        if self.no_speech_prob.is_some_and(|p| p > LIKELY_NO_SPEECH) {
+            doubts.push(Doubt::NoSpeech);
+        }
+        doubts
+    }
+
+    /// Move the segment and its words `seconds` later, or earlier when
+    /// negative, stopping at the start of the audio
+    pub fn shift(&mut self, seconds: f64) {
+        self.start = (self.start + seconds).max(0.0);
+        self.end = (self.end + seconds).max(0.0);
+        for word in &mut self.words {
+            word.start = (word.start + seconds).max(0.0);
+            word.end = (word.end + seconds).max(0.0);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TranscriptionResult {
+    pub text: String,
+    pub segments: Vec<TranscriptSegment>,
+    /// Language the audio was decoded as, when the backend reports it
+    pub language: Option<String>,
+}
+
+// Subset of whisper-cli's `-ojf` output
+#[derive(Deserialize)]
+struct CliJson {
+    #[serde(default)]
+    result: Option<CliResult>,
+    transcription: Vec<CliSegment>,
+}
+
+#[derive(Deserialize)]
+struct CliResult {
+    language: String,
+}
+
+#[derive(Deserialize)]
+struct CliSegment {
+    offsets: CliOffsets,
+    text: String,
+    #[serde(default)]
+    tokens: Vec<CliToken>,
+}
+
+#[derive(Deserialize)]
+struct CliToken {
+    text: String,
+    p: f64,
+    #[serde(default)]
+    offsets: Option<CliOffsets>,
+    /// Where `-dtw` placed the token, in hundredths of a second; -1 when
+    /// it wasn't run
+    #[serde(default)]
+    t_dtw: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct CliOffsets {
+    from: i64,
+    to: i64,
+}
+
+/// Language setting that has a multilingual model pick the language from
+/// the first 30 seconds of each recording
+pub const AUTO_LANGUAGE: &str = "auto";
+
+/// Per-run decoding options
+#[derive(Debug, Clone, Default)]
+pub struct TranscribeOptions {
+    /// Known reading passage; decoding is biased toward its vocabulary
+    pub passage: Option<String>,
+    /// Language code to decode as, or `AUTO_LANGUAGE` to let the model
+    /// pick; English when unset
+    pub language: Option<String>,
+    /// Watches the run and can stop it
+    pub monitor: Option<TranscriptionMonitor>,
+    /// Time each word as well as each segment
+    pub word_timestamps: bool,
+    /// How many people diarizing may find
+    pub speakers: SpeakerCount,
+    /// Names and terms the audio is likely to hold, such as the student's
+    /// name or "photosynthesis", prompted so they're spelled right
+    pub vocabulary: Vec<String>,
+    /// Give the transcript in English whatever language is spoken, with
+    /// `language` as the language to listen for
+    pub translate: bool,
+}
+
+impl TranscribeOptions {
+    /// What the model is primed with: the passage, then the vocabulary.
+    /// Whisper keeps the end of an overlong prompt, so the vocabulary comes
+    /// last to survive a long passage.
+    fn initial_prompt(&self) -> Option<String> {
+        let passage = self.passage.as_deref().map(str::trim).filter(|p| !p.is_empty());
+        let terms = (!self.vocabulary.is_empty()).then(|| format!("{}.", self.vocabulary.join(", ")));
+        match (passage, terms) {
+            (Some(passage), Some(terms)) => Some(format!("{}\n{}", passage, terms)),
+            (passage, terms) => passage.map(str::to_string).or(terms),
+        }
+    }
+}
+
+/// Bounds on the number of speakers; unset ones are left to the model.
+/// Without them it often hears a third person in a two-person session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SpeakerCount {
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+
+/// How far a transcription has got
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TranscriptionProgress {
+    pub percent: f32,
+    /// Segments decoded so far, joined. WhisperX only reports the percent.
+    pub partial_text: String,
+}
+
+type ProgressCallback = Box<dyn Fn(&TranscriptionProgress) + Send + Sync>;
+
+/// Shared with a running transcription to follow its progress and cancel
+/// it. Cancelling kills the engine's process, so nothing is left running.
+#[derive(Clone)]
+pub struct TranscriptionMonitor {
+    inner: Arc<MonitorInner>,
+}
+
+struct MonitorInner {
+    cancelled: AtomicBool,
+    child: Mutex<Option<Child>>,
+    progress: Mutex<TranscriptionProgress>,
+    on_progress: ProgressCallback,
+}
+
+impl TranscriptionMonitor {
+    /// `on_progress` is called each time the percent or text moves on
+    pub fn new(on_progress: impl Fn(&TranscriptionProgress) + Send + Sync + 'static) -> Self {
+        Self {
+            inner: Arc::new(MonitorInner {
+                cancelled: AtomicBool::new(false),
+                child: Mutex::new(None),
+                progress: Mutex::new(TranscriptionProgress::default()),
+                on_progress: Box::new(on_progress),
+            }),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        if let Some(child) = self.inner.child.lock_or_recover().as_mut() {
+            let _ = child.kill();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn update(&self, change: impl FnOnce(&mut TranscriptionProgress)) {
+        let mut progress = self.inner.progress.lock_or_recover();
+        change(&mut progress);
+        (self.inner.on_progress)(&progress);
+    }
+
+    /// Run `command` to completion like `Command::output`, reporting
+    /// progress from its output as it goes. `parse_text` picks decoded text
+    /// out of a line of stdout.
+    fn run(&self, command: &mut Command, parse_text: fn(&str) -> Option<String>) -> Result<Output, WhisperError> {
+        let spawn_error = |e: std::io::Error| WhisperError::TranscriptionError(e.to_string());
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(spawn_error)?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        {
+            let mut slot = self.inner.child.lock_or_recover();
+            // Cancelled before it started
+            if self.is_cancelled() {
+                let _ = child.kill();
+            }
+            *slot = Some(child);
+        }
+
+        // Each pipe is drained on its own thread so neither fills up and
+        // stalls the process while the other is read
+        let monitor = self.clone();
+        let stderr_reader = std::thread::spawn(move || {
+            read_lines(stderr, |line| {
+                if let Some(percent) = parse_progress(line) {
+                    monitor.update(|p| p.percent = percent);
+                }
+            })
+        });
+        let stdout = read_lines(stdout, |line| {
+            if let Some(text) = parse_text(line) {
+                self.update(|p| {
+                    if !p.partial_text.is_empty() {
+                        p.partial_text.push(' ');
+                    }
+                    p.partial_text.push_str(&text);
+                });
+            }
+        });
+        let stderr = stderr_reader.join().unwrap_or_default();
+
+        let child = self.inner.child.lock_or_recover().take();
+        let status = match child {
+            Some(mut child) => child.wait().map_err(spawn_error)?,
+            None => return Err(WhisperError::Cancelled),
+        };
+        if self.is_cancelled() {
+            return Err(WhisperError::Cancelled);
+        }
+        Ok(Output { status, stdout, stderr })
+    }
+}
+
+impl std::fmt::Debug for TranscriptionMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TranscriptionMonitor")
+            .field("cancelled", &self.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Everything read from `pipe`, handing each line to `on_line` as it comes
+fn read_lines(pipe: Option<impl Read>, mut on_line: impl FnMut(&str)) -> Vec<u8> {
+    let mut all = Vec::new();
+    let Some(pipe) = pipe else {
+        return all;
+    };
+    for line in BufReader::new(pipe).split(b'\n').map_while(Result::ok) {
+        on_line(String::from_utf8_lossy(&line).trim_end());
+        all.extend_from_slice(&line);
+        all.push(b'\n');
+    }
+    all
+}
+
+/// The percent in a progress line, whisper.cpp's `progress =  42%` or
+/// WhisperX's `Progress: 42.00%...`. The last "progress" is the one
+/// before the number, since whisper.cpp's line starts with
+/// `whisper_print_progress_callback:`.
+fn parse_progress(line: &str) -> Option<f32> {
+    let at = line.to_ascii_lowercase().rfind("progress")?;
+    let rest = line[at + "progress".len()..].trim_start_matches([' ', '=', ':']);
+    let number: String = rest.chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+    if !rest[number.len()..].starts_with('%') {
+        return None;
+    }
+    number.parse::<f32>().ok().map(|p| p.clamp(0.0, 100.0))
+}
+
+/// Text of one of whisper-cli's `[00:00:00.000 --> 00:00:02.000]  text` lines
+fn parse_cli_text(line: &str) -> Option<String> {
+    parse_stdout_segments(line).pop().map(|s| s.text)
+}
+
+/// Run `command` under `monitor` when there is one
+fn run_command(
+    command: &mut Command,
+    monitor: Option<&TranscriptionMonitor>,
+    parse_text: fn(&str) -> Option<String>,
+) -> Result<Output, WhisperError> {
+    match monitor {
+        Some(monitor) => monitor.run(command, parse_text),
+        None => command
+            .output()
+            .map_err(|e| WhisperError::TranscriptionError(e.to_string())),
+    }
+}
+
+/// The language whisper hears in a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectedLanguage {
+    /// Language code, such as "en" or "es"
+    pub code: String,
+    pub probability: f64,
+}
+
+// Subset of WhisperX's `--output_format json` output
+#[derive(Deserialize)]
+struct WhisperXJson {
+    segments: Vec<WhisperXSegment>,
+    #[serde(default)]
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WhisperXSegment {
+    start: f64,
+    end: f64,
+    text: String,
+    #[serde(default)]
+    words: Vec<WhisperXWord>,
+    /// Only there when WhisperX was run with diarization
+    #[serde(default)]
+    speaker: Option<String>,
+    /// Only there from versions that pass faster-whisper's scores through
+    #[serde(default)]
+    avg_logprob: Option<f64>,
+    #[serde(default)]
+    no_speech_prob: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct WhisperXWord {
+    #[serde(default)]
+    word: String,
+    /// These are missing for words alignment couldn't place
+    #[serde(default)]
+    start: Option<f64>,
+    #[serde(default)]
+    end: Option<f64>,
+    #[serde(default)]
+    score: Option<f64>,
+}
+
+// Penalty whisper.cpp applies to tokens outside the grammar. Soft enough
+// that genuine misreadings still come through for miscue analysis.
+const GRAMMAR_PENALTY: &str = "40";
+
+/// A speech-to-text engine. The app holds whichever one the
+/// `transcription_backend` setting picks, boxed, so adding an engine means
+/// adding an implementation rather than changing its callers.
+pub trait TranscriptionBackend: Send {
+    fn transcribe_with(
+        &self,
+        audio_path: &Path,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriptionResult, WhisperError>;
+
+    fn transcribe(&self, audio_path: &Path) -> Result<TranscriptionResult, WhisperError> {
+        self.transcribe_with(audio_path, &TranscribeOptions::default())
+    }
+
+    /// Whether the model covers more than English
+    fn is_multilingual(&self) -> bool;
+
+    /// Which language is spoken in the first 30 seconds of the audio. Needs
+    /// a multilingual model.
+    fn detect_language(&self, audio_path: &Path) -> Result<DetectedLanguage, WhisperError>;
+
+    /// Tell speakers apart from here on, with the Hugging Face token that
+    /// unlocks the diarization models; None stops. Engines that can't
+    /// diarize ignore it.
+    fn set_hf_token(&mut self, _token: Option<String>) {}
+
+    /// Whether transcripts come back with their speakers
+    fn diarizes(&self) -> bool {
+        false
+    }
+}
+
+/// Engines the `transcription_backend` setting can pick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// whisper.cpp's CLI, reading the ggml model file
+    #[default]
+    WhisperCpp,
+    /// The WhisperX Python tool, with the same model size as the ggml file
+    WhisperX,
+}
+
+impl BackendKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BackendKind::WhisperCpp => "whisper_cpp",
+            BackendKind::WhisperX => "whisperx",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "whisper_cpp" => Some(BackendKind::WhisperCpp),
+            "whisperx" => Some(BackendKind::WhisperX),
+            _ => None,
+        }
+    }
+}
+
+/// Load `kind` with the model at `model_path`, diarizing with `hf_token`
+/// if it can
+pub fn load_backend(
+    kind: BackendKind,
+    model_path: &Path,
+    hf_token: Option<String>,
+) -> Result<Box<dyn TranscriptionBackend>, WhisperError> {
+    let mut backend: Box<dyn TranscriptionBackend> = match kind {
+        BackendKind::WhisperCpp => Box::new(Transcriber::new(model_path)?),
+        BackendKind::WhisperX => Box::new(WhisperX::new(model_path)?),
+    };
+    backend.set_hf_token(hf_token);
+    Ok(backend)
+}
+
+/// Whether a model named like `ggml-base.en.bin` is English-only
+fn english_only(model_path: &Path) -> bool {
+    model_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.contains(".en."))
+}
+
+/// Models whisper.cpp can align token times for with `-dtw`, by the
+/// presets' names
+const DTW_PRESETS: &[&str] = &[
+    "tiny", "tiny.en", "base", "base.en", "small", "small.en", "medium", "medium.en", "large.v1", "large.v2",
+    "large.v3", "large.v3.turbo",
+];
+
+/// The `-dtw` preset for a model named like `ggml-large-v3-turbo.bin`.
+/// Quantized and fine-tuned models have none.
+fn dtw_preset(model_path: &Path) -> Option<String> {
+    let file_name = model_path.file_name()?.to_str()?;
+    let preset = file_name.strip_prefix("ggml-")?.strip_suffix(".bin")?.replace('-', ".");
+    DTW_PRESETS.contains(&preset.as_str()).then_some(preset)
+}
+
+/// The language `options` ask for, refused up front when an English-only
+/// model would only turn it into English-sounding nonsense
+fn requested_language(options: &TranscribeOptions, multilingual: bool) -> Result<&str, WhisperError> {
+    let language = options.language.as_deref().unwrap_or("en");
+    if language != "en" && !multilingual {
+        return Err(WhisperError::TranscriptionError(format!(
+            "English-only models cannot transcribe \"{}\"; a multilingual model is needed",
+            language
+        )));
+    }
+    if options.translate && !multilingual {
+        return Err(WhisperError::TranscriptionError(
+            "English-only models cannot translate; a multilingual model is needed".to_string(),
+        ));
+    }
+    Ok(language)
+}
+
+/// whisper.cpp, run through its CLI
 pub struct Transcriber {
     model_path: PathBuf,
     whisper_cli: PathBuf,
 }
 
 impl Transcriber {
-    pub fn new(model_path: &PathBuf) -> Result<Self, WhisperError> {
+    pub fn new(model_path: &Path) -> Result<Self, WhisperError> {
         if !model_path.exists() {
             return Err(WhisperError::ModelNotFound(
                 model_path.to_string_lossy().to_string(),
@@ -29,13 +545,100 @@ impl Transcriber {
         let whisper_cli = find_whisper_cli()?;
 
         Ok(Self {
-            model_path: model_path.clone(),
+            model_path: model_path.to_path_buf(),
             whisper_cli,
         })
     }
+}
+
+impl TranscriptionBackend for Transcriber {
+    fn transcribe_with(
+        &self,
+        audio_path: &Path,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriptionResult, WhisperError> {
+        let language = requested_language(options, self.is_multilingual())?;
+        let mut command = Command::new(&self.whisper_cli);
+        command.args([
+            "-m",
+            self.model_path.to_str().unwrap(),
+            "-f",
+            audio_path.to_str().unwrap(),
+            "-l",
+            language,
+            "-ojf",
+        ]);
+        if let Some(threads) = threads() {
+            command.args(["-t", &threads.to_string()]);
+        }
+        if options.translate {
+            command.arg("-tr");
+        }
+        if options.monitor.is_some() {
+            command.arg("--print-progress");
+        }
+        // Token times come with the full JSON anyway; this lines them up
+        if let Some(preset) = dtw_preset(&self.model_path).filter(|_| options.word_timestamps) {
+            command.args(["-dtw", &preset]);
+        }
+
+        if let Some(prompt) = options.initial_prompt() {
+            command.arg("--prompt").arg(prompt);
+        }
+        // Constrain toward the passage vocabulary
+        let grammar_path = audio_path.with_extension("gbnf");
+        if let Some(grammar) = options.passage.as_deref().and_then(passage_grammar) {
+            std::fs::write(&grammar_path, grammar)
+                .map_err(|e| WhisperError::TranscriptionError(e.to_string()))?;
+            command
+                .arg("--grammar")
+                .arg(&grammar_path)
+                .args(["--grammar-rule", "root", "--grammar-penalty", GRAMMAR_PENALTY]);
+        }
 
-    pub fn transcribe(&self, audio_path: &PathBuf) -> Result<String, WhisperError> {
         // Run whisper CLI
+        let output = run_command(&mut command, options.monitor.as_ref(), parse_cli_text);
+        let _ = std::fs::remove_file(&grammar_path);
+        let json_path = audio_path.with_extension("wav.json");
+        if matches!(output, Err(WhisperError::Cancelled)) {
+            let _ = std::fs::remove_file(&json_path);
+        }
+        let output = output?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(WhisperError::TranscriptionError(stderr.to_string()));
+        }
+
+        // Read the JSON output (whisper creates .json file next to input)
+        let (segments, detected) = if json_path.exists() {
+            let json = std::fs::read_to_string(&json_path)
+                .map_err(|e| WhisperError::TranscriptionError(e.to_string()))?;
+            // Clean up the json file
+            let _ = std::fs::remove_file(&json_path);
+            parse_json_segments(&json, options.word_timestamps)?
+        } else {
+            // Fallback: parse timestamped stdout
+            (parse_stdout_segments(&String::from_utf8_lossy(&output.stdout)), None)
+        };
+
+        Ok(TranscriptionResult {
+            text: join_segments(&segments),
+            segments,
+            language: detected.or_else(|| (language != AUTO_LANGUAGE).then(|| language.to_string())),
+        })
+    }
+
+    fn is_multilingual(&self) -> bool {
+        !english_only(&self.model_path)
+    }
+
+    fn detect_language(&self, audio_path: &Path) -> Result<DetectedLanguage, WhisperError> {
+        if !self.is_multilingual() {
+            return Err(WhisperError::TranscriptionError(
+                "English-only models cannot detect the language".to_string(),
+            ));
+        }
         let output = Command::new(&self.whisper_cli)
             .args([
                 "-m",
@@ -43,66 +646,806 @@ impl Transcriber {
                 "-f",
                 audio_path.to_str().unwrap(),
                 "-l",
-                "en",
-                "--no-timestamps",
-                "-otxt",
+                "auto",
+                "--detect-language",
             ])
             .output()
             .map_err(|e| WhisperError::TranscriptionError(e.to_string()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(WhisperError::TranscriptionError(stderr.to_string()));
+        }
+
+        // whisper.cpp logs the result rather than printing it
+        let logs = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(&output.stdout)
+        );
+        parse_detected_language(&logs)
+            .ok_or_else(|| WhisperError::TranscriptionError("Language detection gave no result".to_string()))
+    }
+}
+
+/// WhisperX, run as a subprocess. It downloads and caches its own
+/// faster-whisper models, so the ggml file only names the size to use and
+/// doesn't have to be present.
+pub struct WhisperX {
+    /// Such as "base.en", from `ggml-base.en.bin`
+    model: String,
+    whisperx: PathBuf,
+    /// Diarizes when set
+    hf_token: Option<String>,
+}
+
+impl WhisperX {
+    pub fn new(model_path: &Path) -> Result<Self, WhisperError> {
+        let file_name = model_path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let model = file_name
+            .strip_prefix("ggml-")
+            .unwrap_or(file_name)
+            .trim_end_matches(".bin")
+            .to_string();
+        if model.is_empty() {
+            return Err(WhisperError::ModelNotFound(model_path.to_string_lossy().to_string()));
+        }
+        let whisperx = find_whisperx().ok_or(WhisperError::WhisperXNotFound)?;
+        Ok(Self {
+            model,
+            whisperx,
+            hf_token: None,
+        })
+    }
+
+    /// Run WhisperX on `audio_path`, returning its JSON output and logs.
+    /// Diarizes too, finding `diarize` speakers, if that's set and there's
+    /// a token.
+    fn run(
+        &self,
+        audio_path: &Path,
+        language: Option<&str>,
+        prompt: Option<&str>,
+        translate: bool,
+        monitor: Option<&TranscriptionMonitor>,
+        diarize: Option<SpeakerCount>,
+    ) -> Result<(String, String), WhisperError> {
+        let output_dir = audio_path.with_extension("whisperx");
+        std::fs::create_dir_all(&output_dir).map_err(|e| WhisperError::TranscriptionError(e.to_string()))?;
+        let mut command = Command::new(&self.whisperx);
+        command
+            .arg(audio_path)
+            .args(["--model", &self.model, "--output_format", "json", "--compute_type", "int8"])
+            .arg("--output_dir")
+            .arg(&output_dir);
+        if let Some(language) = language {
+            command.args(["--language", language]);
+        }
+        if let Some(prompt) = prompt {
+            command.args(["--initial_prompt", prompt]);
+        }
+        if translate {
+            command.args(["--task", "translate"]);
+        }
+        if let Some(threads) = threads() {
+            command.args(["--threads", &threads.to_string()]);
+        }
+        if monitor.is_some() {
+            command.args(["--print_progress", "True"]);
+        }
+        // Passed in the environment, where other users' process listings
+        // can't see it; the Hugging Face client reads it from there
+        if let (Some(token), Some(speakers)) = (self.hf_token.as_ref(), diarize) {
+            command.arg("--diarize").env("HF_TOKEN", token);
+            if let Some(min) = speakers.min {
+                command.args(["--min_speakers", &min.to_string()]);
+            }
+            if let Some(max) = speakers.max {
+                command.args(["--max_speakers", &max.to_string()]);
+            }
+        }
 
+        let output = run_command(&mut command, monitor, |_| None);
+        let json_path = output_dir
+            .join(audio_path.file_stem().unwrap_or_default())
+            .with_extension("json");
+        let json = std::fs::read_to_string(&json_path);
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let output = output?;
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(WhisperError::TranscriptionError(stderr.to_string()));
         }
+        let logs = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(&output.stdout)
+        );
+        Ok((json.map_err(|e| WhisperError::TranscriptionError(e.to_string()))?, logs))
+    }
+}
 
-        // Read the output text file (whisper creates .txt file next to input)
-        let txt_path = audio_path.with_extension("wav.txt");
-        if txt_path.exists() {
-            let transcript = std::fs::read_to_string(&txt_path)
-                .map_err(|e| WhisperError::TranscriptionError(e.to_string()))?;
-            // Clean up the txt file
-            let _ = std::fs::remove_file(&txt_path);
-            return Ok(transcript.trim().to_string());
+impl TranscriptionBackend for WhisperX {
+    fn transcribe_with(
+        &self,
+        audio_path: &Path,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriptionResult, WhisperError> {
+        // No grammar support, so the passage is only a prompt
+        let prompt = options.initial_prompt();
+        // Left off, WhisperX detects the language itself
+        let language = Some(requested_language(options, self.is_multilingual())?).filter(|&l| l != AUTO_LANGUAGE);
+        let (json, _) = self.run(
+            audio_path,
+            language,
+            prompt.as_deref(),
+            options.translate,
+            options.monitor.as_ref(),
+            Some(options.speakers),
+        )?;
+        let (segments, detected) = parse_whisperx_segments(&json, options.word_timestamps)?;
+        Ok(TranscriptionResult {
+            text: join_segments(&segments),
+            segments,
+            language: detected.or_else(|| language.map(str::to_string)),
+        })
+    }
+
+    fn is_multilingual(&self) -> bool {
+        !self.model.ends_with(".en")
+    }
+
+    fn set_hf_token(&mut self, token: Option<String>) {
+        self.hf_token = token;
+    }
+
+    fn diarizes(&self) -> bool {
+        self.hf_token.is_some()
+    }
+
+    /// WhisperX only detects the language on the way to transcribing, so
+    /// this costs a full transcription
+    fn detect_language(&self, audio_path: &Path) -> Result<DetectedLanguage, WhisperError> {
+        if !self.is_multilingual() {
+            return Err(WhisperError::TranscriptionError(
+                "English-only models cannot detect the language".to_string(),
+            ));
         }
+        let (_, logs) = self.run(audio_path, None, None, false, None, None)?;
+        parse_whisperx_language(&logs)
+            .ok_or_else(|| WhisperError::TranscriptionError("Language detection gave no result".to_string()))
+    }
+}
 
-        // Fallback: parse stdout
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        Ok(stdout.trim().to_string())
+/// Segments from WhisperX's JSON, each scored by its aligned words and
+/// with them when `words` is set, and the language it transcribed
+fn parse_whisperx_segments(
+    json: &str,
+    words: bool,
+) -> Result<(Vec<TranscriptSegment>, Option<String>), WhisperError> {
+    let parsed: WhisperXJson =
+        serde_json::from_str(json).map_err(|e| WhisperError::TranscriptionError(e.to_string()))?;
+    let segments = parsed
+        .segments
+        .into_iter()
+        .map(|s| {
+            let scores: Vec<f64> = s.words.iter().filter_map(|w| w.score).collect();
+            TranscriptSegment {
+                start: s.start,
+                end: s.end,
+                text: s.text.trim().to_string(),
+                confidence: (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64),
+                avg_logprob: s.avg_logprob,
+                no_speech_prob: s.no_speech_prob,
+                speaker: s.speaker,
+                words: if words { aligned_words(&s.words) } else { Vec::new() },
+            }
+        })
+        .filter(|s| !s.text.is_empty())
+        .collect();
+    Ok((segments, parsed.language))
+}
+
+/// The words alignment placed; the rest are left out rather than guessed at
+fn aligned_words(words: &[WhisperXWord]) -> Vec<TranscriptWord> {
+    words
+        .iter()
+        .filter_map(|w| {
+            Some(TranscriptWord {
+                start: w.start?,
+                end: w.end?,
+                text: w.word.trim().to_string(),
+                confidence: w.score,
+            })
+        })
+        .filter(|w| !w.text.is_empty())
+        .collect()
+}
+
+/// Find `Detected language: es (0.98) in first 30s of audio` in WhisperX's logs
+fn parse_whisperx_language(logs: &str) -> Option<DetectedLanguage> {
+    let (_, rest) = logs.split_once("Detected language:")?;
+    let (code, rest) = rest.trim_start().split_once(char::is_whitespace)?;
+    let probability = rest
+        .trim_start()
+        .strip_prefix('(')
+        .and_then(|p| p.split(')').next())
+        .and_then(|p| p.trim().parse().ok())
+        .unwrap_or(0.0);
+    Some(DetectedLanguage {
+        code: code.to_string(),
+        probability,
+    })
+}
+
+/// Find `auto-detected language: es (p = 0.981234)` in whisper.cpp's logs
+fn parse_detected_language(logs: &str) -> Option<DetectedLanguage> {
+    let (_, rest) = logs.split_once("auto-detected language:")?;
+    let (code, rest) = rest.trim_start().split_once(char::is_whitespace)?;
+    let probability = rest
+        .trim_start()
+        .strip_prefix("(p =")
+        .and_then(|p| p.split(')').next())
+        .and_then(|p| p.trim().parse().ok())
+        .unwrap_or(0.0);
+    Some(DetectedLanguage {
+        code: code.to_string(),
+        probability,
+    })
+}
+
+/// GBNF grammar accepting sequences of the passage's words. None when it
+/// has no words, as a rule with no alternatives is one whisper-cli rejects.
+fn passage_grammar(passage: &str) -> Option<String> {
+    let mut words: Vec<String> = passage
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'').to_string())
+        .filter(|w| !w.is_empty())
+        .flat_map(|w| [w.to_lowercase(), capitalize(&w), w])
+        .collect();
+    words.sort();
+    words.dedup();
+    if words.is_empty() {
+        return None;
     }
+
+    let alternatives = words
+        .iter()
+        .map(|w| format!("\"{}\"", w.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(" | ");
+
+    Some(format!(
+        "root ::= (\" \" word punct?)+\nword ::= {}\npunct ::= \".\" | \",\" | \"!\" | \"?\" | \";\" | \":\"\n",
+        alternatives
+    ))
 }
 
-fn find_whisper_cli() -> Result<PathBuf, WhisperError> {
-    // Common locations for whisper CLI (Homebrew installs as whisper-cli)
-    let candidates = [
-        "/usr/local/bin/whisper-cli",
-        "/opt/homebrew/bin/whisper-cli",
-        "/usr/local/bin/whisper-cpp",
-        "/opt/homebrew/bin/whisper-cpp",
-    ];
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Segments from whisper-cli's JSON, timed word by word when `words` is
+/// set, and the language it decoded as
+fn parse_json_segments(json: &str, words: bool) -> Result<(Vec<TranscriptSegment>, Option<String>), WhisperError> {
+    let parsed: CliJson = serde_json::from_str(json)
+        .map_err(|e| WhisperError::TranscriptionError(format!("Invalid whisper output: {}", e)))?;
+
+    let segments = parsed
+        .transcription
+        .into_iter()
+        .map(|s| TranscriptSegment {
+            start: s.offsets.from as f64 / 1000.0,
+            end: s.offsets.to as f64 / 1000.0,
+            text: s.text.trim().to_string(),
+            confidence: token_confidence(&s.tokens),
+            avg_logprob: token_logprob(&s.tokens),
+            // whisper-cli doesn't write it out
+            no_speech_prob: None,
+            speaker: None,
+            words: if words { token_words(&s.tokens) } else { Vec::new() },
+        })
+        .filter(|s| !s.text.is_empty())
+        .collect();
+    Ok((segments, parsed.result.map(|r| r.language)))
+}
 
-    for path in &candidates {
-        let p = PathBuf::from(path);
-        if p.exists() {
-            return Ok(p);
+/// Words from a segment's tokens. A token led by a space starts a word and
+/// the rest carry it on; a word is as confident as its tokens on average.
+/// DTW times, when there are any, place the starts more closely than the
+/// token offsets.
+fn token_words(tokens: &[CliToken]) -> Vec<TranscriptWord> {
+    let mut words: Vec<(TranscriptWord, usize)> = Vec::new();
+    for token in tokens.iter().filter(|t| !t.text.starts_with("[_")) {
+        let Some(offsets) = &token.offsets else { continue };
+        let end = offsets.to as f64 / 1000.0;
+        match words.last_mut() {
+            Some((word, tokens)) if !token.text.starts_with(' ') => {
+                word.text.push_str(&token.text);
+                word.end = end.max(word.start);
+                word.confidence = word.confidence.map(|p| p + token.p);
+                *tokens += 1;
+            }
+            _ => {
+                let start = match token.t_dtw {
+                    Some(t) if t >= 0 => t as f64 / 100.0,
+                    _ => offsets.from as f64 / 1000.0,
+                };
+                let word = TranscriptWord {
+                    start,
+                    end: end.max(start),
+                    text: token.text.trim_start().to_string(),
+                    confidence: Some(token.p),
+                };
+                words.push((word, 1));
+            }
         }
     }
+    words
+        .into_iter()
+        .map(|(mut word, tokens)| {
+            word.text = word.text.trim_end().to_string();
+            word.confidence = word.confidence.map(|p| p / tokens as f64);
+            word
+        })
+        .filter(|w| !w.text.is_empty())
+        .collect()
+}
+
+/// Mean probability of the text tokens, skipping markers like `[_BEG_]`
+fn token_confidence(tokens: &[CliToken]) -> Option<f64> {
+    let probabilities: Vec<f64> = tokens
+        .iter()
+        .filter(|t| !t.text.starts_with("[_"))
+        .map(|t| t.p)
+        .collect();
+    if probabilities.is_empty() {
+        None
+    } else {
+        Some(probabilities.iter().sum::<f64>() / probabilities.len() as f64)
+    }
+}
 
-    // Try to find via which
-    for cmd in &["whisper-cli", "whisper-cpp"] {
-        if let Ok(output) = Command::new("which").arg(cmd).output() {
-            if output.status.success() {
-                let path = String::from_utf8_lossy(&output.stdout);
-                let p = PathBuf::from(path.trim());
-                if p.exists() {
-                    return Ok(p);
-                }
+/// Mean log probability of the text tokens, the way Whisper scores a
+/// decode
+fn token_logprob(tokens: &[CliToken]) -> Option<f64> {
+    let logprobs: Vec<f64> = tokens
+        .iter()
+        .filter(|t| !t.text.starts_with("[_"))
+        .map(|t| t.p.max(f64::MIN_POSITIVE).ln())
+        .collect();
+    (!logprobs.is_empty()).then(|| logprobs.iter().sum::<f64>() / logprobs.len() as f64)
+}
+
+/// Parse lines like `[00:00:01.000 --> 00:00:04.500]   text`
+fn parse_stdout_segments(stdout: &str) -> Vec<TranscriptSegment> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let rest = line.strip_prefix('[')?;
+            let (times, text) = rest.split_once(']')?;
+            let (from, to) = times.split_once("-->")?;
+            let text = text.trim();
+            if text.is_empty() {
+                return None;
             }
+            Some(TranscriptSegment {
+                start: parse_timestamp(from.trim())?,
+                end: parse_timestamp(to.trim())?,
+                text: text.to_string(),
+                confidence: None,
+                avg_logprob: None,
+                no_speech_prob: None,
+                speaker: None,
+                words: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// `HH:MM:SS.mmm` (or with a comma) to seconds
+fn parse_timestamp(ts: &str) -> Option<f64> {
+    let mut parts = ts.split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.replace(',', ".").parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+pub fn join_segments(segments: &[TranscriptSegment]) -> String {
+    segments
+        .iter()
+        .map(|s| s.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Where the engines were set to be found, from the `whisper_cli_path` and
+/// `whisperx_path` settings; see `set_program_paths`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramPaths {
+    pub whisper_cli: Option<PathBuf>,
+    /// The whisperx program, or a Python environment it's installed in
+    pub whisperx: Option<PathBuf>,
+}
+
+/// Looked in before anywhere else. Process-wide, since engines are found
+/// wherever a backend is loaded.
+static PROGRAM_PATHS: RwLock<ProgramPaths> = RwLock::new(ProgramPaths {
+    whisper_cli: None,
+    whisperx: None,
+});
+
+/// Threads each engine transcribes with; 0 leaves it to the engine.
+/// Process-wide, like the program paths.
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_threads(threads: usize) {
+    THREADS.store(threads, Ordering::Relaxed);
+}
+
+fn threads() -> Option<usize> {
+    Some(THREADS.load(Ordering::Relaxed)).filter(|&n| n > 0)
+}
+
+pub fn set_program_paths(paths: ProgramPaths) {
+    *PROGRAM_PATHS.write().unwrap_or_else(|e| e.into_inner()) = paths;
+}
+
+fn program_paths() -> ProgramPaths {
+    PROGRAM_PATHS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Where an engine was found, if it was, and everywhere that was looked
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendDiscovery {
+    pub backend: BackendKind,
+    pub path: Option<PathBuf>,
+    /// "setting", "python environment", "known location" or "PATH"
+    pub found_by: Option<&'static str>,
+    /// Folders and files checked, in order, up to the one it was found in
+    pub searched: Vec<PathBuf>,
+}
+
+/// Look for each engine the way loading it would
+pub fn detect_backends() -> Vec<BackendDiscovery> {
+    vec![locate_whisper_cli(), locate_whisperx()]
+}
+
+/// Whether the WhisperX Python tool is installed where it can be run
+pub fn whisperx_installed() -> bool {
+    find_whisperx().is_some()
+}
+
+fn find_whisperx() -> Option<PathBuf> {
+    locate_whisperx().path
+}
+
+fn find_whisper_cli() -> Result<PathBuf, WhisperError> {
+    locate_whisper_cli().path.ok_or(WhisperError::CliNotFound)
+}
+
+fn locate_whisper_cli() -> BackendDiscovery {
+    // Homebrew installs it as whisper-cli, older builds as whisper-cpp
+    let mut search = Search::new(BackendKind::WhisperCpp, &["whisper-cli", "whisper-cpp"]);
+    if let Some(path) = program_paths().whisper_cli {
+        search.file(&path, "setting");
+    }
+    for dir in known_dirs() {
+        search.dir(&dir, "known location");
+    }
+    search.path_env();
+    search.result
+}
+
+fn locate_whisperx() -> BackendDiscovery {
+    let mut search = Search::new(BackendKind::WhisperX, &["whisperx"]);
+    match program_paths().whisperx {
+        Some(path) if path.is_dir() => search.dir(&path.join(ENV_SCRIPTS), "setting"),
+        Some(path) => search.file(&path, "setting"),
+        None => {}
+    }
+    // An activated virtualenv or conda environment
+    for var in ["VIRTUAL_ENV", "CONDA_PREFIX"] {
+        if let Some(env) = std::env::var_os(var).filter(|v| !v.is_empty()) {
+            search.dir(&PathBuf::from(env).join(ENV_SCRIPTS), "python environment");
+        }
+    }
+    for dir in python_script_dirs() {
+        search.dir(&dir, "python environment");
+    }
+    for dir in known_dirs() {
+        search.dir(&dir, "known location");
+    }
+    search.path_env();
+    search.result
+}
+
+/// Folder a Python environment keeps its installed programs in
+#[cfg(windows)]
+const ENV_SCRIPTS: &str = "Scripts";
+#[cfg(not(windows))]
+const ENV_SCRIPTS: &str = "bin";
+
+/// Looks for the first of some programs, noting each place it tries
+struct Search {
+    programs: Vec<String>,
+    result: BackendDiscovery,
+}
+
+impl Search {
+    fn new(backend: BackendKind, programs: &[&str]) -> Self {
+        Self {
+            programs: programs.iter().map(|p| executable_name(p)).collect(),
+            result: BackendDiscovery {
+                backend,
+                path: None,
+                found_by: None,
+                searched: Vec::new(),
+            },
+        }
+    }
+
+    fn file(&mut self, path: &Path, found_by: &'static str) {
+        if self.result.path.is_some() {
+            return;
+        }
+        self.result.searched.push(path.to_path_buf());
+        if path.is_file() {
+            self.result.path = Some(path.to_path_buf());
+            self.result.found_by = Some(found_by);
+        }
+    }
+
+    fn dir(&mut self, dir: &Path, found_by: &'static str) {
+        if self.result.path.is_some() || self.result.searched.iter().any(|d| d == dir) {
+            return;
+        }
+        self.result.searched.push(dir.to_path_buf());
+        if let Some(path) = self.programs.iter().map(|p| dir.join(p)).find(|p| p.is_file()) {
+            self.result.path = Some(path);
+            self.result.found_by = Some(found_by);
+        }
+    }
+
+    fn path_env(&mut self) {
+        let Some(path) = std::env::var_os("PATH") else {
+            return;
+        };
+        for dir in std::env::split_paths(&path) {
+            self.dir(&dir, "PATH");
+        }
+    }
+}
+
+/// Whether the whisper-cli found would run on an NVIDIA card. A driver
+/// being installed isn't enough; the program has to be built with ggml's
+/// CUDA backend, either beside it as a loadable library or linked in.
+pub fn whisper_cli_uses_cuda() -> bool {
+    let Ok(program) = find_whisper_cli() else {
+        return false;
+    };
+    let dir = program.parent().unwrap_or(Path::new("."));
+    let beside = [dir.to_path_buf(), dir.join("../lib")].into_iter().any(|dir| {
+        std::fs::read_dir(dir).is_ok_and(|entries| {
+            entries.flatten().any(|entry| {
+                let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+                name.starts_with("ggml-cuda") || name.starts_with("libggml-cuda")
+            })
+        })
+    });
+    if beside {
+        return true;
+    }
+    // A CUDA backend linked in registers itself before the usage is printed
+    let reports_cuda = Command::new(&program)
+        .arg("--help")
+        .stdin(Stdio::null())
+        .output()
+        .is_ok_and(|output| {
+            let text = [output.stdout, output.stderr].concat();
+            String::from_utf8_lossy(&text).contains("ggml_cuda_init")
+        });
+    reports_cuda || std::fs::read(&program).is_ok_and(|binary| contains(&binary, b"ggml_cuda_init"))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Where a helper program such as ffmpeg is installed, looking in the same
+/// known locations as for the backends and then on PATH
+pub fn locate_program(program: &str) -> Option<PathBuf> {
+    let name = executable_name(program);
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    known_dirs()
+        .into_iter()
+        .chain(std::env::split_paths(&path))
+        .map(|dir| dir.join(&name))
+        .find(|p| p.is_file())
+}
+
+/// `program` as its file is named on this platform
+fn executable_name(program: &str) -> String {
+    if cfg!(windows) {
+        format!("{}.exe", program)
+    } else {
+        program.to_string()
+    }
+}
+
+/// Where package managers put programs, which an app started from the
+/// desktop may not have on its PATH
+fn known_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(target_os = "macos") {
+        dirs.push(PathBuf::from("/opt/homebrew/bin"));
+    }
+    if cfg!(unix) {
+        dirs.push(PathBuf::from("/usr/local/bin"));
+        // Where `pip install --user` and pipx put programs
+        if let Some(home) = dirs::home_dir() {
+            dirs.push(home.join(".local").join("bin"));
         }
     }
+    dirs
+}
 
-    Err(WhisperError::CliNotFound)
+/// Scripts folders of Python installs made by the python.org installer on
+/// Windows, for everyone's and for `pip install --user` programs
+fn python_script_dirs() -> Vec<PathBuf> {
+    if !cfg!(windows) {
+        return Vec::new();
+    }
+    let roots = [
+        dirs::data_local_dir().map(|d| d.join("Programs").join("Python")),
+        dirs::data_dir().map(|d| d.join("Python")),
+    ];
+    let mut found: Vec<PathBuf> = roots
+        .into_iter()
+        .flatten()
+        .filter_map(|root| std::fs::read_dir(root).ok())
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path().join("Scripts"))
+        .filter(|dir| dir.is_dir())
+        .collect();
+    // Newest Python first
+    found.sort();
+    found.reverse();
+    found
 }
 
-pub fn check_whisper_installed() -> bool {
-    find_whisper_cli().is_ok()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed from `whisper-cli -ojf` output, model and params left out
+    const CLI_JSON: &str = r#"{
+        "systeminfo": "AVX = 1 | AVX2 = 1",
+        "result": {"language": "en"},
+        "transcription": [
+            {
+                "timestamps": {"from": "00:00:00,000", "to": "00:00:02,500"},
+                "offsets": {"from": 0, "to": 2500},
+                "text": " The cat sat.",
+                "tokens": [
+                    {"text": "[_BEG_]", "offsets": {"from": 0, "to": 0}, "id": 50365, "p": 0.98, "t_dtw": -1},
+                    {"text": " The", "offsets": {"from": 0, "to": 400}, "id": 440, "p": 0.9, "t_dtw": -1},
+                    {"text": " c", "offsets": {"from": 400, "to": 700}, "id": 269, "p": 0.8, "t_dtw": -1},
+                    {"text": "at", "offsets": {"from": 700, "to": 1000}, "id": 267, "p": 0.6, "t_dtw": -1},
+                    {"text": " sat", "offsets": {"from": 1000, "to": 1800}, "id": 3227, "p": 0.9, "t_dtw": -1},
+                    {"text": ".", "offsets": {"from": 1800, "to": 2000}, "id": 13, "p": 0.7, "t_dtw": -1},
+                    {"text": "[_TT_125]", "offsets": {"from": 2500, "to": 2500}, "id": 50490, "p": 0.5, "t_dtw": -1}
+                ]
+            },
+            {
+                "timestamps": {"from": "00:00:02,500", "to": "00:00:04,000"},
+                "offsets": {"from": 2500, "to": 4000},
+                "text": " Run!",
+                "tokens": [
+                    {"text": " Run", "offsets": {"from": 2500, "to": 3500}, "id": 7506, "p": 0.5, "t_dtw": 262},
+                    {"text": "!", "offsets": {"from": 3500, "to": 3600}, "id": 0, "p": 0.9, "t_dtw": 351}
+                ]
+            },
+            {
+                "timestamps": {"from": "00:00:04,000", "to": "00:00:05,000"},
+                "offsets": {"from": 4000, "to": 5000},
+                "text": " ",
+                "tokens": []
+            }
+        ]
+    }"#;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn reads_segments_from_cli_json() {
+        let (segments, language) = parse_json_segments(CLI_JSON, false).unwrap();
+        assert_eq!(language.as_deref(), Some("en"));
+        // The blank segment is dropped
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].text, "The cat sat.");
+        assert!(close(segments[0].start, 0.0) && close(segments[0].end, 2.5));
+        assert!(close(segments[1].start, 2.5) && close(segments[1].end, 4.0));
+        assert!(segments.iter().all(|s| s.words.is_empty()));
+    }
+
+    #[test]
+    fn merges_sub_word_tokens_into_words() {
+        let (segments, _) = parse_json_segments(CLI_JSON, true).unwrap();
+
+        let words: Vec<&str> = segments[0].words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(words, ["The", "cat", "sat."]);
+        let cat = &segments[0].words[1];
+        assert!(close(cat.start, 0.4) && close(cat.end, 1.0));
+        assert!(close(cat.confidence.unwrap(), 0.7));
+
+        // DTW times place the start when they're there
+        let run = &segments[1].words[0];
+        assert_eq!(run.text, "Run!");
+        assert!(close(run.start, 2.62) && close(run.end, 3.6));
+    }
+
+    #[test]
+    fn rejects_output_that_is_not_cli_json() {
+        assert!(parse_json_segments("[00:00:00.000 --> 00:00:02.000]  Hello", true).is_err());
+    }
+
+    #[test]
+    fn reads_progress_lines() {
+        assert_eq!(parse_progress("whisper_print_progress_callback: progress =  42%"), Some(42.0));
+        assert_eq!(parse_progress("Progress: 87.50%..."), Some(87.5));
+    }
+
+    #[test]
+    fn ignores_lines_without_progress() {
+        assert_eq!(parse_progress("whisper_init_from_file_with_params_no_state: loading model"), None);
+        assert_eq!(parse_progress("[00:00:00.000 --> 00:00:02.000]   Progress on the project"), None);
+        assert_eq!(parse_progress("progress = %"), None);
+    }
+
+    #[test]
+    fn passage_grammar_lists_each_word_once_per_casing() {
+        let grammar = passage_grammar("The cat, the \"hat\".").unwrap();
+        let word_rule = grammar.lines().find(|l| l.starts_with("word ::=")).unwrap();
+        assert_eq!(word_rule, r#"word ::= "Cat" | "Hat" | "The" | "cat" | "hat" | "the""#);
+        assert!(grammar.starts_with("root ::= "));
+    }
+
+    #[test]
+    fn passage_grammar_escapes_quotes_and_backslashes() {
+        let grammar = passage_grammar(r#"say"so back\slash"#).unwrap();
+        assert!(grammar.contains(r#""say\"so""#));
+        assert!(grammar.contains(r#""back\\slash""#));
+    }
+
+    #[test]
+    fn no_grammar_for_a_passage_without_words() {
+        assert_eq!(passage_grammar(""), None);
+        assert_eq!(passage_grammar("  -- ... !! "), None);
+    }
+
+    #[test]
+    fn reads_the_auto_detected_language() {
+        let logs = "whisper_full_with_state: processing 480000 samples\n\
+                    whisper_full_with_state: auto-detected language: es (p = 0.981234)\n\
+                    whisper_print_timings: total time = 812.04 ms\n";
+        let detected = parse_detected_language(logs).unwrap();
+        assert_eq!(detected.code, "es");
+        assert!((detected.probability - 0.981234).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_language_when_none_was_detected() {
+        let logs = "whisper_init_from_file_with_params_no_state: loading model from 'ggml-base.en.bin'\n\
+                    whisper_full_with_state: processing 480000 samples\n";
+        assert!(parse_detected_language(logs).is_none());
+    }
 }
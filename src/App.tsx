@@ -11,6 +11,43 @@ interface Recording {
   duration_seconds: number;
   recorded_at: string;
   synced: boolean;
+  guest: boolean;
+  transcript_language: string | null;
+  detected_language: string | null;
+  speaker_labels: Record<string, string>;
+  assignment_id: string | null;
+  teacher_only: boolean;
+  student_speaker: string | null;
+  translate: boolean;
+  translation: string | null;
+  unmasked_transcript: string | null;
+  unmasked_translation: string | null;
+}
+
+interface Assignment {
+  id: string;
+  title: string;
+  passage: string;
+  expected_duration_seconds: number | null;
+  status: string;
+}
+
+interface TranscriptSegment {
+  start: number;
+  end: number;
+  text: string;
+  confidence: number | null;
+  avg_logprob: number | null;
+  no_speech_prob: number | null;
+  speaker: string | null;
+  words?: TranscriptWord[];
+}
+
+interface TranscriptWord {
+  start: number;
+  end: number;
+  text: string;
+  confidence: number | null;
 }
 
 interface Settings {
@@ -20,6 +57,46 @@ interface Settings {
   server_url: string;
   model_loaded: boolean;
   setup_complete: boolean;
+  local_only: boolean;
+  guest_mode: boolean;
+  audio_device: string | null;
+  microphone_available: boolean;
+  transcription_language: string;
+  live_captions: boolean;
+  word_timestamps: boolean;
+  sync_speaker_stats: boolean;
+  student_speaker_strategy: "most_talkative" | "least_talkative" | "voiceprint" | "manual" | null;
+  transcribe_pending_on_start: boolean;
+}
+
+interface BatchProgress {
+  total: number;
+  transcribed: number;
+  failed: number;
+}
+
+// Languages offered in settings; any other whisper code can be pushed
+const TRANSCRIPTION_LANGUAGES: [string, string][] = [
+  ["auto", "Detect automatically"],
+  ["en", "English"],
+  ["es", "Spanish"],
+  ["fr", "French"],
+  ["pt", "Portuguese"],
+  ["zh", "Chinese"],
+  ["vi", "Vietnamese"],
+  ["ar", "Arabic"],
+  ["ko", "Korean"],
+  ["tl", "Tagalog"],
+];
+
+interface InputLevel {
+  rms_dbfs: number;
+  peak_dbfs: number;
+}
+
+interface InputDevice {
+  name: string;
+  is_default: boolean;
 }
 
 interface ProcessingStatus {
@@ -30,6 +107,42 @@ interface ProcessingStatus {
   synced: boolean;
 }
 
+interface AvailableModel {
+  name: string;
+  file_name: string;
+  size_mb: number;
+  multilingual: boolean;
+  downloaded: boolean;
+  selected: boolean;
+}
+
+interface DiarizationStatus {
+  supported: boolean;
+  token_source: "settings" | "environment" | null;
+  token_error: string | null;
+  active: boolean;
+  speakers: { min: number | null; max: number | null };
+}
+
+interface StartupCheck {
+  name: string;
+  status: "ok" | "failed" | "skipped";
+  detail: string | null;
+  disables: string[];
+}
+
+interface StartupReport {
+  checks: StartupCheck[];
+  disabled: string[];
+}
+
+interface ModelDownloadProgress {
+  model: string;
+  bytes_downloaded: number;
+  total_bytes: number | null;
+  percent: number | null;
+}
+
 interface Student {
   id: string;
   name: string;
@@ -43,6 +156,11 @@ interface Teacher {
 
 type Tab = "record" | "history" | "settings";
 
+// Speaker keys in the order they're first heard
+function speakersHeard(segments: TranscriptSegment[] | undefined): string[] {
+  return [...new Set((segments ?? []).flatMap((s) => (s.speaker ? [s.speaker] : [])))];
+}
+
 function App() {
   const [isLoading, setIsLoading] = useState(true);
   const [showSetup, setShowSetup] = useState(false);
@@ -50,7 +168,12 @@ function App() {
   const [isRecording, setIsRecording] = useState(false);
   const [isProcessing, setIsProcessing] = useState(false);
   const [processingStatus, setProcessingStatus] = useState<ProcessingStatus | null>(null);
+  const [partialTranscript, setPartialTranscript] = useState("");
+  const [liveCaption, setLiveCaption] = useState("");
   const [recordings, setRecordings] = useState<Recording[]>([]);
+  const [timedSegments, setTimedSegments] = useState<Record<string, TranscriptSegment[]>>({});
+  const [assignments, setAssignments] = useState<Assignment[]>([]);
+  const [activeAssignment, setActiveAssignment] = useState<Assignment | null>(null);
   const [settings, setSettings] = useState<Settings>({
     student_id: "",
     student_name: "",
@@ -58,6 +181,16 @@ function App() {
     server_url: "http://localhost:3000",
     model_loaded: false,
     setup_complete: false,
+    local_only: false,
+    guest_mode: false,
+    audio_device: null,
+    microphone_available: true,
+    transcription_language: "en",
+    live_captions: false,
+    word_timestamps: false,
+    sync_speaker_stats: false,
+    student_speaker_strategy: null,
+    transcribe_pending_on_start: false,
   });
 
   // Setup form state
@@ -65,6 +198,8 @@ function App() {
   const [setupTeacherName, setSetupTeacherName] = useState("");
   const [setupServerUrl, setSetupServerUrl] = useState("http://localhost:3000");
   const [setupError, setSetupError] = useState("");
+  const [micTest, setMicTest] = useState<string | null>(null);
+  const [micTesting, setMicTesting] = useState(false);
   const [studentsList, setStudentsList] = useState<Student[]>([]);
   const [teachersList, setTeachersList] = useState<Teacher[]>([]);
   const [loadingLists, setLoadingLists] = useState(false);
@@ -75,12 +210,23 @@ function App() {
   const [teacherName, setTeacherName] = useState("");
   const [serverUrl, setServerUrl] = useState("http://localhost:3000");
   const [modelPath, setModelPath] = useState("");
+  const [models, setModels] = useState<AvailableModel[]>([]);
+  const [diarization, setDiarization] = useState<DiarizationStatus | null>(null);
+  const [startupReport, setStartupReport] = useState<StartupReport | null>(null);
+  const [hfTokenInput, setHfTokenInput] = useState("");
+  const [downloading, setDownloading] = useState<ModelDownloadProgress | null>(null);
+  const [batch, setBatch] = useState<BatchProgress | null>(null);
+  const [audioDevices, setAudioDevices] = useState<InputDevice[]>([]);
+  const [inputLevel, setInputLevel] = useState<InputLevel | null>(null);
   const [unsyncedCount, setUnsyncedCount] = useState(0);
+  const [failedSyncCount, setFailedSyncCount] = useState(0);
   const [serverConnected, setServerConnected] = useState(false);
   const [recordingDuration, setRecordingDuration] = useState(0);
   const [error, setError] = useState<string | null>(null);
   const [success, setSuccess] = useState<string | null>(null);
   const [lastTranscript, setLastTranscript] = useState<string | null>(null);
+  const [pushToTalkSession, setPushToTalkSession] = useState<string | null>(null);
+  const [utteranceCount, setUtteranceCount] = useState(0);
 
   const loadSettings = useCallback(async () => {
     try {
@@ -117,6 +263,8 @@ function App() {
     try {
       const count = await invoke<number>("get_unsynced_count");
       setUnsyncedCount(count);
+      const backlog = await invoke<{ failed_permanently: number }>("get_backlog_summary");
+      setFailedSyncCount(backlog.failed_permanently);
     } catch (e) {
       console.error("Failed to get unsynced count:", e);
     }
@@ -131,6 +279,25 @@ function App() {
     }
   }, []);
 
+  const loadAudioDevices = useCallback(async () => {
+    try {
+      const devices = await invoke<InputDevice[]>("list_audio_devices");
+      setAudioDevices(devices);
+    } catch (e) {
+      console.error("Failed to list audio devices:", e);
+    }
+  }, []);
+
+  const loadAssignments = useCallback(async () => {
+    try {
+      setActiveAssignment(await invoke<Assignment | null>("get_active_assignment"));
+      setAssignments(await invoke<Assignment[]>("get_assignments"));
+    } catch (e) {
+      // Offline or local-only; an assignment in progress still shows
+      console.error("Failed to load assignments:", e);
+    }
+  }, []);
+
   const getModelPath = useCallback(async () => {
     try {
       const path = await invoke<string>("get_model_path");
@@ -140,6 +307,30 @@ function App() {
     }
   }, []);
 
+  const loadModels = useCallback(async () => {
+    try {
+      setModels(await invoke<AvailableModel[]>("list_available_models"));
+    } catch (e) {
+      console.error("Failed to list models:", e);
+    }
+  }, []);
+
+  const loadDiarizationStatus = useCallback(async () => {
+    try {
+      setDiarization(await invoke<DiarizationStatus>("get_diarization_status"));
+    } catch (e) {
+      console.error("Failed to get diarization status:", e);
+    }
+  }, []);
+
+  const loadStartupReport = useCallback(async () => {
+    try {
+      setStartupReport(await invoke<StartupReport>("get_startup_report"));
+    } catch (e) {
+      console.error("Failed to get startup report:", e);
+    }
+  }, []);
+
   const fetchStudentsAndTeachers = useCallback(async (serverUrlToUse: string) => {
     setLoadingLists(true);
     try {
@@ -168,7 +359,13 @@ function App() {
   useEffect(() => {
     const unlisten = listen<ProcessingStatus>("processing-status", (event) => {
       setProcessingStatus(event.payload);
-      if (event.payload.stage === "done") {
+      if (event.payload.stage !== "transcribing") {
+        setPartialTranscript("");
+      }
+      if (event.payload.stage === "queued") {
+        // Transcription goes on in the background; recording can carry on
+        setIsProcessing(false);
+      } else if (event.payload.stage === "done") {
         if (event.payload.transcript) {
           setLastTranscript(event.payload.transcript);
         }
@@ -186,9 +383,49 @@ function App() {
         }, 3000);
       }
     });
+    const unlistenConversion = listen<{ recording_id: string; progress: number }>("conversion-progress", (event) => {
+      const percent = Math.round(event.payload.progress * 100);
+      setProcessingStatus((s) => (s?.stage === "saving" ? { ...s, message: `Saving audio... ${percent}%` } : s));
+    });
+    const unlistenTranscription = listen<{ recording_id: string; percent: number; partial_text: string }>(
+      "transcription-progress",
+      (event) => {
+        const percent = Math.round(event.payload.percent);
+        setProcessingStatus((s) =>
+          s?.stage === "transcribing" ? { ...s, message: `Transcribing audio... ${percent}%` } : s
+        );
+        setPartialTranscript(event.payload.partial_text);
+      }
+    );
+    const unlistenLive = listen<{ session: number; text: string; is_final: boolean }>("live-transcript", (event) => {
+      setLiveCaption(event.payload.text);
+    });
+
+    const unlistenJobs = listen<{ recording_id: string; status: string; error: string | null }>(
+      "job-finished",
+      (event) => {
+        if (event.payload.status === "failed") {
+          showError(`Transcription failed: ${event.payload.error}`);
+        }
+        loadRecordings();
+        loadUnsyncedCount();
+      }
+    );
+
+    // A batch queued on start may be under way before this listens
+    invoke<BatchProgress | null>("get_batch_progress").then(setBatch).catch(() => {});
+    const unlistenBatch = listen<BatchProgress>("batch-progress", (event) => {
+      const { total, transcribed, failed } = event.payload;
+      setBatch(transcribed + failed < total ? event.payload : null);
+    });
 
     return () => {
       unlisten.then((fn) => fn());
+      unlistenBatch.then((fn) => fn());
+      unlistenConversion.then((fn) => fn());
+      unlistenTranscription.then((fn) => fn());
+      unlistenLive.then((fn) => fn());
+      unlistenJobs.then((fn) => fn());
     };
   }, [loadRecordings, loadUnsyncedCount]);
 
@@ -198,14 +435,57 @@ function App() {
     loadUnsyncedCount();
     checkServerConnection();
     getModelPath();
-  }, [loadSettings, loadRecordings, loadUnsyncedCount, checkServerConnection, getModelPath]);
+    loadModels();
+    loadDiarizationStatus();
+    loadStartupReport();
+    loadAudioDevices();
+    loadAssignments();
+  }, [
+    loadSettings,
+    loadRecordings,
+    loadUnsyncedCount,
+    checkServerConnection,
+    getModelPath,
+    loadModels,
+    loadDiarizationStatus,
+    loadStartupReport,
+    loadAudioDevices,
+    loadAssignments,
+  ]);
+
+  useEffect(() => {
+    const unlisten = listen<{ available: boolean }>("microphone-status", (event) => {
+      setSettings((s) => ({ ...s, microphone_available: event.payload.available }));
+    });
+    const unlistenDevices = listen<InputDevice[]>("audio-devices-changed", (event) => {
+      setAudioDevices(event.payload);
+    });
+    const unlistenLevel = listen<InputLevel>("audio-level", (event) => {
+      setInputLevel(event.payload);
+    });
+    const unlistenAutoStop = listen<{ silence_seconds: number }>("recording-auto-stopped", () => {
+      setIsRecording(false);
+      setIsProcessing(true);
+    });
+    const unlistenDownload = listen<ModelDownloadProgress>("model-download-progress", (event) => {
+      setDownloading(event.payload);
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+      unlistenDevices.then((fn) => fn());
+      unlistenLevel.then((fn) => fn());
+      unlistenAutoStop.then((fn) => fn());
+      unlistenDownload.then((fn) => fn());
+    };
+  }, []);
 
   // Fetch students/teachers when setup wizard is shown
   useEffect(() => {
-    if (showSetup) {
+    if (showSetup && !settings.local_only) {
       fetchStudentsAndTeachers(setupServerUrl);
     }
-  }, [showSetup, setupServerUrl, fetchStudentsAndTeachers]);
+  }, [showSetup, setupServerUrl, settings.local_only, fetchStudentsAndTeachers]);
 
   useEffect(() => {
     let interval: number | null = null;
@@ -221,6 +501,30 @@ function App() {
     };
   }, [isRecording]);
 
+  // Hold the space bar to talk. Foot pedals and presenter clickers send
+  // a key too, so they work the same way.
+  useEffect(() => {
+    if (!pushToTalkSession) return;
+    const isTalkKey = (e: KeyboardEvent) =>
+      e.code === "Space" && !(e.target instanceof HTMLInputElement || e.target instanceof HTMLTextAreaElement);
+    const onKeyDown = (e: KeyboardEvent) => {
+      if (!isTalkKey(e)) return;
+      e.preventDefault();
+      if (!e.repeat) handlePushToTalkPress();
+    };
+    const onKeyUp = (e: KeyboardEvent) => {
+      if (!isTalkKey(e)) return;
+      e.preventDefault();
+      handlePushToTalkRelease();
+    };
+    window.addEventListener("keydown", onKeyDown);
+    window.addEventListener("keyup", onKeyUp);
+    return () => {
+      window.removeEventListener("keydown", onKeyDown);
+      window.removeEventListener("keyup", onKeyUp);
+    };
+  }, [pushToTalkSession, handlePushToTalkPress, handlePushToTalkRelease]);
+
   // Check server connection periodically
   useEffect(() => {
     const interval = setInterval(checkServerConnection, 30000);
@@ -237,6 +541,31 @@ function App() {
     setTimeout(() => setSuccess(null), 3000);
   };
 
+  const handleMicTest = async () => {
+    setMicTesting(true);
+    setMicTest("Say a sentence or two...");
+    try {
+      const result = await invoke<{
+        sample_rate: number;
+        rms_dbfs: number;
+        quality: "ok" | "clipped" | "too_quiet";
+        transcript: string | null;
+      }>("run_mic_test", { seconds: 5, transcribe: settings.model_loaded });
+      const verdict =
+        result.quality === "clipped" ? "Too loud - move the microphone further away." :
+        result.quality === "too_quiet" ? "Too quiet - move closer or check the microphone." :
+        "Sounds good!";
+      setMicTest(
+        `${verdict} (${Math.round(result.rms_dbfs)} dBFS at ${result.sample_rate} Hz)` +
+        (result.transcript ? ` Heard: "${result.transcript.trim()}"` : "")
+      );
+    } catch (e) {
+      setMicTest(`Microphone test failed: ${e}`);
+    } finally {
+      setMicTesting(false);
+    }
+  };
+
   const handleCompleteSetup = async () => {
     if (!setupStudentName.trim()) {
       setSetupError("Please select your name");
@@ -291,12 +620,21 @@ function App() {
       await invoke("start_recording");
       setIsRecording(true);
       setLastTranscript(null);
+      setLiveCaption("");
       setError(null);
     } catch (e) {
       showError(`Failed to start recording: ${e}`);
     }
   };
 
+  const handleAddMarker = async () => {
+    try {
+      await invoke("add_marker", { label: "Good answer" });
+    } catch (e) {
+      showError(`Failed to add marker: ${e}`);
+    }
+  };
+
   const handleStopRecording = async () => {
     setIsRecording(false);
     setIsProcessing(true);
@@ -317,6 +655,81 @@ function App() {
     }
   };
 
+  const handleStartPushToTalk = async () => {
+    if (!settings.model_loaded) {
+      showError("Please load the Whisper model in Settings first");
+      setActiveTab("settings");
+      return;
+    }
+
+    try {
+      const sessionId = await invoke<string>("start_push_to_talk");
+      setPushToTalkSession(sessionId);
+      setUtteranceCount(0);
+      setLastTranscript(null);
+      setError(null);
+    } catch (e) {
+      showError(`Failed to start push-to-talk: ${e}`);
+    }
+  };
+
+  const handlePushToTalkPress = useCallback(async () => {
+    try {
+      if (await invoke<boolean>("push_to_talk_press")) {
+        setIsRecording(true);
+      }
+    } catch (e) {
+      showError(`Failed to start recording: ${e}`);
+    }
+  }, []);
+
+  const handlePushToTalkRelease = useCallback(async () => {
+    setIsRecording(false);
+    try {
+      const status = await invoke<ProcessingStatus | null>("push_to_talk_release");
+      if (status) {
+        setUtteranceCount((n) => n + 1);
+        if (status.transcript) {
+          setLastTranscript(status.transcript);
+        }
+      }
+    } catch (e) {
+      showError(`Failed to process recording: ${e}`);
+    }
+  }, []);
+
+  const handleEndPushToTalk = async () => {
+    try {
+      const result = await invoke<{ session_id: string; utterances: Recording[] }>("end_push_to_talk");
+      setPushToTalkSession(null);
+      setIsRecording(false);
+      loadRecordings();
+      loadUnsyncedCount();
+      showSuccess(`Saved ${result.utterances.length} recordings`);
+    } catch (e) {
+      showError(`Failed to end push-to-talk: ${e}`);
+    }
+  };
+
+  const handleStartAssignment = async (assignment: Assignment) => {
+    try {
+      setActiveAssignment(await invoke<Assignment>("start_assignment", { assignment }));
+    } catch (e) {
+      showError(`Failed to start assignment: ${e}`);
+    }
+  };
+
+  const handleFinishAssignment = async () => {
+    try {
+      const finished = await invoke<Assignment | null>("finish_assignment");
+      setActiveAssignment(null);
+      loadAssignments();
+      showSuccess(finished ? `Finished "${finished.title}"` : "Nothing was recorded, so the assignment was dropped");
+    } catch (e) {
+      showError(`Failed to finish assignment: ${e}`);
+    }
+  };
+
   const handleSaveSettings = async () => {
     try {
       await invoke("save_settings", {
@@ -332,6 +745,174 @@ function App() {
     }
   };
 
+  const handleSelectAudioDevice = async (name: string) => {
+    try {
+      await invoke("set_audio_device", { name: name || null });
+      loadSettings();
+      showSuccess("Microphone updated!");
+    } catch (e) {
+      showError(`Failed to select microphone: ${e}`);
+    }
+  };
+
+  const handleRetranscribeLanguage = async (recordingId: string) => {
+    try {
+      await invoke("retranscribe_detected_language", { recordingId });
+      loadRecordings();
+      showSuccess("Transcribed again in the detected language");
+    } catch (e) {
+      showError(`Failed to transcribe again: ${e}`);
+    }
+  };
+
+  const handleToggleGuestMode = async (enabled: boolean) => {
+    try {
+      await invoke("set_guest_mode", { enabled });
+      loadSettings();
+      showSuccess(enabled ? "Guest mode on" : "Guest mode off");
+    } catch (e) {
+      showError(`Failed to change guest mode: ${e}`);
+    }
+  };
+
+  const handleSelectStudentStrategy = async (strategy: string) => {
+    try {
+      await invoke("set_student_speaker_strategy", { strategy: strategy || null });
+      loadSettings();
+    } catch (e) {
+      showError(`Failed to change how the student is found: ${e}`);
+    }
+  };
+
+  const handlePickStudent = async (recordingId: string, speaker: string) => {
+    try {
+      await invoke("set_student_speaker", { recordingId, speaker: speaker || null });
+      loadRecordings();
+    } catch (e) {
+      showError(`Failed to pick the student: ${e}`);
+    }
+  };
+
+  const handleSelectLanguage = async (language: string) => {
+    try {
+      await invoke("set_transcription_language", { language });
+      loadSettings();
+    } catch (e) {
+      showError(`Failed to change language: ${e}`);
+    }
+  };
+
+  const handleToggleLiveCaptions = async (enabled: boolean) => {
+    try {
+      await invoke("set_live_captions", { windowSeconds: enabled ? 8 : null });
+      loadSettings();
+    } catch (e) {
+      showError(`Failed to change live captions: ${e}`);
+    }
+  };
+
+  const handleToggleWordTimestamps = async (enabled: boolean) => {
+    try {
+      await invoke("set_word_timestamps", { enabled });
+      loadSettings();
+    } catch (e) {
+      showError(`Failed to change word timings: ${e}`);
+    }
+  };
+
+  const handleToggleSyncSpeakerStats = async (enabled: boolean) => {
+    try {
+      await invoke("set_sync_speaker_stats", { enabled });
+      loadSettings();
+    } catch (e) {
+      showError(`Failed to change speaker statistics sharing: ${e}`);
+    }
+  };
+
+  const handleTranscribeAllPending = async () => {
+    try {
+      const queued = await invoke<BatchProgress>("transcribe_all_pending");
+      if (queued.total === 0) {
+        showSuccess("Every recording is already transcribed");
+      }
+    } catch (e) {
+      showError(`Failed to queue recordings: ${e}`);
+    }
+  };
+
+  const handleToggleTranscribePendingOnStart = async (enabled: boolean) => {
+    try {
+      await invoke("set_transcribe_pending_on_start", { enabled });
+      loadSettings();
+    } catch (e) {
+      showError(`Failed to change transcribing on start: ${e}`);
+    }
+  };
+
+  const handleRenameSpeaker = async (rec: Recording, speaker: string) => {
+    const name = window.prompt(`Name for ${speaker}`, rec.speaker_labels[speaker] ?? "");
+    if (name === null) return;
+    const remember = window.confirm("Use this name in this student's future recordings too?");
+    try {
+      await invoke("rename_speaker", { recordingId: rec.id, speaker, name: name.trim() || null, remember });
+      loadRecordings();
+    } catch (e) {
+      showError(`Failed to rename speaker: ${e}`);
+    }
+  };
+
+  const handleSaveHfToken = async (token: string | null) => {
+    try {
+      setDiarization(await invoke<DiarizationStatus>("set_hf_token", { token }));
+      setHfTokenInput("");
+    } catch (e) {
+      showError(`Failed to save the Hugging Face token: ${e}`);
+    }
+  };
+
+  const handleToggleTwoSpeakers = async (enabled: boolean) => {
+    try {
+      const bound = enabled ? 2 : null;
+      setDiarization(await invoke<DiarizationStatus>("set_speaker_count", { min: bound, max: bound }));
+    } catch (e) {
+      showError(`Failed to save the speaker count: ${e}`);
+    }
+  };
+
+  const handleDownloadModel = async (name: string) => {
+    setDownloading({ model: name, bytes_downloaded: 0, total_bytes: null, percent: null });
+    try {
+      await invoke("download_model", { modelName: name });
+      loadModels();
+      showSuccess(`Downloaded ${name}`);
+    } catch (e) {
+      showError(`Failed to download ${name}: ${e}`);
+    } finally {
+      setDownloading(null);
+    }
+  };
+
+  const handleSelectModel = async (name: string) => {
+    try {
+      await invoke("select_model", { modelName: name });
+      loadModels();
+      loadSettings();
+      getModelPath();
+      showSuccess(`Using ${name}`);
+    } catch (e) {
+      showError(`Failed to switch model: ${e}`);
+    }
+  };
+
+  const handleDeleteModel = async (name: string) => {
+    try {
+      await invoke("delete_model", { modelName: name });
+      loadModels();
+    } catch (e) {
+      showError(`Failed to delete ${name}: ${e}`);
+    }
+  };
+
   const handleLoadModel = async () => {
     try {
       await invoke("load_model");
@@ -342,6 +923,23 @@ function App() {
     }
   };
 
+  const handleToggleTimings = async (recordingId: string) => {
+    if (timedSegments[recordingId]) {
+      setTimedSegments((s) => {
+        const next = { ...s };
+        delete next[recordingId];
+        return next;
+      });
+      return;
+    }
+    try {
+      const segments = await invoke<TranscriptSegment[]>("get_segments", { recordingId });
+      setTimedSegments((s) => ({ ...s, [recordingId]: segments }));
+    } catch (e) {
+      showError(`Failed to load transcript timings: ${e}`);
+    }
+  };
+
   const handleDelete = async (recordingId: string) => {
     if (!confirm("Delete this recording?")) return;
     try {
@@ -366,6 +964,15 @@ function App() {
     }
   };
 
+  const handleRetryFailedSyncs = async () => {
+    try {
+      await invoke<number>("retry_failed_syncs");
+      await handleManualSync();
+    } catch (e) {
+      showError(`Retry failed: ${e}`);
+    }
+  };
+
   const formatDuration = (seconds: number) => {
     const mins = Math.floor(seconds / 60);
     const secs = Math.floor(seconds % 60);
@@ -379,10 +986,12 @@ function App() {
   const getStatusIcon = (stage: string) => {
     switch (stage) {
       case "saving": return "💾";
+      case "queued": return "⏳";
       case "transcribing": return "🎯";
       case "syncing": return "☁️";
       case "done": return "✓";
       case "error": return "✗";
+      case "cancelled": return "⏹";
       default: return "...";
     }
   };
@@ -415,7 +1024,7 @@ function App() {
             {setupError && <div className="setup-error">{setupError}</div>}
 
             {/* Only show server URL on first time setup or if no students loaded */}
-            {(isFirstTime || studentsList.length === 0) && (
+            {!settings.local_only && (isFirstTime || studentsList.length === 0) && (
               <div className="setup-field">
                 <label>Server URL</label>
                 <input
@@ -483,6 +1092,16 @@ function App() {
               )}
             </div>
 
+            {isFirstTime && (
+              <div className="setup-field">
+                <label>Check your microphone</label>
+                <button onClick={handleMicTest} disabled={micTesting}>
+                  {micTesting ? "Listening..." : "Test microphone"}
+                </button>
+                {micTest && <p>{micTest}</p>}
+              </div>
+            )}
+
             <button className="setup-button" onClick={handleCompleteSetup} disabled={loadingLists}>
               {isFirstTime ? "Get Started" : "Start Recording"}
             </button>
@@ -497,15 +1116,22 @@ function App() {
       {/* Header */}
       <header className="header">
         <h1>Classroom Transcriber</h1>
-        <div className="header-status">
-          <span className={`status-dot ${serverConnected ? "connected" : "disconnected"}`}></span>
-          <span>{serverConnected ? "Server Connected" : "Server Offline"}</span>
-          {unsyncedCount > 0 && (
-            <span className="badge" onClick={handleManualSync} style={{ cursor: "pointer" }}>
-              {unsyncedCount} unsynced
-            </span>
-          )}
-        </div>
+        {settings.guest_mode && <span className="badge">Guest mode</span>}
+        {settings.local_only ? (
+          <div className="header-status">
+            <span>Local-only mode</span>
+          </div>
+        ) : (
+          <div className="header-status">
+            <span className={`status-dot ${serverConnected ? "connected" : "disconnected"}`}></span>
+            <span>{serverConnected ? "Server Connected" : "Server Offline"}</span>
+            {unsyncedCount > 0 && (
+              <span className="badge" onClick={handleManualSync} style={{ cursor: "pointer" }}>
+                {unsyncedCount} unsynced
+              </span>
+            )}
+          </div>
+        )}
       </header>
 
       {/* Alerts */}
@@ -536,6 +1162,19 @@ function App() {
 
       {/* Content */}
       <main className="content">
+        {startupReport && startupReport.disabled.length > 0 && (
+          <div className="model-warning">
+            <strong>Some features are off: {startupReport.disabled.join(", ")}</strong>
+            <ul>
+              {startupReport.checks
+                .filter((c) => c.status === "failed")
+                .map((c) => (
+                  <li key={c.name}>{c.detail ?? c.name}</li>
+                ))}
+            </ul>
+          </div>
+        )}
+
         {/* Record Tab */}
         {activeTab === "record" && (
           <div className="record-tab">
@@ -544,13 +1183,58 @@ function App() {
               <div><strong>Teacher:</strong> {settings.teacher_name}</div>
             </div>
 
+            {activeAssignment ? (
+              <div className="assignment">
+                <div><strong>Assignment:</strong> {activeAssignment.title}</div>
+                {activeAssignment.expected_duration_seconds !== null && (
+                  <p className="hint">
+                    About {Math.round(activeAssignment.expected_duration_seconds / 60)} min expected
+                  </p>
+                )}
+                <p className="assignment-passage">{activeAssignment.passage}</p>
+                <button className="small-btn" onClick={handleFinishAssignment} disabled={isRecording}>
+                  Finish assignment
+                </button>
+              </div>
+            ) : assignments.length > 0 && (
+              <ul className="assignment-list">
+                {assignments.map((a) => (
+                  <li key={a.id}>
+                    <span>{a.title}</span>
+                    <button className="small-btn" onClick={() => handleStartAssignment(a)} disabled={isRecording}>
+                      Start
+                    </button>
+                  </li>
+                ))}
+              </ul>
+            )}
+
             {/* Recording UI */}
-            {!isProcessing ? (
+            {pushToTalkSession ? (
+              <div className="recorder">
+                <div className={`record-button ${isRecording ? "recording" : ""}`}>
+                  <button
+                    onPointerDown={handlePushToTalkPress}
+                    onPointerUp={handlePushToTalkRelease}
+                    onPointerLeave={() => isRecording && handlePushToTalkRelease()}
+                    disabled={!settings.microphone_available}
+                  >
+                    {isRecording ? "Talking..." : "Hold to talk"}
+                  </button>
+                </div>
+                <p className="hint">
+                  Hold the button or the space bar while speaking. {utteranceCount} saved so far.
+                </p>
+                <button className="small-btn" onClick={handleEndPushToTalk}>
+                  End session
+                </button>
+              </div>
+            ) : !isProcessing ? (
               <div className="recorder">
                 <div className={`record-button ${isRecording ? "recording" : ""}`}>
                   <button
                     onClick={isRecording ? handleStopRecording : handleStartRecording}
-                    disabled={!settings.model_loaded}
+                    disabled={!settings.model_loaded || (!isRecording && !settings.microphone_available)}
                   >
                     {isRecording ? "Stop" : "Record"}
                   </button>
@@ -563,9 +1247,46 @@ function App() {
                   </div>
                 )}
 
+                {isRecording && (
+                  <button className="marker-button" onClick={handleAddMarker}>
+                    Mark this moment
+                  </button>
+                )}
+
+                {(isRecording || isProcessing) && liveCaption && (
+                  <p className="live-caption">{liveCaption}</p>
+                )}
+
+                {!isRecording && (
+                  <button
+                    className="small-btn"
+                    onClick={handleStartPushToTalk}
+                    disabled={!settings.model_loaded || !settings.microphone_available}
+                  >
+                    Push-to-talk session
+                  </button>
+                )}
+
+                {isRecording && inputLevel && (
+                  <div className="level-meter" title={`${inputLevel.peak_dbfs.toFixed(0)} dBFS peak`}>
+                    <div
+                      className="level-meter-fill"
+                      style={{ width: `${Math.max(0, Math.min(100, ((inputLevel.rms_dbfs + 60) / 60) * 100))}%` }}
+                    />
+                  </div>
+                )}
+
+                {!isRecording && processingStatus && (
+                  <p className="hint">{processingStatus.message}</p>
+                )}
+
                 {!settings.model_loaded && (
                   <p className="hint">Load the Whisper model in Settings to enable recording</p>
                 )}
+
+                {!settings.microphone_available && (
+                  <p className="hint">No microphone detected. Plug one in to enable recording.</p>
+                )}
               </div>
             ) : (
               /* Processing Status UI */
@@ -573,19 +1294,36 @@ function App() {
                 <div className="processing-status">
                   <div className="processing-icon">{getStatusIcon(processingStatus?.stage || "")}</div>
                   <div className="processing-message">{processingStatus?.message}</div>
+                  {processingStatus?.stage === "transcribing" && (
+                    <>
+                      {partialTranscript && <p className="partial-transcript">{partialTranscript}</p>}
+                      <button
+                        className="small-btn"
+                        onClick={() =>
+                          invoke("cancel_transcription", { recordingId: processingStatus.recording_id ?? null })
+                        }
+                      >
+                        Cancel
+                      </button>
+                    </>
+                  )}
                   <div className="processing-stages">
                     <span className={processingStatus?.stage === "saving" ? "active" : processingStatus?.stage && ["transcribing", "syncing", "done"].includes(processingStatus.stage) ? "complete" : ""}>Save</span>
                     <span className="arrow">→</span>
                     <span className={processingStatus?.stage === "transcribing" ? "active" : processingStatus?.stage && ["syncing", "done"].includes(processingStatus.stage) ? "complete" : ""}>Transcribe</span>
-                    <span className="arrow">→</span>
-                    <span className={processingStatus?.stage === "syncing" ? "active" : processingStatus?.stage === "done" ? "complete" : ""}>Sync</span>
+                    {!settings.local_only && (
+                      <>
+                        <span className="arrow">→</span>
+                        <span className={processingStatus?.stage === "syncing" ? "active" : processingStatus?.stage === "done" ? "complete" : ""}>Sync</span>
+                      </>
+                    )}
                   </div>
                 </div>
               </div>
             )}
 
             {/* Show last transcript */}
-            {lastTranscript && !isProcessing && !isRecording && (
+            {lastTranscript && (!isProcessing || pushToTalkSession) && !isRecording && (
               <div className="last-transcript">
                 <h3>Last Transcript:</h3>
                 <p>{lastTranscript}</p>
@@ -598,7 +1336,7 @@ function App() {
               <ol>
                 <li>Press <strong>Record</strong> and speak</li>
                 <li>Press <strong>Stop</strong> when finished</li>
-                <li>Audio is automatically transcribed and synced to server</li>
+                <li>Audio is automatically transcribed{settings.local_only ? " and saved on this device" : " and synced to server"}</li>
               </ol>
             </div>
           </div>
@@ -609,11 +1347,16 @@ function App() {
           <div className="history-tab">
             <div className="history-header">
               <h2>Recording History</h2>
-              {unsyncedCount > 0 && (
+              {!settings.local_only && unsyncedCount > 0 && (
                 <button className="sync-button" onClick={handleManualSync}>
                   Sync {unsyncedCount} Pending
                 </button>
               )}
+              {!settings.local_only && failedSyncCount > 0 && (
+                <button className="sync-button" onClick={handleRetryFailedSyncs}>
+                  Retry {failedSyncCount} Rejected
+                </button>
+              )}
             </div>
 
             {recordings.length === 0 ? (
@@ -625,12 +1368,54 @@ function App() {
                     <div className="recording-header">
                       <span className="recording-date">{formatDate(rec.recorded_at)}</span>
                       <span className="recording-duration">{formatDuration(rec.duration_seconds)}</span>
-                      <span className={`sync-status ${rec.synced ? "synced" : "unsynced"}`}>
-                        {rec.synced ? "Synced" : "Pending"}
-                      </span>
+                      {rec.guest ? (
+                        <span className="sync-status unsynced">Guest · deleted after 24h</span>
+                      ) : (
+                        <span className={`sync-status ${rec.synced ? "synced" : "unsynced"}`}>
+                          {settings.local_only ? "Local" : rec.synced ? "Synced" : "Pending"}
+                        </span>
+                      )}
+                      {rec.teacher_only && <span className="sync-status unsynced">Teacher only · not scored</span>}
                     </div>
 
-                    {rec.transcript ? (
+                    {rec.transcript && timedSegments[rec.id] ? (
+                      <ol className="timed-transcript">
+                        {timedSegments[rec.id].map((segment, i) => (
+                          <li key={i}>
+                            <span className="segment-time">{formatDuration(segment.start)}</span>
+                            {segment.speaker && (
+                              <span
+                                className="segment-speaker"
+                                title="Rename speaker"
+                                onClick={() => handleRenameSpeaker(rec, segment.speaker!)}
+                              >
+                                {rec.speaker_labels[segment.speaker] ?? segment.speaker}
+                              </span>
+                            )}
+                            {segment.words?.length ? (
+                              <span className="segment-text">
+                                {segment.words.map((word, w) => (
+                                  <span
+                                    key={w}
+                                    className="segment-word"
+                                    title={formatDuration(word.start)}
+                                    onClick={() =>
+                                      invoke("play_word", { recordingId: rec.id, segmentIndex: i, wordIndex: w }).catch(
+                                        (e) => showError(`Failed to play word: ${e}`)
+                                      )
+                                    }
+                                  >
+                                    {word.text}{" "}
+                                  </span>
+                                ))}
+                              </span>
+                            ) : (
+                              <span className="segment-text">{segment.text}</span>
+                            )}
+                          </li>
+                        ))}
+                      </ol>
+                    ) : rec.transcript ? (
                       <div className="transcript">
                         <p>{rec.transcript}</p>
                       </div>
@@ -640,7 +1425,38 @@ function App() {
                       </div>
                     )}
 
+                    {speakersHeard(timedSegments[rec.id]).length > 1 && (
+                      <div className="hint">
+                        Student:{" "}
+                        <select
+                          value={rec.student_speaker ?? ""}
+                          onChange={(e) => handlePickStudent(rec.id, e.target.value)}
+                        >
+                          <option value="">Everyone (score all speech)</option>
+                          {speakersHeard(timedSegments[rec.id]).map((speaker) => (
+                            <option key={speaker} value={speaker}>
+                              {rec.speaker_labels[speaker] ?? speaker}
+                            </option>
+                          ))}
+                        </select>
+                      </div>
+                    )}
+
+                    {rec.transcript_language && rec.detected_language && rec.transcript_language !== rec.detected_language && (
+                      <div className="alert alert-error">
+                        Sounds like "{rec.detected_language}" but was transcribed as "{rec.transcript_language}".
+                        <button className="small-btn" onClick={() => handleRetranscribeLanguage(rec.id)}>
+                          Transcribe in {rec.detected_language}
+                        </button>
+                      </div>
+                    )}
+
                     <div className="recording-actions">
+                      {rec.transcript && (
+                        <button className="small-btn" onClick={() => handleToggleTimings(rec.id)}>
+                          {timedSegments[rec.id] ? "Hide timings" : "Show timings"}
+                        </button>
+                      )}
                       <button className="delete-btn" onClick={() => handleDelete(rec.id)}>
                         Delete
                       </button>
@@ -677,19 +1493,162 @@ function App() {
               />
             </div>
 
+            {!settings.local_only && (
+              <div className="setting-group">
+                <label>Server URL</label>
+                <input
+                  type="text"
+                  value={serverUrl}
+                  onChange={(e) => setServerUrl(e.target.value)}
+                  placeholder="http://localhost:3000"
+                />
+                <button className="small-btn" onClick={checkServerConnection}>
+                  Test Connection
+                </button>
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={settings.sync_speaker_stats}
+                    onChange={(e) => handleToggleSyncSpeakerStats(e.target.checked)}
+                  />
+                  Share speaker statistics
+                </label>
+                <p className="hint">Sends how long each speaker talked, for talk-ratio charts on the server.</p>
+              </div>
+            )}
+
             <div className="setting-group">
-              <label>Server URL</label>
-              <input
-                type="text"
-                value={serverUrl}
-                onChange={(e) => setServerUrl(e.target.value)}
-                placeholder="http://localhost:3000"
-              />
-              <button className="small-btn" onClick={checkServerConnection}>
-                Test Connection
+              <label>Microphone</label>
+              <select
+                value={settings.audio_device ?? ""}
+                onChange={(e) => handleSelectAudioDevice(e.target.value)}
+              >
+                <option value="">System default</option>
+                {audioDevices.map((d) => (
+                  <option key={d.name} value={d.name}>
+                    {d.name}{d.is_default ? " (default)" : ""}
+                  </option>
+                ))}
+              </select>
+              <button className="small-btn" onClick={loadAudioDevices}>
+                Refresh
               </button>
             </div>
 
+            <div className="setting-group">
+              <label>Finding the student</label>
+              <select
+                value={settings.student_speaker_strategy ?? ""}
+                onChange={(e) => handleSelectStudentStrategy(e.target.value)}
+              >
+                <option value="">Score everyone heard</option>
+                <option value="most_talkative">Whoever talks most</option>
+                <option value="least_talkative">Whoever talks least</option>
+                <option value="voiceprint">Whoever doesn't sound like the teacher</option>
+                <option value="manual">Pick on each recording</option>
+              </select>
+              <p className="hint">Only the student's speech is scored once they're picked out.</p>
+            </div>
+
+            <div className="setting-group">
+              <label>Transcription language</label>
+              <select
+                value={settings.transcription_language}
+                onChange={(e) => handleSelectLanguage(e.target.value)}
+              >
+                {!TRANSCRIPTION_LANGUAGES.some(([code]) => code === settings.transcription_language) && (
+                  <option value={settings.transcription_language}>{settings.transcription_language}</option>
+                )}
+                {TRANSCRIPTION_LANGUAGES.map(([code, name]) => (
+                  <option key={code} value={code}>{name}</option>
+                ))}
+              </select>
+              {settings.transcription_language !== "en" &&
+                !models.some((m) => m.multilingual && m.downloaded) && (
+                  <p className="hint">Download a multilingual model below to transcribe in this language.</p>
+                )}
+            </div>
+
+            <div className="setting-group">
+              <label>
+                <input
+                  type="checkbox"
+                  checked={settings.live_captions}
+                  onChange={(e) => handleToggleLiveCaptions(e.target.checked)}
+                />
+                Live captions
+              </label>
+              <p className="hint">
+                Shows what's being said while recording. The full transcript replaces the captions once the
+                recording stops.
+              </p>
+            </div>
+
+            <div className="setting-group">
+              <label>
+                <input
+                  type="checkbox"
+                  checked={settings.word_timestamps}
+                  onChange={(e) => handleToggleWordTimestamps(e.target.checked)}
+                />
+                Time each word
+              </label>
+              <p className="hint">New transcripts can be played back a word at a time from their timings.</p>
+            </div>
+
+            {diarization?.supported && (
+              <div className="setting-group">
+                <label>Speaker detection</label>
+                <p className="hint">
+                  {diarization.active
+                    ? `On, using the Hugging Face token from ${diarization.token_source}.`
+                    : "Off. Enter a Hugging Face token to tell speakers apart in new transcripts."}
+                </p>
+                {diarization.token_error && <p className="hint">{diarization.token_error}</p>}
+                <input
+                  type="password"
+                  placeholder="hf_..."
+                  value={hfTokenInput}
+                  onChange={(e) => setHfTokenInput(e.target.value)}
+                />
+                <button
+                  className="small-btn"
+                  disabled={!hfTokenInput.trim()}
+                  onClick={() => handleSaveHfToken(hfTokenInput)}
+                >
+                  Save
+                </button>
+                {diarization.token_source === "settings" && (
+                  <button className="small-btn" onClick={() => handleSaveHfToken(null)}>
+                    Remove
+                  </button>
+                )}
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={diarization.speakers.min === 2 && diarization.speakers.max === 2}
+                    onChange={(e) => handleToggleTwoSpeakers(e.target.checked)}
+                  />
+                  Exactly two speakers
+                </label>
+                <p className="hint">For 1:1 sessions, so no third speaker is found.</p>
+              </div>
+            )}
+
+            <div className="setting-group">
+              <label>
+                <input
+                  type="checkbox"
+                  checked={settings.guest_mode}
+                  onChange={(e) => handleToggleGuestMode(e.target.checked)}
+                />
+                Guest mode
+              </label>
+              <p className="hint">
+                For demos and mic tests. Recordings stay on this device and are deleted after 24 hours.
+              </p>
+            </div>
+
             <button className="save-btn" onClick={handleSaveSettings}>
               Save Settings
             </button>
@@ -706,17 +1665,48 @@ function App() {
                 )}
               </p>
 
+              <ul className="model-list">
+                {models.map((model) => (
+                  <li key={model.name}>
+                    <span>
+                      {model.name} ({model.size_mb} MB{model.multilingual ? ", multilingual" : ""})
+                    </span>
+                    {downloading?.model === model.name ? (
+                      <span>
+                        {downloading.percent !== null
+                          ? `${Math.round(downloading.percent)}%`
+                          : `${Math.round(downloading.bytes_downloaded / 1048576)} MB`}
+                      </span>
+                    ) : model.selected && model.downloaded ? (
+                      <span className="loaded">In use</span>
+                    ) : (
+                      <span>
+                        {model.downloaded ? (
+                          <>
+                            <button className="small-btn" onClick={() => handleSelectModel(model.name)}>Use</button>
+                            <button className="small-btn" onClick={() => handleDeleteModel(model.name)}>Delete</button>
+                          </>
+                        ) : (
+                          <button
+                            className="small-btn"
+                            onClick={() => handleDownloadModel(model.name)}
+                            disabled={downloading !== null || settings.local_only}
+                          >
+                            Download
+                          </button>
+                        )}
+                      </span>
+                    )}
+                  </li>
+                ))}
+              </ul>
+
               {!settings.model_loaded && (
                 <>
                   <p className="model-instructions">
-                    Download the Whisper model file and place it at:
+                    Download a model above, or place a Whisper model file at:
                   </p>
                   <code className="model-path">{modelPath}</code>
-                  <p className="model-download">
-                    Download from: <a href="https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.en.bin" target="_blank" rel="noreferrer">
-                      ggml-base.en.bin (142 MB)
-                    </a>
-                  </p>
                   <button onClick={handleLoadModel}>Load Model</button>
                 </>
               )}
@@ -724,6 +1714,24 @@ function App() {
               {settings.model_loaded && (
                 <p className="model-ready">Model is ready for transcription!</p>
               )}
+
+              <button
+                className="small-btn"
+                onClick={handleTranscribeAllPending}
+                disabled={!settings.model_loaded || batch !== null}
+              >
+                {batch
+                  ? `Transcribing ${batch.transcribed + batch.failed + 1} of ${batch.total}...`
+                  : "Transcribe all pending"}
+              </button>
+              <label>
+                <input
+                  type="checkbox"
+                  checked={settings.transcribe_pending_on_start}
+                  onChange={(e) => handleToggleTranscribePendingOnStart(e.target.checked)}
+                />
+                Transcribe pending recordings on start
+              </label>
             </div>
           </div>
         )}